@@ -0,0 +1,132 @@
+// ============================================
+// engine/src/lib.rs
+// タイピングの判定と採点（画面・ファイル・時計に触れない部分）
+// アプリ本体と、wasm32 向けのウェブのデモの両方から使う
+// ============================================
+
+pub mod roman_mapping;
+pub mod scoring;
+pub mod typing;
+
+// `wasm` を有効にしたときだけ wasm-bindgen の書き出しを入れる
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use roman_mapping::create_roman_mapping;
+use typing::TypingSession;
+
+/// ウェブのデモに渡す1問分の進行（打鍵と結果は JSON で受け渡す）
+/// wasm-bindgen の書き出しはこれを包むだけなので、ネイティブのテストで同じ動きを確かめられる
+pub struct EngineSession {
+    session: TypingSession,
+}
+
+impl EngineSession {
+    pub fn new(hiragana: &str) -> Self {
+        Self { session: TypingSession::new(&create_roman_mapping(), hiragana) }
+    }
+
+    /// `timestamp_ms` に押した `key` を処理し、判定を JSON で返す
+    pub fn feed_key(&mut self, key: char, timestamp_ms: f64) -> String {
+        to_json(&self.session.feed_key(key, timestamp_ms))
+    }
+
+    /// ここまでの成績を JSON で返す
+    pub fn finish(&self) -> String {
+        to_json(&self.session.finish())
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("engine results always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scoring::{Keystrokes, ScoringPreset, score_question};
+    use serde_json::Value;
+    use typing::{KeyOutcome, parse_hiragana};
+
+    /// JSON を通さずに単位を直接打って成績を出す（アプリ本体との比較は src/main.rs のテストで行う）
+    fn drive_units(hiragana: &str, keys: &[(char, f64)]) -> (u32, u32, Keystrokes, f64) {
+        let mut units = parse_hiragana(&create_roman_mapping(), hiragana);
+        let mut current = 0;
+        let (mut misses, mut keystrokes) = (0, Keystrokes::default());
+        for &(c, _) in keys {
+            let Some(cs) = units.get_mut(current) else { break };
+            let counted = !cs.unsupported;
+            let outcome = cs.judge(c, current);
+            match outcome {
+                KeyOutcome::Hit(_) if cs.is_complete() => current += 1,
+                KeyOutcome::Miss(_) => misses += 1,
+                _ => {}
+            }
+            if counted {
+                keystrokes.total += 1;
+                keystrokes.correct += u32::from(matches!(outcome, KeyOutcome::Hit(_)));
+            }
+        }
+        let total_chars = units.iter().map(|cs| cs.typed_len()).sum::<usize>() as u32;
+        let duration_sec = (keys.last().unwrap().1 - keys[0].1) / 1000.0;
+        (total_chars, misses, keystrokes, duration_sec)
+    }
+
+    fn feed_all(hiragana: &str, keys: &[(char, f64)]) -> (Vec<Value>, Value) {
+        let mut session = EngineSession::new(hiragana);
+        let outcomes = keys
+            .iter()
+            .map(|&(c, at)| serde_json::from_str(&session.feed_key(c, at)).unwrap())
+            .collect();
+        (outcomes, serde_json::from_str(&session.finish()).unwrap())
+    }
+
+    fn timed(keys: &str) -> Vec<(char, f64)> {
+        keys.chars().enumerate().map(|(i, c)| (c, 1000.0 + i as f64 * 250.0)).collect()
+    }
+
+    #[test]
+    fn json_api_scores_like_driving_the_units_directly() {
+        // 綴りの切り替え（si → shi）、ミス、ん の打ち分けを含む
+        for (hiragana, keys) in [("すし", "susxhi"), ("かんたん", "kanntann"), ("ふぁいる", "fuxairu"), ("きゃく", "kyzaku")] {
+            let keys = timed(keys);
+            let (_, result) = feed_all(hiragana, &keys);
+            let (total_chars, misses, keystrokes, duration_sec) = drive_units(hiragana, &keys);
+            let expected = score_question(ScoringPreset::Current, total_chars, duration_sec, misses, keystrokes);
+            assert_eq!(result["complete"], true, "{}", hiragana);
+            assert_eq!(result["total_chars"], total_chars, "{}", hiragana);
+            assert_eq!(result["misses"], misses, "{}", hiragana);
+            assert_eq!(result["keystrokes"], keystrokes.total, "{}", hiragana);
+            assert_eq!(result["correct_keystrokes"], keystrokes.correct, "{}", hiragana);
+            assert_eq!(result["xp"], expected.xp, "{}", hiragana);
+            assert!((result["score"].as_f64().unwrap() - expected.score).abs() < 1e-9, "{}", hiragana);
+        }
+    }
+
+    #[test]
+    fn outcomes_report_switches_misses_and_the_remaining_romaji() {
+        let (outcomes, _) = feed_all("しか", &timed("shxika"));
+        let kinds: Vec<_> = outcomes.iter().map(|o| o["outcome"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["hit", "hit", "miss", "hit", "hit", "hit"]);
+        assert_eq!(outcomes[1]["switched"], true);
+        assert_eq!(outcomes[2]["expected"], "i");
+        assert_eq!(outcomes[2]["remaining"], "ika");
+        assert_eq!(outcomes[5]["typed"], "shika");
+        assert_eq!(outcomes[5]["complete"], true);
+    }
+
+    #[test]
+    fn keys_after_the_end_and_unfinished_questions_earn_nothing() {
+        let mut session = EngineSession::new("か");
+        session.feed_key('k', 0.0);
+        let partial: Value = serde_json::from_str(&session.finish()).unwrap();
+        assert_eq!(partial["complete"], false);
+        assert_eq!(partial["total_chars"], 1);
+        assert_eq!(partial["xp"], 0);
+        session.feed_key('a', 400.0);
+        let after: Value = serde_json::from_str(&session.feed_key('a', 900.0)).unwrap();
+        assert_eq!(after["outcome"], "finished");
+        let result: Value = serde_json::from_str(&session.finish()).unwrap();
+        assert_eq!(result["duration_sec"], 0.4);
+    }
+}
@@ -1,8 +1,9 @@
 // ============================================
-// src/roman_mapping.rs (新規ファイル)
+// engine/src/roman_mapping.rs
 // ============================================
 
 use std::collections::HashMap;
+use std::ops::Range;
 
 pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     let mut map: HashMap<&'static str, Vec<&'static str>> = HashMap::new();
@@ -87,6 +88,9 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("ゅ", vec!["lyu", "xyu"]);
     map.insert("ょ", vec!["lyo", "xyo"]);
     map.insert("っ", vec!["ltu", "ltsu", "xtu", "xtsu"]);
+    map.insert("ゎ", vec!["lwa", "xwa"]);
+    map.insert("ゕ", vec!["lka", "xka"]);
+    map.insert("ゖ", vec!["lke", "xke"]);
     map.insert("きゃ", vec!["kya", "kilya", "kixya"]);
     map.insert("きぃ", vec!["kyi", "kili", "kilyi", "kixi", "kixyi"]);
     map.insert("きゅ", vec!["kyu", "kilyu", "kixyu"]);
@@ -137,23 +141,23 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("つぇ", vec!["tse", "tule", "tuxe", "tulye", "tuxye", "tsule", "tsuxe", "tsulye", "tsuxye"]);
     map.insert("つぉ", vec!["tso", "tulo", "tuxo", "tsulo", "tsuxo"]);
     map.insert("てゃ", vec!["tha", "telya", "texya"]);
-    map.insert("てぃ", vec!["thi", "teli", "texi", "telyi", "texyi"]);
-    map.insert("てゅ", vec!["thu", "telyu", "texyu"]);
+    map.insert("てぃ", vec!["thi", "teli", "texi", "telyi", "texyi", "t'i"]);
+    map.insert("てゅ", vec!["thu", "telyu", "texyu", "t'yu"]);
     map.insert("てぇ", vec!["the", "tele", "texe", "telye", "texye"]);
     map.insert("てょ", vec!["tho", "telyo", "texyo"]);
     map.insert("でゃ", vec!["dha", "delya", "dexya"]);
-    map.insert("でぃ", vec!["dhi", "deli", "dexi", "delyi", "dexyi"]);
-    map.insert("でゅ", vec!["dhu", "delyu", "dexyu"]);
+    map.insert("でぃ", vec!["dhi", "deli", "dexi", "delyi", "dexyi", "d'i"]);
+    map.insert("でゅ", vec!["dhu", "delyu", "dexyu", "d'yu"]);
     map.insert("でぇ", vec!["dhe", "dele", "dexe", "delye", "dexye"]);
     map.insert("でょ", vec!["dho", "delyo", "dexyo"]);
     map.insert("とぁ", vec!["twa", "tola", "toxa"]);
     map.insert("とぃ", vec!["twi", "toli", "toxi", "tolyi", "toxyi"]);
-    map.insert("とぅ", vec!["twu", "tolu", "toxu"]);
+    map.insert("とぅ", vec!["twu", "tolu", "toxu", "t'u"]);
     map.insert("とぇ", vec!["twe", "tole", "toxe", "tolye", "toxye"]);
     map.insert("とぉ", vec!["two", "tolo", "toxo"]);
     map.insert("どぁ", vec!["dwa", "dola", "doxa"]);
     map.insert("どぃ", vec!["dwi", "doli", "doxi", "dolyi", "doxyi"]);
-    map.insert("どぅ", vec!["dwu", "dolu", "doxu"]);
+    map.insert("どぅ", vec!["dwu", "dolu", "doxu", "d'u"]);
     map.insert("どぇ", vec!["dwe", "dole", "doxe", "dolye", "doxye"]);
     map.insert("どぉ", vec!["dwo", "dolo", "doxo"]);
     map.insert("にゃ", vec!["nya", "nilya", "nixya"]);
@@ -177,12 +181,13 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("びぇ", vec!["bye", "bile", "bixe", "bilye", "bixye"]);
     map.insert("びょ", vec!["byo", "bilyo", "bixyo"]);
     map.insert("ふゃ", vec!["fya", "fulya", "fuxya", "hulya", "huxya"]);
-    map.insert("ふぃ", vec!["fi", "fyi", "fuli", "fuxi", "fulyi", "fuxyi", "huli", "huxi", "hulyi", "huxyi"]);
+    map.insert("ふぃ", vec!["fi", "fyi", "fuli", "fuxi", "fulyi", "fuxyi", "huli", "huxi", "hulyi", "huxyi", "fwi"]);
+    map.insert("ふぅ", vec!["fwu", "fulu", "fuxu", "hulu", "huxu"]);
     map.insert("ふゅ", vec!["fyu", "fulyu", "fuxyu", "hulyu", "huxyu"]);
-    map.insert("ふぇ", vec!["fe", "fye", "fule", "fuxe", "fulye", "fuxye", "hule", "huxe", "hulye", "huxye"]);
+    map.insert("ふぇ", vec!["fe", "fye", "fule", "fuxe", "fulye", "fuxye", "hule", "huxe", "hulye", "huxye", "fwe"]);
     map.insert("ふょ", vec!["fyo", "fulyo", "fuxyo", "hulyo", "huxyo"]);
-    map.insert("ふぁ", vec!["fa", "fula", "fuxa", "hula", "huxa"]);
-    map.insert("ふぉ", vec!["fo", "fulo", "fuxo", "hulo", "huxo"]);
+    map.insert("ふぁ", vec!["fa", "fula", "fuxa", "hula", "huxa", "fwa", "hwa"]);
+    map.insert("ふぉ", vec!["fo", "fulo", "fuxo", "hulo", "huxo", "fwo"]);
     map.insert("みゃ", vec!["mya", "milya", "mixya"]);
     map.insert("みぃ", vec!["myi", "mili", "mixi", "milyi", "mixyi"]);
     map.insert("みゅ", vec!["myu", "milyu", "mixyu"]);
@@ -197,6 +202,7 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("うぃ", vec!["wi", "whi", "uli", "uxi", "ulyi", "uxyi", "wuli", "wuxi", "wulyi", "wuxyi", "whuli"]);
     map.insert("うぇ", vec!["we", "whe", "ule", "uxe", "ulye", "uxye", "wule", "wuxe", "wulye", "wuxye", "whule", "whuxe", "whulye", "whuxye"]);
     map.insert("うぉ", vec!["who", "ulo", "uxo", "wulo", "wuxo", "whulo", "whuxo"]);
+    map.insert("いぇ", vec!["ye", "ile", "ixe", "ilye", "ixye"]);
     map.insert("ゔぁ", vec!["va", "vula", "vuxa"]);
     map.insert("ゔぃ", vec!["vi", "vyi", "vuli", "vuxi", "vulyi", "vuxyi"]);
     map.insert("ゔ", vec!["vu"]);
@@ -307,23 +313,23 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("んつぇ", vec!["ntse", "ntule", "ntuxe", "ntulye", "ntuxye", "ntsule", "ntsuxe", "ntsulye", "ntsuxye", "nntse", "nntule", "nntuxe", "nntulye", "nntuxye", "nntsule", "nntsuxe", "nntsulye", "nntsuxye", "xntse", "xntule", "xntuxe", "xntulye", "xntuxye", "xntsule", "xntsuxe", "xntsulye", "xntsuxye"]);
     map.insert("んつぉ", vec!["ntso", "ntulo", "ntuxo", "ntsulo", "ntsuxo", "nntso", "nntulo", "nntuxo", "nntsulo", "nntsuxo", "xntso", "xntulo", "xntuxo", "xntsulo", "xntsuxo"]);
     map.insert("んてゃ", vec!["ntha", "ntelya", "ntexya", "nntha", "nntelya", "nntexya", "xntha", "xntelya", "xntexya"]);
-    map.insert("んてぃ", vec!["nthi", "nteli", "ntexi", "ntelyi", "ntexyi", "nnthi", "nnteli", "nntexi", "nntelyi", "nntexyi", "xnthi", "xnteli", "xntexi", "xntelyi", "xntexyi"]);
-    map.insert("んてゅ", vec!["nthu", "ntelyu", "ntexyu", "nnthu", "nntelyu", "nntexyu", "xnthu", "xntelyu", "xntexyu"]);
+    map.insert("んてぃ", vec!["nthi", "nteli", "ntexi", "ntelyi", "ntexyi", "nnthi", "nnteli", "nntexi", "nntelyi", "nntexyi", "xnthi", "xnteli", "xntexi", "xntelyi", "xntexyi", "nt'i", "nnt'i", "xnt'i"]);
+    map.insert("んてゅ", vec!["nthu", "ntelyu", "ntexyu", "nnthu", "nntelyu", "nntexyu", "xnthu", "xntelyu", "xntexyu", "nt'yu", "nnt'yu", "xnt'yu"]);
     map.insert("んてぇ", vec!["nthe", "ntele", "ntexe", "ntelye", "ntexye", "nnthe", "nntele", "nntexe", "nntelye", "nntexye", "xnthe", "xntele", "xntexe", "xntelye", "xntexye"]);
     map.insert("んてょ", vec!["ntho", "ntelyo", "ntexyo", "nntho", "nntelyo", "nntexyo", "xntho", "xntelyo", "xntexyo"]);
     map.insert("んでゃ", vec!["ndha", "ndelya", "ndexya", "nndha", "nndelya", "nndexya", "xndha", "xndelya", "xndexya"]);
-    map.insert("んでぃ", vec!["ndhi", "ndeli", "ndexi", "ndelyi", "ndexyi", "nndhi", "nndeli", "nndexi", "nndelyi", "nndexyi", "xndhi", "xndeli", "xndexi", "xndelyi", "xndexyi"]);
-    map.insert("んでゅ", vec!["ndhu", "ndelyu", "ndexyu", "nndhu", "nndelyu", "nndexyu", "xndhu", "xndelyu", "xndexyu"]);
+    map.insert("んでぃ", vec!["ndhi", "ndeli", "ndexi", "ndelyi", "ndexyi", "nndhi", "nndeli", "nndexi", "nndelyi", "nndexyi", "xndhi", "xndeli", "xndexi", "xndelyi", "xndexyi", "nd'i", "nnd'i", "xnd'i"]);
+    map.insert("んでゅ", vec!["ndhu", "ndelyu", "ndexyu", "nndhu", "nndelyu", "nndexyu", "xndhu", "xndelyu", "xndexyu", "nd'yu", "nnd'yu", "xnd'yu"]);
     map.insert("んでぇ", vec!["ndhe", "ndele", "ndexe", "ndelye", "ndexye", "nndhe", "nndele", "nndexe", "nndelye", "nndexye", "xndhe", "xndele", "xndexe", "xndelye", "xndexye"]);
     map.insert("んでょ", vec!["ndho", "ndelyo", "ndexyo", "nndho", "nndelyo", "nndexyo", "xndho", "xndelyo", "xndexyo"]);
     map.insert("んとぁ", vec!["ntwa", "ntola", "ntoxa", "nntwa", "nntola", "nntoxa", "xntwa", "xntola", "xntoxa"]);
     map.insert("んとぃ", vec!["ntwi", "ntoli", "ntoxi", "ntolyi", "ntoxyi", "nntwi", "nntoli", "nntoxi", "nntolyi", "nntoxyi", "xntwi", "xntoli", "xntoxi", "xntolyi", "xntoxyi"]);
-    map.insert("んとぅ", vec!["ntwu", "ntolu", "ntoxu", "nntwu", "nntolu", "nntoxu", "xntwu", "xntolu", "xntoxu"]);
+    map.insert("んとぅ", vec!["ntwu", "ntolu", "ntoxu", "nntwu", "nntolu", "nntoxu", "xntwu", "xntolu", "xntoxu", "nt'u", "nnt'u", "xnt'u"]);
     map.insert("んとぇ", vec!["ntwe", "ntole", "ntoxe", "ntolye", "ntoxye", "nntwe", "nntole", "nntoxe", "nntolye", "nntoxye", "xntwe", "xntole", "xntoxe", "xntolye", "xntoxye"]);
     map.insert("んとぉ", vec!["ntwo", "ntolo", "ntoxo", "nntwo", "nntolo", "nntoxo", "xntwo", "xntolo", "xntoxo"]);
     map.insert("んどぁ", vec!["ndwa", "ndola", "ndoxa", "nndwa", "nndola", "nndoxa", "xndwa", "xndola", "xndoxa"]);
     map.insert("んどぃ", vec!["ndwi", "ndoli", "ndoxi", "ndolyi", "ndoxyi", "nndwi", "nndoli", "nndoxi", "nndolyi", "nndoxyi", "xndwi", "xndoli", "xndoxi", "xndolyi", "xndoxyi"]);
-    map.insert("んどぅ", vec!["ndwu", "ndolu", "ndoxu", "nndwu", "nndolu", "nndoxu", "xndwu", "xndolu", "xndoxu"]);
+    map.insert("んどぅ", vec!["ndwu", "ndolu", "ndoxu", "nndwu", "nndolu", "nndoxu", "xndwu", "xndolu", "xndoxu", "nd'u", "nnd'u", "xnd'u"]);
     map.insert("んどぇ", vec!["ndwe", "ndole", "ndoxe", "ndolye", "ndoxye", "nndwe", "nndole", "nndoxe", "nndolye", "nndoxye", "xndwe", "xndole", "xndoxe", "xndolye", "xndoxye"]);
     map.insert("んどぉ", vec!["ndwo", "ndolo", "ndoxo", "nndwo", "nndolo", "nndoxo", "xndwo", "xndolo", "xndoxo"]);
     map.insert("んぴゃ", vec!["npya", "npilya", "npixya", "nnpya", "nnpilya", "nnpixya", "xnpya", "xnpilya", "xnpixya"]);
@@ -342,12 +348,13 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("んびぇ", vec!["nbye", "nbile", "nbixe", "nbilye", "nbixye", "nnbye", "nnbile", "nnbixe", "nnbilye", "nnbixye", "xnbye", "xnbile", "xnbixe", "xnbilye", "xnbixye"]);
     map.insert("んびょ", vec!["nbyo", "nbilyo", "nbixyo", "nnbyo", "nnbilyo", "nnbixyo", "xnbyo", "xnbilyo", "xnbixyo"]);
     map.insert("んふゃ", vec!["nfya", "nfulya", "nfuxya", "nhulya", "nhuxya", "nnfya", "nnfulya", "nnfuxya", "nnhulya", "nnhuxya", "xnfya", "xnfulya", "xnfuxya", "xnhulya", "xnhuxya"]);
-    map.insert("んふぃ", vec!["nfi", "nfyi", "nfuli", "nfuxi", "nfulyi", "nfuxyi", "nhuli", "nhuxi", "nhulyi", "nhuxyi", "nnfi", "nnfyi", "nnfuli", "nnfuxi", "nnfulyi", "nnfuxyi", "nnhuli", "nnhuxi", "nnhulyi", "nnhuxyi", "xnfi", "xnfyi", "xnfuli", "xnfuxi", "xnfulyi", "xnfuxyi", "xnhuli", "xnhuxi", "xnhulyi", "xnhuxyi"]);
+    map.insert("んふぃ", vec!["nfi", "nfyi", "nfuli", "nfuxi", "nfulyi", "nfuxyi", "nhuli", "nhuxi", "nhulyi", "nhuxyi", "nnfi", "nnfyi", "nnfuli", "nnfuxi", "nnfulyi", "nnfuxyi", "nnhuli", "nnhuxi", "nnhulyi", "nnhuxyi", "xnfi", "xnfyi", "xnfuli", "xnfuxi", "xnfulyi", "xnfuxyi", "xnhuli", "xnhuxi", "xnhulyi", "xnhuxyi", "nfwi", "nnfwi", "xnfwi"]);
+    map.insert("んふぅ", vec!["nfwu", "nfulu", "nfuxu", "nhulu", "nhuxu", "nnfwu", "nnfulu", "nnfuxu", "nnhulu", "nnhuxu", "xnfwu", "xnfulu", "xnfuxu", "xnhulu", "xnhuxu"]);
     map.insert("んふゅ", vec!["nfyu", "nfulyu", "nfuxyu", "nhulyu", "nhuxyu", "nnfyu", "nnfulyu", "nnfuxyu", "nnhulyu", "nnhuxyu", "xnfyu", "xnfulyu", "xnfuxyu", "xnhulyu", "xnhuxyu"]);
-    map.insert("んふぇ", vec!["nfe", "nfye", "nfule", "nfuxe", "nfulye", "nfuxye", "nhule", "nhuxe", "nhulye", "nhuxye", "nnfe", "nnfye", "nnfule", "nnfuxe", "nnfulye", "nnfuxye", "nnhule", "nnhuxe", "nnhulye", "nnhuxye", "xnfe", "xnfye", "xnfule", "xnfuxe", "xnfulye", "xnfuxye", "xnhule", "xnhuxe", "xnhulye", "xnhuxye"]);
+    map.insert("んふぇ", vec!["nfe", "nfye", "nfule", "nfuxe", "nfulye", "nfuxye", "nhule", "nhuxe", "nhulye", "nhuxye", "nnfe", "nnfye", "nnfule", "nnfuxe", "nnfulye", "nnfuxye", "nnhule", "nnhuxe", "nnhulye", "nnhuxye", "xnfe", "xnfye", "xnfule", "xnfuxe", "xnfulye", "xnfuxye", "xnhule", "xnhuxe", "xnhulye", "xnhuxye", "nfwe", "nnfwe", "xnfwe"]);
     map.insert("んふょ", vec!["nfyo", "nfulyo", "nfuxyo", "nhulyo", "nhuxyo", "nnfyo", "nnfulyo", "nnfuxyo", "nnhulyo", "nnhuxyo", "xnfyo", "xnfulyo", "xnfuxyo", "xnhulyo", "xnhuxyo"]);
-    map.insert("んふぁ", vec!["nfa", "nfula", "nfuxa", "nhula", "nhuxa", "nnfa", "nnfula", "nnfuxa", "nnhula", "nnhuxa", "xnfa", "xnfula", "xnfuxa", "xnhula", "xnhuxa"]);
-    map.insert("んふぉ", vec!["nfo", "nfulo", "nfuxo", "nhulo", "nhuxo", "nnfo", "nnfulo", "nnfuxo", "nnhulo", "nnhuxo", "xnfo", "xnfulo", "xnfuxo", "xnhulo", "xnhuxo"]);
+    map.insert("んふぁ", vec!["nfa", "nfula", "nfuxa", "nhula", "nhuxa", "nnfa", "nnfula", "nnfuxa", "nnhula", "nnhuxa", "xnfa", "xnfula", "xnfuxa", "xnhula", "xnhuxa", "nfwa", "nhwa", "nnfwa", "nnhwa", "xnfwa", "xnhwa"]);
+    map.insert("んふぉ", vec!["nfo", "nfulo", "nfuxo", "nhulo", "nhuxo", "nnfo", "nnfulo", "nnfuxo", "nnhulo", "nnhuxo", "xnfo", "xnfulo", "xnfuxo", "xnhulo", "xnhuxo", "nfwo", "nnfwo", "xnfwo"]);
     map.insert("んみゃ", vec!["nmya", "nmilya", "nmixya", "nnmya", "nnmilya", "nnmixya", "xnmya", "xnmilya", "xnmixya"]);
     map.insert("んみぃ", vec!["nmyi", "nmili", "nmixi", "nmilyi", "nmixyi", "nnmyi", "nnmili", "nnmixi", "nnmilyi", "nnmixyi", "xnmyi", "xnmili", "xnmixi", "xnmilyi", "xnmixyi"]);
     map.insert("んみゅ", vec!["nmyu", "nmilyu", "nmixyu", "nnmyu", "nnmilyu", "nnmixyu", "xnmyu", "xnmilyu", "xnmixyu"]);
@@ -479,13 +486,13 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("っつぇ", vec!["ttse", "ttule", "ttuxe", "ttulye", "ttuxye", "ttsule", "ttsuxe", "ttsulye", "ttsuxye", "ltutse", "xtutse", "ltsutse", "xtsutse", "ltutule", "xtutule", "ltsutule", "xtsutule", "ltutuxe", "xtutuxe", "ltsutuxe", "xtsutuxe", "ltutulye", "xtutulye", "ltsutulye", "xtsutulye", "ltutuxye", "xtutuxye", "ltsutuxye", "xtsutuxye", "ltutsule", "xtutsule", "ltsutsule", "xtsutsule", "ltutsuxe", "xtutsuxe", "ltsutsuxe", "xtsutsuxe", "ltutsulye", "xtutsulye", "ltsutsulye", "xtsutsulye", "ltutsuxye", "xtutsuxye", "ltsutsuxye", "xtsutsuxye"]);
     map.insert("っつぉ", vec!["ttso", "ttulo", "ttuxo", "ttsulo", "ttsuxo", "ltutso", "xtutso", "ltsutso", "xtsutso", "ltutulo", "xtutulo", "ltsutulo", "xtsutulo", "ltutuxo", "xtutuxo", "ltsutuxo", "xtsutuxo", "ltutsulo", "xtutsulo", "ltsutsulo", "xtsutsulo", "ltutsuxo", "xtutsuxo", "ltsutsuxo", "xtsutsuxo"]);
     map.insert("ってゃ", vec!["ttha", "ttelya", "ttexya", "ltutha", "xtutha", "ltsutha", "xtsutha", "ltutelya", "xtutelya", "ltsutelya", "xtsutelya", "ltutexya", "xtutexya", "ltsutexya", "xtsutexya"]);
-    map.insert("ってぃ", vec!["tthi", "tteli", "ttexi", "ttelyi", "ttexyi", "ltuthi", "xtuthi", "ltsuthi", "xtsuthi", "ltuteli", "xtuteli", "ltsuteli", "xtsuteli", "ltutexi", "xtutexi", "ltsutexi", "xtsutexi", "ltutelyi", "xtutelyi", "ltsutelyi", "xtsutelyi", "ltutexyi", "xtutexyi", "ltsutexyi", "xtsutexyi"]);
-    map.insert("ってゅ", vec!["tthu", "ttelyu", "ttexyu", "ltuthu", "xtuthu", "ltsuthu", "xtsuthu", "ltutelyu", "xtutelyu", "ltsutelyu", "xtsutelyu", "ltutexyu", "xtutexyu", "ltsutexyu", "xtsutexyu"]);
+    map.insert("ってぃ", vec!["tthi", "tteli", "ttexi", "ttelyi", "ttexyi", "ltuthi", "xtuthi", "ltsuthi", "xtsuthi", "ltuteli", "xtuteli", "ltsuteli", "xtsuteli", "ltutexi", "xtutexi", "ltsutexi", "xtsutexi", "ltutelyi", "xtutelyi", "ltsutelyi", "xtsutelyi", "ltutexyi", "xtutexyi", "ltsutexyi", "xtsutexyi", "tt'i", "ltut'i", "xtut'i", "ltsut'i", "xtsut'i"]);
+    map.insert("ってゅ", vec!["tthu", "ttelyu", "ttexyu", "ltuthu", "xtuthu", "ltsuthu", "xtsuthu", "ltutelyu", "xtutelyu", "ltsutelyu", "xtsutelyu", "ltutexyu", "xtutexyu", "ltsutexyu", "xtsutexyu", "tt'yu", "ltut'yu", "xtut'yu", "ltsut'yu", "xtsut'yu"]);
     map.insert("ってぇ", vec!["tthe", "ttele", "ttexe", "ttelye", "ttexye", "ltuthe", "xtuthe", "ltsuthe", "xtsuthe", "ltutele", "xtutele", "ltsutele", "xtsutele", "ltutexe", "xtutexe", "ltsutexe", "xtsutexe", "ltutelye", "xtutelye", "ltsutelye", "xtsutelye", "ltutexye", "xtutexye", "ltsutexye", "xtsutexye"]);
     map.insert("ってょ", vec!["ttho", "ttelyo", "ttexyo", "ltutho", "xtutho", "ltsutho", "xtsutho", "ltutelyo", "xtutelyo", "ltsutelyo", "xtsutelyo", "ltutexyo", "xtutexyo", "ltsutexyo", "xtsutexyo"]);
     map.insert("っでゃ", vec!["ddha", "ddelya", "ddexya", "ltudha", "xtudha", "ltsudha", "xtsudha", "ltudelya", "xtudelya", "ltsudelya", "xtsudelya", "ltudexya", "xtudexya", "ltsudexya", "xtsudexya"]);
-    map.insert("っでぃ", vec!["ddhi", "ddeli", "ddexi", "ddelyi", "ddexyi", "ltudhi", "xtudhi", "ltsudhi", "xtsudhi", "ltudeli", "xtudeli", "ltsudeli", "xtsudeli", "ltudexi", "xtudexi", "ltsudexi", "xtsudexi", "ltudelyi", "xtudelyi", "ltsudelyi", "xtsudelyi", "ltudexyi", "xtudexyi", "ltsudexyi", "xtsudexyi"]);
-    map.insert("っでゅ", vec!["ddhu", "ddelyu", "ddexyu", "ltudhu", "xtudhu", "ltsudhu", "xtsudhu", "ltudelyu", "xtudelyu", "ltsudelyu", "xtsudelyu", "ltudexyu", "xtudexyu", "ltsudexyu", "xtsudexyu"]);
+    map.insert("っでぃ", vec!["ddhi", "ddeli", "ddexi", "ddelyi", "ddexyi", "ltudhi", "xtudhi", "ltsudhi", "xtsudhi", "ltudeli", "xtudeli", "ltsudeli", "xtsudeli", "ltudexi", "xtudexi", "ltsudexi", "xtsudexi", "ltudelyi", "xtudelyi", "ltsudelyi", "xtsudelyi", "ltudexyi", "xtudexyi", "ltsudexyi", "xtsudexyi", "dd'i", "ltud'i", "xtud'i", "ltsud'i", "xtsud'i"]);
+    map.insert("っでゅ", vec!["ddhu", "ddelyu", "ddexyu", "ltudhu", "xtudhu", "ltsudhu", "xtsudhu", "ltudelyu", "xtudelyu", "ltsudelyu", "xtsudelyu", "ltudexyu", "xtudexyu", "ltsudexyu", "xtsudexyu", "dd'yu", "ltud'yu", "xtud'yu", "ltsud'yu", "xtsud'yu"]);
     map.insert("っでぇ", vec!["ddhe", "ddele", "ddexe", "ddelye", "ddexye", "ltudhe", "xtudhe", "ltsudhe", "xtsudhe", "ltudele", "xtudele", "ltsudele", "xtsudele", "ltudexe", "xtudexe", "ltsudexe", "xtsudexe", "ltudelye", "xtudelye", "ltsudelye", "xtsudelye", "ltudexye", "xtudexye", "ltsudexye", "xtsudexye"]);
     map.insert("っでょ", vec!["ddho", "ddelyo", "ddexyo", "ltudho", "xtudho", "ltsudho", "xtsudho", "ltudelyo", "xtudelyo", "ltsudelyo", "xtsudelyo", "ltudexyo", "xtudexyo", "ltsudexyo", "xtsudexyo"]);
     map.insert("っぴゃ", vec!["ppya", "ppilya", "ppixya", "ltupya", "xtupya", "ltsupya", "xtsupya", "ltupilya", "xtupilya", "ltsupilya", "xtsupilya", "ltupixya", "xtupixya", "ltsupixya", "xtsupixya"]);
@@ -503,10 +510,11 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("っびゅ", vec!["bbyu", "bbilyu", "bbixyu", "ltubyu", "xtubyu", "ltsubyu", "xtsubyu", "ltubilyu", "xtubilyu", "ltsubilyu", "xtsubilyu", "ltubixyu", "xtubixyu", "ltsubixyu", "xtsubixyu"]);
     map.insert("っびぇ", vec!["bbye", "bbile", "bbixe", "bbilye", "bbixye", "ltubye", "xtubye", "ltsubye", "xtsubye", "ltubile", "xtubile", "ltsubile", "xtsubile", "ltubixe", "xtubixe", "ltsubixe", "xtsubixe", "ltubilye", "xtubilye", "ltsubilye", "xtsubilye", "ltubixye", "xtubixye", "ltsubixye", "xtsubixye"]);
     map.insert("っびょ", vec!["bbyo", "bbilyo", "bbixyo", "ltubyo", "xtubyo", "ltsubyo", "xtsubyo", "ltubilyo", "xtubilyo", "ltsubilyo", "xtsubilyo", "ltubixyo", "xtubixyo", "ltsubixyo", "xtsubixyo"]);
-    map.insert("っふぁ", vec!["ffa", "ffula", "ffuxa", "hhula", "hhuxa", "ltufa", "xtufa", "ltsufa", "xtsufa", "ltufula", "xtufula", "ltsufula", "xtsufula", "ltufuxa", "xtufuxa", "ltsufuxa", "xtsufuxa", "ltuhula", "xtuhula", "ltsuhula", "xtsuhula", "ltuhuxa", "xtuhuxa", "ltsuhuxa", "xtsuhuxa"]);
-    map.insert("っふぃ", vec!["ffi", "ffyi", "ffuli", "ffuxi", "ffulyi", "ffuxyi", "hhuli", "hhuxi", "hhulyi", "hhuxyi", "ltufi", "xtufi", "ltsufi", "xtsufi", "ltufyi", "xtufyi", "ltsufyi", "xtsufyi", "ltufuli", "xtufuli", "ltsufuli", "xtsufuli", "ltufuxi", "xtufuxi", "ltsufuxi", "xtsufuxi", "ltufulyi", "xtufulyi", "ltsufulyi", "xtsufulyi", "ltufuxyi", "xtufuxyi", "ltsufuxyi", "xtsufuxyi", "ltuhuli", "xtuhuli", "ltsuhuli", "xtsuhuli", "ltuhuxi", "xtuhuxi", "ltsuhuxi", "xtsuhuxi", "ltuhulyi", "xtuhulyi", "ltsuhulyi", "xtsuhulyi", "ltuhuxyi", "xtuhuxyi", "ltsuhuxyi", "xtsuhuxyi"]);
-    map.insert("っふぇ", vec!["ffe", "ffye", "ffule", "ffuxe", "ffulye", "ffuxye", "hhule", "hhuxe", "hhulye", "hhuxye", "ltufe", "xtufe", "ltsufe", "xtsufe", "ltufye", "xtufye", "ltsufye", "xtsufye", "ltufule", "xtufule", "ltsufule", "xtsufule", "ltufuxe", "xtufuxe", "ltsufuxe", "xtsufuxe", "ltufulye", "xtufulye", "ltsufulye", "xtsufulye", "ltufuxye", "xtufuxye", "ltsufuxye", "xtsufuxye", "ltuhule", "xtuhule", "ltsuhule", "xtsuhule", "ltuhuxe", "xtuhuxe", "ltsuhuxe", "xtsuhuxe", "ltuhulye", "xtuhulye", "ltsuhulye", "xtsuhulye", "ltuhuxye", "xtuhuxye", "ltsuhuxye", "xtsuhuxye"]);
-    map.insert("っふぉ", vec!["ffo", "ffulo", "ffuxo", "hhulo", "hhuxo", "ltufo", "xtufo", "ltsufo", "xtsufo", "ltufulo", "xtufulo", "ltsufulo", "xtsufulo", "ltufuxo", "xtufuxo", "ltsufuxo", "xtsufuxo", "ltuhulo", "xtuhulo", "ltsuhulo", "xtsuhulo", "ltuhuxo", "xtuhuxo", "ltsuhuxo", "xtsuhuxo"]);
+    map.insert("っふぁ", vec!["ffa", "ffula", "ffuxa", "hhula", "hhuxa", "ltufa", "xtufa", "ltsufa", "xtsufa", "ltufula", "xtufula", "ltsufula", "xtsufula", "ltufuxa", "xtufuxa", "ltsufuxa", "xtsufuxa", "ltuhula", "xtuhula", "ltsuhula", "xtsuhula", "ltuhuxa", "xtuhuxa", "ltsuhuxa", "xtsuhuxa", "ffwa", "hhwa", "ltufwa", "xtufwa", "ltsufwa", "xtsufwa", "ltuhwa", "xtuhwa", "ltsuhwa", "xtsuhwa"]);
+    map.insert("っふぃ", vec!["ffi", "ffyi", "ffuli", "ffuxi", "ffulyi", "ffuxyi", "hhuli", "hhuxi", "hhulyi", "hhuxyi", "ltufi", "xtufi", "ltsufi", "xtsufi", "ltufyi", "xtufyi", "ltsufyi", "xtsufyi", "ltufuli", "xtufuli", "ltsufuli", "xtsufuli", "ltufuxi", "xtufuxi", "ltsufuxi", "xtsufuxi", "ltufulyi", "xtufulyi", "ltsufulyi", "xtsufulyi", "ltufuxyi", "xtufuxyi", "ltsufuxyi", "xtsufuxyi", "ltuhuli", "xtuhuli", "ltsuhuli", "xtsuhuli", "ltuhuxi", "xtuhuxi", "ltsuhuxi", "xtsuhuxi", "ltuhulyi", "xtuhulyi", "ltsuhulyi", "xtsuhulyi", "ltuhuxyi", "xtuhuxyi", "ltsuhuxyi", "xtsuhuxyi", "ffwi", "ltufwi", "xtufwi", "ltsufwi", "xtsufwi"]);
+    map.insert("っふぅ", vec!["ffwu", "ffulu", "ffuxu", "hhulu", "hhuxu", "ltufwu", "xtufwu", "ltsufwu", "xtsufwu", "ltufulu", "xtufulu", "ltsufulu", "xtsufulu", "ltufuxu", "xtufuxu", "ltsufuxu", "xtsufuxu", "ltuhulu", "xtuhulu", "ltsuhulu", "xtsuhulu", "ltuhuxu", "xtuhuxu", "ltsuhuxu", "xtsuhuxu"]);
+    map.insert("っふぇ", vec!["ffe", "ffye", "ffule", "ffuxe", "ffulye", "ffuxye", "hhule", "hhuxe", "hhulye", "hhuxye", "ltufe", "xtufe", "ltsufe", "xtsufe", "ltufye", "xtufye", "ltsufye", "xtsufye", "ltufule", "xtufule", "ltsufule", "xtsufule", "ltufuxe", "xtufuxe", "ltsufuxe", "xtsufuxe", "ltufulye", "xtufulye", "ltsufulye", "xtsufulye", "ltufuxye", "xtufuxye", "ltsufuxye", "xtsufuxye", "ltuhule", "xtuhule", "ltsuhule", "xtsuhule", "ltuhuxe", "xtuhuxe", "ltsuhuxe", "xtsuhuxe", "ltuhulye", "xtuhulye", "ltsuhulye", "xtsuhulye", "ltuhuxye", "xtuhuxye", "ltsuhuxye", "xtsuhuxye", "ffwe", "ltufwe", "xtufwe", "ltsufwe", "xtsufwe"]);
+    map.insert("っふぉ", vec!["ffo", "ffulo", "ffuxo", "hhulo", "hhuxo", "ltufo", "xtufo", "ltsufo", "xtsufo", "ltufulo", "xtufulo", "ltsufulo", "xtsufulo", "ltufuxo", "xtufuxo", "ltsufuxo", "xtsufuxo", "ltuhulo", "xtuhulo", "ltsuhulo", "xtsuhulo", "ltuhuxo", "xtuhuxo", "ltsuhuxo", "xtsuhuxo", "ffwo", "ltufwo", "xtufwo", "ltsufwo", "xtsufwo"]);
     map.insert("っみゃ", vec!["mmya", "mmilya", "mmixya", "ltumya", "xtumya", "ltsumya", "xtsumya", "ltumilya", "xtumilya", "ltsumilya", "xtsumilya", "ltumixya", "xtumixya", "ltsumixya", "xtsumixya"]);
     map.insert("っみぃ", vec!["mmyi", "mmili", "mmixi", "mmilyi", "mmixyi", "ltumyi", "xtumyi", "ltsumyi", "xtsumyi", "ltumili", "xtumili", "ltsumili", "xtsumili", "ltumixi", "xtumixi", "ltsumixi", "xtsumixi", "ltumilyi", "xtumilyi", "ltsumilyi", "xtsumilyi", "ltumixyi", "xtumixyi", "ltsumixyi", "xtsumixyi"]);
     map.insert("っみゅ", vec!["mmyu", "mmilyu", "mmixyu", "ltumyu", "xtumyu", "ltsumyu", "xtsumyu", "ltumilyu", "xtumilyu", "ltsumilyu", "xtsumilyu", "ltumixyu", "xtumixyu", "ltsumixyu", "xtsumixyu"]);
@@ -639,4 +647,116 @@ pub fn create_roman_mapping() -> HashMap<&'static str, Vec<&'static str>> {
     map.insert("んZ", vec!["nnZ", "xnZ"]);
 
     map
-}
\ No newline at end of file
+}
+/// カタカナを対応するひらがなにする（小書きの ァ〜ョ・ッ・ヮ・ヵ・ヶ、ヴ を含む）
+/// ひらがなのない文字（ヷ など）や長音記号 ー はそのまま返す
+pub fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        // ァ (U+30A1) 〜 ヶ (U+30F6) は ぁ (U+3041) 〜 ゖ (U+3096) と同じ並び
+        'ァ'..='ヶ' => char::from_u32(u32::from(c) - 0x60).unwrap_or(c),
+        'ヽ' => 'ゝ',
+        'ヾ' => 'ゞ',
+        _ => c,
+    }
+}
+
+/// 文字列を辞書の見出しに区切る（3文字 → 2文字 → 1文字の順に長いものを優先する）
+/// 戻り値は (文字の位置の範囲, 見出し)。辞書にない文字は見出しなしの1文字になる
+/// カタカナはひらがなに直して辞書を引く
+pub fn split_units(map: &HashMap<&'static str, Vec<&'static str>>, text: &str) -> Vec<(Range<usize>, Option<&'static str>)> {
+    let kana: Vec<char> = text.chars().map(katakana_to_hiragana).collect();
+    let mut units = Vec::new();
+    let mut idx = 0;
+    while idx < kana.len() {
+        let found = (1..=3).rev().filter(|len| idx + len <= kana.len()).find_map(|len| {
+            let key: String = kana[idx..idx + len].iter().collect();
+            map.get_key_value(key.as_str()).map(|(&key, _)| (len, key))
+        });
+        match found {
+            Some((len, key)) => {
+                units.push((idx..idx + len, Some(key)));
+                idx += len;
+            }
+            None => {
+                units.push((idx..idx + 1, None));
+                idx += 1;
+            }
+        }
+    }
+    units
+}
+
+/// 複数の文字の見出しを「先頭の1文字 + 残り」に分けて打つ綴り（例: ふぁ の "fuxa"）なら、先頭の文字を打ち終える位置
+/// まとめて打つ綴り（"fa" など）は None
+pub fn decomposed_split(map: &HashMap<&'static str, Vec<&'static str>>, key: &str, pattern: &str) -> Option<usize> {
+    let first_len = key.chars().next()?.len_utf8();
+    let (first, rest) = key.split_at(first_len);
+    let rest_patterns = map.get(rest)?;
+    map.get(first)?
+        .iter()
+        .filter_map(|head| pattern.strip_prefix(head).map(|tail| (head.len(), tail)))
+        .find(|(_, tail)| rest_patterns.contains(tail))
+        .map(|(split, _)| split)
+}
+
+/// 各単位を最初の候補のローマ字で打ったときの打鍵数（入力できない文字は数えない）
+pub fn canonical_keystrokes(map: &HashMap<&'static str, Vec<&'static str>>, text: &str) -> usize {
+    split_units(map, text)
+        .into_iter()
+        .filter_map(|(_, key)| key.and_then(|key| map.get(key)))
+        .map(|patterns| patterns[0].len())
+        .sum()
+}
+
+/// ローマ字辞書で入力できない文字を出現順に返す（重複は除く）
+/// 拗音などの組み合わせも最後は1文字ずつの入力にできるため、1文字単位で調べれば十分
+/// カタカナはひらがなに直して調べる
+pub fn unsupported_chars(map: &HashMap<&'static str, Vec<&'static str>>, text: &str) -> Vec<char> {
+    let mut unsupported = Vec::new();
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        if !map.contains_key(&*katakana_to_hiragana(c).encode_utf8(&mut buf)) && !unsupported.contains(&c) {
+            unsupported.push(c);
+        }
+    }
+    unsupported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn katakana_maps_onto_the_same_hiragana() {
+        let pairs = [('ア', 'あ'), ('ン', 'ん'), ('ャ', 'ゃ'), ('ョ', 'ょ'), ('ァ', 'ぁ'), ('ォ', 'ぉ'), ('ッ', 'っ'), ('ヴ', 'ゔ'), ('ヮ', 'ゎ'), ('ヵ', 'ゕ'), ('ヶ', 'ゖ'), ('ヾ', 'ゞ')];
+        for (katakana, hiragana) in pairs {
+            assert_eq!(katakana_to_hiragana(katakana), hiragana, "{katakana}");
+        }
+        // ひらがなのない文字、長音記号、ひらがな自身はそのまま
+        for c in ['ヷ', 'ー', 'ね', 'a', '・'] {
+            assert_eq!(katakana_to_hiragana(c), c);
+        }
+    }
+
+    #[test]
+    fn every_katakana_can_be_typed() {
+        let map = create_roman_mapping();
+        let all: String = ('ァ'..='ヶ').chain(['ー']).collect();
+        assert_eq!(unsupported_chars(&map, &all), Vec::<char>::new());
+        for c in 'ァ'..='ヶ' {
+            assert!(('ぁ'..='ゖ').contains(&katakana_to_hiragana(c)), "{c}");
+        }
+    }
+
+    #[test]
+    fn katakana_splits_into_the_same_units_as_hiragana() {
+        let map = create_roman_mapping();
+        for (katakana, hiragana) in [("チョコレートケーキ", "ちょこれーとけーき"), ("ヴァイオリン", "ゔぁいおりん"), ("カフェラテ", "かふぇらて")] {
+            assert_eq!(split_units(&map, katakana), split_units(&map, hiragana), "{katakana}");
+            assert_eq!(canonical_keystrokes(&map, katakana), canonical_keystrokes(&map, hiragana));
+        }
+        // 長音記号も1つの単位になる
+        let keys: Vec<_> = split_units(&map, "ノートパソコン").into_iter().map(|(_, key)| key).collect();
+        assert_eq!(keys, [Some("の"), Some("ー"), Some("と"), Some("ぱ"), Some("そ"), Some("こ"), Some("ん")]);
+    }
+}
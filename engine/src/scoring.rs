@@ -0,0 +1,417 @@
+// ============================================
+// engine/src/scoring.rs
+// お題1問分の成績からスコアと経験値を計算する
+// （プレイ中の採点と rescore コマンドの両方で使う）
+// ============================================
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+/// 採点方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum ScoringPreset {
+    /// 最初から使われている方式
+    Classic,
+    /// プレイ中に使っている方式（正確率を実際に押したキーの数から求める。打鍵数の記録がない古い記録は classic と同じ）
+    Current,
+}
+
+/// 1問分の採点結果
+#[derive(Debug, Clone, Copy)]
+pub struct QuestionScore {
+    /// 正確率 (%)
+    pub accuracy: f64,
+    pub cps: f64,
+    pub score: f64,
+    pub xp: u32,
+    /// 経験値の内訳
+    pub xp_parts: XpParts,
+}
+
+/// お題1問分の経験値の内訳（合計は `QuestionScore::xp` と一致する）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XpParts {
+    /// 打鍵数の分
+    pub base: i64,
+    /// 速さのボーナス
+    pub speed: i64,
+    /// ミスによる減点（0 以下）
+    pub accuracy: i64,
+}
+
+/// 実際に押したキーの数（パターンの切り替えや打ち直しも含む）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Keystrokes {
+    pub correct: u32,
+    pub total: u32,
+}
+
+impl Keystrokes {
+    /// 正確率 (%)：押したキーのうち正しかったものの割合（押したキーの記録がなければ None）
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.total > 0).then(|| f64::from(self.correct.min(self.total)) / f64::from(self.total) * 100.0)
+    }
+}
+
+/// 打鍵数・入力時間・ミス数から採点する
+pub fn score_question(
+    preset: ScoringPreset,
+    total_chars: u32,
+    duration_sec: f64,
+    misses: u32,
+    keystrokes: Keystrokes,
+) -> QuestionScore {
+    let accuracy = match preset {
+        ScoringPreset::Classic => classic_accuracy(total_chars, misses),
+        ScoringPreset::Current => keystrokes.accuracy().unwrap_or_else(|| classic_accuracy(total_chars, misses)),
+    };
+    score_with_accuracy(total_chars, duration_sec, accuracy)
+}
+
+/// 正確率 (%)：お題の打鍵数とミス数から求める（最初からの方式）
+pub fn classic_accuracy(total_chars: u32, misses: u32) -> f64 {
+    let total_attempts = f64::from(total_chars.saturating_add(misses));
+    if total_attempts > 0.0 {
+        (f64::from(total_chars) / total_attempts) * 100.0
+    } else {
+        100.0
+    }
+}
+
+/// 正確率の下限と比べるときの誤差（計算の丸めで、下限ちょうどの記録が対象から外れないように）
+const ACCURACY_FLOOR_EPSILON: f64 = 1e-9;
+
+/// 正確率が自己ベストの対象の下限に届いているか（下限ちょうどは対象）
+pub fn meets_accuracy_floor(accuracy: f64, floor: f64) -> bool {
+    accuracy + ACCURACY_FLOOR_EPSILON >= floor
+}
+
+/// スコアの CPS の倍率（CPS 1.00 を 100 点として数える）
+const SCORE_PER_CPS: f64 = 100.0;
+/// 正確率の補正の指数（正確率 90% なら 0.9^3 ≒ 0.73 倍。ミスを速さより重く見る）
+const ACCURACY_EXPONENT: i32 = 3;
+/// 速さのボーナスで経験値が 2 倍になる CPS（CPS 5 なら 1.5 倍）
+const XP_DOUBLING_CPS: f64 = 10.0;
+
+/// 正確率の補正（0.0 〜 1.0）。スコアと経験値の両方で使う
+fn accuracy_factor(accuracy: f64) -> f64 {
+    (accuracy / 100.0).powi(ACCURACY_EXPONENT)
+}
+
+/// スコア = CPS × 100 × (正確率)^3 × 打鍵数
+/// - 速さ（CPS）に比例する
+/// - 正確率は3乗で効く（100% なら補正なし）
+/// - 打鍵数に比例する（長いお題ほど点が大きい）
+pub fn score(cps: f64, accuracy: f64, total_chars: u32) -> f64 {
+    (cps * SCORE_PER_CPS) * accuracy_factor(accuracy) * f64::from(total_chars)
+}
+
+/// 経験値 = 打鍵数 × (1 + CPS / 10) × (正確率)^3 を四捨五入した値と、その内訳
+/// - 基本: 打鍵数 1 につき 1
+/// - 速さのボーナス: CPS 10 で 2 倍になる割合で増える
+/// - 正確率の補正: スコアと同じく3乗で減らす
+///
+/// 内訳は 基本 → 速さのボーナス → 正確率の補正 の順に掛けた差分（合計は経験値と一致する）
+pub fn xp(total_chars: u32, cps: f64, accuracy: f64) -> (u32, XpParts) {
+    let base_xp = f64::from(total_chars);
+    let skill_bonus = 1.0 + (cps / XP_DOUBLING_CPS);
+    let xp = (base_xp * skill_bonus * accuracy_factor(accuracy)).round() as u32;
+
+    let base = i64::from(total_chars);
+    let speed = (base_xp * skill_bonus).round() as i64 - base;
+    (xp, XpParts { base, speed, accuracy: i64::from(xp) - base - speed })
+}
+
+/// 1秒あたりの打鍵数（入力時間が 0 なら 0）
+pub fn cps(total_chars: u32, duration_sec: f64) -> f64 {
+    if duration_sec > 0.0 { f64::from(total_chars) / duration_sec } else { 0.0 }
+}
+
+/// CPS は打鍵数から、スコアと経験値は正確率で補正して求める
+fn score_with_accuracy(total_chars: u32, duration_sec: f64, accuracy: f64) -> QuestionScore {
+    let cps = cps(total_chars, duration_sec);
+    let (xp, xp_parts) = xp(total_chars, cps, accuracy);
+    QuestionScore { accuracy, cps, score: score(cps, accuracy, total_chars), xp, xp_parts }
+}
+
+// --------------------------------------------------
+// MARK:タグの経験値の倍率
+// --------------------------------------------------
+
+/// 設定できるタグの倍率と、重ねたときの上限の範囲（倍率で経験値が減ることはない）
+pub const TAG_MULTIPLIER_RANGE: RangeInclusive<f64> = 1.0..=3.0;
+
+/// タグごとの経験値の倍率（例: "N4" → 1.5。今週の重点のお題を多めに評価する）
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagMultipliers {
+    multipliers: BTreeMap<String, f64>,
+    /// 複数のタグの倍率を掛け合わせたときの上限
+    cap: f64,
+}
+
+/// あるお題に掛かる倍率
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagBoost {
+    pub multiplier: f64,
+    /// 倍率の掛かったタグ（設定の順）
+    pub tags: Vec<String>,
+}
+
+impl TagMultipliers {
+    /// 設定のタグ → 倍率の表と上限から作る。範囲外の倍率は飛ばし、範囲外の上限は範囲に収め、その理由を返す
+    pub fn from_settings(entries: &BTreeMap<String, f64>, cap: f64) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let (min, max) = (*TAG_MULTIPLIER_RANGE.start(), *TAG_MULTIPLIER_RANGE.end());
+        let mut multipliers = BTreeMap::new();
+        for (tag, &multiplier) in entries {
+            if tag.trim().is_empty() {
+                warnings.push("XP multiplier: empty tag; ignored.".to_string());
+            } else if !TAG_MULTIPLIER_RANGE.contains(&multiplier) {
+                warnings.push(format!(
+                    "XP multiplier for \"{}\": x{} is outside x{}–x{}; ignored.",
+                    tag, multiplier, min, max
+                ));
+            } else {
+                multipliers.insert(tag.trim().to_string(), multiplier);
+            }
+        }
+        let cap = if TAG_MULTIPLIER_RANGE.contains(&cap) {
+            cap
+        } else {
+            let clamped = if cap.is_nan() { max } else { cap.clamp(min, max) };
+            warnings.push(format!("XP multiplier cap x{} is outside x{}–x{}; using x{}.", cap, min, max, clamped));
+            clamped
+        };
+        (Self { multipliers, cap }, warnings)
+    }
+
+    /// お題のタグに掛かる倍率（倍率を設定したタグがなければ None）
+    /// 複数のタグに倍率があれば掛け合わせ、上限に収める
+    pub fn boost(&self, question_tags: &[String]) -> Option<TagBoost> {
+        let multiplier = self.multiplier(question_tags)?;
+        let tags = self.multipliers.keys().filter(|tag| question_tags.contains(tag)).cloned().collect();
+        Some(TagBoost { multiplier, tags })
+    }
+
+    /// `boost` の倍率だけを求める（メモリを確保しないので、打っている途中の表示に毎回使える）
+    pub fn multiplier(&self, question_tags: &[String]) -> Option<f64> {
+        let mut matched = self.multipliers.iter().filter(|(tag, _)| question_tags.contains(tag)).peekable();
+        matched.peek()?;
+        let product: f64 = matched.map(|(_, &multiplier)| multiplier).product();
+        Some(product.clamp(*TAG_MULTIPLIER_RANGE.start(), self.cap))
+    }
+}
+
+/// 倍率を掛けた経験値
+pub fn boosted_xp(xp: u32, multiplier: f64) -> u32 {
+    (f64::from(xp) * multiplier).round() as u32
+}
+
+impl TagBoost {
+    /// 倍率を掛けた経験値と、お題の経験値との差分
+    pub fn apply(&self, xp: u32) -> (u32, i64) {
+        let boosted = boosted_xp(xp, self.multiplier);
+        (boosted, i64::from(boosted) - i64::from(xp))
+    }
+
+    /// 結果の内訳の表示（例: "x1.5 N4 focus"）
+    pub fn label(&self) -> String {
+        let multiplier = format!("{:.2}", self.multiplier);
+        let multiplier = multiplier.trim_end_matches('0').trim_end_matches('.');
+        format!("x{} {} focus", multiplier, self.tags.join("+"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 打鍵数, 入力時間, ミス数
+    type Input = (u32, f64, u32);
+    /// 正確率, CPS, スコア, 経験値, 内訳（基本, 速さ, 正確率）
+    type Expected = (f64, f64, f64, u32, (i64, i64, i64));
+
+    /// 式を変えたときはこの表も書き換え、差分で変化が分かるようにする
+    const GOLDEN: [(Input, Expected); 15] = [
+        ((1, 1.0, 0), (100.0, 1.0, 100.0, 1, (1, 0, 0))),
+        ((10, 2.0, 0), (100.0, 5.0, 5000.0, 15, (10, 5, 0))),
+        ((10, 2.0, 1), (90.909091, 5.0, 3756.574005, 11, (10, 5, -4))),
+        ((10, 2.0, 5), (66.666667, 5.0, 1481.481481, 4, (10, 5, -11))),
+        ((24, 4.0, 0), (100.0, 6.0, 14400.0, 38, (24, 14, 0))),
+        ((24, 4.0, 3), (88.888889, 6.0, 10113.580247, 27, (24, 14, -11))),
+        // 入力時間 0 は CPS 0 として扱う（スコアは 0、経験値は基本と正確率の分だけ）
+        ((24, 0.0, 0), (100.0, 0.0, 0.0, 24, (24, 0, 0))),
+        ((24, 0.0, 2), (92.307692, 0.0, 0.0, 19, (24, 0, -5))),
+        ((0, 0.0, 0), (100.0, 0.0, 0.0, 0, (0, 0, 0))),
+        ((0, 1.0, 3), (0.0, 0.0, 0.0, 0, (0, 0, 0))),
+        ((200, 25.0, 0), (100.0, 8.0, 160000.0, 360, (200, 160, 0))),
+        ((200, 25.0, 10), (95.238095, 8.0, 138214.015765, 311, (200, 160, -49))),
+        // とても長いお題
+        ((5000, 600.0, 0), (100.0, 8.333333, 4166666.666667, 9167, (5000, 4167, 0))),
+        ((5000, 600.0, 250), (95.238095, 8.333333, 3599323.327214, 7919, (5000, 4167, -1248))),
+        ((1_000_000, 100_000.0, 0), (100.0, 10.0, 1_000_000_000.0, 2_000_000, (1_000_000, 1_000_000, 0))),
+    ];
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!((actual - expected).abs() <= 1e-6 * expected.abs().max(1.0), "{}: {} != {}", what, actual, expected);
+    }
+
+    #[test]
+    fn classic_scores_match_the_golden_table() {
+        for ((chars, duration, misses), (accuracy, cps, score, xp, parts)) in GOLDEN {
+            let case = format!("{} chars / {}s / {} misses", chars, duration, misses);
+            let result = score_question(ScoringPreset::Classic, chars, duration, misses, Keystrokes::default());
+            assert_close(result.accuracy, accuracy, &format!("accuracy of {}", case));
+            assert_close(result.cps, cps, &format!("cps of {}", case));
+            assert_close(result.score, score, &format!("score of {}", case));
+            assert_eq!(result.xp, xp, "xp of {}", case);
+            let XpParts { base, speed, accuracy } = result.xp_parts;
+            assert_eq!((base, speed, accuracy), parts, "xp parts of {}", case);
+            assert_eq!(base + speed + accuracy, i64::from(xp), "parts must add up for {}", case);
+        }
+    }
+
+    #[test]
+    fn current_scores_use_the_keys_actually_pressed() {
+        let current = |correct, total| {
+            score_question(ScoringPreset::Current, 10, 2.0, 1, Keystrokes { correct, total })
+        };
+        let golden = [((10, 10), 100.0, 5000.0, 15), ((9, 10), 90.0, 3645.0, 11), ((95, 100), 95.0, 4286.875, 13)];
+        for ((correct, total), accuracy, score, xp) in golden {
+            let result = current(correct, total);
+            assert_close(result.accuracy, accuracy, "accuracy");
+            assert_close(result.score, score, "score");
+            assert_eq!(result.xp, xp);
+        }
+        // 押したキーの記録がない古い記録は classic と同じ
+        let classic = score_question(ScoringPreset::Classic, 10, 2.0, 1, Keystrokes::default());
+        assert_eq!(current(0, 0).xp, classic.xp);
+        assert_close(current(0, 0).score, classic.score, "score without keystrokes");
+    }
+
+    #[test]
+    fn score_and_xp_agree_with_their_documented_terms() {
+        // スコア = CPS × 100 × (正確率)^3 × 打鍵数
+        assert_close(score(4.0, 90.0, 20), 4.0 * 100.0 * 0.9_f64.powi(3) * 20.0, "score");
+        // 経験値 = 打鍵数 × (1 + CPS / 10) × (正確率)^3
+        assert_eq!(xp(20, 4.0, 90.0).0, (20.0 * 1.4 * 0.9_f64.powi(3)).round() as u32);
+        // 正確率 100% なら正確率の補正はない
+        assert_eq!(xp(20, 4.0, 100.0).1.accuracy, 0);
+    }
+
+    fn multipliers(entries: &[(&str, f64)], cap: f64) -> TagMultipliers {
+        let entries = entries.iter().map(|&(tag, multiplier)| (tag.to_string(), multiplier)).collect();
+        let (multipliers, warnings) = TagMultipliers::from_settings(&entries, cap);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        multipliers
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn a_boosted_tag_multiplies_the_question_xp() {
+        let boost = multipliers(&[("N4", 1.5)], 3.0).boost(&tags(&["N4", "noun"])).unwrap();
+        assert_eq!(boost.apply(30), (45, 15));
+        assert_eq!(boost.label(), "x1.5 N4 focus");
+    }
+
+    #[test]
+    fn questions_without_a_boosted_tag_are_not_boosted() {
+        let multipliers = multipliers(&[("N4", 1.5)], 3.0);
+        assert_eq!(multipliers.boost(&tags(&["noun"])), None);
+        assert_eq!(multipliers.boost(&[]), None);
+    }
+
+    #[test]
+    fn several_boosted_tags_stack_multiplicatively() {
+        let boost = multipliers(&[("N4", 1.5), ("verb", 1.2), ("food", 2.0)], 3.0)
+            .boost(&tags(&["verb", "N4"]))
+            .unwrap();
+        assert!((boost.multiplier - 1.8).abs() < 1e-9);
+        assert_eq!(boost.tags, tags(&["N4", "verb"]));
+        assert_eq!(boost.apply(50), (90, 40));
+        assert_eq!(boost.label(), "x1.8 N4+verb focus");
+    }
+
+    #[test]
+    fn stacked_multipliers_stop_at_the_cap() {
+        let question = tags(&["N4", "verb"]);
+        let boost = multipliers(&[("N4", 2.0), ("verb", 2.0)], 2.5).boost(&question).unwrap();
+        assert_eq!(boost.multiplier, 2.5);
+        let boost = multipliers(&[("N4", 3.0), ("verb", 3.0)], 3.0).boost(&question).unwrap();
+        assert_eq!(boost.multiplier, 3.0);
+    }
+
+    #[test]
+    fn a_tag_cannot_reduce_the_question_xp() {
+        let entries: BTreeMap<String, f64> = [("N4".to_string(), 0.5)].into_iter().collect();
+        let (multipliers, warnings) = TagMultipliers::from_settings(&entries, 3.0);
+        assert_eq!(warnings, ["XP multiplier for \"N4\": x0.5 is outside x1–x3; ignored."]);
+        assert_eq!(multipliers.boost(&tags(&["N4"])), None);
+        let (multipliers, warnings) = TagMultipliers::from_settings(&BTreeMap::new(), 0.5);
+        assert_eq!(warnings, ["XP multiplier cap x0.5 is outside x1–x3; using x1."]);
+        assert_eq!(multipliers.cap, 1.0);
+    }
+
+    #[test]
+    fn the_bare_multiplier_matches_the_boost() {
+        let multipliers = multipliers(&[("N4", 1.5), ("verb", 1.2), ("food", 2.0)], 2.5);
+        for question in [tags(&["N4"]), tags(&["verb", "N4"]), tags(&["N4", "food"]), tags(&["noun"]), Vec::new()] {
+            assert_eq!(multipliers.multiplier(&question), multipliers.boost(&question).map(|boost| boost.multiplier));
+        }
+        assert_eq!(boosted_xp(31, 1.5), 47);
+    }
+
+    #[test]
+    fn multipliers_outside_the_range_are_ignored_with_a_warning() {
+        let entries: BTreeMap<String, f64> =
+            [("N4".to_string(), 3.5), ("N5".to_string(), 0.4), ("verb".to_string(), f64::NAN), ("food".to_string(), 3.0)]
+                .into_iter()
+                .collect();
+        let (multipliers, warnings) = TagMultipliers::from_settings(&entries, 3.0);
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(multipliers.boost(&tags(&["N4", "N5", "verb"])), None);
+        assert!(multipliers.boost(&tags(&["food"])).is_some());
+    }
+
+    #[test]
+    fn a_cap_outside_the_range_is_clamped_with_a_warning() {
+        let entries: BTreeMap<String, f64> = [("N4".to_string(), 2.0), ("verb".to_string(), 2.0)].into_iter().collect();
+        let (multipliers, warnings) = TagMultipliers::from_settings(&entries, 10.0);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(multipliers.boost(&tags(&["N4", "verb"])).unwrap().multiplier, 3.0);
+        let (_, warnings) = TagMultipliers::from_settings(&entries, f64::NAN);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn accuracy_exactly_at_the_floor_meets_it() {
+        // (正しいキー, 押したキー, 下限)。割り算で下限をわずかに下回る値になっても、ちょうどなら届いたとみなす
+        let cases = [(19, 20, 95.0), (57, 60, 95.0), (49, 50, 98.0), (9, 10, 90.0), (97, 100, 97.0), (7, 10, 70.0)];
+        for (correct, total, floor) in cases {
+            let at = Keystrokes { correct, total }.accuracy().unwrap();
+            assert!(meets_accuracy_floor(at, floor), "{correct}/{total} = {at} vs {floor}");
+            // キー1つ分でも間違いが多ければ届かない
+            let below = Keystrokes { correct: correct - 1, total }.accuracy().unwrap();
+            assert!(!meets_accuracy_floor(below, floor), "{}/{total} = {below} vs {floor}", correct - 1);
+            // 押したキーの記録がない古い記録の正確率でも同じ
+            assert!(meets_accuracy_floor(classic_accuracy(correct, total - correct), floor), "{correct}/{total}");
+        }
+    }
+
+    #[test]
+    fn the_floor_epsilon_only_absorbs_rounding() {
+        assert!(!meets_accuracy_floor(94.99999, 95.0));
+        assert!(!meets_accuracy_floor(95.0 - 1e-6, 95.0));
+        assert!(meets_accuracy_floor(95.0 - 1e-12, 95.0));
+        // 下限 0 はどの記録も対象にする
+        assert!(meets_accuracy_floor(0.0, 0.0));
+        assert!(meets_accuracy_floor(100.0, 100.0));
+    }
+}
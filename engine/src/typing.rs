@@ -0,0 +1,416 @@
+// ============================================
+// engine/src/typing.rs
+// 「タイピング単位」ごとの打鍵の判定（綴りの切り替え・ミスの割り当て）と、1問分の進行
+// 時刻は呼び出し側からミリ秒で受け取る（Instant を使わないので wasm32 でもそのまま動く）
+// ============================================
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+use crate::roman_mapping::{decomposed_split, split_units};
+use crate::scoring::{Keystrokes, ScoringPreset, score_question};
+
+/// 1回のミスの割り当て
+/// - 打つべきだったキーは、今の綴り（画面に出ている綴り）の次の文字
+///   別の綴りに切り替えれば合うキーは正しい入力で、ミスにはならない（"si" の途中で h を押せば "shi"）
+/// - 押したキーが同じ単位の後ろの文字や次の単位の先頭に合っても（"ka" で先に a を押した）、先へは進めず今の単位のミスにする
+/// - 単位の番号は今打っている単位（ミスの印もこの単位に付ける）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Miss {
+    pub expected: char,
+    pub actual: char,
+    pub unit: usize,
+}
+
+/// 1打鍵の判定結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    /// 正しい入力（別の綴りに切り替えたなら true）
+    Hit(bool),
+    /// ミス（どのキーのミスとして数えるかは `Miss` を参照）
+    Miss(Miss),
+    /// 読み飛ばす単位でスペース以外を押した（ミスに数えない）
+    Ignored,
+}
+
+/// 「タイピング単位」（例：「し」「きゃ」）の状態を管理する
+#[derive(Debug, Clone)]
+pub struct CharState {
+    pub hiragana: String,           // "し" や "きゃ"
+    pub patterns: Vec<String>,      // ["si", "shi", "ci"]
+    pub current_pattern_idx: usize, // 今 "shi" を入力中など
+    pub typed_count: usize,         // "shi" の "s" まで入力済みなら 1
+    pub unsupported: bool,          // ローマ字辞書にない文字（スペースで読み飛ばす）
+    pub splits: Vec<Option<usize>>, // パターンごとの、先頭のかなを打ち終える位置（"fuxa" なら 2、"fa" なら None）
+}
+
+impl CharState {
+    pub fn new(hiragana: String, patterns: Vec<String>) -> Self {
+        Self {
+            hiragana,
+            patterns,
+            current_pattern_idx: 0,
+            typed_count: 0,
+            unsupported: false,
+            splits: Vec::new(),
+        }
+    }
+
+    /// ふぁ → "fu" + "xa" のように分けて打てる綴りの区切りを設定する
+    pub fn with_splits(self, splits: Vec<Option<usize>>) -> Self {
+        Self { splits, ..self }
+    }
+
+    /// ローマ字辞書にない文字の単位（表示はそのままで、スペースを押すと読み飛ばす）
+    pub fn unsupported(hiragana: String) -> Self {
+        Self {
+            unsupported: true,
+            ..Self::new(hiragana, vec![" ".to_string()])
+        }
+    }
+
+    /// 打鍵数として数える長さ（読み飛ばす単位は数えない）
+    pub fn typed_len(&self) -> usize {
+        if self.unsupported { 0 } else { self.current_pattern().len() }
+    }
+
+    /// 入力を取り消し、綴りの選択も最初のパターンに戻す
+    pub fn reset(&mut self) {
+        self.current_pattern_idx = 0;
+        self.typed_count = 0;
+    }
+
+    /// 現在アクティブなローマ字パターン（例: "shi"）を返す
+    pub fn current_pattern(&self) -> &str {
+        &self.patterns[self.current_pattern_idx]
+    }
+
+    /// この CharState が完了したか（例: "shi" を3文字打ち終わったか）
+    pub fn is_complete(&self) -> bool {
+        self.typed_count >= self.current_pattern().len()
+    }
+
+    /// 現在のパターンで、まだタイプしていない残りの部分（例: "hi"）
+    pub fn remaining(&self) -> &str {
+        &self.current_pattern()[self.typed_count..]
+    }
+
+    /// `c` を打つ。今の綴りで合わなければ、打った部分が同じ別の綴りに切り替える
+    /// 正しい入力なら Some(綴りを切り替えたか)、ミスなら None（状態は変えない）
+    pub fn type_key(&mut self, c: char) -> Option<bool> {
+        let pattern_idx = self.pattern_for(c)?;
+        let switched = pattern_idx != self.current_pattern_idx;
+        self.current_pattern_idx = pattern_idx;
+        self.typed_count += 1;
+        Some(switched)
+    }
+
+    /// `c` が正しい入力になるか（別の綴りへの切り替えも含む。状態は変えない）
+    pub fn would_accept(&self, c: char) -> bool {
+        self.pattern_for(c).is_some()
+    }
+
+    /// `c` を打てる綴りの番号（今の綴りを優先し、なければ打った部分が同じ別の綴り）
+    fn pattern_for(&self, c: char) -> Option<usize> {
+        if self.remaining().starts_with(c) {
+            return Some(self.current_pattern_idx);
+        }
+        let typed_so_far = &self.current_pattern()[..self.typed_count];
+        self.patterns
+            .iter()
+            .position(|pattern| pattern.starts_with(typed_so_far) && pattern[self.typed_count..].starts_with(c))
+    }
+
+    /// `c` を打って判定する（`unit` はこの単位の番号）
+    /// ミスは今の綴りの次の文字のミスとし、後ろの文字に合うキーでも先へは進めない
+    pub fn judge(&mut self, c: char, unit: usize) -> KeyOutcome {
+        let expected = self.remaining().chars().next();
+        match self.type_key(c) {
+            Some(switched) => KeyOutcome::Hit(switched),
+            None if self.unsupported => KeyOutcome::Ignored,
+            None => KeyOutcome::Miss(Miss { expected: expected.unwrap_or(c), actual: c, unit }),
+        }
+    }
+
+    /// 打ち終えたかなの文字数（"fuxa" で "fu" まで打てば ふぁ のうち1文字）
+    pub fn typed_kana(&self) -> usize {
+        if self.is_complete() {
+            return self.hiragana.chars().count();
+        }
+        match self.splits.get(self.current_pattern_idx).copied().flatten() {
+            Some(split) if self.typed_count >= split => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// ひらがな文字列を `Vec<CharState>` に分解（パース）する
+/// カタカナはひらがなに直して辞書を引き、表示用の文字はカタカナのまま残す
+pub fn parse_hiragana(map: &HashMap<&'static str, Vec<&'static str>>, text: &str) -> Vec<CharState> {
+    let chars: Vec<char> = text.chars().collect();
+    split_units(map, text)
+        .into_iter()
+        .map(|(range, key)| {
+            let unit: String = chars[range].iter().collect();
+            match key.and_then(|key| map.get_key_value(key)) {
+                Some((&key, patterns)) => {
+                    let splits = patterns.iter().map(|p| decomposed_split(map, key, p)).collect();
+                    CharState::new(unit, patterns.iter().map(|s| s.to_string()).collect()).with_splits(splits)
+                }
+                // 入力できない文字も表示とずれないよう単位として残す
+                None => CharState::unsupported(unit),
+            }
+        })
+        .collect()
+}
+
+/// 今の単位で `c` を判定し、単位を打ち終えたら次の単位へ進める（すべて打ち終えていれば None）
+pub fn advance(units: &mut [CharState], current: &mut usize, c: char) -> Option<KeyOutcome> {
+    let unit = *current;
+    let cs = units.get_mut(unit)?;
+    let outcome = cs.judge(c, unit);
+    if matches!(outcome, KeyOutcome::Hit(_)) && cs.is_complete() {
+        *current += 1;
+    }
+    Some(outcome)
+}
+
+/// 今の位置までに打ち終えた打鍵数（途中の単位は打ったところまで）
+pub fn typed_len_so_far(units: &[CharState], current: usize) -> usize {
+    units
+        .iter()
+        .take(current + 1)
+        .enumerate()
+        .map(|(i, cs)| match i.cmp(&current) {
+            std::cmp::Ordering::Less => cs.typed_len(),
+            _ if cs.unsupported => 0,
+            _ => cs.typed_count,
+        })
+        .sum()
+}
+
+/// 1打鍵を渡したあとの様子
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyFeed {
+    /// "hit" / "miss" / "ignored"。打ち終えたお題にキーを渡したときは "finished"
+    pub outcome: &'static str,
+    /// 別の綴りに切り替えたか
+    pub switched: bool,
+    /// ミスなら打つべきだったキー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<char>,
+    /// 今打っている単位の番号
+    pub unit: usize,
+    /// 打ち終えたローマ字と、これから打つローマ字
+    pub typed: String,
+    pub remaining: String,
+    pub complete: bool,
+}
+
+/// 1問分の成績（アプリ本体の記録と同じ数え方）
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionResult {
+    pub complete: bool,
+    /// 打鍵数（打ち終えていなければ打ったところまで）
+    pub total_chars: u32,
+    pub misses: u32,
+    /// 押したキーの数と、そのうち正しかった数（読み飛ばす単位のキーは数えない）
+    pub keystrokes: u32,
+    pub correct_keystrokes: u32,
+    /// 最初のキーから最後のキーまで（秒）
+    pub duration_sec: f64,
+    pub accuracy: f64,
+    pub cps: f64,
+    /// 打ち終えていなければスコアと経験値は 0（時間切れの記録と同じ）
+    pub score: f64,
+    pub xp: u32,
+}
+
+/// 画面を持たない1問分の進行（ウェブのデモと、アプリ本体と同じ判定になるかの確かめに使う）
+/// 時間は最初のキーから最後に打ったキーまで（アプリ本体の first-key の計り方と同じ）
+#[derive(Debug, Clone)]
+pub struct TypingSession {
+    units: Vec<CharState>,
+    current: usize,
+    misses: u32,
+    keystrokes: Keystrokes,
+    first_key_ms: Option<f64>,
+    last_key_ms: Option<f64>,
+}
+
+impl TypingSession {
+    pub fn new(map: &HashMap<&'static str, Vec<&'static str>>, hiragana: &str) -> Self {
+        Self {
+            units: parse_hiragana(map, hiragana),
+            current: 0,
+            misses: 0,
+            keystrokes: Keystrokes::default(),
+            first_key_ms: None,
+            last_key_ms: None,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.current >= self.units.len()
+    }
+
+    /// `timestamp_ms` に押した `c` を処理する
+    pub fn feed_key(&mut self, c: char, timestamp_ms: f64) -> KeyFeed {
+        let unit = self.current;
+        let counted = self.units.get(unit).is_some_and(|cs| !cs.unsupported);
+        let Some(outcome) = advance(&mut self.units, &mut self.current, c) else {
+            return self.feed("finished", false, None);
+        };
+        self.first_key_ms.get_or_insert(timestamp_ms);
+        self.last_key_ms = Some(timestamp_ms);
+        if counted {
+            self.keystrokes.total += 1;
+        }
+        match outcome {
+            KeyOutcome::Hit(switched) => {
+                if counted {
+                    self.keystrokes.correct += 1;
+                }
+                self.feed("hit", switched, None)
+            }
+            KeyOutcome::Miss(miss) => {
+                self.misses += 1;
+                self.feed("miss", false, Some(miss.expected))
+            }
+            KeyOutcome::Ignored => self.feed("ignored", false, None),
+        }
+    }
+
+    fn feed(&self, outcome: &'static str, switched: bool, expected: Option<char>) -> KeyFeed {
+        let mut typed = String::new();
+        let mut remaining = String::new();
+        for (i, cs) in self.units.iter().enumerate() {
+            let text = if cs.unsupported { cs.hiragana.as_str() } else { cs.current_pattern() };
+            let done = match i.cmp(&self.current) {
+                std::cmp::Ordering::Less => text.len(),
+                std::cmp::Ordering::Equal if !cs.unsupported => cs.typed_count.min(text.len()),
+                _ => 0,
+            };
+            typed.push_str(&text[..done]);
+            remaining.push_str(&text[done..]);
+        }
+        KeyFeed { outcome, switched, expected, unit: self.current, typed, remaining, complete: self.is_complete() }
+    }
+
+    /// ここまでの成績（アプリ本体が記録するときと同じ採点）
+    pub fn finish(&self) -> SessionResult {
+        let complete = self.is_complete();
+        let total_chars = if complete {
+            self.units.iter().map(CharState::typed_len).sum()
+        } else {
+            typed_len_so_far(&self.units, self.current)
+        } as u32;
+        let duration_sec = match (self.first_key_ms, self.last_key_ms) {
+            (Some(first), Some(last)) => (last - first).max(0.0) / 1000.0,
+            _ => 0.0,
+        };
+        let scored = score_question(ScoringPreset::Current, total_chars, duration_sec, self.misses, self.keystrokes);
+        SessionResult {
+            complete,
+            total_chars,
+            misses: self.misses,
+            keystrokes: self.keystrokes.total,
+            correct_keystrokes: self.keystrokes.correct,
+            duration_sec,
+            accuracy: scored.accuracy,
+            cps: scored.cps,
+            score: if complete { scored.score } else { 0.0 },
+            xp: if complete { scored.xp } else { 0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::create_roman_mapping;
+
+    fn units(hiragana: &str) -> Vec<CharState> {
+        parse_hiragana(&create_roman_mapping(), hiragana)
+    }
+
+    #[test]
+    fn a_key_that_only_fits_another_spelling_switches_to_it() {
+        let mut units = units("し");
+        assert_eq!(units[0].judge('s', 0), KeyOutcome::Hit(false));
+        assert_eq!(units[0].current_pattern(), "si");
+        assert_eq!(units[0].judge('h', 0), KeyOutcome::Hit(true));
+        assert_eq!((units[0].current_pattern(), units[0].typed_count), ("shi", 2));
+        assert_eq!(units[0].judge('i', 0), KeyOutcome::Hit(false));
+        assert!(units[0].is_complete());
+    }
+
+    #[test]
+    fn would_accept_matches_type_key_without_typing() {
+        let mut units = units("し");
+        units[0].type_key('s');
+        assert!(units[0].would_accept('h') && units[0].would_accept('i'));
+        assert!(!units[0].would_accept('a'));
+        // 確かめるだけでは綴りも位置も変わらない
+        assert_eq!((units[0].current_pattern(), units[0].typed_count), ("si", 1));
+    }
+
+    #[test]
+    fn a_later_letter_of_the_same_unit_is_a_miss_on_the_next_letter() {
+        let mut units = units("か");
+        assert_eq!(units[0].judge('a', 0), KeyOutcome::Miss(Miss { expected: 'k', actual: 'a', unit: 0 }));
+        assert_eq!(units[0].typed_count, 0);
+    }
+
+    #[test]
+    fn a_key_for_the_next_unit_is_a_miss_on_the_current_unit() {
+        let mut units = units("かに");
+        let mut current = 0;
+        assert_eq!(advance(&mut units, &mut current, 'k'), Some(KeyOutcome::Hit(false)));
+        assert_eq!(
+            advance(&mut units, &mut current, 'n'),
+            Some(KeyOutcome::Miss(Miss { expected: 'a', actual: 'n', unit: 0 }))
+        );
+        // 先へは進まず、次の単位にも打った印は付かない
+        assert_eq!((current, units[0].typed_count, units[1].typed_count), (0, 1, 0));
+    }
+
+    #[test]
+    fn a_plain_miss_names_the_expected_key_and_leaves_the_unit_untouched() {
+        let mut units = units("ねこ");
+        let mut current = 0;
+        for c in "ne".chars() {
+            advance(&mut units, &mut current, c);
+        }
+        assert_eq!(
+            advance(&mut units, &mut current, 'x'),
+            Some(KeyOutcome::Miss(Miss { expected: 'k', actual: 'x', unit: 1 }))
+        );
+        assert_eq!((current, units[1].current_pattern(), units[1].typed_count), (1, "ko", 0));
+    }
+
+    #[test]
+    fn misses_are_always_attributed_to_the_unit_being_typed() {
+        // ミスの印は (Miss::unit, 今の単位の打った位置) で付けるので、この2つがいつも今の単位を指すことを確かめる
+        let mut units = units("きゃっかんしゃ");
+        let mut current = 0;
+        for c in "kqyzaxkkqazsnhxya".chars() {
+            let before = (current, units.get(current).map(|cs| cs.typed_count));
+            if let Some(KeyOutcome::Miss(miss)) = advance(&mut units, &mut current, c) {
+                assert_eq!(miss.unit, before.0, "{}", c);
+                assert_eq!((current, Some(units[current].typed_count)), before, "{}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_units_ignore_everything_but_space() {
+        let mut units = units("☆");
+        assert!(units[0].unsupported);
+        assert_eq!(units[0].judge('a', 0), KeyOutcome::Ignored);
+        assert_eq!(units[0].judge(' ', 0), KeyOutcome::Hit(false));
+        assert!(units[0].is_complete());
+        assert_eq!(units[0].typed_len(), 0);
+    }
+}
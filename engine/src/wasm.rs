@@ -0,0 +1,29 @@
+// ============================================
+// engine/src/wasm.rs
+// ウェブのデモ向けの wasm-bindgen の書き出し（`EngineSession` をそのまま包む）
+// ============================================
+
+use wasm_bindgen::prelude::*;
+
+use crate::EngineSession;
+
+#[wasm_bindgen]
+pub struct Session(EngineSession);
+
+/// ひらがな（カタカナ混じりでもよい）のお題で1問分の進行を始める
+#[wasm_bindgen]
+pub fn new_session(hiragana: &str) -> Session {
+    Session(EngineSession::new(hiragana))
+}
+
+#[wasm_bindgen]
+impl Session {
+    /// `timestamp_ms` は呼び出し側の時計（`performance.now()` など）
+    pub fn feed_key(&mut self, key: char, timestamp_ms: f64) -> String {
+        self.0.feed_key(key, timestamp_ms)
+    }
+
+    pub fn finish(&self) -> String {
+        self.0.finish()
+    }
+}
@@ -0,0 +1,308 @@
+// ============================================
+// src/achievements.rs
+// 実績（節目の達成）の定義と判定
+// ============================================
+
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::locale::format_count;
+use crate::save_data::{PlayerData, TypeRecord};
+use crate::stats::SetTotals;
+
+/// 実績の達成条件
+pub enum Goal {
+    /// 累計タイプ数
+    LifetimeChars(u64),
+    /// ノーミスのお題が続いた数（ウォームアップは数えない）
+    PerfectRun(u32),
+    /// 1セッションの平均 CPS（お題数が少ないうちは判定しない）
+    SessionCps { cps: f64, min_questions: u32 },
+    /// 練習した日が続いた日数
+    DayStreak(u32),
+}
+
+/// 実績の定義
+pub struct Achievement {
+    /// セーブデータに記録する ID（変えないこと）
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub goal: Goal,
+}
+
+/// 実績の一覧（表示もこの順）
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "chars-10k",
+        name: "First Steps",
+        description: "Type 10,000 characters in total",
+        goal: Goal::LifetimeChars(10_000),
+    },
+    Achievement {
+        id: "chars-100k",
+        name: "Keyboard Regular",
+        description: "Type 100,000 characters in total",
+        goal: Goal::LifetimeChars(100_000),
+    },
+    Achievement {
+        id: "chars-1m",
+        name: "Million Keys",
+        description: "Type 1,000,000 characters in total",
+        goal: Goal::LifetimeChars(1_000_000),
+    },
+    Achievement {
+        id: "perfect-10",
+        name: "Clean Hands",
+        description: "Finish 10 questions in a row without a miss",
+        goal: Goal::PerfectRun(10),
+    },
+    Achievement {
+        id: "perfect-30",
+        name: "Flawless",
+        description: "Finish 30 questions in a row without a miss",
+        goal: Goal::PerfectRun(30),
+    },
+    Achievement {
+        id: "session-cps-5",
+        name: "Quick Fingers",
+        description: "Average 5 CPS or more over a session of 10+ questions",
+        goal: Goal::SessionCps { cps: 5.0, min_questions: 10 },
+    },
+    Achievement {
+        id: "streak-7",
+        name: "Habit",
+        description: "Practice 7 days in a row",
+        goal: Goal::DayStreak(7),
+    },
+    Achievement {
+        id: "streak-30",
+        name: "Devoted",
+        description: "Practice 30 days in a row",
+        goal: Goal::DayStreak(30),
+    },
+];
+
+/// 達成した実績（ID と達成した時刻）
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct EarnedAchievement {
+    pub id: String,
+    /// 達成した時刻（UNIX 秒）
+    pub earned_at: i64,
+}
+
+impl EarnedAchievement {
+    pub fn earned_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.earned_at, 0).single().unwrap_or_default()
+    }
+}
+
+/// 実績の進み具合（`current >= target` で達成）
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub current: u64,
+    pub target: u64,
+}
+
+impl Progress {
+    pub fn is_complete(&self) -> bool {
+        self.current >= self.target
+    }
+}
+
+impl Goal {
+    /// 今の進み具合。セッションの平均 CPS は 0.01 CPS 単位で表す
+    pub fn progress(&self, data: &PlayerData, session: &SetTotals, today: NaiveDate) -> Progress {
+        match *self {
+            Goal::LifetimeChars(target) => Progress { current: data.total_typed_chars, target },
+            Goal::PerfectRun(target) => Progress {
+                current: u64::from(perfect_run(&data.history, target)),
+                target: u64::from(target),
+            },
+            Goal::SessionCps { cps, min_questions } => {
+                let current = if session.questions >= min_questions && session.duration_sec > 0.0 {
+                    f64::from(session.chars) / session.duration_sec
+                } else {
+                    0.0
+                };
+                Progress { current: (current * 100.0) as u64, target: (cps * 100.0).round() as u64 }
+            }
+            Goal::DayStreak(target) => Progress {
+                current: u64::from(day_streak(&data.history, today, target)),
+                target: u64::from(target),
+            },
+        }
+    }
+
+    /// 進み具合の表示（「62,000 / 100,000」など）
+    pub fn format_progress(&self, progress: Progress) -> String {
+        match self {
+            Goal::LifetimeChars(_) => {
+                format!("{} / {}", format_count(progress.current), format_count(progress.target))
+            }
+            Goal::PerfectRun(_) => format!("{} / {} in a row", progress.current, progress.target),
+            Goal::SessionCps { .. } => format!(
+                "{:.2} / {:.2} CPS this session",
+                progress.current as f64 / 100.0,
+                progress.target as f64 / 100.0
+            ),
+            Goal::DayStreak(_) => format!("{} / {} days", progress.current, progress.target),
+        }
+    }
+}
+
+/// 履歴の末尾から数えたノーミスのお題の連続数（`cap` で数えるのをやめる）
+pub fn perfect_run(history: &[TypeRecord], cap: u32) -> u32 {
+    let mut run = 0;
+    for record in history.iter().rev().filter(|record| record.counts_for_bests()) {
+        if record.misses > 0 || run >= cap {
+            break;
+        }
+        run += 1;
+    }
+    run
+}
+
+/// `today`（今日練習していなければ昨日）から遡って、練習した日が続いている日数（`cap` で数えるのをやめる）
+pub fn day_streak(history: &[TypeRecord], today: NaiveDate, cap: u32) -> u32 {
+    let mut streak = 0;
+    let mut expected = today;
+    for record in history.iter().rev() {
+        let date = record.timestamp.with_timezone(&Local).date_naive();
+        if date == expected {
+            streak += 1;
+            if streak >= cap {
+                break;
+            }
+            expected = expected.pred_opt().unwrap_or(expected);
+        } else if streak == 0 && date == today.pred_opt().unwrap_or(today) {
+            // 今日はまだ練習していない
+            streak = 1;
+            expected = date.pred_opt().unwrap_or(date);
+        } else if date < expected {
+            break;
+        }
+    }
+    streak
+}
+
+/// まだ達成していない実績のうち、条件を満たしたもの
+/// 練習した日の連続は、その日の最初のお題のときだけ調べれば足りる（`check_days`）
+pub fn newly_earned(
+    data: &PlayerData,
+    session: &SetTotals,
+    today: NaiveDate,
+    check_days: bool,
+) -> Vec<&'static Achievement> {
+    ACHIEVEMENTS
+        .iter()
+        .filter(|achievement| !data.has_achievement(achievement.id))
+        .filter(|achievement| check_days || !matches!(achievement.goal, Goal::DayStreak(_)))
+        .filter(|achievement| achievement.goal.progress(data, session, today).is_complete())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeDelta};
+
+    /// `today` の `days_ago` 日前の昼に打った記録
+    fn record_on(today: NaiveDate, days_ago: i64, misses: u32) -> TypeRecord {
+        let mut record = TypeRecord::sample("ねこ", 4, 1.0, misses);
+        let noon = (today - TimeDelta::days(days_ago)).and_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        record.timestamp = Local.from_local_datetime(&noon).unwrap().with_timezone(&Utc);
+        record
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 15).unwrap()
+    }
+
+    fn earned_ids(data: &PlayerData, session: &SetTotals, check_days: bool) -> Vec<&'static str> {
+        newly_earned(data, session, today(), check_days).iter().map(|a| a.id).collect()
+    }
+
+    #[test]
+    fn lifetime_characters_unlock_at_the_target() {
+        let mut data = PlayerData::default();
+        data.total_typed_chars = 9_999;
+        assert!(earned_ids(&data, &SetTotals::default(), false).is_empty());
+        let goal = &ACHIEVEMENTS[0].goal;
+        assert_eq!(goal.format_progress(goal.progress(&data, &SetTotals::default(), today())), "9,999 / 10,000");
+
+        data.total_typed_chars = 10_000;
+        assert_eq!(earned_ids(&data, &SetTotals::default(), false), ["chars-10k"]);
+    }
+
+    #[test]
+    fn a_perfect_run_counts_back_to_the_last_miss() {
+        let mut data = PlayerData::default();
+        data.history.push(record_on(today(), 0, 1));
+        data.history.extend((0..9).map(|_| record_on(today(), 0, 0)));
+        assert_eq!(perfect_run(&data.history, 30), 9);
+        assert!(earned_ids(&data, &SetTotals::default(), false).is_empty());
+
+        // ウォームアップはミスがあっても数えない
+        let mut warmup = record_on(today(), 0, 3);
+        warmup.warmup = true;
+        data.history.push(warmup);
+        data.history.push(record_on(today(), 0, 0));
+        assert_eq!(perfect_run(&data.history, 30), 10);
+        assert_eq!(earned_ids(&data, &SetTotals::default(), false), ["perfect-10"]);
+        assert_eq!(perfect_run(&data.history, 4), 4);
+    }
+
+    #[test]
+    fn session_speed_needs_enough_questions_and_the_average() {
+        let session = |questions: u32, chars: u32, duration_sec: f64| SetTotals {
+            questions,
+            chars,
+            duration_sec,
+            ..SetTotals::default()
+        };
+        let data = PlayerData::default();
+        assert!(earned_ids(&data, &session(9, 90, 10.0), false).is_empty());
+        assert!(earned_ids(&data, &session(10, 499, 100.0), false).is_empty());
+        assert_eq!(earned_ids(&data, &session(10, 500, 100.0), false), ["session-cps-5"]);
+        let goal = &ACHIEVEMENTS[5].goal;
+        assert_eq!(goal.format_progress(goal.progress(&data, &session(10, 499, 100.0), today())), "4.99 / 5.00 CPS this session");
+    }
+
+    #[test]
+    fn a_day_streak_may_end_yesterday_but_not_earlier() {
+        let mut data = PlayerData::default();
+        data.history.extend((1..=6).rev().map(|days_ago| record_on(today(), days_ago, 0)));
+        assert_eq!(day_streak(&data.history, today(), 30), 6);
+        assert!(earned_ids(&data, &SetTotals::default(), true).is_empty());
+
+        data.history.push(record_on(today(), 0, 0));
+        data.history.push(record_on(today(), 0, 0));
+        assert_eq!(day_streak(&data.history, today(), 30), 7);
+        assert_eq!(earned_ids(&data, &SetTotals::default(), true), ["streak-7"]);
+        // 日の最初のお題以外では調べない
+        assert!(earned_ids(&data, &SetTotals::default(), false).is_empty());
+
+        // 一日空くと途切れる
+        let gap = [record_on(today(), 3, 0), record_on(today(), 1, 0)];
+        assert_eq!(day_streak(&gap, today(), 30), 1);
+        assert_eq!(day_streak(&gap[..1], today(), 30), 0);
+    }
+
+    #[test]
+    fn an_earned_achievement_is_not_earned_again() {
+        let mut data = PlayerData::default();
+        data.total_typed_chars = 100_000;
+        let at = Utc::now();
+        for achievement in newly_earned(&data, &SetTotals::default(), today(), true) {
+            data.award_achievement(achievement.id, at);
+        }
+        assert_eq!(data.achievements.len(), 2);
+        assert!(earned_ids(&data, &SetTotals::default(), true).is_empty());
+
+        data.award_achievement("chars-10k", at + TimeDelta::days(1));
+        assert_eq!(data.achievements.len(), 2);
+        assert_eq!(data.achievements[0].earned_at(), Utc.timestamp_opt(at.timestamp(), 0).unwrap());
+    }
+}
@@ -0,0 +1,128 @@
+// ============================================
+// src/author.rs
+// TUI でお題を作る画面の入力状態
+// ============================================
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::text_input::TextInput;
+use crate::user_questions::QUESTION_TAGS;
+
+/// 日本語・ひらがなの最大文字数
+const FIELD_MAX_CHARS: usize = 40;
+
+/// 入力中の欄
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorField {
+    Japanese,
+    Hiragana,
+    Tags,
+}
+
+impl AuthorField {
+    fn next(self) -> Self {
+        match self {
+            AuthorField::Japanese => AuthorField::Hiragana,
+            AuthorField::Hiragana => AuthorField::Tags,
+            AuthorField::Tags => AuthorField::Japanese,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            AuthorField::Japanese => AuthorField::Tags,
+            AuthorField::Hiragana => AuthorField::Japanese,
+            AuthorField::Tags => AuthorField::Hiragana,
+        }
+    }
+}
+
+/// キー入力を処理した結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorOutcome {
+    Editing,
+    /// Enter で保存を求められた
+    Save,
+    /// Esc で画面を閉じる
+    Back,
+}
+
+/// お題を作る画面の入力状態
+pub struct AuthorForm {
+    pub japanese: TextInput,
+    pub hiragana: TextInput,
+    /// `QUESTION_TAGS` のそれぞれを選んでいるか
+    pub tags: [bool; QUESTION_TAGS.len()],
+    /// タグ欄のカーソル位置
+    pub tag_cursor: usize,
+    pub focus: AuthorField,
+    /// 直前の保存の結果 (メッセージ, 成功したか)
+    pub message: Option<(String, bool)>,
+}
+
+impl Default for AuthorForm {
+    fn default() -> Self {
+        Self {
+            japanese: TextInput::new("", FIELD_MAX_CHARS),
+            hiragana: TextInput::new("", FIELD_MAX_CHARS),
+            tags: [false; QUESTION_TAGS.len()],
+            tag_cursor: 0,
+            focus: AuthorField::Japanese,
+            message: None,
+        }
+    }
+}
+
+impl AuthorForm {
+    /// 保存した後に次のお題を作れるよう、入力欄とタグを空にする
+    pub fn clear(&mut self) {
+        self.japanese.clear();
+        self.hiragana.clear();
+        self.tags = [false; QUESTION_TAGS.len()];
+        self.focus = AuthorField::Japanese;
+    }
+
+    /// 選んでいるタグ
+    pub fn selected_tags(&self) -> Vec<String> {
+        QUESTION_TAGS
+            .iter()
+            .zip(self.tags)
+            .filter(|(_, selected)| *selected)
+            .map(|(tag, _)| tag.to_string())
+            .collect()
+    }
+
+    /// キー入力を処理する
+    pub fn handle_key(&mut self, key: &KeyEvent) -> AuthorOutcome {
+        match key.code {
+            KeyCode::Esc => return AuthorOutcome::Back,
+            KeyCode::Enter => return AuthorOutcome::Save,
+            KeyCode::Tab | KeyCode::Down => self.focus = self.focus.next(),
+            KeyCode::BackTab | KeyCode::Up => self.focus = self.focus.prev(),
+            _ => match self.focus {
+                AuthorField::Japanese => {
+                    self.japanese.handle_key(key);
+                }
+                AuthorField::Hiragana => {
+                    self.hiragana.handle_key(key);
+                }
+                AuthorField::Tags => match key.code {
+                    KeyCode::Left => self.tag_cursor = self.tag_cursor.saturating_sub(1),
+                    KeyCode::Right => self.tag_cursor = (self.tag_cursor + 1).min(QUESTION_TAGS.len() - 1),
+                    KeyCode::Char(' ') => self.tags[self.tag_cursor] = !self.tags[self.tag_cursor],
+                    _ => {}
+                },
+            },
+        }
+        AuthorOutcome::Editing
+    }
+
+    /// 貼り付け（IME で確定した文字列など）を入力中の欄に入れる
+    pub fn handle_paste(&mut self, text: &str) {
+        match self.focus {
+            AuthorField::Japanese => self.japanese.insert_str(text),
+            AuthorField::Hiragana => self.hiragana.insert_str(text),
+            AuthorField::Tags => {}
+        }
+    }
+}
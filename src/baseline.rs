@@ -0,0 +1,159 @@
+// ============================================
+// src/baseline.rs
+// 一般的なタイピストの速さの目安（お題の打鍵数ごとの CPS）と比べる
+// 自分の履歴がまだない人にも、結果画面で今の速さがどのくらいかを示す
+// ============================================
+
+/// 速さの段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkillBand {
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+}
+
+impl SkillBand {
+    const ALL: [SkillBand; 4] = [SkillBand::Beginner, SkillBand::Intermediate, SkillBand::Advanced, SkillBand::Expert];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkillBand::Beginner => "beginner",
+            SkillBand::Intermediate => "intermediate",
+            SkillBand::Advanced => "advanced",
+            SkillBand::Expert => "expert",
+        }
+    }
+}
+
+/// 打鍵数ごとの、中級・上級・達人になる CPS（打鍵数の昇順）
+/// ローマ字入力の一般的な速さの目安。短いお題ほど打ち始めの反応時間が効くので低くしてある
+const BASELINE_TABLE: [(u32, [f64; 3]); 6] = [
+    (3, [1.5, 2.5, 3.5]),
+    (6, [2.0, 3.2, 4.5]),
+    (10, [2.3, 3.7, 5.2]),
+    (20, [2.6, 4.2, 6.0]),
+    (40, [2.8, 4.5, 6.5]),
+    (80, [2.9, 4.7, 6.8]),
+];
+
+/// 打鍵数での各段階の下限の CPS（表の間は直線で補い、表の外は端の値を使う）
+pub fn thresholds(keystrokes: u32) -> [f64; 3] {
+    let after = BASELINE_TABLE.partition_point(|&(len, _)| len < keystrokes);
+    match (after.checked_sub(1).map(|i| BASELINE_TABLE[i]), BASELINE_TABLE.get(after)) {
+        (Some((low_len, low)), Some(&(high_len, high))) => {
+            let t = f64::from(keystrokes - low_len) / f64::from(high_len - low_len);
+            [0, 1, 2].map(|i| low[i] + (high[i] - low[i]) * t)
+        }
+        (None, Some(&(_, first))) => first,
+        (Some((_, last)), None) => last,
+        (None, None) => [0.0; 3],
+    }
+}
+
+/// 一般的なタイピスト（中級の下限の速さ）がその打鍵数のお題を打つのにかかる時間（秒）。打鍵がなければ None
+pub fn expected_secs(keystrokes: u32) -> Option<f64> {
+    let cps = thresholds(keystrokes)[0];
+    (keystrokes > 0 && cps > 0.0).then(|| f64::from(keystrokes) / cps)
+}
+
+/// 1問分の速さが、同じ打鍵数のお題の目安のどの段階にあるか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaselineRank {
+    pub keystrokes: u32,
+    pub band: SkillBand,
+    /// 次の段階と、そこに届く CPS（達人なら None）
+    pub next: Option<(SkillBand, f64)>,
+}
+
+impl BaselineRank {
+    pub fn classify(keystrokes: u32, cps: f64) -> Self {
+        let thresholds = thresholds(keystrokes);
+        let reached = thresholds.iter().filter(|&&threshold| cps >= threshold).count();
+        Self {
+            keystrokes,
+            band: SkillBand::ALL[reached],
+            next: thresholds.get(reached).map(|&threshold| (SkillBand::ALL[reached + 1], threshold)),
+        }
+    }
+
+    /// "advanced pace for 12 keys (expert from 5.41 CPS)"
+    pub fn summary(&self) -> String {
+        let next = self
+            .next
+            .map_or(String::new(), |(band, cps)| format!(" ({} from {:.2} CPS)", band.label(), cps));
+        format!("{} pace for {} keys{}", self.band.label(), self.keystrokes, next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f64; 3], expected: [f64; 3]) {
+        for (a, e) in actual.into_iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn table_rows_are_used_as_they_are() {
+        for (len, row) in BASELINE_TABLE {
+            assert_close(thresholds(len), row);
+        }
+    }
+
+    #[test]
+    fn lengths_between_rows_are_interpolated() {
+        assert_close(thresholds(8), [2.15, 3.45, 4.85]);
+        assert_close(thresholds(4), [1.5 + 0.5 / 3.0, 2.5 + 0.7 / 3.0, 3.5 + 1.0 / 3.0]);
+        assert_close(thresholds(30), [2.7, 4.35, 6.25]);
+    }
+
+    #[test]
+    fn lengths_outside_the_table_use_the_nearest_row() {
+        assert_close(thresholds(0), BASELINE_TABLE[0].1);
+        assert_close(thresholds(1), BASELINE_TABLE[0].1);
+        assert_close(thresholds(500), BASELINE_TABLE[5].1);
+    }
+
+    #[test]
+    fn bands_rise_with_the_skill_and_never_drop_with_the_length() {
+        let mut previous = [0.0; 3];
+        for len in 0..=100 {
+            let row = thresholds(len);
+            assert!(row[0] < row[1] && row[1] < row[2], "{len}: {row:?}");
+            assert!((0..3).all(|i| row[i] >= previous[i]), "{len}: {row:?}");
+            previous = row;
+        }
+    }
+
+    #[test]
+    fn a_run_exactly_at_a_threshold_reaches_that_band() {
+        // 10 打鍵の表は [2.3, 3.7, 5.2]
+        let cases = [
+            (0.0, SkillBand::Beginner, Some((SkillBand::Intermediate, 2.3))),
+            (2.29, SkillBand::Beginner, Some((SkillBand::Intermediate, 2.3))),
+            (2.3, SkillBand::Intermediate, Some((SkillBand::Advanced, 3.7))),
+            (3.7, SkillBand::Advanced, Some((SkillBand::Expert, 5.2))),
+            (5.2, SkillBand::Expert, None),
+            (12.0, SkillBand::Expert, None),
+        ];
+        for (cps, band, next) in cases {
+            assert_eq!(BaselineRank::classify(10, cps), BaselineRank { keystrokes: 10, band, next }, "{cps}");
+        }
+    }
+
+    #[test]
+    fn the_summary_names_the_next_band() {
+        assert_eq!(BaselineRank::classify(10, 4.0).summary(), "advanced pace for 10 keys (expert from 5.20 CPS)");
+        assert_eq!(BaselineRank::classify(10, 6.0).summary(), "expert pace for 10 keys");
+    }
+
+    #[test]
+    fn the_expected_time_uses_the_intermediate_pace() {
+        assert_eq!(expected_secs(0), None);
+        assert_eq!(expected_secs(10), Some(10.0 / 2.3));
+        assert_eq!(expected_secs(80), Some(80.0 / 2.9));
+    }
+}
@@ -0,0 +1,362 @@
+// ============================================
+// src/bests_diff.rs
+// 履歴を書き換えるコマンド（取り込み・再採点・再計算）の前後での、ランキングとお題ごとのベストの差分
+// 書き換えた結果はデータディレクトリのコマンドログに追記して、あとから確かめられるようにする
+// ============================================
+
+use chrono::{DateTime, Local, Utc};
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{Result, Write};
+use std::path::PathBuf;
+
+use crate::locale::format_date;
+use crate::save_data::{TypeRecord, get_data_dir};
+
+/// 比べるランキングの件数
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// ランキングに載った記録（時刻と読みで見分ける。再採点しても変わらない）
+#[derive(Debug, Clone)]
+pub struct RankedRecord {
+    pub timestamp: DateTime<Utc>,
+    pub hiragana: String,
+    pub japanese: String,
+    pub score: f64,
+}
+
+impl RankedRecord {
+    fn from_record(record: &TypeRecord) -> Self {
+        Self {
+            timestamp: record.timestamp,
+            hiragana: record.question_hiragana.clone(),
+            japanese: record.question_japanese.clone(),
+            score: record.score,
+        }
+    }
+
+    fn same_record(&self, other: &RankedRecord) -> bool {
+        self.timestamp == other.timestamp && self.hiragana == other.hiragana
+    }
+
+    /// "1234  May 1, 2024 | 漢字"
+    fn label(&self) -> String {
+        format!(
+            "{:>8.0}  {} | {}",
+            self.score,
+            format_date(self.timestamp.with_timezone(&Local).date_naive()),
+            self.japanese
+        )
+    }
+}
+
+/// ランキングの順位の変化（順位は 1 始まり）
+#[derive(Debug, Clone)]
+pub enum RankChange {
+    Entered { rank: usize, record: RankedRecord },
+    Left { rank: usize, record: RankedRecord },
+    Moved { from: usize, to: usize, record: RankedRecord },
+}
+
+/// お題ごとのベストの変化
+#[derive(Debug, Clone)]
+pub struct QuestionBestChange {
+    pub japanese: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    /// 前のベストの記録がベストでなくなった（別の記録に入れ替わったか、なくなった）
+    pub displaced: bool,
+}
+
+/// 履歴を書き換える前と後のベストの差分
+#[derive(Debug, Clone, Default)]
+pub struct BestsDiff {
+    pub leaderboard: Vec<RankChange>,
+    pub question_bests: Vec<QuestionBestChange>,
+}
+
+impl BestsDiff {
+    /// 書き換える前と後の履歴を比べる
+    /// ベストの対象はプレイ中と同じ（ウォームアップと速さを制限した記録は除き、取り込んだ記録は `include_imported` のときだけ）
+    /// 正確率が `accuracy_floor` (%) に届かない記録も除く
+    pub fn between(before: &[TypeRecord], after: &[TypeRecord], include_imported: bool, accuracy_floor: f64) -> Self {
+        let eligible = |record: &&TypeRecord| {
+            (record.counts_for_bests() || (include_imported && record.imported)) && record.meets_accuracy_floor(accuracy_floor)
+        };
+        let before: Vec<&TypeRecord> = before.iter().filter(eligible).collect();
+        let after: Vec<&TypeRecord> = after.iter().filter(eligible).collect();
+        Self {
+            leaderboard: leaderboard_changes(&leaderboard(&before), &leaderboard(&after)),
+            question_bests: question_best_changes(question_bests(&before), question_bests(&after)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaderboard.is_empty() && self.question_bests.is_empty()
+    }
+
+    /// ベストだった記録がベストでなくなるか（ランキングから外れるか、お題のベストが入れ替わる）
+    pub fn displaces_best(&self) -> bool {
+        self.leaderboard.iter().any(|change| matches!(change, RankChange::Left { .. }))
+            || self.question_bests.iter().any(|change| change.displaced)
+    }
+
+    /// 画面とコマンドログに出す行
+    /// お題ごとのベストは、入れ替わったものだけを1行ずつ出し、スコアが変わっただけのものは数だけ出す
+    pub fn lines(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec!["Bests: no changes".to_string()];
+        }
+        let mut lines = Vec::new();
+        if self.leaderboard.is_empty() {
+            lines.push(format!("Top {}: no changes", LEADERBOARD_SIZE));
+        } else {
+            lines.push(format!("Top {}:", LEADERBOARD_SIZE));
+        }
+        for change in &self.leaderboard {
+            lines.push(match change {
+                RankChange::Entered { rank, record } => format!("  + {:>2}.      {}", rank, record.label()),
+                RankChange::Left { rank, record } => format!("  - {:>2}.      {}", rank, record.label()),
+                RankChange::Moved { from, to, record } => format!("    {:>2}. → {:>2}. {}", from, to, record.label()),
+            });
+        }
+
+        let displaced = self.question_bests.iter().filter(|change| change.displaced).count();
+        lines.push(format!(
+            "Question bests: {} changed, {} replaced",
+            self.question_bests.len(),
+            displaced
+        ));
+        let score = |score: Option<f64>| score.map_or("none".to_string(), |score| format!("{:.0}", score));
+        for change in self.question_bests.iter().filter(|change| change.displaced) {
+            lines.push(format!("  ! {}: {} → {}", change.japanese, score(change.before), score(change.after)));
+        }
+        lines
+    }
+}
+
+/// スコアの高い順（同じスコアなら古い記録が上）の上位
+fn leaderboard(records: &[&TypeRecord]) -> Vec<RankedRecord> {
+    let mut sorted: Vec<&TypeRecord> = records.to_vec();
+    sorted.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.timestamp.cmp(&b.timestamp)));
+    sorted.into_iter().take(LEADERBOARD_SIZE).map(RankedRecord::from_record).collect()
+}
+
+/// 外れた記録（前の順位の順）、入った記録と順位が変わった記録（後の順位の順）
+fn leaderboard_changes(before: &[RankedRecord], after: &[RankedRecord]) -> Vec<RankChange> {
+    let mut changes: Vec<RankChange> = before
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| !after.iter().any(|other| other.same_record(record)))
+        .map(|(idx, record)| RankChange::Left { rank: idx + 1, record: record.clone() })
+        .collect();
+    for (idx, record) in after.iter().enumerate() {
+        match before.iter().position(|other| other.same_record(record)) {
+            None => changes.push(RankChange::Entered { rank: idx + 1, record: record.clone() }),
+            Some(from) if from != idx => {
+                changes.push(RankChange::Moved { from: from + 1, to: idx + 1, record: record.clone() })
+            }
+            Some(_) => {}
+        }
+    }
+    changes
+}
+
+/// 読みごとのベストの記録（同じスコアなら古い記録）
+fn question_bests(records: &[&TypeRecord]) -> BTreeMap<String, RankedRecord> {
+    let mut bests: BTreeMap<String, RankedRecord> = BTreeMap::new();
+    for record in records {
+        match bests.get(&record.question_hiragana) {
+            Some(best) if best.score >= record.score => {}
+            _ => {
+                bests.insert(record.question_hiragana.clone(), RankedRecord::from_record(record));
+            }
+        }
+    }
+    bests
+}
+
+/// ベストの記録かスコアが変わったお題（読みの順）
+fn question_best_changes(
+    mut before: BTreeMap<String, RankedRecord>,
+    after: BTreeMap<String, RankedRecord>,
+) -> Vec<QuestionBestChange> {
+    let mut changes = Vec::new();
+    for (hiragana, new_best) in &after {
+        let old_best = before.remove(hiragana);
+        if old_best.as_ref().is_some_and(|old| old.same_record(new_best) && old.score == new_best.score) {
+            continue;
+        }
+        changes.push(QuestionBestChange {
+            japanese: new_best.japanese.clone(),
+            before: old_best.as_ref().map(|old| old.score),
+            after: Some(new_best.score),
+            displaced: old_best.is_some_and(|old| !old.same_record(new_best)),
+        });
+    }
+    // 後の履歴にベストの対象となる記録がなくなったお題
+    changes.extend(before.into_values().map(|old| QuestionBestChange {
+        japanese: old.japanese,
+        before: Some(old.score),
+        after: None,
+        displaced: true,
+    }));
+    changes
+}
+
+// --------------------------------------------------
+// MARK:コマンドログ
+// --------------------------------------------------
+
+// MARK:コマンドログのパスを取得する関数
+pub fn get_command_log_path() -> PathBuf {
+    get_data_dir().join("command-log.txt")
+}
+
+/// 履歴を書き換えたコマンドの結果をコマンドログの末尾に追記する
+pub fn append_command_log(command: &str, lines: &[String]) -> Result<PathBuf> {
+    let path = get_command_log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "== {} {} ==", Local::now().format("%Y-%m-%d %H:%M:%S"), command)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    writeln!(file)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// `day` 日目の正午に打った、スコア `score` の記録
+    fn record(hiragana: &str, day: u32, score: f64) -> TypeRecord {
+        let mut record = TypeRecord::sample(hiragana, 4, 1.0, 0);
+        record.question_japanese = format!("{}（漢字）", hiragana);
+        record.timestamp = Local.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap().with_timezone(&Utc);
+        record.score = score;
+        record
+    }
+
+    /// 読みの違う 12 件（1日目が 100 点、12日目が 1200 点）
+    fn history() -> Vec<TypeRecord> {
+        (1..=12).map(|day| record(&format!("q{}", day), day, f64::from(day) * 100.0)).collect()
+    }
+
+    fn ranks(diff: &BestsDiff) -> Vec<String> {
+        diff.leaderboard
+            .iter()
+            .map(|change| match change {
+                RankChange::Entered { rank, record } => format!("+{} {}", rank, record.hiragana),
+                RankChange::Left { rank, record } => format!("-{} {}", rank, record.hiragana),
+                RankChange::Moved { from, to, record } => format!("{}>{} {}", from, to, record.hiragana),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_same_history_has_no_changes() {
+        let diff = BestsDiff::between(&history(), &history(), false, 0.0);
+        assert!(diff.is_empty() && !diff.displaces_best());
+        assert_eq!(diff.lines(), ["Bests: no changes"]);
+    }
+
+    #[test]
+    fn a_better_import_enters_and_pushes_the_tenth_out() {
+        let before = history();
+        let mut after = before.clone();
+        after.push(record("q5", 20, 5_000.0));
+        let diff = BestsDiff::between(&before, &after, true, 0.0);
+        // 上位 10 件は 12..3 日目。3日目が外れ、ほかは1つずつ下がる
+        let mut expected = vec!["-10 q3".to_string(), "+1 q5".to_string()];
+        expected.extend((4..=12).rev().enumerate().map(|(idx, day)| format!("{}>{} q{}", idx + 1, idx + 2, day)));
+        assert_eq!(ranks(&diff), expected);
+        assert!(diff.displaces_best());
+
+        let replaced: Vec<_> =
+            diff.question_bests.iter().map(|c| (c.japanese.as_str(), c.before, c.after, c.displaced)).collect();
+        assert_eq!(replaced, [("q5（漢字）", Some(500.0), Some(5_000.0), true)]);
+    }
+
+    #[test]
+    fn imported_warmup_and_inaccurate_records_are_not_bests() {
+        let before = history();
+        let mut imported = record("q1", 20, 9_000.0);
+        imported.imported = true;
+        let mut warmup = record("q2", 21, 9_000.0);
+        warmup.warmup = true;
+        let mut sloppy = record("q3", 22, 9_000.0);
+        sloppy.misses = 4;
+        let mut after = before.clone();
+        after.extend([imported, warmup, sloppy]);
+
+        assert!(BestsDiff::between(&before, &after, false, 90.0).is_empty());
+        // 取り込んだ記録を含める設定なら、取り込んだ記録だけは入る
+        let diff = BestsDiff::between(&before, &after, true, 90.0);
+        assert!(ranks(&diff).contains(&"+1 q1".to_string()));
+        assert_eq!(diff.question_bests.len(), 1);
+    }
+
+    #[test]
+    fn a_rescored_best_changes_the_score_without_replacing_it() {
+        let before = history();
+        let mut after = before.clone();
+        // 12日目の記録が再採点で 2位 に下がる
+        after[11].score = 1_050.0;
+        let diff = BestsDiff::between(&before, &after, false, 0.0);
+        assert_eq!(ranks(&diff), ["2>1 q11", "1>2 q12"]);
+        assert!(!diff.displaces_best());
+        assert_eq!(
+            diff.lines()[1..],
+            [
+                "     2. →  1.     1100  May 11, 2024 | q11（漢字）".to_string(),
+                "     1. →  2.     1050  May 12, 2024 | q12（漢字）".to_string(),
+                "Question bests: 1 changed, 0 replaced".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_question_whose_records_all_disappear_loses_its_best() {
+        let before = history();
+        let after: Vec<TypeRecord> = before.iter().filter(|r| r.question_hiragana != "q1").cloned().collect();
+        let diff = BestsDiff::between(&before, &after, false, 0.0);
+        // q1 はランキングの外なので、変わるのはお題のベストだけ
+        assert!(diff.leaderboard.is_empty());
+        assert!(diff.displaces_best());
+        assert_eq!(
+            diff.lines(),
+            ["Top 10: no changes", "Question bests: 1 changed, 1 replaced", "  ! q1（漢字）: 100 → none"]
+        );
+    }
+
+    #[test]
+    fn ties_keep_the_older_record_as_the_best() {
+        let before = vec![record("ねこ", 1, 500.0)];
+        let mut after = before.clone();
+        after.push(record("ねこ", 2, 500.0));
+        let diff = BestsDiff::between(&before, &after, false, 0.0);
+        assert_eq!(ranks(&diff), ["+2 ねこ"]);
+        assert!(diff.question_bests.is_empty() && !diff.displaces_best());
+    }
+
+    #[test]
+    fn records_exactly_at_the_floor_enter_the_leaderboard() {
+        let before = history();
+        // 20 打鍵中 1 ミスは正確率 95%
+        let mut at_floor = record("q13", 13, 9_000.0);
+        at_floor.keystrokes = 20;
+        at_floor.misses = 1;
+        let mut after = before.clone();
+        after.push(at_floor);
+
+        let diff = BestsDiff::between(&before, &after, false, 95.0);
+        assert!(ranks(&diff).contains(&"+1 q13".to_string()), "{:?}", ranks(&diff));
+        assert!(BestsDiff::between(&before, &after, false, 95.5).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+// ============================================
+// src/break_timer.rs
+// 長く打ち続けたときに休憩を勧めるための、続けて打った時間の計測
+// 数えるのはお題の入力中の時間だけ（メニューや結果の画面にいた時間は含まない）
+// 打ち終えてから次のお題を打ち終えるまでの間が `NATURAL_BREAK_GAP` を超えたら、休憩したとみなして数え直す
+// ============================================
+
+use bincode::{Decode, Encode};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+/// 休憩を勧める画面を閉じられるようになるまでの時間
+pub const BREAK_LOCK: Duration = Duration::from_secs(10);
+/// お題とお題の間がこれ以上空いたら、休憩したとみなす
+const NATURAL_BREAK_GAP: Duration = Duration::from_secs(5 * 60);
+
+/// 休憩を取った記録（週報で続けて打った時間の平均を出すのに使う）
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct BreakEvent {
+    /// 休憩した時刻（UNIX 秒）
+    pub taken_at: i64,
+    /// 休憩までに続けて打った時間（秒）
+    pub stretch_secs: u32,
+    /// 休憩を勧める画面から取った休憩なら true（お題の間が空いただけなら false）
+    pub reminded: bool,
+}
+
+impl BreakEvent {
+    fn new(stretch: Duration, reminded: bool) -> Self {
+        Self {
+            taken_at: Utc::now().timestamp(),
+            stretch_secs: stretch.as_secs().min(u64::from(u32::MAX)) as u32,
+            reminded,
+        }
+    }
+
+    pub fn taken_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.taken_at, 0).single().unwrap_or_default()
+    }
+}
+
+/// 続けて打った時間
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BreakTimer {
+    /// 前の休憩から打った時間の合計
+    stretch: Duration,
+    /// 最後にお題を打ち終えた時刻
+    last_active: Option<Instant>,
+}
+
+impl BreakTimer {
+    /// お題を打ち終えたときに、そのお題を打っていた時間を加える
+    /// 前のお題との間が空いていて休憩したとみなしたら、その休憩の記録を返す
+    pub fn add(&mut self, now: Instant, active: Duration) -> Option<BreakEvent> {
+        let gap = self
+            .last_active
+            .map(|last| now.saturating_duration_since(last).saturating_sub(active));
+        self.last_active = Some(now);
+        let rested = gap.is_some_and(|gap| gap >= NATURAL_BREAK_GAP) && self.stretch > Duration::ZERO;
+        let event = rested.then(|| BreakEvent::new(self.stretch, false));
+        if rested {
+            self.stretch = Duration::ZERO;
+        }
+        self.stretch += active;
+        event
+    }
+
+    /// 続けて打った時間が `limit` に届いたか
+    pub fn is_due(&self, limit: Duration) -> bool {
+        self.stretch >= limit
+    }
+
+    /// 休憩を勧める画面から休憩を取った。記録を返して数え直す
+    pub fn take_break(&mut self) -> BreakEvent {
+        let event = BreakEvent::new(self.stretch, true);
+        self.stretch = Duration::ZERO;
+        event
+    }
+}
+
+/// 続けて打った時間の平均（分）と休憩の回数
+pub fn average_stretch_mins<'a>(events: impl Iterator<Item = &'a BreakEvent>) -> Option<(f64, usize)> {
+    let (total, count) = events.fold((0u64, 0usize), |(total, count), event| {
+        (total + u64::from(event.stretch_secs), count + 1)
+    });
+    (count > 0).then(|| (total as f64 / 60.0 / count as f64, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn only_the_typing_time_adds_up() {
+        let mut timer = BreakTimer::default();
+        let start = Instant::now();
+        assert!(timer.add(start + secs(30), secs(30)).is_none());
+        // 結果の画面にいた 60 秒は数えない
+        assert!(timer.add(start + secs(120), secs(30)).is_none());
+        assert!(!timer.is_due(secs(61)));
+        assert!(timer.is_due(secs(60)));
+    }
+
+    #[test]
+    fn a_long_gap_between_questions_is_a_natural_break() {
+        let mut timer = BreakTimer::default();
+        let start = Instant::now();
+        timer.add(start, secs(40));
+        // 間が休憩の長さにわずかに足りなければ続けて数える
+        let just_short = start + NATURAL_BREAK_GAP - secs(1) + secs(20);
+        assert!(timer.add(just_short, secs(20)).is_none());
+        assert!(timer.is_due(secs(60)));
+
+        let event = timer.add(just_short + NATURAL_BREAK_GAP + secs(10), secs(10)).unwrap();
+        assert_eq!(event.stretch_secs, 60);
+        assert!(!event.reminded);
+        assert!(timer.is_due(secs(10)) && !timer.is_due(secs(11)));
+    }
+
+    #[test]
+    fn a_reminded_break_starts_the_count_over() {
+        let mut timer = BreakTimer::default();
+        timer.add(Instant::now(), secs(90));
+        let event = timer.take_break();
+        assert_eq!(event.stretch_secs, 90);
+        assert!(event.reminded);
+        assert!(!timer.is_due(secs(1)));
+        assert!((Utc::now() - event.taken_at()).num_seconds() < 5);
+    }
+
+    #[test]
+    fn the_average_stretch_is_in_minutes() {
+        let event = |stretch_secs| BreakEvent { taken_at: 0, stretch_secs, reminded: true };
+        assert_eq!(average_stretch_mins([].iter()), None);
+        assert_eq!(average_stretch_mins([event(600), event(1200)].iter()), Some((15.0, 2)));
+    }
+}
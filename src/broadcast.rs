@@ -0,0 +1,344 @@
+// ============================================
+// src/broadcast.rs
+// 打っている画面を別の PC（プロジェクターなど）に映すための配信
+// タイピング画面の状態を1行1つの JSON にして、TCP でつないできた観戦側に数回 / 秒送る
+// 送るのは別スレッドで行い、入力のループは待たせない（送りきれないコマは捨てる）
+// ============================================
+
+use serde::{Deserialize, Serialize};
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 状態を送る間隔（4回 / 秒）
+pub const BROADCAST_INTERVAL: Duration = Duration::from_millis(250);
+/// 送信スレッドに渡して待たせておけるコマの数（これを超えたら新しいコマを捨てる）
+const FRAME_QUEUE: usize = 2;
+/// 送信スレッドが新しい観戦側を確かめる間隔
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// 観戦側がつながらなかった / 切れたときに、つなぎ直すまでの時間（つなぐときに待つ長さも同じ）
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 観戦側に送る1コマ分の状態
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpectatorFrame {
+    pub japanese: String,
+    pub hiragana: String,
+    /// 打ち終えたローマ字
+    pub typed: String,
+    /// これから打つローマ字
+    pub remaining: String,
+    /// 打っている途中の CPS（打ち始める前は None）
+    pub cps: Option<f64>,
+    /// このお題のミスの数
+    pub misses: u32,
+    /// このセッションで打ち終えたお題の数
+    pub questions: u32,
+    pub level: u32,
+}
+
+/// 配信側。作るとポートで待ち受け、落とすと観戦側との接続を閉じてスレッドを止める
+pub struct Broadcaster {
+    addr: SocketAddr,
+    /// 落とすと送信スレッドが止まる
+    frames: Option<SyncSender<String>>,
+    worker: Option<JoinHandle<()>>,
+    last_sent: Option<Instant>,
+}
+
+impl Broadcaster {
+    /// `host` の `port` で待ち受ける（0 なら空いているポート）
+    pub fn start(host: IpAddr, port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let (frames, receiver) = mpsc::sync_channel(FRAME_QUEUE);
+        let worker = thread::spawn(move || serve(listener, receiver));
+        Ok(Self { addr, frames: Some(frames), worker: Some(worker), last_sent: None })
+    }
+
+    /// 待ち受けているアドレス
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 前に送ってから `BROADCAST_INTERVAL` 経っていれば、状態を送信スレッドに渡す
+    /// 送信スレッドが詰まっていればそのコマは捨てる（入力のループは待たない）
+    pub fn send_if_due(&mut self, now: Instant, frame: impl FnOnce() -> SpectatorFrame) {
+        if self.last_sent.is_some_and(|last| now.saturating_duration_since(last) < BROADCAST_INTERVAL) {
+            return;
+        }
+        self.last_sent = Some(now);
+        let Some(frames) = &self.frames else {
+            return;
+        };
+        if let Ok(line) = serde_json::to_string(&frame()) {
+            let _ = frames.try_send(line);
+        }
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        // 送る側を閉じると送信スレッドのループが終わる
+        self.frames = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// 送信スレッド。新しい観戦側を受け入れ、渡されたコマのうち一番新しいものを全員に送る
+fn serve(listener: TcpListener, frames: Receiver<String>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+    loop {
+        // 待っている観戦側がいなければ WouldBlock になって抜ける
+        while let Ok((stream, _)) = listener.accept() {
+            // 観戦側が読まなくなっても、このスレッドが止まったままにならないようにする
+            // 待ち受けのソケットのノンブロッキングを引き継ぐ OS があるので、送る側は待つ設定に戻す
+            let ready = stream.set_nonblocking(false).is_ok()
+                && stream.set_nodelay(true).is_ok()
+                && stream.set_write_timeout(Some(BROADCAST_INTERVAL)).is_ok();
+            if ready {
+                clients.push(stream);
+            }
+        }
+
+        let mut line = match frames.recv_timeout(ACCEPT_INTERVAL) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // 待っている間にたまった古いコマは捨てる
+        while let Ok(newer) = frames.try_recv() {
+            line = newer;
+        }
+        line.push('\n');
+        clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+    }
+    for client in clients {
+        let _ = client.shutdown(Shutdown::Both);
+    }
+}
+
+// --------------------------------------------------
+// MARK:観戦側
+// --------------------------------------------------
+
+/// 観戦側が受け取ったもの
+pub enum FeedUpdate {
+    /// 届いている中で一番新しいコマ
+    Frame(SpectatorFrame),
+    /// 前に見てから新しいコマは届いていない
+    Nothing,
+    /// 配信側との接続が切れた
+    Closed,
+}
+
+/// 観戦側の受信。受け取ったコマは別スレッドで読んでここに渡す
+pub struct SpectatorFeed {
+    frames: Receiver<SpectatorFrame>,
+}
+
+impl SpectatorFeed {
+    /// 配信側につなぐ（`RECONNECT_INTERVAL` 以上は待たない）
+    pub fn connect(addr: &str) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "the address did not resolve"))?;
+        let stream = TcpStream::connect_timeout(&addr, RECONNECT_INTERVAL)?;
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // 読めない行（新しいバージョンの配信など）は飛ばす
+                let Ok(frame) = serde_json::from_str::<SpectatorFrame>(&line) else {
+                    continue;
+                };
+                if sender.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { frames })
+    }
+
+    /// 前に見てから届いたコマのうち一番新しいもの（古いコマは捨てる）
+    pub fn poll(&self) -> FeedUpdate {
+        let mut latest = None;
+        loop {
+            match self.frames.try_recv() {
+                Ok(frame) => latest = Some(frame),
+                Err(TryRecvError::Empty) => break,
+                // 切れる前に届いたコマがあれば先に見せる（次に呼んだときに Closed になる）
+                Err(TryRecvError::Disconnected) if latest.is_none() => return FeedUpdate::Closed,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        latest.map_or(FeedUpdate::Nothing, FeedUpdate::Frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// ループバックのテストで待つ上限
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    fn frame(typed: &str, remaining: &str) -> SpectatorFrame {
+        SpectatorFrame {
+            japanese: "猫".to_string(),
+            hiragana: "ねこ".to_string(),
+            typed: typed.to_string(),
+            remaining: remaining.to_string(),
+            cps: Some(4.5),
+            misses: 1,
+            questions: 3,
+            level: 2,
+        }
+    }
+
+    fn loopback() -> Broadcaster {
+        Broadcaster::start(IpAddr::V4(Ipv4Addr::LOCALHOST), 0).unwrap()
+    }
+
+    /// 観戦側が何か受け取るまで、間隔を空けてコマを送り続ける
+    /// 送信スレッドが観戦側を受け入れる前のコマは誰にも届かないので、1回送るだけでは足りない
+    fn broadcast_until_received(
+        broadcaster: &mut Broadcaster,
+        feed: &SpectatorFeed,
+        sent: &SpectatorFrame,
+    ) -> SpectatorFrame {
+        let start = Instant::now();
+        let mut now = start;
+        while start.elapsed() < PATIENCE {
+            broadcaster.send_if_due(now, || sent.clone());
+            now += BROADCAST_INTERVAL;
+            thread::sleep(Duration::from_millis(20));
+            match feed.poll() {
+                FeedUpdate::Frame(received) => return received,
+                FeedUpdate::Nothing => {}
+                FeedUpdate::Closed => panic!("the broadcaster closed the connection"),
+            }
+        }
+        panic!("no frame arrived within {PATIENCE:?}");
+    }
+
+    fn wait_for_close(feed: &SpectatorFeed) -> Vec<SpectatorFrame> {
+        let start = Instant::now();
+        let mut frames = Vec::new();
+        while start.elapsed() < PATIENCE {
+            match feed.poll() {
+                FeedUpdate::Frame(received) => frames.push(received),
+                FeedUpdate::Nothing => thread::sleep(Duration::from_millis(10)),
+                FeedUpdate::Closed => return frames,
+            }
+        }
+        panic!("the connection was not closed within {PATIENCE:?}");
+    }
+
+    #[test]
+    fn a_spectator_receives_frames_over_loopback() {
+        let mut broadcaster = loopback();
+        assert!(broadcaster.addr().ip().is_loopback());
+        let feed = SpectatorFeed::connect(&broadcaster.addr().to_string()).unwrap();
+
+        let sent = frame("ne", "ko");
+        let received = broadcast_until_received(&mut broadcaster, &feed, &sent);
+        assert_eq!(
+            (received.japanese, received.hiragana, received.typed, received.remaining),
+            (sent.japanese, sent.hiragana, sent.typed, sent.remaining)
+        );
+        assert_eq!((received.cps, received.misses, received.questions, received.level), (Some(4.5), 1, 3, 2));
+    }
+
+    #[test]
+    fn dropping_the_broadcaster_closes_the_spectators_connection() {
+        let mut broadcaster = loopback();
+        let feed = SpectatorFeed::connect(&broadcaster.addr().to_string()).unwrap();
+        broadcast_until_received(&mut broadcaster, &feed, &frame("", "neko"));
+
+        let addr = broadcaster.addr();
+        drop(broadcaster);
+        wait_for_close(&feed);
+        // 待ち受けのソケットも閉じている
+        assert!(TcpStream::connect_timeout(&addr, RECONNECT_INTERVAL).is_err());
+    }
+
+    #[test]
+    fn frames_are_throttled_to_the_broadcast_interval() {
+        let mut broadcaster = loopback();
+        let start = Instant::now();
+        let mut built = 0;
+        for elapsed in [0, 100, 249, 250, 300, 499, 500] {
+            broadcaster.send_if_due(start + Duration::from_millis(elapsed), || {
+                built += 1;
+                frame("", "neko")
+            });
+        }
+        assert_eq!(built, 3, "frames are built at 0, 250 and 500 ms only");
+    }
+
+    #[test]
+    fn a_spectator_that_never_reads_does_not_block_the_sender() {
+        let mut broadcaster = loopback();
+        // 受け取るだけで一度も読まない観戦側
+        let _stalled = TcpStream::connect(broadcaster.addr()).unwrap();
+        thread::sleep(ACCEPT_INTERVAL * 2);
+
+        // 受け取る側のバッファがすぐいっぱいになるよう大きなコマを送る
+        // 送信スレッドが書き込みで待っていても、1回の送信はその待ち時間よりずっと短く終わる
+        let long = frame(&"a".repeat(64 * 1024), "");
+        let mut now = Instant::now();
+        let mut slowest = Duration::ZERO;
+        for _ in 0..100 {
+            let start = Instant::now();
+            broadcaster.send_if_due(now, || long.clone());
+            slowest = slowest.max(start.elapsed());
+            now += BROADCAST_INTERVAL;
+        }
+        assert!(slowest < BROADCAST_INTERVAL / 2, "the slowest send took {slowest:?}");
+
+        // 落とすときも送信スレッドは書き込みの待ち時間で抜けてくる
+        let start = Instant::now();
+        drop(broadcaster);
+        assert!(start.elapsed() < PATIENCE, "shutting down took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn the_spectator_keeps_the_newest_frame_and_skips_unreadable_lines() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let feed = SpectatorFeed::connect(&listener.local_addr().unwrap().to_string()).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        for (typed, remaining) in [("", "neko"), ("n", "eko"), ("ne", "ko")] {
+            writeln!(stream, "{}", serde_json::to_string(&frame(typed, remaining)).unwrap()).unwrap();
+        }
+        writeln!(stream, "{{\"version\":2}}").unwrap();
+        writeln!(stream, "not json").unwrap();
+        // 閉じるまでの行を読み終えたら、最後に読めたコマと Closed が順に見える
+        stream.shutdown(Shutdown::Both).unwrap();
+        drop(stream);
+
+        let frames = wait_for_close(&feed);
+        let last = frames.last().expect("the frames before the close are shown first");
+        assert_eq!((last.typed.as_str(), last.remaining.as_str()), ("ne", "ko"));
+        assert!(matches!(feed.poll(), FeedUpdate::Closed));
+    }
+
+    #[test]
+    fn connecting_to_a_closed_port_fails_without_hanging() {
+        let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap();
+        let start = Instant::now();
+        assert!(SpectatorFeed::connect(&addr.to_string()).is_err());
+        assert!(start.elapsed() <= RECONNECT_INTERVAL * 2);
+        assert!(SpectatorFeed::connect("not an address").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+// ============================================
+// src/clipboard.rs
+// 記録の1行の要約をクリップボードにコピーする
+// OS のクリップボード（`clipboard` 機能で有効）→ 端末への OSC 52 の順に試す
+// SSH 越しでは OS のクリップボードはリモート側のものになるので、最初から OSC 52 を使う
+// ============================================
+
+use std::env;
+use std::io::{IsTerminal, Write, stdout};
+
+use crate::locale::format_count;
+use crate::save_data::TypeRecord;
+
+/// Base64 の文字
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// コピーできた方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// OS のクリップボード
+    Native,
+    /// 端末の OSC 52（端末が対応していなければ何も起きないが、確かめる手段はない）
+    Terminal,
+}
+
+impl CopyMethod {
+    /// コピーしたときに出すお知らせ
+    pub fn toast(self) -> &'static str {
+        match self {
+            Self::Native => "Copied!",
+            Self::Terminal => "Copied! (via the terminal)",
+        }
+    }
+}
+
+/// コピーできなかったときのお知らせ
+pub const COPY_UNSUPPORTED: &str = "Copy unsupported in this terminal";
+
+/// 記録を共有する1行（"TYPE WiZ — 新幹線 — 4.2 CPS, 98% acc, score 5,120"）
+pub fn share_line(record: &TypeRecord) -> String {
+    let time_up = if record.timed_out() { " (time up)" } else { "" };
+    format!(
+        "TYPE WiZ — {} — {:.1} CPS, {:.0}% acc, score {}{}",
+        record.question_japanese,
+        record.cps,
+        record.accuracy(),
+        format_count(record.score.max(0.0).round() as u64),
+        time_up
+    )
+}
+
+/// 文字列をクリップボードにコピーする（どの方法も使えなければ None）
+pub fn copy_text(text: &str) -> Option<CopyMethod> {
+    let over_ssh = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+    if !over_ssh && copy_native(text) {
+        return Some(CopyMethod::Native);
+    }
+    copy_osc52(text).then_some(CopyMethod::Terminal)
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_native(text: &str) -> bool {
+    arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)).is_ok()
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_native(_text: &str) -> bool {
+    false
+}
+
+/// 端末に OSC 52 を送る（出力が端末でなければ送らない）
+fn copy_osc52(text: &str) -> bool {
+    let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+    if dumb || !stdout().is_terminal() {
+        return false;
+    }
+    let mut out = stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes())).is_ok() && out.flush().is_ok()
+}
+
+/// Base64（パディング付き）
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_data::QuestionOutcome;
+
+    #[test]
+    fn base64_matches_the_rfc_vectors() {
+        let cases = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        for (plain, encoded) in cases {
+            assert_eq!(base64(plain.as_bytes()), encoded, "{:?}", plain);
+        }
+        assert_eq!(base64("ねこ".as_bytes()), "44Gt44GT");
+    }
+
+    #[test]
+    fn the_share_line_names_the_question_and_rounds_the_numbers() {
+        let mut record = TypeRecord::sample("しんかんせん", 12, 2.5, 0);
+        record.question_japanese = "新幹線".to_string();
+        record.score = 5119.6;
+        assert_eq!(share_line(&record), "TYPE WiZ — 新幹線 — 4.8 CPS, 100% acc, score 5,120");
+
+        record.outcome = QuestionOutcome::TimedOut;
+        record.score = 0.0;
+        assert!(share_line(&record).ends_with("score 0 (time up)"));
+    }
+}
@@ -0,0 +1,229 @@
+// ============================================
+// src/code_drill.rs
+// 記号と数字の多いコード片を、書いてあるとおりの文字で打つ練習（プログラマー向け）
+// かなの入力とは別に、大文字・小文字や記号も1文字ずつそのまま照合する。タブは Tab キー、改行は Enter キーで打つ
+// ============================================
+
+use rand::seq::SliceRandom;
+
+use std::time::{Duration, Instant};
+
+use crate::confusion::Miss;
+
+/// 画面でタブが占める幅（「→」と空白で埋める）
+pub const TAB_WIDTH: usize = 4;
+
+/// コード片1つ
+#[derive(Debug, Clone, Copy)]
+pub struct CodeSnippet {
+    /// 一覧や記録に出す名前
+    pub title: &'static str,
+    /// 打つ文字（タブと改行を含んでよい）
+    pub text: &'static str,
+}
+
+/// 組み込みのコード片（Rust / JSON / シェルの短い断片）
+pub const CODE_SNIPPETS: &[CodeSnippet] = &[
+    CodeSnippet { title: "Rust: vec! macro", text: "let v: Vec<u32> = vec![1, 2, 3];" },
+    CodeSnippet { title: "Rust: match arm", text: "Some(x) if x > 0 => x * 2," },
+    CodeSnippet { title: "Rust: closure", text: "items.iter().map(|&(a, b)| a + b).sum::<i64>()" },
+    CodeSnippet { title: "Rust: fn signature", text: "fn get<'a>(map: &'a HashMap<String, u8>) -> Option<&'a u8> {" },
+    CodeSnippet { title: "Rust: format", text: "println!(\"{:>8.2} {}\", x[0], y_max);" },
+    CodeSnippet { title: "Rust: if let", text: "if let Err(e) = run() {\n    eprintln!(\"{e}\");\n}" },
+    CodeSnippet { title: "JSON: object", text: "{\"id\": 42, \"tags\": [\"a_b\", \"c-d\"], \"ok\": true}" },
+    CodeSnippet { title: "JSON: nested", text: "{\n\t\"port\": 8080,\n\t\"env\": {\"DEBUG\": \"1\"}\n}" },
+    CodeSnippet { title: "Shell: pipe", text: "grep -rn \"TODO\" src/ | wc -l" },
+    CodeSnippet { title: "Shell: loop", text: "for f in *.log; do gzip -9 \"$f\"; done" },
+    CodeSnippet { title: "Shell: test", text: "[ -z \"${HOME}\" ] && echo $((1 + 2)) || exit 1" },
+    CodeSnippet { title: "Makefile: rule", text: "build: main.o\n\tcc -o app main.o -lm" },
+];
+
+/// 端末から打てる文字だけでできているか確かめる（制御文字はタブと改行だけ許す）
+fn validate_snippet(text: &str) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("the snippet is empty".to_string());
+    }
+    match text.chars().find(|c| c.is_control() && !matches!(c, '\t' | '\n')) {
+        Some(c) => Err(format!("contains a control character the terminal cannot send ({:?})", c)),
+        None => Ok(()),
+    }
+}
+
+/// 出題するコード片の番号（打てない文字を含むものを除き、順番を混ぜる）と、除いたコード片の説明
+pub fn snippet_pool() -> (Vec<usize>, Vec<String>) {
+    let mut pool = Vec::new();
+    let mut rejected = Vec::new();
+    for (idx, snippet) in CODE_SNIPPETS.iter().enumerate() {
+        match validate_snippet(snippet.text) {
+            Ok(()) => pool.push(idx),
+            Err(e) => rejected.push(format!("Code snippet \"{}\" skipped: {}", snippet.title, e)),
+        }
+    }
+    pool.shuffle(&mut rand::rng());
+    (pool, rejected)
+}
+
+/// 画面に出す1文字分の見た目（タブは「→」、改行は「↵」、まだ打っていない空白は「·」）
+/// 返す文字列の幅は、タブが `TAB_WIDTH`、それ以外は 1（行頭の空白も詰めずに表示できる）
+pub fn display_cell(c: char, typed: bool) -> String {
+    match c {
+        '\t' => format!("{:<width$}", '→', width = TAB_WIDTH),
+        '\n' => "↵".to_string(),
+        ' ' if !typed => "·".to_string(),
+        c => c.to_string(),
+    }
+}
+
+/// 1文字を打った結果
+#[derive(Debug, Clone, Copy)]
+pub enum CodeKey {
+    Hit,
+    Miss(Miss),
+}
+
+/// コード片1つ分の入力
+#[derive(Debug, Clone)]
+pub struct CodeDrill {
+    pub snippet_idx: usize,
+    chars: Vec<char>,
+    /// 次に打つ文字の位置
+    pub position: usize,
+    /// 文字ごとのミスの回数
+    miss_counts: Vec<u32>,
+    /// 押したキーの数
+    pub keystrokes: u32,
+    started_at: Option<Instant>,
+    finished_at: Option<Instant>,
+}
+
+impl CodeDrill {
+    pub fn new(snippet_idx: usize) -> Self {
+        let chars: Vec<char> = CODE_SNIPPETS[snippet_idx].text.chars().collect();
+        Self {
+            snippet_idx,
+            miss_counts: vec![0; chars.len()],
+            chars,
+            position: 0,
+            keystrokes: 0,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    pub fn snippet(&self) -> &'static CodeSnippet {
+        &CODE_SNIPPETS[self.snippet_idx]
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+
+    /// 最初のキーから打ち終えるまで（打ち終えていなければ今まで）の時間
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        match self.started_at {
+            Some(start) => self.finished_at.unwrap_or(now).saturating_duration_since(start),
+            None => Duration::ZERO,
+        }
+    }
+
+    pub fn misses(&self) -> u32 {
+        self.miss_counts.iter().sum()
+    }
+
+    /// その位置の文字をミスした回数
+    pub fn misses_at(&self, idx: usize) -> u32 {
+        self.miss_counts.get(idx).copied().unwrap_or(0)
+    }
+
+    /// ミスのあった文字の (位置, ミス回数)
+    pub fn char_misses(&self) -> Vec<(u32, u32)> {
+        self.miss_counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(idx, &count)| (idx as u32, count))
+            .collect()
+    }
+
+    /// 1文字打つ（タブは '\t'、Enter は '\n' で渡す）。大文字・小文字も区別する
+    /// 打ち終えたあとは何もしない（None）
+    pub fn press(&mut self, c: char, now: Instant) -> Option<CodeKey> {
+        let &expected = self.chars.get(self.position)?;
+        self.started_at.get_or_insert(now);
+        self.keystrokes += 1;
+        if c != expected {
+            self.miss_counts[self.position] += 1;
+            return Some(CodeKey::Miss(Miss { expected, actual: c, unit: self.position }));
+        }
+        self.position += 1;
+        if self.position == self.chars.len() {
+            self.finished_at = Some(now);
+        }
+        Some(CodeKey::Hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet_idx(title: &str) -> usize {
+        CODE_SNIPPETS.iter().position(|snippet| snippet.title == title).unwrap()
+    }
+
+    #[test]
+    fn every_builtin_snippet_can_be_typed() {
+        let (pool, rejected) = snippet_pool();
+        assert!(rejected.is_empty(), "{:?}", rejected);
+        assert_eq!(pool.len(), CODE_SNIPPETS.len());
+    }
+
+    #[test]
+    fn only_tabs_and_newlines_are_allowed_control_characters() {
+        assert!(validate_snippet("a\tb\nc").is_ok());
+        assert!(validate_snippet(" \n\t").is_err());
+        assert!(validate_snippet("a\rb").is_err());
+        assert!(validate_snippet("\u{1b}[0m").is_err());
+    }
+
+    #[test]
+    fn tabs_newlines_and_pending_spaces_are_drawn_visibly() {
+        assert_eq!(display_cell('\t', false), "→   ");
+        assert_eq!(display_cell('\n', false), "↵");
+        assert_eq!(display_cell(' ', false), "·");
+        assert_eq!(display_cell(' ', true), " ");
+        assert_eq!(display_cell('{', false), "{");
+    }
+
+    #[test]
+    fn keys_are_matched_literally_including_case_tab_and_enter() {
+        let mut drill = CodeDrill::new(snippet_idx("Makefile: rule"));
+        let start = Instant::now();
+        assert!(matches!(drill.press('B', start), Some(CodeKey::Miss(Miss { expected: 'b', actual: 'B', unit: 0 }))));
+        assert_eq!(drill.position, 0);
+
+        let text = drill.snippet().text;
+        let (head, tail) = text.split_at(text.find('\t').unwrap());
+        for c in head.chars() {
+            assert!(matches!(drill.press(c, start), Some(CodeKey::Hit)));
+        }
+        // タブの代わりに空白を打つとミス
+        let tab = drill.position;
+        assert!(matches!(drill.press(' ', start), Some(CodeKey::Miss(Miss { expected: '\t', .. }))));
+        for c in tail.chars() {
+            drill.press(c, start + Duration::from_secs(4));
+        }
+
+        assert!(drill.is_finished());
+        assert!(drill.press('x', start + Duration::from_secs(9)).is_none());
+        assert_eq!(drill.elapsed(start + Duration::from_secs(9)), Duration::from_secs(4));
+        assert_eq!(drill.misses(), 2);
+        assert_eq!(drill.misses_at(tab), 1);
+        assert_eq!(drill.char_misses(), vec![(0, 1), (tab as u32, 1)]);
+        assert_eq!(drill.keystrokes, text.chars().count() as u32 + 2);
+    }
+}
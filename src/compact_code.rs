@@ -0,0 +1,377 @@
+// ============================================
+// src/compact_code.rs
+// 1日分の成績を1行で書き写せる短いコードに変換する（ネットワークのない教室向け）
+// ============================================
+
+use chrono::{Local, NaiveDate, TimeDelta};
+use serde::Serialize;
+
+use std::fmt;
+
+use crate::save_data::TypeRecord;
+
+/// コードの形式のバージョン（形式を変えたら増やす）
+const CODE_VERSION: u64 = 1;
+/// 日付の基準日（ここからの日数を保存する）
+const EPOCH: NaiveDate = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+/// 名前の最大バイト数（UTF-8）
+pub const NAME_MAX_BYTES: usize = 31;
+/// Crockford の Base32（I, L, O, U を使わないので書き写しやすい）
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// 読みやすいようにハイフンで区切る文字数
+const GROUP_LEN: usize = 4;
+
+// 各フィールドのビット数
+const VERSION_BITS: u32 = 3;
+const DATE_BITS: u32 = 16;
+const CHARS_BITS: u32 = 24;
+/// CPS は 0.01 単位
+const CPS_BITS: u32 = 14;
+/// 正確率は 0.1% 単位
+const ACCURACY_BITS: u32 = 10;
+const SCORE_BITS: u32 = 28;
+const NAME_LEN_BITS: u32 = 5;
+const CHECKSUM_BITS: u32 = 16;
+/// 名前を除いたフィールドのビット数
+const PAYLOAD_FIXED_BITS: usize =
+    (VERSION_BITS + DATE_BITS + CHARS_BITS + CPS_BITS + ACCURACY_BITS + SCORE_BITS + NAME_LEN_BITS) as usize;
+
+/// 1日分の成績のまとめ
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub name: String,
+    pub date: NaiveDate,
+    pub total_chars: u32,
+    pub cps: f64,
+    /// 正確率 (%)
+    pub accuracy: f64,
+    pub score: u64,
+}
+
+impl SessionSummary {
+    /// 指定した日（ローカル時刻）の記録をまとめる。記録がなければ None
+    pub fn from_history(name: &str, history: &[TypeRecord], date: NaiveDate) -> Option<Self> {
+        let records: Vec<&TypeRecord> = history
+            .iter()
+            .filter(|r| r.timestamp.with_timezone(&Local).date_naive() == date)
+            .collect();
+        if records.is_empty() {
+            return None;
+        }
+
+        let total_chars: u32 = records.iter().map(|r| r.total_chars).sum();
+        let misses: u32 = records.iter().map(|r| r.misses).sum();
+        let duration: f64 = records.iter().map(|r| r.duration_sec).sum();
+        let attempts = f64::from(total_chars) + f64::from(misses);
+        Some(Self {
+            name: truncate_name(name).to_string(),
+            date,
+            total_chars,
+            cps: if duration > 0.0 { f64::from(total_chars) / duration } else { 0.0 },
+            accuracy: if attempts > 0.0 { f64::from(total_chars) / attempts * 100.0 } else { 100.0 },
+            score: records.iter().map(|r| r.score.max(0.0).round() as u64).sum(),
+        })
+    }
+
+    /// コードに変換する。数値は各フィールドの範囲に丸める
+    pub fn encode(&self) -> String {
+        let mut writer = BitWriter::default();
+        writer.push(CODE_VERSION, VERSION_BITS);
+        let days = (self.date - EPOCH).num_days().max(0) as u64;
+        writer.push(days, DATE_BITS);
+        writer.push(u64::from(self.total_chars), CHARS_BITS);
+        writer.push((self.cps.max(0.0) * 100.0).round() as u64, CPS_BITS);
+        writer.push((self.accuracy.clamp(0.0, 100.0) * 10.0).round() as u64, ACCURACY_BITS);
+        writer.push(self.score, SCORE_BITS);
+        let name = truncate_name(&self.name).as_bytes();
+        writer.push(name.len() as u64, NAME_LEN_BITS);
+        for &byte in name {
+            writer.push(u64::from(byte), 8);
+        }
+        let checksum = crc16(&writer.to_bytes());
+        writer.push(u64::from(checksum), CHECKSUM_BITS);
+
+        let symbols: Vec<char> = writer
+            .bits
+            .chunks(5)
+            .map(|chunk| {
+                let value = chunk.iter().enumerate().fold(0, |acc, (i, &bit)| acc | (usize::from(bit) << (4 - i)));
+                char::from(ALPHABET[value])
+            })
+            .collect();
+        symbols
+            .chunks(GROUP_LEN)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// コードを読み取る。ハイフンや空白、大文字小文字の違いは無視する
+    pub fn decode(code: &str) -> Result<Self, CodeError> {
+        let mut bits = Vec::new();
+        let mut symbols = 0;
+        for ch in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+            symbols += 1;
+            let value = symbol_value(ch).ok_or(CodeError::InvalidChar { ch, position: symbols })?;
+            bits.extend((0..5).rev().map(|i| (value >> i) & 1 == 1));
+        }
+
+        // 名前の長さごとにコードの文字数が決まるので、文字数から名前の長さを求める
+        let name_len = (0..=NAME_MAX_BYTES)
+            .find(|&len| (PAYLOAD_FIXED_BITS + 8 * len + CHECKSUM_BITS as usize).div_ceil(5) == symbols)
+            .ok_or(CodeError::WrongLength(symbols))?;
+        let payload_len = PAYLOAD_FIXED_BITS + 8 * name_len;
+        let mut reader = BitReader { bits: &bits, pos: payload_len };
+        let checksum = reader.take(CHECKSUM_BITS) as u16;
+        let payload = BitWriter { bits: bits[..payload_len].to_vec() };
+        if crc16(&payload.to_bytes()) != checksum || bits[reader.pos..].iter().any(|&bit| bit) {
+            return Err(CodeError::ChecksumMismatch);
+        }
+
+        let mut reader = BitReader { bits: &bits, pos: 0 };
+        let version = reader.take(VERSION_BITS);
+        if version != CODE_VERSION {
+            return Err(CodeError::UnsupportedVersion(version));
+        }
+        let days = reader.take(DATE_BITS);
+        let total_chars = reader.take(CHARS_BITS) as u32;
+        let cps_centi = reader.take(CPS_BITS);
+        let accuracy_permille = reader.take(ACCURACY_BITS);
+        let score = reader.take(SCORE_BITS);
+        if reader.take(NAME_LEN_BITS) as usize != name_len {
+            return Err(CodeError::ChecksumMismatch);
+        }
+        let name: Vec<u8> = (0..name_len).map(|_| reader.take(8) as u8).collect();
+        let name = String::from_utf8(name).map_err(|_| CodeError::InvalidName)?;
+        let date = EPOCH + TimeDelta::days(days as i64);
+        Ok(Self {
+            name,
+            date,
+            total_chars,
+            cps: cps_centi as f64 / 100.0,
+            accuracy: (accuracy_permille as f64 / 10.0).min(100.0),
+            score,
+        })
+    }
+}
+
+/// コードが読み取れなかった理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodeError {
+    /// コードに使えない文字がある（位置は区切りを除いて 1 から数える）
+    InvalidChar { ch: char, position: usize },
+    /// コードの文字数がおかしい（区切りを除いた文字数）
+    WrongLength(usize),
+    /// チェックサムが合わない（書き写し間違い）
+    ChecksumMismatch,
+    /// 新しいバージョンの type-wiz で作られたコード
+    UnsupportedVersion(u64),
+    /// 名前が壊れている
+    InvalidName,
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidChar { ch, position } => {
+                write!(f, "'{}' (character {}) is not used in codes; check for typos", ch, position)
+            }
+            Self::WrongLength(symbols) => write!(
+                f,
+                "a code cannot have {} characters; some characters may be missing or duplicated",
+                symbols
+            ),
+            Self::ChecksumMismatch => write!(f, "the checksum does not match; check the code for typos"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "the code uses format version {}; update type-wiz to read it", version)
+            }
+            Self::InvalidName => write!(f, "the name in the code is not valid text"),
+        }
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+/// 名前を文字の途中で切らないように最大バイト数までに縮める
+fn truncate_name(name: &str) -> &str {
+    let name = name.trim();
+    let mut end = name.len().min(NAME_MAX_BYTES);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// 書き写しで間違えやすい文字も受け付ける（O → 0, I/L → 1）
+fn symbol_value(ch: char) -> Option<u8> {
+    let ch = match ch.to_ascii_uppercase() {
+        'O' => '0',
+        'I' | 'L' => '1',
+        c => c,
+    };
+    ALPHABET.iter().position(|&c| char::from(c) == ch).map(|idx| idx as u8)
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in bytes {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// 上位ビットから順に詰めていく
+#[derive(Default)]
+struct BitWriter {
+    bits: Vec<bool>,
+}
+
+impl BitWriter {
+    /// `value` の下位 `width` ビットを追加する（収まらない値は最大値にする）
+    fn push(&mut self, value: u64, width: u32) {
+        let value = value.min((1 << width) - 1);
+        self.bits.extend((0..width).rev().map(|i| (value >> i) & 1 == 1));
+    }
+
+    /// 8 ビットずつのバイト列（最後は 0 で埋める）
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bits
+            .chunks(8)
+            .map(|chunk| chunk.iter().enumerate().fold(0u8, |acc, (i, &bit)| acc | (u8::from(bit) << (7 - i))))
+            .collect()
+    }
+}
+
+/// 上位ビットから順に読む（長さは呼び出し側で確認済み）
+struct BitReader<'a> {
+    bits: &'a [bool],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn take(&mut self, width: u32) -> u64 {
+        let end = self.pos + width as usize;
+        let value = self.bits[self.pos..end].iter().fold(0, |acc, &bit| (acc << 1) | u64::from(bit));
+        self.pos = end;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn summary() -> SessionSummary {
+        SessionSummary {
+            name: "Sato 太郎".to_string(),
+            date: NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(),
+            total_chars: 1234,
+            cps: 5.67,
+            accuracy: 97.3,
+            score: 45_678,
+        }
+    }
+
+    #[test]
+    fn a_summary_round_trips_through_its_code() {
+        let code = summary().encode();
+        assert!(code.split('-').all(|group| group.len() <= GROUP_LEN));
+        assert_eq!(SessionSummary::decode(&code), Ok(summary()));
+
+        // 区切り・空白・小文字、書き写しで間違えやすい文字も読める
+        let relaxed = code.replace('-', " ").to_lowercase().replace('0', "o").replace('1', "l");
+        assert_eq!(SessionSummary::decode(&relaxed), Ok(summary()));
+
+        let unnamed = SessionSummary { name: String::new(), ..summary() };
+        assert_eq!(SessionSummary::decode(&unnamed.encode()), Ok(unnamed));
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped_and_long_names_cut_on_a_char_boundary() {
+        let big = SessionSummary {
+            name: "あ".repeat(11),
+            date: NaiveDate::from_ymd_opt(2019, 12, 31).unwrap(),
+            total_chars: u32::MAX,
+            cps: 1_000.0,
+            accuracy: 120.0,
+            score: u64::MAX,
+        };
+        let decoded = SessionSummary::decode(&big.encode()).unwrap();
+        assert_eq!(decoded.name, "あ".repeat(10));
+        assert_eq!(decoded.date, EPOCH);
+        assert_eq!(decoded.total_chars, (1 << CHARS_BITS) - 1);
+        assert_eq!(decoded.cps, ((1 << CPS_BITS) - 1) as f64 / 100.0);
+        assert_eq!(decoded.accuracy, 100.0);
+        assert_eq!(decoded.score, (1 << SCORE_BITS) - 1);
+    }
+
+    #[test]
+    fn every_single_character_typo_is_rejected() {
+        let code = summary().encode();
+        let symbols: Vec<char> = code.chars().filter(|&c| c != '-').collect();
+        for position in 0..symbols.len() {
+            for replacement in ALPHABET.iter().map(|&b| char::from(b)) {
+                if replacement == symbols[position] {
+                    continue;
+                }
+                let mut typo = symbols.clone();
+                typo[position] = replacement;
+                let typo: String = typo.into_iter().collect();
+                assert_eq!(SessionSummary::decode(&typo), Err(CodeError::ChecksumMismatch), "{}", typo);
+            }
+        }
+    }
+
+    #[test]
+    fn swapped_neighbours_are_rejected() {
+        let symbols: Vec<char> = summary().encode().chars().filter(|&c| c != '-').collect();
+        for i in 0..symbols.len() - 1 {
+            if symbols[i] == symbols[i + 1] {
+                continue;
+            }
+            let mut swapped = symbols.clone();
+            swapped.swap(i, i + 1);
+            let swapped: String = swapped.into_iter().collect();
+            assert!(SessionSummary::decode(&swapped).is_err(), "{}", swapped);
+        }
+    }
+
+    #[test]
+    fn malformed_codes_say_what_is_wrong() {
+        let code = summary().encode();
+        let with_u = format!("U{}", &code[1..]);
+        assert_eq!(SessionSummary::decode(&with_u), Err(CodeError::InvalidChar { ch: 'U', position: 1 }));
+        // 1文字足りないコードは、短い名前の長さと重なればチェックサムで弾かれる
+        assert!(SessionSummary::decode(&code[..code.len() - 1]).is_err());
+        assert_eq!(SessionSummary::decode("ABCD-EF"), Err(CodeError::WrongLength(6)));
+        assert_eq!(SessionSummary::decode(""), Err(CodeError::WrongLength(0)));
+    }
+
+    #[test]
+    fn a_day_of_records_is_summarized() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let at = |day: NaiveDate, hour| {
+            Local.from_local_datetime(&day.and_hms_opt(hour, 0, 0).unwrap()).single().unwrap().with_timezone(&Utc)
+        };
+        let mut records = vec![
+            TypeRecord::sample("ねこ", 40, 10.0, 2),
+            TypeRecord::sample("いぬ", 60, 10.0, 3),
+            TypeRecord::sample("とり", 99, 5.0, 0),
+        ];
+        records[0].timestamp = at(date, 9);
+        records[1].timestamp = at(date, 21);
+        records[2].timestamp = at(date.succ_opt().unwrap(), 9);
+
+        let day = SessionSummary::from_history("  Sato  ", &records, date).unwrap();
+        assert_eq!((day.name.as_str(), day.date, day.total_chars), ("Sato", date, 100));
+        assert_eq!(day.cps, 5.0);
+        assert!((day.accuracy - 100.0 / 105.0 * 100.0).abs() < 1e-9);
+        assert_eq!(day.score, records[..2].iter().map(|r| r.score.round() as u64).sum::<u64>());
+        assert_eq!(SessionSummary::from_history("Sato", &records, date.pred_opt().unwrap()), None);
+    }
+}
@@ -0,0 +1,64 @@
+// ============================================
+// src/completion_sweep.rs
+// お題を打ち終えたとき、ローマ字の行を左から右へ緑で塗っていく短い演出
+// 演出中も次のお題はもう始まっていて、キーを押せばその場で打ち切る
+// ============================================
+
+use std::time::{Duration, Instant};
+
+/// 演出の長さ
+pub const SWEEP_DURATION: Duration = Duration::from_millis(300);
+
+/// 打ち終えたお題の表示と演出の開始時刻
+#[derive(Debug, Clone)]
+pub struct CompletionSweep {
+    pub japanese: String,
+    pub hiragana: String,
+    pub romaji: String,
+    started: Instant,
+}
+
+impl CompletionSweep {
+    pub fn new(japanese: String, hiragana: String, romaji: String, now: Instant) -> Self {
+        Self { japanese, hiragana, romaji, started: now }
+    }
+
+    /// 塗り終えたローマ字の文字数（演出が終わっていれば None）
+    pub fn swept_chars(&self, now: Instant) -> Option<usize> {
+        let elapsed = now.saturating_duration_since(self.started);
+        if elapsed >= SWEEP_DURATION {
+            return None;
+        }
+        let progress = elapsed.as_secs_f64() / SWEEP_DURATION.as_secs_f64();
+        let len = self.romaji.chars().count();
+        Some(((len as f64 * progress).ceil() as usize).min(len))
+    }
+
+    pub fn is_active(&self, now: Instant) -> bool {
+        self.swept_chars(now).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sweep_fills_left_to_right_and_then_ends() {
+        let start = Instant::now();
+        let sweep = CompletionSweep::new("猫".to_string(), "ねこ".to_string(), "neko".to_string(), start);
+        assert_eq!(sweep.swept_chars(start), Some(0));
+        assert_eq!(sweep.swept_chars(start + SWEEP_DURATION / 4), Some(1));
+        assert_eq!(sweep.swept_chars(start + SWEEP_DURATION / 2 + Duration::from_millis(1)), Some(3));
+        assert_eq!(sweep.swept_chars(start + SWEEP_DURATION - Duration::from_millis(1)), Some(4));
+        assert_eq!(sweep.swept_chars(start + SWEEP_DURATION), None);
+        assert!(sweep.is_active(start) && !sweep.is_active(start + SWEEP_DURATION));
+    }
+
+    #[test]
+    fn multibyte_romaji_is_counted_in_characters() {
+        let start = Instant::now();
+        let sweep = CompletionSweep::new("星☆".to_string(), "ほし☆".to_string(), "hoshi☆".to_string(), start);
+        assert_eq!(sweep.swept_chars(start + SWEEP_DURATION - Duration::from_millis(1)), Some(6));
+    }
+}
@@ -0,0 +1,179 @@
+// ============================================
+// src/confusion.rs
+// キーの取り違え（打つべきキー → 実際に押したキー）の集計
+// ミスは打つべきだったキーのミスとして数え、押したキーは取り違えの元として別に記録する
+// ============================================
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+/// `stats --confusions` で表示する数
+pub const TOP_CONFUSIONS: usize = 10;
+/// 割合を出すのに必要な、そのキーを打つべきだった回数（少ないと割合がぶれる）
+const MIN_EXPECTED: u64 = 20;
+
+// 1回のミスの割り当て方は typewiz-engine の `Miss` を参照
+pub use typewiz_engine::typing::Miss;
+
+/// 打つべきキーごとの、そのキーを打つべきだった回数（正しく打った数 + ミスした数）
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct KeyCount {
+    pub key: char,
+    pub expected: u64,
+}
+
+/// 打つべきキーと押したキーの組の回数
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct ConfusionPair {
+    pub expected: char,
+    pub actual: char,
+    pub count: u64,
+}
+
+/// キーの取り違えの表（キーは小文字にそろえる）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct ConfusionMatrix {
+    pub keys: Vec<KeyCount>,
+    pub pairs: Vec<ConfusionPair>,
+}
+
+impl ConfusionMatrix {
+    fn count_expected(&mut self, key: char) {
+        match self.keys.iter_mut().find(|entry| entry.key == key) {
+            Some(entry) => entry.expected += 1,
+            None => self.keys.push(KeyCount { key, expected: 1 }),
+        }
+    }
+
+    /// 正しく打ったキー
+    pub fn record_hit(&mut self, key: char) {
+        self.count_expected(key.to_ascii_lowercase());
+    }
+
+    /// ミス（大文字・小文字の違いだけなら、打つべきキーのミスとしてだけ数える）
+    pub fn record_miss(&mut self, miss: &Miss) {
+        let expected = miss.expected.to_ascii_lowercase();
+        let actual = miss.actual.to_ascii_lowercase();
+        self.count_expected(expected);
+        if expected == actual {
+            return;
+        }
+        match self.pairs.iter_mut().find(|pair| pair.expected == expected && pair.actual == actual) {
+            Some(pair) => pair.count += 1,
+            None => self.pairs.push(ConfusionPair { expected, actual, count: 1 }),
+        }
+    }
+
+    /// 打つべきキーに対する割合の高い取り違え（打つべきだった回数の少ないキーは除く）
+    pub fn top(&self, limit: usize) -> Vec<Confusion> {
+        let mut confusions: Vec<Confusion> = self
+            .pairs
+            .iter()
+            .filter_map(|pair| {
+                let expected_total = self.keys.iter().find(|entry| entry.key == pair.expected)?.expected;
+                (expected_total >= MIN_EXPECTED).then(|| Confusion {
+                    expected: pair.expected,
+                    actual: pair.actual,
+                    count: pair.count,
+                    expected_total,
+                    share: pair.count as f64 / expected_total as f64 * 100.0,
+                })
+            })
+            .collect();
+        confusions.sort_by(|a, b| {
+            b.share
+                .total_cmp(&a.share)
+                .then(b.count.cmp(&a.count))
+                .then(a.expected.cmp(&b.expected))
+                .then(a.actual.cmp(&b.actual))
+        });
+        confusions.truncate(limit);
+        confusions
+    }
+}
+
+/// 表示用の取り違え1つ
+#[derive(Debug, Clone, Serialize)]
+pub struct Confusion {
+    pub expected: char,
+    pub actual: char,
+    pub count: u64,
+    /// `expected` を打つべきだった回数
+    pub expected_total: u64,
+    /// `expected` を打つべきときに `actual` を押した割合 (%)
+    pub share: f64,
+}
+
+impl Confusion {
+    /// "you press 'o' when 'p' is expected 9.0% of the time (27 of 300)"
+    pub fn line(&self) -> String {
+        format!(
+            "you press {:?} when {:?} is expected {:.1}% of the time ({} of {})",
+            self.actual, self.expected, self.share, self.count, self.expected_total
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn miss(expected: char, actual: char) -> Miss {
+        Miss { expected, actual, unit: 0 }
+    }
+
+    fn expected_count(matrix: &ConfusionMatrix, key: char) -> Option<u64> {
+        matrix.keys.iter().find(|entry| entry.key == key).map(|entry| entry.expected)
+    }
+
+    #[test]
+    fn hits_and_misses_count_towards_the_expected_key() {
+        let mut matrix = ConfusionMatrix::default();
+        matrix.record_hit('k');
+        matrix.record_hit('K');
+        matrix.record_miss(&miss('k', 'j'));
+        matrix.record_miss(&miss('K', 'J'));
+        matrix.record_miss(&miss('a', 'k'));
+        assert_eq!(expected_count(&matrix, 'k'), Some(4));
+        assert_eq!(expected_count(&matrix, 'a'), Some(1));
+        // 押しただけのキーは、打つべきだった回数に数えない
+        assert_eq!(expected_count(&matrix, 'j'), None);
+        let pairs: Vec<_> = matrix.pairs.iter().map(|p| (p.expected, p.actual, p.count)).collect();
+        assert_eq!(pairs, [('k', 'j', 2), ('a', 'k', 1)]);
+    }
+
+    #[test]
+    fn a_case_only_miss_is_not_a_confusion() {
+        let mut matrix = ConfusionMatrix::default();
+        matrix.record_miss(&miss('a', 'A'));
+        assert_eq!(expected_count(&matrix, 'a'), Some(1));
+        assert!(matrix.pairs.is_empty());
+    }
+
+    #[test]
+    fn top_ranks_by_share_and_skips_rarely_expected_keys() {
+        let mut matrix = ConfusionMatrix::default();
+        for _ in 0..27 {
+            matrix.record_miss(&miss('p', 'o'));
+        }
+        for _ in 0..273 {
+            matrix.record_hit('p');
+        }
+        for _ in 0..5 {
+            matrix.record_miss(&miss('t', 'r'));
+        }
+        for _ in 0..15 {
+            matrix.record_hit('t');
+        }
+        // 打つべきだった回数が少ないキーは出さない
+        for _ in 0..MIN_EXPECTED - 1 {
+            matrix.record_miss(&miss('q', 'w'));
+        }
+
+        let top = matrix.top(TOP_CONFUSIONS);
+        let rows: Vec<_> = top.iter().map(|c| (c.expected, c.actual, c.count, c.expected_total)).collect();
+        assert_eq!(rows, [('t', 'r', 5, 20), ('p', 'o', 27, 300)]);
+        assert_eq!(top[1].line(), "you press 'o' when 'p' is expected 9.0% of the time (27 of 300)");
+        assert_eq!(matrix.top(1).len(), 1);
+    }
+}
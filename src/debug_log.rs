@@ -0,0 +1,157 @@
+// ============================================
+// src/debug_log.rs
+// 入力の不具合などを調べるためのデバッグログ（`--log-file` か TYPEWIZ_LOG で有効）
+// TUI を壊さないよう、標準出力・標準エラーには一切書かない
+// 無効なときは `dlog!` の引数を評価しない
+// ============================================
+
+use chrono::Local;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// ログファイルのパスを指定する環境変数
+pub const LOG_ENV: &str = "TYPEWIZ_LOG";
+/// この大きさを超えたら `.1` に移して新しいファイルに書く
+const ROTATE_BYTES: u64 = 5 * 1024 * 1024;
+/// `doctor --tail-log` で表示する行数
+pub const TAIL_LINES: usize = 100;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+struct Logger {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl Logger {
+    fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path: path.to_path_buf(), file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > ROTATE_BYTES {
+            fs::rename(&self.path, rotated_path(&self.path))?;
+            *self = Self::open(&self.path)?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// 前のログを移す先（例: debug.log → debug.log.1）
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// ログのパス（`--log-file` を優先し、なければ環境変数）
+pub fn configured_path(cli: Option<&Path>) -> Option<PathBuf> {
+    cli.map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(LOG_ENV).filter(|value| !value.is_empty()).map(PathBuf::from))
+}
+
+/// ログを有効にする（起動時に一度だけ呼ぶ）
+pub fn init(path: &Path) -> io::Result<()> {
+    let logger = Logger::open(path)?;
+    *LOGGER.lock().unwrap_or_else(|e| e.into_inner()) = Some(logger);
+    ENABLED.store(true, Ordering::Relaxed);
+    write("log", format_args!("started version={} pid={}", env!("CARGO_PKG_VERSION"), std::process::id()));
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 1行書く（`dlog!` から呼ぶ）。書けなくても黙って続ける
+pub fn write(category: &str, message: fmt::Arguments) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(logger) = logger.as_mut() {
+        let line = format!("{} [{}] {}\n", Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"), category, message);
+        let _ = logger.write_line(&line);
+    }
+}
+
+/// ログの末尾 `count` 行
+pub fn tail(path: &Path, count: usize) -> io::Result<Vec<String>> {
+    let mut lines = VecDeque::with_capacity(count);
+    for line in BufReader::new(File::open(path)?).lines() {
+        if lines.len() == count {
+            lines.pop_front();
+        }
+        lines.push_back(line?);
+    }
+    Ok(lines.into())
+}
+
+/// `format!` と同じ書式でデバッグログに1行書く（無効なときは何もしない）
+/// 例: `dlog!("save", "path={} bytes={}", path.display(), bytes.len())`
+macro_rules! dlog {
+    ($category:expr, $($arg:tt)*) => {
+        if $crate::debug_log::enabled() {
+            $crate::debug_log::write($category, format_args!($($arg)*))
+        }
+    };
+}
+
+pub(crate) use dlog;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとの一時フォルダ
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("typewiz-log-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn a_full_log_moves_aside_before_the_line_that_would_overflow() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("nested").join("debug.log");
+        let mut logger = Logger::open(&path).unwrap();
+        let big = format!("{}\n", "x".repeat(ROTATE_BYTES as usize - 10));
+        logger.write_line(&big).unwrap();
+        logger.write_line("short\n").unwrap();
+        assert!(!rotated_path(&path).exists());
+
+        logger.write_line("overflow\n").unwrap();
+        assert_eq!(rotated_path(&path), dir.join("nested").join("debug.log.1"));
+        assert_eq!(fs::read_to_string(rotated_path(&path)).unwrap().len(), big.len() + "short\n".len());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "overflow\n");
+
+        // 開き直したときは今の大きさから数える
+        drop(logger);
+        assert_eq!(Logger::open(&path).unwrap().size, "overflow\n".len() as u64);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tail_keeps_only_the_last_lines() {
+        let dir = temp_dir("tail");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("debug.log");
+        fs::write(&path, (1..=5).map(|n| format!("line {}\n", n)).collect::<String>()).unwrap();
+        assert_eq!(tail(&path, 2).unwrap(), vec!["line 4", "line 5"]);
+        assert_eq!(tail(&path, 10).unwrap().len(), 5);
+        assert!(tail(&dir.join("missing.log"), 2).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+// ============================================
+// src/error_cost.rs
+// ミスタイプで失った時間の見積もり
+// ミスのあと正しいキーが入るまでにかかった時間を、ふだんの打鍵間隔（中央値）と比べる
+// ============================================
+
+use std::time::{Duration, Instant};
+
+/// これより長い打鍵間隔は手を止めたものとみなし、ふだんの間隔の計算から外す
+const PAUSE_THRESHOLD: Duration = Duration::from_millis(1500);
+/// 1回の立ち直りで失ったとみなす時間の上限（途中で手を止めた分までは数えない）
+const MAX_RECOVERY_LOSS: Duration = Duration::from_secs(3);
+
+/// ミスで失った時間
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ErrorCost {
+    pub errors: u32,
+    pub lost: Duration,
+}
+
+impl ErrorCost {
+    /// "lost 2.4s to 3 errors"（ミスがなければ None）
+    pub fn summary(&self) -> Option<String> {
+        (self.errors > 0).then(|| {
+            let unit = if self.errors == 1 { "error" } else { "errors" };
+            format!("lost {:.1}s to {} {}", self.lost.as_secs_f64(), self.errors, unit)
+        })
+    }
+}
+
+/// 1問分の打鍵の時刻と正誤（読み飛ばす単位のキーは含めない）
+#[derive(Debug, Clone, Default)]
+pub struct KeyTimeline {
+    keys: Vec<(Instant, bool)>,
+}
+
+impl KeyTimeline {
+    pub fn record(&mut self, at: Instant, correct: bool) {
+        self.keys.push((at, correct));
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    /// 打鍵の時刻（正誤は問わない）
+    pub fn times(&self) -> Vec<Instant> {
+        self.keys.iter().map(|&(at, _)| at).collect()
+    }
+
+    /// 正しいキーが続いたところの打鍵間隔の中央値（手を止めた間隔は除く）
+    fn median_interval(&self) -> Option<Duration> {
+        let mut intervals: Vec<Duration> = self
+            .keys
+            .windows(2)
+            .filter(|pair| pair[0].1 && pair[1].1)
+            .map(|pair| pair[1].0.saturating_duration_since(pair[0].0))
+            .filter(|&interval| interval < PAUSE_THRESHOLD)
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        intervals.sort_unstable();
+        let mid = intervals.len() / 2;
+        Some(if intervals.len().is_multiple_of(2) { (intervals[mid - 1] + intervals[mid]) / 2 } else { intervals[mid] })
+    }
+
+    /// ミスで失った時間を見積もる
+    /// 直前の正しいキーから、ミスのあと正しいキーが入るまでの時間のうち、ふだんの間隔を超えた分を数える
+    /// 続けてミスしたときはまとめて1回の立ち直りとして数える（同じ時間を二重に数えない）
+    /// ふだんの間隔がわからない（正しいキーが続いたところがない）ときは、ミスの数だけ返す
+    pub fn error_cost(&self) -> ErrorCost {
+        let errors = self.keys.iter().filter(|(_, correct)| !correct).count() as u32;
+        let Some(median) = self.median_interval() else {
+            return ErrorCost { errors, lost: Duration::ZERO };
+        };
+        let mut lost = Duration::ZERO;
+        let mut previous_hit: Option<Instant> = None;
+        // 立ち直りの起点と、ミスがなければかかったはずの時間
+        let mut stall: Option<(Instant, Duration)> = None;
+        for &(at, correct) in &self.keys {
+            if !correct {
+                stall.get_or_insert(match previous_hit {
+                    Some(hit) => (hit, median),
+                    // 最初のキーからミスしたときは、ミスした時刻から数える
+                    None => (at, Duration::ZERO),
+                });
+                continue;
+            }
+            if let Some((from, expected)) = stall.take() {
+                lost += at.saturating_duration_since(from).saturating_sub(expected).min(MAX_RECOVERY_LOSS);
+            }
+            previous_hit = Some(at);
+        }
+        ErrorCost { errors, lost }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (ミリ秒, 正誤) の並びから作る打鍵の記録
+    fn timeline(keys: &[(u64, bool)]) -> KeyTimeline {
+        let t0 = Instant::now();
+        let mut timeline = KeyTimeline::default();
+        for &(ms, correct) in keys {
+            timeline.record(t0 + Duration::from_millis(ms), correct);
+        }
+        timeline
+    }
+
+    fn cost(keys: &[(u64, bool)]) -> (u32, u64) {
+        let cost = timeline(keys).error_cost();
+        (cost.errors, cost.lost.as_millis() as u64)
+    }
+
+    /// 100 ミリ秒おきの正しいキー
+    fn steady(from: u64, count: u64) -> impl Iterator<Item = (u64, bool)> {
+        (0..count).map(move |i| (from + i * 100, true))
+    }
+
+    #[test]
+    fn the_median_interval_ignores_pauses_and_misses() {
+        let keys: Vec<_> = steady(0, 4).chain([(5_000, true), (5_300, true), (5_350, false), (5_700, true)]).collect();
+        // 100, 100, 100, 300（4.7 秒の間と、ミスをはさむ間隔は除く）
+        assert_eq!(timeline(&keys).median_interval(), Some(Duration::from_millis(100)));
+        // 数が偶数なら真ん中2つの平均
+        assert_eq!(timeline(&[(0, true), (100, true), (400, true)]).median_interval(), Some(Duration::from_millis(200)));
+        assert_eq!(timeline(&[(0, true), (100, false), (200, true)]).median_interval(), None);
+    }
+
+    #[test]
+    fn synthetic_streams_attribute_the_stall_to_each_recovery() {
+        let prefix: Vec<_> = steady(0, 4).collect();
+        let with = |tail: &[(u64, bool)]| -> Vec<(u64, bool)> { prefix.iter().copied().chain(tail.iter().copied()).collect() };
+        // (打鍵, ミス, 失った時間 ms)
+        let cases = [
+            (with(&[(400, true)]), 0, 0),
+            // 直前の正しいキー (300) から 600 まで。ふだんなら 100 で済んだ
+            (with(&[(350, false), (600, true)]), 1, 200),
+            // 続けてミスしても1回の立ち直りとして数える
+            (with(&[(350, false), (420, false), (700, true)]), 2, 300),
+            // 別々の立ち直りはそれぞれ数える
+            (with(&[(350, false), (600, true), (650, false), (900, true)]), 2, 200 + 200),
+            // ふだんより速く立ち直ったら失っていない
+            (with(&[(330, false), (380, true)]), 1, 0),
+            // 途中で手を止めた分は上限までしか数えない
+            (with(&[(350, false), (20_000, true)]), 1, MAX_RECOVERY_LOSS.as_millis() as u64),
+            // 立ち直らずに終わったミスは時間に数えない
+            (with(&[(350, false)]), 1, 0),
+        ];
+        for (keys, errors, lost) in cases {
+            assert_eq!(cost(&keys), (errors, lost), "{keys:?}");
+        }
+    }
+
+    #[test]
+    fn a_miss_on_the_first_key_counts_from_the_miss() {
+        let keys: Vec<_> = [(0, false)].into_iter().chain(steady(250, 4)).collect();
+        assert_eq!(cost(&keys), (1, 250));
+    }
+
+    #[test]
+    fn without_a_usual_interval_only_the_errors_are_counted() {
+        assert_eq!(cost(&[(0, true), (500, false), (900, true)]), (1, 0));
+        assert_eq!(cost(&[]), (0, 0));
+    }
+
+    #[test]
+    fn the_summary_reads_naturally() {
+        assert_eq!(ErrorCost::default().summary(), None);
+        let one = ErrorCost { errors: 1, lost: Duration::from_millis(430) };
+        assert_eq!(one.summary().as_deref(), Some("lost 0.4s to 1 error"));
+        let three = ErrorCost { errors: 3, lost: Duration::from_millis(2_400) };
+        assert_eq!(three.summary().as_deref(), Some("lost 2.4s to 3 errors"));
+    }
+}
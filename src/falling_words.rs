@@ -0,0 +1,215 @@
+// ============================================
+// src/falling_words.rs
+// 落ち物ゲーム（上から落ちてくる単語を、下に着く前に打ち切る）の進行
+// 単語の位置は経過時間で進める（描画の回数には左右されない）。打つのは一番下の単語だけ
+// ============================================
+
+use bincode::{Decode, Encode};
+use chrono::{DateTime, TimeZone, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::questions::{Question, TIER_COUNT};
+
+/// 画面の高さ（行）。単語の位置はこの行数で表し、描画のときに画面の高さに合わせる
+pub const FIELD_ROWS: f64 = 20.0;
+/// 落とした単語がこの数になったら終わり
+pub const MAX_DROPS: u32 = 3;
+/// ハイスコア表に残す数
+pub const HIGH_SCORE_SLOTS: usize = 10;
+/// 最初の落ちる速さ（行/秒）
+const BASE_SPEED: f64 = 1.0;
+/// 1語消すごとに速くなる割合
+const SPEED_STEP: f64 = 0.05;
+/// 落ちる速さの上限（行/秒）
+const MAX_SPEED: f64 = 6.0;
+/// 最初の単語が出る間隔
+const BASE_SPAWN_INTERVAL: Duration = Duration::from_millis(3000);
+/// 単語が出る間隔の下限
+const MIN_SPAWN_INTERVAL: Duration = Duration::from_millis(900);
+/// この数だけ消すごとに段階が1つ上がる（出す単語の長さの上限も上がる）
+const WORDS_PER_LEVEL: u32 = 5;
+
+/// 落ちている単語
+#[derive(Debug, Clone)]
+pub struct FallingWord {
+    /// 出した順の通し番号（打つ単語が変わったことに気づくため）
+    pub id: u64,
+    pub question: &'static Question,
+    /// 横の位置（0.0 が左端、1.0 が右端）
+    pub column: f64,
+    /// 縦の位置（0.0 が上端、`FIELD_ROWS` に着いたら落としたことになる）
+    pub row: f64,
+}
+
+/// ハイスコア表の1行
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct FallingScore {
+    /// 消した単語の数
+    pub words: u32,
+    /// 最後に着いた段階（1 始まり）
+    pub level: u32,
+    /// 遊んだ時間（ミリ秒）
+    pub duration_ms: u64,
+    /// 終わった時刻（UNIX 秒）
+    pub played_at: i64,
+}
+
+impl FallingScore {
+    pub fn played_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.played_at, 0).single().unwrap_or_default()
+    }
+}
+
+/// ミニゲームで打った分の累計（ミニゲームは履歴に記録を残さないので、再計算のときはここから足し戻す）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct MiniGameTotals {
+    pub typed_chars: u64,
+    pub misses: u64,
+    /// 遊んだ時間（秒）。累計練習時間と同じく1回ごとに丸めて足す
+    pub practice_secs: u64,
+}
+
+/// 1回分の進行
+#[derive(Debug, Clone)]
+pub struct FallingWords {
+    /// 出題する単語
+    pool: Vec<&'static Question>,
+    /// 出した順（先頭が一番下）。速さはすべての単語で同じなので、出した順がそのまま高さの順になる
+    words: VecDeque<FallingWord>,
+    next_id: u64,
+    started_at: Instant,
+    last_tick: Instant,
+    last_spawn: Instant,
+    /// 消した単語の数
+    pub cleared: u32,
+    /// 消した単語のかなの数（経験値に使う）
+    pub cleared_kana: u32,
+    /// 落とした単語の数
+    pub drops: u32,
+    /// 終わった時刻
+    ended_at: Option<Instant>,
+}
+
+impl FallingWords {
+    /// 出題する単語を受け取って始める（空なら何も落ちてこないので呼ぶ側で避ける）
+    pub fn new(pool: Vec<&'static Question>, now: Instant) -> Self {
+        let mut game = Self {
+            pool,
+            words: VecDeque::new(),
+            next_id: 0,
+            started_at: now,
+            last_tick: now,
+            last_spawn: now,
+            cleared: 0,
+            cleared_kana: 0,
+            drops: 0,
+            ended_at: None,
+        };
+        game.spawn();
+        game
+    }
+
+    /// 今の段階（1 始まり）
+    pub fn level(&self) -> u32 {
+        self.cleared / WORDS_PER_LEVEL + 1
+    }
+
+    /// 今の落ちる速さ（行/秒）
+    pub fn speed(&self) -> f64 {
+        (BASE_SPEED * (1.0 + SPEED_STEP * f64::from(self.cleared))).min(MAX_SPEED)
+    }
+
+    /// 今の単語が出る間隔（段階が上がるごとに短くなる）
+    fn spawn_interval(&self) -> Duration {
+        BASE_SPAWN_INTERVAL
+            .mul_f64(0.85_f64.powi(self.level() as i32 - 1))
+            .max(MIN_SPAWN_INTERVAL)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.ended_at.is_some()
+    }
+
+    /// 始めてからの時間（終わっていれば終わるまで）
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.ended_at.unwrap_or(now).saturating_duration_since(self.started_at)
+    }
+
+    /// 画面にある単語（一番下から）
+    pub fn words(&self) -> impl Iterator<Item = &FallingWord> {
+        self.words.iter()
+    }
+
+    /// 打つ単語（一番下の単語）
+    pub fn active(&self) -> Option<&FallingWord> {
+        self.words.front()
+    }
+
+    /// 経過時間だけ単語を落とし、下に着いた単語を落としたものとして数え、間隔が来たら次の単語を出す
+    pub fn tick(&mut self, now: Instant) {
+        if self.is_over() {
+            return;
+        }
+        let dt = now.saturating_duration_since(self.last_tick).as_secs_f64();
+        self.last_tick = now;
+        let speed = self.speed();
+        for word in &mut self.words {
+            word.row += speed * dt;
+        }
+
+        while self.words.front().is_some_and(|word| word.row >= FIELD_ROWS) {
+            self.words.pop_front();
+            self.drops += 1;
+        }
+        if self.drops >= MAX_DROPS {
+            self.ended_at = Some(now);
+            return;
+        }
+
+        if self.words.is_empty() || now.saturating_duration_since(self.last_spawn) >= self.spawn_interval() {
+            self.last_spawn = now;
+            self.spawn();
+        }
+    }
+
+    /// 打ち切った一番下の単語を消す
+    pub fn clear_active(&mut self) {
+        if let Some(word) = self.words.pop_front() {
+            self.cleared += 1;
+            self.cleared_kana += word.question.hiragana.chars().count() as u32;
+        }
+    }
+
+    /// 途中でやめる
+    pub fn end(&mut self, now: Instant) {
+        self.ended_at.get_or_insert(now);
+    }
+
+    /// ハイスコア表に入れる形にする
+    pub fn score(&self, now: Instant, at: DateTime<Utc>) -> FallingScore {
+        FallingScore {
+            words: self.cleared,
+            level: self.level(),
+            duration_ms: self.elapsed(now).as_millis() as u64,
+            played_at: at.timestamp(),
+        }
+    }
+
+    /// 上端に単語を1つ出す。短い単語から始め、段階が上がるほど長い単語も出す
+    fn spawn(&mut self) {
+        let max_tier = (self.level() as i32 - 1).min(TIER_COUNT - 1);
+        let candidates: Vec<&'static Question> =
+            self.pool.iter().copied().filter(|question| question.tier() <= max_tier).collect();
+        let candidates = if candidates.is_empty() { &self.pool } else { &candidates };
+        let mut rng = rand::rng();
+        let Some(&question) = candidates.get(rng.random_range(0..candidates.len().max(1))) else {
+            return;
+        };
+        self.words.push_back(FallingWord { id: self.next_id, question, column: rng.random_range(0.0..=1.0), row: 0.0 });
+        self.next_id += 1;
+    }
+}
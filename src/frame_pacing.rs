@@ -0,0 +1,100 @@
+// ============================================
+// src/frame_pacing.rs
+// タイピング画面の描き直しの間隔とキー入力の待ち方
+// 打っている間は短く待って入力の遅れをなくし、何も動いていないときは長く待って CPU を休ませる
+// 描き直すのは入力があったときと、1コマ分（何も動いていなければ1拍分）の時間が経ったときだけ
+// ============================================
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 打っている間のキー入力の待ち時間
+pub const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// 設定で選べるアニメーションのコマ数（1秒あたり）
+pub const ANIMATION_FPS_OPTIONS: [u32; 3] = [15, 30, 60];
+/// 設定で選べる、何も動いていないときの描き直しの間隔（ミリ秒）
+pub const TICK_RATE_OPTIONS: [u64; 5] = [50, 100, 250, 500, 1000];
+/// フレーム時間の平均を取るコマ数
+const FRAME_SAMPLES: usize = 60;
+
+/// 画面の動き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// お題を打っている（タイマーが動いている）
+    Typing,
+    /// 演出・ミスの点滅・メトロノームなど、動いているものがある
+    Animating,
+    /// 結果の表示中・一時停止中など、何も動いていない
+    Idle,
+}
+
+/// 描き直しの時刻の管理と、フレーム時間の記録
+#[derive(Debug)]
+pub struct FramePacer {
+    frame_interval: Duration,
+    tick_interval: Duration,
+    last_draw: Option<Instant>,
+    /// 入力などで画面が変わり、すぐに描き直す必要がある
+    dirty: bool,
+    /// 直近の描き直しの間隔
+    frame_times: VecDeque<Duration>,
+}
+
+impl FramePacer {
+    pub fn new(animation_fps: u32, tick_rate_ms: u64) -> Self {
+        Self {
+            frame_interval: Duration::from_secs(1) / animation_fps.max(1),
+            tick_interval: Duration::from_millis(tick_rate_ms.max(1)),
+            last_draw: None,
+            dirty: true,
+            frame_times: VecDeque::with_capacity(FRAME_SAMPLES),
+        }
+    }
+
+    /// 次の機会にすぐ描き直す
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    fn interval(&self, pace: Pace) -> Duration {
+        match pace {
+            Pace::Typing | Pace::Animating => self.frame_interval,
+            Pace::Idle => self.tick_interval,
+        }
+    }
+
+    /// 描き直す時刻か
+    pub fn should_draw(&self, now: Instant, pace: Pace) -> bool {
+        self.dirty || self.last_draw.is_none_or(|last| now.saturating_duration_since(last) >= self.interval(pace))
+    }
+
+    /// 描き直したことを記録する（前回からの間隔をフレーム時間として数える）
+    pub fn record_draw(&mut self, now: Instant) {
+        if let Some(last) = self.last_draw {
+            if self.frame_times.len() == FRAME_SAMPLES {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now.saturating_duration_since(last));
+        }
+        self.last_draw = Some(now);
+        self.dirty = false;
+    }
+
+    /// キー入力を待つ時間（次に描き直す時刻まで。打っている間は入力の遅れが出ないよう短く区切る）
+    pub fn poll_timeout(&self, now: Instant, pace: Pace) -> Duration {
+        let until_draw = self
+            .last_draw
+            .map_or(Duration::ZERO, |last| (last + self.interval(pace)).saturating_duration_since(now));
+        match pace {
+            Pace::Typing => until_draw.min(ACTIVE_POLL_INTERVAL),
+            Pace::Animating | Pace::Idle => until_draw,
+        }
+    }
+
+    /// 直近のフレーム時間の (平均, 最大)。まだ2回描いていなければ None
+    pub fn frame_stats(&self) -> Option<(Duration, Duration)> {
+        let max = *self.frame_times.iter().max()?;
+        let total: Duration = self.frame_times.iter().sum();
+        Some((total / self.frame_times.len() as u32, max))
+    }
+}
@@ -0,0 +1,449 @@
+// ============================================
+// src/history_import.rs
+// 他のタイピングソフト（e-typing など）の CSV から過去の記録を取り込む
+// 足りない項目は分かる範囲で計算し、分からなければ 0 にする
+// ============================================
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+
+use crate::save_data::{QuestionOutcome, TypeRecord};
+use crate::settings::{SessionIntent, TimingPolicy};
+
+/// 取り込む CSV の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// e-typing の成績 CSV
+    Etyping,
+    /// 見出し行のある CSV（列の対応を `--map` で指定する）
+    Csv,
+}
+
+impl ImportFormat {
+    /// 記録の日本語の欄に入れる名前（お題の列がないとき）
+    fn source_label(self) -> &'static str {
+        match self {
+            ImportFormat::Etyping => "e-typing",
+            ImportFormat::Csv => "CSV",
+        }
+    }
+}
+
+/// 取り込みに使う項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Timestamp,
+    Text,
+    Chars,
+    Time,
+    Misses,
+    Accuracy,
+    Score,
+    Cps,
+    /// 1分あたりの打鍵数（CPS に直す）
+    Kpm,
+}
+
+impl Field {
+    const ALL: [Field; 9] = [
+        Field::Timestamp,
+        Field::Text,
+        Field::Chars,
+        Field::Time,
+        Field::Misses,
+        Field::Accuracy,
+        Field::Score,
+        Field::Cps,
+        Field::Kpm,
+    ];
+
+    /// `--map` で使う名前
+    fn key(self) -> &'static str {
+        match self {
+            Field::Timestamp => "date",
+            Field::Text => "text",
+            Field::Chars => "chars",
+            Field::Time => "time",
+            Field::Misses => "misses",
+            Field::Accuracy => "accuracy",
+            Field::Score => "score",
+            Field::Cps => "cps",
+            Field::Kpm => "kpm",
+        }
+    }
+}
+
+/// e-typing の CSV の見出し
+const ETYPING_COLUMNS: [(Field, &str); 7] = [
+    (Field::Timestamp, "日時"),
+    (Field::Score, "スコア"),
+    (Field::Time, "入力時間"),
+    (Field::Chars, "入力文字数"),
+    (Field::Misses, "ミス入力数"),
+    (Field::Kpm, "WPM"),
+    (Field::Accuracy, "正確率"),
+];
+
+/// 項目と CSV の見出しの対応
+#[derive(Debug, Clone, Default)]
+pub struct ColumnMap {
+    columns: Vec<(Field, String)>,
+}
+
+impl ColumnMap {
+    /// 形式ごとの対応（`csv` は `--map` が必要）
+    pub fn for_format(format: ImportFormat, map: Option<&str>) -> Result<Self, String> {
+        match (format, map) {
+            (_, Some(spec)) => Self::parse(spec),
+            (ImportFormat::Etyping, None) => Ok(Self {
+                columns: ETYPING_COLUMNS.iter().map(|&(field, name)| (field, name.to_string())).collect(),
+            }),
+            (ImportFormat::Csv, None) => Err(format!(
+                "--format csv needs --map, e.g. --map \"date=Date,chars=Characters,time=Seconds\" (fields: {})",
+                field_keys()
+            )),
+        }
+    }
+
+    /// 「項目=見出し」をカンマで区切った指定を読む（例: "date=Date,time=Seconds,accuracy=Acc"）
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut columns = Vec::new();
+        for pair in spec.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let Some((key, column)) = pair.split_once('=') else {
+                return Err(format!("\"{}\" is not field=column", pair));
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let Some(field) = Field::ALL.into_iter().find(|field| field.key() == key) else {
+                return Err(format!("unknown field \"{}\" (fields: {})", key, field_keys()));
+            };
+            columns.retain(|(existing, _)| *existing != field);
+            columns.push((field, column.trim().to_string()));
+        }
+        if !columns.iter().any(|(field, _)| *field == Field::Timestamp) {
+            return Err("the map must include date=<column>".to_string());
+        }
+        Ok(Self { columns })
+    }
+
+    /// 見出し行から各項目の列の位置を決める
+    fn resolve(&self, header: &[String]) -> Result<Vec<(Field, usize)>, String> {
+        self.columns
+            .iter()
+            .map(|(field, name)| {
+                header
+                    .iter()
+                    .position(|column| column.trim() == name)
+                    .map(|idx| (*field, idx))
+                    .ok_or_else(|| format!("column \"{}\" ({}) is missing from the header", name, field.key()))
+            })
+            .collect()
+    }
+}
+
+fn field_keys() -> String {
+    Field::ALL.map(Field::key).join(", ")
+}
+
+/// 取り込みの結果
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub records: Vec<TypeRecord>,
+    /// 読み飛ばした行 (行番号, 理由)
+    pub skipped: Vec<(usize, String)>,
+}
+
+/// CSV の全文を記録に変換する（1行目は見出し）
+pub fn parse_history_csv(text: &str, format: ImportFormat, map: &ColumnMap) -> Result<ImportSummary, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Err("the file is empty".to_string());
+    };
+    let columns = map.resolve(&split_csv_line(header)?)?;
+
+    let mut summary = ImportSummary::default();
+    for (idx, line) in lines {
+        let line_no = idx + 1;
+        match split_csv_line(line).and_then(|cells| parse_row(&cells, &columns, format)) {
+            Ok(record) => summary.records.push(record),
+            Err(reason) => summary.skipped.push((line_no, reason)),
+        }
+    }
+    Ok(summary)
+}
+
+/// 1行分の値から記録を作る
+fn parse_row(cells: &[String], columns: &[(Field, usize)], format: ImportFormat) -> Result<TypeRecord, String> {
+    let cell = |field: Field| {
+        columns
+            .iter()
+            .find(|(f, _)| *f == field)
+            .and_then(|(_, idx)| cells.get(*idx))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    let number = |field: Field| -> Result<Option<f64>, String> {
+        cell(field)
+            .map(|value| {
+                parse_number(value)
+                    .filter(|n| n.is_finite() && *n >= 0.0)
+                    .ok_or_else(|| format!("{} \"{}\" is not a number", field.key(), value))
+            })
+            .transpose()
+    };
+
+    let timestamp = match cell(Field::Timestamp) {
+        Some(value) => parse_timestamp(value).ok_or_else(|| format!("date \"{}\" is not recognized", value))?,
+        None => return Err("date is empty".to_string()),
+    };
+    let duration = match cell(Field::Time) {
+        Some(value) => Some(parse_duration(value).ok_or_else(|| format!("time \"{}\" is not recognized", value))?),
+        None => None,
+    };
+    let chars = number(Field::Chars)?;
+    let misses = number(Field::Misses)?;
+    let accuracy = number(Field::Accuracy)?.filter(|acc| *acc > 0.0 && *acc <= 100.0);
+    let score = number(Field::Score)?;
+    let cps = number(Field::Cps)?.or(number(Field::Kpm)?.map(|kpm| kpm / 60.0));
+
+    // 打鍵数・時間・CPS は2つ分かれば残りを計算する
+    let chars = chars.or_else(|| cps.zip(duration).map(|(cps, secs)| (cps * secs).round()));
+    let duration = duration.or_else(|| chars.zip(cps.filter(|cps| *cps > 0.0)).map(|(chars, cps)| chars / cps));
+    let cps = cps.or_else(|| chars.zip(duration.filter(|secs| *secs > 0.0)).map(|(chars, secs)| chars / secs));
+    // ミスの数がなければ正確率（正しい打鍵 / 全打鍵）から戻す
+    let misses =
+        misses.or_else(|| chars.zip(accuracy).map(|(chars, acc)| (chars * (100.0 / acc - 1.0)).round()));
+
+    let total_chars = chars.unwrap_or(0.0).min(f64::from(u32::MAX)) as u32;
+    let misses = misses.unwrap_or(0.0).min(f64::from(u32::MAX)) as u32;
+    if total_chars == 0 && duration.is_none() {
+        return Err("neither characters nor time could be determined".to_string());
+    }
+    let text = cell(Field::Text).map_or_else(|| format!("(imported: {})", format.source_label()), str::to_string);
+
+    Ok(TypeRecord {
+        timestamp,
+        question_japanese: text,
+        question_hiragana: String::new(),
+        total_chars,
+        duration_sec: duration.unwrap_or(0.0),
+        misses,
+        cps: cps.unwrap_or(0.0),
+        score: score.unwrap_or(0.0),
+        xp_gained: 0,
+        key_remap: "none".to_string(),
+        afk_pauses: 0,
+        components: Vec::new(),
+        question_id: None,
+        warmup: false,
+        after_cooldown: false,
+        time_attack: None,
+        keystrokes: if total_chars > 0 { total_chars.saturating_add(misses) } else { 0 },
+        imported: true,
+        timing: TimingPolicy::default(),
+        governor_ms: 0,
+        error_loss_ms: 0,
+        unit_misses: Vec::new(),
+        intent: SessionIntent::Unspecified,
+        code: false,
+        pace: Vec::new(),
+        outcome: QuestionOutcome::Completed,
+        gloss: None,
+    })
+}
+
+/// 引用符に対応した CSV の1行の分割（値の中の改行には対応しない）
+pub fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if cell.trim().is_empty() => {
+                cell.clear();
+                quoted = true;
+            }
+            ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(ch),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    cells.push(cell);
+    Ok(cells)
+}
+
+/// 数値（"97.5%"・"1,234" のような書き方も読む）
+fn parse_number(value: &str) -> Option<f64> {
+    let cleaned: String = value.chars().filter(|ch| !matches!(ch, ',' | '%' | ' ')).collect();
+    cleaned.parse().ok()
+}
+
+/// 時間（"83.4"・"1:23.4"・"1分23.4秒"・"83.4秒" のいずれか）を秒にする
+fn parse_duration(value: &str) -> Option<f64> {
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        return Some(minutes.trim().parse::<f64>().ok()? * 60.0 + seconds.trim().parse::<f64>().ok()?);
+    }
+    let value = value.trim_end_matches('秒');
+    if let Some((minutes, seconds)) = value.split_once('分') {
+        let seconds = if seconds.is_empty() { 0.0 } else { seconds.trim().parse().ok()? };
+        return Some(minutes.trim().parse::<f64>().ok()? * 60.0 + seconds);
+    }
+    value.trim().parse().ok().filter(|secs: &f64| secs.is_finite() && *secs >= 0.0)
+}
+
+/// 日時（時差の指定がなければこの PC の時刻として読む）
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    const DATETIME_FORMATS: [&str; 6] = [
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y年%m月%d日 %H:%M",
+    ];
+    const DATE_FORMATS: [&str; 2] = ["%Y/%m/%d", "%Y-%m-%d"];
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    let naive = DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_data::PlayerData;
+
+    const ETYPING_CSV: &str = include_str!("fixtures/etyping.csv");
+    const GENERIC_CSV: &str = include_str!("fixtures/generic.csv");
+    const GENERIC_MAP: &str = "date=When, text=Text, chars=Keys, time=Seconds, accuracy=Acc, cps=CPS";
+
+    fn local(text: &str) -> DateTime<Utc> {
+        parse_timestamp(text).unwrap()
+    }
+
+    fn etyping() -> ImportSummary {
+        let map = ColumnMap::for_format(ImportFormat::Etyping, None).unwrap();
+        parse_history_csv(ETYPING_CSV, ImportFormat::Etyping, &map).unwrap()
+    }
+
+    #[test]
+    fn etyping_rows_fill_in_what_the_export_leaves_out() {
+        let summary = etyping();
+        let rows: Vec<_> = summary
+            .records
+            .iter()
+            .map(|r| (r.timestamp, r.total_chars, r.duration_sec, r.misses, r.score, r.keystrokes))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (local("2024/06/03 21:15:02"), 402, 62.5, 8, 312.0, 410),
+                // ミスの数は正確率から戻す
+                (local("2024/06/04 07:30"), 370, 58.1, 9, 288.0, 379),
+                // 打鍵数は WPM と時間から求める
+                (local("2024/06/05 21:00:00"), 390, 60.0, 5, 301.0, 395),
+            ]
+        );
+        assert!((summary.records[0].cps - 386.0 / 60.0).abs() < 1e-9);
+        for record in &summary.records {
+            assert!(record.imported && !record.counts_for_bests());
+            assert_eq!((record.xp_gained, record.question_japanese.as_str()), (0, "(imported: e-typing)"));
+        }
+    }
+
+    #[test]
+    fn malformed_rows_are_skipped_with_their_line_and_reason() {
+        let summary = etyping();
+        let skipped: Vec<(usize, &str)> = summary.skipped.iter().map(|(line, why)| (*line, why.as_str())).collect();
+        assert_eq!(
+            skipped,
+            [
+                (6, "score \"abc\" is not a number"),
+                (7, "date is empty"),
+                (8, "time \"1,2\" is not recognized"),
+                (9, "date \"2024/13/40 12:00\" is not recognized"),
+                (10, "neither characters nor time could be determined"),
+                (11, "unterminated quote"),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_mapped_csv_reads_the_named_columns() {
+        let map = ColumnMap::for_format(ImportFormat::Csv, Some(GENERIC_MAP)).unwrap();
+        let summary = parse_history_csv(GENERIC_CSV, ImportFormat::Csv, &map).unwrap();
+        let rows: Vec<_> = summary
+            .records
+            .iter()
+            .map(|r| (r.timestamp, r.question_japanese.as_str(), r.total_chars, r.misses, r.cps))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap(), "hello world", 55, 0, 5.5),
+                (local("2024-06-03 10:00"), "quoted, text", 60, 7, 5.0),
+                // 時間が 0 なら CPS は分からない
+                (local("2024-06-03 11:00"), "no time", 40, 2, 0.0),
+            ]
+        );
+        assert_eq!(summary.skipped, [(5, "chars \"-5\" is not a number".to_string())]);
+    }
+
+    #[test]
+    fn column_maps_are_checked_before_reading() {
+        let error = |format, map| ColumnMap::for_format(format, map).unwrap_err();
+        assert!(error(ImportFormat::Csv, None).starts_with("--format csv needs --map"));
+        assert_eq!(error(ImportFormat::Csv, Some("date")), "\"date\" is not field=column");
+        assert!(error(ImportFormat::Csv, Some("date=When,wpm=W")).starts_with("unknown field \"wpm\""));
+        assert_eq!(error(ImportFormat::Csv, Some("chars=Keys")), "the map must include date=<column>");
+
+        // 同じ項目を2回指定したら後のほうを使う
+        let map = ColumnMap::parse("date=Nope,DATE=When").unwrap();
+        assert_eq!(parse_history_csv(GENERIC_CSV, ImportFormat::Csv, &map).unwrap().skipped.len(), 4);
+        let missing = ColumnMap::parse("date=When,time=Duration").unwrap();
+        assert_eq!(
+            parse_history_csv(GENERIC_CSV, ImportFormat::Csv, &missing).unwrap_err(),
+            "column \"Duration\" (time) is missing from the header"
+        );
+        assert_eq!(parse_history_csv("\n\n", ImportFormat::Csv, &map).unwrap_err(), "the file is empty");
+    }
+
+    #[test]
+    fn quoted_cells_may_hold_commas_and_quotes() {
+        assert_eq!(split_csv_line(r#"a,"b, c","say ""hi""",,"#).unwrap(), ["a", "b, c", "say \"hi\"", "", ""]);
+        assert_eq!(split_csv_line(r#"a,"b"#), Err("unterminated quote".to_string()));
+    }
+
+    #[test]
+    fn importing_twice_adds_each_row_once_without_xp() {
+        let mut data = PlayerData::default();
+        let (level, xp) = (data.level, data.current_xp);
+        assert_eq!(data.merge_imported(etyping().records), 3);
+        assert_eq!(data.merge_imported(etyping().records), 0);
+        assert_eq!(data.history.len(), 3);
+        assert_eq!((data.level, data.current_xp), (level, xp));
+        assert_eq!(data.total_typed_chars, 402 + 370 + 390);
+        assert_eq!(data.total_practice_secs, 63 + 58 + 60);
+    }
+}
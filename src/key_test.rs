@@ -0,0 +1,321 @@
+// ============================================
+// src/key_test.rs
+// キーボードの確認画面の状態（押したキーを配列図で光らせ、配列の食い違いとキーリピートの速さを調べる）
+// 何も記録しない。入力の不具合の報告を受けたときの確認にも使う
+// ============================================
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// キーリピートの計測で、これだけ入力がなければキーを離したとみなす
+const REPEAT_RELEASE_GAP: Duration = Duration::from_millis(400);
+/// キーリピートの速さを出すのに要る入力の数（押した1回 + リピート2回）
+const REPEAT_MIN_PRESSES: usize = 3;
+
+/// 配列図の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    Ansi,
+    Jis,
+}
+
+impl KeyboardLayout {
+    pub fn next(self) -> Self {
+        match self {
+            KeyboardLayout::Ansi => KeyboardLayout::Jis,
+            KeyboardLayout::Jis => KeyboardLayout::Ansi,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyboardLayout::Ansi => "US (ANSI)",
+            KeyboardLayout::Jis => "JIS",
+        }
+    }
+
+    /// 上の段から順に (Shift なしの文字, Shift ありの文字)。同じ位置の文字が同じキー
+    /// Shift ありで文字の出ないキーは空白にしてある（JIS の 0）
+    pub fn rows(&self) -> [(&'static str, &'static str); 4] {
+        match self {
+            KeyboardLayout::Ansi => [
+                ("`1234567890-=", "~!@#$%^&*()_+"),
+                ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+                ("asdfghjkl;'", "ASDFGHJKL:\""),
+                ("zxcvbnm,./", "ZXCVBNM<>?"),
+            ],
+            KeyboardLayout::Jis => [
+                ("1234567890-^\\", "!\"#$%&'() =~|"),
+                ("qwertyuiop@[", "QWERTYUIOP`{"),
+                ("asdfghjkl;:]", "ASDFGHJKL+*}"),
+                ("zxcvbnm,./\\", "ZXCVBNM<>?_"),
+            ],
+        }
+    }
+
+    /// 文字を出したキー（Shift なしの文字で表す）。スペースはそのまま。配列図にない文字は None
+    pub fn base_key(&self, c: char) -> Option<char> {
+        if c == ' ' {
+            return Some(' ');
+        }
+        self.rows().iter().find_map(|(plain, shifted)| {
+            plain
+                .chars()
+                .zip(shifted.chars())
+                .find(|&(base, upper)| base == c || (upper != ' ' && upper == c))
+                .map(|(base, _)| base)
+        })
+    }
+
+    /// 順番に押してもらうキー（上の段の左から。同じ文字のキーは1回だけ）
+    fn sweep_keys(&self) -> Vec<char> {
+        let mut seen = HashSet::new();
+        self.rows()
+            .iter()
+            .flat_map(|(plain, _)| plain.chars())
+            .filter(|&c| seen.insert(c))
+            .collect()
+    }
+}
+
+/// キーリピートの計測結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepeatRate {
+    /// 押してからリピートが始まるまで
+    pub delay: Duration,
+    /// リピートの1秒あたりの回数
+    pub per_sec: f64,
+}
+
+impl RepeatRate {
+    /// "delay 500 ms · 30.0 keys/s"
+    pub fn summary(&self) -> String {
+        format!("delay {} ms · {:.1} keys/s", self.delay.as_millis(), self.per_sec)
+    }
+}
+
+/// キーを押し続けてもらい、同じ文字が届いた時刻を集める
+#[derive(Debug, Clone, Default)]
+struct RepeatProbe {
+    key: Option<char>,
+    presses: Vec<Instant>,
+}
+
+impl RepeatProbe {
+    /// 別のキーに変わったら、そのキーで測り直す
+    fn record(&mut self, c: char, now: Instant) {
+        if self.key != Some(c) {
+            self.key = Some(c);
+            self.presses.clear();
+        }
+        self.presses.push(now);
+    }
+
+    fn result(&self) -> Option<RepeatRate> {
+        if self.presses.len() < REPEAT_MIN_PRESSES {
+            return None;
+        }
+        let first_repeat = self.presses[1];
+        let repeating = self.presses[self.presses.len() - 1].saturating_duration_since(first_repeat);
+        Some(RepeatRate {
+            delay: first_repeat.saturating_duration_since(self.presses[0]),
+            per_sec: (self.presses.len() - 2) as f64 / repeating.as_secs_f64().max(f64::EPSILON),
+        })
+    }
+}
+
+/// キーボードの確認画面の状態
+#[derive(Debug, Clone)]
+pub struct KeyTest {
+    pub layout: KeyboardLayout,
+    /// 押したことのあるキー（Shift なしの文字）
+    pressed: HashSet<char>,
+    /// 順番に押してもらうキーのうち、いくつ済んだか
+    swept: usize,
+    /// 順番に押してもらったキーと違う文字が出たもの (押してもらったキー, 出た文字)
+    pub mismatches: Vec<(char, char)>,
+    /// 配列図にない文字
+    pub unknown: Vec<char>,
+    /// キーリピートを計測中なら、その途中経過
+    probe: Option<RepeatProbe>,
+    /// 最後に計測したキーリピート
+    pub repeat: Option<RepeatRate>,
+    /// 最後に届いたイベント（加工していない crossterm の表現）
+    pub last_event: Option<String>,
+}
+
+impl KeyTest {
+    pub fn new(layout: KeyboardLayout) -> Self {
+        Self {
+            layout,
+            pressed: HashSet::new(),
+            swept: 0,
+            mismatches: Vec::new(),
+            unknown: Vec::new(),
+            probe: None,
+            repeat: None,
+            last_event: None,
+        }
+    }
+
+    /// 配列図を切り替える（光らせたキーと食い違いの記録は配列ごとなので最初からにする）
+    pub fn switch_layout(&mut self) {
+        let last_event = self.last_event.take();
+        *self = Self { repeat: self.repeat, last_event, ..Self::new(self.layout.next()) };
+    }
+
+    pub fn is_pressed(&self, key: char) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// 次に押してもらうキー（全部済んだら None）
+    pub fn expected(&self) -> Option<char> {
+        self.layout.sweep_keys().get(self.swept).copied()
+    }
+
+    /// 順番に押してもらうキーの (済んだ数, 全体の数)
+    pub fn sweep_progress(&self) -> (usize, usize) {
+        let total = self.layout.sweep_keys().len();
+        (self.swept.min(total), total)
+    }
+
+    pub fn is_measuring_repeat(&self) -> bool {
+        self.probe.is_some()
+    }
+
+    /// キーリピートの計測を始める（計測中なら打ち切る）
+    pub fn toggle_repeat_probe(&mut self) {
+        match self.probe.take() {
+            Some(probe) => self.repeat = probe.result().or(self.repeat),
+            None => self.probe = Some(RepeatProbe::default()),
+        }
+    }
+
+    /// 文字キーの入力
+    pub fn press(&mut self, c: char, now: Instant) {
+        if let Some(probe) = self.probe.as_mut() {
+            probe.record(c, now);
+            return;
+        }
+        let base = self.layout.base_key(c);
+        match base {
+            Some(key) => {
+                self.pressed.insert(key);
+            }
+            None if !self.unknown.contains(&c) => self.unknown.push(c),
+            None => {}
+        }
+        // スペースは配列図の確認の順番に含めない
+        if c == ' ' {
+            return;
+        }
+        if let Some(expected) = self.expected() {
+            if base != Some(expected) {
+                self.mismatches.push((expected, c));
+            }
+            self.swept += 1;
+        }
+    }
+
+    /// キーリピートの計測中に入力が途切れたら、キーを離したとみなして結果を出す（毎フレーム呼ぶ）
+    pub fn finish_repeat_if_released(&mut self, now: Instant) {
+        let released = self.probe.as_ref().is_some_and(|probe| {
+            probe.presses.len() >= REPEAT_MIN_PRESSES
+                && probe.presses.last().is_some_and(|&last| now.saturating_duration_since(last) >= REPEAT_RELEASE_GAP)
+        });
+        if released {
+            self.toggle_repeat_probe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn shifted_characters_light_their_base_key() {
+        let ansi = KeyboardLayout::Ansi;
+        assert_eq!(ansi.base_key('A'), Some('a'));
+        assert_eq!(ansi.base_key('@'), Some('2'));
+        assert_eq!(ansi.base_key(' '), Some(' '));
+        assert_eq!(ansi.base_key('あ'), None);
+
+        let jis = KeyboardLayout::Jis;
+        assert_eq!(jis.base_key('@'), Some('@'));
+        assert_eq!(jis.base_key('"'), Some('2'));
+        assert_eq!(jis.base_key('_'), Some('\\'));
+        // Shift+0 で文字の出ない JIS の 0 に空白を当てない
+        assert_eq!(jis.base_key('0'), Some('0'));
+    }
+
+    #[test]
+    fn sweep_keys_are_unique_and_cover_every_row() {
+        for layout in [KeyboardLayout::Ansi, KeyboardLayout::Jis] {
+            let keys = layout.sweep_keys();
+            let unique: HashSet<char> = keys.iter().copied().collect();
+            assert_eq!(unique.len(), keys.len(), "{}", layout.label());
+            let expected: HashSet<char> = layout.rows().iter().flat_map(|(plain, _)| plain.chars()).collect();
+            assert_eq!(unique, expected, "{}", layout.label());
+        }
+    }
+
+    #[test]
+    fn a_different_character_from_the_expected_key_is_a_mismatch() {
+        let mut test = KeyTest::new(KeyboardLayout::Ansi);
+        let now = Instant::now();
+        assert_eq!(test.expected(), Some('`'));
+        test.press('`', now);
+        test.press(' ', now);
+        // US 配列のつもりで JIS のキーボードを押すと 2 の Shift が " になる
+        test.press('"', now);
+        test.press('あ', now);
+        assert_eq!(test.mismatches, vec![('1', '"'), ('2', 'あ')]);
+        assert_eq!(test.unknown, vec!['あ']);
+        assert!(test.is_pressed('`') && test.is_pressed('\'') && test.is_pressed(' '));
+        assert_eq!(test.sweep_progress().0, 3);
+
+        test.switch_layout();
+        assert_eq!(test.layout, KeyboardLayout::Jis);
+        assert!(test.mismatches.is_empty() && !test.is_pressed('`'));
+        assert_eq!(test.sweep_progress().0, 0);
+    }
+
+    #[test]
+    fn holding_a_key_measures_the_repeat_delay_and_rate() {
+        let mut test = KeyTest::new(KeyboardLayout::Ansi);
+        let start = Instant::now();
+        test.toggle_repeat_probe();
+        assert!(test.is_measuring_repeat());
+        // 別のキーに変わったら測り直す
+        test.press('x', start);
+        for at in [0, 500, 540, 580, 620] {
+            test.press('j', start + ms(1000 + at));
+        }
+        test.finish_repeat_if_released(start + ms(1700));
+        assert!(test.is_measuring_repeat());
+        test.finish_repeat_if_released(start + ms(2100));
+
+        assert!(!test.is_measuring_repeat());
+        let repeat = test.repeat.unwrap();
+        assert_eq!(repeat.delay, ms(500));
+        assert!((repeat.per_sec - 25.0).abs() < 1e-9);
+        assert_eq!(repeat.summary(), "delay 500 ms · 25.0 keys/s");
+        // 計測中のキーは配列の確認に数えない
+        assert_eq!(test.sweep_progress().0, 0);
+    }
+
+    #[test]
+    fn stopping_before_enough_repeats_keeps_the_last_result() {
+        let mut test = KeyTest::new(KeyboardLayout::Ansi);
+        test.repeat = Some(RepeatRate { delay: ms(300), per_sec: 20.0 });
+        test.toggle_repeat_probe();
+        test.press('j', Instant::now());
+        test.toggle_repeat_probe();
+        assert_eq!(test.repeat, Some(RepeatRate { delay: ms(300), per_sec: 20.0 }));
+    }
+}
@@ -0,0 +1,457 @@
+// ============================================
+// src/keybindings.rs
+// キー割り当て表（入力の振り分けとヘルプ表示の両方で使う）
+// ============================================
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// キー操作で実行するアクション
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// アプリを終了する
+    Quit,
+    /// メニューに戻る
+    Back,
+    /// 1文字戻す
+    Backspace,
+    /// 入力中の「タイピング単位」を最初から打ち直す
+    DeleteUnit,
+    /// お題の入力を最初からやり直す（タイマーとミスはそのまま）
+    ResetQuestion,
+    /// キー配列リマップの一時切り替え
+    ToggleRemap,
+    /// ヘルプの表示
+    Help,
+    /// 直前のお題のメモを編集する
+    EditNote,
+    /// 集中モードの切り替え
+    ToggleFocus,
+    /// 直前のお題をブラックリストに入れる
+    Blacklist,
+    /// 直前のお題をお気に入りに入れる / 外す
+    Bookmark,
+    /// 直前のお題の成績を表示しておく長さの切り替え
+    CycleResultDisplay,
+    /// 結果を見せている間に、直前のお題をもう一度出す
+    RetryLast,
+    /// 記録の1行の要約をクリップボードにコピーする
+    CopyRecord,
+    /// 表示する期間の切り替え
+    CycleWindow,
+    /// 表示する指標の切り替え
+    CycleMetric,
+    /// 直近の期間とその前の期間の比較の表示
+    Compare,
+    /// 1行上へ
+    ScrollUp,
+    /// 1行下へ
+    ScrollDown,
+    /// 1画面上へ
+    PageUp,
+    /// 1画面下へ
+    PageDown,
+    /// 検索語の入力を始める
+    Search,
+    /// 次の一致へ
+    NextMatch,
+    /// 前の一致へ
+    PrevMatch,
+    /// 授業モードで今の生徒が打ち始める
+    StartTurn,
+    /// 授業モードで今の生徒を休みとして飛ばす
+    SkipStudent,
+    /// キーボードの確認で配列図を切り替える
+    CycleLayout,
+    /// キーボードの確認でキーリピートの計測を始める / やめる
+    MeasureRepeat,
+}
+
+/// キー1つ分の割り当て
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+/// タイピング中のキー割り当て（これ以外の文字キーはすべて入力として扱う）
+pub const TYPING_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Quit,
+        description: "Quit",
+    },
+    KeyBinding {
+        code: KeyCode::Backspace,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Backspace,
+        description: "Delete the last typed key",
+    },
+    KeyBinding {
+        code: KeyCode::Char('w'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::DeleteUnit,
+        description: "Retype the current kana",
+    },
+    KeyBinding {
+        code: KeyCode::Char('u'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::ResetQuestion,
+        description: "Retype the whole question",
+    },
+    KeyBinding {
+        code: KeyCode::Char('x'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::Blacklist,
+        description: "Never show the last question again",
+    },
+    KeyBinding {
+        code: KeyCode::Char('b'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::Bookmark,
+        description: "Bookmark the last question (again to remove)",
+    },
+    KeyBinding {
+        code: KeyCode::Char('n'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::EditNote,
+        description: "Edit the note for the last question",
+    },
+    KeyBinding {
+        code: KeyCode::Char('a'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::RetryLast,
+        description: "Try the last question again (on the result screen)",
+    },
+    KeyBinding {
+        code: KeyCode::Char('y'),
+        modifiers: KeyModifiers::CONTROL,
+        action: Action::CopyRecord,
+        description: "Copy the last result to the clipboard (on the result screen)",
+    },
+    KeyBinding {
+        code: KeyCode::F(1),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Help,
+        description: "Show this help (pauses the timer)",
+    },
+    KeyBinding {
+        code: KeyCode::F(2),
+        modifiers: KeyModifiers::NONE,
+        action: Action::ToggleRemap,
+        description: "Toggle the key remap on/off",
+    },
+    KeyBinding {
+        code: KeyCode::F(3),
+        modifiers: KeyModifiers::NONE,
+        action: Action::ToggleFocus,
+        description: "Toggle focus mode (hide stats)",
+    },
+    KeyBinding {
+        code: KeyCode::F(4),
+        modifiers: KeyModifiers::NONE,
+        action: Action::CycleResultDisplay,
+        description: "Cycle how long the last result stays (always/until first key/timed)",
+    },
+];
+
+/// ログ画面のキー割り当て（これ以外のキーでもメニューに戻る）
+pub const LOG_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Char('/'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Search,
+        description: "Search the whole history",
+    },
+    KeyBinding {
+        code: KeyCode::Char('y'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::CopyRecord,
+        description: "Copy the latest record to the clipboard",
+    },
+    KeyBinding {
+        code: KeyCode::Char('?'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Help,
+        description: "Show this help",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Return to menu (any other key works too)",
+    },
+];
+
+/// 履歴検索画面のキー割り当て（検索語の入力中は Enter で確定、Esc で取り消し）
+pub const LOG_SEARCH_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Char('/'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Search,
+        description: "Edit the search (shows only matching records)",
+    },
+    KeyBinding {
+        code: KeyCode::Char('n'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::NextMatch,
+        description: "Jump to the next match",
+    },
+    KeyBinding {
+        code: KeyCode::Char('N'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::PrevMatch,
+        description: "Jump to the previous match",
+    },
+    KeyBinding {
+        code: KeyCode::Char('y'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::CopyRecord,
+        description: "Copy the selected record to the clipboard",
+    },
+    KeyBinding {
+        code: KeyCode::Char('?'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Help,
+        description: "Show this help",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Clear the search, then return to the log",
+    },
+    KeyBinding {
+        code: KeyCode::Char('q'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Clear the search, then return to the log",
+    },
+];
+
+/// 文書を読む画面（週報など）のキー割り当て
+pub const PAGER_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Up,
+        modifiers: KeyModifiers::NONE,
+        action: Action::ScrollUp,
+        description: "Scroll up",
+    },
+    KeyBinding {
+        code: KeyCode::Char('k'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::ScrollUp,
+        description: "Scroll up",
+    },
+    KeyBinding {
+        code: KeyCode::Down,
+        modifiers: KeyModifiers::NONE,
+        action: Action::ScrollDown,
+        description: "Scroll down",
+    },
+    KeyBinding {
+        code: KeyCode::Char('j'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::ScrollDown,
+        description: "Scroll down",
+    },
+    KeyBinding {
+        code: KeyCode::PageUp,
+        modifiers: KeyModifiers::NONE,
+        action: Action::PageUp,
+        description: "Scroll up one page",
+    },
+    KeyBinding {
+        code: KeyCode::PageDown,
+        modifiers: KeyModifiers::NONE,
+        action: Action::PageDown,
+        description: "Scroll down one page",
+    },
+    KeyBinding {
+        code: KeyCode::Char(' '),
+        modifiers: KeyModifiers::NONE,
+        action: Action::PageDown,
+        description: "Scroll down one page",
+    },
+    KeyBinding {
+        code: KeyCode::Char('?'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Help,
+        description: "Show this help",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Return to menu",
+    },
+    KeyBinding {
+        code: KeyCode::Char('q'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Return to menu",
+    },
+];
+
+/// 授業モードの準備の画面（次の生徒の名前を表示中）のキー割り当て
+pub const ROSTER_READY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Enter,
+        modifiers: KeyModifiers::NONE,
+        action: Action::StartTurn,
+        description: "Start typing",
+    },
+    KeyBinding {
+        code: KeyCode::Char(' '),
+        modifiers: KeyModifiers::NONE,
+        action: Action::StartTurn,
+        description: "Start typing",
+    },
+    KeyBinding {
+        code: KeyCode::Tab,
+        modifiers: KeyModifiers::NONE,
+        action: Action::SkipStudent,
+        description: "Skip this student (absent)",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Quit,
+        description: "End the class and write the summary",
+    },
+];
+
+/// 授業モードで生徒が打っている間のキー割り当て（文字キーはお題の入力）
+pub const ROSTER_TYPING_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Stop and return to this student's start screen",
+    },
+    KeyBinding {
+        code: KeyCode::Backspace,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Backspace,
+        description: "Delete the last character",
+    },
+];
+
+/// 推移グラフ画面のキー割り当て
+pub const TRENDS_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Char('w'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::CycleWindow,
+        description: "Switch the window (30/90/365 days)",
+    },
+    KeyBinding {
+        code: KeyCode::Char('m'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::CycleMetric,
+        description: "Switch the metrics shown",
+    },
+    KeyBinding {
+        code: KeyCode::Char('c'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Compare,
+        description: "Compare the window with the one before it",
+    },
+    KeyBinding {
+        code: KeyCode::Char('?'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Help,
+        description: "Show this help",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Return to menu",
+    },
+    KeyBinding {
+        code: KeyCode::Char('q'),
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Return to menu",
+    },
+];
+
+/// キーボードの確認画面のキー割り当て（文字キーはすべて確認に使う）
+pub const KEY_TEST_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        code: KeyCode::Tab,
+        modifiers: KeyModifiers::NONE,
+        action: Action::CycleLayout,
+        description: "Switch the layout (US/JIS)",
+    },
+    KeyBinding {
+        code: KeyCode::F(5),
+        modifiers: KeyModifiers::NONE,
+        action: Action::MeasureRepeat,
+        description: "Measure key repeat (hold any key)",
+    },
+    KeyBinding {
+        code: KeyCode::Esc,
+        modifiers: KeyModifiers::NONE,
+        action: Action::Back,
+        description: "Go back without recording anything",
+    },
+];
+
+/// 落ち物ゲームのキー割り当て（これ以外の文字キーはすべて入力として扱う）
+pub const FALLING_WORDS_BINDINGS: &[KeyBinding] = &[KeyBinding {
+    code: KeyCode::Esc,
+    modifiers: KeyModifiers::NONE,
+    action: Action::Back,
+    description: "End the run",
+}];
+
+/// コード片の練習のキー割り当て（Tab と Enter はコード片の文字として打つので割り当てない）
+pub const CODE_DRILL_BINDINGS: &[KeyBinding] = &[KeyBinding {
+    code: KeyCode::Esc,
+    modifiers: KeyModifiers::NONE,
+    action: Action::Back,
+    description: "Back to menu",
+}];
+
+/// 観戦画面のキー割り当て
+pub const SPECTATE_BINDINGS: &[KeyBinding] = &[KeyBinding {
+    code: KeyCode::Esc,
+    modifiers: KeyModifiers::NONE,
+    action: Action::Back,
+    description: "Stop watching",
+}];
+
+/// 押されたキーに割り当てられたアクションを探す（Shift の有無は区別しない）
+pub fn lookup(bindings: &[KeyBinding], key: &KeyEvent) -> Option<Action> {
+    let modifiers = key.modifiers.difference(KeyModifiers::SHIFT);
+    bindings
+        .iter()
+        .find(|binding| binding.code == key.code && binding.modifiers == modifiers)
+        .map(|binding| binding.action)
+}
+
+/// ヘルプ表示用のキー名 (例: "Ctrl+W", "F1", "Esc")
+pub fn key_label(binding: &KeyBinding) -> String {
+    let key = match binding.code {
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) if binding.modifiers.contains(KeyModifiers::CONTROL) => {
+            c.to_ascii_uppercase().to_string()
+        }
+        KeyCode::Char(c) => c.to_string(),
+        other => format!("{:?}", other),
+    };
+
+    if binding.modifiers.contains(KeyModifiers::CONTROL) {
+        format!("Ctrl+{}", key)
+    } else {
+        key
+    }
+}
@@ -0,0 +1,252 @@
+// ============================================
+// src/locale.rs
+// 表示言語に合わせた日付・時間・数の書き方
+// 画面やレポートに日時や大きな数を出すときは、ここの関数を通す（CSV や JSON に書く値は `machine_format` を使う）
+// ============================================
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 表示言語（日付や数の書き方に使う）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UiLanguage {
+    #[default]
+    En,
+    Ja,
+}
+
+impl UiLanguage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            UiLanguage::En => "English",
+            UiLanguage::Ja => "日本語",
+        }
+    }
+
+    /// 言語コード（お題の訳を選ぶのに使う）
+    pub fn code(&self) -> &'static str {
+        match self {
+            UiLanguage::En => "en",
+            UiLanguage::Ja => "ja",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            UiLanguage::En => UiLanguage::Ja,
+            UiLanguage::Ja => UiLanguage::En,
+        }
+    }
+}
+
+/// 日本語の書き方にするか
+static JAPANESE: AtomicBool = AtomicBool::new(false);
+
+/// 表示言語を決める（起動時と、設定を変えたときに呼ぶ）
+pub fn set_language(language: UiLanguage) {
+    JAPANESE.store(language == UiLanguage::Ja, Ordering::Relaxed);
+}
+
+fn language() -> UiLanguage {
+    if JAPANESE.load(Ordering::Relaxed) { UiLanguage::Ja } else { UiLanguage::En }
+}
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// 月日（ja: "6月3日" / en: "Jun 3"）
+pub fn format_month_day(date: NaiveDate) -> String {
+    month_day(language(), date)
+}
+
+/// 年月日（ja: "2024年6月3日" / en: "Jun 3, 2024"）
+pub fn format_date(date: NaiveDate) -> String {
+    full_date(language(), date)
+}
+
+/// ローカル時刻の月日と時刻（ja: "6月3日 14:05" / en: "Jun 3, 14:05"）
+pub fn format_timestamp(at: DateTime<Utc>) -> String {
+    timestamp(language(), at)
+}
+
+/// ローカル時刻の年月日と時刻（ja: "2024年6月3日 14:05" / en: "Jun 3, 2024 14:05"）
+pub fn format_full_timestamp(at: DateTime<Utc>) -> String {
+    full_timestamp(language(), at)
+}
+
+fn month_day(language: UiLanguage, date: NaiveDate) -> String {
+    match language {
+        UiLanguage::Ja => format!("{}月{}日", date.month(), date.day()),
+        UiLanguage::En => format!("{} {}", MONTHS[date.month0() as usize], date.day()),
+    }
+}
+
+fn full_date(language: UiLanguage, date: NaiveDate) -> String {
+    match language {
+        UiLanguage::Ja => format!("{}年{}", date.year(), month_day(language, date)),
+        UiLanguage::En => format!("{}, {}", month_day(language, date), date.year()),
+    }
+}
+
+fn timestamp(language: UiLanguage, at: DateTime<Utc>) -> String {
+    let local = at.with_timezone(&Local);
+    let time = local.format("%H:%M");
+    match language {
+        UiLanguage::Ja => format!("{} {}", month_day(language, local.date_naive()), time),
+        UiLanguage::En => format!("{}, {}", month_day(language, local.date_naive()), time),
+    }
+}
+fn full_timestamp(language: UiLanguage, at: DateTime<Utc>) -> String {
+    let local = at.with_timezone(&Local);
+    format!("{} {}", full_date(language, local.date_naive()), local.format("%H:%M"))
+}
+
+/// 所要時間。60 秒未満は "12.34s"、それ以上は "1m 23s"
+pub fn format_duration(secs: f64) -> String {
+    if secs < 60.0 {
+        return format!("{:.2}s", secs.max(0.0));
+    }
+    let whole = secs.round() as u64;
+    format!("{}m {:02}s", whole / 60, whole % 60)
+}
+
+/// 3桁ごとに区切った数（ja も en も "12,345"）
+pub fn format_count(value: u64) -> String {
+    let digits = value.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (idx, c) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// どのくらい前か（ja: "3日前" / en: "3d ago"）
+pub fn format_relative_time(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    relative_time(language(), then, now)
+}
+
+fn relative_time(language: UiLanguage, then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - then).num_seconds().max(0);
+    let (value, en, ja) = match secs {
+        0..60 => {
+            return match language {
+                UiLanguage::Ja => "たった今".to_string(),
+                UiLanguage::En => "just now".to_string(),
+            };
+        }
+        60..3_600 => (secs / 60, "m", "分"),
+        3_600..86_400 => (secs / 3_600, "h", "時間"),
+        86_400..2_592_000 => (secs / 86_400, "d", "日"),
+        2_592_000..31_536_000 => (secs / 2_592_000, "mo", "か月"),
+        _ => (secs / 31_536_000, "y", "年"),
+    };
+    match language {
+        UiLanguage::Ja => format!("{}{}前", value, ja),
+        UiLanguage::En => format!("{}{} ago", value, en),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn ago(language: UiLanguage, secs: i64) -> String {
+        let now = Utc::now();
+        relative_time(language, now - TimeDelta::seconds(secs), now)
+    }
+
+    #[test]
+    fn relative_time_uses_the_largest_whole_unit() {
+        let cases = [
+            (0, "just now"),
+            (59, "just now"),
+            (60, "1m ago"),
+            (3_599, "59m ago"),
+            (3_600, "1h ago"),
+            (86_399, "23h ago"),
+            (86_400, "1d ago"),
+            (3 * 86_400 + 7_200, "3d ago"),
+            (30 * 86_400, "1mo ago"),
+            (365 * 86_400, "1y ago"),
+            (800 * 86_400, "2y ago"),
+        ];
+        for (secs, expected) in cases {
+            assert_eq!(ago(UiLanguage::En, secs), expected, "{} seconds", secs);
+        }
+    }
+
+    #[test]
+    fn relative_time_in_japanese() {
+        assert_eq!(ago(UiLanguage::Ja, 10), "たった今");
+        assert_eq!(ago(UiLanguage::Ja, 3 * 86_400), "3日前");
+        assert_eq!(ago(UiLanguage::Ja, 60 * 86_400), "2か月前");
+    }
+
+    #[test]
+    fn a_time_in_the_future_is_just_now() {
+        assert_eq!(ago(UiLanguage::En, -120), "just now");
+    }
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        chrono::TimeZone::with_ymd_and_hms(&Local, year, month, day, hour, minute, 0).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn dates_in_both_languages() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        assert_eq!(month_day(UiLanguage::En, date), "Jun 3");
+        assert_eq!(month_day(UiLanguage::Ja, date), "6月3日");
+        assert_eq!(full_date(UiLanguage::En, date), "Jun 3, 2024");
+        assert_eq!(full_date(UiLanguage::Ja, date), "2024年6月3日");
+        // 月の端
+        let new_year = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let new_years_eve = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        assert_eq!(full_date(UiLanguage::En, new_year), "Jan 1, 2025");
+        assert_eq!(full_date(UiLanguage::En, new_years_eve), "Dec 31, 2024");
+        assert_eq!(month_day(UiLanguage::Ja, new_years_eve), "12月31日");
+    }
+
+    #[test]
+    fn timestamps_use_the_local_time_with_two_digit_clock() {
+        let at = local(2024, 6, 3, 14, 5);
+        assert_eq!(timestamp(UiLanguage::En, at), "Jun 3, 14:05");
+        assert_eq!(timestamp(UiLanguage::Ja, at), "6月3日 14:05");
+        assert_eq!(full_timestamp(UiLanguage::En, at), "Jun 3, 2024 14:05");
+        assert_eq!(full_timestamp(UiLanguage::Ja, at), "2024年6月3日 14:05");
+        assert_eq!(timestamp(UiLanguage::En, local(2024, 6, 3, 0, 0)), "Jun 3, 00:00");
+        assert_eq!(timestamp(UiLanguage::Ja, local(2024, 6, 3, 23, 59)), "6月3日 23:59");
+    }
+
+    #[test]
+    fn durations_switch_to_minutes_at_sixty_seconds() {
+        let cases = [
+            (0.0, "0.00s"),
+            (-1.0, "0.00s"),
+            (2.5, "2.50s"),
+            (59.994, "59.99s"),
+            (60.0, "1m 00s"),
+            (83.4, "1m 23s"),
+            (119.6, "2m 00s"),
+            (3_725.0, "62m 05s"),
+        ];
+        for (secs, expected) in cases {
+            assert_eq!(format_duration(secs), expected, "{secs}");
+        }
+    }
+
+    #[test]
+    fn counts_are_grouped_by_thousands() {
+        let cases =
+            [(0, "0"), (999, "999"), (1_000, "1,000"), (12_345, "12,345"), (100_000, "100,000"), (1_234_567, "1,234,567")];
+        for (value, expected) in cases {
+            assert_eq!(format_count(value), expected);
+        }
+        assert_eq!(format_count(u64::MAX), "18,446,744,073,709,551,615");
+    }
+}
@@ -0,0 +1,243 @@
+// ============================================
+// src/log_search.rs
+// ログ画面の履歴検索（日本語・ひらがなの部分一致）
+// 英字は大文字小文字を区別せず、かなはそのまま比べる
+// ============================================
+
+use std::ops::Range;
+
+use crossterm::event::KeyEvent;
+
+use crate::save_data::TypeRecord;
+use crate::text_input::{InputOutcome, TextInput};
+
+/// 検索語の最大文字数
+const QUERY_MAX_CHARS: usize = 40;
+
+/// 検索用に英字を小文字にしておいた履歴（バイト位置は元の文字列と同じ）
+pub struct HistoryIndex {
+    /// 履歴と同じ並びの (日本語, ひらがな)
+    keys: Vec<(String, String)>,
+}
+
+impl HistoryIndex {
+    pub fn new(history: &[TypeRecord]) -> Self {
+        Self {
+            keys: history
+                .iter()
+                .map(|r| (r.question_japanese.to_ascii_lowercase(), r.question_hiragana.to_ascii_lowercase()))
+                .collect(),
+        }
+    }
+
+    /// 検索語を含む記録の位置（新しい順）。`query` は英字を小文字にしたもの
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        (0..self.keys.len())
+            .rev()
+            .filter(|&idx| {
+                let (japanese, hiragana) = &self.keys[idx];
+                japanese.contains(query) || hiragana.contains(query)
+            })
+            .collect()
+    }
+
+    /// 記録の日本語・ひらがなの中で検索語と一致する範囲（元の文字列のバイト位置）
+    pub fn highlights(&self, idx: usize, query: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+        match self.keys.get(idx) {
+            Some((japanese, hiragana)) => (match_ranges(japanese, query), match_ranges(hiragana, query)),
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+/// `haystack` の中で `query` と一致する範囲（重ならないもの）
+pub fn match_ranges(haystack: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    haystack
+        .match_indices(query)
+        .map(|(start, matched)| start..start + matched.len())
+        .collect()
+}
+
+/// ログ画面の検索の状態
+pub struct LogSearch {
+    index: HistoryIndex,
+    pub input: TextInput,
+    /// 検索語を入力中か（入力中は一致した記録だけを表示する）
+    pub editing: bool,
+    /// 一致した記録の履歴での位置（新しい順）
+    pub matches: Vec<usize>,
+    /// 選んでいる一致（`matches` の位置）
+    pub selected: usize,
+}
+
+impl LogSearch {
+    /// 検索語の入力から始める
+    pub fn new(history: &[TypeRecord]) -> Self {
+        Self {
+            index: HistoryIndex::new(history),
+            input: TextInput::new("", QUERY_MAX_CHARS),
+            editing: true,
+            matches: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// 英字を小文字にした検索語
+    pub fn query(&self) -> String {
+        self.input.value().to_ascii_lowercase()
+    }
+
+    pub fn highlights(&self, idx: usize) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+        self.index.highlights(idx, &self.query())
+    }
+
+    /// 選んでいる記録の履歴での位置
+    pub fn selected_record(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    /// 検索語の入力を再開する
+    pub fn start_editing(&mut self) {
+        self.editing = true;
+    }
+
+    /// 検索語の入力中のキー
+    pub fn handle_key(&mut self, key: &KeyEvent) {
+        match self.input.handle_key(key) {
+            InputOutcome::Editing => self.refresh(),
+            InputOutcome::Submitted => self.editing = false,
+            InputOutcome::Cancelled => {
+                self.clear();
+            }
+        }
+    }
+
+    /// 貼り付け（IME で確定した文字列もここに届く）
+    pub fn handle_paste(&mut self, text: &str) {
+        self.input.insert_str(text);
+        self.refresh();
+    }
+
+    /// 次の一致へ（最後まで行ったら最初に戻る）
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    /// 前の一致へ（最初まで行ったら最後に戻る）
+    pub fn prev(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// 検索を取り消す。検索語がなかったら false（画面を閉じてよい）
+    pub fn clear(&mut self) -> bool {
+        let had_query = !self.input.value().is_empty();
+        self.input.clear();
+        self.editing = false;
+        self.refresh();
+        had_query
+    }
+
+    /// 「3 of 12」の形式（検索語がなければ None）
+    pub fn status(&self) -> Option<String> {
+        if self.input.value().is_empty() {
+            return None;
+        }
+        Some(if self.matches.is_empty() {
+            "no matches".to_string()
+        } else {
+            format!("{} of {}", self.selected + 1, self.matches.len())
+        })
+    }
+
+    fn refresh(&mut self) {
+        self.matches = self.index.search(&self.query());
+        self.selected = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn record(japanese: &str, hiragana: &str) -> TypeRecord {
+        let mut record = TypeRecord::sample(hiragana, 4, 1.0, 0);
+        record.question_japanese = japanese.to_string();
+        record
+    }
+
+    fn history() -> Vec<TypeRecord> {
+        vec![
+            record("猫", "ねこ"),
+            record("C++ の本", "しーぷらすぷらすのほん"),
+            record("子猫", "こねこ"),
+            record("犬", "いぬ"),
+        ]
+    }
+
+    fn type_query(search: &mut LogSearch, text: &str) {
+        for c in text.chars() {
+            search.handle_key(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn matches_either_text_newest_first() {
+        let index = HistoryIndex::new(&history());
+        assert_eq!(index.search("ねこ"), vec![2, 0]);
+        assert_eq!(index.search("猫"), vec![2, 0]);
+        assert_eq!(index.search("c++"), vec![1]);
+        assert_eq!(index.search("うま"), Vec::<usize>::new());
+        assert_eq!(index.search(""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn highlights_are_byte_ranges_in_the_original_text() {
+        let index = HistoryIndex::new(&history());
+        assert_eq!(index.highlights(2, "ねこ"), (Vec::new(), vec![Range { start: 3, end: 9 }]));
+        assert_eq!(index.highlights(1, "c++"), (vec![Range { start: 0, end: 3 }], Vec::new()));
+        assert_eq!(index.highlights(9, "ねこ"), (Vec::new(), Vec::new()));
+        assert_eq!(match_ranges("ぷらすぷらす", "ぷら"), vec![0..6, 9..15]);
+    }
+
+    #[test]
+    fn typing_narrows_the_matches_and_cycling_wraps() {
+        let mut search = LogSearch::new(&history());
+        assert_eq!(search.status(), None);
+        type_query(&mut search, "C");
+        assert_eq!(search.matches, vec![1]);
+        search.handle_key(&KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        search.handle_paste("ねこ");
+        assert_eq!(search.status().as_deref(), Some("1 of 2"));
+        assert_eq!(search.selected_record(), Some(2));
+
+        search.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(!search.editing);
+        search.next();
+        assert_eq!(search.selected_record(), Some(0));
+        search.next();
+        assert_eq!(search.selected_record(), Some(2));
+        search.prev();
+        assert_eq!(search.selected_record(), Some(0));
+    }
+
+    #[test]
+    fn clearing_reports_whether_there_was_a_query() {
+        let mut search = LogSearch::new(&history());
+        type_query(&mut search, "zz");
+        assert_eq!(search.status().as_deref(), Some("no matches"));
+        assert!(search.clear());
+        assert!(search.matches.is_empty());
+        assert!(!search.clear());
+    }
+}
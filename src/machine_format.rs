@@ -0,0 +1,169 @@
+// ============================================
+// src/machine_format.rs
+// スクリプトや表計算ソフトが読むファイル（CSV / JSON）に値を書くときの書き方
+// 画面向けの `locale` の関数は使わず、書き出す値はすべてここを通す（表示言語によって小数点や区切りが変わらないように）
+//
+// CSV の数値の桁数（小数点は常に `.`、桁区切りは付けない）:
+//   CPS       小数 2 桁  例: 5.25
+//   正確率 %  小数 1 桁  例: 97.3
+//   スコア    整数       例: 1234
+// 日付は "YYYY-MM-DD"、日時は RFC 3339 の UTC（"2024-06-03T05:04:03.250Z"）
+// JSON の数値は丸めずにそのまま書く（serde_json は元の値に読み戻せる最短の表記にする）
+// ============================================
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use std::io::{Error, ErrorKind, Result};
+
+/// CSV に書く CPS の小数の桁数
+pub const CPS_DECIMALS: usize = 2;
+/// CSV に書く正確率（%）の小数の桁数
+pub const ACCURACY_DECIMALS: usize = 1;
+
+/// 決まった桁数の小数（NaN や無限大は空欄にする）
+/// Rust の書式はロケールを見ないので、どの環境でも小数点は `.` になる
+fn fixed(value: f64, decimals: usize) -> String {
+    if value.is_finite() { format!("{:.*}", decimals, value) } else { String::new() }
+}
+
+/// CSV の CPS の欄
+pub fn csv_cps(cps: f64) -> String {
+    fixed(cps, CPS_DECIMALS)
+}
+
+/// CSV の正確率（%）の欄
+pub fn csv_accuracy(accuracy: f64) -> String {
+    fixed(accuracy, ACCURACY_DECIMALS)
+}
+
+/// CSV のスコアの欄（整数に丸める）
+pub fn csv_score(score: f64) -> String {
+    fixed(score, 0)
+}
+
+/// CSV の文字列の欄。カンマや引用符、改行を含む値は引用符で囲む
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 日付（"2024-06-03"）
+pub fn date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// 整形した JSON
+/// 日時（`DateTime<Utc>`）は chrono の直列化で RFC 3339 の UTC（末尾 "Z"）になり、日付は "YYYY-MM-DD" になる
+pub fn json_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_data::TypeRecord;
+    use chrono::{DateTime, TimeDelta, Utc};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// 小数の桁数がちょうど `decimals` で、読み戻すと半単位以内に戻ることを確かめる
+    fn assert_reparses(text: &str, value: f64, decimals: usize) {
+        assert!(!text.contains(',') && !text.contains(' '), "{text}");
+        let fraction = text.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+        assert_eq!(fraction, decimals, "{text}");
+        let parsed: f64 = text.parse().unwrap();
+        let half_unit = 0.5 / 10f64.powi(decimals as i32);
+        assert!((parsed - value).abs() <= half_unit * (1.0 + 1e-9), "{value} -> {text}");
+    }
+
+    #[test]
+    fn csv_numbers_reparse_within_their_precision() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..10_000 {
+            let cps = rng.random_range(0.0..30.0);
+            let accuracy = rng.random_range(0.0..=100.0);
+            let score = rng.random_range(0.0..1_000_000.0);
+            assert_reparses(&csv_cps(cps), cps, CPS_DECIMALS);
+            assert_reparses(&csv_accuracy(accuracy), accuracy, ACCURACY_DECIMALS);
+            assert_reparses(&csv_score(score), score, 0);
+        }
+        // 画面向けの数と違い、大きな数にも桁区切りを付けない
+        assert_eq!((csv_cps(1_234.5), csv_score(1_234_567.0)), ("1234.50".to_string(), "1234567".to_string()));
+        assert_eq!((csv_accuracy(100.0), csv_cps(0.0)), ("100.0".to_string(), "0.00".to_string()));
+    }
+
+    #[test]
+    fn non_finite_numbers_are_left_empty() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!([csv_cps(value), csv_accuracy(value), csv_score(value)], ["", "", ""]);
+        }
+    }
+
+    /// 引用符で囲まれた CSV の1欄を読み戻す
+    fn unquote(field: &str) -> String {
+        match field.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+            Some(inner) => inner.replace("\"\"", "\""),
+            None => field.to_string(),
+        }
+    }
+
+    #[test]
+    fn csv_text_fields_reparse_to_the_original() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let alphabet: Vec<char> = "ab ,\"\n\r猫ねこ".chars().collect();
+        for _ in 0..2_000 {
+            let len = rng.random_range(0..12);
+            let text: String = (0..len).map(|_| alphabet[rng.random_range(0..alphabet.len())]).collect();
+            let field = csv_field(&text);
+            assert_eq!(unquote(&field), text, "{field:?}");
+            if !field.starts_with('"') {
+                assert!(!field.contains([',', '"', '\n', '\r']));
+            }
+        }
+    }
+
+    #[test]
+    fn json_keeps_full_precision_and_utc_timestamps() {
+        let mut rng = StdRng::seed_from_u64(13);
+        let base = DateTime::parse_from_rfc3339("2024-06-03T05:04:03Z").unwrap().with_timezone(&Utc);
+        for _ in 0..500 {
+            let mut record = TypeRecord::sample("ねこ", 4, rng.random_range(0.1..30.0), 0);
+            record.cps = rng.random_range(0.0..30.0);
+            record.score = rng.random_range(0.0..1e7);
+            record.timestamp = base + TimeDelta::milliseconds(rng.random_range(0..10_000_000_000));
+
+            let json = json_pretty(&record).unwrap();
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            let timestamp = value["timestamp"].as_str().unwrap();
+            assert!(timestamp.ends_with('Z'), "{timestamp}");
+            assert_eq!(DateTime::parse_from_rfc3339(timestamp).unwrap(), record.timestamp);
+
+            // 書いた数字をそのまま読むと元の値に戻る（丸めずに書いている）
+            let fields = [("cps", record.cps), ("score", record.score), ("duration_sec", record.duration_sec)];
+            for (field, original) in fields {
+                let text = number_text(&json, field);
+                assert_eq!(text.parse::<f64>().unwrap().to_bits(), original.to_bits(), "{field}: {text}");
+            }
+            let parsed: TypeRecord = serde_json::from_str(&json).unwrap();
+            assert!((parsed.score - record.score).abs() <= record.score * f64::EPSILON);
+        }
+    }
+
+    /// 整形した JSON の中の `"field": 数` の数の部分
+    fn number_text<'a>(json: &'a str, field: &str) -> &'a str {
+        let key = format!("\"{}\": ", field);
+        let start = json.find(&key).unwrap() + key.len();
+        let rest = &json[start..];
+        &rest[..rest.find([',', '\n']).unwrap_or(rest.len())]
+    }
+
+    #[test]
+    fn dates_are_iso_regardless_of_the_display_language() {
+        assert_eq!(date(NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()), "2024-06-03");
+        assert_eq!(date(NaiveDate::from_ymd_opt(987, 12, 31).unwrap()), "0987-12-31");
+    }
+}
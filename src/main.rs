@@ -2,43 +2,249 @@
 // src/main.rs (メインファイル)
 // ============================================
 
-use std::collections::HashMap;
-use std::io::{Result, stdout};
-use std::time::{Duration, Instant};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::io::{IsTerminal, Result, Write, stdout};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use chrono::Utc;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeDelta, Utc};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use console::Term;
 use crossterm::{
     ExecutableCommand,
-    event::{self, Event, KeyCode},
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
     cursor::Hide,
+    style::Print,
 };
-use dialoguer::{theme::ColorfulTheme, Select};
-use rand::seq::SliceRandom;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 use ratatui::{
     prelude::*,
     style::{Color, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Gauge},
+    symbols::Marker,
+    widgets::{Axis, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph, Gauge, Wrap},
 };
 
 // `src/questions.rs` をモジュールとして読み込む
 mod questions;
-use questions::{QUESTIONS_LIST, Question};
+use questions::{
+    BUILTIN_PACK_ID, PoolEntry, PoolHealth, QUESTIONS_LIST, Question, QuestionId, QuestionMeta, SANITY_MAX_KEYSTROKES,
+    TIER_COUNT, USER_PACK_ID, find_question, served_packs, served_questions,
+};
+
+// かなとローマ字の対応・採点・打鍵の判定は `engine/`（typewiz-engine）にある
+use typewiz_engine::{roman_mapping, scoring};
+use roman_mapping::{canonical_keystrokes, create_roman_mapping, unsupported_chars};
+use typewiz_engine::typing::{self, CharState, KeyOutcome, parse_hiragana};
 
-// `src/roman_mapping.rs` をモジュールとして読み込む
-mod roman_mapping;
-use roman_mapping::create_roman_mapping;
+// `src/user_questions.rs` をモジュールとして読み込む
+mod user_questions;
+use user_questions::{
+    Gloss, GlossBook, QUESTION_TAGS, UserQuestion, UserQuestions, check_question, is_duplicate, parse_word_list, pick_gloss,
+    serve_user_questions,
+};
 
 // `src/save_data.rs` をモジュールとして読み込む
 mod save_data;
-use save_data::{PlayerData, TypeRecord};
+use save_data::{
+    IntegrityReport, NOTE_MAX_CHARS, PlayerData, QuestionOutcome, TypeRecord, find_stray_debug_json, get_data_dir, set_json_mirror, validate_note,
+};
+
+// `src/settings.rs` をモジュールとして読み込む
+mod settings;
+use settings::{AfkAction, Cooldown, DEFAULT_WARMUP, ErrorFlash, ResultPersistence, SessionConfig, SessionIntent, SessionMode, Settings, TimingPolicy};
+
+// `src/question_queue.rs` をモジュールとして読み込む
+mod question_queue;
+use question_queue::{DifficultyController, QuestionQueue, ROTATION_FACTORS, TierShift, base_tier_for_level};
+
+// `src/stats.rs` をモジュールとして読み込む
+mod stats;
+use stats::{CooldownComparison, IntentTotals, PercentileTable, PersonalBests, QuestionAggregate, QuestionAggregates, RotationReport, RotationRow, SessionEstimate, SessionStats, SetTotals, TODAY_COMPARE_DAYS, TodayStats, WindowComparison, build_daily_stats, downsample, format_delta, format_estimate, format_practice_time, format_secs_range, parse_window, split_runs, totals_by_intent};
+
+// `src/sentence.rs` をモジュールとして読み込む
+mod sentence;
+use sentence::Sentence;
+
+use scoring::{
+    Keystrokes, QuestionScore, ScoringPreset, TagMultipliers, XpParts, boosted_xp, cps, meets_accuracy_floor, score_question,
+};
+
+// `src/output.rs` をモジュールとして読み込む
+mod output;
+use output::{ColorMode, OutputFormat, Report, emit, init_color, out, outln};
+
+// `src/text_input.rs` をモジュールとして読み込む
+mod text_input;
+use text_input::{InputOutcome, TextInput};
+
+// `src/compact_code.rs` をモジュールとして読み込む
+mod compact_code;
+use compact_code::SessionSummary;
+
+// `src/roster.rs` をモジュールとして読み込む
+mod roster;
+use roster::{ClassSession, RosterPhase, StudentOutcome, StudentResult};
+
+// `src/xp_ledger.rs` をモジュールとして読み込む
+mod xp_ledger;
+use xp_ledger::XpSource;
+
+// `src/persist.rs` をモジュールとして読み込む
+mod persist;
+use persist::{Checkpoint, CheckpointWriter, Recovery, Tracked, install_panic_hook, load_checkpoint, load_recovery, remove_checkpoint, remove_recovery, set_snapshot};
+
+// `src/author.rs` をモジュールとして読み込む
+mod author;
+use author::{AuthorField, AuthorForm, AuthorOutcome};
+
+// `src/time_attack.rs` をモジュールとして読み込む
+mod time_attack;
+use time_attack::{Attempt, DEFAULT_ATTEMPTS as TIME_ATTACK_ATTEMPTS, TimeAttack};
+
+// `src/keybindings.rs` をモジュールとして読み込む
+mod keybindings;
+use keybindings::{Action, CODE_DRILL_BINDINGS, FALLING_WORDS_BINDINGS, KEY_TEST_BINDINGS, KeyBinding, LOG_BINDINGS, LOG_SEARCH_BINDINGS, PAGER_BINDINGS, ROSTER_READY_BINDINGS, ROSTER_TYPING_BINDINGS, SPECTATE_BINDINGS, TRENDS_BINDINGS, TYPING_BINDINGS, key_label, lookup};
+
+// `src/remap.rs` をモジュールとして読み込む
+mod remap;
+use remap::Remapper;
 
 // `src/update.rs` をモジュールとして読み込む
 mod update;
-use update::update;
+use update::{ManualUpdate, UpdateCheck, UpdateOutcome, latest_version, old_binary_path, rollback, update};
+
+// `src/result_display.rs` をモジュールとして読み込む
+mod result_display;
+use result_display::{LastResult, ResultDisplay};
+
+// `src/log_search.rs` をモジュールとして読み込む
+mod log_search;
+use log_search::LogSearch;
+
+// `src/metronome.rs` をモジュールとして読み込む
+mod metronome;
+use metronome::{BeatPhase, METRONOME_RATES, Metronome};
+
+// `src/render_model.rs` をモジュールとして読み込む
+mod render_model;
+use render_model::{AuthorModel, Overlay, QuestionView, RosterModel, TypingChrome, TypingModel};
+
+// `src/clipboard.rs` をモジュールとして読み込む
+mod clipboard;
+use clipboard::{COPY_UNSUPPORTED, copy_text, share_line};
+
+// `src/pace_profile.rs` をモジュールとして読み込む
+mod pace_profile;
+use pace_profile::{PaceProfile, segment_pace};
+
+// `src/break_timer.rs` をモジュールとして読み込む
+mod break_timer;
+use break_timer::{BREAK_LOCK, BreakTimer};
+
+// `src/broadcast.rs` をモジュールとして読み込む
+mod broadcast;
+use broadcast::{Broadcaster, FeedUpdate, RECONNECT_INTERVAL, SpectatorFeed, SpectatorFrame};
+
+// `src/sleep_detect.rs` をモジュールとして読み込む
+mod sleep_detect;
+use sleep_detect::SleepDetector;
+
+// `src/machine_format.rs` をモジュールとして読み込む
+mod machine_format;
+
+// `src/code_drill.rs` をモジュールとして読み込む
+mod code_drill;
+use code_drill::{CodeDrill, CodeKey, display_cell, snippet_pool};
+
+// `src/bests_diff.rs` をモジュールとして読み込む
+mod bests_diff;
+use bests_diff::{BestsDiff, append_command_log};
+
+// `src/storage.rs` をモジュールとして読み込む
+mod storage;
+use storage::{FileStorage, MemoryStorage, ProfileLoader, QueuedFileStorage, Storage};
+
+// `src/confusion.rs` をモジュールとして読み込む
+mod confusion;
+use confusion::{Confusion, TOP_CONFUSIONS};
+
+// `src/question_split.rs` をモジュールとして読み込む
+mod question_split;
+use question_split::{LengthLimit, fit_question};
+
+// `src/falling_words.rs` をモジュールとして読み込む
+mod falling_words;
+use falling_words::{FIELD_ROWS, FallingWords, MAX_DROPS};
+
+// `src/locale.rs` をモジュールとして読み込む
+mod locale;
+use locale::{format_count, format_date, format_duration, format_full_timestamp, format_month_day, format_relative_time, format_timestamp, set_language};
+
+// `src/study_sheet.rs` をモジュールとして読み込む
+mod study_sheet;
+use study_sheet::StudySheet;
+
+// `src/key_test.rs` をモジュールとして読み込む
+mod key_test;
+use key_test::{KeyTest, KeyboardLayout};
+
+// `src/error_cost.rs` をモジュールとして読み込む
+mod error_cost;
+use error_cost::KeyTimeline;
+
+// `src/schedule.rs` をモジュールとして読み込む
+mod schedule;
+use schedule::{DayPlan, Schedule};
+
+// `src/baseline.rs` をモジュールとして読み込む
+mod baseline;
+use baseline::{BaselineRank, expected_secs};
+
+// `src/frame_pacing.rs` をモジュールとして読み込む
+mod frame_pacing;
+use frame_pacing::{ANIMATION_FPS_OPTIONS, FramePacer, Pace, TICK_RATE_OPTIONS};
+
+// `src/recommender.rs` をモジュールとして読み込む
+mod recommender;
+use recommender::{KeyTally, Recommendation, StatProfile, recommend};
+
+// `src/streaming_stats.rs` をモジュールとして読み込む
+mod streaming_stats;
+
+// `src/debug_log.rs` をモジュールとして読み込む
+mod debug_log;
+use debug_log::dlog;
+
+// `src/completion_sweep.rs` をモジュールとして読み込む
+mod completion_sweep;
+use completion_sweep::CompletionSweep;
+
+// `src/history_import.rs` をモジュールとして読み込む
+mod history_import;
+use history_import::{ColumnMap, ImportFormat, parse_history_csv};
+
+// `src/typing_layout.rs` をモジュールとして読み込む
+mod typing_layout;
+use typing_layout::{TypingContent, TypingPhase, typing_layout, wrapped_height};
+
+// `src/weekly_report.rs` をモジュールとして読み込む
+mod weekly_report;
+
+// `src/achievements.rs` をモジュールとして読み込む
+mod achievements;
+use achievements::{ACHIEVEMENTS, Achievement, newly_earned};
+
+// `src/packs.rs` をモジュールとして読み込む
+mod packs;
+use packs::{HttpDownload, InstalledPack, fetch, installed_packs, packs_dir, serve_installed_packs, update_all};
 
 // --------------------------------------------------
 // アプリケーションモード
@@ -49,6 +255,15 @@ enum AppMode {
     Menu,
     Typing,
     Log,
+    Trends,
+    Achievements,
+    WeeklyReport,
+    Today,
+    Settings,
+    Picker,
+    Author,
+    FallingWords,
+    CodeDrill,
     Exit,
 }
 
@@ -60,663 +275,8407 @@ enum AppMode {
 struct Cli {
     #[command(subcommand,)]
     command: Option<Commands>,
+    /// 結果の出力形式（log, doctor で有効。指定すると log は画面を開かずに出力する）
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+    /// 色の使い方（auto は端末のときだけ。NO_COLOR が設定されていれば付けない）
+    #[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+    /// 色を付けずに出力する（`--color never` と同じ）
+    #[arg(long, global = true)]
+    plain: bool,
+    /// デバッグログを書き出すファイル（環境変数 TYPEWIZ_LOG でも指定できる）
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// タイピング画面をこのポートで配信する（別の PC から `spectate` で見る）
+    #[arg(long, value_name = "PORT")]
+    broadcast: Option<u16>,
+    /// 配信で待ち受けるアドレス（既定は localhost。ほかの PC から直接つなぐときは 0.0.0.0 など）
+    #[arg(long, value_name = "ADDR", requires = "broadcast", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    broadcast_bind: IpAddr,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// タイピングゲームを開始
     #[command(visible_aliases = ["S","s"])]
-    Start,
+    /// フラグを省いた項目は前回の設定を使い、指定した値は次回のために覚えておく
+    Start {
+        /// 出題範囲の量と所要時間の見積もりを表示して終了する
+        #[arg(long)]
+        dry_run: bool,
+        /// セッションの種類
+        #[arg(long, value_enum)]
+        mode: Option<SessionMode>,
+        /// 最初に出すお題の ID（16進数。タイムアタックの対象）
+        #[arg(long, value_name = "ID")]
+        question: Option<QuestionId>,
+        /// 難易度の自動調整を有効にする
+        #[arg(long, overrides_with = "no_adaptive")]
+        adaptive: bool,
+        /// 難易度の自動調整を無効にする
+        #[arg(long, overrides_with = "adaptive")]
+        no_adaptive: bool,
+        /// 曜日ごとの出題範囲（設定の schedule）を使わず、すべてのお題から出題する
+        #[arg(long)]
+        no_schedule: bool,
+    },
     /// ゲームログを表示
     #[command(visible_aliases = ["L","l"])]
-    Log,
+    Log {
+        /// この目的（warm-up / test / learning / unspecified）のセッションの記録だけを表示する
+        #[arg(long, value_enum)]
+        intent: Option<SessionIntent>,
+    },
+    /// データの保存場所を表示
+    Where,
+    /// 最新版に更新する
+    Update {
+        /// 1つ前のバージョンに戻す
+        #[arg(long)]
+        rollback: bool,
+    },
+    /// 累計の成績を表示
+    Stats(StatsArgs),
+    /// セーブデータの整合性をチェック
+    Doctor {
+        /// 代わりに出題範囲（使われない辞書・出題されない／長すぎる／読みが重複するお題）を診断する
+        #[arg(long)]
+        pool: bool,
+        /// 代わりにデバッグログの末尾 100 行を表示する
+        #[arg(long, conflicts_with = "pool")]
+        tail_log: bool,
+    },
+    /// 履歴からレベル・経験値・累計値を再計算
+    Recompute {
+        /// 確認なしで再計算結果を保存する
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 重なっている記録を1件だけ残して取り除き、累計値を再計算（元のセーブはバックアップする）
+    Dedupe {
+        /// 見つかった重複を表示するだけで、セーブは変更しない
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 履歴のスコアと経験値を指定した採点方式で再計算（元のセーブはバックアップする）
+    Rescore {
+        /// 採点方式
+        #[arg(long, value_enum, default_value = "current")]
+        preset: ScoringPreset,
+        /// ベストが入れ替わるときも確認せずに保存する
+        #[arg(long)]
+        yes: bool,
+    },
+    /// ユーザーのお題を管理
+    Questions {
+        #[command(subcommand)]
+        command: QuestionsCommand,
+    },
+    /// URL から取り込むお題のパックを管理
+    Packs {
+        #[command(subcommand)]
+        command: PacksCommand,
+    },
+    /// 1日分の成績を書き写せるコードにして出力
+    /// `--study-sheet` のときは、お題ごとの成績をまとめた Markdown の学習シートを書き出す
+    Export {
+        /// 短いコード（Base32・チェックサム付き）で出力する
+        #[arg(long)]
+        compact_code: bool,
+        /// 学習シート（Markdown）の書き出し先
+        #[arg(long, value_name = "FILE", conflicts_with = "compact_code")]
+        study_sheet: Option<PathBuf>,
+        /// コードに入れる名前（省略時はユーザー名）
+        #[arg(long)]
+        name: Option<String>,
+        /// 対象の日付（YYYY-MM-DD、省略時は今日）
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+    /// 書き写したコードを読み取り、成績表（roster.csv）に追加
+    /// ファイルを指定したときは、他のタイピングソフトの記録（CSV）を履歴に取り込む（経験値は増えない）
+    Import {
+        /// `export --compact-code` で出力したコード
+        #[arg(long, value_name = "CODE", required_unless_present = "file", conflicts_with = "file")]
+        compact_code: Option<String>,
+        /// 取り込む CSV ファイル
+        #[arg(requires = "format")]
+        file: Option<PathBuf>,
+        /// CSV の形式
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// 項目と列の見出しの対応（例: "date=Date,chars=Characters,time=Seconds,accuracy=Accuracy"）
+        /// 項目: date, text, chars, time, misses, accuracy, score, cps, kpm
+        #[arg(long, value_name = "FIELD=COLUMN,...")]
+        map: Option<String>,
+        /// 結果を表示するだけで保存しない
+        #[arg(long)]
+        dry_run: bool,
+        /// ベストが入れ替わるときも確認せずに保存する
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 授業モード：名簿の生徒が順番にその日のお題を打ち、クラスのまとめ（CSV）を書き出す
+    Roster {
+        /// 名簿ファイル（1行1人）
+        file: PathBuf,
+    },
+    /// キーボードの確認画面を開く（押したキー・配列の食い違い・キーリピートの速さ。何も記録しない）
+    KeyTest,
+    /// `--broadcast` で配信しているタイピング画面を読み取り専用で表示する
+    Spectate {
+        /// 配信している PC のアドレス（例: 192.168.0.10:7777）
+        addr: String,
+    },
+    /// シェル補完スクリプトを出力
+    Completions {
+        /// 対象のシェル
+        #[arg(value_enum)]
+        shell: Shell,
+    },
 }
 
-// --------------------------------------------------
-// データ構造
-// --------------------------------------------------
+/// `stats` のフラグ
+#[derive(Args)]
+struct StatsArgs {
+    /// 経験値の入手元ごとの月別の内訳も表示する
+    #[arg(long)]
+    xp: bool,
+    /// 休憩をはさんだ直後のお題とそれ以外の成績を比べる
+    #[arg(long)]
+    cooldowns: bool,
+    /// 直近の期間とその前の同じ長さの期間を比べる（例: 7d, 2w）
+    #[arg(long, value_name = "WINDOW", value_parser = parse_window)]
+    compare: Option<u32>,
+    /// お題ごとの出題回数と、出題の偏り（ジニ係数）を表示する
+    #[arg(long)]
+    rotation: bool,
+    /// よくあるキーの取り違え（打つべきキーの代わりに押したキー）を表示する
+    #[arg(long)]
+    confusions: bool,
+    /// セッションの目的（warm-up / test / learning / unspecified）ごとの成績を並べる
+    #[arg(long)]
+    by_intent: bool,
+    /// この目的のセッションの記録だけで、休憩・期間の比較と取り戻し時間を集計する
+    #[arg(long, value_enum)]
+    intent: Option<SessionIntent>,
+    /// コード片の練習の成績を表示する（コード片の記録は他の集計には含めない）
+    #[arg(long)]
+    code: bool,
+    /// お題の打ち始め・中盤・終盤の速さを比べる（お題全体の速さに対する割合）
+    #[arg(long)]
+    pace: bool,
+}
 
-/// 「タイピング単位」（例：「し」「きゃ」）の状態を管理する
-#[derive(Debug, Clone)]
-struct CharState {
-    _hiragana: String,          // "し" や "きゃ"
-    patterns: Vec<String>,      // ["si", "shi", "ci"]
-    current_pattern_idx: usize, // 今 "shi" を入力中など
-    typed_count: usize,         // "shi" の "s" まで入力済みなら 1
+#[derive(Subcommand)]
+enum QuestionsCommand {
+    /// 1行1語のテキストファイルからお題を取り込む（「漢字<TAB>かな」の行にも対応）
+    ImportTxt {
+        /// 取り込むファイル
+        file: PathBuf,
+        /// タブのない行は、かなをそのまま表示用の日本語にも使う
+        #[arg(long)]
+        japanese_same: bool,
+        /// 結果を表示するだけで保存しない
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// お題にメモを付ける（空文字列でメモを削除）
+    Note {
+        /// お題の ID（16進数）
+        id: QuestionId,
+        /// メモ
+        text: String,
+    },
 }
 
-impl CharState {
-    fn new(hiragana: String, patterns: Vec<String>) -> Self {
-        Self {
-            _hiragana: hiragana,
-            patterns,
-            current_pattern_idx: 0,
-            typed_count: 0,
-        }
-    }
-    
-    /// 現在アクティブなローマ字パターン（例: "shi"）を返す
-    fn current_pattern(&self) -> &str {
-        &self.patterns[self.current_pattern_idx]
-    }
-    
-    /// この CharState が完了したか（例: "shi" を3文字打ち終わったか）
-    fn is_complete(&self) -> bool {
-        self.typed_count >= self.current_pattern().len()
-    }
-    
-    /// 現在のパターンで、まだタイプしていない残りの部分（例: "hi"）
-    fn remaining(&self) -> &str {
-        &self.current_pattern()[self.typed_count..]
-    }
+#[derive(Subcommand)]
+enum PacksCommand {
+    /// URL からパックをダウンロードして入れる（取り込み元の URL を記録する）
+    Fetch {
+        /// パックのファイルの URL（http / https）
+        url: String,
+        /// パックの SHA-256（16進数）。省略すると `<URL>.sha256` があればそれで確かめる
+        #[arg(long, value_name = "HEX")]
+        sha256: Option<String>,
+    },
+    /// URL から取り込んだパックをすべて取り込み直し、変わったパックを表示する
+    Update,
+    /// 入っているパックを表示する
+    List,
 }
 
+// --------------------------------------------------
+// データ構造
+// --------------------------------------------------
+
+/// 難易度調整で比較する平均 CPS の対象件数
+const AVERAGE_CPS_WINDOW: usize = 50;
+/// 難易度変化の表示時間
+const TIER_NOTICE_DURATION: Duration = Duration::from_secs(3);
+/// セッション開始時（と自己ベスト更新後）に目標を表示する時間
+const TARGETS_CARD_DURATION: Duration = Duration::from_secs(5);
+/// 休憩を促すか判断するために見る直近の問題数（休憩後に印を付ける問題数も同じ）
+const COOLDOWN_WINDOW: usize = 5;
+/// 休憩を強制するときの長さ
+const COOLDOWN_DURATION: Duration = Duration::from_secs(10);
+/// ノーミスがこの問数続くごとに、1問あたりの連続ボーナスが 1 増える
+const STREAK_BONUS_STEP: u32 = 5;
+/// 1問あたりの連続ボーナスの上限
+const STREAK_BONUS_MAX: u32 = 5;
+/// 未保存の変更をこの時間そのままにしていたら自動で保存する
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(30);
+/// 起動時にセーブデータの読み込みを待つ時間（これより遅ければ読み込み中のままメニューを出す）
+const PROFILE_GRACE: Duration = Duration::from_millis(300);
+/// セーブデータの読み込みを待つ上限（過ぎたら一時的なデータで続ける）
+const PROFILE_LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+/// 読み込みを待つ間に回す印
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+/// タイピング中にセッションの途中経過を書き出す間隔
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+/// お題を出したときにメモを表示する時間
+const NOTE_DISPLAY_DURATION: Duration = Duration::from_secs(2);
+/// ミスタイプ時に枠を光らせる時間
+const ERROR_FLASH_DURATION: Duration = Duration::from_millis(120);
+/// 速さの制限（ガバナー）でキーを無視したときに「too fast」を表示する時間
+const GOVERNOR_FLASH_DURATION: Duration = Duration::from_millis(400);
+/// ミスタイプしたキーを、打つべきキーの前に表示しておく時間
+const ERROR_GHOST_DURATION: Duration = Duration::from_secs(1);
+/// 「NEW RECORD」の表示時間
+const RECORD_BANNER_DURATION: Duration = Duration::from_secs(3);
+/// 名簿モードの画面でキー入力を待つ長さ（タイピング画面は `FramePacer` で決める）
+const ROSTER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// `hybrid` の計り方で、お題の表示から最初のキーまでの時間として数える上限
+const HYBRID_MAX_REACTION: Duration = Duration::from_secs(2);
+
 /// MARK:アプリ全体の状態を管理する
-struct AppState<'a> {
+struct AppState {
     mode: AppMode,
     _menu_index: usize,         // メニューの選択インデックス
     
-    questions: Vec<&'a Question>,     // お題リストへの参照
-    current_question_index: usize, // 今何問目か
+    /// 出題キュー
+    queue: QuestionQueue,
+    /// セッション中の難易度調整
+    difficulty: DifficultyController,
+    /// 難易度が変化したときの表示 (変化の向き, 変化した時刻)
+    tier_notice: Option<(TierShift, Instant)>,
+    /// タイピング画面に一時的に表示するお知らせ (内容, 表示した時刻)
+    flash: Option<(String, Instant)>,
+    /// 直前に打ち終えたお題の ID（文章モードでは None）
+    last_question_id: Option<QuestionId>,
+    /// 今回のセッションの成績（ウォームアップと本番を分けて数える）
+    session: SessionStats,
+    /// 直近のお題の正確率（ウォームアップを除く）
+    recent_accuracies: VecDeque<f64>,
+    /// 休憩を促す画面を表示し始めた時刻
+    cooldown_since: Option<Instant>,
+    /// 休憩後の印を付ける残りの問題数
+    cooldown_followup: usize,
+    /// 続けて打った時間（セッションをまたいで数える）
+    break_timer: BreakTimer,
+    /// 長く打ち続けたので休憩を勧める画面を表示し始めた時刻
+    break_since: Option<Instant>,
+    /// 同じお題を続けて打つタイムアタック中なら、その進行状況
+    time_attack: Option<TimeAttack>,
+    /// 現在のモードの自己ベスト（セッション中に更新される）
+    targets: PersonalBests,
+    /// 目標の表示を始めた時刻
+    targets_shown_at: Option<Instant>,
+    /// 自己ベストを更新したときの表示 (更新した項目, 更新した時刻)
+    record_banner: Option<(Vec<&'static str>, Instant)>,
+    /// 実績を達成したときの表示 (達成した実績の名前, 達成した時刻)
+    achievement_toast: Option<(Vec<&'static str>, Instant)>,
+    /// メトロノーム（設定で目標テンポを決めたときだけ）
+    metronome: Option<Metronome>,
+    /// このセッションでノーミスが続いているお題の数
+    current_streak: u32,
+    /// 文章モード（お題をつなげて出題する）か
+    sentence_mode: bool,
+    /// 文章モードで出題中の文章
+    sentence: Option<Sentence>,
     
     /// お題を CharState に分解したリスト
     char_states: Vec<CharState>,
+    /// 出題中のお題の難易度と打鍵数（お題を読み込んだときに求める）
+    question_meta: QuestionMeta,
+    /// ユーザーのお題の訳（起動時に読み込む）
+    glosses: GlossBook,
+    /// ユーザーのお題のタグ（起動時に読み込む。タグの経験値の倍率に使う）
+    question_tags: HashMap<QuestionId, Vec<String>>,
+    /// タグごとの経験値の倍率（設定から作る）
+    tag_multipliers: TagMultipliers,
+    /// 出題中のお題の訳（訳がなければ None）
+    question_gloss: Option<Gloss>,
     /// 現在タイプ中の CharState のインデックス
     current_char_index: usize,
     
     is_error: bool,              // ミスタイプ中か
+    /// ミスタイプで枠を光らせる期限（描画の頻度によらず時間で消す）
+    error_flash_until: Option<Instant>,
+    /// 直前にミスタイプしたキーとその時刻（ミスタイプ中の間だけ、打つべきキーの前に表示する）
+    error_ghost: Option<(char, Instant)>,
+    /// 打ち終えたお題のローマ字を緑で塗る演出（次のキーで打ち切る）
+    completion_sweep: Option<CompletionSweep>,
     start_time: Option<Instant>, // タイマー開始時刻
+    /// 今のお題が（演出や重ねた画面に隠れずに）表示された時刻。打ち始める前だけ使う
+    question_shown_at: Option<Instant>,
+
+    /// 最後にキー入力があった時刻（放置検出用）
+    last_key_time: Option<Instant>,
+    /// 一時停止を開始した時刻（一時停止中のみ Some）
+    paused_since: Option<Instant>,
+    /// 現在のお題で一時停止していた合計時間
+    paused_duration: Duration,
+    /// 放置を検出済みか（同じ放置を二重に数えないため）
+    is_afk: bool,
+    /// 現在のお題で放置が検出された回数
+    afk_pauses: u32,
+    /// `--broadcast` で指定した配信のアドレスとポート（タイピング画面を開いている間だけ待ち受ける）
+    broadcast: Option<(IpAddr, u16)>,
+    /// スリープからの復帰を見つける
+    sleep_detector: SleepDetector,
+    /// スリープで打ち直しにしたお題の案内（スリープしていた時間。次のキーで消す）
+    sleep_notice: Option<Duration>,
+    /// キー操作ヘルプを表示中か
+    show_help: bool,
+    /// 編集中のメモ (対象のお題, 入力欄)
+    note_editor: Option<(QuestionId, TextInput)>,
+    /// 現在のお題のメモを表示し始めた時刻
+    note_shown_at: Option<Instant>,
+    /// 直前に正しく入力されたキーとその時刻（チャタリング判定用）
+    last_hit: Option<(char, Instant)>,
+    /// このセッションでチャタリングとして無視した入力の数
+    filtered_chatter: u32,
+    /// キーの順序の入れ替わりを疑って処理を保留しているキーと、届いた時刻
+    held_key: Option<(char, Instant)>,
+    /// このセッションで順序を入れ替えて正しい入力にした回数
+    forgiven_rollovers: u32,
+    /// 速さの制限（ガバナー）で最後に受け付けたキーの時刻
+    last_accepted_key: Option<Instant>,
+    /// 現在のお題で、速さの制限で無視したキーの数
+    governor_drops: u32,
+    /// 「too fast」を表示する期限
+    governor_flash_until: Option<Instant>,
     
-    // 直前のリザルト表示用
-    last_cps: Option<f64>, // (CPS表示用)
-    last_time: Option<f64>,
-    
+    /// 直前のお題の成績の表示
+    result_display: ResultDisplay,
+
     /// 現在のお題でのミス回数
     current_misses: u32,
-    /// 直前のお題のミス回数
-    last_misses: Option<u32>,
-    /// 直前のお題のスコア
-    last_score: Option<f64>,
+    /// 現在のお題で押した正しいキーの数（パターンの切り替えや打ち直しも含む）
+    correct_keystrokes: u32,
+    /// 現在のお題で押したキーの数（読み飛ばす単位で押したキーは含まない）
+    total_keystrokes: u32,
+    /// 現在のお題でミスした位置 (CharState の番号, その中の位置) ごとのミス回数
+    miss_marks: HashMap<(usize, usize), u32>,
+    /// 現在のお題で押したキーの時刻と正誤（ミスで失った時間の見積もりに使う）
+    key_timeline: KeyTimeline,
+    /// このセッションの、打つべきだったキーごとの正解とミスの数（練習の勧めに使う）
+    key_tally: KeyTally,
+    /// 勧めの練習中・お気に入りのセッション中なら、終わったときに戻す元の出題キュー
+    drill_return: Option<QuestionQueue>,
     /// 直前に獲得した経験値
     last_xp_gained: Option<u32>,
+    /// 直前のお題の経験値の内訳（ラベル, 経験値）
+    last_xp_breakdown: Vec<(String, i64)>,
 
     /// ローマ字辞書
     roman_map: HashMap<&'static str, Vec<&'static str>>,
 
     /// プレイヤーデータ
-    player_data: PlayerData,
+    player_data: Tracked<PlayerData>,
+    /// プレイヤーデータの保存先（ふだんはファイル、`start --dry-run` ではメモリ）
+    storage: Box<dyn Storage>,
+    /// 別スレッドで読み込み中のプレイヤーデータと、待つ期限（読み終わるまでは空のデータをメモリに置く）
+    pending_profile: Option<(ProfileLoader, Instant)>,
+    /// 起動時に裏で行う更新の確認（結果はメインのスレッドが安全なときに取り出す）
+    update_check: UpdateCheck,
+
+    /// ユーザー設定
+    settings: Tracked<Settings>,
+    /// 復旧用のコピーを作ったときの (player_data, settings) の変更番号
+    snapshot_generation: (u64, u64),
+    /// セッションの途中経過を書き出すスレッド
+    checkpoint_writer: CheckpointWriter,
+    /// 最後に途中経過を書き出した時刻（セッション外では None）
+    last_checkpoint: Option<Instant>,
+    /// 前回の中断したセッションから引き継ぐ成績（次のセッションの開始時に使う）
+    resumed_session: Option<SessionStats>,
+    /// 次に始めるセッションの目的（`start_session` で聞いたもの）
+    next_intent: SessionIntent,
+    /// `log --intent` で絞った目的（メニューから開いたログでは None）
+    log_intent: Option<SessionIntent>,
+    /// キー配列リマップ
+    remapper: Remapper,
+    /// メニューに一度だけ表示するお知らせ（設定やセーブの読み込みエラーなど）
+    menu_notices: Vec<String>,
+    /// 今日の曜日の出題範囲（予定のない曜日や `start --no-schedule` では None）
+    day_plan: Option<DayPlan>,
 }
 
-impl<'a> AppState<'a> {
-    /// AppState の初期化
+impl AppState {
+    /// AppState の初期化（設定とセーブデータをファイルから読み込み、出題順をシャッフルする）
+    /// セーブデータは別スレッドで読み込む。すぐに読み終わらなければ（ネットワークドライブなど）、
+    /// 読み込み中のままメニューを出し、データが要る操作を選んだときに待つ
     fn new() -> Self {
-        let mut rng = rand::rng();
-        let mut questions: Vec<&Question> = QUESTIONS_LIST.iter().collect();
-        questions.shuffle(&mut rng);
+        let settings = Settings::load();
+        let pack_notices = serve_extra_questions();
+        let queue = QuestionQueue::from_packs(&served_packs());
+
+        let (schedule, schedule_warnings) = Schedule::from_settings(&settings.schedule);
+        let loader = ProfileLoader::start();
+        let started = Instant::now();
+        let mut state = match loader.wait_until(started + PROFILE_GRACE, || {}) {
+            Some(profile) => Self::with_profile(settings, Box::new(QueuedFileStorage::start()), queue, profile),
+            None => {
+                // 読み終わるまでは空のデータをメモリに置く（間違ってもセーブファイルを空のデータで上書きしないように）
+                let mut state = Self::with_data(settings, Box::new(MemoryStorage::default()), queue);
+                state.pending_profile = Some((loader, started + PROFILE_LOAD_TIMEOUT));
+                state
+            }
+        };
+        state.menu_notices.extend(schedule_warnings);
+        state.set_day_plan(schedule.plan_for(Local::now().date_naive()).cloned());
+        state.menu_notices.extend(pack_notices);
+        if state.pending_profile.is_none() {
+            state.generate_weekly_report();
+        }
+        state
+    }
+
+    /// 渡された設定・保存先・出題キューから AppState を作る
+    /// セーブデータは渡された保存先からだけ読み書きし、乱数も使わないので、同じ入力からは同じ状態になる
+    /// （リマップの設定が custom のときだけリマップファイルを読む）
+    fn with_data(settings: Settings, storage: Box<dyn Storage>, queue: QuestionQueue) -> Self {
+        let profile = storage.load_with_report();
+        Self::with_profile(settings, storage, queue, profile)
+    }
+
+    /// 読み込み済みのセーブデータから AppState を作る
+    fn with_profile(
+        settings: Settings,
+        storage: Box<dyn Storage>,
+        queue: QuestionQueue,
+        (player_data, integrity): (PlayerData, IntegrityReport),
+    ) -> Self {
+        let (remapper, remap_warning) =
+            Remapper::new(settings.key_remap, settings.custom_remap_file.as_deref());
+        let roman_map = create_roman_mapping();
+
+        let mut menu_notices: Vec<String> = remap_warning.into_iter().collect();
+        if let Some(notice) = integrity_notice(&integrity) {
+            menu_notices.insert(0, notice);
+        }
+        let untypable = served_questions()
+            .filter(|(_, q)| !unsupported_chars(&roman_map, q.hiragana).is_empty())
+            .count();
+        if untypable > 0 {
+            menu_notices.push(format!(
+                "{} question(s) contain kana without a romaji mapping. Press Space to skip those kana while typing.",
+                untypable
+            ));
+        }
+        let (tag_multipliers, multiplier_warnings) =
+            TagMultipliers::from_settings(&settings.tag_xp_multipliers, settings.tag_xp_multiplier_cap);
+        menu_notices.extend(multiplier_warnings);
+        let user_questions = UserQuestions::load().unwrap_or_default();
 
         let mut state = Self {
             mode: AppMode::Menu,
             _menu_index: 0,
             
-            questions,
-            current_question_index: 0,
+            queue,
+            difficulty: DifficultyController::default(),
+            tier_notice: None,
+            flash: None,
+            last_question_id: None,
+            session: SessionStats::default(),
+            recent_accuracies: VecDeque::new(),
+            cooldown_since: None,
+            cooldown_followup: 0,
+            break_timer: BreakTimer::default(),
+            break_since: None,
+            time_attack: None,
+            targets: PersonalBests::default(),
+            targets_shown_at: None,
+            record_banner: None,
+            achievement_toast: None,
+            metronome: None,
+            current_streak: 0,
+            sentence_mode: false,
+            sentence: None,
             char_states: Vec::new(),
+            question_meta: QuestionMeta::default(),
+            glosses: GlossBook::from_questions(&user_questions.questions),
+            question_tags: user_questions.tags_by_id(),
+            tag_multipliers,
+            question_gloss: None,
             current_char_index: 0,
             is_error: false,
+            error_flash_until: None,
+            error_ghost: None,
+            completion_sweep: None,
             start_time: None,
-            last_cps: None,
-            last_time: None,
+            question_shown_at: None,
+            last_key_time: None,
+            paused_since: None,
+            paused_duration: Duration::ZERO,
+            is_afk: false,
+            broadcast: None,
+            sleep_detector: SleepDetector::default(),
+            sleep_notice: None,
+            afk_pauses: 0,
+            show_help: false,
+            note_editor: None,
+            note_shown_at: None,
+            last_hit: None,
+            filtered_chatter: 0,
+            held_key: None,
+            forgiven_rollovers: 0,
+            last_accepted_key: None,
+            governor_drops: 0,
+            governor_flash_until: None,
+            result_display: ResultDisplay::default(),
             
             current_misses: 0,
-            last_misses: None,
-            last_score: None,
+            correct_keystrokes: 0,
+            total_keystrokes: 0,
+            miss_marks: HashMap::new(),
+            key_timeline: KeyTimeline::default(),
+            key_tally: KeyTally::default(),
+            drill_return: None,
             last_xp_gained: None,
+            last_xp_breakdown: Vec::new(),
+
+            roman_map,
+            player_data: Tracked::new(player_data),
+            storage,
+            pending_profile: None,
+            update_check: UpdateCheck::default(),
 
-            roman_map: create_roman_mapping(),
-            player_data: PlayerData::load(),
+            settings: Tracked::new(settings),
+            snapshot_generation: (0, 0),
+            checkpoint_writer: CheckpointWriter::default(),
+            last_checkpoint: None,
+            resumed_session: None,
+            next_intent: SessionIntent::Unspecified,
+            log_intent: None,
+            remapper,
+            menu_notices,
+            day_plan: None,
         };
+        state.queue.set_rotation(state.settings.rotation_factor, &state.player_data.serve_counts);
+        state.apply_blacklist();
         state.load_current_question();
         state
     }
-    
-    /// 現在のお題を読み込み、`char_states` に分解する
-    fn load_current_question(&mut self) {
-        let question = self.questions[self.current_question_index];
-        self.char_states = self.parse_hiragana(question.hiragana);
-        self.current_char_index = 0;
-        self.is_error = false;
-        self.current_misses = 0;
-    }
-    
-    /// ひらがな文字列を `Vec<CharState>` に分解（パース）する
-    fn parse_hiragana(&self, text: &str) -> Vec<CharState> {
-        let mut result = Vec::new();
-        let chars: Vec<char> = text.chars().collect();
-        let mut idx = 0;
-        
-        while idx < chars.len() {
-            let mut found = false;
-
-            // 3文字チェック
-            if idx + 2 < chars.len() {
-                let tri: String = chars[idx..=idx + 2].iter().collect();
-                if let Some(patterns) = self.roman_map.get(tri.as_str()) {
-                    result.push(CharState::new(
-                        tri,
-                        patterns.iter().map(|s| s.to_string()).collect(),
-                    ));
-                    idx += 3;
-                    found = true;
-                }
-            }
 
-            // 2文字チェック
-            if !found && idx + 1 < chars.len() {
-                let bi: String = chars[idx..=idx + 1].iter().collect();
-                if let Some(patterns) = self.roman_map.get(bi.as_str()) {
-                    result.push(CharState::new(
-                        bi,
-                        patterns.iter().map(|s| s.to_string()).collect(),
-                    ));
-                    idx += 2;
-                    found = true;
-                }
-            }
-
-            // 1文字チェック
-            if !found {
-                let uni = chars[idx].to_string();
-                if let Some(patterns) = self.roman_map.get(uni.as_str()) {
-                    result.push(CharState::new(
-                        uni,
-                        patterns.iter().map(|s| s.to_string()).collect(),
-                    ));
-                    idx += 1;
-                } else {
-                    idx += 1;
-                }
-            }
+    /// ユーザーのお題を読み込み直し、通常の出題キューを作り直す（お題を追加したあと）
+    fn reload_user_questions(&mut self) {
+        let (user_questions, notice) = serve_user_questions();
+        self.glosses = GlossBook::from_questions(&user_questions.questions);
+        self.question_tags = user_questions.tags_by_id();
+        self.menu_notices.extend(notice);
+        let mut queue = QuestionQueue::from_packs(&served_packs());
+        queue.set_rotation(self.settings.rotation_factor, &self.player_data.serve_counts);
+        match &mut self.drill_return {
+            Some(queue_after_drill) => *queue_after_drill = queue,
+            None => self.queue = queue,
         }
-        result
+        self.apply_blacklist();
+        self.load_current_question();
     }
 
-    /// 表示用の日本語（漢字混じり）を返す
-    fn get_current_question(&self) -> &'a Question {
-        self.questions[self.current_question_index]
-    }
-    
-    /// キー入力の処理
-    fn handle_char_input(&mut self, c: char) {
-        // タイマー開始
-        if self.start_time.is_none() {
-            self.start_time = Some(Instant::now());
-        }
-         // すべて打ち終わっている
-        if self.current_char_index >= self.char_states.len() {
+    /// 読み込み中のセーブデータが読み終わっていれば、待たずに取り込む
+    fn poll_profile(&mut self) {
+        let Some(profile) = self.pending_profile.as_ref().and_then(|(loader, _)| loader.poll()) else {
             return;
+        };
+        self.pending_profile = None;
+        self.adopt_profile(profile);
+    }
+
+    /// 裏での更新の確認の結果が届いていれば取り出し、お知らせはメニューで出す
+    /// 確認のスレッドは画面に書かないので、お知らせはいつもここ（メインのスレッド）から出る
+    fn poll_update(&mut self) {
+        if let Some(notice) = self.update_check.poll() {
+            self.menu_notices.push(notice);
         }
-        
-        let current_state = &mut self.char_states[self.current_char_index];
-        let expected_char = current_state.remaining().chars().next();
-        
-        if Some(c) == expected_char {
-            current_state.typed_count += 1;
-            self.is_error = false;
-            // 次の CharState へ
-            if current_state.is_complete() {
-                self.current_char_index += 1;
-            }
-        } else {
-            let mut found = false;
-            let typed_so_far = &current_state.current_pattern()[..current_state.typed_count];
-            
-            for (i, pattern) in current_state.patterns.iter().enumerate() {
-                if i == current_state.current_pattern_idx {
-                    continue;
-                }
-                
-                if pattern.starts_with(typed_so_far) {
-                    if Some(c) == pattern.chars().nth(current_state.typed_count) {
-                        current_state.current_pattern_idx = i;
-                        current_state.typed_count += 1;
-                        self.is_error = false;
-                        found = true;
-                        
-                        if current_state.is_complete() {
-                            self.current_char_index += 1;
-                        }
-                        break;
-                    }
-                }
-            }
+    }
 
-            if !found {
-                self.is_error = true;
-                self.current_misses += 1;
+    /// 手動で更新する（このプロセスの中で更新し終えていたら、もう一度ダウンロードはしない）
+    fn update_now(&mut self) {
+        self.poll_update();
+        match self.update_check.update_now(update) {
+            ManualUpdate::AlreadyInstalled(version) => {
+                outln!("\x1b[90m  Already updated to v{}. Restart TYPE WiZ to use it.\x1b[0m", version)
+            }
+            ManualUpdate::Ran(Ok(UpdateOutcome::UpToDate)) => outln!("  Already up to date ({}).", env!("CARGO_PKG_VERSION")),
+            ManualUpdate::Ran(Ok(UpdateOutcome::Updated { from, to })) => {
+                outln!("\x1b[32m  Updated {} -> {}. Restart TYPE WiZ to use it.\x1b[0m", from, to);
+                self.settings.edit().previous_version = Some(from);
             }
+            ManualUpdate::Ran(Err(e)) => outln!("\x1b[31m  Update failed: {}\x1b[0m", e),
         }
     }
-    
-    /// Backspace の処理
-    fn handle_backspace(&mut self) {
-        if self.current_char_index >= self.char_states.len() && self.current_char_index > 0 {
-            self.current_char_index -= 1;
+
+    /// セーブデータを読み終わるまで待って取り込む（待つ間はくるくる回る印を出す）
+    /// 期限までに読み終わらなければ、このまま一時的なデータで続ける（セーブファイルには書かない）
+    fn ensure_profile(&mut self) -> Result<()> {
+        let Some((loader, deadline)) = self.pending_profile.take() else {
+            return Ok(());
+        };
+        let mut frame = 0;
+        let profile = loader.wait_until(deadline, || {
+            out!("\r\x1b[90m  {} Loading profile…\x1b[0m", SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]);
+            let _ = stdout().flush();
+            frame += 1;
+        });
+        if frame > 0 {
+            Term::stdout().clear_line()?;
         }
-        
-        if self.current_char_index < self.char_states.len() {
-            let current = &mut self.char_states[self.current_char_index];
-            if current.typed_count > 0 {
-                current.typed_count -= 1;
-            } else if self.current_char_index > 0 {
-                self.current_char_index -= 1;
-                let prev_len = self.char_states[self.current_char_index]
-                    .current_pattern()
-                    .len();
-                self.char_states[self.current_char_index].typed_count = prev_len.saturating_sub(1);
-            }
+        match profile {
+            Some(profile) => self.adopt_profile(profile),
+            None => self.menu_notices.push(format!(
+                "The data folder did not respond within {}s, so this session uses a temporary profile that will not be saved.",
+                PROFILE_LOAD_TIMEOUT.as_secs()
+            )),
         }
-        self.is_error = false;
-    }
-    
-    /// お題をすべて打ち終わったか
-    fn is_question_complete(&self) -> bool {
-        self.current_char_index >= self.char_states.len()
+        Ok(())
     }
-    
-    /// 次のお題に進む
-    fn next_question(&mut self) {
-        if let Some(start) = self.start_time {
-            let duration = start.elapsed();
-            let duration_sec = duration.as_secs_f64();
-            let total_chars: usize = self
-                .char_states
-                .iter()
-                .map(|cs| cs.current_pattern().len())
-                .sum();
-            
-            let misses = self.current_misses;
-            let total_attempts = (total_chars as u32 + misses) as f64;
-            let accuracy = if total_attempts > 0.0 {
-                (total_chars as f64 / total_attempts) * 100.0
-            } else {
-                100.0
-            };
 
-            let mut cps = 0.0;
-            if duration_sec > 0.0 {
-                cps = total_chars as f64 / duration_sec;
-            }
+    /// 読み込んだセーブデータに差し替え、以降はセーブファイルに保存する
+    fn adopt_profile(&mut self, (player_data, integrity): (PlayerData, IntegrityReport)) {
+        self.player_data = Tracked::new(player_data);
+        self.storage = Box::new(QueuedFileStorage::start());
+        if let Some(notice) = integrity_notice(&integrity) {
+            self.menu_notices.insert(0, notice);
+        }
+        self.queue.set_rotation(self.settings.rotation_factor, &self.player_data.serve_counts);
+        self.apply_blacklist();
+        self.load_current_question();
+        self.generate_weekly_report();
+    }
 
-            let score = (cps * 100.0) * (accuracy / 100.0).powi(3) * (total_chars as f64);
+    /// ブラックリストと今日の出題範囲を出題キューに反映する（現在のお題が対象外なら次へ進む）
+    fn apply_blacklist(&mut self) {
+        self.refresh_excluded();
+        if self.queue.is_excluded(self.queue.current_id()) {
+            self.advance_queue(None);
+        }
+    }
 
-            let base_xp = total_chars as f64;
-            let skill_bonus = 1.0 + (cps / 10.0);
-            let accuracy_mod = (accuracy / 100.0).powi(3);
-            let final_xp = (base_xp * skill_bonus * accuracy_mod).round() as u32;
+    /// 出題しないお題（ブラックリストと、今日の出題範囲の外のお題）を出題キューに設定する
+    fn refresh_excluded(&mut self) {
+        let off_plan = served_questions()
+            .filter(|(_, q)| self.day_plan.as_ref().is_some_and(|plan| !plan.includes(q)))
+            .map(|(id, _)| id);
+        self.queue.set_excluded(self.player_data.blacklist.iter().copied().chain(off_plan));
+    }
 
-            self.last_cps = Some(cps);
-            self.last_time = Some(duration_sec);
-            self.last_misses = Some(misses);
-            self.last_score = Some(score);
-            self.last_xp_gained = Some(final_xp);
+    /// 今日の出題範囲を変えて出題キューに反映する
+    fn set_day_plan(&mut self, plan: Option<DayPlan>) {
+        self.day_plan = plan;
+        self.apply_blacklist();
+    }
 
-            let question = self.get_current_question();
-            let record = TypeRecord {
-                timestamp: Utc::now(),
-                question_japanese: question.japanese.to_string(),
-                question_hiragana: question.hiragana.to_string(),
-                total_chars: total_chars as u32,
-                duration_sec,
-                misses,
-                cps,
-                score,
-                xp_gained: final_xp,
+    /// 出題キューを進める（ブラックリストで出題できるお題がなくなったら知らせる）
+    /// 出題したお題は出題回数に数える
+    fn advance_queue(&mut self, tier: Option<i32>) {
+        if self.queue.advance(tier) {
+            let notice = if self.day_plan.is_some() {
+                "Every question is blacklisted or outside today's plan, so both are being ignored.".to_string()
+            } else {
+                "Every question is blacklisted, so the blacklist is being ignored.".to_string()
             };
-            self.player_data.history.push(record);
+            if !self.menu_notices.contains(&notice) {
+                self.menu_notices.push(notice);
+            }
+        }
+        let id = self.queue.current_id();
+        self.player_data.edit().record_serve(id);
+    }
+
+    /// 直前に打ち終えたお題をブラックリストに入れる / 外す
+    fn toggle_blacklist_last(&mut self) {
+        let Some(id) = self.last_question_id else {
+            return;
+        };
+        let message = if self.player_data.edit().toggle_blacklist(id) {
+            "Blacklisted the last question (Ctrl+X again to undo)"
+        } else {
+            "Removed the last question from the blacklist"
+        };
+        self.refresh_excluded();
+        self.flash = Some((message.to_string(), Instant::now()));
+    }
 
-            self.player_data.add_xp(final_xp, total_chars as u32);
-            self.player_data.total_misses += misses;
-            self.player_data.save();
+    /// 結果を見せている間に、直前に打ち終えたお題をもう一度出す（キューは進めない）
+    /// 前の回の記録はそのまま残り、やり直した回も別の記録になるので、ベストには良い方が残る
+    /// やり直しも出題の1回として数える（出題の保証の間隔と出題回数に入る）
+    fn retry_last_question(&mut self) {
+        if self.typing_phase(Instant::now()) != TypingPhase::Result || self.time_attack.is_some() {
+            return;
         }
-        
-        self.current_question_index = (self.current_question_index + 1) % self.questions.len();
+        let Some(id) = self.last_question_id else {
+            return;
+        };
+        if self.queue.current_id() == id {
+            return;
+        }
+        // ウォームアップを出し直すと残りのウォームアップが飛んでしまうので出し直さない
+        if self.player_data.history.last().is_some_and(|record| record.warmup) {
+            self.flash = Some(("Warm-up questions cannot be retried".to_string(), Instant::now()));
+            return;
+        }
+        if !self.queue.jump_to(id) {
+            return;
+        }
+        self.player_data.edit().record_serve(id);
         self.load_current_question();
         self.start_time = None;
+        self.flash = Some(("Retrying the last question".to_string(), Instant::now()));
     }
-}
-
-// --------------------------------------------------
-// MARK:メイン関数
-// --------------------------------------------------
-
-fn main() -> Result<()> {
-    let mut app_state = AppState::new();
 
-    let cli = Cli::parse();
-    match &cli.command {
-        Some(Commands::Start) =>  app_state.mode = AppMode::Typing,
-        Some(Commands::Log) => app_state.mode = AppMode::Log,
-        // デフォルトの挙動
-        None => app_state.mode = AppMode::Menu,
+    /// 結果を見せている間に、直前の記録の要約をクリップボードにコピーする
+    fn copy_last_result(&mut self) {
+        if self.typing_phase(Instant::now()) != TypingPhase::Result {
+            return;
+        }
+        let Some(record) = self.player_data.history.last() else {
+            return;
+        };
+        let message = copy_text(&share_line(record)).map_or(COPY_UNSUPPORTED, |method| method.toast());
+        self.flash = Some((message.to_string(), Instant::now()));
     }
 
-    let _ = update();
+    /// 直前に打ち終えたお題をお気に入りに入れる / 外す
+    fn toggle_bookmark_last(&mut self) {
+        let Some(id) = self.last_question_id else {
+            return;
+        };
+        let message = if self.player_data.edit().toggle_bookmark(id) {
+            "★ Bookmarked the last question (Ctrl+B again to remove)"
+        } else {
+            "Removed the last question from favorites"
+        };
+        self.flash = Some((message.to_string(), Instant::now()));
+    }
     
-    loop {
-        match app_state.mode {
-            AppMode::Menu => {
-                if !show_menu(&mut app_state)? {
-                    // falseだった時の処理
-                }
-            }
-            AppMode::Typing => {
-                run_typing_mode(&mut app_state)?;
-            }
-            AppMode::Log => {
-                show_log(&mut app_state)?;
-            }
-            AppMode::Exit => {
-                break;
-            }
+    /// 直近の正確率が下がっていたら、次のお題の前に休憩を促す
+    fn check_cooldown(&mut self, accuracy: f64) {
+        self.recent_accuracies.push_back(accuracy);
+        if self.recent_accuracies.len() > COOLDOWN_WINDOW {
+            self.recent_accuracies.pop_front();
+        }
+        if self.settings.cooldown == Cooldown::Off || self.recent_accuracies.len() < COOLDOWN_WINDOW {
+            return;
+        }
+        let average = self.recent_accuracies.iter().sum::<f64>() / COOLDOWN_WINDOW as f64;
+        if average < self.settings.cooldown_accuracy_floor {
+            self.cooldown_since = Some(Instant::now());
+            // 休憩後の成績だけで判断し直す
+            self.recent_accuracies.clear();
         }
     }
-    
-    Ok(())
-}
 
-// --------------------------------------------------
-// MARK:メニュー表示（通常スクリーン）
-// --------------------------------------------------
+    /// 休憩の残り時間（強制しない設定なら 0）
+    fn cooldown_remaining(&self) -> Duration {
+        match self.cooldown_since {
+            Some(since) if self.settings.cooldown == Cooldown::Enforce => {
+                COOLDOWN_DURATION.saturating_sub(since.elapsed())
+            }
+            _ => Duration::ZERO,
+        }
+    }
 
-fn show_menu(app_state: &mut AppState) -> Result<bool> {
-    
-    let term = Term::stdout();
+    /// 休憩の画面を閉じる（強制中で時間が残っていれば何もしない）
+    fn end_cooldown(&mut self) {
+        if self.cooldown_remaining() > Duration::ZERO {
+            return;
+        }
+        if self.cooldown_since.take().is_some() && self.settings.cooldown == Cooldown::Enforce {
+            self.cooldown_followup = COOLDOWN_WINDOW;
+        }
+        self.register_activity();
+    }
 
-    // タイトルロゴ
-    println!();
+    /// 打ち終えたお題の時間を続けて打った時間に加え、設定の時間に届いていたら次のお題の前に休憩を勧める
+    fn track_break(&mut self, duration_sec: f64) {
+        let active = Duration::try_from_secs_f64(duration_sec).unwrap_or_default();
+        if let Some(event) = self.break_timer.add(Instant::now(), active) {
+            self.player_data.edit().breaks.push(event);
+        }
+        let limit = self.settings.break_reminder_mins;
+        if limit > 0 && self.break_timer.is_due(Duration::from_secs(limit * 60)) {
+            self.break_since = Some(Instant::now());
+        }
+    }
 
-    println!("\x1b[38;5;202m    ████████\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╗   \x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██████\x1b[38;5;166m╗ \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+    /// 休憩を勧める画面を閉じられるようになるまでの残り時間
+    fn break_remaining(&self) -> Duration {
+        self.break_since.map_or(Duration::ZERO, |since| BREAK_LOCK.saturating_sub(since.elapsed()))
+    }
 
-    println!("    \x1b[38;5;166m╚══\x1b[38;5;202m██\x1b[38;5;166m╔══╝╚\x1b[38;5;202m██\x1b[38;5;166m╗ \x1b[38;5;202m██\x1b[38;5;166m╔╝\x1b[38;5;202m██\x1b[38;5;166m╔══\x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╔════╝\x1b[0m");
+    /// 休憩を勧める画面を閉じて、休憩を取ったことを記録する（時間が残っていれば何もしない）
+    fn end_break(&mut self) {
+        if self.break_remaining() > Duration::ZERO {
+            return;
+        }
+        if self.break_since.take().is_some() {
+            let event = self.break_timer.take_break();
+            self.player_data.edit().breaks.push(event);
+        }
+        self.register_activity();
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║    ╚\x1b[38;5;202m████\x1b[38;5;166m╔╝ \x1b[38;5;202m██████\x1b[38;5;166m╔╝\x1b[38;5;202m█████\x1b[38;5;166m╗  \x1b[0m");
+    /// 選んだお題のタイムアタックを始める（お題が出題範囲になければ false）
+    fn start_time_attack(&mut self, id: QuestionId, attempts: u32) -> bool {
+        if !self.queue.jump_to(id) {
+            return false;
+        }
+        let aggregates = self.player_data.question_aggregates(self.settings.accuracy_floor_for_bests);
+        let previous_best = aggregates.get(&id).map(|a| a.best_score);
+        self.time_attack = Some(TimeAttack::new(attempts, previous_best));
+        self.set_sentence_mode(false);
+        true
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║     ╚\x1b[38;5;202m██\x1b[38;5;166m╔╝  \x1b[38;5;202m██\x1b[38;5;166m╔═══╝ \x1b[38;5;202m██\x1b[38;5;166m╔══╝  \x1b[0m");
+    /// セッションを始める。始めた設定は次の Quick Start のために覚えておく
+    /// 指定のお題がもう出題範囲にないとき（お気に入りが空のとき）は、通常の出題で始め、画面で知らせる
+    /// 設定で有効なら、始める前にセッションの目的を聞き、選んだ目的を次の初期選択として覚えておく
+    fn start_session(&mut self, config: SessionConfig) -> Result<()> {
+        self.ensure_profile()?;
+        self.next_intent = SessionIntent::Unspecified;
+        if self.settings.ask_session_intent && let Some(intent) = prompt_session_intent(self.settings.last_intent)? {
+            if intent != self.settings.last_intent {
+                self.settings.edit().last_intent = intent;
+            }
+            self.next_intent = intent;
+        }
+        let mut config = config;
+        if matches!(config.mode, SessionMode::Sentence | SessionMode::Favorites) {
+            config.question = None;
+        }
+        let started = match (config.mode, config.question) {
+            (SessionMode::TimeAttack, Some(id)) => self.start_time_attack(id, TIME_ATTACK_ATTEMPTS),
+            (SessionMode::TimeAttack, None) => false,
+            (SessionMode::Favorites, _) => self.start_favorites(),
+            (_, Some(id)) => {
+                let found = self.queue.jump_to(id);
+                if found {
+                    self.set_sentence_mode(false);
+                }
+                found
+            }
+            (mode, None) => {
+                // 文章モードは毎回新しい文章にする。通常の出題で文章モードでもなければ、表示中のお題をそのまま使う
+                let sentence = mode == SessionMode::Sentence;
+                if sentence || self.sentence_mode {
+                    self.set_sentence_mode(sentence);
+                }
+                true
+            }
+        };
+        if !started {
+            let message = match (config.mode, config.question) {
+                (SessionMode::Favorites, _) => {
+                    "No favorites yet (Ctrl+B bookmarks the last question); starting the regular queue".to_string()
+                }
+                (_, Some(id)) => format!("Question {} is no longer available; starting the regular queue", id),
+                (_, None) => "No question was remembered for time attack; starting the regular queue".to_string(),
+            };
+            self.flash = Some((message, Instant::now()));
+            config = SessionConfig::default();
+            if self.sentence_mode {
+                self.set_sentence_mode(false);
+            }
+        }
+        if self.settings.last_session != config {
+            self.settings.edit().last_session = config;
+        }
+        self.mode = AppMode::Typing;
+        Ok(())
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║      \x1b[38;5;202m██\x1b[38;5;166m║   \x1b[38;5;202m██\x1b[38;5;166m║     \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+    /// 週の最初の起動なら、前の週の週報を書き出してメニューで知らせる
+    fn generate_weekly_report(&mut self) {
+        let today = Local::now().date_naive();
+        match weekly_report::generate_if_due(
+            &self.player_data.history,
+            &self.player_data.breaks,
+            self.settings.last_weekly_report.as_deref(),
+            today,
+        ) {
+            Ok(Some((label, _))) => {
+                self.menu_notices.push(format!("Weekly report {} ready — open \"Weekly Report\" to view", label));
+                self.settings.edit().last_weekly_report = Some(label);
+                self.settings.save();
+            }
+            Ok(None) => {}
+            Err(e) => self.menu_notices.push(format!("Could not write the weekly report: {}", e)),
+        }
+    }
 
-    println!("\x1b[38;5;166m       ╚═╝      ╚═╝   ╚═╝     ╚══════╝ \x1b[38;5;202mWiZ.\x1b[0m");
+    /// 直前のお題の成績を表示しておく長さを切り替える
+    fn cycle_result_persistence(&mut self) {
+        let next = self.settings.result_persistence.next();
+        self.settings.edit().result_persistence = next;
+        self.flash = Some((format!("Last result: {}", format_result_persistence(&self.settings)), Instant::now()));
+    }
 
-    println!();
+    /// メトロノームの新しい拍を迎え、ベルを鳴らすべきなら true（一時停止中とお題の外では鳴らさない）
+    fn metronome_beat(&mut self) -> bool {
+        let active = self.settings.metronome_bell && self.start_time.is_some() && !self.is_paused();
+        self.metronome.as_mut().is_some_and(|m| m.take_beat(Instant::now())) && active
+    }
 
-    // タイトルロゴ
-    println!();
+    /// お題を打ち終えたあとに実績を判定し、新しく達成したものを記録して表示する
+    fn check_achievements(&mut self) {
+        let today = Local::now().date_naive();
+        // 練習した日の連続が伸びるのは、その日の最初のお題のときだけ
+        let first_today = match self.player_data.history.len() {
+            0 => false,
+            1 => true,
+            len => {
+                let date = |record: &TypeRecord| record.timestamp.with_timezone(&Local).date_naive();
+                date(&self.player_data.history[len - 2]) != date(&self.player_data.history[len - 1])
+            }
+        };
+        let earned = newly_earned(&self.player_data, &self.session.main, today, first_today);
+        if earned.is_empty() {
+            return;
+        }
+        let now = Utc::now();
+        let player_data = self.player_data.edit();
+        for achievement in &earned {
+            player_data.award_achievement(achievement.id, now);
+        }
+        self.achievement_toast = Some((earned.iter().map(|a| a.name).collect(), Instant::now()));
+    }
 
-    println!("\x1b[38;5;202m    ████████\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╗   \x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██████\x1b[38;5;166m╗ \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+    /// タイムアタックの一番良い回にだけ経験値を与える
+    /// 途中でやめたときも、それまでの一番良い回の分を与える
+    fn settle_time_attack(&mut self) {
+        let Some(best) = self.time_attack.as_ref().and_then(TimeAttack::best).cloned() else {
+            return;
+        };
+        let player_data = self.player_data.edit();
+        if let Some(record) = player_data.history.get_mut(best.history_idx) {
+            record.xp_gained = best.xp;
+        }
+        player_data.add_xp(XpSource::QuestionCompletion, best.xp, 0);
+        self.last_xp_gained = Some(best.xp);
+        self.last_xp_breakdown = vec![("best attempt".to_string(), i64::from(best.xp))];
+        self.save_player_data();
+    }
 
-    println!("    \x1b[38;5;166m╚══\x1b[38;5;202m██\x1b[38;5;166m╔══╝╚\x1b[38;5;202m██\x1b[38;5;166m╗ \x1b[38;5;202m██\x1b[38;5;166m╔╝\x1b[38;5;202m██\x1b[38;5;166m╔══\x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╔════╝\x1b[0m");
+    /// タイムアタックの結果を閉じて、通常の出題に戻る
+    fn close_time_attack(&mut self) {
+        self.time_attack = None;
+        self.advance_queue(None);
+        self.load_current_question();
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║    ╚\x1b[38;5;202m████\x1b[38;5;166m╔╝ \x1b[38;5;202m██████\x1b[38;5;166m╔╝\x1b[38;5;202m█████\x1b[38;5;166m╗  \x1b[0m");
+    /// プレイヤーデータを保存先に書き出す
+    fn save_player_data(&mut self) {
+        self.storage.save(&self.player_data);
+        self.player_data.mark_saved();
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║     ╚\x1b[38;5;202m██\x1b[38;5;166m╔╝  \x1b[38;5;202m██\x1b[38;5;166m╔═══╝ \x1b[38;5;202m██\x1b[38;5;166m╔══╝  \x1b[0m");
+    /// 打ち終えたコード片の記録を残して保存し、画面に出す成績の1行を返す
+    /// コード片の記録は自己ベストの対象外で、お題の ID も付けない（お題ごとの集計に混ぜない）
+    fn record_code_drill(&mut self, drill: &CodeDrill) -> String {
+        let duration_sec = drill.elapsed(Instant::now()).as_secs_f64();
+        let total_chars = drill.chars().len() as u32;
+        let misses = drill.misses();
+        let keystrokes = Keystrokes { correct: drill.keystrokes.saturating_sub(misses), total: drill.keystrokes };
+        let result = score_question(ScoringPreset::Current, total_chars, duration_sec, misses, keystrokes);
+        let snippet = drill.snippet();
+        let record = TypeRecord {
+            timestamp: Utc::now(),
+            question_japanese: snippet.title.to_string(),
+            question_hiragana: snippet.text.to_string(),
+            total_chars,
+            duration_sec,
+            misses,
+            cps: result.cps,
+            score: result.score,
+            xp_gained: result.xp,
+            key_remap: self.remapper.active_label().to_string(),
+            afk_pauses: 0,
+            components: Vec::new(),
+            question_id: None,
+            warmup: false,
+            after_cooldown: false,
+            time_attack: None,
+            keystrokes: drill.keystrokes,
+            imported: false,
+            timing: TimingPolicy::FirstKey,
+            governor_ms: 0,
+            error_loss_ms: 0,
+            unit_misses: drill.char_misses(),
+            intent: SessionIntent::Unspecified,
+            code: true,
+            pace: Vec::new(),
+            outcome: QuestionOutcome::Completed,
+            gloss: None,
+        };
+        let player_data = self.player_data.edit();
+        player_data.history.push(record);
+        player_data.add_xp(XpSource::QuestionCompletion, result.xp, total_chars);
+        player_data.total_misses = player_data.total_misses.saturating_add(u64::from(misses));
+        player_data.add_practice_time(duration_sec);
+        self.storage.append_history(&self.player_data);
+        self.player_data.mark_saved();
+        format!(
+            "{}: {:.2} CPS · {:.1}% · Miss: {} · +{} XP",
+            snippet.title, result.cps, result.accuracy, misses, result.xp
+        )
+    }
 
-    println!("\x1b[38;5;202m       ██\x1b[38;5;166m║      \x1b[38;5;202m██\x1b[38;5;166m║   \x1b[38;5;202m██\x1b[38;5;166m║     \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+    /// 未保存の変更があるか
+    fn has_unsaved_changes(&self) -> bool {
+        self.player_data.is_dirty() || self.settings.is_dirty()
+    }
 
-    println!("\x1b[38;5;166m       ╚═╝      ╚═╝   ╚═╝     ╚══════╝ \x1b[38;5;202mWiZ.\x1b[0m");
+    /// しばらく保存していない変更を保存し、異常終了に備えて復旧用のコピーを更新する
+    fn autosave_if_due(&mut self) {
+        let due = |since: Option<Instant>| since.is_some_and(|at| at.elapsed() >= AUTOSAVE_DELAY);
+        if due(self.player_data.dirty_since()) {
+            self.save_player_data();
+        }
+        if due(self.settings.dirty_since()) {
+            self.settings.save();
+        }
+        self.sync_recovery();
+    }
 
-    println!();
+    /// 変更があったときだけ復旧用のコピーを作り直す
+    fn sync_recovery(&mut self) {
+        let generation = (self.player_data.generation(), self.settings.generation());
+        if generation == self.snapshot_generation {
+            return;
+        }
+        self.snapshot_generation = generation;
+        let recovery = self.has_unsaved_changes().then(|| Recovery {
+            player_data: self.player_data.is_dirty().then(|| (*self.player_data).clone()),
+            settings: self.settings.is_dirty().then(|| (*self.settings).clone()),
+        });
+        set_snapshot(recovery);
+    }
 
+    /// 終了時の処理（未保存の変更をすべて保存する）
+    fn shutdown(&mut self) {
+        if self.player_data.is_dirty() {
+            self.save_player_data();
+        }
+        self.settings.save_if_dirty();
+        set_snapshot(None);
+        self.checkpoint_writer.finish();
+    }
 
-    let items = vec![
-        "Start Type",
-        "Mission (Coming Soon...)",
-        "Game Log",
-        "Leaderboard (Coming Soon...)",
-        "Settings (Coming Soon...)",
-        "Exit",
-    ];
-    
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .items(&items)
-        .default(app_state._menu_index | 0)
-        .interact_opt()?;
+    /// タイピング中、一定の間隔でセッションの途中経過を書き出す（書き出しは別スレッド）
+    fn checkpoint_if_due(&mut self) {
+        if let Some(checkpoint) = self.due_checkpoint(Instant::now()) {
+            self.checkpoint_writer.write(checkpoint);
+        }
+    }
 
-    match selection {
-        Some(0) => {
-            app_state.mode = AppMode::Typing;
-            Ok(true)
+    /// 前のチェックポイントから間隔が空いていれば、`now` 時点の途中経過を返す（一時停止中は作らない）
+    fn due_checkpoint(&mut self, now: Instant) -> Option<Checkpoint> {
+        let due = self.last_checkpoint.is_some_and(|at| now.saturating_duration_since(at) >= CHECKPOINT_INTERVAL);
+        if self.is_paused() || !due {
+            return None;
         }
-        Some(1) => {
-            
-            app_state.mode = AppMode::Menu;
-            term.clear_screen()?;
+        self.last_checkpoint = Some(now);
+        Some(Checkpoint {
+            saved_at: Utc::now(),
+            session: self.session,
+            question_id: self.sentence.is_none().then(|| self.queue.current_id()),
+            sentence_mode: self.sentence_mode,
+            typed_units: self.current_char_index,
+            total_units: self.char_states.len(),
+        })
+    }
 
-            Ok(false)
+    /// 中断したセッションを、途中だったお題の最初から続ける
+    fn resume_from(&mut self, checkpoint: &Checkpoint) {
+        self.resumed_session = Some(checkpoint.session);
+        match checkpoint.question_id {
+            Some(id) if !checkpoint.sentence_mode && self.queue.jump_to(id) => self.set_sentence_mode(false),
+            _ => self.set_sentence_mode(checkpoint.sentence_mode),
         }
-        Some(2) => {
-            // Game Log
-            app_state.mode = AppMode::Log;
-            Ok(true)
+        self.mode = AppMode::Typing;
+    }
+
+    /// 設定変更後にリマップ表を作り直す
+    fn reload_remapper(&mut self) {
+        let (remapper, warning) = Remapper::new(
+            self.settings.key_remap,
+            self.settings.custom_remap_file.as_deref(),
+        );
+        self.remapper = remapper;
+        self.menu_notices.extend(warning);
+    }
+
+    /// タイピングのセッションを始める（現在のモードの自己ベストを目標として表示する）
+    /// 前のセッションの表示が残っていないこと（`end_session` を通ったこと）を確かめる
+    fn begin_session(&mut self) {
+        debug_assert!(self.last_xp_gained.is_none(), "previous session's XP leaked into a new session");
+        debug_assert!(
+            self.result_display.visible(Instant::now()).is_none() && self.completion_sweep.is_none(),
+            "previous session's result leaked into a new session"
+        );
+        let sentence_mode = self.sentence_mode;
+        self.targets = PersonalBests::from_records(
+            self.player_data
+                .history
+                .iter()
+                .filter(|record| record.is_sentence() == sentence_mode)
+                .filter(|record| !record.imported || self.settings.imported_in_bests)
+                // 計り方が違う記録とは比べない
+                .filter(|record| record.timing == self.settings.timing_policy),
+            self.settings.accuracy_floor_for_bests,
+        );
+        self.targets_shown_at = Some(Instant::now());
+        self.record_banner = None;
+        self.achievement_toast = None;
+        self.metronome = Metronome::new(self.settings.metronome_kpm, Instant::now());
+        self.current_streak = 0;
+        let resumed = self.resumed_session.take();
+        self.session = resumed.unwrap_or(SessionStats { intent: self.next_intent, ..SessionStats::default() });
+        self.recent_accuracies.clear();
+        self.key_tally.clear();
+        self.cooldown_since = None;
+        self.last_checkpoint = Some(Instant::now());
+        self.sleep_detector.reset();
+        self.sleep_notice = None;
+
+        // 通常モードではウォームアップのお題から始める（タイムアタック・中断したセッションの再開・勧めの練習は除く）
+        if sentence_mode || self.time_attack.is_some() || resumed.is_some() || self.drill_return.is_some() {
+            self.queue.clear_prelude();
+            return;
         }
-        Some(5) | None => {
-            // Exit or Esc
-            app_state.mode = AppMode::Exit;
-            Ok(false)
+        let skipped = self.queue.set_prelude(&self.settings.warmup);
+        if self.queue.is_warmup() {
+            self.load_current_question();
         }
-        _ => {
-            // Coming Soon...
-            // show_menu(app_state)?;
-            app_state.mode = AppMode::Menu;
-            Ok(false)
+        if !skipped.is_empty() {
+            let message = format!("Warm-up skipped: {}", skipped.join(", "));
+            self.flash = Some((message, Instant::now()));
         }
     }
-}
 
-// --------------------------------------------------
-// MARK:タイピングモード（代替スクリーン）
-// --------------------------------------------------
+    /// 文章モードを切り替えて、お題を読み込み直す
+    fn set_sentence_mode(&mut self, enabled: bool) {
+        self.sentence_mode = enabled;
+        self.sentence = enabled.then(|| self.compose_sentence());
+        self.load_current_question();
+    }
 
-fn run_typing_mode(app_state: &mut AppState) -> Result<()> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?; // 代替スクリーンを使用
-    stdout().execute(Hide)?; // カーソルを非表示
-    let backend = CrosstermBackend::new(stdout());
-    let mut terminal = Terminal::new(backend)?;
+    /// タイピング画面を離れるときの後片付け
+    /// 直前のお題の結果・演出の期限・セッション中の集計など、画面にだけ残る状態をすべて消す
+    /// （消さないと、次のセッションで打ち始めたときに前の結果が表示される）
+    fn end_session(&mut self) {
+        // セッション中に届いた更新の確認の結果は、ここでメニューのお知らせに回す
+        self.poll_update();
+        self.last_xp_gained = None;
+        self.last_xp_breakdown.clear();
+        self.result_display = ResultDisplay::default();
+        self.record_banner = None;
+        self.achievement_toast = None;
+        self.targets_shown_at = None;
+        self.tier_notice = None;
+        self.flash = None;
+        self.completion_sweep = None;
+        self.error_flash_until = None;
+        self.error_ghost = None;
+        self.governor_flash_until = None;
+        self.held_key = None;
+        self.last_hit = None;
+        self.note_shown_at = None;
+        self.show_help = false;
+        self.cooldown_since = None;
+        self.cooldown_followup = 0;
+        self.break_since = None;
+        self.metronome = None;
+        // 経験値は終了時に精算済み。残すと次のセッションで同じ回をもう一度精算してしまう
+        self.time_attack = None;
+        self.recent_accuracies.clear();
+        self.filtered_chatter = 0;
+        self.forgiven_rollovers = 0;
+        self.last_checkpoint = None;
+    }
 
-    loop {
-        terminal.draw(|f| ui_typing(f, app_state))?;
-
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Esc => {
-                            // stdout().execute(Show)?;
-                            stdout().execute(LeaveAlternateScreen)?;
-                            disable_raw_mode()?;
-                            app_state.mode = AppMode::Exit;
-                            app_state.load_current_question();
-                            return Ok(());
-                        }
-                        KeyCode::Backspace => app_state.handle_backspace(),
-                        KeyCode::Char(c) => {
-                            app_state.handle_char_input(c);
-                            if app_state.is_question_complete() {
-                                app_state.next_question();
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+    /// セッションの終わりに呼ぶ。勧めの練習中・お気に入りのセッション中なら元の出題キューに戻し、そうでなければ次に練習することを選ぶ
+    fn finish_drill_or_recommend(&mut self) -> Option<Recommendation> {
+        if let Some(queue) = self.drill_return.take() {
+            self.queue = queue;
+            return None;
+        }
+        if self.sentence_mode || self.session.main.questions == 0 {
+            return None;
+        }
+        let profile = StatProfile::from_history(&self.player_data.history, &self.roman_map, &self.key_tally);
+        let recommendation = recommend(&profile, self.settings.last_recommendation.as_deref())?;
+        self.settings.edit().last_recommendation = Some(recommendation.focus.id());
+        self.settings.save();
+        Some(recommendation)
+    }
+
+    /// 勧めの練習を始める（対象を含むお題だけを出題する）。練習できるお題がなければ false
+    fn start_drill(&mut self, recommendation: &Recommendation) -> bool {
+        let pool = recommendation.focus.drill_pool(&self.roman_map, QUESTIONS_LIST);
+        if pool.is_empty() {
+            return false;
+        }
+        let mut drill = QuestionQueue::with_order(BUILTIN_PACK_ID, QUESTIONS_LIST, pool);
+        drill.set_rotation(self.settings.rotation_factor, &self.player_data.serve_counts);
+        self.drill_return = Some(std::mem::replace(&mut self.queue, drill));
+        self.apply_blacklist();
+        self.set_sentence_mode(false);
+        true
+    }
+
+    /// お気に入りのお題だけを出題する（終わったら元の出題キューに戻す）。出題できるお気に入りがなければ false
+    /// 消えたお題のお気に入りは残したまま飛ばし、画面で知らせる
+    fn start_favorites(&mut self) -> bool {
+        let bookmarks = &self.player_data.bookmarks;
+        let Some(mut favorites) = QuestionQueue::subset(&served_packs(), |id| bookmarks.contains(&id))
+        else {
+            return false;
+        };
+        let orphaned = bookmarks.iter().filter(|&&id| find_question(id).is_none()).count();
+        favorites.set_rotation(self.settings.rotation_factor, &self.player_data.serve_counts);
+        self.drill_return = Some(std::mem::replace(&mut self.queue, favorites));
+        self.apply_blacklist();
+        self.set_sentence_mode(false);
+        if orphaned > 0 {
+            let message = format!("{} bookmarked question(s) no longer exist and were skipped", orphaned);
+            self.flash = Some((message, Instant::now()));
+        }
+        true
+    }
+
+    /// 文章モード用に新しい文章を作る
+    fn compose_sentence(&self) -> Sentence {
+        Sentence::compose(
+            QUESTIONS_LIST,
+            |hiragana| self.canonical_keystrokes(hiragana),
+            |idx| !self.player_data.blacklist.contains(&QuestionId::builtin(idx)),
+        )
+    }
+
+    /// 現在のお題を読み込み、`char_states` に分解する
+    fn load_current_question(&mut self) {
+        // お題の切り替えの間はリズムの集計に入れない
+        if let Some(metronome) = self.metronome.as_mut() {
+            metronome.break_run();
+        }
+        if let Some(mut sentence) = self.sentence.take() {
+            // お題ごとの成績を記録できるよう、お題単位で分解して区切り位置を覚えておく
+            let mut char_states = Vec::new();
+            let mut ends = Vec::with_capacity(sentence.parts.len());
+            for (idx, part) in sentence.parts.iter().enumerate() {
+                char_states.extend(self.parse_hiragana(part.hiragana));
+                char_states.extend(self.parse_hiragana(sentence.punctuation(idx)));
+                ends.push(char_states.len());
             }
+            sentence.reset_progress(ends);
+            self.char_states = char_states;
+            self.sentence = Some(sentence);
+        } else {
+            let question = self.queue.current();
+            self.char_states = self.parse_hiragana(question.hiragana);
         }
+        self.question_meta = QuestionMeta {
+            tier: self.sentence.is_none().then(|| self.queue.current().tier()),
+            keys: self.canonical_keystrokes(self.current_hiragana()) as u32,
+        };
+        let gloss = match self.sentence {
+            Some(_) => None,
+            None => self.glosses.get(self.current_japanese(), self.current_hiragana()).cloned(),
+        };
+        self.question_gloss = gloss;
+        self.current_char_index = 0;
+        self.is_error = false;
+        self.current_misses = 0;
+        self.correct_keystrokes = 0;
+        self.total_keystrokes = 0;
+        self.miss_marks.clear();
+        self.key_timeline.clear();
+        self.last_accepted_key = None;
+        self.governor_drops = 0;
+        self.question_shown_at = None;
+        self.last_key_time = None;
+        self.paused_since = None;
+        self.paused_duration = Duration::ZERO;
+        self.is_afk = false;
+        self.afk_pauses = 0;
+
+        // メモのあるお題なら少しの間表示する
+        let has_note = self.sentence.is_none()
+            && self.player_data.note(self.queue.current_id()).is_some();
+        self.note_shown_at = (self.settings.show_notes && has_note).then(Instant::now);
     }
-}
 
-// --------------------------------------------------
-// MARK:ログ表示（通常スクリーン）
-// --------------------------------------------------
+    /// 表示中のメモ（表示時間を過ぎていれば None）
+    fn visible_note(&self) -> Option<&str> {
+        let shown_at = self.note_shown_at?;
+        if shown_at.elapsed() >= NOTE_DISPLAY_DURATION || self.sentence.is_some() {
+            return None;
+        }
+        self.player_data.note(self.queue.current_id())
+    }
 
-fn show_log(app_state: &mut AppState) -> Result<()> {
-    println!();
-    println!("\x1b[36m═══════════════════════════════════════════════════════════════════════════\x1b[0m");
-    println!("\x1b[36m  Game Log\x1b[0m");
-    println!("\x1b[36m═══════════════════════════════════════════════════════════════════════════\x1b[0m");
-    println!();
-    
-    if app_state.player_data.history.is_empty() {
-        println!("\x1b[90m  No records yet. Start typing to create history!\x1b[0m");
-    } else {
-        let recent: Vec<_> = app_state
-            .player_data
-            .history
-            .iter()
-            .rev()
-            .take(15)
-            .collect();
-        
-        for record in recent {
-            println!(
-                "  {} | {} | CPS: {:.2} | Miss: {} | Score: {:.0}",
-                record.timestamp.format("%m/%d %H:%M"),
-                record.question_japanese,
-                record.cps,
-                record.misses,
-                record.score
-            );
+    /// 直前のお題のメモの編集を始める（お題の入力中ならタイマーを止める）
+    fn open_note_editor(&mut self) {
+        let Some(id) = self.last_question_id else {
+            return;
+        };
+        let current = self.player_data.note(id).unwrap_or_default();
+        self.note_editor = Some((id, TextInput::new(current, NOTE_MAX_CHARS)));
+        self.register_activity();
+        if self.start_time.is_some() {
+            self.paused_since = Some(Instant::now());
         }
     }
-    
-    println!();
-    println!("\x1b[90m  Press any key to return to menu...\x1b[0m");
-    
-    enable_raw_mode()?;
-    loop {
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press {
-                    disable_raw_mode()?;
-                    app_state.mode = AppMode::Menu;
-                    return Ok(());
+
+    /// メモ編集中のキー入力
+    fn handle_note_key(&mut self, key: &event::KeyEvent) {
+        let Some((id, input)) = self.note_editor.as_mut() else {
+            return;
+        };
+        match input.handle_key(key) {
+            InputOutcome::Editing => return,
+            InputOutcome::Submitted => {
+                let id = *id;
+                match validate_note(&input.value()) {
+                    Ok(text) => {
+                        self.player_data.edit().set_note(id, text);
+                        self.flash = Some(("Note saved".to_string(), Instant::now()));
+                    }
+                    Err(e) => self.flash = Some((e, Instant::now())),
                 }
             }
+            InputOutcome::Cancelled => {}
         }
+        self.note_editor = None;
+        self.register_activity();
     }
-}
 
-// --------------------------------------------------
-// UI描画 - タイピング
-// --------------------------------------------------
+    /// キー入力があったことを記録し、放置による一時停止を解除する
+    fn register_activity(&mut self) {
+        if let Some(since) = self.paused_since.take() {
+            self.paused_duration += since.elapsed();
+        }
+        self.is_afk = false;
+        self.sleep_notice = None;
+        self.last_key_time = Some(Instant::now());
+    }
 
-fn ui_typing(f: &mut Frame, app_state: &AppState) {
-    let size = f.area();
-    let block = Block::default().borders(Borders::ALL).title(" TYPE WiZ ");
-    let inner_area = block.inner(size);
-    f.render_widget(block, size);
+    /// 入力待ちのループの1周ごとに、PC がスリープしていなかったか確かめる
+    /// 打っている途中でスリープしていたら、最後の入力の時点から一時停止していたことにする（眠っていた間を計測時間に入れない）
+    /// 打ち始める前なら、お題を表示した時刻を忘れる
+    fn check_sleep(&mut self) {
+        let now = Instant::now();
+        let Some(gap) = self.sleep_detector.check(now, SystemTime::now()) else {
+            return;
+        };
+        dlog!("sleep", "time jumped by {:?} in_question={}", gap, self.start_time.is_some());
+        // スリープから戻って見えるようになった時刻から数え直す
+        self.question_shown_at = None;
+        if self.start_time.is_some() {
+            if !self.is_paused() {
+                self.paused_since = Some(self.last_key_time.max(self.start_time).unwrap_or(now));
+            }
+            self.sleep_notice = Some(gap);
+        }
+    }
 
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(1),
-            Constraint::Length(2),
-            Constraint::Length(1),
-            Constraint::Length(1),
-            Constraint::Length(1),
-            Constraint::Min(1),
-        ])
-        .split(inner_area);
+    /// 今のお題の制限時間（設定で無効なとき・ウォームアップ・目安の時間がないお題は None）
+    /// 一般的なタイピストがかける時間に設定の倍率を掛ける
+    fn time_limit(&self) -> Option<Duration> {
+        let factor = self.settings.time_limit_factor;
+        if factor <= 0.0 || (self.sentence.is_none() && self.queue.is_warmup()) {
+            return None;
+        }
+        expected_secs(self.question_meta.keys).and_then(|secs| Duration::try_from_secs_f64(secs * factor).ok())
+    }
 
-    // ステータスバー
-    let pd = &app_state.player_data;
-    let req_xp = pd.required_xp_for_next_level();
-    let ratio = if req_xp > 0 {
-        (pd.current_xp as f64 / req_xp as f64).min(1.0)
-    } else {
-        0.0
-    };
+    /// 制限時間の (残り時間, 制限時間)
+    /// 残り時間はお題の計測時間（一時停止を除く）で数えるので、一時停止の間は減らず、打ち始めるまでは満タン
+    fn time_left(&self) -> Option<(Duration, Duration)> {
+        self.time_left_at(Instant::now())
+    }
 
-    let xp_text = match app_state.last_xp_gained {
-        Some(xp) => format!(" +{}XP", xp),
-        None => String::new(),
-    };
-    
-    let label = format!("Lv.{} ({} / {}) {}", pd.level, pd.current_xp, req_xp, xp_text);
-    let gauge = Gauge::default()
-        .block(Block::default().borders(Borders::NONE))
-        .gauge_style(Style::default().fg(Color::Magenta).bg(Color::Black))
-        .ratio(ratio)
-        .label(label);
-    f.render_widget(gauge, chunks[0]);
+    fn time_left_at(&self, now: Instant) -> Option<(Duration, Duration)> {
+        self.time_limit().map(|limit| (limit.saturating_sub(self.active_elapsed_at(now)), limit))
+    }
 
-    // リザルト
-    let cps_time_text = match (app_state.last_cps, app_state.last_time) {
-        (Some(cps), Some(time)) => format!("CPS: {:.2} / Time: {:.2}s", cps, time),
-        _ => String::new(),
-    };
-    let score_miss_text = match (app_state.last_score, app_state.last_misses) {
-        (Some(score), Some(misses)) => format!("Score: {:.0} / Miss: {}", score, misses),
-        _ => String::new(),
-    };
+    /// 入力待ちのループの1周ごとに、制限時間を過ぎていないか確かめる。過ぎていたら打ち切って次のお題へ進み true を返す
+    /// 最後のキーと時間切れが重なったときは、このチェックより前に届いたキーを優先する
+    /// （入れ替わりを待っている保留中のキーも先に処理し、それでお題を打ち終えたら時間切れにしない）
+    fn check_time_limit(&mut self) -> bool {
+        if self.start_time.is_none() || self.is_paused() || self.time_left().is_none_or(|(left, _)| !left.is_zero()) {
+            return false;
+        }
+        self.flush_rollover();
+        if self.start_time.is_none() || self.is_question_complete() {
+            return true;
+        }
+        dlog!("typing", "time limit reached after {:?}", self.active_elapsed());
+        self.next_question(QuestionOutcome::TimedOut);
+        self.flash = Some(("Time's up — moved on to the next question".to_string(), Instant::now()));
+        true
+    }
 
-    let result_paragraph = Paragraph::new(vec![
-        Line::from(cps_time_text).style(Style::default().fg(Color::Yellow)),
-        Line::from(score_miss_text).style(Style::default().fg(Color::Yellow)),
-    ]);
-    f.render_widget(result_paragraph, chunks[1]);
+    /// お題の入力中に一定時間キー入力がなければ放置とみなす
+    fn check_afk(&mut self) {
+        let threshold = self.settings.afk_threshold_secs;
+        if threshold == 0 || self.is_afk || self.is_paused() || self.start_time.is_none() {
+            return;
+        }
+        if let Some(last) = self.last_key_time && last.elapsed() >= Duration::from_secs(threshold) {
+            self.is_afk = true;
+            self.afk_pauses += 1;
+            if self.settings.afk_action == AfkAction::Pause {
+                // 最後の入力時点から一時停止していたことにする
+                self.paused_since = Some(last);
+            }
+        }
+    }
 
-    // 日本語
-    f.render_widget(
-        Paragraph::new(app_state.get_current_question().japanese)
-            .style(Style::default().fg(Color::White).bold())
-            .centered(),
-        chunks[2],
-    );
-    
-    // ひらがな
-    f.render_widget(
-        Paragraph::new(app_state.get_current_question().hiragana)
-            .style(Style::default().fg(Color::Gray))
-            .centered(),
-        chunks[4],
-    );
+    /// キー操作ヘルプを開く（お題の入力中ならタイマーを止める）
+    fn open_help(&mut self) {
+        self.register_activity();
+        if self.start_time.is_some() {
+            self.paused_since = Some(Instant::now());
+        }
+        self.show_help = true;
+    }
 
-    // ローマ字
-    let mut spans = Vec::new();
-    for (i, cs) in app_state.char_states.iter().enumerate() {
-        let pattern = cs.current_pattern(); 
-        
-        if i < app_state.current_char_index {
-            spans.push(Span::styled(pattern, Style::default().fg(Color::Green)));
-        } else if i == app_state.current_char_index {
-            let typed = &pattern[..cs.typed_count];
-            let remaining = &pattern[cs.typed_count..];
-            
-            if !typed.is_empty() {
-                spans.push(Span::styled(typed, Style::default().fg(Color::Green)));
-            }
-            
-            if let Some(next) = remaining.chars().next() {
-                let style = if app_state.is_error {
-                    Style::default().fg(Color::White).bg(Color::Red)
-                } else {
-                    Style::default().fg(Color::Black).bg(Color::White)
-                };
-                spans.push(Span::styled(next.to_string(), style));
-                
-                if remaining.len() > 1 {
-                    spans.push(Span::styled(
-                        &remaining[1..],
-                        Style::default().fg(Color::Gray),
-                    ));
-                }
+    /// キー操作ヘルプを閉じてタイマーを再開する
+    fn close_help(&mut self) {
+        self.show_help = false;
+        self.register_activity();
+    }
+
+    /// 一時停止中か
+    fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// 一時停止していた時間を除いた経過時間
+    fn active_elapsed(&self) -> Duration {
+        self.active_elapsed_at(Instant::now())
+    }
+
+    /// now の時点での active_elapsed（画面は描画の時刻で数える）
+    fn active_elapsed_at(&self, now: Instant) -> Duration {
+        match self.start_time {
+            Some(start) => {
+                let paused = self.paused_duration
+                    + self.paused_since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+                now.saturating_duration_since(start).saturating_sub(paused)
             }
-        } else {
-            spans.push(Span::styled(pattern, Style::default().fg(Color::DarkGray)));
+            None => Duration::ZERO,
         }
     }
-
+    
+    /// 標準的なローマ字（各文字の最初の候補）で打ったときの打鍵数
+    fn canonical_keystrokes(&self, hiragana: &str) -> usize {
+        canonical_keystrokes(&self.roman_map, hiragana)
+    }
+
+    /// 出題範囲の量と、直近の CPS で打ったときの所要時間を見積もる
+    fn session_estimate(&self) -> SessionEstimate {
+        let served: Vec<(QuestionId, &Question)> = served_questions().collect();
+        let pool: Vec<(i32, usize)> = served
+            .iter()
+            .filter(|(id, _)| !self.queue.is_excluded(*id))
+            .map(|(_, q)| (q.tier(), self.canonical_keystrokes(q.hiragana)))
+            .collect();
+        // 自動調整では今の難易度から1段上下しうる
+        let tiers = self.settings.adaptive_difficulty.then(|| {
+            let tier = self.difficulty.target_tier(base_tier_for_level(self.player_data.level));
+            ((tier - 1).max(0), (tier + 1).min(TIER_COUNT - 1))
+        });
+        SessionEstimate::new(
+            &pool,
+            served.len() - pool.len(),
+            self.player_data.average_cps(AVERAGE_CPS_WINDOW),
+            tiers,
+        )
+    }
+
+    /// ひらがな文字列を `Vec<CharState>` に分解（パース）する
+    /// カタカナはひらがなに直して辞書を引き、表示用の文字はカタカナのまま残す
+    fn parse_hiragana(&self, text: &str) -> Vec<CharState> {
+        parse_hiragana(&self.roman_map, text)
+    }
+
+    /// 表示用の日本語（漢字混じり）を返す
+    fn current_japanese(&self) -> &str {
+        match &self.sentence {
+            Some(sentence) => &sentence.japanese,
+            None => self.queue.current().japanese,
+        }
+    }
+
+    /// お題の下に表示する訳（表示言語の訳。`show_gloss` ならほかの言語の訳でも表示する）
+    fn shown_gloss(&self) -> Option<&str> {
+        let gloss = self.question_gloss.as_ref()?;
+        pick_gloss(gloss, self.settings.ui_language.code(), self.settings.show_gloss)
+    }
+
+    /// 表示用のひらがなを返す
+    fn current_hiragana(&self) -> &str {
+        match &self.sentence {
+            Some(sentence) => &sentence.hiragana,
+            None => self.queue.current().hiragana,
+        }
+    }
+    
+    /// 現在の入力位置で `c` が正しい入力になるか（別の綴りへの切り替えも含む）
+    fn accepts(&self, c: char) -> bool {
+        self.char_states.get(self.current_char_index).is_some_and(|cs| cs.would_accept(c))
+    }
+
+    /// キーのチャタリング（1回の打鍵が2回届く）による入力なら数えて true を返す
+    /// 直前の正しい入力と同じキーが短時間で届き、かつミスになる場合だけが対象
+    /// （「った」の "tt" のように、続けて同じキーを打つのが正しい場合は対象外）
+    fn filter_chatter(&mut self, c: char) -> bool {
+        let window = Duration::from_millis(self.settings.chatter_filter_ms);
+        let is_chatter = !window.is_zero()
+            && self
+                .last_hit
+                .is_some_and(|(prev, at)| prev == c && at.elapsed() < window)
+            && !self.accepts(c);
+        if is_chatter {
+            self.filtered_chatter += 1;
+        }
+        is_chatter
+    }
+
+    /// 速さの制限（ガバナー）が有効で、前に受け付けたキーからの間隔が短すぎれば、数えて true を返す
+    /// 無視したキーはミスにも打鍵数にも数えない
+    fn governor_drops_key(&mut self, now: Instant) -> bool {
+        let interval = Duration::from_millis(self.settings.governor_interval_ms);
+        let too_fast = !interval.is_zero()
+            && self.last_accepted_key.is_some_and(|last| now.saturating_duration_since(last) < interval);
+        if too_fast {
+            self.governor_drops += 1;
+            self.governor_flash_until = Some(now + GOVERNOR_FLASH_DURATION);
+        } else {
+            self.last_accepted_key = Some(now);
+        }
+        too_fast
+    }
+
+    /// 現在の入力位置から `first`、`then` の順に打つと、両方とも正しい入力になるか
+    /// 綴りの切り替えと、`first` で単位を打ち終えて次の単位に進む場合も含めて確かめる
+    fn accepts_pair(&self, first: char, then: char) -> bool {
+        let mut states = self.char_states.iter().skip(self.current_char_index).cloned();
+        let Some(mut cs) = states.next() else {
+            return false;
+        };
+        if cs.unsupported || cs.type_key(first).is_none() {
+            return false;
+        }
+        if !cs.is_complete() {
+            return cs.would_accept(then);
+        }
+        states.next().is_some_and(|next| !next.unsupported && next.would_accept(then))
+    }
+
+    /// 打ったキーを、順序の入れ替わりを考えて処理する順に並べて返す
+    /// ミスになるキーが、次に打つべきキーの後なら正しい入力になる場合は、返さずに保留する
+    /// 保留中に次のキーが時間内に届き、入れ替えると両方とも正しい入力になるなら、その順で2つとも返す
+    fn rollover_keys(&mut self, c: char, now: Instant) -> Vec<char> {
+        let window = Duration::from_millis(self.settings.rollover_forgiveness_ms);
+        if let Some((held, at)) = self.held_key.take() {
+            if now.saturating_duration_since(at) <= window && self.accepts_pair(c, held) {
+                self.forgiven_rollovers += 1;
+                dlog!("match", "char={:?} held={:?} forgiven as rollover", c, held);
+                return vec![c, held];
+            }
+            return vec![held, c];
+        }
+        let suspicious = !window.is_zero()
+            && !self.accepts(c)
+            && self.char_states.get(self.current_char_index).is_some_and(|cs| {
+                let typed_so_far = &cs.current_pattern()[..cs.typed_count];
+                cs.patterns
+                    .iter()
+                    .filter(|pattern| pattern.starts_with(typed_so_far))
+                    .filter_map(|pattern| pattern[cs.typed_count..].chars().next())
+                    .any(|expected| self.accepts_pair(expected, c))
+            });
+        if suspicious {
+            // 保留している間もタイマーは押した時刻から動かす
+            self.start_timer(now);
+            self.held_key = Some((c, now));
+            return Vec::new();
+        }
+        vec![c]
+    }
+
+    /// 保留中のキーの待ち時間が過ぎていれば、入れ替えずに処理するキーとして取り出す
+    fn expire_rollover(&mut self, now: Instant) -> Option<char> {
+        let window = Duration::from_millis(self.settings.rollover_forgiveness_ms);
+        let (held, at) = self.held_key?;
+        if now.saturating_duration_since(at) <= window {
+            return None;
+        }
+        self.held_key = None;
+        Some(held)
+    }
+
+    /// 保留中のキーの待ち時間が切れる時刻
+    fn rollover_deadline(&self) -> Option<Instant> {
+        self.held_key.map(|(_, at)| at + Duration::from_millis(self.settings.rollover_forgiveness_ms))
+    }
+
+    /// 1文字を入力として処理し、お題を打ち終えたら次のお題へ進む
+    fn submit_char(&mut self, c: char) {
+        self.handle_char_input(c);
+        dlog!("match", "char={:?} {}", c, self.matcher_state());
+        if self.is_question_complete() {
+            self.start_completion_sweep();
+            self.next_question(QuestionOutcome::Completed);
+        }
+    }
+
+    /// 保留中のキーがあれば、入れ替えずにそのまま処理する（キー操作の前に呼ぶ）
+    fn flush_rollover(&mut self) {
+        if let Some((held, _)) = self.held_key.take() {
+            self.submit_char(held);
+        }
+    }
+
+    /// デバッグログ用の入力判定の状態（例: "unit=3/10 kana=し pattern=shi typed=2 error=false misses=1"）
+    fn matcher_state(&self) -> String {
+        let unit = match self.char_states.get(self.current_char_index) {
+            Some(cs) => format!("kana={} pattern={} typed={}", cs.hiragana, cs.current_pattern(), cs.typed_count),
+            None => "complete".to_string(),
+        };
+        format!(
+            "unit={}/{} {} error={} misses={} keystrokes={}/{}",
+            self.current_char_index,
+            self.char_states.len(),
+            unit,
+            self.is_error,
+            self.current_misses,
+            self.correct_keystrokes,
+            self.total_keystrokes
+        )
+    }
+
+    /// 打ち始める前に、お題が見えるようになった時刻を記録する（毎フレーム呼ぶ）
+    /// 休憩・ヘルプ・メモ入力などの画面や完了の演出で隠れている間は、まだ表示していないとみなす
+    fn mark_question_shown(&mut self, now: Instant) {
+        if self.start_time.is_some() {
+            return;
+        }
+        let hidden = self.cooldown_since.is_some()
+            || self.break_since.is_some()
+            || self.show_help
+            || self.note_editor.is_some()
+            || self.time_attack.as_ref().is_some_and(TimeAttack::is_finished)
+            || self.active_completion_sweep(now).is_some();
+        if hidden {
+            self.question_shown_at = None;
+        } else if self.question_shown_at.is_none() {
+            self.question_shown_at = Some(now);
+        }
+    }
+
+    /// 設定の計り方で、最初のキーを押した時刻からタイマーの開始時刻を決める
+    fn timing_start(&self, first_key: Instant) -> Instant {
+        let shown = self.question_shown_at.map_or(first_key, |shown| shown.min(first_key));
+        match self.settings.timing_policy {
+            TimingPolicy::FirstKey => first_key,
+            TimingPolicy::OnDisplay => shown,
+            TimingPolicy::Hybrid => first_key.checked_sub(HYBRID_MAX_REACTION).map_or(shown, |cap| shown.max(cap)),
+        }
+    }
+
+    /// タイマーがまだ動いていなければ、最初のキーを押した時刻から動かし始める
+    /// 表示から最初のキーまでの間も、入力中と同じく放置の判定に掛ける（しきい値を超えたら放置とみなす）
+    fn start_timer(&mut self, first_key: Instant) {
+        if self.start_time.is_none() {
+            let mut start = self.timing_start(first_key);
+            let threshold = self.settings.afk_threshold_secs;
+            if threshold > 0 && first_key.saturating_duration_since(start) >= Duration::from_secs(threshold) {
+                self.afk_pauses += 1;
+                if self.settings.afk_action == AfkAction::Pause {
+                    // 放置していた間は数えない（入力中の一時停止と同じ）
+                    start = first_key;
+                }
+            }
+            self.start_time = Some(start);
+            self.result_display.on_first_key(self.settings.result_persistence);
+        }
+    }
+
+    /// キー入力の処理
+    fn handle_char_input(&mut self, c: char) {
+        // タイマー開始
+        self.start_timer(Instant::now());
+        if let Some(metronome) = self.metronome.as_mut() {
+            metronome.record_keystroke(Instant::now());
+        }
+         // すべて打ち終わっている
+        if self.current_char_index >= self.char_states.len() {
+            return;
+        }
+        
+        let unit = self.current_char_index;
+        let counted = !self.char_states[unit].unsupported;
+        let mut switched_pattern = false;
+        let current_state = &mut self.char_states[self.current_char_index];
+        
+        match current_state.judge(c, unit) {
+            KeyOutcome::Hit(switched) => {
+                self.is_error = false;
+                switched_pattern = switched;
+                if counted {
+                    self.key_tally.record(c, true);
+                    self.player_data.edit().confusions.record_hit(c);
+                }
+                // 次の CharState へ
+                if current_state.is_complete() {
+                    self.current_char_index += 1;
+                }
+            }
+            KeyOutcome::Ignored => {
+                // 読み飛ばす単位ではスペース以外を押してもミスに数えない
+                self.is_error = true;
+            }
+            KeyOutcome::Miss(miss) => {
+                self.is_error = true;
+                self.current_misses += 1;
+                // 連続したミスでは光っている時間を延ばす（一度消えてから光り直さない）
+                let until = Instant::now() + ERROR_FLASH_DURATION;
+                self.error_flash_until = Some(self.error_flash_until.map_or(until, |prev| prev.max(until)));
+                *self.miss_marks.entry((miss.unit, current_state.typed_count)).or_insert(0) += 1;
+                self.error_ghost = Some((miss.actual, Instant::now()));
+                // キーごとの成績では打つべきだったキーのミスとし、押したキーは取り違えとして残す
+                self.key_tally.record(miss.expected, false);
+                self.player_data.edit().confusions.record_miss(&miss);
+                if let Some(sentence) = self.sentence.as_mut() {
+                    sentence.record_miss(miss.unit);
+                }
+            }
+        }
+
+        // 正確率は押したキーの数から求める（打鍵数は CPS 用に別に数える）
+        if counted {
+            self.total_keystrokes += 1;
+            if !self.is_error {
+                self.correct_keystrokes += 1;
+            }
+            self.key_timeline.record(Instant::now(), !self.is_error);
+        }
+
+        if switched_pattern {
+            self.trim_miss_marks(unit);
+        }
+        self.last_hit = (!self.is_error).then(|| (c, Instant::now()));
+
+        let elapsed_sec = self.active_elapsed().as_secs_f64();
+        if let Some(sentence) = self.sentence.as_mut() {
+            sentence.record_progress(self.current_char_index, elapsed_sec);
+        }
+    }
+    
+    /// Backspace の処理
+    fn handle_backspace(&mut self) {
+        if self.current_char_index >= self.char_states.len() && self.current_char_index > 0 {
+            self.current_char_index -= 1;
+        }
+        
+        if self.current_char_index < self.char_states.len() {
+            let current = &mut self.char_states[self.current_char_index];
+            if current.typed_count > 0 {
+                current.typed_count -= 1;
+            } else if self.current_char_index > 0 {
+                self.current_char_index -= 1;
+                let prev_len = self.char_states[self.current_char_index]
+                    .current_pattern()
+                    .len();
+                self.char_states[self.current_char_index].typed_count = prev_len.saturating_sub(1);
+            }
+        }
+        self.is_error = false;
+        self.rewind_sentence();
+    }
+
+    /// Ctrl+W の処理：入力中の単位を最初から打ち直す（未入力なら1つ前の単位を打ち直す）
+    fn handle_delete_unit(&mut self) {
+        let in_progress = self
+            .char_states
+            .get(self.current_char_index)
+            .is_some_and(|cs| cs.typed_count > 0);
+        if !in_progress && self.current_char_index > 0 {
+            self.current_char_index -= 1;
+        }
+        if let Some(cs) = self.char_states.get_mut(self.current_char_index) {
+            cs.reset();
+        }
+        self.trim_miss_marks(self.current_char_index);
+        self.is_error = false;
+        self.rewind_sentence();
+    }
+
+    /// Ctrl+U の処理：お題を最初から打ち直す（タイマーとミス回数はそのまま）
+    fn handle_reset_question(&mut self) {
+        for cs in &mut self.char_states {
+            cs.reset();
+        }
+        for unit in 0..self.char_states.len() {
+            self.trim_miss_marks(unit);
+        }
+        self.current_char_index = 0;
+        self.is_error = false;
+        self.rewind_sentence();
+    }
+
+    /// 打つべきキーの前に表示する、直前にミスタイプしたキー
+    /// 正しいキーを打つか、打ち直しなどでミスタイプの状態が消えるか、一定時間が経てば表示しない
+    fn visible_error_ghost(&self, now: Instant) -> Option<char> {
+        self.error_ghost
+            .filter(|&(_, at)| self.is_error && now.saturating_duration_since(at) < ERROR_GHOST_DURATION)
+            .map(|(key, _)| key)
+    }
+
+    /// 綴りが変わった単位で、新しい綴りに存在しない位置のミスの印を消す
+    fn trim_miss_marks(&mut self, unit: usize) {
+        let len = self.char_states.get(unit).map_or(0, |cs| cs.current_pattern().len());
+        self.miss_marks
+            .retain(|&(char_index, offset), _| char_index != unit || offset < len);
+    }
+
+    /// 入力位置が戻ったことを文章モードの記録に反映する
+    fn rewind_sentence(&mut self) {
+        if let Some(sentence) = self.sentence.as_mut() {
+            sentence.rewind(self.current_char_index);
+        }
+    }
+    
+    /// お題をすべて打ち終わったか
+    fn is_question_complete(&self) -> bool {
+        self.current_char_index >= self.char_states.len()
+    }
+
+    /// 授業モードで次の生徒の分を始める（同じお題を最初から）
+    fn reset_turn(&mut self) {
+        self.load_current_question();
+        self.start_time = None;
+    }
+
+    /// 授業モードで打ち終えた生徒の成績
+    fn turn_result(&self) -> StudentResult {
+        let QuestionScore { accuracy, cps, score, .. } = score_question(
+            ScoringPreset::Current,
+            self.typed_chars() as u32,
+            self.active_elapsed().as_secs_f64(),
+            self.current_misses,
+            self.keystrokes(),
+        );
+        StudentResult { cps, accuracy, score }
+    }
+
+    /// 打ち終えたお題の演出を始める（集中モードでは行わない）
+    fn start_completion_sweep(&mut self) {
+        if self.settings.focus_mode {
+            return;
+        }
+        let romaji = self
+            .char_states
+            .iter()
+            .map(|cs| if cs.unsupported { cs.hiragana.as_str() } else { cs.current_pattern() })
+            .collect();
+        self.completion_sweep = Some(CompletionSweep::new(
+            self.current_japanese().to_string(),
+            self.current_hiragana().to_string(),
+            romaji,
+            Instant::now(),
+        ));
+    }
+
+    /// 演出中の打ち終えたお題（終わっていれば None）
+    fn active_completion_sweep(&self, now: Instant) -> Option<&CompletionSweep> {
+        self.completion_sweep.as_ref().filter(|sweep| sweep.is_active(now))
+    }
+
+    /// 画面の動き方（描き直しの間隔とキー入力の待ち方を決める）
+    fn pace(&self, now: Instant) -> Pace {
+        let animating = self.active_completion_sweep(now).is_some()
+            || self.metronome.is_some()
+            || self.error_flash_until.is_some_and(|until| now < until)
+            || self.visible_error_ghost(now).is_some()
+            || self.governor_flash_until.is_some_and(|until| now < until);
+        if animating {
+            Pace::Animating
+        } else if self.start_time.is_some()
+            && !self.is_paused()
+            && self.cooldown_since.is_none()
+            && self.break_since.is_none()
+            && !self.show_help
+        {
+            Pace::Typing
+        } else {
+            Pace::Idle
+        }
+    }
+    
+    /// 今のお題を打ち終えるのに要る打鍵数（選んでいる綴りで数える。打ち終えたあとは打った打鍵数と同じ）
+    fn typed_chars(&self) -> usize {
+        self.char_states.iter().map(CharState::typed_len).sum()
+    }
+
+    /// 今のお題で、今の位置までに打ち終えた打鍵数（途中の単位は打ったところまで）
+    fn typed_chars_so_far(&self) -> usize {
+        typing::typed_len_so_far(&self.char_states, self.current_char_index)
+    }
+
+    /// 現在のお題で押したキーの数
+    fn keystrokes(&self) -> Keystrokes {
+        Keystrokes { correct: self.correct_keystrokes, total: self.total_keystrokes }
+    }
+
+    /// 結果を見せている段階か（次のお題をまだ打ち始めていなければ結果を見せる）
+    fn typing_phase(&self, now: Instant) -> TypingPhase {
+        if self.start_time.is_none() && !self.is_paused() && self.result_display.visible(now).is_some() {
+            TypingPhase::Result
+        } else {
+            TypingPhase::Typing
+        }
+    }
+
+    /// ローマ字の行の表示幅（入力できない文字は元の文字の幅）
+    fn romaji_width(&self) -> usize {
+        self.char_states
+            .iter()
+            .map(|cs| if cs.unsupported { Line::from(cs.hiragana.as_str()).width() } else { cs.current_pattern().len() })
+            .sum()
+    }
+
+    /// 今の位置が入力できない文字なら、その文字
+    fn current_unsupported_char(&self) -> Option<&CharState> {
+        self.char_states.get(self.current_char_index).filter(|cs| cs.unsupported)
+    }
+
+    /// 打っている途中のお題を、今の位置までに打った打鍵数で今の時点で採点した結果（打ち始める前は None）
+    /// 打ち終えたときに `next_question` が使うのと同じ採点なので、最後の値は実際に得る経験値と一致する
+    fn live_score(&self, now: Instant) -> Option<QuestionScore> {
+        self.start_time?;
+        let duration_sec = self.active_elapsed_at(now).as_secs_f64();
+        Some(score_question(
+            ScoringPreset::Current,
+            self.typed_chars_so_far() as u32,
+            duration_sec,
+            self.current_misses,
+            self.keystrokes(),
+        ))
+    }
+
+    /// 今打ち終えたら得られる経験値（タグの倍率も `next_question` と同じように掛ける）
+    fn live_xp(&self, live: &QuestionScore) -> u32 {
+        self.tag_multiplier().map_or(live.xp, |multiplier| boosted_xp(live.xp, multiplier))
+    }
+
+    /// タグの倍率を掛けるお題か（タグの付いたお題を1問ずつ打ったときだけ。タイムアタックの回には掛けない）
+    fn tag_boosted(&self) -> Option<&[String]> {
+        if self.sentence.is_some() || self.time_attack.is_some() {
+            return None;
+        }
+        self.question_tags.get(&self.queue.current_id()).map(Vec::as_slice)
+    }
+
+    /// 今のお題に掛かるタグの倍率
+    fn tag_multiplier(&self) -> Option<f64> {
+        self.tag_boosted().and_then(|tags| self.tag_multipliers.multiplier(tags))
+    }
+
+    /// 観戦側に送る now の時点の画面の状態（速さは打ったところまでの打鍵数で数える）
+    fn spectator_frame(&self, now: Instant) -> SpectatorFrame {
+        let mut typed = String::new();
+        let mut remaining = String::new();
+        for (i, cs) in self.char_states.iter().enumerate() {
+            // 入力できない文字は元の文字のまま送る
+            let text = if cs.unsupported { cs.hiragana.as_str() } else { cs.current_pattern() };
+            let done = match i.cmp(&self.current_char_index) {
+                Ordering::Less => text.len(),
+                Ordering::Equal if !cs.unsupported => cs.typed_count.min(text.len()),
+                _ => 0,
+            };
+            let (head, tail) = text.split_at(done);
+            typed.push_str(head);
+            remaining.push_str(tail);
+        }
+        SpectatorFrame {
+            japanese: self.current_japanese().to_string(),
+            hiragana: self.current_hiragana().to_string(),
+            typed,
+            remaining,
+            cps: self.start_time.map(|_| cps(self.typed_chars_so_far() as u32, self.active_elapsed_at(now).as_secs_f64())),
+            misses: self.current_misses,
+            questions: self.session.warmup.questions + self.session.main.questions,
+            level: self.player_data.level,
+        }
+    }
+
+    /// 現在のお題の「タイピング単位」ごとのミス回数（単位の番号順。ミスのない単位は含めない）
+    fn unit_misses(&self) -> Vec<(u32, u32)> {
+        let mut per_unit: BTreeMap<u32, u32> = BTreeMap::new();
+        for (&(unit, _), &count) in &self.miss_marks {
+            *per_unit.entry(unit as u32).or_default() += count;
+        }
+        per_unit.into_iter().collect()
+    }
+
+    /// 今のお題を記録して次のお題へ進む（`outcome` が時間切れなら、打ったところまでを記録する）
+    fn next_question(&mut self, outcome: QuestionOutcome) {
+        self.next_question_at(outcome, Instant::now());
+    }
+
+    /// `now` の時点で打ち終えたものとして `next_question` を行う
+    fn next_question_at(&mut self, outcome: QuestionOutcome, now: Instant) {
+        if self.start_time.is_some() {
+            let timed_out = outcome == QuestionOutcome::TimedOut;
+            let duration = self.active_elapsed_at(now);
+            let duration_sec = duration.as_secs_f64();
+            // 時間切れなら打ったところまで（打たなかった残りは数えない）
+            let total_chars = if timed_out { self.typed_chars_so_far() } else { self.typed_chars() };
+            
+            let misses = self.current_misses;
+            let keystrokes = self.keystrokes();
+            let error_cost = self.key_timeline.error_cost();
+            let warmup = self.sentence.is_none() && self.queue.is_warmup();
+            let time_attack = self.time_attack.as_ref().map(TimeAttack::current_attempt);
+            // 速さを制限した記録は、自己ベスト・順位・難易度の調整に使わない
+            let governor_ms = self.settings.governor_interval_ms.min(u64::from(u32::MAX)) as u32;
+            let governed = governor_ms > 0;
+            let QuestionScore { accuracy, cps, score, xp: final_xp, xp_parts } =
+                score_question(ScoringPreset::Current, total_chars as u32, duration_sec, misses, keystrokes);
+            // 時間切れのお題はスコアも経験値も 0（正確率と速さはそのまま記録する）
+            let (score, final_xp, xp_parts) =
+                if timed_out { (0.0, 0, XpParts::default()) } else { (score, final_xp, xp_parts) };
+            let tag_boost =
+                self.tag_boosted().filter(|_| !timed_out).and_then(|tags| self.tag_multipliers.boost(tags));
+            let (final_xp, boost_xp) = tag_boost.as_ref().map_or((final_xp, 0), |boost| boost.apply(final_xp));
+
+            // 直近の成績から難易度を調整する（平均は今回の記録を追加する前の値）
+            if self.settings.adaptive_difficulty && !warmup && !governed {
+                let average_cps = self.player_data.average_cps(AVERAGE_CPS_WINDOW).unwrap_or(0.0);
+                let base_tier = base_tier_for_level(self.player_data.level);
+                if let Some(shift) = self.difficulty.record(accuracy, cps, average_cps, base_tier) {
+                    self.tier_notice = Some((shift, Instant::now()));
+                }
+            }
+
+            let XpParts { base, speed, accuracy: accuracy_xp } = xp_parts;
+            self.last_xp_breakdown =
+                vec![("base".to_string(), base), ("speed".to_string(), speed), ("accuracy".to_string(), accuracy_xp)];
+            if let Some(boost) = &tag_boost {
+                self.last_xp_breakdown.push((boost.label(), boost_xp));
+            }
+
+            let components = match &self.sentence {
+                Some(sentence) => {
+                    let unit_lens: Vec<usize> =
+                        self.char_states.iter().map(CharState::typed_len).collect();
+                    sentence.component_stats(&unit_lens)
+                }
+                None => Vec::new(),
+            };
+            let record = TypeRecord {
+                timestamp: Utc::now(),
+                question_japanese: self.current_japanese().to_string(),
+                question_hiragana: self.current_hiragana().to_string(),
+                total_chars: total_chars as u32,
+                duration_sec,
+                misses,
+                cps,
+                score,
+                // タイムアタックの経験値は最後に一番良い回の分だけ与える
+                xp_gained: if time_attack.is_some() { 0 } else { final_xp },
+                key_remap: self.remapper.active_label().to_string(),
+                afk_pauses: self.afk_pauses,
+                components,
+                question_id: self.sentence.is_none().then(|| self.queue.current_id()),
+                warmup,
+                after_cooldown: !warmup && self.cooldown_followup > 0,
+                time_attack,
+                keystrokes: keystrokes.total,
+                imported: false,
+                timing: self.settings.timing_policy,
+                governor_ms,
+                error_loss_ms: error_cost.lost.as_millis().min(u128::from(u32::MAX)) as u32,
+                unit_misses: if self.sentence.is_none() { self.unit_misses() } else { Vec::new() },
+                intent: self.session.intent,
+                code: false,
+                // 途中で打ち切ったお題の区間は長さがそろわないので数えない
+                pace: if timed_out { Vec::new() } else { segment_pace(&self.key_timeline.times()) },
+                outcome,
+                // 学習シートに書き出せるよう、表示していなくても訳は残す
+                gloss: self
+                    .question_gloss
+                    .as_ref()
+                    .and_then(|gloss| pick_gloss(gloss, self.settings.ui_language.code(), true))
+                    .map(str::to_string),
+            };
+            self.last_question_id = record.question_id;
+            // 順位は今回の記録を追加する前の履歴と比べる
+            let percentiles = (!warmup && !governed && !timed_out)
+                .then(|| self.player_data.percentiles().rank(total_chars as u32, cps, accuracy));
+            // 正確率が下限に届かない記録は保存するが、自己ベストには数えない
+            let floor = self.settings.accuracy_floor_for_bests;
+            let below_floor = (!warmup && !governed && !timed_out && !meets_accuracy_floor(accuracy, floor))
+                .then_some((accuracy, floor));
+            self.result_display.show(
+                LastResult {
+                    cps,
+                    duration_sec,
+                    misses,
+                    score,
+                    percentiles,
+                    baseline: BaselineRank::classify(total_chars as u32, cps),
+                    governor: governed.then_some((self.governor_drops, accuracy)),
+                    error_cost,
+                    meta: self.question_meta,
+                    expected_secs: expected_secs(self.question_meta.keys),
+                    timed_out,
+                    below_floor,
+                },
+                self.settings.result_persistence,
+                self.settings.result_timeout_secs,
+                Instant::now(),
+            );
+            self.player_data.edit().history.push(record);
+            self.track_break(duration_sec);
+
+            // ウォームアップは自己ベストや連続記録の対象にしない
+            let mut streak_bonus = 0;
+            if warmup {
+                self.session.warmup.add(total_chars as u32, misses, duration_sec, error_cost.lost.as_secs_f64());
+            } else {
+                self.session.main.add(total_chars as u32, misses, duration_sec, error_cost.lost.as_secs_f64());
+                self.cooldown_followup = self.cooldown_followup.saturating_sub(1);
+                self.check_cooldown(accuracy);
+                self.current_streak = if misses == 0 && !timed_out { self.current_streak + 1 } else { 0 };
+                streak_bonus = (self.current_streak / STREAK_BONUS_STEP).min(STREAK_BONUS_MAX);
+                let beaten = if governed || timed_out || below_floor.is_some() {
+                    Vec::new()
+                } else {
+                    self.targets.update(cps, self.current_streak, score)
+                };
+                if !beaten.is_empty() {
+                    let now = Instant::now();
+                    self.record_banner = Some((beaten, now));
+                    self.targets_shown_at = Some(now);
+                }
+            }
+
+            // タイムアタックの回は経験値を保留し、最後に一番良い回の分だけ与える
+            let awarded_xp = match self.time_attack.as_mut() {
+                Some(attack) => {
+                    attack.attempts.push(Attempt {
+                        cps,
+                        score,
+                        misses,
+                        duration_sec,
+                        xp: final_xp,
+                        history_idx: self.player_data.history.len() - 1,
+                    });
+                    streak_bonus = 0;
+                    0
+                }
+                None => final_xp,
+            };
+            if streak_bonus > 0 {
+                self.last_xp_breakdown.push(("streak".to_string(), i64::from(streak_bonus)));
+            }
+            self.last_xp_gained = self.time_attack.is_none().then_some(final_xp + streak_bonus);
+
+            let player_data = self.player_data.edit();
+            // 倍率で増えた分は台帳に分けて記録する（履歴のお題の経験値には含める）
+            let boost_gain = boost_xp.max(0) as u32;
+            player_data.add_xp(XpSource::QuestionCompletion, awarded_xp - boost_gain, total_chars as u32);
+            player_data.add_xp(XpSource::TagBoost, boost_gain, 0);
+            player_data.add_xp(XpSource::StreakBonus, streak_bonus, 0);
+            player_data.total_misses = player_data.total_misses.saturating_add(u64::from(misses));
+            player_data.add_practice_time(duration_sec);
+            self.check_achievements();
+            // お題の記録はすぐに保存する
+            self.storage.append_history(&self.player_data);
+            self.player_data.mark_saved();
+
+            // タイムアタック中は同じお題をすぐに出し直し、最後の回が終わったら結果を出す
+            if let Some(attack) = &self.time_attack {
+                if attack.is_finished() {
+                    self.settle_time_attack();
+                }
+                self.load_current_question();
+                self.start_time = None;
+                return;
+            }
+        }
+        
+        let tier = self
+            .settings
+            .adaptive_difficulty
+            .then(|| self.difficulty.target_tier(base_tier_for_level(self.player_data.level)));
+        if self.sentence_mode {
+            self.sentence = Some(self.compose_sentence());
+        } else {
+            self.advance_queue(tier);
+        }
+        self.load_current_question();
+        self.start_time = None;
+    }
+}
+
+// --------------------------------------------------
+// MARK:メイン関数
+// --------------------------------------------------
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_color(if cli.plain { ColorMode::Never } else { cli.color });
+    let settings = Settings::load();
+    set_json_mirror(settings.json_mirror);
+    set_language(settings.ui_language);
+    install_panic_hook();
+    if let Some(path) = debug_log::configured_path(cli.log_file.as_deref()) {
+        // まだ raw モードではないので、開けなかったことは標準エラーに出してよい
+        if let Err(e) = debug_log::init(&path) {
+            eprintln!("Could not open the log file {}: {}", path.display(), e);
+        }
+    }
+
+    // TUI を使わないコマンド
+    match &cli.command {
+        Some(Commands::Recompute { yes }) => return run_recompute(*yes),
+        Some(Commands::Dedupe { dry_run }) => return run_dedupe(*dry_run),
+        Some(Commands::Where) => return show_where(),
+        Some(Commands::Stats(args)) => {
+            let format = cli.output.unwrap_or(OutputFormat::Plain);
+            return run_stats(args, format);
+        }
+        Some(Commands::Doctor { tail_log: true, .. }) => return run_tail_log(cli.log_file.as_deref()),
+        Some(Commands::Doctor { pool, .. }) => {
+            let format = cli.output.unwrap_or(OutputFormat::Plain);
+            return if *pool { run_pool_doctor(format) } else { run_doctor(format) };
+        }
+        Some(Commands::Questions { command }) => return run_questions(command),
+        Some(Commands::Packs { command }) => return run_packs(command),
+        Some(Commands::Rescore { preset, yes }) => return run_rescore(*preset, *yes),
+        Some(Commands::Export { study_sheet: Some(path), .. }) => return run_study_sheet(path),
+        Some(Commands::Export { compact_code, name, date, .. }) => {
+            return run_export(*compact_code, name.as_deref(), *date, cli.output.unwrap_or(OutputFormat::Plain));
+        }
+        Some(Commands::Import { compact_code: Some(code), .. }) => return run_import(code),
+        Some(Commands::Import { file: Some(file), format: Some(format), map, dry_run, yes, .. }) => {
+            return run_history_import(file, *format, map.as_deref(), *dry_run, *yes);
+        }
+        Some(Commands::Update { rollback }) => return run_update(*rollback),
+        Some(Commands::Roster { file }) => return run_roster(file),
+        Some(Commands::KeyTest) => return run_key_test(),
+        Some(Commands::Spectate { addr }) => return run_spectate(addr),
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(*shell, &mut Cli::command(), "typewiz", &mut stdout());
+            return Ok(());
+        }
+        Some(Commands::Start { dry_run: true, no_schedule, .. }) => {
+            let settings = Settings::load();
+            let (schedule, _) = Schedule::from_settings(&settings.schedule);
+            serve_extra_questions();
+            // 見積もるだけなのでセーブファイルには書き戻さない
+            let mut app_state = AppState::with_data(
+                settings,
+                Box::new(MemoryStorage::new(FileStorage.load())),
+                QuestionQueue::from_packs(&served_packs()),
+            );
+            if !*no_schedule {
+                app_state.set_day_plan(schedule.plan_for(Local::now().date_naive()).cloned());
+            }
+            return emit(&app_state.session_estimate(), cli.output.unwrap_or(OutputFormat::Plain));
+        }
+        Some(Commands::Log { intent }) => {
+            // 出力形式が指定されたときは画面を開かずに出力する
+            if let Some(format) = cli.output {
+                let player_data = FileStorage.load();
+                return emit(&LogReport::from_player_data(&player_data, *intent), format);
+            }
+        }
+        _ => {}
+    }
+
+    offer_recovery()?;
+    let mut app_state = AppState::new();
+    app_state.broadcast = cli.broadcast.map(|port| (cli.broadcast_bind, port));
+
+    match &cli.command {
+        Some(Commands::Start { mode, question, adaptive, no_adaptive, no_schedule, .. }) => {
+            if *adaptive || *no_adaptive {
+                app_state.settings.edit().adaptive_difficulty = *adaptive;
+            }
+            if *no_schedule {
+                app_state.set_day_plan(None);
+            }
+            let config = app_state.settings.last_session.merged(*mode, *question);
+            app_state.start_session(config)?;
+        }
+        Some(Commands::Log { intent }) => {
+            app_state.log_intent = *intent;
+            app_state.mode = AppMode::Log;
+        }
+        Some(
+            Commands::Recompute { .. }
+            | Commands::Dedupe { .. }
+            | Commands::Where
+            | Commands::Stats(_)
+            | Commands::Doctor { .. }
+            | Commands::Questions { .. }
+            | Commands::Packs { .. }
+            | Commands::Rescore { .. }
+            | Commands::Export { .. }
+            | Commands::Import { .. }
+            | Commands::Roster { .. }
+            | Commands::Update { .. }
+            | Commands::KeyTest
+            | Commands::Spectate { .. }
+            | Commands::Completions { .. },
+        ) => unreachable!(),
+        // デフォルトの挙動
+        None => app_state.mode = AppMode::Menu,
+    }
+
+    // 起動時は新しいバージョンがあるかを裏で確かめるだけにする（画面を待たせず、セッション中に差し替えない）
+    // 失敗しても黙って続ける（理由はデバッグログにだけ残す）
+    app_state.update_check = UpdateCheck::spawn(latest_version);
+
+    offer_stray_json_cleanup(&mut app_state)?;
+    offer_resume(&mut app_state)?;
+
+    // エラーで抜けた場合も含め、終了時の保存はここだけで行う
+    let result = run_app(&mut app_state);
+    app_state.shutdown();
+    result
+}
+
+/// 画面を切り替えながらアプリを動かす（終了を選ぶまで戻らない）
+fn run_app(app_state: &mut AppState) -> Result<()> {
+    loop {
+        // メニュー以外の画面はセーブデータを使うので、読み込み中なら読み終わるまで待つ
+        if app_state.mode != AppMode::Menu {
+            app_state.ensure_profile()?;
+        }
+        app_state.autosave_if_due();
+        match app_state.mode {
+            AppMode::Menu => {
+                if !show_menu(app_state)? {
+                    // falseだった時の処理
+                }
+            }
+            AppMode::Typing => {
+                run_typing_mode(app_state)?;
+            }
+            AppMode::Log => {
+                show_log(app_state)?;
+            }
+            AppMode::Trends => {
+                show_trends(app_state)?;
+            }
+            AppMode::Achievements => {
+                show_achievements(app_state)?;
+            }
+            AppMode::WeeklyReport => {
+                show_weekly_report(app_state)?;
+            }
+            AppMode::Today => {
+                show_today(app_state)?;
+            }
+            AppMode::Settings => {
+                show_settings(app_state)?;
+            }
+            AppMode::Picker => {
+                show_picker(app_state)?;
+            }
+            AppMode::Author => {
+                show_author(app_state)?;
+            }
+            AppMode::FallingWords => {
+                run_falling_words(app_state)?;
+            }
+            AppMode::CodeDrill => {
+                run_code_drill(app_state)?;
+            }
+            AppMode::Exit => {
+                break;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// セーブファイルの一部が壊れていて読み飛ばしたときのお知らせ
+fn integrity_notice(integrity: &IntegrityReport) -> Option<String> {
+    if let Some(backup) = &integrity.unreadable_backup {
+        return Some(format!(
+            "Save file could not be read; a copy was kept at {}. Run `typewiz doctor` for details.",
+            backup.display()
+        ));
+    }
+    if integrity.header_rebuilt {
+        return Some(
+            "Save header was corrupted: records were recovered and level/totals rebuilt from history. Run `typewiz doctor` for details."
+                .to_string(),
+        );
+    }
+    (!integrity.dropped_offsets.is_empty()).then(|| {
+        format!(
+            "Save file is partially corrupted: {} damaged region(s) were skipped. Run `typewiz doctor` for details.",
+            integrity.dropped_offsets.len()
+        )
+    })
+}
+
+/// 前回異常終了したときの未保存の変更を反映するか確認する
+fn offer_recovery() -> Result<()> {
+    let Some(recovery) = load_recovery() else {
+        return Ok(());
+    };
+
+    let mut parts = Vec::new();
+    if recovery.player_data.is_some() {
+        parts.push("save data");
+    }
+    if recovery.settings.is_some() {
+        parts.push("settings");
+    }
+    if !parts.is_empty() {
+        outln!("\x1b[33m  The last session ended unexpectedly with unsaved changes ({}).\x1b[0m", parts.join(", "));
+        let restore = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Restore them?")
+            .default(true)
+            .interact()?;
+        if restore {
+            if let Some(player_data) = &recovery.player_data {
+                FileStorage.save(player_data);
+            }
+            if let Some(settings) = &recovery.settings {
+                settings.save();
+            }
+        }
+    }
+    remove_recovery();
+    Ok(())
+}
+
+/// 前回のセッションが途中で終わっていれば、途中だったお題から続けるか確認する
+fn offer_resume(app_state: &mut AppState) -> Result<()> {
+    let Some(checkpoint) = load_checkpoint() else {
+        return Ok(());
+    };
+    remove_checkpoint();
+
+    let session = checkpoint.session;
+    outln!(
+        "\x1b[33m  The last session was interrupted at {} ({} question(s) done, {}/{} of the current one typed).\x1b[0m",
+        format_timestamp(checkpoint.saved_at),
+        session.warmup.questions + session.main.questions,
+        checkpoint.typed_units,
+        checkpoint.total_units
+    );
+    let resume = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Resume from the start of that question?")
+        .default(true)
+        .interact()?;
+    if resume {
+        app_state.resume_from(&checkpoint);
+    }
+    Ok(())
+}
+
+/// 旧バージョンがカレントディレクトリに書き出した JSON を削除するか一度だけ確認する
+fn offer_stray_json_cleanup(app_state: &mut AppState) -> Result<()> {
+    if app_state.settings.stray_json_prompted || !PlayerData::get_save_file_path().exists() {
+        return Ok(());
+    }
+    let Some(stray) = find_stray_debug_json() else {
+        return Ok(());
+    };
+
+    outln!("\x1b[33m  Found a copy of your save data left by an older version:\x1b[0m");
+    outln!("  {}", stray.display());
+    let delete = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Delete this stray save_data.json?")
+        .default(true)
+        .interact()?;
+    if delete && let Err(e) = fs::remove_file(&stray) {
+        outln!("\x1b[31m  Failed to delete the file: {}\x1b[0m", e);
+    }
+
+    app_state.settings.edit().stray_json_prompted = true;
+    app_state.settings.save();
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:デバッグログの表示コマンド
+// --------------------------------------------------
+
+fn run_tail_log(log_file: Option<&Path>) -> Result<()> {
+    let Some(path) = debug_log::configured_path(log_file) else {
+        outln!("\x1b[33m  No log file is configured. Pass --log-file <PATH> or set {}.\x1b[0m", debug_log::LOG_ENV);
+        return Ok(());
+    };
+    match debug_log::tail(&path, debug_log::TAIL_LINES) {
+        Ok(lines) => {
+            outln!("\x1b[90m  {} (last {} lines)\x1b[0m", path.display(), lines.len());
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(e) => outln!("\x1b[31m  Could not read {}: {}\x1b[0m", path.display(), e),
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:記録の取り込みコマンド
+// --------------------------------------------------
+
+fn run_history_import(file: &Path, format: ImportFormat, map: Option<&str>, dry_run: bool, yes: bool) -> Result<()> {
+    let Ok(text) = String::from_utf8(fs::read(file)?) else {
+        outln!("\x1b[31m  {} is not UTF-8. Save it as UTF-8 and try again.\x1b[0m", file.display());
+        return Ok(());
+    };
+    let summary = match ColumnMap::for_format(format, map).and_then(|map| parse_history_csv(&text, format, &map)) {
+        Ok(summary) => summary,
+        Err(e) => {
+            outln!("\x1b[31m  Could not read the file: {}\x1b[0m", e);
+            return Ok(());
+        }
+    };
+
+    for (line_no, reason) in &summary.skipped {
+        outln!("\x1b[33m  line {}: {}\x1b[0m", line_no, reason);
+    }
+    let read = summary.records.len();
+    let before = FileStorage.load();
+    let mut player_data = before.clone();
+    let added = player_data.merge_imported(summary.records);
+    outln!(
+        "  Imported {}, already in history {}, skipped {}",
+        added,
+        read - added,
+        summary.skipped.len()
+    );
+
+    let settings = Settings::load();
+    let diff = BestsDiff::between(
+        &before.history,
+        &player_data.history,
+        settings.imported_in_bests,
+        settings.accuracy_floor_for_bests,
+    );
+    let command = format!("import {}", file.display());
+    if dry_run {
+        print_bests_diff(&diff);
+        outln!("  Dry run: nothing was saved.");
+    } else if added > 0 && confirm_bests_diff(&command, &diff, yes)? {
+        FileStorage.save(&player_data);
+        outln!("  Saved to {}", PlayerData::get_save_file_path().display());
+        outln!("\x1b[90m  Imported records count toward totals and charts, but not toward bests or XP.\x1b[0m");
+        log_bests_diff(&command, &diff, &format!("imported {} record(s)", added));
+    }
+    Ok(())
+}
+
+/// 履歴を書き換えるコマンドで、ベストがどう変わるかを表示する
+fn print_bests_diff(diff: &BestsDiff) {
+    for line in diff.lines() {
+        outln!("  {}", line);
+    }
+}
+
+/// ベストの差分を表示し、書き換えてよいかを返す
+/// ベストだった記録がベストでなくなるときは、`--yes` がなければ確認する（端末でなければ書き換えない）
+fn confirm_bests_diff(command: &str, diff: &BestsDiff, yes: bool) -> Result<bool> {
+    print_bests_diff(diff);
+    if yes || !diff.displaces_best() {
+        return Ok(true);
+    }
+    let confirmed = std::io::stdin().is_terminal()
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Some bests will be replaced. Save anyway?")
+            .default(false)
+            .interact()?;
+    if !confirmed {
+        outln!("  Nothing was saved. Run with --yes to apply.");
+        log_bests_diff(command, diff, "cancelled");
+    }
+    Ok(confirmed)
+}
+
+/// ベストの差分と結果をコマンドログに残す（書けなくてもコマンドは失敗させない）
+fn log_bests_diff(command: &str, diff: &BestsDiff, outcome: &str) {
+    let mut lines = diff.lines();
+    lines.push(format!("Result: {}", outcome));
+    if let Err(e) = append_command_log(command, &lines) {
+        outln!("\x1b[33m  Could not write the command log: {}\x1b[0m", e);
+    }
+}
+
+// --------------------------------------------------
+// MARK:再計算コマンド
+// --------------------------------------------------
+
+fn run_recompute(apply: bool) -> Result<()> {
+    let player_data = FileStorage.load();
+    let recomputed = player_data.recomputed();
+
+    outln!("  Records: {}", player_data.history.len());
+    outln!("  {:<20} {:>14} {:>14}", "", "saved", "recomputed");
+    let rows = [
+        ("level", u64::from(player_data.level), u64::from(recomputed.level)),
+        ("current_xp", u64::from(player_data.current_xp), u64::from(recomputed.current_xp)),
+        ("total_typed_chars", player_data.total_typed_chars, recomputed.total_typed_chars),
+        ("total_misses", player_data.total_misses, recomputed.total_misses),
+        ("total_practice_secs", player_data.total_practice_secs, recomputed.total_practice_secs),
+    ];
+
+    let mut has_discrepancy = false;
+    for (name, saved, new) in rows {
+        let mark = if saved != new {
+            has_discrepancy = true;
+            "\x1b[33m*\x1b[0m"
+        } else {
+            " "
+        };
+        outln!("{} {:<20} {:>14} {:>14}", mark, name, saved, new);
+    }
+
+    let settings = Settings::load();
+    let diff = BestsDiff::between(
+        &player_data.history,
+        &recomputed.history,
+        settings.imported_in_bests,
+        settings.accuracy_floor_for_bests,
+    );
+    print_bests_diff(&diff);
+    if !has_discrepancy {
+        outln!("  No discrepancies found.");
+    } else if apply {
+        FileStorage.save(&recomputed);
+        outln!("  Recomputed values have been saved.");
+        log_bests_diff("recompute --yes", &diff, "saved");
+    } else {
+        outln!("  Run with --yes to apply the recomputed values.");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:重複の削除コマンド
+// --------------------------------------------------
+
+fn run_dedupe(dry_run: bool) -> Result<()> {
+    let mut player_data = FileStorage.load();
+    let records = player_data.history.len();
+    let removed = player_data.remove_duplicate_records();
+    if removed.is_empty() {
+        outln!("  No duplicate records found among {} record(s).", records);
+        return Ok(());
+    }
+
+    // 日ごとの件数（ローカルの日付で、古い日から）
+    let mut by_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for record in &removed {
+        *by_day.entry(record.timestamp.with_timezone(&Local).date_naive()).or_default() += 1;
+    }
+    outln!("  Found {} duplicate record(s) on {} day(s):", removed.len(), by_day.len());
+    for (day, count) in &by_day {
+        outln!("    {}  {:>5}", format_date(*day), count);
+    }
+
+    if dry_run {
+        outln!("  Dry run: nothing was changed. Run without --dry-run to remove them.");
+        return Ok(());
+    }
+    // 取り除いた記録の分の経験値と累計値を戻すため、履歴から計算し直す
+    let deduped = player_data.recomputed();
+    outln!("  Level: {} -> {}", player_data.level, deduped.level);
+    backup_save_file()?;
+    FileStorage.save(&deduped);
+    outln!("  Removed {} record(s) and saved.", removed.len());
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:再採点コマンド
+// --------------------------------------------------
+
+fn run_rescore(preset: ScoringPreset, yes: bool) -> Result<()> {
+    let player_data = FileStorage.load();
+    let mut rescored = player_data.clone();
+
+    // 打鍵数や入力時間が記録されていないものは採点し直せないので、そのまま残す
+    let mut untouched = 0;
+    for record in &mut rescored.history {
+        if record.total_chars == 0 || record.duration_sec <= 0.0 {
+            untouched += 1;
+            continue;
+        }
+        let result =
+            score_question(preset, record.total_chars, record.duration_sec, record.misses, record.keystrokes());
+        record.score = result.score;
+        // タイムアタックで経験値を与えなかった回は 0 のままにする
+        if record.time_attack.is_none() || record.xp_gained > 0 {
+            record.xp_gained = result.xp;
+        }
+    }
+    // 記録の経験値が変わるので、レベルと累計値も履歴から計算し直す
+    let rescored = rescored.recomputed();
+
+    outln!(
+        "  Rescored {} record(s), left {} untouched (missing chars or duration).",
+        rescored.history.len() - untouched,
+        untouched
+    );
+    outln!("  Level: {} -> {}", player_data.level, rescored.level);
+
+    let settings = Settings::load();
+    let diff = BestsDiff::between(
+        &player_data.history,
+        &rescored.history,
+        settings.imported_in_bests,
+        settings.accuracy_floor_for_bests,
+    );
+    let command = format!("rescore --preset {:?}", preset).to_lowercase();
+    if !confirm_bests_diff(&command, &diff, yes)? {
+        return Ok(());
+    }
+    backup_save_file()?;
+    FileStorage.save(&rescored);
+    outln!("  Saved.");
+    log_bests_diff(&command, &diff, &format!("saved (level {} -> {})", player_data.level, rescored.level));
+    Ok(())
+}
+
+/// 書き換える前のセーブファイルを、日時を付けた名前でコピーしておく
+fn backup_save_file() -> Result<()> {
+    let save_path = PlayerData::get_save_file_path();
+    if save_path.exists() {
+        let mut backup = save_path.as_os_str().to_owned();
+        backup.push(format!(".{}.bak", Local::now().format("%Y%m%d%H%M%S")));
+        let backup = PathBuf::from(backup);
+        fs::copy(&save_path, &backup)?;
+        outln!("  Backed up the previous save to {}", backup.display());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:診断コマンド
+// --------------------------------------------------
+
+/// 診断コマンドの結果
+#[derive(serde::Serialize)]
+struct DoctorReport {
+    save_file: PathBuf,
+    format: String,
+    records: usize,
+    /// 壊れていて読み飛ばした箇所（ファイル先頭からのバイト位置）
+    dropped_offsets: Vec<usize>,
+    /// ヘッダ部が読めず、レベル・累計値を履歴から作り直したか
+    header_rebuilt: bool,
+    /// 読めなかったセーブファイルのコピー
+    unreadable_backup: Option<PathBuf>,
+}
+
+impl Report for DoctorReport {
+    fn print_plain(&self) {
+        outln!("  Save file : {}", self.save_file.display());
+        outln!("  Format    : {}", self.format);
+        outln!("  Records   : {}", self.records);
+
+        if let Some(backup) = &self.unreadable_backup {
+            outln!("  Integrity : \x1b[31mFAILED — the save file could not be read\x1b[0m");
+            outln!("  A copy was kept at {}", backup.display());
+            outln!("  The next save will start a new profile; restore the copy to keep the old one.");
+        } else if self.header_rebuilt {
+            outln!("  Integrity : \x1b[31mFAILED — the save header is corrupted\x1b[0m");
+            outln!("  Records were recovered; level and totals were rebuilt from history.");
+            outln!("  Notes, blacklist, bookmarks and achievements stored in the header were lost.");
+        } else if self.dropped_offsets.is_empty() {
+            outln!("  Integrity : \x1b[32mOK\x1b[0m");
+        } else {
+            outln!(
+                "  Integrity : \x1b[31m{} damaged region(s) skipped\x1b[0m",
+                self.dropped_offsets.len()
+            );
+            for offset in &self.dropped_offsets {
+                outln!("    - byte offset {}", offset);
+            }
+            outln!("  The next save will rewrite the file without the damaged regions.");
+        }
+    }
+}
+
+/// セーブファイルを診断する（記録以外のデータを失っていたらエラーで終わる）
+fn run_doctor(format: OutputFormat) -> Result<()> {
+    let (player_data, integrity) = FileStorage.load_with_report();
+    let failed = integrity.lost_data();
+    let report = DoctorReport {
+        save_file: PlayerData::get_save_file_path(),
+        format: integrity.source,
+        records: player_data.history.len(),
+        dropped_offsets: integrity.dropped_offsets,
+        header_rebuilt: integrity.header_rebuilt,
+        unreadable_backup: integrity.unreadable_backup,
+    };
+    emit(&report, format)?;
+    if failed {
+        return Err(std::io::Error::other("the save file is damaged"));
+    }
+    Ok(())
+}
+
+impl Report for PoolHealth {
+    fn print_plain(&self) {
+        outln!("  {}", self.summary());
+
+        if !self.unreachable.is_empty() {
+            outln!();
+            outln!("  Unreachable questions:");
+            for question in &self.unreachable {
+                outln!("    - [{}] {} \x1b[90m({})\x1b[0m", question.pack, question.japanese, question.reason);
+            }
+        }
+        if !self.too_long.is_empty() {
+            outln!();
+            outln!("  Longer than {} keystrokes:", SANITY_MAX_KEYSTROKES);
+            for question in &self.too_long {
+                outln!("    - {} ({} keys)", question.japanese, question.keystrokes);
+            }
+        }
+        if !self.duplicates.is_empty() {
+            outln!();
+            outln!("  Duplicate readings:");
+            for duplicate in &self.duplicates {
+                outln!("    - {}: {}", duplicate.hiragana, duplicate.questions.join(", "));
+            }
+        }
+        if !self.unused_mappings.is_empty() {
+            // 記号や英字の見出しは数が多いので、かなの見出しだけ並べる
+            let (kana, other): (Vec<&str>, Vec<&str>) = self
+                .unused_mappings
+                .iter()
+                .partition(|key| key.chars().all(|c| ('\u{3041}'..='\u{3096}').contains(&c) || c == 'ー'));
+            outln!();
+            if !kana.is_empty() {
+                outln!("  Unused mappings: {}", kana.join(" "));
+            }
+            if !other.is_empty() {
+                outln!("  \x1b[90m(and {} unused symbol/alphabet mappings)\x1b[0m", other.len());
+            }
+        }
+    }
+}
+
+/// 組み込みのお題・ユーザーのお題・入っているパックをまとめて診断する
+fn build_pool_health(roman_map: &HashMap<&'static str, Vec<&'static str>>, blacklist: &[QuestionId]) -> PoolHealth {
+    let user_questions = UserQuestions::load().unwrap_or_default();
+    let builtin = QUESTIONS_LIST.iter().enumerate().map(|(idx, q)| PoolEntry {
+        pack: BUILTIN_PACK_ID,
+        japanese: q.japanese,
+        hiragana: q.hiragana,
+        unreachable: blacklist.contains(&QuestionId::builtin(idx)).then_some("blacklisted"),
+    });
+    let user = user_questions.questions.iter().enumerate().map(|(idx, q)| PoolEntry {
+        pack: USER_PACK_ID,
+        japanese: &q.japanese,
+        hiragana: &q.hiragana,
+        unreachable: blacklist.contains(&QuestionId::new(USER_PACK_ID, idx)).then_some("blacklisted"),
+    });
+    let (packs, _) = installed_packs(&packs_dir());
+    let installed = packs.iter().flat_map(|pack| {
+        pack.file.questions.iter().enumerate().map(|(idx, q)| PoolEntry {
+            pack: &pack.id,
+            japanese: &q.japanese,
+            hiragana: &q.hiragana,
+            unreachable: blacklist.contains(&QuestionId::new(&pack.id, idx)).then_some("blacklisted"),
+        })
+    });
+    let entries: Vec<PoolEntry> = builtin.chain(user).chain(installed).collect();
+    PoolHealth::analyze(&entries, roman_map)
+}
+
+fn run_pool_doctor(format: OutputFormat) -> Result<()> {
+    let player_data = FileStorage.load();
+    let report = build_pool_health(&create_roman_mapping(), &player_data.blacklist);
+    emit(&report, format)
+}
+
+// --------------------------------------------------
+// MARK:累計の成績
+// --------------------------------------------------
+
+/// 1か月分の入手元ごとの経験値
+#[derive(serde::Serialize)]
+struct XpMonth {
+    month: String,
+    questions: u64,
+    missions: u64,
+    streak: u64,
+    daily: u64,
+    mini_game: u64,
+    tag_boost: u64,
+}
+
+/// `stats` コマンドの結果
+#[derive(serde::Serialize)]
+struct StatsReport {
+    level: u32,
+    current_xp: u32,
+    total_typed_chars: u64,
+    total_misses: u64,
+    total_practice_secs: u64,
+    /// `--intent` で絞った目的（下の記録からの集計はこの目的の記録だけ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intent: Option<SessionIntent>,
+    /// ミスから立ち直るのにかかった時間の合計（見積もりを記録している記録のみ）
+    time_lost_to_errors_secs: f64,
+    /// `--xp` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xp_by_month: Option<Vec<XpMonth>>,
+    /// `--cooldowns` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cooldowns: Option<CooldownComparison>,
+    /// `--compare` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compare: Option<WindowComparison>,
+    /// `--rotation` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotation: Option<RotationReport>,
+    /// `--confusions` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confusions: Option<Vec<Confusion>>,
+    /// `--by-intent` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    by_intent: Option<Vec<IntentTotals>>,
+    /// `--code` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<SetTotals>,
+    /// `--pace` のときだけ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pace: Option<PaceProfile>,
+}
+
+impl Report for StatsReport {
+    fn print_plain(&self) {
+        outln!("  Level          : {} ({} XP)", self.level, self.current_xp);
+        outln!("  Typed chars    : {}", format_count(self.total_typed_chars));
+        outln!("  Misses         : {}", format_count(self.total_misses));
+        outln!("  Practice time  : {}", format_practice_time(self.total_practice_secs));
+        if let Some(intent) = self.intent {
+            outln!("\x1b[90m  Records below  : {} sessions only\x1b[0m", intent.label());
+        }
+        outln!("  Lost to errors : {}", format_duration(self.time_lost_to_errors_secs));
+
+        if let Some(cooldowns) = &self.cooldowns {
+            outln!();
+            let line = |totals: &SetTotals| totals.summary().unwrap_or_else(|| "no records".to_string());
+            outln!("  After cooldown : {}", line(&cooldowns.after_cooldown));
+            outln!("  Other          : {}", line(&cooldowns.other));
+        }
+
+        if let Some(comparison) = &self.compare {
+            outln!();
+            print_comparison(comparison);
+        }
+
+        if let Some(code) = &self.code {
+            outln!();
+            outln!("  Code drill     : {}", code.summary().unwrap_or_else(|| "no records".to_string()));
+        }
+
+        if let Some(pace) = &self.pace {
+            outln!();
+            match pace.summary() {
+                Some(summary) => {
+                    outln!("  {} ({} questions)", summary, format_count(pace.records as u64));
+                    for line in pace.bar_lines() {
+                        outln!("    {}", line);
+                    }
+                }
+                None => outln!("\x1b[90m  Not enough keystroke timings recorded to show a pace profile yet.\x1b[0m"),
+            }
+        }
+
+        if let Some(rows) = &self.by_intent {
+            outln!();
+            if rows.is_empty() {
+                outln!("\x1b[90m  No records yet.\x1b[0m");
+            }
+            for row in rows {
+                outln!("  {:<14} : {}", row.intent.label(), row.totals.summary().unwrap_or_default());
+            }
+        }
+
+        if let Some(rotation) = &self.rotation {
+            outln!();
+            print_rotation(rotation);
+        }
+
+        if let Some(confusions) = &self.confusions {
+            outln!();
+            if confusions.is_empty() {
+                outln!("\x1b[90m  Not enough misses recorded to show confusions yet.\x1b[0m");
+            }
+            for confusion in confusions {
+                outln!("  {}", confusion.line());
+            }
+        }
+
+        let Some(months) = &self.xp_by_month else {
+            return;
+        };
+        outln!();
+        if months.is_empty() {
+            outln!("\x1b[90m  No XP earned yet.\x1b[0m");
+            return;
+        }
+        let labels = XpSource::ALL.map(|source| source.label());
+        outln!(
+            "  {:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "month", labels[0], labels[1], labels[2], labels[3], labels[4], labels[5], "total"
+        );
+        let mut sums = [0u64; XpSource::ALL.len()];
+        for month in months {
+            let row = [month.questions, month.missions, month.streak, month.daily, month.mini_game, month.tag_boost];
+            for (sum, value) in sums.iter_mut().zip(row) {
+                *sum += value;
+            }
+            outln!(
+                "  {:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                month.month, row[0], row[1], row[2], row[3], row[4], row[5], row.iter().sum::<u64>()
+            );
+        }
+        outln!(
+            "\x1b[1m  {:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}\x1b[0m",
+            "total", sums[0], sums[1], sums[2], sums[3], sums[4], sums[5], sums.iter().sum::<u64>()
+        );
+    }
+}
+
+impl Report for SessionEstimate {
+    fn print_plain(&self) {
+        outln!("  Questions      : {} ({} blacklisted)", self.questions, self.blacklisted);
+        outln!("  Keystrokes     : {}", self.keystrokes);
+        outln!(
+            "  Speed          : {:.2} CPS ({})",
+            self.cps,
+            if self.cps_from_history { "recent average" } else { "default; no history yet" }
+        );
+        outln!("  Whole pool     : {}", format_estimate(self.total_secs));
+        if let (Some((low, high)), Some((min, max))) = (self.tiers, self.per_question_secs) {
+            outln!("  Per question   : {} (adaptive, tiers {}–{})", format_secs_range(min, max), low, high);
+        }
+    }
+}
+
+/// 出題回数の多いお題と少ないお題を並べる
+fn print_rotation(rotation: &RotationReport) {
+    const SHOWN: usize = 5;
+    outln!("  Serves         : {} across {} questions", rotation.total_serves, rotation.rows.len());
+    outln!("  Never served   : {}", rotation.never_served);
+    outln!("  Skew (Gini)    : {:.3} (0 = perfectly even)", rotation.skew);
+    if rotation.total_serves == 0 {
+        return;
+    }
+    let print_rows = |label: &str, rows: &[RotationRow]| {
+        outln!("  {}", label);
+        for row in rows {
+            outln!("    {:>5}  {} ({})", row.serves, row.japanese, row.hiragana);
+        }
+    };
+    let shown = SHOWN.min(rotation.rows.len() / 2);
+    print_rows("Most served:", &rotation.rows[..shown]);
+    print_rows("Least served:", &rotation.rows[rotation.rows.len() - shown..]);
+}
+
+/// 直近の期間とその前の期間の比較を表にする
+fn print_comparison(comparison: &WindowComparison) {
+    let days = comparison.days;
+    outln!("  {:<12} {:>14} {:>14}  change", "", format!("prev {}d", days), format!("last {}d", days));
+    if comparison.current.is_none() && comparison.previous.is_none() {
+        outln!("\x1b[90m  No records in the last {} days.\x1b[0m", days * 2);
+        return;
+    }
+    for (label, decimals, previous, current) in comparison.rows() {
+        let cell = |value: Option<f64>| value.map_or("no data".to_string(), |v| format!("{:.*}", decimals, v));
+        let change = match (previous, current) {
+            (Some(previous), Some(current)) => {
+                let delta = format_delta(previous, current);
+                let color = if current > previous { "32" } else if current < previous { "31" } else { "90" };
+                format!("\x1b[{}m{}\x1b[0m", color, delta)
+            }
+            _ => "\x1b[90m-\x1b[0m".to_string(),
+        };
+        outln!("  {:<12} {:>14} {:>14}  {}", label, cell(previous), cell(current), change);
+    }
+}
+
+fn run_stats(args: &StatsArgs, format: OutputFormat) -> Result<()> {
+    let player_data = FileStorage.load();
+    if args.rotation {
+        serve_extra_questions();
+    }
+    emit(&stats_report(&player_data, args, Utc::now()), format)
+}
+
+/// `stats` の集計（`now` は期間の比較の基準にする時刻）
+fn stats_report(player_data: &PlayerData, args: &StatsArgs, now: DateTime<Utc>) -> StatsReport {
+    let xp_by_month = args.xp.then(|| {
+        player_data
+            .xp_ledger
+            .monthly_totals()
+            .into_iter()
+            .map(|(month, totals)| XpMonth {
+                month: month.format("%Y-%m").to_string(),
+                questions: totals[0],
+                missions: totals[1],
+                streak: totals[2],
+                daily: totals[3],
+                mini_game: totals[4],
+                tag_boost: totals[5],
+            })
+            .collect()
+    });
+    // 目的を指定したときは、記録から集計する項目だけをその目的の記録に絞る
+    let only = args.intent;
+    let history = || {
+        player_data.history.iter().filter(move |record| !record.code && only.is_none_or(|intent| record.intent == intent))
+    };
+    StatsReport {
+        level: player_data.level,
+        current_xp: player_data.current_xp,
+        total_typed_chars: player_data.total_typed_chars,
+        total_misses: player_data.total_misses,
+        total_practice_secs: player_data.total_practice_secs,
+        intent: args.intent,
+        time_lost_to_errors_secs: history().map(|record| f64::from(record.error_loss_ms) / 1000.0).sum(),
+        xp_by_month,
+        cooldowns: args.cooldowns.then(|| CooldownComparison::from_history(history())),
+        compare: args.compare.map(|days| WindowComparison::from_history(history(), now, days)),
+        rotation: args.rotation.then(|| rotation_report(player_data)),
+        confusions: args.confusions.then(|| player_data.confusions.top(TOP_CONFUSIONS)),
+        pace: args.pace.then(|| PaceProfile::from_records(history())),
+        by_intent: args.by_intent.then(|| totals_by_intent(player_data.history.iter().filter(|record| !record.code))),
+        code: args.code.then(|| {
+            let mut totals = SetTotals::default();
+            for record in player_data.history.iter().filter(|record| record.code) {
+                totals.add(record.total_chars, record.misses, record.duration_sec, f64::from(record.error_loss_ms) / 1000.0);
+            }
+            totals
+        }),
+    }
+}
+
+/// 出題範囲（ブラックリストを除く、通常の出題に使うお題）の出題回数
+fn rotation_report(player_data: &PlayerData) -> RotationReport {
+    let rows = served_questions()
+        .filter(|(id, _)| !player_data.blacklist.contains(id))
+        .map(|(id, q)| RotationRow {
+            japanese: q.japanese.to_string(),
+            hiragana: q.hiragana.to_string(),
+            serves: player_data.serve_counts.get(&id).copied().unwrap_or(0),
+        })
+        .collect();
+    RotationReport::new(rows)
+}
+
+// --------------------------------------------------
+// MARK:お題の管理コマンド
+// --------------------------------------------------
+
+/// ユーザーのお題とダウンロードしたパックを通常の出題に加える（読めなかったものを知らせる文を返す）
+fn serve_extra_questions() -> Vec<String> {
+    let (_, user_notice) = serve_user_questions();
+    user_notice.into_iter().chain(serve_installed_packs()).collect()
+}
+
+fn run_packs(command: &PacksCommand) -> Result<()> {
+    let dir = packs_dir();
+    match command {
+        PacksCommand::Fetch { url, sha256 } => {
+            match fetch(&dir, url, sha256.as_deref(), &create_roman_mapping(), &HttpDownload) {
+                Ok(fetched) => outln!(
+                    "\x1b[32m  Installed \"{}\" as {} ({} questions){}\x1b[0m",
+                    fetched.name,
+                    fetched.id,
+                    fetched.questions,
+                    if fetched.changed { "" } else { " — unchanged" }
+                ),
+                Err(e) => outln!("\x1b[31m  Could not fetch {}: {}\x1b[0m", url, e),
+            }
+        }
+        PacksCommand::Update => {
+            let results = update_all(&dir, &create_roman_mapping(), &HttpDownload)?;
+            if results.is_empty() {
+                outln!("\x1b[90m  No packs were fetched from a URL.\x1b[0m");
+            }
+            for (id, result) in results {
+                match result {
+                    Ok(fetched) if fetched.changed => {
+                        outln!("\x1b[32m  {:<20} updated ({} questions)\x1b[0m", id, fetched.questions)
+                    }
+                    Ok(_) => outln!("  {:<20} unchanged", id),
+                    Err(e) => outln!("\x1b[31m  {:<20} {}\x1b[0m", id, e),
+                }
+            }
+        }
+        PacksCommand::List => {
+            let (packs, warnings) = installed_packs(&dir);
+            for warning in warnings {
+                outln!("\x1b[33m  {}\x1b[0m", warning);
+            }
+            if packs.is_empty() {
+                outln!("\x1b[90m  No packs installed in {}\x1b[0m", dir.display());
+            }
+            for pack in packs {
+                outln!("  {:<20} {}", pack.id, pack_label(&pack));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// パックの名前・お題の数・取り込み元と最後に変わった日
+fn pack_label(pack: &InstalledPack) -> String {
+    let origin = match &pack.source {
+        Some(source) => format!(
+            "from {} · updated {}",
+            source.url,
+            source.updated.with_timezone(&Local).format("%Y-%m-%d")
+        ),
+        None => "local file".to_string(),
+    };
+    format!("{} ({} questions) · {}", pack.file.name, pack.file.questions.len(), origin)
+}
+
+fn run_questions(command: &QuestionsCommand) -> Result<()> {
+    match command {
+        QuestionsCommand::ImportTxt { file, japanese_same, dry_run } => {
+            run_import_txt(file, *japanese_same, *dry_run)
+        }
+        QuestionsCommand::Note { id, text } => run_note(*id, text),
+    }
+}
+
+fn run_note(id: QuestionId, text: &str) -> Result<()> {
+    serve_extra_questions();
+    let Some(question) = find_question(id) else {
+        outln!("\x1b[31m  No question with id {}\x1b[0m", id);
+        return Ok(());
+    };
+    let text = match validate_note(text) {
+        Ok(text) => text,
+        Err(e) => {
+            outln!("\x1b[31m  {}\x1b[0m", e);
+            return Ok(());
+        }
+    };
+
+    let mut player_data = FileStorage.load();
+    if text.is_empty() {
+        outln!("  Removed the note from {}", question.japanese);
+    } else {
+        outln!("  {}: {}", question.japanese, text);
+    }
+    player_data.set_note(id, text);
+    FileStorage.save(&player_data);
+    Ok(())
+}
+
+fn run_import_txt(file: &Path, japanese_same: bool, dry_run: bool) -> Result<()> {
+    let text = fs::read_to_string(file)?;
+    let mut user_questions = UserQuestions::load()?;
+    let limit = Settings::load().length_limit();
+    let summary = parse_word_list(&text, japanese_same, &create_roman_mapping(), &user_questions.questions, limit);
+
+    for (line_no, reason) in &summary.skipped {
+        outln!("\x1b[33m  line {}: {}\x1b[0m", line_no, reason);
+    }
+    for (line_no, parts) in &summary.split {
+        outln!("\x1b[90m  line {}: longer than {} keys, split into {} parts\x1b[0m", line_no, limit.max_keystrokes, parts);
+    }
+    outln!("  Imported {}, skipped {}", summary.imported.len(), summary.skipped.len());
+
+    if dry_run {
+        outln!("  Dry run: nothing was saved.");
+    } else if !summary.imported.is_empty() {
+        user_questions.questions.extend(summary.imported);
+        user_questions.save()?;
+        outln!("  Saved to {}", UserQuestions::get_file_path().display());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:保存場所の表示コマンド
+// --------------------------------------------------
+
+fn show_where() -> Result<()> {
+    outln!("  Data directory : {}", get_data_dir().display());
+
+    let files = [
+        ("Save file", PlayerData::get_save_file_path()),
+        ("Debug JSON", PlayerData::get_debug_json_path()),
+        ("Settings", Settings::get_settings_file_path()),
+        ("User questions", UserQuestions::get_file_path()),
+        ("Roster", roster::get_file_path()),
+    ];
+    for (name, path) in files {
+        let status = match fs::metadata(&path) {
+            Ok(meta) => format!("{} bytes", meta.len()),
+            Err(_) => "not found".to_string(),
+        };
+        outln!("  {:<15}: {} ({})", name, path.display(), status);
+    }
+    let user_count = UserQuestions::load().map_or(0, |user_questions| user_questions.questions.len());
+    outln!("  {:<15}: built-in ({} questions) + user ({} questions)", "Questions", QUESTIONS_LIST.len(), user_count);
+    outln!("  {:<15}: {} ({} installed)", "Packs", packs_dir().display(), installed_packs(&packs_dir()).0.len());
+    Ok(())
+}
+
+/// OS のファイルマネージャーでデータフォルダを開く
+fn open_data_dir() -> Result<()> {
+    let dir = get_data_dir();
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    // explorer は成功しても終了コードが 1 になることがあるため、起動できたかだけを確認する
+    Command::new(program).arg(&dir).spawn()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:メニュー表示（通常スクリーン）
+// --------------------------------------------------
+
+fn show_menu(app_state: &mut AppState) -> Result<bool> {
+    
+    let term = Term::stdout();
+    // 読み込み中のセーブデータが読み終わっていれば差し替える（お知らせもこの画面で出す）
+    app_state.poll_profile();
+    app_state.poll_update();
+
+    // タイトルロゴ
+    outln!();
+
+    outln!("\x1b[38;5;202m    ████████\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╗   \x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██████\x1b[38;5;166m╗ \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+
+    outln!("    \x1b[38;5;166m╚══\x1b[38;5;202m██\x1b[38;5;166m╔══╝╚\x1b[38;5;202m██\x1b[38;5;166m╗ \x1b[38;5;202m██\x1b[38;5;166m╔╝\x1b[38;5;202m██\x1b[38;5;166m╔══\x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╔════╝\x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║    ╚\x1b[38;5;202m████\x1b[38;5;166m╔╝ \x1b[38;5;202m██████\x1b[38;5;166m╔╝\x1b[38;5;202m█████\x1b[38;5;166m╗  \x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║     ╚\x1b[38;5;202m██\x1b[38;5;166m╔╝  \x1b[38;5;202m██\x1b[38;5;166m╔═══╝ \x1b[38;5;202m██\x1b[38;5;166m╔══╝  \x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║      \x1b[38;5;202m██\x1b[38;5;166m║   \x1b[38;5;202m██\x1b[38;5;166m║     \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+
+    outln!("\x1b[38;5;166m       ╚═╝      ╚═╝   ╚═╝     ╚══════╝ \x1b[38;5;202mWiZ.\x1b[0m");
+
+    outln!();
+
+    // タイトルロゴ
+    outln!();
+
+    outln!("\x1b[38;5;202m    ████████\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╗   \x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██████\x1b[38;5;166m╗ \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+
+    outln!("    \x1b[38;5;166m╚══\x1b[38;5;202m██\x1b[38;5;166m╔══╝╚\x1b[38;5;202m██\x1b[38;5;166m╗ \x1b[38;5;202m██\x1b[38;5;166m╔╝\x1b[38;5;202m██\x1b[38;5;166m╔══\x1b[38;5;202m██\x1b[38;5;166m╗\x1b[38;5;202m██\x1b[38;5;166m╔════╝\x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║    ╚\x1b[38;5;202m████\x1b[38;5;166m╔╝ \x1b[38;5;202m██████\x1b[38;5;166m╔╝\x1b[38;5;202m█████\x1b[38;5;166m╗  \x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║     ╚\x1b[38;5;202m██\x1b[38;5;166m╔╝  \x1b[38;5;202m██\x1b[38;5;166m╔═══╝ \x1b[38;5;202m██\x1b[38;5;166m╔══╝  \x1b[0m");
+
+    outln!("\x1b[38;5;202m       ██\x1b[38;5;166m║      \x1b[38;5;202m██\x1b[38;5;166m║   \x1b[38;5;202m██\x1b[38;5;166m║     \x1b[38;5;202m███████\x1b[38;5;166m╗\x1b[0m");
+
+    outln!("\x1b[38;5;166m       ╚═╝      ╚═╝   ╚═╝     ╚══════╝ \x1b[38;5;202mWiZ.\x1b[0m");
+
+    outln!();
+
+    if !app_state.menu_notices.is_empty() {
+        for notice in app_state.menu_notices.drain(..) {
+            outln!("\x1b[33m  {}\x1b[0m", notice);
+        }
+        outln!();
+    }
+
+    // 未保存の変更があれば * を付ける
+    if app_state.pending_profile.is_some() {
+        outln!("\x1b[90m  Loading profile…\x1b[0m");
+    } else {
+        outln!(
+            "\x1b[90m  Lv.{} · {} total\x1b[0m{}",
+            app_state.player_data.level,
+            format_practice_time(app_state.player_data.total_practice_secs),
+            if app_state.has_unsaved_changes() { " \x1b[33m* unsaved changes\x1b[0m" } else { "" }
+        );
+    }
+    if app_state.storage.save_pending() {
+        outln!("\x1b[33m  Saving is waiting for the data folder to respond (retrying in the background)\x1b[0m");
+    }
+    outln!("\x1b[90m  Pool: {}\x1b[0m", app_state.session_estimate().summary());
+    if let Some(plan) = &app_state.day_plan {
+        outln!("\x1b[90m  Today's plan: {} ({})\x1b[0m", plan.label(), Local::now().weekday());
+    }
+    if let Some(version) = &app_state.settings.previous_version && old_binary_path().is_some_and(|path| path.exists()) {
+        outln!("\x1b[90m  Rollback available: v{} (typewiz update --rollback)\x1b[0m", version);
+    }
+    outln!();
+
+    let quick_start = format!("Quick Start (last settings: {})", app_state.settings.last_session.label());
+    let items = vec![
+        quick_start.as_str(),
+        "Start Type",
+        "Sentence Mode",
+        "Favorites",
+        "Falling Words",
+        "Code Drill",
+        "Pick Question",
+        "Author Question",
+        "Mission (Coming Soon...)",
+        "Game Log",
+        "Trends",
+        "Achievements",
+        "Weekly Report",
+        "Today",
+        "Leaderboard (Coming Soon...)",
+        "Settings",
+        "Exit",
+    ];
+    
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .items(&items)
+        .default(app_state._menu_index)
+        .interact_opt()?;
+
+    match selection {
+        Some(0) => {
+            // Quick Start
+            let config = app_state.settings.last_session;
+            app_state.start_session(config)?;
+            Ok(true)
+        }
+        Some(1) => {
+            app_state.start_session(SessionConfig::default())?;
+            Ok(true)
+        }
+        Some(2) => {
+            // Sentence Mode
+            app_state.start_session(SessionConfig { mode: SessionMode::Sentence, question: None })?;
+            Ok(true)
+        }
+        Some(3) => {
+            // Favorites
+            app_state.start_session(SessionConfig { mode: SessionMode::Favorites, question: None })?;
+            Ok(true)
+        }
+        Some(4) => {
+            // Falling Words
+            app_state.mode = AppMode::FallingWords;
+            Ok(true)
+        }
+        Some(5) => {
+            // Code Drill
+            app_state.mode = AppMode::CodeDrill;
+            Ok(true)
+        }
+        Some(6) => {
+            // Pick Question
+            app_state.mode = AppMode::Picker;
+            Ok(true)
+        }
+        Some(7) => {
+            // Author Question
+            app_state.mode = AppMode::Author;
+            Ok(true)
+        }
+        Some(8) => {
+            
+            app_state.mode = AppMode::Menu;
+            term.clear_screen()?;
+
+            Ok(false)
+        }
+        Some(9) => {
+            // Game Log
+            app_state.mode = AppMode::Log;
+            Ok(true)
+        }
+        Some(10) => {
+            // Trends
+            app_state.mode = AppMode::Trends;
+            Ok(true)
+        }
+        Some(11) => {
+            // Achievements
+            app_state.mode = AppMode::Achievements;
+            Ok(true)
+        }
+        Some(12) => {
+            // Weekly Report
+            app_state.mode = AppMode::WeeklyReport;
+            Ok(true)
+        }
+        Some(13) => {
+            // Today
+            app_state.mode = AppMode::Today;
+            Ok(true)
+        }
+        Some(15) => {
+            // Settings
+            app_state.mode = AppMode::Settings;
+            Ok(true)
+        }
+        Some(16) | None => {
+            // Exit or Esc
+            app_state.mode = AppMode::Exit;
+            Ok(false)
+        }
+        _ => {
+            // Coming Soon...
+            // show_menu(app_state)?;
+            app_state.mode = AppMode::Menu;
+            Ok(false)
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:タイピングモード（代替スクリーン）
+// --------------------------------------------------
+
+fn run_typing_mode(app_state: &mut AppState) -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?; // 代替スクリーンを使用
+    stdout().execute(Hide)?; // カーソルを非表示
+    let backend = CrosstermBackend::new(stdout());
+    let mut terminal = Terminal::new(backend)?;
+    app_state.begin_session();
+    let mut pacer = FramePacer::new(app_state.settings.animation_fps, app_state.settings.tick_rate_ms);
+    // 配信はこの画面を開いている間だけ。関数を抜けると落ちて、観戦側との接続も閉じる
+    let mut broadcaster = match app_state.broadcast.map(|(host, port)| Broadcaster::start(host, port)) {
+        Some(Ok(broadcaster)) => {
+            app_state.flash = Some((format!("Broadcasting on {}", broadcaster.addr()), Instant::now()));
+            Some(broadcaster)
+        }
+        Some(Err(e)) => {
+            app_state.flash = Some((format!("Could not start the broadcast: {}", e), Instant::now()));
+            None
+        }
+        None => None,
+    };
+
+    loop {
+        app_state.check_sleep();
+        app_state.check_afk();
+        app_state.autosave_if_due();
+        app_state.checkpoint_if_due();
+        // 入れ替わりを待つ時間が過ぎた保留中のキーは、そのままミスとして処理する
+        if let Some(c) = app_state.expire_rollover(Instant::now()) {
+            app_state.submit_char(c);
+            pacer.mark_dirty();
+        }
+        if app_state.check_time_limit() {
+            pacer.mark_dirty();
+        }
+        if app_state.metronome_beat() {
+            stdout().execute(Print("\x07"))?;
+        }
+
+        // 入力があったときと、1コマ分の時間が経ったときだけ描き直す
+        let now = Instant::now();
+        let pace = app_state.pace(now);
+        if pacer.should_draw(now, pace) {
+            app_state.mark_question_shown(now);
+            terminal.draw(|f| {
+                ui_typing(f, &typing_model(app_state, now));
+                if debug_log::enabled() {
+                    render_frame_stats(f, &pacer);
+                }
+            })?;
+            pacer.record_draw(now);
+        }
+        if let Some(broadcaster) = broadcaster.as_mut() {
+            broadcaster.send_if_due(now, || app_state.spectator_frame(now));
+        }
+
+        // メトロノームの拍に遅れて表示しないよう、次の拍までしか待たない
+        let now = Instant::now();
+        let mut poll_timeout = pacer.poll_timeout(now, pace);
+        if let Some(metronome) = app_state.metronome.as_ref() {
+            poll_timeout = poll_timeout.min(metronome.until_next_beat(now));
+        }
+        if let Some(deadline) = app_state.rollover_deadline() {
+            poll_timeout = poll_timeout.min(deadline.saturating_duration_since(now));
+        }
+        if event::poll(poll_timeout)? {
+            pacer.mark_dirty();
+            let event = event::read()?;
+            dlog!("input", "event={:?}", event);
+            if let Event::Key(key) = event && key.kind == event::KeyEventKind::Press {
+                // 演出は待たずに打ち切り、このキーは次のお題に使う
+                app_state.completion_sweep = None;
+                // メモの編集中は入力欄にキーを渡す（ローマ字の判定はしない）
+                if app_state.note_editor.is_some() {
+                    app_state.handle_note_key(&key);
+                    continue;
+                }
+                // 休憩の画面では Esc 以外のキーで閉じるだけ（強制中は時間が経つまで閉じない）
+                if app_state.cooldown_since.is_some() && lookup(TYPING_BINDINGS, &key) != Some(Action::Quit) {
+                    app_state.end_cooldown();
+                    continue;
+                }
+                // 休憩を勧める画面も同じ（閉じられるまでの時間は設定によらず待つ）
+                if app_state.break_since.is_some() && lookup(TYPING_BINDINGS, &key) != Some(Action::Quit) {
+                    app_state.end_break();
+                    continue;
+                }
+                // ヘルプ表示中はどのキーでも閉じるだけ
+                if app_state.show_help {
+                    app_state.close_help();
+                    continue;
+                }
+                // タイムアタックの結果は Esc 以外のキーで閉じて通常の出題に戻る
+                if app_state.time_attack.as_ref().is_some_and(TimeAttack::is_finished)
+                    && lookup(TYPING_BINDINGS, &key) != Some(Action::Quit)
+                {
+                    app_state.close_time_attack();
+                    continue;
+                }
+
+                let action = lookup(TYPING_BINDINGS, &key);
+                dlog!("input", "action={:?}", action);
+                if !matches!(action, Some(Action::Quit | Action::Help)) {
+                    app_state.register_activity();
+                }
+                if action.is_some() {
+                    app_state.flush_rollover();
+                }
+                match action {
+                    Some(Action::Quit) => {
+                        // stdout().execute(Show)?;
+                        stdout().execute(LeaveAlternateScreen)?;
+                        disable_raw_mode()?;
+                        app_state.mode = AppMode::Exit;
+                        // 途中でやめたタイムアタックも、それまでの一番良い回の経験値は与える
+                        if app_state.time_attack.as_ref().is_some_and(|attack| !attack.is_finished()) {
+                            app_state.settle_time_attack();
+                        }
+                        let recommendation = app_state.finish_drill_or_recommend();
+                        app_state.load_current_question();
+                        if let Some(summary) = app_state.session.summary() {
+                            app_state.menu_notices.push(summary);
+                        }
+                        if let Some(rhythm) = app_state.metronome.as_ref().and_then(Metronome::summary) {
+                            app_state.menu_notices.push(rhythm.line());
+                        }
+                        app_state.end_session();
+                        if let Some(recommendation) = recommendation {
+                            offer_drill(app_state, &recommendation)?;
+                        }
+                        return Ok(());
+                    }
+                    Some(Action::Backspace) => app_state.handle_backspace(),
+                    Some(Action::DeleteUnit) => app_state.handle_delete_unit(),
+                    Some(Action::ResetQuestion) => app_state.handle_reset_question(),
+                    Some(Action::Blacklist) => app_state.toggle_blacklist_last(),
+                    Some(Action::Bookmark) => app_state.toggle_bookmark_last(),
+                    Some(Action::EditNote) => app_state.open_note_editor(),
+                    Some(Action::ToggleFocus) => {
+                        app_state.settings.edit().focus_mode = !app_state.settings.focus_mode;
+                    }
+                    Some(Action::CycleResultDisplay) => app_state.cycle_result_persistence(),
+                    Some(Action::RetryLast) => app_state.retry_last_question(),
+                    Some(Action::CopyRecord) => app_state.copy_last_result(),
+                    // リマップの一時切り替え
+                    Some(Action::ToggleRemap) => app_state.remapper.toggle(),
+                    Some(Action::Help) => app_state.open_help(),
+                    Some(
+                        Action::Back
+                        | Action::CycleWindow
+                        | Action::CycleMetric
+                        | Action::Compare
+                        | Action::Search
+                        | Action::NextMatch
+                        | Action::PrevMatch
+                        | Action::ScrollUp
+                        | Action::ScrollDown
+                        | Action::PageUp
+                        | Action::PageDown
+                        | Action::StartTurn
+                        | Action::SkipStudent
+                        | Action::CycleLayout
+                        | Action::MeasureRepeat,
+                    ) => {}
+                    None => {
+                        if let KeyCode::Char(c) = key.code {
+                            let c = app_state.remapper.apply(c);
+                            if app_state.governor_drops_key(Instant::now()) {
+                                dlog!("match", "char={:?} dropped by the governor", c);
+                                continue;
+                            }
+                            if app_state.filter_chatter(c) {
+                                dlog!("match", "char={:?} dropped as chatter", c);
+                                continue;
+                            }
+                            for c in app_state.rollover_keys(c, Instant::now()) {
+                                app_state.submit_char(c);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:授業モード（代替スクリーン）
+// --------------------------------------------------
+
+/// 名簿の生徒が順番にその日のお題を打つ（記録はプレイヤーのセーブデータに入れない）
+fn run_roster(file: &Path) -> Result<()> {
+    let names = roster::parse_names(&fs::read_to_string(file)?);
+    if names.is_empty() {
+        outln!("\x1b[33m  No names found in {}.\x1b[0m", file.display());
+        return Ok(());
+    }
+    let today = Local::now().date_naive();
+    let daily = roster::daily_question_index(today, QUESTIONS_LIST.len());
+    let mut app_state = AppState::with_data(
+        Settings::load(),
+        Box::new(MemoryStorage::default()),
+        QuestionQueue::with_order(BUILTIN_PACK_ID, QUESTIONS_LIST, vec![daily]),
+    );
+    let mut class = ClassSession::new(names);
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    loop {
+        class.tick(Instant::now());
+        if class.phase == RosterPhase::Finished {
+            break;
+        }
+        terminal.draw(|f| ui_roster(f, &roster_model(&class, &app_state, Instant::now())))?;
+
+        if !event::poll(ROSTER_POLL_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != event::KeyEventKind::Press {
+            continue;
+        }
+        match class.phase {
+            RosterPhase::Ready => match lookup(ROSTER_READY_BINDINGS, &key) {
+                Some(Action::StartTurn) => {
+                    app_state.reset_turn();
+                    class.start();
+                }
+                Some(Action::SkipStudent) => class.skip(),
+                Some(Action::Quit) => class.finish(),
+                _ => {}
+            },
+            RosterPhase::Typing => match lookup(ROSTER_TYPING_BINDINGS, &key) {
+                Some(Action::Back) => class.interrupt(),
+                Some(Action::Backspace) => app_state.handle_backspace(),
+                _ => {
+                    if let KeyCode::Char(c) = key.code {
+                        let c = app_state.remapper.apply(c);
+                        app_state.handle_char_input(c);
+                        if app_state.is_question_complete() {
+                            class.complete(app_state.turn_result(), Instant::now());
+                        }
+                    }
+                }
+            },
+            RosterPhase::Result { .. } => class.dismiss_result(Instant::now()),
+            RosterPhase::Finished => {}
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    let path = roster::class_summary_path(file, today);
+    roster::write_class_summary(&class, &path)?;
+    outln!("  {} of {} student(s) finished.", class.done_count(), class.names.len());
+    outln!("\x1b[32m  Class summary written to {}\x1b[0m", path.display());
+    Ok(())
+}
+
+/// 授業モードの画面の内容（生徒の名前と、段階ごとの案内・お題・結果）
+fn roster_model<'a>(class: &ClassSession, app_state: &'a AppState, now: Instant) -> RosterModel<'a> {
+    let title = format!(" TYPE WiZ · Class {}/{} ", (class.current + 1).min(class.names.len()), class.names.len());
+    let mut message = Vec::new();
+    let mut question = None;
+    match class.phase {
+        RosterPhase::Ready => {
+            message = vec![
+                Line::from("Press Enter or Space to start").yellow(),
+                Line::from("Tab: absent · Esc: end the class").dark_gray(),
+            ];
+        }
+        RosterPhase::Typing => question = Some(question_view(app_state, now)),
+        RosterPhase::Result { shown_at } => {
+            if let Some(StudentOutcome::Done(result)) = class.outcomes.get(class.current) {
+                let remaining = roster::RESULT_SCREEN_DURATION.saturating_sub(now.saturating_duration_since(shown_at));
+                message = vec![
+                    Line::from(format!(
+                        "CPS {:.2} · Accuracy {:.1}% · Score {:.0}",
+                        result.cps, result.accuracy, result.score
+                    ))
+                    .yellow()
+                    .bold(),
+                    Line::from(format!("Next student in {}s (any key to continue)", remaining.as_secs() + 1)).dark_gray(),
+                ];
+            }
+        }
+        RosterPhase::Finished => {}
+    }
+    RosterModel {
+        title,
+        name: class.current_name().unwrap_or_default().to_string(),
+        message,
+        question,
+        japanese_width: Line::from(app_state.current_japanese()).width(),
+        hiragana_width: Line::from(app_state.current_hiragana()).width(),
+    }
+}
+
+fn ui_roster(f: &mut Frame, model: &RosterModel) {
+    let size = f.area();
+    let block = Block::default().borders(Borders::ALL).title(model.title.as_str());
+    let inner = block.inner(size);
+    f.render_widget(block, size);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(2),
+            Constraint::Length(wrapped_height(model.japanese_width, inner.width)),
+            Constraint::Length(1),
+            Constraint::Length(wrapped_height(model.hiragana_width, inner.width)),
+            Constraint::Min(1),
+        ])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(Line::from(model.name.as_str()).bold().cyan()).centered(), chunks[0]);
+    f.render_widget(Paragraph::new(model.message.clone()).centered(), chunks[1]);
+    if let Some(question) = &model.question {
+        render_question_lines(f, question, chunks[2], chunks[4], chunks[5]);
+    }
+}
+
+// --------------------------------------------------
+// MARK:アップデートと巻き戻し
+// --------------------------------------------------
+
+fn run_update(rollback_requested: bool) -> Result<()> {
+    let mut settings = Settings::load();
+    if rollback_requested {
+        match rollback() {
+            Ok(()) => {
+                let version = settings.previous_version.take().unwrap_or_else(|| "the previous version".to_string());
+                settings.save();
+                outln!("\x1b[32m  Rolled back to {}.\x1b[0m", version);
+            }
+            Err(e) => outln!("\x1b[31m  Rollback failed: {}\x1b[0m", e),
+        }
+        return Ok(());
+    }
+
+    match update() {
+        Ok(UpdateOutcome::UpToDate) => outln!("  Already up to date ({}).", env!("CARGO_PKG_VERSION")),
+        Ok(UpdateOutcome::Updated { from, to }) => {
+            outln!("\x1b[32m  Updated {} -> {}.\x1b[0m", from, to);
+            outln!("\x1b[90m  Run `typewiz update --rollback` to go back to {}.\x1b[0m", from);
+            settings.previous_version = Some(from);
+            settings.save();
+        }
+        Err(e) => outln!("\x1b[31m  Update failed: {}\x1b[0m", e),
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:教室向けのコードの書き出しと読み取り
+// --------------------------------------------------
+
+/// `export --compact-code` の結果
+#[derive(serde::Serialize)]
+struct ExportReport {
+    code: String,
+    summary: SessionSummary,
+}
+
+impl Report for ExportReport {
+    fn print_plain(&self) {
+        outln!(
+            "\x1b[90m  {} · {} · {} chars · CPS {:.2} · {:.1}% · Score {}\x1b[0m",
+            self.summary.name,
+            self.summary.date,
+            self.summary.total_chars,
+            self.summary.cps,
+            self.summary.accuracy,
+            self.summary.score
+        );
+        outln!("  \x1b[1m{}\x1b[0m", self.code);
+    }
+}
+
+fn run_export(compact_code: bool, name: Option<&str>, date: Option<NaiveDate>, format: OutputFormat) -> Result<()> {
+    if !compact_code {
+        outln!("\x1b[31m  Only --compact-code is supported for now.\x1b[0m");
+        return Ok(());
+    }
+
+    let name = name
+        .map(str::to_string)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "player".to_string());
+    let date = date.unwrap_or_else(|| Local::now().date_naive());
+    let player_data = FileStorage.load();
+    let Some(summary) = SessionSummary::from_history(&name, &player_data.history, date) else {
+        outln!("\x1b[90m  No records on {}.\x1b[0m", date);
+        return Ok(());
+    };
+    emit(&ExportReport { code: summary.encode(), summary }, format)
+}
+
+/// お題ごとの成績をまとめた学習シートを書き出す
+fn run_study_sheet(path: &Path) -> Result<()> {
+    let player_data = FileStorage.load();
+    let accuracy_floor = Settings::load().accuracy_floor_for_bests;
+    let sheet = StudySheet::from_player_data(&player_data, &create_roman_mapping(), accuracy_floor);
+    fs::write(path, sheet.to_markdown())?;
+    outln!("  Wrote {} question(s) to {}", sheet.rows.len(), path.display());
+    Ok(())
+}
+
+fn run_import(code: &str) -> Result<()> {
+    let summary = match SessionSummary::decode(code) {
+        Ok(summary) => summary,
+        Err(e) => {
+            outln!("\x1b[31m  Could not read the code: {}\x1b[0m", e);
+            return Ok(());
+        }
+    };
+    let path = roster::append(&summary)?;
+    outln!(
+        "  {} · {} · {} chars · CPS {:.2} · {:.1}% · Score {}",
+        summary.name, summary.date, summary.total_chars, summary.cps, summary.accuracy, summary.score
+    );
+    outln!("\x1b[32m  Added to {}\x1b[0m", path.display());
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:ログ表示（通常スクリーン）
+// --------------------------------------------------
+
+/// ログに表示する記録の件数
+const LOG_RECENT_COUNT: usize = 15;
+
+/// ログの内容（新しい順）
+#[derive(serde::Serialize)]
+struct LogReport<'a> {
+    records: Vec<&'a TypeRecord>,
+    /// 同じ長さのお題の記録の中での順位（画面表示用）
+    #[serde(skip)]
+    percentiles: Arc<PercentileTable>,
+}
+
+impl<'a> LogReport<'a> {
+    /// `intent` を指定したときは、その目的のセッションの記録だけを集める
+    fn from_player_data(player_data: &'a PlayerData, intent: Option<SessionIntent>) -> Self {
+        Self {
+            records: player_data
+                .history
+                .iter()
+                .rev()
+                .filter(|record| intent.is_none_or(|intent| record.intent == intent))
+                .take(LOG_RECENT_COUNT)
+                .collect(),
+            percentiles: player_data.percentiles(),
+        }
+    }
+}
+
+impl LogReport<'_> {
+    /// 表示する行（色のエスケープシーケンス付き）
+    fn lines(&self) -> Vec<String> {
+        if self.records.is_empty() {
+            return vec!["\x1b[90m  No records yet. Start typing to create history!\x1b[0m".to_string()];
+        }
+
+        let mut lines = Vec::new();
+        for record in &self.records {
+            let intent = match record.intent {
+                SessionIntent::Unspecified => String::new(),
+                intent => format!(" | [{}]", intent.label()),
+            };
+            lines.push(format!(
+                "  {} | {} | CPS: {:.2} | Miss: {} | Score: {:.0}{}",
+                format_timestamp(record.timestamp),
+                record.question_japanese,
+                record.cps,
+                record.misses,
+                record.score,
+                intent
+            ));
+            if record.counts_for_bests() && record.total_chars > 0 {
+                let rank = self.percentiles.rank(record.total_chars, record.cps, record.accuracy());
+                lines.push(format!("\x1b[90m      {}\x1b[0m", rank.summary()));
+            }
+            // 文章モードの記録はお題ごとの内訳も表示する
+            for component in &record.components {
+                lines.push(format!(
+                    "\x1b[90m      └ {} | {} chars | {:.2}s | Miss: {}\x1b[0m",
+                    component.question_hiragana,
+                    component.total_chars,
+                    component.duration_sec,
+                    component.misses
+                ));
+            }
+        }
+        lines
+    }
+}
+
+impl Report for LogReport<'_> {
+    fn print_plain(&self) {
+        for line in self.lines() {
+            outln!("{}", line);
+        }
+    }
+}
+
+fn show_log(app_state: &mut AppState) -> Result<()> {
+    outln!();
+    outln!("\x1b[36m═══════════════════════════════════════════════════════════════════════════\x1b[0m");
+    outln!("\x1b[36m  Game Log\x1b[0m");
+    outln!("\x1b[36m═══════════════════════════════════════════════════════════════════════════\x1b[0m");
+    outln!();
+
+    if let Some(intent) = app_state.log_intent {
+        outln!("\x1b[90m  Showing {} sessions only\x1b[0m", intent.label());
+    }
+    LogReport::from_player_data(&app_state.player_data, app_state.log_intent).print_plain();
+    
+    outln!();
+    outln!("\x1b[90m  Press any key to return to menu... (? for help)\x1b[0m");
+    
+    enable_raw_mode()?;
+    loop {
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == event::KeyEventKind::Press
+        {
+            match lookup(LOG_BINDINGS, &key) {
+                Some(Action::Help) => {
+                    // raw モード中は改行で行頭に戻らないため \r\n を使う
+                    out!("\r\n");
+                    for binding in LOG_BINDINGS {
+                        out!("  \x1b[33m{:>10}\x1b[0m  {}\r\n", key_label(binding), binding.description);
+                    }
+                    continue;
+                }
+                Some(Action::Search) => {
+                    // 検索画面を閉じると、元のログの表示に戻る
+                    show_log_search(&app_state.player_data.history)?;
+                    continue;
+                }
+                Some(Action::CopyRecord) => {
+                    // 一番上に表示している（一番新しい）記録をコピーする
+                    let latest = app_state
+                        .player_data
+                        .history
+                        .iter()
+                        .rev()
+                        .find(|record| app_state.log_intent.is_none_or(|intent| record.intent == intent));
+                    if let Some(record) = latest {
+                        let message = copy_text(&share_line(record)).map_or(COPY_UNSUPPORTED, |method| method.toast());
+                        out!("\r\n  \x1b[32m{}\x1b[0m\r\n", message);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            disable_raw_mode()?;
+            // 絞り込みは `log --intent` で開いたときだけ（メニューから開き直したら全部の記録）
+            app_state.log_intent = None;
+            app_state.mode = AppMode::Menu;
+            return Ok(());
+        }
+    }
+}
+
+/// 履歴の検索画面（代替スクリーン。呼び出し元で raw モードにしておく）
+fn show_log_search(history: &[TypeRecord]) -> Result<()> {
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    // IME で確定した日本語や貼り付けをまとめて受け取る
+    stdout().execute(EnableBracketedPaste)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut search = LogSearch::new(history);
+    let mut show_help = false;
+    // コピーしたときのお知らせ（次のキーを押すまで出しておく）
+    let mut toast: Option<&str> = None;
+    loop {
+        terminal.draw(|f| {
+            ui_log_search(f, history, &search, toast);
+            if show_help {
+                render_help_overlay(f, "Search", LOG_SEARCH_BINDINGS);
+            }
+        })?;
+
+        match event::read()? {
+            Event::Paste(text) if search.editing => search.handle_paste(&text),
+            Event::Key(key) if key.kind == event::KeyEventKind::Press => {
+                toast = None;
+                if show_help {
+                    show_help = false;
+                } else if search.editing {
+                    search.handle_key(&key);
+                } else {
+                    match lookup(LOG_SEARCH_BINDINGS, &key) {
+                        Some(Action::Search) => search.start_editing(),
+                        Some(Action::NextMatch) => search.next(),
+                        Some(Action::PrevMatch) => search.prev(),
+                        Some(Action::Help) => show_help = true,
+                        Some(Action::CopyRecord) => {
+                            if let Some(idx) = search.selected_record() {
+                                let copied = copy_text(&share_line(&history[idx]));
+                                toast = Some(copied.map_or(COPY_UNSUPPORTED, |method| method.toast()));
+                            }
+                        }
+                        // Esc はまず検索を取り消し、検索語がなければ閉じる
+                        Some(Action::Back) if !search.clear() => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    stdout().execute(DisableBracketedPaste)?;
+    stdout().execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+fn ui_log_search(f: &mut Frame, history: &[TypeRecord], search: &LogSearch, toast: Option<&str>) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1), Constraint::Length(1)])
+        .split(f.area());
+
+    // 検索語の入力欄
+    let query = if search.editing {
+        let mut line = search.input.line();
+        line.spans.insert(0, Span::raw("/ "));
+        line
+    } else if search.input.value().is_empty() {
+        Line::from("/ to search").dark_gray()
+    } else {
+        Line::from(format!("/ {}", search.input.value()))
+    };
+    let status = search.status().unwrap_or_else(|| format!("{} records", history.len()));
+    f.render_widget(
+        Paragraph::new(query).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Search history ")
+                .title_top(Line::from(format!(" {} ", status)).right_aligned()),
+        ),
+        chunks[0],
+    );
+
+    // 入力中は一致した記録だけ、確定後はすべての記録を表示して選んでいる一致に合わせる
+    let height = usize::from(chunks[1].height);
+    let rows: Vec<usize> = if search.editing {
+        search.matches.iter().copied().take(height).collect()
+    } else {
+        let selected_row = search.selected_record().map_or(0, |idx| history.len() - 1 - idx);
+        let offset = selected_row.saturating_sub(height / 2).min(history.len().saturating_sub(height));
+        (0..history.len()).rev().skip(offset).take(height).collect()
+    };
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|idx| log_search_row(&history[idx], search, idx))
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+
+    if let Some(toast) = toast {
+        f.render_widget(Paragraph::new(toast).green(), chunks[2]);
+        return;
+    }
+    let hint = if search.editing {
+        "Enter: keep the search · Esc: clear"
+    } else {
+        "n/N: next/previous match · y: copy · /: search · Esc: clear/back · ?: help"
+    };
+    f.render_widget(Paragraph::new(hint).dark_gray(), chunks[2]);
+}
+
+/// 検索画面の1行（一致した部分を強調し、選んでいる一致は行ごと強調する）
+fn log_search_row(record: &TypeRecord, search: &LogSearch, idx: usize) -> Line<'static> {
+    let (japanese_hits, hiragana_hits) = search.highlights(idx);
+    let date = format_full_timestamp(record.timestamp);
+    let mut spans = vec![Span::raw(format!("{}  ", date)).dark_gray()];
+    spans.extend(highlighted_spans(&record.question_japanese, &japanese_hits));
+    spans.push(Span::raw(" (").dark_gray());
+    spans.extend(highlighted_spans(&record.question_hiragana, &hiragana_hits));
+    spans.push(Span::raw(")").dark_gray());
+    spans.push(
+        Span::raw(format!("  CPS {:.2} · Miss {} · Score {:.0}", record.cps, record.misses, record.score)).dark_gray(),
+    );
+
+    let line = Line::from(spans);
+    if !search.editing && search.selected_record() == Some(idx) {
+        line.on_dark_gray()
+    } else {
+        line
+    }
+}
+
+/// 一致した範囲に色を付けて文字列を分ける
+fn highlighted_spans(text: &str, hits: &[std::ops::Range<usize>]) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for hit in hits {
+        if hit.start > pos {
+            spans.push(Span::raw(text[pos..hit.start].to_string()));
+        }
+        spans.push(Span::styled(text[hit.clone()].to_string(), Style::default().fg(Color::Black).bg(Color::Yellow)));
+        pos = hit.end;
+    }
+    if pos < text.len() {
+        spans.push(Span::raw(text[pos..].to_string()));
+    }
+    spans
+}
+
+// --------------------------------------------------
+// MARK:推移グラフ（代替スクリーン）
+// --------------------------------------------------
+
+/// 推移グラフで選べる期間（日数）
+const TREND_WINDOWS: [u32; 3] = [30, 90, 365];
+
+/// 推移グラフに表示する指標
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrendMetrics {
+    Both,
+    Cps,
+    Accuracy,
+}
+
+impl TrendMetrics {
+    fn next(self) -> Self {
+        match self {
+            TrendMetrics::Both => TrendMetrics::Cps,
+            TrendMetrics::Cps => TrendMetrics::Accuracy,
+            TrendMetrics::Accuracy => TrendMetrics::Both,
+        }
+    }
+}
+
+fn show_trends(app_state: &mut AppState) -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut window_idx = 1;
+    let mut metrics = TrendMetrics::Both;
+    let mut show_help = false;
+    let mut show_compare = false;
+
+    loop {
+        terminal.draw(|f| {
+            ui_trends(f, &app_state.player_data.history, TREND_WINDOWS[window_idx], metrics);
+            if show_compare {
+                let comparison =
+                    WindowComparison::from_history(&app_state.player_data.history, Utc::now(), TREND_WINDOWS[window_idx]);
+                render_comparison_overlay(f, &comparison);
+            }
+            if show_help {
+                render_help_overlay(f, "Trends", TRENDS_BINDINGS);
+            }
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != event::KeyEventKind::Press {
+                continue;
+            }
+            if show_help {
+                show_help = false;
+                continue;
+            }
+            match lookup(TRENDS_BINDINGS, &key) {
+                Some(Action::CycleWindow) => window_idx = (window_idx + 1) % TREND_WINDOWS.len(),
+                Some(Action::CycleMetric) => metrics = metrics.next(),
+                Some(Action::Compare) => show_compare = !show_compare,
+                Some(Action::Help) => show_help = true,
+                Some(Action::Back) => break,
+                _ => {}
+            }
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    app_state.mode = AppMode::Menu;
+    Ok(())
+}
+
+/// 直近の期間とその前の期間の比較を重ねて描く
+fn render_comparison_overlay(f: &mut Frame, comparison: &WindowComparison) {
+    let days = comparison.days;
+    let mut text = vec![Line::from(format!(
+        "{:<12} {:>12} {:>12}  change",
+        "",
+        format!("prev {}d", days),
+        format!("last {}d", days)
+    ))
+    .bold()];
+    if comparison.current.is_none() && comparison.previous.is_none() {
+        text.push(Line::from(format!("No records in the last {} days.", days * 2)).dark_gray());
+    } else {
+        for (label, decimals, previous, current) in comparison.rows() {
+            let cell = |value: Option<f64>| value.map_or("no data".to_string(), |v| format!("{:.*}", decimals, v));
+            let change = match (previous, current) {
+                (Some(previous), Some(current)) if current > previous => {
+                    Span::from(format_delta(previous, current)).green()
+                }
+                (Some(previous), Some(current)) if current < previous => {
+                    Span::from(format_delta(previous, current)).red()
+                }
+                (Some(previous), Some(current)) => Span::from(format_delta(previous, current)).dark_gray(),
+                _ => Span::from("-").dark_gray(),
+            };
+            text.push(Line::from(vec![
+                Span::from(format!("{:<12} {:>12} {:>12}  ", label, cell(previous), cell(current))),
+                change,
+            ]));
+        }
+    }
+
+    let popup = centered_rect(60, text.len() as u16 + 2, f.area());
+    let block = Block::default().borders(Borders::ALL).title(" Compare ");
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(text).block(block), popup);
+}
+
+/// 推移のグラフ1つ分（名前、色、点の並び）
+type TrendChart<'a> = (&'a str, Color, Vec<(f64, f64)>);
+
+fn ui_trends(f: &mut Frame, history: &[TypeRecord], window_days: u32, metrics: TrendMetrics) {
+    let area = f.area();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Trends: last {} days ", window_days))
+        .title_bottom(Line::from(" w: window · m: metrics · c: compare · ?: help · Esc: back ").centered());
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let today = Local::now().date_naive();
+    let daily = build_daily_stats(history, today, window_days);
+    if daily.is_empty() {
+        f.render_widget(
+            Paragraph::new(format!("No records in the last {} days.", window_days))
+                .style(Style::default().fg(Color::DarkGray))
+                .centered(),
+            centered_rect(inner.width, 1, inner),
+        );
+        return;
+    }
+
+    // x は期間の最初の日を 0、今日を window_days - 1 とする
+    let to_x = |days_ago: u32| f64::from(window_days - 1 - days_ago);
+    let cps: Vec<(f64, f64)> = daily.iter().map(|d| (to_x(d.days_ago), d.average_cps)).collect();
+    let accuracy: Vec<(f64, f64)> = daily.iter().map(|d| (to_x(d.days_ago), d.accuracy)).collect();
+
+    let charts: Vec<TrendChart> = match metrics {
+        TrendMetrics::Both => vec![("CPS", Color::Yellow, cps), ("Accuracy %", Color::Cyan, accuracy)],
+        TrendMetrics::Cps => vec![("CPS", Color::Yellow, cps)],
+        TrendMetrics::Accuracy => vec![("Accuracy %", Color::Cyan, accuracy)],
+    };
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Ratio(1, charts.len() as u32); charts.len()])
+        .split(inner);
+
+    for ((name, color, points), area) in charts.into_iter().zip(areas.iter()) {
+        render_trend_chart(f, *area, name, color, &points, window_days, today);
+    }
+}
+
+/// 1つの指標の推移を折れ線グラフで描く（記録のない日で線を途切れさせる）
+fn render_trend_chart(
+    f: &mut Frame,
+    area: Rect,
+    name: &str,
+    color: Color,
+    points: &[(f64, f64)],
+    window_days: u32,
+    today: NaiveDate,
+) {
+    let span = f64::from(window_days);
+    let (points, step) = downsample(points, span, usize::from(area.width.saturating_sub(8)));
+    let runs = split_runs(&points, step);
+
+    let datasets: Vec<Dataset> = runs
+        .iter()
+        .enumerate()
+        .map(|(i, run)| {
+            let dataset = Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .data(run);
+            // 凡例には1回だけ表示する
+            if i == 0 { dataset.name(name.to_string()) } else { dataset }
+        })
+        .collect();
+
+    let max_y = points.iter().map(|&(_, y)| y).fold(0.0, f64::max);
+    let min_y = points.iter().map(|&(_, y)| y).fold(f64::INFINITY, f64::min);
+    let (y_min, y_max) = if name.starts_with("Accuracy") {
+        ((min_y - 5.0).floor().clamp(0.0, 95.0), 100.0)
+    } else {
+        (0.0, (max_y * 1.1).max(1.0))
+    };
+
+    let date_label = |days_ago: u32| {
+        let date = today - TimeDelta::days(i64::from(days_ago));
+        format_month_day(date)
+    };
+    let x_labels = vec![
+        Span::raw(date_label(window_days - 1)),
+        Span::raw(date_label(window_days / 2)),
+        Span::raw("today"),
+    ];
+    let y_labels = vec![
+        Span::raw(format!("{:.1}", y_min)),
+        Span::raw(format!("{:.1}", (y_min + y_max) / 2.0)),
+        Span::raw(format!("{:.1}", y_max)),
+    ];
+
+    let chart = Chart::new(datasets)
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, span - 1.0])
+                .labels(x_labels),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([y_min, y_max])
+                .labels(y_labels),
+        );
+    f.render_widget(chart, area);
+}
+
+// --------------------------------------------------
+// MARK:落ち物ゲーム（代替スクリーン）
+// --------------------------------------------------
+
+/// 落ち物ゲームで入力を待つ間隔（単語は経過時間で落ちるので、変わるのは描き直す間隔だけ）
+const FALLING_WORDS_TICK: Duration = Duration::from_millis(50);
+/// 終わった画面に出すハイスコアの数
+const FALLING_SCORES_SHOWN: usize = 5;
+
+/// 一番下の単語の入力状態
+struct FallingInput {
+    /// 打っている単語の通し番号
+    word_id: u64,
+    char_states: Vec<CharState>,
+    current: usize,
+}
+
+/// 落ちてくる単語を、下に着く前に打ち切るミニゲーム。3語落としたら終わり
+/// 打鍵とミスは累計に入れるが、記録の履歴には残さない。経験値は設定で有効にしたときだけ与える
+fn run_falling_words(app_state: &mut AppState) -> Result<()> {
+    app_state.mode = AppMode::Menu;
+    // ブラックリストのお題と、ローマ字辞書にない文字を含むお題は出さない
+    let pool: Vec<&'static Question> = QUESTIONS_LIST
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !app_state.player_data.blacklist.contains(&QuestionId::builtin(*idx)))
+        .filter(|(_, q)| app_state.parse_hiragana(q.hiragana).iter().all(|cs| !cs.unsupported))
+        .map(|(_, q)| q)
+        .collect();
+    if pool.is_empty() {
+        app_state.menu_notices.push("No questions left for Falling Words.".to_string());
+        return Ok(());
+    }
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut game = FallingWords::new(pool, Instant::now());
+    let mut input: Option<FallingInput> = None;
+    let (mut typed, mut misses) = (0u32, 0u32);
+
+    while !game.is_over() {
+        game.tick(Instant::now());
+        // 一番下の単語が変わったら（消した / 落とした）次の単語を最初から打つ
+        if input.as_ref().map(|input| input.word_id) != game.active().map(|word| word.id) {
+            input = game.active().map(|word| FallingInput {
+                word_id: word.id,
+                char_states: app_state.parse_hiragana(word.question.hiragana),
+                current: 0,
+            });
+        }
+        terminal.draw(|f| ui_falling_words(f, &game, input.as_ref(), None))?;
+
+        if !event::poll(FALLING_WORDS_TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != event::KeyEventKind::Press {
+            continue;
+        }
+        if lookup(FALLING_WORDS_BINDINGS, &key) == Some(Action::Back) {
+            game.end(Instant::now());
+            continue;
+        }
+        let (KeyCode::Char(c), Some(input)) = (key.code, input.as_mut()) else {
+            continue;
+        };
+        let c = app_state.remapper.apply(c);
+        let Some(cs) = input.char_states.get_mut(input.current) else {
+            continue;
+        };
+        match cs.judge(c, input.current) {
+            KeyOutcome::Hit(_) => app_state.player_data.edit().confusions.record_hit(c),
+            KeyOutcome::Miss(miss) => {
+                misses += 1;
+                app_state.player_data.edit().confusions.record_miss(&miss);
+                continue;
+            }
+            KeyOutcome::Ignored => continue,
+        }
+        typed += 1;
+        if cs.is_complete() {
+            input.current += 1;
+        }
+        if input.current >= input.char_states.len() {
+            game.clear_active();
+        }
+    }
+
+    let now = Instant::now();
+    let xp = if app_state.settings.falling_words_xp { game.cleared_kana } else { 0 };
+    let player_data = app_state.player_data.edit();
+    player_data.add_mini_game_run(xp, typed, misses, game.elapsed(now).as_secs_f64());
+    let rank = player_data.record_falling_score(game.score(now, Utc::now()));
+    app_state.save_player_data();
+
+    // 結果はキーを押すまで表示しておく
+    loop {
+        terminal.draw(|f| ui_falling_words(f, &game, None, Some((&*app_state.player_data, rank))))?;
+        if let Event::Key(key) = event::read()? && key.kind == event::KeyEventKind::Press {
+            break;
+        }
+    }
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+
+    let mut summary = format!("Falling Words: {} words (level {})", game.cleared, game.level());
+    if let Some(rank) = rank {
+        summary.push_str(&format!(" · high score #{}", rank + 1));
+    }
+    if xp > 0 {
+        summary.push_str(&format!(" · +{} XP", xp));
+    }
+    app_state.menu_notices.push(summary);
+    Ok(())
+}
+
+/// `finished` は終わったあとの (ハイスコア表を持つセーブデータ, 今回の順位)
+fn ui_falling_words(
+    f: &mut Frame,
+    game: &FallingWords,
+    input: Option<&FallingInput>,
+    finished: Option<(&PlayerData, Option<usize>)>,
+) {
+    let lives: String = (0..MAX_DROPS).map(|i| if i < MAX_DROPS.saturating_sub(game.drops) { '●' } else { '○' }).collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Falling Words · {} words · Level {} · {} ", game.cleared, game.level(), lives))
+        .title_bottom(
+            Line::from(
+                FALLING_WORDS_BINDINGS
+                    .iter()
+                    .map(|binding| format!(" {}: {} ", key_label(binding), binding.description))
+                    .collect::<Vec<_>>()
+                    .join("·"),
+            )
+            .centered(),
+        );
+    let inner = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    if let Some((player_data, rank)) = finished {
+        let mut lines = vec![
+            Line::from(""),
+            Line::from("GAME OVER").bold().red(),
+            Line::from(format!(
+                "{} words · level {} · {}",
+                game.cleared,
+                game.level(),
+                format_duration(game.elapsed(Instant::now()).as_secs_f64())
+            ))
+            .yellow(),
+        ];
+        if let Some(rank) = rank {
+            lines.push(Line::from(format!("New high score! #{}", rank + 1)).green().bold());
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from("High scores").bold());
+        for (idx, score) in player_data.falling_scores.iter().take(FALLING_SCORES_SHOWN).enumerate() {
+            let line = Line::from(format!(
+                "{:>2}. {:>4} words · level {:>2} · {}",
+                idx + 1,
+                score.words,
+                score.level,
+                format_timestamp(score.played_at())
+            ));
+            lines.push(if Some(idx) == rank { line.green() } else { line });
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from("Press any key to return to the menu").dark_gray());
+        f.render_widget(Paragraph::new(lines).centered(), inner);
+        return;
+    }
+
+    let last_row = inner.height.saturating_sub(1);
+    for word in game.words() {
+        let line = match input.filter(|input| input.word_id == word.id) {
+            // 打っている単語は、打ったローマ字を緑、残りを灰色で後ろに付ける
+            Some(input) => {
+                let mut typed = String::new();
+                let mut rest = String::new();
+                for (idx, cs) in input.char_states.iter().enumerate() {
+                    match idx.cmp(&input.current) {
+                        Ordering::Less => typed.push_str(cs.current_pattern()),
+                        Ordering::Equal => {
+                            typed.push_str(&cs.current_pattern()[..cs.typed_count]);
+                            rest.push_str(cs.remaining());
+                        }
+                        Ordering::Greater => rest.push_str(cs.current_pattern()),
+                    }
+                }
+                Line::from(vec![
+                    Span::styled(word.question.japanese, Style::default().fg(Color::Yellow).bold()),
+                    Span::raw(" "),
+                    Span::styled(typed, Style::default().fg(Color::Green)),
+                    Span::styled(rest, Style::default().fg(Color::DarkGray)),
+                ])
+            }
+            None => Line::from(word.question.japanese),
+        };
+        let width = (line.width() as u16).min(inner.width);
+        let x = inner.x + ((inner.width - width) as f64 * word.column) as u16;
+        let y = inner.y + ((word.row / FIELD_ROWS * f64::from(last_row)) as u16).min(last_row);
+        f.render_widget(Paragraph::new(line), Rect { x, y, width, height: 1 });
+    }
+}
+
+// --------------------------------------------------
+// MARK:コード片の練習（代替スクリーン）
+// --------------------------------------------------
+
+/// コード片を書いてあるとおりの文字で順番に打つ練習。Esc で戻る
+/// 1つ打ち終えるごとに記録（コード片の印付き）を残し、打鍵とミスはキーの取り違えの表にも入れる
+fn run_code_drill(app_state: &mut AppState) -> Result<()> {
+    app_state.mode = AppMode::Menu;
+    let (pool, rejected) = snippet_pool();
+    app_state.menu_notices.extend(rejected);
+    if pool.is_empty() {
+        app_state.menu_notices.push("No code snippets can be typed on this terminal.".to_string());
+        return Ok(());
+    }
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut order = pool.iter().copied().cycle();
+    let mut drill = CodeDrill::new(order.next().unwrap_or_default());
+    let mut last_result: Option<String> = None;
+    let mut completed = 0;
+
+    loop {
+        terminal.draw(|f| ui_code_drill(f, &drill, last_result.as_deref()))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != event::KeyEventKind::Press {
+            continue;
+        }
+        if lookup(CODE_DRILL_BINDINGS, &key) == Some(Action::Back) {
+            break;
+        }
+        // タブと改行もコード片の文字として打つ
+        let c = match key.code {
+            KeyCode::Tab => '\t',
+            KeyCode::Enter => '\n',
+            KeyCode::Char(c) => app_state.remapper.apply(c),
+            _ => continue,
+        };
+        match drill.press(c, Instant::now()) {
+            Some(CodeKey::Hit) => app_state.player_data.edit().confusions.record_hit(c),
+            Some(CodeKey::Miss(miss)) => app_state.player_data.edit().confusions.record_miss(&miss),
+            None => {}
+        }
+        if drill.is_finished() {
+            last_result = Some(app_state.record_code_drill(&drill));
+            completed += 1;
+            drill = CodeDrill::new(order.next().unwrap_or_default());
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    if completed > 0 {
+        app_state.menu_notices.push(format!("Code Drill: {} snippet(s) typed", completed));
+    }
+    Ok(())
+}
+
+/// `last_result` は直前に打ち終えたコード片の成績
+fn ui_code_drill(f: &mut Frame, drill: &CodeDrill, last_result: Option<&str>) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Code Drill · {} ", drill.snippet().title))
+        .title_bottom(
+            Line::from(
+                CODE_DRILL_BINDINGS
+                    .iter()
+                    .map(|binding| format!(" {}: {} ", key_label(binding), binding.description))
+                    .chain([" Tab/Enter: type \u{2192} / \u{21b5} ".to_string()])
+                    .collect::<Vec<_>>()
+                    .join("·"),
+            )
+            .centered(),
+        );
+    let inner = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    // 1文字ずつ色を付ける（打った文字は緑、ミスのあった文字は黄、次の文字は反転、残りは灰色）
+    let mut lines = vec![Line::from("")];
+    let mut spans = Vec::new();
+    for (idx, &c) in drill.chars().iter().enumerate() {
+        let typed = idx < drill.position;
+        let style = match idx.cmp(&drill.position) {
+            Ordering::Less if drill.misses_at(idx) > 0 => Style::default().fg(Color::Yellow),
+            Ordering::Less => Style::default().fg(Color::Green),
+            Ordering::Equal => Style::default().fg(Color::Black).bg(Color::White),
+            Ordering::Greater => Style::default().fg(Color::DarkGray),
+        };
+        spans.push(Span::styled(display_cell(c, typed), style));
+        if c == '\n' {
+            lines.push(Line::from(std::mem::take(&mut spans)));
+        }
+    }
+    lines.push(Line::from(spans));
+    lines.push(Line::from(""));
+    if let Some(result) = last_result {
+        lines.push(Line::from(result.to_string()).dark_gray());
+    }
+    // 行頭の空白をそのまま表示するため、折り返しはしない
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+// --------------------------------------------------
+// MARK:観戦（代替スクリーン）
+// --------------------------------------------------
+
+/// 観戦画面を描き直す間隔（配信は 4回 / 秒なので、それより短くする）
+const SPECTATE_TICK: Duration = Duration::from_millis(100);
+
+/// `--broadcast` の配信を読み取り専用で表示する。切れたら待ち受けに戻り、つなぎ直す。Esc で終わる
+fn run_spectate(addr: &str) -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut feed: Option<SpectatorFeed> = None;
+    let mut frame: Option<SpectatorFrame> = None;
+    let mut last_attempt: Option<Instant> = None;
+    loop {
+        if feed.is_none() && last_attempt.is_none_or(|at| at.elapsed() >= RECONNECT_INTERVAL) {
+            last_attempt = Some(Instant::now());
+            feed = SpectatorFeed::connect(addr).ok();
+        }
+        if let Some(current) = &feed {
+            match current.poll() {
+                FeedUpdate::Frame(latest) => frame = Some(latest),
+                FeedUpdate::Nothing => {}
+                FeedUpdate::Closed => {
+                    feed = None;
+                    frame = None;
+                }
+            }
+        }
+        terminal.draw(|f| ui_spectate(f, addr, frame.as_ref()))?;
+
+        if !event::poll(SPECTATE_TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == event::KeyEventKind::Press && lookup(SPECTATE_BINDINGS, &key) == Some(Action::Back) {
+            break;
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    Ok(())
+}
+
+fn ui_spectate(f: &mut Frame, addr: &str, frame: Option<&SpectatorFrame>) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" TYPE WiZ · watching {} ", addr))
+        .title_bottom(
+            Line::from(
+                SPECTATE_BINDINGS
+                    .iter()
+                    .map(|binding| format!(" {}: {} ", key_label(binding), binding.description))
+                    .collect::<Vec<_>>()
+                    .join("·"),
+            )
+            .centered(),
+        );
+    let inner = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    let Some(frame) = frame else {
+        let waiting = Paragraph::new(Line::from("Waiting for the typist to start a session...").dark_gray()).centered();
+        f.render_widget(waiting, centered_rect(inner.width, 1, inner));
+        return;
+    };
+    let cps = frame.cps.map_or("-".to_string(), |cps| format!("{:.2}", cps));
+    let lines = vec![
+        Line::from(format!("Lv.{} · {} question(s) · {} CPS · {} miss(es)", frame.level, frame.questions, cps, frame.misses))
+            .dark_gray(),
+        Line::from(""),
+        Line::from(frame.japanese.as_str()).white().bold(),
+        Line::from(""),
+        Line::from(frame.hiragana.as_str()).gray(),
+        Line::from(vec![
+            Span::styled(frame.typed.as_str(), Style::default().fg(Color::Green)),
+            Span::styled(frame.remaining.as_str(), Style::default().fg(Color::DarkGray)),
+        ]),
+    ];
+    let area = centered_rect(inner.width, lines.len() as u16 + 2, inner);
+    f.render_widget(Paragraph::new(lines).centered().wrap(Wrap { trim: false }), area);
+}
+
+// --------------------------------------------------
+// MARK:キーボードの確認（代替スクリーン）
+// --------------------------------------------------
+
+/// 入力を待つ間隔（キーリピートの計測で、キーを離したことに気づくため）
+const KEY_TEST_TICK: Duration = Duration::from_millis(100);
+
+/// キーボードの確認画面。Esc で戻る（何も記録しない）
+fn run_key_test() -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut test = KeyTest::new(KeyboardLayout::Ansi);
+
+    loop {
+        terminal.draw(|f| ui_key_test(f, &test))?;
+        if !event::poll(KEY_TEST_TICK)? {
+            test.finish_repeat_if_released(Instant::now());
+            continue;
+        }
+        let event = event::read()?;
+        test.last_event = Some(format!("{:?}", event));
+        let Event::Key(key) = event else {
+            continue;
+        };
+        // キーリピートは Press の繰り返しで届く端末と Repeat で届く端末がある
+        if key.kind == event::KeyEventKind::Release {
+            continue;
+        }
+        match lookup(KEY_TEST_BINDINGS, &key) {
+            Some(Action::Back) => break,
+            Some(Action::CycleLayout) => test.switch_layout(),
+            Some(Action::MeasureRepeat) => test.toggle_repeat_probe(),
+            _ => {
+                if let KeyCode::Char(c) = key.code {
+                    test.press(c, Instant::now());
+                }
+            }
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    Ok(())
+}
+
+fn ui_key_test(f: &mut Frame, test: &KeyTest) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Key test: {} ", test.layout.label()))
+        .title_bottom(
+            Line::from(
+                KEY_TEST_BINDINGS
+                    .iter()
+                    .map(|binding| format!(" {}: {} ", key_label(binding), binding.description))
+                    .collect::<Vec<_>>()
+                    .join("·"),
+            )
+            .centered(),
+        );
+    let inner = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    // 押したキーは緑、次に押してもらうキーは黄色
+    let expected = test.expected();
+    let key_style = |key: char| {
+        if Some(key) == expected {
+            Style::default().fg(Color::Black).bg(Color::Yellow)
+        } else if test.is_pressed(key) {
+            Style::default().fg(Color::Black).bg(Color::Green)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }
+    };
+    let mut lines: Vec<Line> = vec![Line::from("")];
+    for (row, (plain, _)) in test.layout.rows().iter().enumerate() {
+        let mut spans = vec![Span::raw(" ".repeat(2 + row * 2))];
+        for key in plain.chars() {
+            spans.push(Span::styled(format!(" {} ", key), key_style(key)));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from(vec![Span::raw(" ".repeat(14)), Span::styled(format!("{:^24}", "space"), key_style(' '))]));
+    lines.push(Line::from(""));
+
+    let (swept, total) = test.sweep_progress();
+    lines.push(match expected {
+        Some(key) => Line::from(format!("Press each key in turn: {} ({}/{})", key, swept, total)).yellow(),
+        None => Line::from(format!("All {} keys checked.", total)).green(),
+    });
+    if test.mismatches.is_empty() {
+        lines.push(Line::from("No layout mismatches.").dark_gray());
+    } else {
+        let shown: Vec<String> = test
+            .mismatches
+            .iter()
+            .rev()
+            .take(6)
+            .map(|(expected, got)| format!("{} → {:?}", expected, got))
+            .collect();
+        lines.push(Line::from(format!("Mismatches ({}): {}", test.mismatches.len(), shown.join(", "))).red());
+    }
+    if !test.unknown.is_empty() {
+        let unknown: Vec<String> = test.unknown.iter().map(|c| format!("{:?}", c)).collect();
+        lines.push(Line::from(format!("Not on this layout: {}", unknown.join(" "))).red());
+    }
+    lines.push(if test.is_measuring_repeat() {
+        Line::from("Key repeat: hold any key down, then let go…").cyan()
+    } else {
+        Line::from(format!(
+            "Key repeat: {}",
+            test.repeat.map_or("not measured (F5)".to_string(), |repeat| repeat.summary())
+        ))
+        .cyan()
+    });
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("Last event: {}", test.last_event.as_deref().unwrap_or("-"))).dark_gray());
+
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+// --------------------------------------------------
+// MARK:お題選択（通常スクリーン）
+// --------------------------------------------------
+
+/// お題一覧の並び順
+const PICKER_SORTS: [&str; 3] = ["Default order", "Least recently played", "Worst best score"];
+
+/// お題一覧を `PICKER_SORTS` の `sort` 番目の順に並べる
+fn sort_picker_questions(questions: &mut [(QuestionId, &Question)], sort: usize, aggregates: &QuestionAggregates) {
+    match sort {
+        // 未挑戦のお題を先に、あとは最後に挑戦したのが古い順
+        1 => questions.sort_by_key(|(id, _)| aggregates.get(id).and_then(|a| a.last_played)),
+        2 => questions.sort_by(|(a, _), (b, _)| {
+            // 未挑戦のお題は最後に並べる
+            let score = |id: &QuestionId| aggregates.get(id).map_or(f64::INFINITY, |agg| agg.best_score);
+            score(a).total_cmp(&score(b))
+        }),
+        _ => {}
+    }
+}
+
+/// お題一覧の1行に添える成績（"3 tries · best 4.20 CPS / 512 · 3d ago"、未挑戦なら "new"）
+fn picker_stats(aggregate: Option<&QuestionAggregate>, now: DateTime<Utc>) -> String {
+    match aggregate {
+        Some(a) => format!(
+            "{} tries · best {:.2} CPS / {:.0} · {}",
+            a.attempts,
+            a.best_cps,
+            a.best_score,
+            a.last_played.map_or(String::new(), |t| format_relative_time(t, now)),
+        ),
+        None => "new".to_string(),
+    }
+}
+
+fn show_picker(app_state: &mut AppState) -> Result<()> {
+    // パックが入っていれば、先にどのパックから選ぶかを聞く
+    let packs = served_packs();
+    let (installed, _) = installed_packs(&packs_dir());
+    let pack_filter = if installed.is_empty() {
+        None
+    } else {
+        let count = |pack_id: &str| packs.iter().find(|(id, _)| *id == pack_id).map_or(0, |(_, qs)| qs.len());
+        let mut items = vec![
+            format!("All packs ({} questions)", packs.iter().map(|(_, qs)| qs.len()).sum::<usize>()),
+            format!("Built-in ({} questions)", count(BUILTIN_PACK_ID)),
+            format!("My questions ({} questions)", count(USER_PACK_ID)),
+        ];
+        items.extend(installed.iter().map(pack_label));
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Pick from")
+            .items(&items)
+            .default(0)
+            .interact_opt()?;
+        match selection {
+            None => {
+                app_state.mode = AppMode::Menu;
+                return Ok(());
+            }
+            Some(0) => None,
+            Some(1) => Some(BUILTIN_PACK_ID.to_string()),
+            Some(2) => Some(USER_PACK_ID.to_string()),
+            Some(idx) => Some(installed[idx - 3].id.clone()),
+        }
+    };
+
+    let sort = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Sort questions by")
+        .items(PICKER_SORTS)
+        .default(0)
+        .interact_opt()?;
+    let Some(sort) = sort else {
+        app_state.mode = AppMode::Menu;
+        return Ok(());
+    };
+
+    let aggregates = app_state.player_data.question_aggregates(app_state.settings.accuracy_floor_for_bests);
+    let mut questions: Vec<(QuestionId, &'static Question)> = packs
+        .iter()
+        .filter(|(pack_id, _)| pack_filter.as_deref().is_none_or(|filter| filter == *pack_id))
+        .flat_map(|&(pack_id, qs)| qs.iter().enumerate().map(move |(idx, q)| (QuestionId::new(pack_id, idx), q)))
+        .collect();
+    if questions.is_empty() {
+        outln!("\x1b[90m  This pack has no questions.\x1b[0m");
+        app_state.mode = AppMode::Menu;
+        return Ok(());
+    }
+    sort_picker_questions(&mut questions, sort, &aggregates);
+
+    let now = Utc::now();
+    let items: Vec<String> = questions
+        .iter()
+        .map(|(id, q)| {
+            let stats = picker_stats(aggregates.get(id), now);
+            let hidden = if app_state.player_data.blacklist.contains(id) { " [blacklisted]" } else { "" };
+            format!("{}{} ({})  {}{}", bookmark_mark(&app_state.player_data, *id), q.japanese, q.hiragana, stats, hidden)
+        })
+        .collect();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Pick a question")
+        .items(&items)
+        .default(0)
+        .max_length(15)
+        .interact_opt()?;
+
+    let Some(idx) = selection else {
+        app_state.mode = AppMode::Menu;
+        return Ok(());
+    };
+    let (id, _) = questions[idx];
+
+    let blacklisted = app_state.player_data.blacklist.contains(&id);
+    let bookmarked = app_state.player_data.is_bookmarked(id);
+    let time_attack = format!("Time attack ({} tries)", TIME_ATTACK_ATTEMPTS);
+    let actions = [
+        "Play",
+        time_attack.as_str(),
+        if blacklisted { "Show this question again" } else { "Never show this question again" },
+        if bookmarked { "Remove from favorites" } else { "Add to favorites" },
+        "Edit note",
+        "Back",
+    ];
+    if let Some(note) = app_state.player_data.note(id) {
+        outln!("\x1b[33m  Note: {}\x1b[0m", note);
+    }
+    let pace = PaceProfile::from_records(app_state.player_data.history.iter().filter(|r| r.question_id == Some(id)));
+    if let Some(summary) = pace.summary() {
+        outln!("  {}", summary);
+        for line in pace.bar_lines() {
+            outln!("\x1b[90m    {}\x1b[0m", line);
+        }
+    }
+    outln!("\x1b[90m  id: {}\x1b[0m", id);
+    let action = Select::with_theme(&ColorfulTheme::default())
+        .items(actions)
+        .default(0)
+        .interact_opt()?;
+
+    match action {
+        Some(0) => app_state.start_session(SessionConfig { mode: SessionMode::Questions, question: Some(id) })?,
+        Some(1) => app_state.start_session(SessionConfig { mode: SessionMode::TimeAttack, question: Some(id) })?,
+        Some(2) => {
+            app_state.player_data.edit().toggle_blacklist(id);
+            app_state.refresh_excluded();
+        }
+        Some(3) => {
+            app_state.player_data.edit().toggle_bookmark(id);
+        }
+        Some(4) => {
+            let current = app_state.player_data.note(id).unwrap_or_default().to_string();
+            let text: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Note (empty to remove)")
+                .with_initial_text(current)
+                .allow_empty(true)
+                .validate_with(|input: &String| validate_note(input).map(|_| ()))
+                .interact_text()?;
+            if let Ok(text) = validate_note(&text) {
+                app_state.player_data.edit().set_note(id, text);
+            }
+        }
+        _ => app_state.mode = AppMode::Menu,
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:お題の作成（代替スクリーン）
+// --------------------------------------------------
+
+fn show_author(app_state: &mut AppState) -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    // IME で確定した文字列や貼り付けをまとめて受け取る
+    stdout().execute(EnableBracketedPaste)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let mut form = AuthorForm::default();
+    loop {
+        terminal.draw(|f| ui_author(f, &author_model(app_state, &form)))?;
+
+        match event::read()? {
+            Event::Paste(text) => form.handle_paste(&text),
+            Event::Key(key) if key.kind == event::KeyEventKind::Press => match form.handle_key(&key) {
+                AuthorOutcome::Editing => {}
+                AuthorOutcome::Save => {
+                    let (message, saved) = save_authored_question(&form, app_state.settings.length_limit());
+                    // 保存できたら続けて次のお題を作れるよう入力欄を空にし、追加したお題を出題に加える
+                    if saved {
+                        form.clear();
+                        app_state.reload_user_questions();
+                    }
+                    form.message = Some((message, saved));
+                }
+                AuthorOutcome::Back => break,
+            },
+            _ => {}
+        }
+    }
+
+    stdout().execute(DisableBracketedPaste)?;
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    app_state.mode = AppMode::Menu;
+    Ok(())
+}
+
+/// 入力内容を検証してユーザーのお題に追加する（メッセージ, 成功したか）
+/// 長すぎるお題は番号付きの部分に分けて追加する
+fn save_authored_question(form: &AuthorForm, limit: LengthLimit) -> (String, bool) {
+    let japanese = form.japanese.value().trim().to_string();
+    let hiragana = form.hiragana.value().trim().to_string();
+    let roman_map = create_roman_mapping();
+    if let Err(reason) = check_question(&japanese, &hiragana, &roman_map) {
+        return (format!("Not saved: {}", reason), false);
+    }
+
+    let mut user_questions = match UserQuestions::load() {
+        Ok(user_questions) => user_questions,
+        Err(e) => return (format!("Could not read the user question file: {}", e), false),
+    };
+    if is_duplicate(&user_questions.questions, &japanese, &hiragana) {
+        return (format!("Not saved: {} ({}) already exists", japanese, hiragana), false);
+    }
+    let question = UserQuestion { japanese: japanese.clone(), hiragana, tags: form.selected_tags(), gloss: Gloss::new() };
+    let parts = match fit_question(question, limit, &roman_map) {
+        Ok(parts) => parts,
+        Err(reason) => return (format!("Not saved: {}", reason), false),
+    };
+    let split = if parts.len() > 1 { format!(" in {} parts", parts.len()) } else { String::new() };
+    user_questions.questions.extend(parts);
+    match user_questions.save() {
+        Ok(()) => (format!("Saved {}{} ({} user questions)", japanese, split, user_questions.questions.len()), true),
+        Err(e) => (format!("Could not save: {}", e), false),
+    }
+}
+
+/// お題を作る画面の内容
+fn author_model(app_state: &AppState, form: &AuthorForm) -> AuthorModel {
+    let japanese = form.japanese.styled_line(form.focus == AuthorField::Japanese, |_| Style::default());
+
+    // ローマ字辞書で入力できない文字は赤で表示する
+    let hiragana_text = form.hiragana.value();
+    let unsupported = unsupported_chars(&app_state.roman_map, &hiragana_text);
+    let hiragana = form.hiragana.styled_line(form.focus == AuthorField::Hiragana, |c| {
+        if unsupported.contains(&c) {
+            Style::default().fg(Color::White).bg(Color::Red)
+        } else {
+            Style::default()
+        }
+    });
+
+    let romaji: Vec<Span> = app_state
+        .parse_hiragana(&hiragana_text)
+        .iter()
+        .map(|cs| {
+            if cs.unsupported {
+                Span::styled(cs.hiragana.clone(), Style::default().fg(Color::Red))
+            } else {
+                Span::styled(cs.current_pattern().to_string(), Style::default().fg(Color::Gray))
+            }
+        })
+        .collect();
+
+    let mut tags = Vec::new();
+    for (i, (tag, selected)) in QUESTION_TAGS.iter().zip(form.tags).enumerate() {
+        let label = format!("[{}] {}", if selected { "x" } else { " " }, tag);
+        let style = if form.focus == AuthorField::Tags && i == form.tag_cursor {
+            Style::default().fg(Color::Black).bg(Color::White)
+        } else if selected {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default()
+        };
+        tags.push(Span::styled(label, style));
+        tags.push(Span::raw("  "));
+    }
+
+    let message = if let Some((message, ok)) = &form.message {
+        let color = if *ok { Color::Green } else { Color::Red };
+        Some(Line::from(message.clone()).style(Style::default().fg(color)))
+    } else if !unsupported.is_empty() {
+        let chars: String = unsupported.iter().collect();
+        Some(Line::from(format!("cannot be typed: {}", chars)).style(Style::default().fg(Color::Red)))
+    } else {
+        None
+    };
+
+    AuthorModel {
+        focus: form.focus,
+        japanese,
+        hiragana,
+        romaji: Line::from(romaji),
+        tags: Line::from(tags),
+        message,
+    }
+}
+
+fn ui_author(f: &mut Frame, model: &AuthorModel) {
+    let area = f.area();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Author Question ")
+        .title_bottom(Line::from(" Tab: next field · Space: toggle tag · Enter: save · Esc: back ").centered());
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // 日本語
+            Constraint::Length(3), // ひらがな
+            Constraint::Length(3), // ローマ字のプレビュー
+            Constraint::Length(3), // タグ
+            Constraint::Length(1), // メッセージ
+            Constraint::Min(0),
+        ])
+        .split(inner);
+
+    let field_block = |title: &'static str, field: AuthorField| {
+        let style = if model.focus == field {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        Block::default().borders(Borders::ALL).border_style(style).title(title)
+    };
+
+    f.render_widget(Paragraph::new(model.japanese.clone()).block(field_block(" Japanese ", AuthorField::Japanese)), chunks[0]);
+    f.render_widget(Paragraph::new(model.hiragana.clone()).block(field_block(" Hiragana ", AuthorField::Hiragana)), chunks[1]);
+    let romaji_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .title(" Romaji ");
+    f.render_widget(Paragraph::new(model.romaji.clone()).block(romaji_block), chunks[2]);
+    f.render_widget(Paragraph::new(model.tags.clone()).block(field_block(" Tags ", AuthorField::Tags)), chunks[3]);
+    if let Some(message) = &model.message {
+        f.render_widget(Paragraph::new(message.clone()), chunks[4]);
+    }
+}
+
+// --------------------------------------------------
+// MARK:実績画面（通常スクリーン）
+// --------------------------------------------------
+
+fn show_achievements(app_state: &mut AppState) -> Result<()> {
+    let data = &app_state.player_data;
+    let today = Local::now().date_naive();
+    let earned_count = ACHIEVEMENTS.iter().filter(|a| data.has_achievement(a.id)).count();
+
+    outln!();
+    outln!("  Achievements ({}/{})", earned_count, ACHIEVEMENTS.len());
+    outln!();
+    for achievement in ACHIEVEMENTS {
+        print_achievement(achievement, data, &app_state.session.main, today);
+    }
+    outln!();
+    outln!("\x1b[90m  Press any key to return to menu...\x1b[0m");
+    Term::stdout().read_key()?;
+
+    app_state.mode = AppMode::Menu;
+    Ok(())
+}
+
+/// 実績を1行で表示する（達成済みなら達成日、未達成なら進み具合）
+fn print_achievement(achievement: &Achievement, data: &PlayerData, session: &SetTotals, today: NaiveDate) {
+    match data.achievements.iter().find(|earned| earned.id == achievement.id) {
+        Some(earned) => outln!(
+            "  \x1b[33m★ {:<18}\x1b[0m {}  \x1b[90m{}\x1b[0m",
+            achievement.name,
+            achievement.description,
+            earned.earned_at().with_timezone(&Local).format("%Y-%m-%d")
+        ),
+        None => {
+            let progress = achievement.goal.progress(data, session, today);
+            outln!(
+                "  \x1b[90m☆ {:<18} {}  ({})\x1b[0m",
+                achievement.name,
+                achievement.description,
+                achievement.goal.format_progress(progress)
+            );
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:今日の成績（通常スクリーン）
+// --------------------------------------------------
+
+/// 今日の成績を小さくまとめて表示する（開くたびに今の日付で集計し直す）
+fn show_today(app_state: &mut AppState) -> Result<()> {
+    let today = Local::now().date_naive();
+    let data = &app_state.player_data;
+
+    outln!();
+    outln!("  Today · {}", format_date(today));
+    outln!();
+    match TodayStats::build(&data.history, data.xp_ledger.total_on(today), today) {
+        Some(stats) => {
+            outln!("  Sessions   {}", stats.sessions);
+            outln!("  Questions  {}", format_count(u64::from(stats.questions)));
+            outln!("  Chars      {}", format_count(stats.chars));
+            outln!("  Misses     {}", format_count(stats.misses));
+            let cps = stats.average_cps.map_or("-".to_string(), |cps| format!("{:.2}", cps));
+            match stats.previous_cps {
+                Some(previous) => outln!(
+                    "  Avg CPS    {}  \x1b[90m(last {} days: {:.2})\x1b[0m",
+                    cps,
+                    TODAY_COMPARE_DAYS,
+                    previous
+                ),
+                None => outln!("  Avg CPS    {}", cps),
+            }
+            outln!("  XP         +{}", format_count(stats.xp));
+            outln!("  Time       {}", format_practice_time(stats.duration_sec as u64));
+        }
+        None => outln!("\x1b[90m  Nothing typed yet today.\x1b[0m"),
+    }
+    outln!();
+    outln!("\x1b[90m  Press any key to return to menu...\x1b[0m");
+    Term::stdout().read_key()?;
+
+    app_state.mode = AppMode::Menu;
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:週報（代替スクリーン）
+// --------------------------------------------------
+
+/// 一番新しい週報を開く（まだなければメニューでそう伝える）
+fn show_weekly_report(app_state: &mut AppState) -> Result<()> {
+    app_state.mode = AppMode::Menu;
+    let Some(path) = weekly_report::latest_report() else {
+        app_state.menu_notices.push("No weekly report yet. One is written on the first launch of each week.".to_string());
+        return Ok(());
+    };
+    let text = fs::read_to_string(&path)?;
+    let title = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    show_pager(&format!("Weekly report {}", title), &text)
+}
+
+/// 文書をスクロールして読む画面
+fn show_pager(title: &str, text: &str) -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(Hide)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut scroll: usize = 0;
+    let mut show_help = false;
+    loop {
+        // 最後の行が画面の下端に来るところまでしかスクロールしない
+        let page = usize::from(terminal.size()?.height.saturating_sub(3)).max(1);
+        let max_scroll = lines.len().saturating_sub(page);
+        scroll = scroll.min(max_scroll);
+
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(1)])
+                .split(f.area());
+            let body: Vec<Line> = lines.iter().skip(scroll).take(page).map(|line| Line::from(*line)).collect();
+            f.render_widget(
+                Paragraph::new(body).block(Block::default().borders(Borders::ALL).title(format!(" {} ", title))),
+                chunks[0],
+            );
+            let position = format!(" {}-{} / {} · ? for help", scroll + 1, (scroll + page).min(lines.len()), lines.len());
+            f.render_widget(Paragraph::new(position).dark_gray(), chunks[1]);
+            if show_help {
+                render_help_overlay(f, "Reader", PAGER_BINDINGS);
+            }
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != event::KeyEventKind::Press {
+                continue;
+            }
+            if show_help {
+                show_help = false;
+                continue;
+            }
+            match lookup(PAGER_BINDINGS, &key) {
+                Some(Action::ScrollUp) => scroll = scroll.saturating_sub(1),
+                Some(Action::ScrollDown) => scroll = (scroll + 1).min(max_scroll),
+                Some(Action::PageUp) => scroll = scroll.saturating_sub(page),
+                Some(Action::PageDown) => scroll = (scroll + page).min(max_scroll),
+                Some(Action::Help) => show_help = true,
+                Some(Action::Back) => break,
+                _ => {}
+            }
+        }
+    }
+
+    stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:設定画面（通常スクリーン）
+// --------------------------------------------------
+
+fn show_settings(app_state: &mut AppState) -> Result<()> {
+    loop {
+        app_state.poll_update();
+        let pool_health = build_pool_health(&app_state.roman_map, &app_state.player_data.blacklist);
+        let items = vec![
+            format!("Key Remap: {}", app_state.settings.key_remap.label()),
+            format!("AFK Threshold: {}", format_afk_threshold(app_state.settings.afk_threshold_secs)),
+            format!("AFK Action: {}", app_state.settings.afk_action.label()),
+            format!("Adaptive Difficulty: {}", if app_state.settings.adaptive_difficulty { "on" } else { "off" }),
+            format!("Chatter Filter: {}", format_chatter_filter(app_state.settings.chatter_filter_ms)),
+            format!("Debug JSON Copy: {}", if app_state.settings.json_mirror { "on" } else { "off" }),
+            format!("Focus Mode: {}", if app_state.settings.focus_mode { "on" } else { "off" }),
+            format!("Blacklisted Questions ({})", app_state.player_data.blacklist.len()),
+            format!("Error Flash: {}", app_state.settings.error_flash.label()),
+            format!("Show Notes: {}", if app_state.settings.show_notes { "on" } else { "off" }),
+            format!("Show Gloss: {}", if app_state.settings.show_gloss { "on" } else { "off" }),
+            format!("Warm-up: {}", format_warmup(&app_state.settings.warmup)),
+            format!("Cooldown: {}", app_state.settings.cooldown.label()),
+            format!("Cooldown Floor: {:.0}%", app_state.settings.cooldown_accuracy_floor),
+            format!("Break Reminder: {}", format_break_reminder(app_state.settings.break_reminder_mins)),
+            format!("Time Limit: {}", format_time_limit(app_state.settings.time_limit_factor)),
+            format!("Last Result: {}", format_result_persistence(&app_state.settings)),
+            format!("Timing: {}", app_state.settings.timing_policy.label()),
+            format!("Metronome: {}", format_metronome(app_state.settings.metronome_kpm)),
+            format!("Metronome Pulse: {}", if app_state.settings.metronome_pulse { "on" } else { "off" }),
+            format!("Metronome Bell: {}", if app_state.settings.metronome_bell { "on" } else { "off" }),
+            format!("Imported Records in Bests: {}", if app_state.settings.imported_in_bests { "on" } else { "off" }),
+            format!("Bests Accuracy Floor: {}", format_bests_floor(app_state.settings.accuracy_floor_for_bests)),
+            format!("Animation FPS: {}", app_state.settings.animation_fps),
+            format!("Idle Tick Rate: {}ms", app_state.settings.tick_rate_ms),
+            format!("Rotation Guarantee: {}", format_rotation(app_state.settings.rotation_factor)),
+            format!("Rollover Forgiveness: {}", format_chatter_filter(app_state.settings.rollover_forgiveness_ms)),
+            format!("Speed Governor: {}", format_governor(app_state.settings.governor_interval_ms)),
+            format!("Date & Number Format: {}", app_state.settings.ui_language.label()),
+            format!("Falling Words XP: {}", if app_state.settings.falling_words_xp { "on" } else { "off" }),
+            format!("Max Question Length: {} keys", app_state.settings.max_question_keystrokes),
+            format!("Split Long Questions: {}", if app_state.settings.split_long_questions { "on" } else { "off" }),
+            format!("Ask Session Intent: {}", if app_state.settings.ask_session_intent { "on" } else { "off" }),
+            "Open data folder".to_string(),
+            format!("Pool health: {}", pool_health.summary()),
+            "Key test".to_string(),
+            match (app_state.update_check.installed(), app_state.update_check.available()) {
+                (Some(version), _) => format!("Update Now (v{} installed, restart to use it)", version),
+                (None, Some(version)) => format!("Update Now (v{} available)", version),
+                (None, None) => "Update Now".to_string(),
+            },
+            "Back".to_string(),
+        ];
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Settings")
+            .items(&items)
+            .default(0)
+            .interact_opt()?;
+
+        match selection {
+            Some(0) => {
+                app_state.settings.edit().key_remap = app_state.settings.key_remap.next();
+                app_state.reload_remapper();
+                for notice in app_state.menu_notices.drain(..) {
+                    outln!("\x1b[33m  {}\x1b[0m", notice);
+                }
+            }
+            Some(1) => {
+                const THRESHOLDS: [u64; 5] = [0, 5, 10, 20, 30];
+                let current = app_state.settings.afk_threshold_secs;
+                let next = THRESHOLDS
+                    .iter()
+                    .position(|&t| t == current)
+                    .map_or(THRESHOLDS[0], |i| THRESHOLDS[(i + 1) % THRESHOLDS.len()]);
+                app_state.settings.edit().afk_threshold_secs = next;
+            }
+            Some(2) => {
+                app_state.settings.edit().afk_action = app_state.settings.afk_action.next();
+            }
+            Some(3) => {
+                app_state.settings.edit().adaptive_difficulty = !app_state.settings.adaptive_difficulty;
+            }
+            Some(4) => {
+                const WINDOWS: [u64; 4] = [0, 15, 30, 50];
+                let current = app_state.settings.chatter_filter_ms;
+                let next = WINDOWS
+                    .iter()
+                    .position(|&w| w == current)
+                    .map_or(WINDOWS[0], |i| WINDOWS[(i + 1) % WINDOWS.len()]);
+                app_state.settings.edit().chatter_filter_ms = next;
+            }
+            Some(5) => {
+                app_state.settings.edit().json_mirror = !app_state.settings.json_mirror;
+                set_json_mirror(app_state.settings.json_mirror);
+            }
+            Some(6) => {
+                app_state.settings.edit().focus_mode = !app_state.settings.focus_mode;
+            }
+            Some(7) => show_blacklist(app_state)?,
+            Some(8) => {
+                app_state.settings.edit().error_flash = app_state.settings.error_flash.next();
+            }
+            Some(9) => {
+                app_state.settings.edit().show_notes = !app_state.settings.show_notes;
+            }
+            Some(10) => {
+                app_state.settings.edit().show_gloss = !app_state.settings.show_gloss;
+            }
+            Some(11) => {
+                app_state.settings.edit().warmup = if app_state.settings.warmup.is_empty() {
+                    DEFAULT_WARMUP.iter().map(|s| s.to_string()).collect()
+                } else {
+                    Vec::new()
+                };
+            }
+            Some(12) => {
+                app_state.settings.edit().cooldown = app_state.settings.cooldown.next();
+            }
+            Some(13) => {
+                const FLOORS: [f64; 4] = [80.0, 85.0, 90.0, 95.0];
+                let next = FLOORS
+                    .iter()
+                    .position(|&floor| floor >= app_state.settings.cooldown_accuracy_floor)
+                    .map_or(FLOORS[0], |i| FLOORS[(i + 1) % FLOORS.len()]);
+                app_state.settings.edit().cooldown_accuracy_floor = next;
+            }
+            Some(14) => {
+                const MINUTES: [u64; 5] = [0, 15, 20, 30, 45];
+                let current = app_state.settings.break_reminder_mins;
+                let next = MINUTES
+                    .iter()
+                    .position(|&m| m == current)
+                    .map_or(MINUTES[0], |i| MINUTES[(i + 1) % MINUTES.len()]);
+                app_state.settings.edit().break_reminder_mins = next;
+            }
+            Some(15) => {
+                const FACTORS: [f64; 5] = [0.0, 1.0, 1.5, 2.0, 3.0];
+                let next = FACTORS
+                    .iter()
+                    .position(|&factor| factor >= app_state.settings.time_limit_factor)
+                    .map_or(FACTORS[0], |i| FACTORS[(i + 1) % FACTORS.len()]);
+                app_state.settings.edit().time_limit_factor = next;
+            }
+            Some(16) => {
+                app_state.settings.edit().result_persistence = app_state.settings.result_persistence.next();
+            }
+            Some(17) => {
+                app_state.settings.edit().timing_policy = app_state.settings.timing_policy.next();
+            }
+            Some(18) => {
+                let current = app_state.settings.metronome_kpm;
+                let next = METRONOME_RATES
+                    .iter()
+                    .position(|&rate| rate == current)
+                    .map_or(METRONOME_RATES[0], |i| METRONOME_RATES[(i + 1) % METRONOME_RATES.len()]);
+                app_state.settings.edit().metronome_kpm = next;
+            }
+            Some(19) => {
+                app_state.settings.edit().metronome_pulse = !app_state.settings.metronome_pulse;
+            }
+            Some(20) => {
+                app_state.settings.edit().metronome_bell = !app_state.settings.metronome_bell;
+            }
+            Some(21) => {
+                app_state.settings.edit().imported_in_bests = !app_state.settings.imported_in_bests;
+            }
+            Some(22) => {
+                const FLOORS: [f64; 4] = [0.0, 90.0, 95.0, 98.0];
+                let next = FLOORS
+                    .iter()
+                    .position(|&floor| floor >= app_state.settings.accuracy_floor_for_bests)
+                    .map_or(FLOORS[0], |i| FLOORS[(i + 1) % FLOORS.len()]);
+                app_state.settings.edit().accuracy_floor_for_bests = next;
+            }
+            Some(23) => {
+                let current = app_state.settings.animation_fps;
+                let next = ANIMATION_FPS_OPTIONS
+                    .iter()
+                    .position(|&fps| fps == current)
+                    .map_or(ANIMATION_FPS_OPTIONS[0], |i| ANIMATION_FPS_OPTIONS[(i + 1) % ANIMATION_FPS_OPTIONS.len()]);
+                app_state.settings.edit().animation_fps = next;
+            }
+            Some(24) => {
+                let current = app_state.settings.tick_rate_ms;
+                let next = TICK_RATE_OPTIONS
+                    .iter()
+                    .position(|&ms| ms == current)
+                    .map_or(TICK_RATE_OPTIONS[0], |i| TICK_RATE_OPTIONS[(i + 1) % TICK_RATE_OPTIONS.len()]);
+                app_state.settings.edit().tick_rate_ms = next;
+            }
+            Some(25) => {
+                let current = app_state.settings.rotation_factor;
+                let next = ROTATION_FACTORS
+                    .iter()
+                    .position(|&factor| factor == current)
+                    .map_or(ROTATION_FACTORS[0], |i| ROTATION_FACTORS[(i + 1) % ROTATION_FACTORS.len()]);
+                app_state.settings.edit().rotation_factor = next;
+                app_state.queue.set_rotation(next, &app_state.player_data.serve_counts);
+            }
+            Some(26) => {
+                const WINDOWS: [u64; 4] = [0, 15, 25, 40];
+                let current = app_state.settings.rollover_forgiveness_ms;
+                let next = WINDOWS
+                    .iter()
+                    .position(|&w| w == current)
+                    .map_or(WINDOWS[0], |i| WINDOWS[(i + 1) % WINDOWS.len()]);
+                app_state.settings.edit().rollover_forgiveness_ms = next;
+            }
+            Some(27) => {
+                const INTERVALS: [u64; 5] = [0, 100, 150, 200, 300];
+                let current = app_state.settings.governor_interval_ms;
+                let next = INTERVALS
+                    .iter()
+                    .position(|&ms| ms == current)
+                    .map_or(INTERVALS[0], |i| INTERVALS[(i + 1) % INTERVALS.len()]);
+                app_state.settings.edit().governor_interval_ms = next;
+            }
+            Some(28) => {
+                let next = app_state.settings.ui_language.next();
+                app_state.settings.edit().ui_language = next;
+                set_language(next);
+            }
+            Some(29) => {
+                app_state.settings.edit().falling_words_xp = !app_state.settings.falling_words_xp;
+            }
+            Some(30) => {
+                const LIMITS: [usize; 4] = [100, 200, 300, 500];
+                let current = app_state.settings.max_question_keystrokes;
+                let next = LIMITS
+                    .iter()
+                    .position(|&l| l == current)
+                    .map_or(LIMITS[0], |i| LIMITS[(i + 1) % LIMITS.len()]);
+                app_state.settings.edit().max_question_keystrokes = next;
+            }
+            Some(31) => {
+                app_state.settings.edit().split_long_questions = !app_state.settings.split_long_questions;
+            }
+            Some(32) => {
+                app_state.settings.edit().ask_session_intent = !app_state.settings.ask_session_intent;
+            }
+            Some(33) => {
+                if let Err(e) = open_data_dir() {
+                    outln!("\x1b[31m  Failed to open the data folder: {}\x1b[0m", e);
+                    outln!("  {}", get_data_dir().display());
+                }
+            }
+            Some(34) => {
+                pool_health.print_plain();
+                outln!();
+                outln!("\x1b[90m  Press any key to go back\x1b[0m");
+                Term::stdout().read_key()?;
+            }
+            Some(35) => run_key_test()?,
+            Some(36) => app_state.update_now(),
+            _ => {
+                app_state.mode = AppMode::Menu;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// お題の一覧でお気に入りのお題に付ける印
+fn bookmark_mark(player_data: &PlayerData, id: QuestionId) -> &'static str {
+    if player_data.is_bookmarked(id) { "★ " } else { "" }
+}
+
+/// ブラックリストの一覧。選んだお題をブラックリストから外す
+fn show_blacklist(app_state: &mut AppState) -> Result<()> {
+    loop {
+        if app_state.player_data.blacklist.is_empty() {
+            outln!("\x1b[90m  No blacklisted questions.\x1b[0m");
+            return Ok(());
+        }
+
+        let items: Vec<String> = app_state
+            .player_data
+            .blacklist
+            .iter()
+            .map(|&id| match find_question(id) {
+                Some(q) => format!("{}{} ({})", bookmark_mark(&app_state.player_data, id), q.japanese, q.hiragana),
+                None => format!("unknown question {}", id),
+            })
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select a question to show again (Esc to go back)")
+            .items(&items)
+            .default(0)
+            .max_length(15)
+            .interact_opt()?;
+
+        let Some(idx) = selection else {
+            return Ok(());
+        };
+        let id = app_state.player_data.blacklist[idx];
+        app_state.player_data.edit().toggle_blacklist(id);
+        app_state.refresh_excluded();
+    }
+}
+
+/// 放置判定のしきい値を表示用に整形する
+fn format_afk_threshold(secs: u64) -> String {
+    if secs == 0 {
+        "off".to_string()
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// 直前のお題の成績を消すタイミングを表示用に整形する
+fn format_result_persistence(settings: &Settings) -> String {
+    match settings.result_persistence {
+        ResultPersistence::Timed => format!("timed ({}s)", settings.result_timeout_secs),
+        other => other.label().to_string(),
+    }
+}
+
+/// 速さの制限の間隔を表示用に整形する
+fn format_governor(ms: u64) -> String {
+    if ms == 0 {
+        "off".to_string()
+    } else {
+        format!("1 key per {}ms (excluded from bests)", ms)
+    }
+}
+
+/// 1問の制限時間の倍率を表示用に整形する
+fn format_time_limit(factor: f64) -> String {
+    if factor <= 0.0 {
+        "off".to_string()
+    } else {
+        format!("{}× the typical time", factor)
+    }
+}
+
+/// 休憩を勧めるまでの時間を表示用に整形する
+fn format_break_reminder(mins: u64) -> String {
+    if mins == 0 {
+        "off".to_string()
+    } else {
+        format!("every {} min of typing", mins)
+    }
+}
+
+/// 出題の保証を表示用に整形する
+fn format_rotation(factor: u32) -> String {
+    if factor == 0 {
+        "off".to_string()
+    } else {
+        format!("every question within {}x the pool", factor)
+    }
+}
+
+/// 自己ベストの対象にする正確率の下限を表示用に整形する
+fn format_bests_floor(floor: f64) -> String {
+    if floor <= 0.0 {
+        "off".to_string()
+    } else {
+        format!("{:.0}%", floor)
+    }
+}
+
+/// メトロノームの目標テンポを表示用に整形する
+fn format_metronome(kpm: u32) -> String {
+    if kpm == 0 {
+        "off".to_string()
+    } else {
+        format!("{} keys/min", kpm)
+    }
+}
+
+/// チャタリング除去・入れ替わりの許容の時間幅を表示用に整形する
+fn format_chatter_filter(ms: u64) -> String {
+    if ms == 0 {
+        "off".to_string()
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+fn format_warmup(warmup: &[String]) -> String {
+    if warmup.is_empty() {
+        "off".to_string()
+    } else {
+        warmup.join(", ")
+    }
+}
+
+// --------------------------------------------------
+// UI描画 - タイピング
+// --------------------------------------------------
+
+// MARK:描画内容を作る
+
+/// タイピング画面の1コマ分の描画内容を作る（描画の関数には AppState を渡さない）
+fn typing_model(app_state: &AppState, now: Instant) -> TypingModel<'_> {
+    let flash = if app_state.error_flash_until.is_some_and(|until| now < until) {
+        app_state.settings.error_flash
+    } else {
+        ErrorFlash::Off
+    };
+    let phase = app_state.typing_phase(now);
+    // 集中モードでは枠や成績を表示せず、お題の3行だけを表示する（記録は通常どおり行う）
+    let focus = app_state.settings.focus_mode;
+    let chrome = (!focus).then(|| typing_chrome(app_state, now, phase));
+    let gloss = if focus { None } else { app_state.shown_gloss() };
+    // 行の割り振りは出題中のお題の大きさで決める（打ち終えたお題を塗っている間も同じ）
+    let content = TypingContent {
+        japanese_width: Line::from(app_state.current_japanese()).width(),
+        hiragana_width: Line::from(app_state.current_hiragana()).width(),
+        romaji_width: app_state.romaji_width(),
+        romaji_extra_rows: if app_state.current_unsupported_char().is_some() { 2 } else { 0 },
+        gloss_rows: u16::from(gloss.is_some()),
+        result_rows: chrome.as_ref().map_or(0, |chrome| chrome.results.len() as u16),
+    };
+    let question = match app_state.active_completion_sweep(now).filter(|_| !focus) {
+        Some(sweep) => sweep_view(sweep, now),
+        None => question_view(app_state, now),
+    };
+    TypingModel { phase, flash, question, gloss, content, chrome, overlays: typing_overlays(app_state) }
+}
+
+/// 枠・ステータスバー・結果の欄の内容
+fn typing_chrome(app_state: &AppState, now: Instant, phase: TypingPhase) -> TypingChrome<'_> {
+    let shown = |at: Instant, duration: Duration| now.saturating_duration_since(at) < duration;
+    let title = if app_state.remapper.enabled {
+        format!(" TYPE WiZ [{}] ", app_state.remapper.active_label())
+    } else {
+        " TYPE WiZ ".to_string()
+    };
+    let mut top = Vec::new();
+    let mut bottom = Vec::new();
+    if let Some((shift, at)) = app_state.tier_notice && shown(at, TIER_NOTICE_DURATION) {
+        let notice = match shift {
+            TierShift::Up => Line::from(" difficulty up ▲ ").green(),
+            TierShift::Down => Line::from(" difficulty down ▼ ").yellow(),
+        };
+        top.push(notice.right_aligned());
+    }
+    if let Some(metronome) = app_state.metronome.as_ref().filter(|_| app_state.settings.metronome_pulse) {
+        let pulse = if metronome.pulse_on(now) { Line::from(" ● ").yellow() } else { Line::from(" ○ ").dark_gray() };
+        top.push(pulse.right_aligned());
+    }
+    if let Some((names, at)) = &app_state.achievement_toast && shown(*at, RECORD_BANNER_DURATION) {
+        top.push(Line::from(format!(" ★ Achievement: {} ", names.join(" / "))).yellow().bold());
+    }
+    if let Some((message, at)) = &app_state.flash && shown(*at, TIER_NOTICE_DURATION) {
+        bottom.push(Line::from(format!(" {} ", message)).cyan());
+    }
+    if let Some(note) = app_state.visible_note() {
+        bottom.push(Line::from(format!(" Note: {} ", note)).yellow());
+    }
+    if app_state.sentence.is_none() && app_state.queue.is_warmup() {
+        top.push(Line::from(" warm-up ").magenta().centered());
+    }
+    // 日本語の行の中央寄せを崩さないよう、難易度と打鍵数は枠の上辺の右に出す
+    top.push(Line::from(format!(" {} ", app_state.question_meta.badge())).dark_gray().right_aligned());
+    if let Some(attack) = &app_state.time_attack {
+        let best = attack.best().map_or(String::new(), |best| format!(" · best {:.2} CPS", best.cps));
+        let attempt = attack.current_attempt().min(attack.total);
+        let title = format!(" time attack {}/{}{} ", attempt, attack.total, best);
+        top.push(Line::from(title).magenta().centered());
+    }
+    if app_state.filtered_chatter > 0 {
+        let chatter = format!(" chatter filtered: {} ", app_state.filtered_chatter);
+        bottom.push(Line::from(chatter).dark_gray().right_aligned());
+    }
+    if app_state.governor_flash_until.is_some_and(|until| now < until) {
+        bottom.push(Line::from(" too fast ").cyan().centered());
+    }
+    if let Some((left, limit)) = app_state.time_left_at(now) {
+        bottom.push(countdown_line(left, limit).centered());
+    }
+    if app_state.forgiven_rollovers > 0 {
+        let rollovers = format!(" rollovers forgiven: {} ", app_state.forgiven_rollovers);
+        bottom.push(Line::from(rollovers).dark_gray().left_aligned());
+    }
+
+    // ステータスバー
+    let pd = &app_state.player_data;
+    let req_xp = pd.required_xp_for_next_level();
+    let gauge_ratio = if req_xp > 0 {
+        (pd.current_xp as f64 / req_xp as f64).min(1.0)
+    } else {
+        0.0
+    };
+
+    // 経験値の内訳（0 の項目は省く）
+    let xp_parts: Vec<String> = app_state
+        .last_xp_breakdown
+        .iter()
+        .filter(|(_, amount)| *amount != 0)
+        .map(|(label, amount)| format!("{} {}", amount, label))
+        .collect();
+
+    // リザルト（設定によっては一定時間や次の入力で消える）
+    let last_result = app_state.result_display.visible(now);
+    let paused = app_state.is_afk && app_state.is_paused();
+    let results: Vec<Line> = if paused {
+        vec![
+            Line::from("PAUSED (AFK)").style(Style::default().fg(Color::Cyan).bold()),
+            Line::from("Press any key to resume").style(Style::default().fg(Color::DarkGray)),
+        ]
+    } else if let Some(gap) = app_state.sleep_notice {
+        vec![
+            Line::from("SYSTEM SLEPT — paused").style(Style::default().fg(Color::Cyan).bold()),
+            Line::from(format!("Away for {} · type to resume", format_duration(gap.as_secs_f64())))
+                .style(Style::default().fg(Color::DarkGray)),
+        ]
+    } else {
+        // 打っている途中の速さと、今打ち終えたら得られる経験値
+        let live_text = app_state
+            .live_score(now)
+            .map_or(String::new(), |live| format!("now {:.2} CPS · +{} XP", live.cps, app_state.live_xp(&live)));
+        let mut lines = vec![Line::from(live_text).dark_gray()];
+        if let Some(r) = last_result {
+            if r.timed_out {
+                lines.push(Line::from("TIME UP — no score for that question").style(Style::default().fg(Color::Red).bold()));
+            }
+            lines.push(Line::from(format!("CPS: {:.2} / Time: {}", r.cps, format_duration(r.duration_sec))).yellow());
+            lines.push(Line::from(format!("Score: {:.0} / Miss: {}", r.score, r.misses)).yellow());
+            if let Some((accuracy, floor)) = r.below_floor {
+                lines.push(
+                    Line::from(format!("Not record-eligible (accuracy {:.1}% < {:.0}%)", accuracy, floor)).dark_gray(),
+                );
+            }
+            if let Some(summary) = r.error_cost.summary() {
+                lines.push(Line::from(format!("You {}", summary)).red());
+            }
+            lines.push(Line::from(r.baseline.summary()).dark_gray());
+            if let Some(expected) = r.expected_secs {
+                lines.push(
+                    Line::from(format!(
+                        "Typical for {}: {} ({:+.2}s)",
+                        r.meta.badge(),
+                        format_duration(expected),
+                        r.duration_sec - expected
+                    ))
+                    .dark_gray(),
+                );
+            }
+            if let Some((drops, accuracy)) = r.governor {
+                lines.push(Line::from(format!("Governor: {} key(s) dropped / Accuracy: {:.1}%", drops, accuracy)).cyan());
+            }
+            lines.push(Line::from(r.percentiles.map(|p| p.summary()).unwrap_or_default()).dark_gray());
+            // 結果を見せている間は経験値の内訳も1行ずつ並べる
+            if phase == TypingPhase::Result {
+                lines.extend(xp_parts.iter().map(|part| Line::from(format!("  +{}", part)).magenta()));
+                if app_state.time_attack.is_none() && app_state.last_question_id.is_some() {
+                    lines.push(Line::from(result_hint()).dark_gray());
+                }
+            }
+        }
+        lines.push(targets_line(app_state, now));
+        // 空の行は詰める
+        lines.retain(|line| line.width() > 0);
+        lines
+    };
+
+    // 結果の内訳を並べている間は、ゲージには合計だけを表示する
+    let xp_text = match app_state.last_xp_gained {
+        Some(xp) if phase == TypingPhase::Result => format!(" +{}XP", xp),
+        Some(xp) => format!(" +{}XP: {}", xp, xp_parts.join(", ")),
+        None => String::new(),
+    };
+    // 読み込み時などに上げ切れなかったレベルがあれば、満タンのまま止まって見えないように知らせる
+    let pending = if pd.level_pending() { " (level pending)" } else { "" };
+    let gauge_label = format!("Lv.{} ({} / {}){} {}", pd.level, pd.current_xp, req_xp, pending, xp_text);
+
+    TypingChrome { title, top, bottom, gauge_ratio, gauge_label, results }
+}
+
+/// 制限時間の残りを表す、減っていく棒（" ⏱ ██████░░░░ 4.2s "）
+fn countdown_line(left: Duration, limit: Duration) -> Line<'static> {
+    const WIDTH: usize = 20;
+    let ratio = if limit.is_zero() { 0.0 } else { left.as_secs_f64() / limit.as_secs_f64() };
+    let filled = ((ratio * WIDTH as f64).ceil() as usize).min(WIDTH);
+    let text = format!(" ⏱ {}{} {:.1}s ", "█".repeat(filled), "░".repeat(WIDTH - filled), left.as_secs_f64());
+    match ratio {
+        r if r > 0.5 => Line::from(text).green(),
+        r if r > 0.2 => Line::from(text).yellow(),
+        _ => Line::from(text).red(),
+    }
+}
+
+/// タイピング画面の上に重ねるヘルプ・メモ入力欄・休憩の画面
+fn typing_overlays(app_state: &AppState) -> Vec<Overlay<'_>> {
+    let continue_hint = |remaining: Duration| {
+        if remaining > Duration::ZERO {
+            format!("You can continue in {}s", remaining.as_secs() + 1)
+        } else {
+            "Press any key to continue".to_string()
+        }
+    };
+    let mut overlays = Vec::new();
+    if app_state.cooldown_since.is_some() {
+        overlays.push(Overlay::Popup {
+            title: " Cooldown ",
+            color: Color::Yellow,
+            width: 50,
+            lines: vec![
+                Line::from("Accuracy dropping — slow down or take a break").yellow(),
+                Line::from(""),
+                Line::from(continue_hint(app_state.cooldown_remaining())).dark_gray(),
+            ],
+        });
+    }
+    if app_state.break_since.is_some() {
+        overlays.push(Overlay::Popup {
+            title: " Break ",
+            color: Color::Cyan,
+            width: 50,
+            lines: vec![
+                Line::from(format!("You have typed for {} minutes", app_state.settings.break_reminder_mins)).cyan(),
+                Line::from("Stretch your hands and rest your eyes").cyan(),
+                Line::from(""),
+                Line::from(continue_hint(app_state.break_remaining())).dark_gray(),
+            ],
+        });
+    }
+    if let Some(attack) = app_state.time_attack.as_ref().filter(|attack| attack.is_finished()) {
+        overlays.push(Overlay::Popup {
+            title: " Time Attack ",
+            color: Color::Magenta,
+            width: 54,
+            lines: time_attack_summary(attack),
+        });
+    }
+    if app_state.show_help {
+        overlays.push(Overlay::Help);
+    }
+    if let Some((id, input)) = &app_state.note_editor {
+        let title = find_question(*id).map_or(String::new(), |q| q.japanese.to_string());
+        overlays.push(Overlay::NoteEditor { title, input: input.line() });
+    }
+    overlays
+}
+
+/// タイムアタックの全回の成績（一番良い回を強調する）
+fn time_attack_summary(attack: &TimeAttack) -> Vec<Line<'static>> {
+    let best_idx = attack.best_index();
+    let mut text: Vec<Line> = attack
+        .attempts
+        .iter()
+        .enumerate()
+        .map(|(idx, attempt)| {
+            let line = Line::from(format!(
+                "#{}  CPS {:.2}  Time {:.2}s  Miss {}  Score {:.0}",
+                idx + 1,
+                attempt.cps,
+                attempt.duration_sec,
+                attempt.misses,
+                attempt.score
+            ));
+            if Some(idx) == best_idx { line.magenta().bold() } else { line }
+        })
+        .collect();
+    text.push(Line::from(""));
+    if attack.is_new_record() {
+        text.push(Line::from("New record for this question!").magenta().bold());
+    }
+    text.push(Line::from("Press any key to continue").dark_gray());
+    text
+}
+
+/// 出題中のお題の3行（日本語・ひらがな・ローマ字）
+fn question_view(app_state: &AppState, now: Instant) -> QuestionView<'_> {
+    // ひらがな（打ち終えたかなは緑。ふぁ を "fu" + "xa" で打つときは ふ だけ先に緑にする）
+    let mut kana_spans = Vec::new();
+    for (i, cs) in app_state.char_states.iter().enumerate() {
+        let done = if i < app_state.current_char_index {
+            cs.hiragana.len()
+        } else if i == app_state.current_char_index && !cs.unsupported {
+            cs.hiragana.char_indices().nth(cs.typed_kana()).map_or(cs.hiragana.len(), |(idx, _)| idx)
+        } else {
+            0
+        };
+        let (typed, rest) = cs.hiragana.split_at(done);
+        kana_spans.push(Span::styled(typed, Style::default().fg(Color::Green)));
+        kana_spans.push(Span::styled(rest, Style::default().fg(Color::Gray)));
+    }
+
+    // ローマ字（このお題でミスした位置には下線を引く。ミスタイプしたキーは打つべきキーの前に薄く出す）
+    let ghost = app_state.visible_error_ghost(now);
+    let mut spans = Vec::new();
+    for (i, cs) in app_state.char_states.iter().enumerate() {
+        if cs.unsupported {
+            // 入力できない文字は元の文字のまま別の色で表示する
+            let style = if i == app_state.current_char_index {
+                Style::default().fg(Color::Black).bg(Color::Magenta)
+            } else if i < app_state.current_char_index {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default().fg(Color::Magenta)
+            };
+            spans.push(Span::styled(cs.hiragana.as_str(), style));
+            continue;
+        }
+        for (offset, ch) in cs.current_pattern().char_indices() {
+            let mut style = if i < app_state.current_char_index
+                || (i == app_state.current_char_index && offset < cs.typed_count)
+            {
+                Style::default().fg(Color::Green)
+            } else if i == app_state.current_char_index && offset == cs.typed_count {
+                if app_state.is_error {
+                    Style::default().fg(Color::White).bg(Color::Red)
+                } else {
+                    // メトロノーム使用中は、直前の打鍵が拍より早ければ水色、遅ければ黄色
+                    let bg = match app_state.metronome.as_ref().and_then(Metronome::last_phase) {
+                        Some(BeatPhase::Ahead) => Color::Cyan,
+                        Some(BeatPhase::Behind) => Color::Yellow,
+                        Some(BeatPhase::OnBeat) | None => Color::White,
+                    };
+                    Style::default().fg(Color::Black).bg(bg)
+                }
+            } else if i == app_state.current_char_index {
+                Style::default().fg(Color::Gray)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            if app_state.miss_marks.contains_key(&(i, offset)) {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            if let Some(key) = ghost.filter(|_| i == app_state.current_char_index && offset == cs.typed_count) {
+                // スペースは見えないので記号で表す（幅は1文字分だけ増える）
+                let glyph = if key == ' ' { '␣' } else { key };
+                spans.push(Span::styled(glyph.to_string(), Style::default().fg(Color::Red).add_modifier(Modifier::DIM)));
+            }
+            spans.push(Span::styled(ch.to_string(), style));
+        }
+    }
+
+    let mut romaji = vec![Line::from(spans)];
+    if let Some(cs) = app_state.current_unsupported_char() {
+        romaji.push(Line::default());
+        romaji.push(Line::from(format!("{} has no romaji mapping — press Space to skip it", cs.hiragana)).magenta());
+    }
+
+    QuestionView { japanese: app_state.current_japanese(), hiragana: Line::from(kana_spans), romaji }
+}
+
+/// 打ち終えたお題の3行（ローマ字を左から右へ塗っていく）
+fn sweep_view(sweep: &CompletionSweep, now: Instant) -> QuestionView<'_> {
+    let swept = sweep.swept_chars(now).unwrap_or(usize::MAX);
+    let split = sweep.romaji.char_indices().nth(swept).map_or(sweep.romaji.len(), |(idx, _)| idx);
+    let romaji = Line::from(vec![
+        Span::styled(&sweep.romaji[..split], Style::default().fg(Color::Black).bg(Color::Green)),
+        Span::styled(&sweep.romaji[split..], Style::default().fg(Color::Green)),
+    ]);
+    QuestionView {
+        japanese: &sweep.japanese,
+        hiragana: Line::from(sweep.hiragana.as_str()).gray(),
+        romaji: vec![romaji],
+    }
+}
+
+/// 結果を見せている間の案内の行（例: "Ctrl+A: try again · Ctrl+Y: copy"）
+fn result_hint() -> String {
+    TYPING_BINDINGS
+        .iter()
+        .filter_map(|binding| match binding.action {
+            Action::RetryLast => Some(format!("{}: try again", key_label(binding))),
+            Action::CopyRecord => Some(format!("{}: copy", key_label(binding))),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// 自己ベスト更新の表示、または目標の表示（どちらも一定時間で消える）
+fn targets_line(app_state: &AppState, now: Instant) -> Line<'static> {
+    if let Some((beaten, at)) = &app_state.record_banner && now.saturating_duration_since(*at) < RECORD_BANNER_DURATION {
+        return Line::from(format!("NEW RECORD: {}", beaten.join(" / "))).magenta().bold();
+    }
+    match app_state.targets_shown_at {
+        Some(at) if now.saturating_duration_since(at) < TARGETS_CARD_DURATION => {
+            let targets = &app_state.targets;
+            let mode = if app_state.sentence_mode { "Sentence" } else { "Normal" };
+            Line::from(format!(
+                "{} bests · CPS {:.2} · Streak {} · Score {:.0}",
+                mode, targets.best_cps, targets.best_streak, targets.best_score
+            ))
+            .dark_gray()
+        }
+        _ => Line::default(),
+    }
+}
+
+// MARK:描画
+
+fn ui_typing(f: &mut Frame, model: &TypingModel) {
+    let size = f.area();
+    let Some(chrome) = &model.chrome else {
+        // 集中モードはお題の3行だけを画面中央に表示する
+        let japanese_height = wrapped_height(model.content.japanese_width, size.width);
+        let hiragana_height = wrapped_height(model.content.hiragana_width, size.width);
+        let area = centered_rect(size.width, japanese_height + hiragana_height + 4, size);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(japanese_height),
+                Constraint::Length(1),
+                Constraint::Length(hiragana_height),
+                Constraint::Min(1),
+            ])
+            .split(area);
+        render_question_lines(f, &model.question, chunks[0], chunks[2], chunks[3]);
+        if model.flash == ErrorFlash::Strong {
+            f.buffer_mut().set_style(chunks[3], Style::default().bg(Color::Red));
+        }
+        render_typing_overlays(f, &model.overlays);
+        return;
+    };
+
+    let mut block = Block::default().borders(Borders::ALL).title(chrome.title.as_str());
+    if model.flash != ErrorFlash::Off {
+        block = block.border_style(Style::default().fg(Color::Red).bold());
+    }
+    for line in &chrome.top {
+        block = block.title_top(line.clone());
+    }
+    for line in &chrome.bottom {
+        block = block.title_bottom(line.clone());
+    }
+    let inner_area = block.inner(size);
+    f.render_widget(block, size);
+
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::NONE))
+        .gauge_style(Style::default().fg(Color::Magenta).bg(Color::Black))
+        .ratio(chrome.gauge_ratio)
+        .label(chrome.gauge_label.as_str());
+
+    // 文章モードの長いお題は折り返して表示する
+    let layout = typing_layout(inner_area, model.phase, &model.content);
+
+    f.render_widget(gauge, layout.status);
+    f.render_widget(Paragraph::new(chrome.results.clone()), layout.results);
+    render_question_lines(f, &model.question, layout.japanese, layout.hiragana, layout.romaji);
+    if let Some(gloss) = model.gloss {
+        f.render_widget(Paragraph::new(gloss).dark_gray().italic().centered(), layout.gloss);
+    }
+    if model.flash == ErrorFlash::Strong {
+        f.buffer_mut().set_style(layout.romaji, Style::default().bg(Color::Red));
+    }
+
+    render_typing_overlays(f, &model.overlays);
+}
+
+/// お題の3行（日本語・ひらがな・ローマ字）を描く
+fn render_question_lines(
+    f: &mut Frame,
+    question: &QuestionView,
+    japanese_area: Rect,
+    hiragana_area: Rect,
+    romaji_area: Rect,
+) {
+    f.render_widget(
+        Paragraph::new(question.japanese)
+            .style(Style::default().fg(Color::White).bold())
+            .centered()
+            .wrap(Wrap { trim: false }),
+        japanese_area,
+    );
     f.render_widget(
-        Paragraph::new(Line::from(spans)).centered(),
-        chunks[5]
+        Paragraph::new(question.hiragana.clone()).centered().wrap(Wrap { trim: false }),
+        hiragana_area,
+    );
+    f.render_widget(
+        Paragraph::new(question.romaji.clone()).centered().wrap(Wrap { trim: false }),
+        romaji_area,
+    );
+}
+
+/// タイピング画面の上に重ねるものを描く
+fn render_typing_overlays(f: &mut Frame, overlays: &[Overlay]) {
+    for overlay in overlays {
+        match overlay {
+            Overlay::Popup { title, color, width, lines } => {
+                let popup = centered_rect(*width, lines.len() as u16 + 2, f.area());
+                let block = Block::default().borders(Borders::ALL).title(*title).fg(*color);
+                f.render_widget(Clear, popup);
+                f.render_widget(Paragraph::new(lines.clone()).centered().block(block), popup);
+            }
+            Overlay::Help => render_help_overlay(f, "Typing", TYPING_BINDINGS),
+            Overlay::NoteEditor { title, input } => {
+                let popup = centered_rect(NOTE_MAX_CHARS as u16 + 4, 3, f.area());
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" Note: {} ", title))
+                    .title_bottom(Line::from(" Enter: save · Esc: cancel ").centered());
+                f.render_widget(Clear, popup);
+                f.render_widget(Paragraph::new(input.clone()).block(block), popup);
+            }
+        }
+    }
+}
+
+/// セッションの目的を1キーで聞く（Enter で前回選んだ目的、Esc で選ばずに始める）
+fn prompt_session_intent(last: SessionIntent) -> Result<Option<SessionIntent>> {
+    outln!("\x1b[36mWhat kind of session is this?\x1b[0m");
+    outln!("  [w] warm-up   [t] test   [l] learning   [u] unspecified");
+    outln!("\x1b[90m  Enter: {}   Esc: skip\x1b[0m", last.label());
+    loop {
+        let intent = match Term::stdout().read_key()? {
+            console::Key::Enter => last,
+            console::Key::Escape => return Ok(None),
+            console::Key::Char(c) => match c.to_ascii_lowercase() {
+                'w' => SessionIntent::WarmUp,
+                't' => SessionIntent::Test,
+                'l' => SessionIntent::Learning,
+                'u' => SessionIntent::Unspecified,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        return Ok(Some(intent));
+    }
+}
+
+/// セッションの成績と次に練習することを表示し、D キーが押されたらその練習を始める
+fn offer_drill(app_state: &mut AppState, recommendation: &Recommendation) -> Result<()> {
+    if let Some(summary) = app_state.session.summary() {
+        outln!("{}", summary);
+    }
+    outln!("\x1b[36m{}\x1b[0m", recommendation.message());
+    outln!("\x1b[90mPress D to start this drill now, or any other key to finish\x1b[0m");
+    if !matches!(Term::stdout().read_key()?, console::Key::Char('d' | 'D')) {
+        return Ok(());
+    }
+    if app_state.start_drill(recommendation) {
+        app_state.mode = AppMode::Typing;
+    } else {
+        outln!("No questions match that drill.");
+    }
+    Ok(())
+}
+
+/// デバッグログが有効なとき、右上に直近のフレーム時間を表示する（描き直しの間隔の確認用）
+fn render_frame_stats(f: &mut Frame, pacer: &FramePacer) {
+    let Some((average, max)) = pacer.frame_stats() else {
+        return;
+    };
+    let text = format!(
+        " frame {:.1}ms avg · {:.1}ms max ",
+        average.as_secs_f64() * 1000.0,
+        max.as_secs_f64() * 1000.0
     );
-}
\ No newline at end of file
+    let area = f.area();
+    let width = (Line::from(text.as_str()).width() as u16).min(area.width);
+    let rect = Rect::new(area.right().saturating_sub(width), area.y, width, 1);
+    f.render_widget(Paragraph::new(text).style(Style::default().fg(Color::Black).bg(Color::DarkGray)), rect);
+}
+
+// --------------------------------------------------
+// UI描画 - キー操作ヘルプ
+// --------------------------------------------------
+
+/// 背景を暗くして、キー割り当て表を中央のポップアップに表示する
+fn render_help_overlay(f: &mut Frame, title: &str, bindings: &[KeyBinding]) {
+    let area = f.area();
+    f.buffer_mut().set_style(area, Style::default().add_modifier(Modifier::DIM));
+
+    let lines: Vec<Line> = bindings
+        .iter()
+        .map(|binding| {
+            Line::from(vec![
+                Span::styled(format!("{:>10}  ", key_label(binding)), Style::default().fg(Color::Yellow)),
+                Span::raw(binding.description),
+            ])
+        })
+        .collect();
+
+    let popup = centered_rect(52, lines.len() as u16 + 2, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Keys: {} ", title))
+        .title_bottom(Line::from(" press any key to close ").centered());
+
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// `area` の中央に指定サイズの矩形を作る（はみ出す場合は `area` に収める）
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_data::ComponentStat;
+    use chrono::TimeDelta;
+    use ratatui::backend::TestBackend;
+    use std::path::Path;
+
+    const SNAPSHOT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/snapshots");
+
+    fn line_diff(expected: &str, actual: &str) -> String {
+        let expected: Vec<&str> = expected.lines().collect();
+        let actual: Vec<&str> = actual.lines().collect();
+        let mut diff = String::new();
+        for i in 0..expected.len().max(actual.len()) {
+            let (e, a) = (expected.get(i), actual.get(i));
+            if e != a {
+                diff.push_str(&format!("{:>3} - {}\n    + {}\n", i + 1, e.unwrap_or(&""), a.unwrap_or(&"")));
+            }
+        }
+        diff
+    }
+
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(SNAPSHOT_DIR).join(format!("{}.snap", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(SNAPSHOT_DIR).unwrap();
+            fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("missing {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display()));
+        if expected != actual {
+            panic!(
+                "{} differs from {} (run with UPDATE_SNAPSHOTS=1 if the change is intended):\n{}",
+                name,
+                path.display(),
+                line_diff(&expected, actual)
+            );
+        }
+    }
+
+    // MARK: タイピング画面のスナップショット
+    // 場面ごとに AppState を決め打ちし、TestBackend に描いたバッファを src/snapshots の
+    // ファイルと比べる。見た目を意図して変えたときは UPDATE_SNAPSHOTS=1 で実行して書き直す
+
+    /// 出題順を固定した AppState でセッションを始める
+    fn scripted_app(settings: Settings, data: PlayerData) -> AppState {
+        scripted_app_with_order(settings, data, vec![0, 1])
+    }
+
+    fn scripted_app_with_order(settings: Settings, data: PlayerData, order: Vec<usize>) -> AppState {
+        let queue = QuestionQueue::with_order(PACK, &QUESTIONS, order);
+        let mut app_state = AppState::with_data(settings, Box::new(MemoryStorage::new(data)), queue);
+        app_state.begin_session();
+        app_state
+    }
+
+    /// 打ち始めてからの計測時間（打っている途中の速さの表示が揺れないよう、開始の時刻をずらして固定する）
+    const TYPED_FOR: Duration = Duration::from_millis(1500);
+
+    /// 打っている途中の場面の描画の時刻を決め、計測の開始をその TYPED_FOR 前にずらす
+    fn pin_typing_clock(app_state: &mut AppState, now: Instant) -> Instant {
+        app_state.start_time = Some(now - TYPED_FOR);
+        now
+    }
+
+    /// 最後のキーの前に開始の時刻をずらし、打ち終えたお題の所要時間をほぼ secs に揃えて次のお題へ進む
+    fn finish_in(app_state: &mut AppState, keys: &str, secs: f64) {
+        let (head, last) = keys.split_at(keys.len() - 1);
+        type_keys(app_state, head);
+        app_state.start_time = Some(Instant::now() - Duration::from_secs_f64(secs));
+        type_keys(app_state, last);
+        app_state.next_question(QuestionOutcome::Completed);
+    }
+
+    /// 最初のお題を出したところ
+    fn fresh_question() -> (AppState, Instant) {
+        (scripted_app(Settings::default(), PlayerData::default()), Instant::now())
+    }
+
+    /// ね を打つ途中で x を打ち間違えたところ（赤い枠と赤いカーソル）
+    fn mid_question_with_error() -> (AppState, Instant) {
+        let settings = Settings { error_flash: ErrorFlash::Subtle, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        type_keys(&mut app_state, "nx");
+        let flash_until = app_state.error_flash_until.expect("the miss flashes the border");
+        let now = pin_typing_clock(&mut app_state, flash_until - Duration::from_millis(1));
+        (app_state, now)
+    }
+
+    /// ち を既定の "ti" ではなく "c" から打ち始め、綴りが "chi" に切り替わったところ
+    fn mid_question_after_pattern_switch() -> (AppState, Instant) {
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![2, 0]);
+        type_keys(&mut app_state, "c");
+        let now = pin_typing_clock(&mut app_state, Instant::now());
+        (app_state, now)
+    }
+
+    /// 猫 を打ち終え、結果と経験値の内訳を見せているところ
+    fn question_complete() -> (AppState, Instant) {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        finish_in(&mut app_state, "neko", 2.5);
+        (app_state, Instant::now())
+    }
+
+    /// レベルが上がる直前の経験値から 猫 を打ち終えたところ
+    fn level_up() -> (AppState, Instant) {
+        let mut data = PlayerData::default();
+        data.current_xp = data.required_xp_for_next_level() - 1;
+        let mut app_state = scripted_app(Settings::default(), data);
+        finish_in(&mut app_state, "neko", 2.5);
+        (app_state, Instant::now())
+    }
+
+    /// 文字の行（全角の文字は2マスで1文字）と、既定の見た目でないマスの区間の一覧
+    fn serialize_buffer(buffer: &Buffer) -> String {
+        let area = buffer.area;
+        let mut out = String::new();
+        for y in area.top()..area.bottom() {
+            let mut row = String::new();
+            let mut skip = 0;
+            for x in area.left()..area.right() {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                let symbol = buffer[(x, y)].symbol();
+                skip = Span::raw(symbol).width().saturating_sub(1);
+                row.push_str(symbol);
+            }
+            out.push_str(&format!("|{}|\n", row));
+        }
+        out.push_str("\nstyles:\n");
+        for y in area.top()..area.bottom() {
+            let mut x = area.left();
+            while x < area.right() {
+                let cell = &buffer[(x, y)];
+                let look = (cell.fg, cell.bg, cell.modifier);
+                let start = x;
+                while x < area.right() && {
+                    let next = &buffer[(x, y)];
+                    (next.fg, next.bg, next.modifier) == look
+                } {
+                    x += 1;
+                }
+                if look != (Color::Reset, Color::Reset, Modifier::empty()) {
+                    out.push_str(&format!(
+                        "{:>2}:{:>2}..{:<2} fg={:?} bg={:?} mod={:?}\n",
+                        y, start, x, look.0, look.1, look.2
+                    ));
+                }
+            }
+        }
+        out
+    }
+
+    fn render_typing(app_state: &AppState, now: Instant, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui_typing(f, &typing_model(app_state, now))).unwrap();
+        serialize_buffer(terminal.backend().buffer())
+    }
+
+    /// 狭い画面で折り返す長いお題
+    static LONG: [Question; 2] = [
+        Question { japanese: "ありがとうございました", hiragana: "ありがとうございました" },
+        Question { japanese: "猫", hiragana: "ねこ" },
+    ];
+
+    fn long_question_app() -> AppState {
+        let queue = QuestionQueue::with_order(PACK, &LONG, vec![0, 1]);
+        let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(PlayerData::default())), queue);
+        app_state.begin_session();
+        app_state
+    }
+
+    #[test]
+    fn snapshot_long_question_while_typing_and_after() {
+        let app_state = long_question_app();
+        let now = Instant::now();
+        assert_eq!(app_state.typing_phase(now), TypingPhase::Typing);
+        assert_snapshot("typing_long_question_typing_20x14", &render_typing(&app_state, now, 20, 14));
+        assert_snapshot("typing_long_question_typing_40x14", &render_typing(&app_state, now, 40, 14));
+
+        let mut app_state = long_question_app();
+        finish_in(&mut app_state, "arigatougozaimasita", 4.5);
+        let now = Instant::now();
+        assert_eq!(app_state.typing_phase(now), TypingPhase::Result);
+        assert_snapshot("typing_long_question_result_20x14", &render_typing(&app_state, now, 20, 14));
+    }
+
+    #[test]
+    fn snapshot_fresh_question() {
+        let (app_state, now) = fresh_question();
+        assert_snapshot("typing_fresh_question_80x24", &render_typing(&app_state, now, 80, 24));
+        assert_snapshot("typing_fresh_question_50x16", &render_typing(&app_state, now, 50, 16));
+    }
+
+    #[test]
+    fn snapshot_mid_question_with_error() {
+        let (app_state, now) = mid_question_with_error();
+        assert_snapshot("typing_mid_question_with_error_80x24", &render_typing(&app_state, now, 80, 24));
+    }
+
+    #[test]
+    fn snapshot_mid_question_after_pattern_switch() {
+        let (app_state, now) = mid_question_after_pattern_switch();
+        assert_snapshot("typing_pattern_switch_80x24", &render_typing(&app_state, now, 80, 24));
+    }
+
+    #[test]
+    fn snapshot_question_complete() {
+        let (app_state, now) = question_complete();
+        assert_snapshot("typing_question_complete_80x24", &render_typing(&app_state, now, 80, 24));
+    }
+
+    #[test]
+    fn snapshot_level_up() {
+        let (app_state, now) = level_up();
+        assert_snapshot("typing_level_up_80x24", &render_typing(&app_state, now, 80, 24));
+    }
+
+    /// 履歴のログに出す記録（時刻は端末のタイムゾーンによらず同じ表示になるよう、ローカル時刻で決める）
+    fn log_data() -> PlayerData {
+        let at = |day: u32, minute: u32| {
+            chrono::TimeZone::with_ymd_and_hms(&Local, 2026, 3, day, 9, minute, 0).unwrap().with_timezone(&Utc)
+        };
+        let mut data = PlayerData::default();
+        for i in 0..20 {
+            let mut record = TypeRecord::sample("ねこ", 4, 1.0 + f64::from(i) * 0.1, i % 3);
+            record.timestamp = at(14, i);
+            data.history.push(record);
+        }
+        let mut warmup = TypeRecord::sample("いぬ", 3, 1.5, 1);
+        warmup.warmup = true;
+        warmup.timestamp = at(15, 0);
+        let mut passage = TypeRecord::sample("ねこといぬ", 7, 2.75, 2);
+        passage.components = vec![
+            ComponentStat { question_hiragana: "ねこ".to_string(), total_chars: 4, duration_sec: 1.25, misses: 0 },
+            ComponentStat { question_hiragana: "いぬ".to_string(), total_chars: 3, duration_sec: 1.5, misses: 2 },
+        ];
+        passage.timestamp = at(15, 5);
+        data.history.extend([warmup, passage]);
+        data
+    }
+
+    #[test]
+    fn snapshot_log_in_color_and_plain() {
+        let data = log_data();
+        let lines = LogReport::from_player_data(&data, None).lines();
+        let colored = lines.iter().map(|line| format!("{}\n", line.replace('\x1b', "\\e"))).collect::<String>();
+        let plain = lines.iter().map(|line| format!("{}\n", output::styled_as(line, false))).collect::<String>();
+        assert!(!plain.contains('\x1b'));
+        assert_snapshot("log_color", &colored);
+        assert_snapshot("log_plain", &plain);
+    }
+
+    #[test]
+    fn snapshot_study_sheet() {
+        let attempt = |idx: usize, japanese: &str, hiragana: &str, secs: f64, misses: u32, units: &[(u32, u32)]| {
+            let mut record = TypeRecord::sample(hiragana, 8, secs, misses);
+            record.question_japanese = japanese.to_string();
+            record.question_id = Some(QuestionId::new(PACK, idx));
+            record.unit_misses = units.to_vec();
+            record
+        };
+        let mut data = PlayerData::default();
+        data.history = vec![
+            attempt(0, "猫", "ねこ", 2.0, 3, &[(0, 2), (1, 1)]),
+            attempt(0, "猫", "ねこ", 1.6, 1, &[(0, 1)]),
+            attempt(0, "猫", "ねこ", 1.5, 0, &[]),
+            attempt(1, "C++ #1 (入門)", "しーぷらすぷらす", 4.0, 2, &[(2, 1), (4, 1)]),
+            attempt(1, "C++ #1 (入門)", "しーぷらすぷらす", 3.2, 2, &[(4, 2)]),
+            attempt(2, "鳥", "とり", 3.0, 0, &[]),
+        ];
+        data.history[5].gloss = Some("bird".to_string());
+        data.set_note(QuestionId::new(PACK, 0), "「ね」は *左手* から".to_string());
+        let sheet = StudySheet::from_player_data(&data, &roman_mapping::create_roman_mapping(), 0.0);
+        assert_snapshot("study_sheet", &sheet.to_markdown());
+    }
+
+    #[test]
+    fn an_empty_log_is_plain_text_without_color() {
+        let data = PlayerData::default();
+        let lines = LogReport::from_player_data(&data, None).lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(output::styled_as(&lines[0], false), "  No records yet. Start typing to create history!");
+        assert_eq!(output::styled_as(&lines[0], true), lines[0]);
+    }
+
+    #[test]
+    fn a_changed_line_is_reported_with_its_number() {
+        assert_eq!(line_diff("a\nb\nc", "a\nB\nc\nd"), "  2 - b\n    + B\n  4 - \n    + d\n");
+    }
+
+    // MARK: ブラックリスト
+
+    static QUESTIONS: [Question; 4] = [
+        Question { japanese: "猫", hiragana: "ねこ" },
+        Question { japanese: "犬", hiragana: "いぬ" },
+        Question { japanese: "地図", hiragana: "ちず" },
+        Question { japanese: "切手", hiragana: "きって" },
+    ];
+    const PACK: &str = "test";
+
+    /// QUESTIONS から出題する AppState
+    fn pack_app(data: PlayerData) -> AppState {
+        let mut app_state = AppState::new();
+        app_state.player_data = Tracked::new(data);
+        app_state.queue = QuestionQueue::from_packs(&[(PACK, &QUESTIONS)]);
+        app_state.apply_blacklist();
+        app_state.load_current_question();
+        app_state
+    }
+
+    /// 出題中のお題を最初の綴りで打ち終え、次のお題へ進む
+    fn finish_current(app_state: &mut AppState) {
+        let keys: String = app_state.char_states.iter().map(|cs| cs.patterns[0].as_str()).collect();
+        type_keys(app_state, &keys);
+        app_state.next_question(QuestionOutcome::Completed);
+    }
+
+    #[test]
+    fn the_last_question_is_bookmarked_from_the_result() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        finish_current(&mut app_state);
+        app_state.toggle_bookmark_last();
+        assert_eq!(app_state.player_data.bookmarks, [QuestionId::new(PACK, 0)]);
+        assert!(app_state.flash.as_ref().is_some_and(|(message, _)| message.starts_with("★ Bookmarked")));
+        assert_eq!(bookmark_mark(&app_state.player_data, QuestionId::new(PACK, 0)), "★ ");
+        assert_eq!(bookmark_mark(&app_state.player_data, QuestionId::new(PACK, 1)), "");
+        app_state.save_player_data();
+        assert_eq!(persisted(&app_state).bookmarks, [QuestionId::new(PACK, 0)]);
+
+        app_state.toggle_bookmark_last();
+        assert!(app_state.player_data.bookmarks.is_empty());
+    }
+
+    #[test]
+    fn favorites_serve_only_bookmarks_and_flag_orphaned_ones() {
+        let favorites = [QuestionId::builtin(2), QuestionId::builtin(5)];
+        let orphan = QuestionId::new("removed-pack", 0);
+        let mut data = PlayerData::default();
+        data.bookmarks = vec![favorites[0], orphan, favorites[1]];
+        let mut app_state = scripted_app(Settings::default(), data);
+        assert!(app_state.start_favorites());
+        for _ in 0..6 {
+            assert!(favorites.contains(&app_state.queue.current_id()));
+            app_state.queue.advance(None);
+        }
+        // 消えたお題は飛ばすが、お気に入りからは消さない
+        assert!(app_state.flash.as_ref().is_some_and(|(message, _)| message.starts_with("1 bookmarked question(s) no longer exist")));
+        assert_eq!(app_state.player_data.bookmarks.len(), 3);
+
+        // 終わったら元の出題キューに戻る
+        app_state.finish_drill_or_recommend();
+        assert_eq!(app_state.queue.current_id(), QuestionId::new(PACK, 0));
+    }
+
+    #[test]
+    fn favorites_without_any_bookmark_fall_back_to_the_regular_queue() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        assert!(!app_state.start_favorites());
+        let mut data = PlayerData::default();
+        data.bookmarks = vec![QuestionId::new("removed-pack", 0)];
+        let mut app_state = scripted_app(Settings::default(), data);
+        assert!(!app_state.start_favorites());
+        assert!(app_state.drill_return.is_none());
+    }
+
+    #[test]
+    fn a_blacklisted_question_is_not_served_again() {
+        let mut app_state = pack_app(PlayerData::default());
+        let first = app_state.queue.current_id();
+        finish_current(&mut app_state);
+        app_state.toggle_blacklist_last();
+        assert_eq!(app_state.player_data.blacklist, [first]);
+
+        for _ in 0..3 {
+            assert_ne!(app_state.queue.current_id(), first);
+            finish_current(&mut app_state);
+        }
+        assert!(!app_state.menu_notices.iter().any(|n| n.starts_with("Every question")));
+
+        // もう一度押すと外れて、また出題される
+        app_state.last_question_id = Some(first);
+        app_state.toggle_blacklist_last();
+        assert!(app_state.player_data.blacklist.is_empty());
+        finish_current(&mut app_state);
+        assert_eq!(app_state.queue.current_id(), first);
+    }
+
+    #[test]
+    fn blacklisting_every_question_warns_and_keeps_serving() {
+        let mut data = PlayerData::default();
+        data.blacklist = (0..QUESTIONS.len()).map(|idx| QuestionId::new(PACK, idx)).collect();
+        let mut app_state = pack_app(data);
+        finish_current(&mut app_state);
+        finish_current(&mut app_state);
+
+        assert_eq!(app_state.player_data.history.len(), 2);
+        let notices = app_state.menu_notices.iter().filter(|n| n.starts_with("Every question"));
+        // 何度対象外のお題を出しても、知らせは1回だけ
+        assert_eq!(notices.collect::<Vec<_>>(), ["Every question is blacklisted, so the blacklist is being ignored."]);
+    }
+
+    // MARK: お題選択の並べ替えと成績の表示
+
+    fn picker_rows() -> (Vec<(QuestionId, &'static Question)>, QuestionAggregates) {
+        let rows = QUESTIONS_LIST[..3].iter().enumerate().map(|(idx, q)| (QuestionId::builtin(idx), q)).collect();
+        let now = Utc::now();
+        let aggregates = QuestionAggregates::from([
+            (
+                QuestionId::builtin(0),
+                QuestionAggregate { attempts: 3, best_cps: 4.2, best_score: 512.0, last_played: Some(now - TimeDelta::days(3)) },
+            ),
+            (
+                QuestionId::builtin(1),
+                QuestionAggregate { attempts: 1, best_cps: 2.0, best_score: 180.0, last_played: Some(now - TimeDelta::hours(2)) },
+            ),
+        ]);
+        (rows, aggregates)
+    }
+
+    fn picker_order(sort: usize) -> Vec<&'static str> {
+        let (mut rows, aggregates) = picker_rows();
+        sort_picker_questions(&mut rows, sort, &aggregates);
+        rows.iter().map(|(_, q)| q.japanese).collect()
+    }
+
+    #[test]
+    fn picker_sorts_by_last_played_and_worst_best_score() {
+        assert_eq!(picker_order(0), ["北海道", "青森県", "岩手県"]);
+        // 未挑戦のお題が先、あとは最後に挑戦したのが古い順
+        assert_eq!(picker_order(1), ["岩手県", "北海道", "青森県"]);
+        // 最高スコアの低い順で、未挑戦のお題は最後
+        assert_eq!(picker_order(2), ["青森県", "北海道", "岩手県"]);
+    }
+
+    #[test]
+    fn picker_stats_show_tries_bests_and_last_played() {
+        let (_, aggregates) = picker_rows();
+        let now = Utc::now();
+        assert_eq!(
+            picker_stats(aggregates.get(&QuestionId::builtin(0)), now),
+            "3 tries · best 4.20 CPS / 512 · 3d ago"
+        );
+        assert_eq!(picker_stats(aggregates.get(&QuestionId::builtin(2)), now), "new");
+    }
+
+    #[test]
+    fn finished_questions_add_their_typing_time_to_practice_time() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        finish_in(&mut app_state, "neko", 2.4);
+        finish_in(&mut app_state, "inu", 3.2);
+        assert_eq!(persisted(&app_state).total_practice_secs, 5);
+    }
+
+    // MARK: --output json の形
+    // 値ではなくフィールド名と型だけを src/snapshots に残し、フィールドの名前や型が変わったら気づけるようにする
+
+    /// JSON の値を型の名前に置き換える（配列は最初の要素の形で代表させる）
+    fn json_shape(value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match value {
+            Value::Null => Value::from("null"),
+            Value::Bool(_) => Value::from("bool"),
+            Value::Number(_) => Value::from("number"),
+            Value::String(_) => Value::from("string"),
+            Value::Array(items) => Value::Array(items.first().map(json_shape).into_iter().collect()),
+            Value::Object(fields) => Value::Object(fields.iter().map(|(key, value)| (key.clone(), json_shape(value))).collect()),
+        }
+    }
+
+    fn assert_json_shape(name: &str, report: &impl serde::Serialize) {
+        let json: serde_json::Value = serde_json::from_str(&machine_format::json_pretty(report).unwrap()).unwrap();
+        assert_snapshot(name, &format!("{}\n", serde_json::to_string_pretty(&json_shape(&json)).unwrap()));
+    }
+
+    #[test]
+    fn json_shape_of_stats_with_every_section() {
+        let mut data = log_data();
+        data.xp_ledger.add(XpSource::QuestionCompletion, 40);
+        for _ in 0..30 {
+            data.confusions.record_hit('k');
+        }
+        data.confusions.record_miss(&confusion::Miss { expected: 'k', actual: 'l', unit: 0 });
+        let mut snippet = TypeRecord::sample("ふぁいる", 12, 3.0, 1);
+        snippet.code = true;
+        data.history.push(snippet);
+        let args = StatsArgs {
+            xp: true,
+            cooldowns: true,
+            compare: Some(1),
+            rotation: true,
+            confusions: true,
+            by_intent: true,
+            intent: None,
+            code: true,
+            pace: true,
+        };
+        // 1日の比較で、前の期間に14日・今の期間に15日の記録が入るようにする
+        let now = data.history[0].timestamp + TimeDelta::hours(36);
+        assert_json_shape("json_stats", &stats_report(&data, &args, now));
+
+        let bare = StatsArgs { xp: false, cooldowns: false, compare: None, rotation: false, confusions: false, by_intent: false, code: false, pace: false, ..args };
+        assert_json_shape("json_stats_bare", &stats_report(&data, &bare, now));
+    }
+
+    #[test]
+    fn json_shape_of_log_export_and_estimate() {
+        let mut data = log_data();
+        data.history.last_mut().unwrap().question_id = Some(QuestionId::new(PACK, 0));
+        assert_json_shape("json_log", &LogReport::from_player_data(&data, None));
+
+        let date = data.history[0].timestamp.with_timezone(&Local).date_naive();
+        let summary = SessionSummary::from_history("ada", &data.history, date).unwrap();
+        assert_json_shape("json_export", &ExportReport { code: summary.encode(), summary });
+
+        assert_json_shape("json_estimate", &SessionEstimate::new(&[(0, 12), (2, 30)], 1, Some(3.5), Some((0, 2))));
+    }
+
+    #[test]
+    fn json_shape_of_doctor_and_pool_health() {
+        let doctor = DoctorReport {
+            save_file: PathBuf::from("save.bin"),
+            format: "v4".to_string(),
+            records: 12,
+            dropped_offsets: vec![40],
+            header_rebuilt: true,
+            unreadable_backup: Some(PathBuf::from("save.bin.bak")),
+        };
+        assert_json_shape("json_doctor", &doctor);
+
+        let long = "あ".repeat(SANITY_MAX_KEYSTROKES + 1);
+        let entries = [
+            PoolEntry { pack: BUILTIN_PACK_ID, japanese: "猫", hiragana: "ねこ", unreachable: None },
+            PoolEntry { pack: "user", japanese: "ねこ", hiragana: "ねこ", unreachable: None },
+            PoolEntry { pack: "user", japanese: "長文", hiragana: &long, unreachable: None },
+            PoolEntry { pack: "user", japanese: "☆", hiragana: "☆", unreachable: Some("no romaji for ☆") },
+        ];
+        assert_json_shape("json_pool_health", &PoolHealth::analyze(&entries, &create_roman_mapping()));
+    }
+
+    /// お題 `hiragana` を打ち始める前の AppState
+    fn typing_app(hiragana: &str) -> AppState {
+        let mut app_state = AppState::new();
+        app_state.char_states = app_state.parse_hiragana(hiragana);
+        app_state
+    }
+
+    // MARK: チャタリングの除去
+
+    fn chatter_app(chatter_filter_ms: u64, hiragana: &str) -> AppState {
+        let mut app_state = typing_app(hiragana);
+        app_state.settings.edit().chatter_filter_ms = chatter_filter_ms;
+        app_state
+    }
+
+    /// 実際の入力と同じく、チャタリングを除いてからキーを渡す
+    fn press(app_state: &mut AppState, keys: &str) {
+        for c in keys.chars() {
+            if !app_state.filter_chatter(c) {
+                app_state.handle_char_input(c);
+            }
+        }
+    }
+
+    #[test]
+    fn a_doubled_key_that_would_miss_is_filtered() {
+        let mut app_state = chatter_app(30, "ねこ");
+        press(&mut app_state, "nneko");
+        assert_eq!(app_state.filtered_chatter, 1);
+        assert!(app_state.is_question_complete());
+        assert_eq!(app_state.current_misses, 0);
+    }
+
+    #[test]
+    fn an_expected_double_letter_is_not_filtered() {
+        // った の "tt" は続けて同じキーを打つのが正しい
+        let mut app_state = chatter_app(30, "きって");
+        press(&mut app_state, "kitte");
+        assert_eq!(app_state.filtered_chatter, 0);
+        assert!(app_state.is_question_complete());
+        assert_eq!(app_state.current_misses, 0);
+    }
+
+    #[test]
+    fn the_filter_can_be_turned_off() {
+        let mut app_state = chatter_app(0, "ねこ");
+        press(&mut app_state, "nneko");
+        assert_eq!(app_state.filtered_chatter, 0);
+        assert_eq!(app_state.current_misses, 1);
+    }
+
+    #[test]
+    fn only_a_repeat_of_a_correct_key_is_chatter() {
+        // 違うキーも、ミスのあとに同じキーを続けたのもミスのまま
+        let mut app_state = chatter_app(30, "ねこ");
+        press(&mut app_state, "nbxxeko");
+        assert_eq!(app_state.filtered_chatter, 0);
+        assert_eq!(app_state.current_misses, 3);
+    }
+
+    // MARK: Ctrl+W / Ctrl+U
+
+    fn type_keys(app_state: &mut AppState, keys: &str) {
+        for c in keys.chars() {
+            app_state.handle_char_input(c);
+        }
+    }
+
+    /// 入力中の単位の (番号, 綴り, 打った文字数)
+    fn cursor(app_state: &AppState) -> (usize, String, usize) {
+        let cs = &app_state.char_states[app_state.current_char_index];
+        (app_state.current_char_index, cs.current_pattern().to_string(), cs.typed_count)
+    }
+
+    #[test]
+    fn ctrl_w_retypes_the_unit_in_progress() {
+        let mut app_state = typing_app("ちず");
+        type_keys(&mut app_state, "chiz");
+        app_state.handle_delete_unit();
+        // 打ち終えた ち は "chi" のまま、ず だけを打ち直す
+        assert_eq!(cursor(&app_state), (1, "zu".to_string(), 0));
+        assert_eq!(app_state.char_states[0].current_pattern(), "chi");
+        type_keys(&mut app_state, "zu");
+        assert!(app_state.is_question_complete());
+        assert_eq!(app_state.current_misses, 0);
+    }
+
+    #[test]
+    fn ctrl_w_at_a_unit_boundary_resets_the_previous_unit_and_its_spelling() {
+        let mut app_state = typing_app("ちず");
+        type_keys(&mut app_state, "chi");
+        app_state.handle_delete_unit();
+        assert_eq!(cursor(&app_state), (0, "ti".to_string(), 0));
+        type_keys(&mut app_state, "tizu");
+        assert!(app_state.is_question_complete());
+    }
+
+    #[test]
+    fn ctrl_w_clears_the_error_but_keeps_the_miss() {
+        let mut app_state = typing_app("ねこ");
+        type_keys(&mut app_state, "nekx");
+        assert!(app_state.is_error);
+        app_state.handle_delete_unit();
+        assert!(!app_state.is_error);
+        assert_eq!(cursor(&app_state), (1, "ko".to_string(), 0));
+        // ミスした位置の印は打ち直しても残す
+        assert!(app_state.miss_marks.contains_key(&(1, 1)));
+        type_keys(&mut app_state, "ko");
+        assert!(app_state.is_question_complete());
+        assert_eq!(app_state.current_misses, 1);
+    }
+
+    #[test]
+    fn ctrl_u_restarts_the_question_without_resetting_the_timer_or_misses() {
+        let mut app_state = typing_app("ちず");
+        type_keys(&mut app_state, "cqhiz");
+        let started = app_state.start_time;
+        app_state.handle_reset_question();
+        assert_eq!(cursor(&app_state), (0, "ti".to_string(), 0));
+        assert!(app_state.char_states.iter().all(|cs| cs.typed_count == 0 && cs.current_pattern_idx == 0));
+        assert_eq!((app_state.start_time, app_state.current_misses), (started, 1));
+
+        type_keys(&mut app_state, "chi");
+        app_state.handle_reset_question();
+        type_keys(&mut app_state, "tizu");
+        assert!(app_state.is_question_complete());
+        assert_eq!(app_state.current_misses, 1);
+    }
+
+    #[test]
+    fn ctrl_w_and_backspace_interleave_with_typing() {
+        let mut app_state = typing_app("ねこ");
+        type_keys(&mut app_state, "nek");
+        app_state.handle_backspace();
+        assert_eq!(cursor(&app_state), (1, "ko".to_string(), 0));
+        app_state.handle_delete_unit();
+        assert_eq!(cursor(&app_state), (0, "ne".to_string(), 0));
+        type_keys(&mut app_state, "nek");
+        app_state.handle_reset_question();
+        type_keys(&mut app_state, "neko");
+        assert!(app_state.is_question_complete());
+    }
+
+    // MARK: ローマ字辞書にない文字
+
+    #[test]
+    fn starting_a_session_remembers_it_and_a_missing_question_falls_back() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let config = SessionConfig { mode: SessionMode::Questions, question: Some(QuestionId::new(PACK, 1)) };
+        app_state.start_session(config).unwrap();
+        assert_eq!(app_state.current_hiragana(), "いぬ");
+        assert_eq!(app_state.settings.last_session, config);
+        assert!(app_state.flash.is_none());
+
+        let gone = SessionConfig { mode: SessionMode::TimeAttack, question: Some(QuestionId::new("removed", 0)) };
+        app_state.start_session(gone).unwrap();
+        assert!(app_state.flash.as_ref().is_some_and(|(message, _)| message.contains("no longer available")));
+        assert_eq!(app_state.settings.last_session, SessionConfig::default());
+        assert!(matches!(app_state.mode, AppMode::Typing));
+    }
+
+    #[test]
+    fn a_finished_question_sweeps_the_spelling_that_was_typed() {
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![2, 0]);
+        type_keys(&mut app_state, "chizu");
+        assert!(app_state.completion_sweep.is_none());
+        // 打ち終えたときの流れ（演出を始めてから次のお題へ）
+        app_state.start_completion_sweep();
+        app_state.next_question(QuestionOutcome::Completed);
+        let sweep = app_state.active_completion_sweep(Instant::now()).expect("the sweep should start");
+        assert_eq!((sweep.hiragana.as_str(), sweep.romaji.as_str()), ("ちず", "chizu"));
+        // 次のお題はもう始まっている
+        assert_eq!(app_state.current_hiragana(), "ねこ");
+        assert!(app_state.active_completion_sweep(Instant::now() + completion_sweep::SWEEP_DURATION).is_none());
+
+        let settings = Settings { focus_mode: true, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        type_keys(&mut app_state, "neko");
+        app_state.start_completion_sweep();
+        assert!(app_state.completion_sweep.is_none());
+    }
+
+    #[test]
+    fn a_question_of_only_unsupported_units_is_skipped_with_space() {
+        static SYMBOLS: [Question; 1] = [Question { japanese: "☆♪", hiragana: "☆♪" }];
+        let queue = QuestionQueue::with_order(PACK, &SYMBOLS, vec![0]);
+        let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(PlayerData::default())), queue);
+        app_state.begin_session();
+        assert!(app_state.char_states.iter().all(|cs| cs.unsupported));
+
+        // スペース以外は読み飛ばさず、ミスにも打鍵にも数えない
+        type_keys(&mut app_state, "ax");
+        assert_eq!(app_state.current_char_index, 0);
+        type_keys(&mut app_state, " ");
+        assert_eq!(app_state.current_char_index, 1);
+        type_keys(&mut app_state, "k");
+        assert_eq!(app_state.current_char_index, 1);
+        assert_eq!(app_state.current_misses, 0);
+        assert_eq!(app_state.total_keystrokes, 0);
+        assert!(app_state.miss_marks.is_empty());
+
+        submit_keys(&mut app_state, " ");
+        let saved = persisted(&app_state);
+        let record = saved.history.last().expect("the question should be completed");
+        assert_eq!(record.question_hiragana, "☆♪");
+        assert_eq!((record.misses, record.keystrokes, record.total_chars), (0, 0, 0));
+    }
+
+    #[test]
+    fn a_katakana_question_is_typed_with_romaji_and_shown_as_katakana() {
+        static KATAKANA: [Question; 1] = [Question { japanese: "カフェラテ", hiragana: "カフェラテ" }];
+        let queue = QuestionQueue::with_order(PACK, &KATAKANA, vec![0]);
+        let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(PlayerData::default())), queue);
+        app_state.begin_session();
+        let units: Vec<&str> = app_state.char_states.iter().map(|state| state.hiragana.as_str()).collect();
+        assert_eq!(units, ["カ", "フェ", "ラ", "テ"]);
+
+        type_keys(&mut app_state, "kafuxerate");
+        assert!(app_state.is_question_complete());
+        app_state.next_question(QuestionOutcome::Completed);
+        let history = &app_state.player_data.history;
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].question_hiragana.as_str(), history[0].misses), ("カフェラテ", 0));
+    }
+
+    #[test]
+    fn checkpoints_are_throttled_to_the_interval() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let start = app_state.last_checkpoint.unwrap();
+        assert!(app_state.due_checkpoint(start + CHECKPOINT_INTERVAL / 2).is_none());
+        assert!(app_state.due_checkpoint(start + CHECKPOINT_INTERVAL).is_some());
+        assert!(app_state.due_checkpoint(start + CHECKPOINT_INTERVAL + Duration::from_secs(1)).is_none());
+        assert!(app_state.due_checkpoint(start + CHECKPOINT_INTERVAL * 2).is_some());
+
+        app_state.end_session();
+        assert!(app_state.due_checkpoint(start + CHECKPOINT_INTERVAL * 5).is_none());
+    }
+
+    #[test]
+    fn a_mid_question_checkpoint_resumes_at_the_start_of_that_question() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        finish_current(&mut app_state);
+        type_keys(&mut app_state, "i");
+        let start = app_state.last_checkpoint.unwrap();
+        let checkpoint = app_state.due_checkpoint(start + CHECKPOINT_INTERVAL).unwrap();
+        assert_eq!((checkpoint.typed_units, checkpoint.total_units), (1, 2));
+        assert_eq!(checkpoint.question_id, Some(QuestionId::new(PACK, 1)));
+        // 書き出すときと同じく JSON を通す
+        let checkpoint: Checkpoint = serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+
+        // 強制終了後、保存済みの履歴から立ち上げ直す
+        let queue = QuestionQueue::with_order(PACK, &QUESTIONS, vec![0, 1]);
+        let mut resumed = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new((*app_state.player_data).clone())), queue);
+        resumed.resume_from(&checkpoint);
+        resumed.begin_session();
+        assert_eq!(resumed.queue.current_id(), QuestionId::new(PACK, 1));
+        assert_eq!(resumed.current_char_index, 0);
+        assert!(resumed.char_states.iter().all(|state| state.typed_count == 0));
+        assert_eq!(
+            serde_json::to_value(resumed.session).unwrap(),
+            serde_json::to_value(app_state.session).unwrap()
+        );
+        assert_eq!(resumed.session.main.questions + resumed.session.warmup.questions, 1);
+
+        finish_current(&mut resumed);
+        assert_eq!(resumed.session.main.questions + resumed.session.warmup.questions, 2);
+        assert_eq!(resumed.player_data.history.len(), 2);
+    }
+
+    /// 最後のキーを処理したところで止め、`now` の時点の経験値の見込みを返してから打ち終える
+    fn preview_then_finish(app_state: &mut AppState, keys: &str, now: Instant) -> u32 {
+        let (head, last) = keys.split_at(keys.len() - 1);
+        type_keys(app_state, head);
+        app_state.start_time = Some(now - Duration::from_millis(2300));
+        type_keys(app_state, last);
+        assert!(app_state.is_question_complete());
+        let preview = app_state.live_xp(&app_state.live_score(now).unwrap());
+        app_state.next_question_at(QuestionOutcome::Completed, now);
+        preview
+    }
+
+    #[test]
+    fn the_xp_preview_at_completion_is_the_awarded_xp() {
+        let mut settings = Settings::default();
+        settings.tag_xp_multipliers.insert("N4".to_string(), 1.5);
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        app_state.question_tags.insert(QuestionId::new(PACK, 0), vec!["N4".to_string()]);
+        let now = Instant::now();
+
+        let boosted = preview_then_finish(&mut app_state, "nekko", now);
+        let plain = preview_then_finish(&mut app_state, "inu", now);
+        let saved = persisted(&app_state);
+        assert_eq!(saved.history[0].misses, 1);
+        assert_eq!([saved.history[0].xp_gained, saved.history[1].xp_gained], [boosted, plain]);
+        assert!(app_state.last_xp_breakdown.iter().all(|(label, _)| !label.contains("N4")));
+    }
+
+    #[test]
+    fn the_live_score_mid_question_counts_only_the_keys_typed_so_far() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let now = Instant::now();
+        assert!(app_state.live_score(now).is_none());
+
+        // ねこ の4打鍵のうち3打鍵を2秒で打ったところ
+        type_keys(&mut app_state, "nek");
+        app_state.start_time = Some(now - Duration::from_secs(2));
+        let live = app_state.live_score(now).unwrap();
+        assert_eq!(live.cps, 1.5);
+        let expected = score_question(ScoringPreset::Current, 3, 2.0, 0, app_state.keystrokes());
+        assert_eq!(live.xp, expected.xp);
+
+        // 打ち終えたら、最後の見込みが実際に得る経験値になる
+        type_keys(&mut app_state, "o");
+        let preview = app_state.live_score(now).unwrap().xp;
+        app_state.next_question_at(QuestionOutcome::Completed, now);
+        assert_eq!(persisted(&app_state).history[0].xp_gained, preview);
+    }
+
+    #[test]
+    fn the_result_row_follows_the_persistence_setting() {
+        let visible_after_first_key = |policy| {
+            let settings = Settings { result_persistence: policy, ..Settings::default() };
+            let mut app_state = scripted_app(settings, PlayerData::default());
+            finish_current(&mut app_state);
+            assert!(app_state.result_display.visible(Instant::now()).is_some());
+            type_keys(&mut app_state, "i");
+            app_state.result_display.visible(Instant::now()).is_some()
+        };
+        assert!(visible_after_first_key(ResultPersistence::Always));
+        assert!(!visible_after_first_key(ResultPersistence::UntilFirstKey));
+        assert!(visible_after_first_key(ResultPersistence::Timed));
+    }
+
+    /// 地図 (ちず) を打ち、押したキーの数が毎回矛盾していないことを確かめて、記録を返す（'\u{8}' は Backspace）
+    fn type_chizu(keys: &str) -> TypeRecord {
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![2, 0]);
+        for c in keys.chars() {
+            match c {
+                '\u{8}' => app_state.handle_backspace(),
+                c => app_state.handle_char_input(c),
+            }
+            let Keystrokes { correct, total } = app_state.keystrokes();
+            assert!(correct <= total, "{keys:?}: {correct}/{total}");
+            assert!((0.0..=100.0).contains(&app_state.keystrokes().accuracy().unwrap_or(100.0)));
+        }
+        app_state.next_question(QuestionOutcome::Completed);
+        app_state.player_data.history.last().unwrap().clone()
+    }
+
+    #[test]
+    fn accuracy_counts_the_keys_actually_pressed_across_pattern_switches() {
+        // (キー, 押したキー, ミス, CPS に使う打鍵数)
+        let cases = [
+            ("chizu", 5, 0, 5),
+            ("tcizu", 5, 1, 4),
+            ("cxhxizu", 7, 2, 5),
+            // chi に切り替えてから消して ti で打ち直す
+            ("ch\u{8}\u{8}tizu", 6, 0, 4),
+            ("c\u{8}cxhizu", 7, 1, 5),
+        ];
+        for (keys, pressed, misses, chars) in cases {
+            let record = type_chizu(keys);
+            assert_eq!((record.keystrokes, record.misses, record.total_chars), (pressed, misses, chars), "{keys:?}");
+            let expected = f64::from(pressed - misses) / f64::from(pressed) * 100.0;
+            assert_eq!(record.accuracy(), expected, "{keys:?}");
+        }
+    }
+
+    /// お題を `shown_for` 前に表示したことにして neko を一気に打ち、記録の所要時間（秒）と放置の回数を返す
+    fn time_with_policy(policy: TimingPolicy, afk_action: AfkAction, shown_for: Duration) -> (f64, u32) {
+        let settings = Settings { timing_policy: policy, afk_action, afk_threshold_secs: 10, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        app_state.question_shown_at = Some(Instant::now() - shown_for);
+        type_keys(&mut app_state, "neko");
+        app_state.next_question(QuestionOutcome::Completed);
+        let record = app_state.player_data.history.last().unwrap();
+        assert_eq!(record.timing, policy);
+        (record.duration_sec, record.afk_pauses)
+    }
+
+    #[test]
+    fn each_timing_policy_counts_the_time_before_the_first_key_differently() {
+        // (計り方, 表示してからの時間, 所要時間, 放置の回数)
+        let cases = [
+            (TimingPolicy::FirstKey, 1, 0.0, 0),
+            (TimingPolicy::OnDisplay, 1, 1.0, 0),
+            (TimingPolicy::Hybrid, 1, 1.0, 0),
+            (TimingPolicy::FirstKey, 5, 0.0, 0),
+            (TimingPolicy::OnDisplay, 5, 5.0, 0),
+            (TimingPolicy::Hybrid, 5, 2.0, 0),
+            // 放置のしきい値（10 秒）を超えた間は、入力中の放置と同じく数えない
+            (TimingPolicy::FirstKey, 600, 0.0, 0),
+            (TimingPolicy::OnDisplay, 600, 0.0, 1),
+            (TimingPolicy::Hybrid, 600, 2.0, 0),
+        ];
+        for (policy, shown_secs, expected, afk) in cases {
+            let (secs, afk_pauses) = time_with_policy(policy, AfkAction::Pause, Duration::from_secs(shown_secs));
+            assert!((secs - expected).abs() < 0.1, "{policy:?} after {shown_secs}s: {secs}");
+            assert_eq!(afk_pauses, afk, "{policy:?} after {shown_secs}s");
+        }
+    }
+
+    #[test]
+    fn a_long_wait_before_the_first_key_is_only_marked_when_afk_marks() {
+        let (secs, afk_pauses) =
+            time_with_policy(TimingPolicy::OnDisplay, AfkAction::MarkOnly, Duration::from_secs(600));
+        assert!((secs - 600.0).abs() < 0.1, "{secs}");
+        assert_eq!(afk_pauses, 1);
+
+        // 放置の判定が無効なら、そのまま数える
+        let settings = Settings { timing_policy: TimingPolicy::OnDisplay, afk_threshold_secs: 0, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        app_state.question_shown_at = Some(Instant::now() - Duration::from_secs(600));
+        type_keys(&mut app_state, "neko");
+        app_state.next_question(QuestionOutcome::Completed);
+        let record = app_state.player_data.history.last().unwrap();
+        assert!((record.duration_sec - 600.0).abs() < 0.1 && record.afk_pauses == 0, "{}", record.duration_sec);
+    }
+
+    /// 画面のうち、緑（打ち終えた）で表示しているかな
+    fn green_kana(app_state: &AppState) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| ui_typing(f, &typing_model(app_state, Instant::now()))).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .filter(|cell| cell.fg == Color::Green && !cell.symbol().is_ascii())
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn backspace_across_a_decomposed_pair_keeps_the_split_spelling() {
+        static FILE: [Question; 1] = [Question { japanese: "ファイル", hiragana: "ふぁいる" }];
+        let queue = QuestionQueue::with_order(PACK, &FILE, vec![0]);
+        let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(PlayerData::default())), queue);
+        app_state.begin_session();
+
+        type_keys(&mut app_state, "fuxa");
+        assert_eq!(app_state.current_char_index, 1);
+        assert_eq!(green_kana(&app_state), "ふぁ");
+
+        // 単位の先頭で戻ると、前の単位の "fuxa" の最後の1文字だけを取り消す（ふ は打ち終えたまま）
+        app_state.handle_backspace();
+        let cs = &app_state.char_states[0];
+        assert_eq!((app_state.current_char_index, cs.current_pattern(), cs.typed_count), (0, "fuxa", 3));
+        assert_eq!(green_kana(&app_state), "ふ");
+
+        // 打ち直すと2文字とも打ち終えた表示に戻る
+        type_keys(&mut app_state, "a");
+        assert_eq!(app_state.current_char_index, 1);
+        assert_eq!(green_kana(&app_state), "ふぁ");
+
+        // "fu" より前まで戻ると ふ も打っていない表示になる
+        for _ in 0..3 {
+            app_state.handle_backspace();
+        }
+        assert_eq!((app_state.current_char_index, app_state.char_states[0].typed_count), (0, 1));
+        assert_eq!(green_kana(&app_state), "");
+        type_keys(&mut app_state, "uxair");
+        assert_eq!((app_state.current_char_index, app_state.current_misses), (2, 0));
+    }
+
+    #[test]
+    fn the_engine_api_counts_and_scores_like_the_typing_screen() {
+        static PARITY: [Question; 4] = [
+            Question { japanese: "新聞", hiragana: "しんぶん" },
+            Question { japanese: "ファイル", hiragana: "ふぁいる" },
+            Question { japanese: "切手", hiragana: "きって" },
+            Question { japanese: "星☆", hiragana: "ほし☆" },
+        ];
+        // 綴りの切り替え・次の単位の先頭に合うミス・ふつうのミス・読み飛ばす単位を含む
+        let cases = [(0, "sixinbqunn"), (0, "shinnbunn"), (1, "fuxairu"), (1, "fzairu"), (2, "kiltqute"), (3, "hoshxia ")];
+        for (idx, keys) in cases {
+            let hiragana = PARITY[idx].hiragana;
+            let queue = QuestionQueue::with_order(PACK, &PARITY, vec![idx]);
+            let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(PlayerData::default())), queue);
+            app_state.begin_session();
+            let mut engine = typewiz_engine::EngineSession::new(hiragana);
+
+            let (head, last) = keys.split_at(keys.len() - 1);
+            for c in head.chars() {
+                app_state.submit_char(c);
+                engine.feed_key(c, 0.0);
+                let live: serde_json::Value = serde_json::from_str(&engine.finish()).unwrap();
+                let app = (app_state.total_keystrokes, app_state.correct_keystrokes, app_state.current_misses);
+                let api = (live["keystrokes"].as_u64().unwrap(), live["correct_keystrokes"].as_u64().unwrap(), live["misses"].as_u64().unwrap());
+                assert_eq!((u64::from(app.0), u64::from(app.1), u64::from(app.2)), api, "{} after {:?}", hiragana, c);
+            }
+
+            let last = last.chars().next().unwrap();
+            app_state.submit_char(last);
+            let record = app_state.player_data.history.last().unwrap_or_else(|| panic!("{} was not completed by {}", hiragana, keys));
+            engine.feed_key(last, record.duration_sec * 1000.0);
+            let result: serde_json::Value = serde_json::from_str(&engine.finish()).unwrap();
+            assert_eq!(result["complete"], true, "{}", keys);
+            assert_eq!(result["total_chars"], record.total_chars, "{}", keys);
+            assert_eq!(result["misses"], record.misses, "{}", keys);
+            assert_eq!(result["keystrokes"], record.keystrokes, "{}", keys);
+            assert_eq!(result["xp"], record.xp_gained, "{}", keys);
+            assert!((result["score"].as_f64().unwrap() - record.score).abs() < 1e-6, "{}", keys);
+        }
+    }
+
+    /// 指定した時刻（ミリ秒）にキーを押してお題 `question` を打ち終え、(ミス, 許した入れ替わり) を返す
+    /// 最後に保留が残っていれば、待ち時間が切れたものとして処理する
+    fn roll(question: usize, forgiveness_ms: u64, events: &[(char, u64)]) -> (u32, u32) {
+        let settings = Settings { rollover_forgiveness_ms: forgiveness_ms, ..Settings::default() };
+        let mut app_state = scripted_app_with_order(settings, PlayerData::default(), vec![question, 1]);
+        let t0 = Instant::now() - Duration::from_secs(2);
+        for &(c, ms) in events {
+            for c in app_state.rollover_keys(c, t0 + Duration::from_millis(ms)) {
+                app_state.submit_char(c);
+            }
+        }
+        if let Some(c) = app_state.expire_rollover(t0 + Duration::from_secs(1)) {
+            app_state.submit_char(c);
+        }
+        let forgiven = app_state.forgiven_rollovers;
+        let record = app_state.player_data.history.last().unwrap();
+        assert_eq!(record.question_hiragana, QUESTIONS[question].hiragana, "{events:?}");
+        (record.misses, forgiven)
+    }
+
+    /// 10 ミリ秒おきに押す
+    fn keys_at(keys: &str) -> Vec<(char, u64)> {
+        keys.chars().zip((0..).step_by(10)).collect()
+    }
+
+    #[test]
+    fn rollovers_within_the_window_are_forgiven() {
+        // (お題, キー, 許した入れ替わり)
+        let cases = [
+            (0, "enko", 1),
+            // 単位をまたぐ入れ替わり（ね の e より先に こ の k が届く）
+            (0, "nkeo", 1),
+            // 綴りの切り替え（chi の h が c より先に届く）
+            (2, "hcizu", 1),
+            (2, "itzu", 1),
+            (1, "niun", 1),
+            (0, "enok", 2),
+        ];
+        for (question, keys, forgiven) in cases {
+            assert_eq!(roll(question, 25, &keys_at(keys)), (0, forgiven), "{keys:?}");
+        }
+    }
+
+    #[test]
+    fn genuine_misses_are_not_forgiven() {
+        // (お題, 待ち時間, キーと時刻, ミス)
+        let cases = [
+            // 許す設定が無効
+            (0, 0, keys_at("eneko"), 1),
+            // 次のキーが待ち時間を過ぎてから届いた
+            (0, 25, vec![('e', 0), ('n', 30), ('e', 40), ('k', 50), ('o', 60)], 1),
+            // 打つべきキーの次でも正しくならないキー
+            (0, 25, keys_at("xneko"), 1),
+            // 次に届いたのが打つべきキーではない
+            (0, 25, keys_at("emneko"), 2),
+            // 入れ替えても両方は正しくならない（i の次に u は打てない）
+            (1, 25, keys_at("nuinu"), 2),
+            // 次のキーが来ないまま待ち時間が切れた
+            (0, 25, vec![('e', 0), ('n', 100), ('e', 110), ('k', 120), ('o', 130)], 1),
+            // 押し間違えたキーが、打つべきキーと同じ
+            (3, 25, keys_at("kiitte"), 1),
+        ];
+        for (question, window, events, misses) in cases {
+            assert_eq!(roll(question, window, &events), (misses, 0), "{events:?}");
+        }
+    }
+
+    #[test]
+    fn a_held_key_is_processed_unchanged_before_a_key_binding() {
+        let settings = Settings { rollover_forgiveness_ms: 40, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        assert!(app_state.rollover_keys('e', Instant::now()).is_empty());
+        assert!(app_state.rollover_deadline().is_some());
+        app_state.flush_rollover();
+        assert_eq!((app_state.current_misses, app_state.rollover_deadline()), (1, None));
+        for c in "neko".chars() {
+            app_state.submit_char(c);
+        }
+        assert_eq!(app_state.player_data.history[0].misses, 1);
+    }
+
+    #[test]
+    fn the_baseline_line_sits_above_the_personal_percentiles() {
+        let position = |screen: &str, text: &str| screen.lines().position(|line| line.contains(text));
+
+        // 履歴がなくても目安との比較は出る
+        let (app_state, now) = question_complete();
+        let screen = render_typing(&app_state, now, 80, 24);
+        let baseline = position(&screen, "beginner pace for 4 keys (intermediate from 1.67 CPS)").unwrap();
+        let percentiles = position(&screen, "1–5 key questions: not enough data").unwrap();
+        assert!(baseline < percentiles);
+
+        // 履歴があれば、目安の下に自分の中での順位が並ぶ
+        let mut data = PlayerData::default();
+        data.history = (1..=20).map(|i| TypeRecord::sample("ねこ", 4, f64::from(i) * 0.5, 0)).collect();
+        let mut app_state = scripted_app(Settings::default(), data);
+        finish_in(&mut app_state, "neko", 2.5);
+        let screen = render_typing(&app_state, Instant::now(), 80, 24);
+        let baseline = position(&screen, "beginner pace for 4 keys").unwrap();
+        let percentiles = position(&screen, "faster than ").unwrap();
+        assert!(baseline < percentiles);
+    }
+
+    #[test]
+    fn leaving_mid_result_starts_the_next_session_with_a_clean_slate() {
+        // レベルアップの演出と結果の表示が残っているうちに Esc で抜ける
+        let mut data = PlayerData::default();
+        data.current_xp = data.required_xp_for_next_level() - 1;
+        let mut app_state = scripted_app(Settings { result_persistence: ResultPersistence::Always, ..Settings::default() }, data);
+        "nekox".chars().for_each(|c| app_state.submit_char(c));
+        let now = Instant::now();
+        assert!(app_state.last_xp_gained.is_some() && app_state.completion_sweep.is_some());
+        assert!(app_state.result_display.visible(now).is_some() && app_state.error_flash_until.is_some());
+
+        app_state.end_session();
+        assert_eq!(app_state.last_xp_gained, None);
+        assert!(app_state.last_xp_breakdown.is_empty() && app_state.recent_accuracies.is_empty());
+        assert!(app_state.result_display.visible(now).is_none() && app_state.completion_sweep.is_none());
+        assert!(app_state.error_flash_until.is_none() && app_state.error_ghost.is_none() && app_state.flash.is_none());
+
+        // 後でまた始めても、打ち始めたときに前の結果は出ない
+        app_state.begin_session();
+        type_keys(&mut app_state, "i");
+        let now = Instant::now();
+        assert_eq!(app_state.typing_phase(now), TypingPhase::Typing);
+        let screen = render_typing(&app_state, now, 80, 24);
+        assert!(!screen.contains("CPS:"), "{}", screen);
+        assert_eq!(app_state.last_xp_gained, None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "previous session's XP leaked into a new session")]
+    fn a_session_started_without_the_teardown_is_caught() {
+        let (mut app_state, _) = question_complete();
+        app_state.begin_session();
+    }
+
+    #[test]
+    fn miss_marks_sit_on_the_unit_being_typed() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        // "n" のあとの k は次の単位（こ）の先頭に合うが、ね の2文字目のミスになる
+        type_keys(&mut app_state, "nke");
+        type_keys(&mut app_state, "xx");
+        let mut marks: Vec<_> = app_state.miss_marks.iter().map(|(&key, &count)| (key, count)).collect();
+        marks.sort();
+        assert_eq!(marks, [((0, 1), 1), ((1, 0), 2)]);
+    }
+
+    #[test]
+    fn the_session_intent_tags_every_record_and_filters_stats_and_log() {
+        let mut data = log_data();
+        let learning = &mut data.history[20];
+        learning.warmup = false;
+        learning.intent = SessionIntent::Learning;
+        let queue = QuestionQueue::with_order(PACK, &QUESTIONS, vec![0, 1]);
+        let mut app_state = AppState::with_data(Settings::default(), Box::new(MemoryStorage::new(data)), queue);
+        app_state.next_intent = SessionIntent::Test;
+        app_state.begin_session();
+        finish_in(&mut app_state, "neko", 1.0);
+        finish_in(&mut app_state, "inu", 1.0);
+        let data = &app_state.player_data;
+        let tagged: Vec<&TypeRecord> = data.history.iter().filter(|record| record.intent == SessionIntent::Test).collect();
+        assert_eq!(tagged.len(), 2);
+        assert_eq!(app_state.session.intent, SessionIntent::Test);
+
+        let log = LogReport::from_player_data(data, Some(SessionIntent::Learning));
+        assert_eq!(log.records.iter().map(|record| record.question_hiragana.as_str()).collect::<Vec<_>>(), ["いぬ"]);
+
+        let args = StatsArgs {
+            xp: false,
+            cooldowns: true,
+            compare: None,
+            rotation: false,
+            confusions: false,
+            by_intent: true,
+            intent: Some(SessionIntent::Test),
+            code: false,
+            pace: false,
+        };
+        let report = stats_report(data, &args, Utc::now());
+        let cooldowns = report.cooldowns.unwrap();
+        assert_eq!(cooldowns.other.questions + cooldowns.after_cooldown.questions, 2);
+        // 目的ごとの表は絞り込まずにすべての目的を並べる
+        let intents: Vec<SessionIntent> = report.by_intent.unwrap().iter().map(|row| row.intent).collect();
+        assert_eq!(intents, [SessionIntent::Unspecified, SessionIntent::Test, SessionIntent::Learning]);
+    }
+
+    /// 保存先に書かれたデータ
+    fn persisted(app_state: &AppState) -> PlayerData {
+        app_state.storage.load()
+    }
+
+    #[test]
+    fn an_update_found_mid_session_is_announced_only_after_the_session() {
+        let (release, gate) = std::sync::mpsc::channel();
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        app_state.update_check = UpdateCheck::spawn(move || gate.recv().unwrap_or(Ok(None)));
+        submit_keys(&mut app_state, "ne");
+        release.send(Ok(Some("9.9.9".to_string()))).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        submit_keys(&mut app_state, "koinu");
+        assert!(app_state.menu_notices.iter().all(|n| !n.starts_with("Update available")));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app_state.update_check.available().is_none() && Instant::now() < deadline {
+            app_state.end_session();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let notices: Vec<_> = app_state.menu_notices.iter().filter(|n| n.starts_with("Update available")).collect();
+        assert_eq!(notices, ["Update available: v9.9.9 (Settings › Update Now)"]);
+        // メニューを描く前に取り出し直しても、お知らせは増えない
+        app_state.poll_update();
+        assert_eq!(app_state.menu_notices.iter().filter(|n| n.starts_with("Update available")).count(), 1);
+    }
+
+    /// 1打鍵ずつ画面からの入力と同じように渡す（打ち終えたお題は次のお題に進む）
+    fn submit_keys(app_state: &mut AppState, keys: &str) {
+        for c in keys.chars() {
+            app_state.submit_char(c);
+        }
+    }
+
+    #[test]
+    fn a_scripted_session_persists_every_question() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        submit_keys(&mut app_state, "nekoinu");
+
+        let saved = persisted(&app_state);
+        let rows: Vec<(&str, &str, u32, u32, Option<QuestionId>)> = saved
+            .history
+            .iter()
+            .map(|r| (r.question_japanese.as_str(), r.question_hiragana.as_str(), r.total_chars, r.misses, r.question_id))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("猫", "ねこ", 4, 0, Some(QuestionId::new(PACK, 0))),
+                ("犬", "いぬ", 3, 0, Some(QuestionId::new(PACK, 1))),
+            ]
+        );
+        for record in &saved.history {
+            let expected = score_question(
+                ScoringPreset::Current,
+                record.total_chars,
+                record.duration_sec,
+                record.misses,
+                Keystrokes { correct: record.keystrokes, total: record.keystrokes },
+            );
+            assert_eq!(record.xp_gained, expected.xp);
+            assert_eq!(record.outcome, QuestionOutcome::Completed);
+        }
+        let xp: u64 = saved.history.iter().map(|r| u64::from(r.xp_gained)).sum();
+        assert_eq!(saved.xp_ledger.total(XpSource::QuestionCompletion), xp);
+        assert_eq!(saved.total_typed_chars, 7);
+        assert_eq!(saved.total_misses, 0);
+    }
+
+    #[test]
+    fn a_miss_is_persisted_with_the_unit_it_happened_in() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        submit_keys(&mut app_state, "nekoiqnu");
+
+        let saved = persisted(&app_state);
+        assert_eq!(saved.history.len(), 2);
+        let record = &saved.history[1];
+        assert_eq!((record.misses, record.keystrokes), (1, 4));
+        // い の次の ぬ（2 つめの単位）でのミス
+        assert_eq!(record.unit_misses, [(1, 1)]);
+        assert_eq!(saved.total_misses, 1);
+    }
+
+    #[test]
+    fn the_stored_profile_is_where_the_session_starts() {
+        let mut data = PlayerData::default();
+        data.history.push(TypeRecord::sample("ねこ", 4, 2.0, 0));
+        let mut app_state = scripted_app(Settings::default(), data);
+        submit_keys(&mut app_state, "neko");
+
+        let saved = persisted(&app_state);
+        assert_eq!(saved.history.len(), 2);
+        assert_eq!(saved.history[0].duration_sec, 2.0);
+    }
+
+    #[test]
+    fn blacklisting_the_last_question_is_persisted_on_save() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        submit_keys(&mut app_state, "neko");
+        app_state.toggle_blacklist_last();
+        assert!(persisted(&app_state).blacklist.is_empty(), "not saved until the profile is saved");
+
+        app_state.save_player_data();
+        assert_eq!(persisted(&app_state).blacklist, [QuestionId::new(PACK, 0)]);
+        assert!(!app_state.has_unsaved_changes());
+    }
+
+    #[test]
+    fn the_last_question_can_be_retried_only_from_the_result() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let neko = QuestionId::new(PACK, 0);
+        submit_keys(&mut app_state, "neko");
+        assert_eq!(app_state.typing_phase(Instant::now()), TypingPhase::Result);
+        let serves = app_state.player_data.serve_counts.get(&neko).copied().unwrap_or(0);
+
+        app_state.retry_last_question();
+        assert_eq!(app_state.current_hiragana(), "ねこ");
+        assert_eq!(app_state.player_data.serve_counts[&neko], serves + 1);
+        // 1回目の記録は残り、やり直した回も別の記録になる
+        submit_keys(&mut app_state, "neko");
+        let saved = persisted(&app_state);
+        assert_eq!(saved.history.iter().filter(|record| record.question_id == Some(neko)).count(), 2);
+
+        // 次のお題を打ち始めたら、もうやり直せない
+        submit_keys(&mut app_state, "i");
+        app_state.retry_last_question();
+        assert_eq!(app_state.current_hiragana(), "いぬ");
+    }
+
+    #[test]
+    fn a_warmup_question_is_not_retried() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        submit_keys(&mut app_state, "neko");
+        app_state.player_data.edit().history.last_mut().unwrap().warmup = true;
+        app_state.retry_last_question();
+        assert_eq!(app_state.current_hiragana(), "いぬ");
+        assert!(app_state.flash.as_ref().is_some_and(|(message, _)| message.contains("cannot be retried")));
+    }
+
+    #[test]
+    fn the_badge_and_typical_time_describe_the_finished_question() {
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![3, 0]);
+        assert_eq!(app_state.question_meta, QuestionMeta { tier: Some(0), keys: 5 });
+        submit_keys(&mut app_state, "kitte");
+        assert_eq!(app_state.question_meta, QuestionMeta { tier: Some(0), keys: 4 });
+
+        let result = app_state.result_display.visible(Instant::now()).unwrap();
+        assert_eq!(result.meta, QuestionMeta { tier: Some(0), keys: 5 });
+        assert_eq!(result.expected_secs, expected_secs(5));
+    }
+
+    #[test]
+    fn sleeping_mid_question_pauses_it_without_counting_the_sleep() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        type_keys(&mut app_state, "nek");
+        let last_key = Instant::now() - Duration::from_secs(2);
+        app_state.start_time = Some(last_key - Duration::from_secs(1));
+        app_state.last_key_time = Some(last_key);
+        // 前の周の壁時計を 1 時間前にして、スリープから戻ったところにする
+        app_state.sleep_detector.check(Instant::now(), SystemTime::now() - Duration::from_secs(3600));
+        app_state.check_sleep();
+
+        // 打ったところは残したまま、最後の入力の時点から一時停止している
+        assert!(app_state.is_paused());
+        assert_eq!(app_state.paused_since, Some(last_key));
+        assert!(app_state.sleep_notice.is_some_and(|gap| gap >= Duration::from_secs(3600)));
+        assert_eq!(app_state.current_char_index, 1);
+        assert!(app_state.active_elapsed() < Duration::from_millis(1500));
+        let model = typing_model(&app_state, Instant::now());
+        assert_eq!(result_texts(&model)[0], "SYSTEM SLEPT — paused");
+
+        // 次のキーで再開し、眠っていた間を除いた時間で記録する
+        submit_keys(&mut app_state, "o");
+        assert!(!app_state.is_paused());
+        let saved = persisted(&app_state);
+        assert_eq!(saved.history.len(), 1);
+        assert_eq!(saved.history[0].question_hiragana, "ねこ");
+        assert!(saved.history[0].duration_sec < 1.5);
+    }
+
+    #[test]
+    fn sleeping_before_the_first_key_forgets_when_the_question_was_shown() {
+        let settings = Settings { timing_policy: TimingPolicy::OnDisplay, ..Settings::default() };
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        let shown = Instant::now() - Duration::from_secs(5);
+        app_state.question_shown_at = Some(shown);
+        // 前の周の壁時計を 1 時間前にして、スリープから戻ったところにする
+        app_state.sleep_detector.check(Instant::now(), SystemTime::now() - Duration::from_secs(3600));
+        app_state.check_sleep();
+        assert_eq!(app_state.question_shown_at, None);
+        assert!(persisted(&app_state).history.is_empty());
+
+        // 次のフレームで表示し直した時刻から数える
+        let now = Instant::now();
+        app_state.mark_question_shown(now);
+        assert_eq!(app_state.question_shown_at, Some(now));
+    }
+
+    #[test]
+    fn the_spectator_frame_splits_the_romaji_at_the_cursor() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let now = Instant::now();
+        let frame = app_state.spectator_frame(now);
+        assert_eq!((frame.japanese.as_str(), frame.typed.as_str(), frame.remaining.as_str()), ("猫", "", "neko"));
+        assert_eq!((frame.cps, frame.misses, frame.questions), (None, 0, 0));
+
+        type_keys(&mut app_state, "nx");
+        let frame = app_state.spectator_frame(now);
+        assert_eq!((frame.typed.as_str(), frame.remaining.as_str(), frame.misses), ("n", "eko", 1));
+
+        // 速さは打ったところまでの打鍵数で数える（ねこ の4打鍵のうち3打鍵を2秒）
+        type_keys(&mut app_state, "ek");
+        app_state.start_time = Some(now - Duration::from_secs(2));
+        let frame = app_state.spectator_frame(now);
+        assert_eq!((frame.typed.as_str(), frame.remaining.as_str(), frame.cps), ("nek", "o", Some(1.5)));
+
+        // 綴りを切り替えたら、切り替えた後の綴りで送る
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![2, 0]);
+        type_keys(&mut app_state, "c");
+        let frame = app_state.spectator_frame(now);
+        assert_eq!((frame.typed.as_str(), frame.remaining.as_str()), ("c", "hizu"));
+
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        finish_in(&mut app_state, "neko", 2.5);
+        assert_eq!(app_state.spectator_frame(now).questions, 1);
+    }
+
+    #[test]
+    fn the_broadcast_binds_to_localhost_unless_told_otherwise() {
+        let cli = Cli::try_parse_from(["type-wiz", "--broadcast", "9000"]).unwrap();
+        assert_eq!((cli.broadcast, cli.broadcast_bind), (Some(9000), IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+        let cli = Cli::try_parse_from(["type-wiz", "--broadcast", "9000", "--broadcast-bind", "0.0.0.0"]).unwrap();
+        assert_eq!(cli.broadcast_bind, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        // 待ち受けるアドレスだけ指定しても配信は始まらない
+        assert!(Cli::try_parse_from(["type-wiz", "--broadcast-bind", "0.0.0.0"]).is_err());
+        assert!(Cli::try_parse_from(["type-wiz"]).unwrap().broadcast.is_none());
+    }
+
+    #[test]
+    fn the_spectate_view_waits_and_then_shows_the_frame() {
+        let render = |frame: Option<&SpectatorFrame>| {
+            let mut terminal = Terminal::new(TestBackend::new(60, 12)).unwrap();
+            terminal.draw(|f| ui_spectate(f, "127.0.0.1:9000", frame)).unwrap();
+            serialize_buffer(terminal.backend().buffer())
+        };
+        let waiting = render(None);
+        assert!(waiting.contains("watching 127.0.0.1:9000"), "{waiting}");
+        assert!(waiting.contains("Waiting for the typist"), "{waiting}");
+
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        type_keys(&mut app_state, "nx");
+        // 打ち込みは一瞬なので、速さは決め打ちにする
+        let frame = SpectatorFrame { cps: Some(4.5), ..app_state.spectator_frame(Instant::now()) };
+        let shown = render(Some(&frame));
+        assert!(shown.contains("Lv.1 · 0 question(s) · 4.50 CPS · 1 miss(es)"), "{shown}");
+        assert!(shown.contains("猫") && shown.contains("ねこ") && shown.contains("neko"), "{shown}");
+        assert!(!shown.contains("Waiting"), "{shown}");
+    }
+
+    #[test]
+    fn a_tag_boost_is_persisted_in_the_history_and_the_ledger() {
+        let mut settings = Settings::default();
+        settings.tag_xp_multipliers.insert("N4".to_string(), 1.5);
+        settings.tag_xp_multipliers.insert("verb".to_string(), 2.0);
+        let mut app_state = scripted_app(settings, PlayerData::default());
+        app_state.question_tags.insert(QuestionId::new(PACK, 0), vec!["N4".to_string(), "verb".to_string()]);
+        submit_keys(&mut app_state, "nekoinu");
+
+        let saved = persisted(&app_state);
+        let [boosted, plain] = [&saved.history[0], &saved.history[1]];
+        let unboosted = |record: &TypeRecord| {
+            let keystrokes = Keystrokes { correct: record.keystrokes, total: record.keystrokes };
+            score_question(ScoringPreset::Current, record.total_chars, record.duration_sec, record.misses, keystrokes).xp
+        };
+        let base = unboosted(boosted);
+        let boost = (f64::from(base) * 3.0).round() as u32;
+        assert_eq!(boosted.xp_gained, boost);
+        assert_eq!(plain.xp_gained, unboosted(plain));
+        assert_eq!(saved.xp_ledger.total(XpSource::TagBoost), u64::from(boost - base));
+        assert_eq!(
+            saved.xp_ledger.total(XpSource::QuestionCompletion),
+            u64::from(base) + u64::from(plain.xp_gained)
+        );
+    }
+
+    fn timed_app(factor: f64, forgiveness_ms: u64) -> AppState {
+        let settings =
+            Settings { time_limit_factor: factor, rollover_forgiveness_ms: forgiveness_ms, ..Settings::default() };
+        scripted_app(settings, PlayerData::default())
+    }
+
+    /// 打ち始めた時刻をずらして、今のお題の制限時間を少し過ぎたことにする
+    fn run_out_the_clock(app_state: &mut AppState) {
+        let limit = app_state.time_limit().expect("the time limit is on");
+        app_state.start_time = Some(Instant::now() - limit - Duration::from_millis(50));
+    }
+
+    #[test]
+    fn the_time_limit_scales_the_baseline_time_for_the_question() {
+        assert_eq!(timed_app(0.0, 0).time_limit(), None);
+        for factor in [1.0, 1.5, 3.0] {
+            let app_state = timed_app(factor, 0);
+            let expected = expected_secs(app_state.question_meta.keys).unwrap() * factor;
+            let limit = app_state.time_limit().unwrap();
+            assert!((limit.as_secs_f64() - expected).abs() < 1e-6, "{factor}: {limit:?} vs {expected}");
+            // 打ち始めるまでは満タン
+            assert_eq!(app_state.time_left_at(Instant::now() + Duration::from_secs(60)), Some((limit, limit)));
+        }
+    }
+
+    #[test]
+    fn running_out_of_time_records_a_timeout_and_moves_on() {
+        let mut app_state = timed_app(1.0, 0);
+        finish_in(&mut app_state, "neko", 1.0);
+        assert_eq!(app_state.current_streak, 1);
+        let xp = persisted(&app_state).current_xp;
+
+        type_keys(&mut app_state, "ixn");
+        assert!(!app_state.check_time_limit(), "there is time left");
+        run_out_the_clock(&mut app_state);
+        assert!(app_state.check_time_limit());
+
+        let data = persisted(&app_state);
+        let record = data.history.last().unwrap();
+        assert_eq!((record.question_hiragana.as_str(), record.outcome), ("いぬ", QuestionOutcome::TimedOut));
+        // 打ったところまで（i と ぬ の n）を記録し、打たなかった残りはミスに数えない
+        assert_eq!((record.total_chars, record.misses), (2, 1));
+        assert_eq!((record.score, record.xp_gained, data.current_xp), (0.0, 0, xp));
+        assert!(!record.counts_for_bests());
+        assert_eq!(app_state.current_streak, 0);
+        assert!(app_state.start_time.is_none());
+        assert_eq!(app_state.current_hiragana(), "ねこ");
+        assert!(app_state.flash.as_ref().is_some_and(|(text, _)| text.starts_with("Time's up")));
+
+        let now = Instant::now() + completion_sweep::SWEEP_DURATION;
+        assert!(result_texts(&typing_model(&app_state, now)).iter().any(|line| line.starts_with("TIME UP")));
+    }
+
+    #[test]
+    fn pausing_freezes_the_countdown() {
+        let mut app_state = timed_app(1.5, 0);
+        type_keys(&mut app_state, "n");
+        let limit = app_state.time_limit().unwrap();
+        let now = Instant::now();
+        app_state.start_time = Some(now - Duration::from_secs(1));
+        app_state.paused_since = Some(now - Duration::from_millis(500));
+        let frozen = limit - Duration::from_millis(500);
+        assert_eq!(app_state.time_left_at(now), Some((frozen, limit)));
+        assert_eq!(app_state.time_left_at(now + Duration::from_secs(60)), Some((frozen, limit)));
+
+        // 一時停止の間に時間を過ぎても打ち切らない
+        run_out_the_clock(&mut app_state);
+        app_state.paused_since = Some(Instant::now() - Duration::from_secs(60));
+        assert!(!app_state.check_time_limit());
+        assert!(persisted(&app_state).history.is_empty());
+
+        // 止めていた時間を除くと、まだ時間は残っている
+        app_state.paused_duration += Duration::from_secs(60);
+        app_state.paused_since = None;
+        assert!(!app_state.check_time_limit());
+        app_state.paused_duration = Duration::ZERO;
+        run_out_the_clock(&mut app_state);
+        assert!(app_state.check_time_limit());
+    }
+
+    #[test]
+    fn a_final_keystroke_delivered_before_the_check_beats_the_timeout() {
+        let mut app_state = timed_app(1.0, 0);
+        type_keys(&mut app_state, "nek");
+        run_out_the_clock(&mut app_state);
+        // 入力待ちのループは届いたキーを先に処理してから時間を確かめる
+        submit_keys(&mut app_state, "o");
+        assert!(!app_state.check_time_limit());
+
+        let history = persisted(&app_state).history;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].outcome, QuestionOutcome::Completed);
+        assert!(history[0].score > 0.0);
+    }
+
+    #[test]
+    fn a_held_rollover_key_is_flushed_before_the_timeout() {
+        let mut app_state = timed_app(1.0, 25);
+        type_keys(&mut app_state, "ne");
+        // こ の k より先に o が届き、入れ替わりかどうか待っている
+        assert!(app_state.rollover_keys('o', Instant::now()).is_empty());
+        run_out_the_clock(&mut app_state);
+        assert!(app_state.check_time_limit());
+
+        let record = persisted(&app_state).history.pop().unwrap();
+        assert_eq!((record.outcome, record.misses), (QuestionOutcome::TimedOut, 1));
+        assert!(app_state.held_key.is_none());
+        assert_eq!(app_state.current_hiragana(), "いぬ");
+    }
+
+    #[test]
+    fn the_countdown_bar_shrinks_and_changes_colour() {
+        let limit = Duration::from_secs(10);
+        let cases = [
+            (Duration::from_secs(10), 20, Color::Green),
+            (Duration::from_secs(6), 12, Color::Green),
+            (Duration::from_secs(5), 10, Color::Yellow),
+            (Duration::from_secs(2), 4, Color::Red),
+            (Duration::from_millis(100), 1, Color::Red),
+            (Duration::ZERO, 0, Color::Red),
+        ];
+        for (left, filled, colour) in cases {
+            let line = countdown_line(left, limit);
+            let text = format!(" ⏱ {}{} {:.1}s ", "█".repeat(filled), "░".repeat(20 - filled), left.as_secs_f64());
+            assert_eq!((line.to_string(), line.style.fg), (text, Some(colour)), "{left:?}");
+        }
+    }
+
+    #[test]
+    fn a_glossed_question_shows_its_gloss_and_keeps_it_on_the_record() {
+        let mut app_state = scripted_app_with_order(Settings::default(), PlayerData::default(), vec![1, 0]);
+        let neko = UserQuestion {
+            gloss: [("en", "cat"), ("zh", "猫咪")].map(|(code, text)| (code.to_string(), text.to_string())).into(),
+            ..UserQuestion::sample("猫", "ねこ")
+        };
+        app_state.glosses = GlossBook::from_questions(&[neko]);
+        // 訳のないお題では行ごと詰める
+        assert_eq!(app_state.shown_gloss(), None);
+        submit_keys(&mut app_state, "inu");
+        assert_eq!(app_state.shown_gloss(), Some("cat"));
+
+        // 表示言語の訳がなければ、設定を入れたときだけほかの言語の訳を出す
+        app_state.settings.edit().ui_language = locale::UiLanguage::Ja;
+        assert_eq!(app_state.shown_gloss(), None);
+        app_state.settings.edit().show_gloss = true;
+        assert_eq!(app_state.shown_gloss(), Some("cat"));
+
+        // 表示していなくても記録には残す
+        app_state.settings.edit().show_gloss = false;
+        submit_keys(&mut app_state, "neko");
+        let saved = persisted(&app_state);
+        let glosses: Vec<Option<&str>> = saved.history.iter().map(|record| record.gloss.as_deref()).collect();
+        assert_eq!(glosses, [None, Some("cat")]);
+    }
+
+
+    // MARK: 描画内容を作る関数（スナップショットと同じ場面で、端末を使わずに確かめる）
+
+    /// ローマ字の行の、文字ごとの (文字, 見た目)
+    fn romaji_cells(model: &TypingModel) -> Vec<(String, Style)> {
+        model.question.romaji[0].spans.iter().map(|span| (span.content.to_string(), span.style)).collect()
+    }
+
+    fn result_texts(model: &TypingModel) -> Vec<String> {
+        model.chrome.as_ref().unwrap().results.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn model_of_a_fresh_question() {
+        let (app_state, now) = fresh_question();
+        let model = typing_model(&app_state, now);
+        assert_eq!(model.phase, TypingPhase::Typing);
+        assert_eq!(model.flash, ErrorFlash::Off);
+        assert_eq!(model.question.japanese, "猫");
+        assert_eq!(model.question.hiragana.to_string(), "ねこ");
+        let cursor = Style::default().fg(Color::Black).bg(Color::White);
+        assert_eq!(romaji_cells(&model)[0], ("n".to_string(), cursor));
+        let chrome = model.chrome.as_ref().unwrap();
+        assert_eq!(chrome.gauge_label, "Lv.1 (0 / 10) ");
+        assert_eq!(result_texts(&model), ["Normal bests · CPS 0.00 · Streak 0 · Score 0"]);
+        assert!(model.overlays.is_empty());
+    }
+
+    #[test]
+    fn model_of_a_miss_shows_the_ghost_before_a_red_cursor() {
+        let (app_state, now) = mid_question_with_error();
+        let model = typing_model(&app_state, now);
+        assert_eq!(model.flash, ErrorFlash::Subtle);
+        let cells = romaji_cells(&model);
+        assert_eq!(cells[1], ("x".to_string(), Style::default().fg(Color::Red).add_modifier(Modifier::DIM)));
+        let cursor = Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::UNDERLINED);
+        assert_eq!(cells[2], ("e".to_string(), cursor));
+        assert_eq!(result_texts(&model)[0], "now 0.67 CPS · +0 XP");
+    }
+
+    #[test]
+    fn model_after_a_pattern_switch_spells_the_new_pattern() {
+        let (app_state, now) = mid_question_after_pattern_switch();
+        let model = typing_model(&app_state, now);
+        assert_eq!(model.question.japanese, "地図");
+        assert_eq!(model.question.romaji[0].to_string(), "chizu");
+        assert_eq!(romaji_cells(&model)[0], ("c".to_string(), Style::default().fg(Color::Green)));
+    }
+
+    #[test]
+    fn model_of_a_completed_question_lists_the_result_and_the_xp() {
+        let (app_state, now) = question_complete();
+        let model = typing_model(&app_state, now);
+        assert_eq!(model.phase, TypingPhase::Result);
+        // 塗りの演出が終わったので、次のお題が出ている
+        assert_eq!(model.question.japanese, "犬");
+        let results = result_texts(&model);
+        assert_eq!(results[0], "CPS: 1.60 / Time: 2.50s");
+        let xp = app_state.last_xp_gained.unwrap();
+        assert_eq!(model.chrome.as_ref().unwrap().gauge_label, format!("Lv.1 ({} / 10)  +{}XP", xp, xp));
+        let parts: i64 = app_state.last_xp_breakdown.iter().map(|(_, amount)| amount).sum();
+        assert_eq!(parts, i64::from(xp));
+        assert!(results.contains(&result_hint()));
+    }
+
+    #[test]
+    fn model_after_a_level_up_shows_the_new_level() {
+        let (app_state, now) = level_up();
+        let model = typing_model(&app_state, now);
+        let pd = &app_state.player_data;
+        assert_eq!(pd.level, 2);
+        let chrome = model.chrome.as_ref().unwrap();
+        assert!(chrome.gauge_label.starts_with(&format!("Lv.2 ({} / {})", pd.current_xp, pd.required_xp_for_next_level())));
+        assert_eq!(chrome.gauge_ratio, pd.current_xp as f64 / pd.required_xp_for_next_level() as f64);
+    }
+
+    #[test]
+    fn focus_mode_models_only_the_question() {
+        let (mut app_state, now) = mid_question_with_error();
+        app_state.settings.edit().focus_mode = true;
+        let model = typing_model(&app_state, now);
+        assert!(model.chrome.is_none());
+        assert_eq!(model.content.result_rows, 0);
+        assert_eq!(model.question.japanese, "猫");
+    }
+
+    #[test]
+    fn roster_model_follows_the_class_phase() {
+        let mut app_state = scripted_app(Settings::default(), PlayerData::default());
+        let mut class = ClassSession::new(vec!["Aoi".to_string(), "Ren".to_string()]);
+        let t0 = Instant::now();
+
+        let ready = roster_model(&class, &app_state, t0);
+        assert_eq!(ready.title, " TYPE WiZ · Class 1/2 ");
+        assert_eq!(ready.name, "Aoi");
+        assert_eq!(ready.message[0].to_string(), "Press Enter or Space to start");
+        assert!(ready.question.is_none());
+
+        app_state.reset_turn();
+        class.start();
+        for c in "ne".chars() {
+            app_state.handle_char_input(c);
+        }
+        let typing = roster_model(&class, &app_state, t0);
+        assert!(typing.message.is_empty());
+        let question = typing.question.unwrap();
+        assert_eq!(question.romaji[0].to_string(), "neko");
+        assert_eq!(question.hiragana.spans[0].style, Style::default().fg(Color::Green));
+
+        class.complete(StudentResult { cps: 2.0, accuracy: 100.0, score: 500.0 }, t0);
+        let result = roster_model(&class, &app_state, t0 + Duration::from_millis(1500));
+        assert_eq!(
+            result.message.iter().map(|line| line.to_string()).collect::<Vec<_>>(),
+            ["CPS 2.00 · Accuracy 100.0% · Score 500", "Next student in 3s (any key to continue)"]
+        );
+    }
+
+    #[test]
+    fn author_model_marks_characters_that_cannot_be_typed() {
+        let app_state = scripted_app(Settings::default(), PlayerData::default());
+        let mut form = AuthorForm::default();
+        form.japanese.insert_str("猫☆");
+        form.hiragana.insert_str("ね☆");
+        let model = author_model(&app_state, &form);
+        assert_eq!(model.focus, AuthorField::Japanese);
+        assert_eq!(model.romaji.to_string(), "ne☆");
+        assert_eq!(model.romaji.spans[1].style, Style::default().fg(Color::Red));
+        assert_eq!(model.message.unwrap().to_string(), "cannot be typed: ☆");
+
+        form.message = Some(("Saved".to_string(), true));
+        let saved = author_model(&app_state, &form).message.unwrap();
+        assert_eq!((saved.to_string(), saved.style.fg), ("Saved".to_string(), Some(Color::Green)));
+    }
+
+    #[test]
+    fn a_result_exactly_at_the_floor_is_record_eligible() {
+        // neko を 1 ミスで打つと、5 打鍵中 4 打鍵が正しく正確率 80%
+        let finish_at_floor = |floor: f64| {
+            let settings = Settings { accuracy_floor_for_bests: floor, ..Settings::default() };
+            let mut app_state = scripted_app(settings, PlayerData::default());
+            finish_in(&mut app_state, "nxeko", 2.0);
+            let texts = result_texts(&typing_model(&app_state, Instant::now() + completion_sweep::SWEEP_DURATION));
+            (app_state, texts)
+        };
+
+        let (app_state, texts) = finish_at_floor(80.0);
+        assert!(!texts.iter().any(|line| line.contains("record-eligible")), "{texts:?}");
+        assert!(app_state.targets.best_cps > 0.0);
+
+        let (app_state, texts) = finish_at_floor(85.0);
+        assert!(texts.iter().any(|line| line == "Not record-eligible (accuracy 80.0% < 85%)"), "{texts:?}");
+        assert_eq!(app_state.targets.best_cps, 0.0);
+        // 記録は下限に関係なく保存する
+        let record = persisted(&app_state).history.pop().unwrap();
+        assert_eq!((record.misses, record.keystrokes), (1, 5));
+        assert!(record.xp_gained > 0);
+    }
+}
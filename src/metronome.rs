@@ -0,0 +1,233 @@
+// ============================================
+// src/metronome.rs
+// 目標の打鍵テンポに合わせて拍を刻むメトロノームと、打鍵のリズムの集計
+// 拍の位置は開始からの経過時間で決める（画面の更新回数には頼らない）
+// ============================================
+
+use std::time::{Duration, Instant};
+
+/// 設定画面で選べる目標テンポ（打鍵/分。0 は無効）
+pub const METRONOME_RATES: [u32; 5] = [0, 180, 240, 300, 360];
+
+/// 拍の直後、点灯して見せる長さ（周期に対する割合）
+const PULSE_FRACTION: f64 = 0.2;
+/// 拍からのずれがこの割合以内なら拍に合っているとみなす
+const ON_BEAT_FRACTION: f64 = 0.15;
+/// 打鍵の間隔がこの周期数を超えたら、お題の切り替えなどの間とみなして集計しない
+const GAP_PERIODS: f64 = 3.0;
+
+/// 直前の打鍵と拍の関係
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeatPhase {
+    /// 拍より早い
+    Ahead,
+    /// 拍に合っている
+    OnBeat,
+    /// 拍より遅い
+    Behind,
+}
+
+/// セッション中のメトロノーム
+#[derive(Debug, Clone)]
+pub struct Metronome {
+    period: Duration,
+    started: Instant,
+    /// 最後に知らせた拍の番号
+    last_beat: u64,
+    last_key: Option<Instant>,
+    last_phase: Option<BeatPhase>,
+    rhythm: RhythmStats,
+}
+
+impl Metronome {
+    /// `rate` は目標テンポ（打鍵/分）。0 なら None
+    pub fn new(rate: u32, now: Instant) -> Option<Self> {
+        (rate > 0).then(|| Self {
+            period: Duration::from_secs_f64(60.0 / f64::from(rate)),
+            started: now,
+            last_beat: 0,
+            last_key: None,
+            last_phase: None,
+            rhythm: RhythmStats::default(),
+        })
+    }
+
+    /// 拍の中での位置 (0.0 〜 1.0)。0 が拍の瞬間
+    pub fn phase(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64();
+        (elapsed / self.period.as_secs_f64()).fract()
+    }
+
+    /// 拍の直後で、点灯して見せるべきか
+    pub fn pulse_on(&self, now: Instant) -> bool {
+        self.phase(now) < PULSE_FRACTION
+    }
+
+    /// 次の拍までの時間（画面の更新をこれより遅らせない）
+    pub fn until_next_beat(&self, now: Instant) -> Duration {
+        self.period.mul_f64(1.0 - self.phase(now))
+    }
+
+    /// 前回呼んだときから新しい拍を迎えていれば true（音を鳴らす用）
+    pub fn take_beat(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64();
+        let beat = (elapsed / self.period.as_secs_f64()) as u64;
+        let new_beat = beat > self.last_beat;
+        self.last_beat = beat;
+        new_beat
+    }
+
+    /// 打鍵の時刻を記録する（CPS の計算には使わない）
+    pub fn record_keystroke(&mut self, now: Instant) {
+        // 拍の直後なら遅れ、直前なら早すぎ
+        let phase = self.phase(now);
+        let offset = if phase < 0.5 { phase } else { phase - 1.0 };
+        self.last_phase = Some(if offset.abs() <= ON_BEAT_FRACTION {
+            BeatPhase::OnBeat
+        } else if offset < 0.0 {
+            BeatPhase::Ahead
+        } else {
+            BeatPhase::Behind
+        });
+
+        if let Some(prev) = self.last_key {
+            let interval = now.saturating_duration_since(prev).as_secs_f64();
+            if interval <= self.period.as_secs_f64() * GAP_PERIODS {
+                self.rhythm.add(interval, self.period.as_secs_f64());
+            }
+        }
+        self.last_key = Some(now);
+    }
+
+    /// お題の切り替えなどで打鍵の間が空くとき（次の打鍵との間隔は集計しない）
+    pub fn break_run(&mut self) {
+        self.last_key = None;
+    }
+
+    pub fn last_phase(&self) -> Option<BeatPhase> {
+        self.last_phase
+    }
+
+    /// セッションのリズムの集計（打鍵の間隔が1つもなければ None）
+    pub fn summary(&self) -> Option<RhythmSummary> {
+        self.rhythm.summary(self.period.as_secs_f64())
+    }
+}
+
+/// 打鍵の間隔の集計
+#[derive(Debug, Clone, Copy, Default)]
+struct RhythmStats {
+    intervals: u32,
+    sum: f64,
+    /// 目標の周期からのずれの2乗の合計
+    sum_sq_deviation: f64,
+}
+
+impl RhythmStats {
+    fn add(&mut self, interval: f64, period: f64) {
+        self.intervals += 1;
+        self.sum += interval;
+        self.sum_sq_deviation += (interval - period).powi(2);
+    }
+
+    fn summary(&self, period: f64) -> Option<RhythmSummary> {
+        (self.intervals > 0).then(|| RhythmSummary {
+            intervals: self.intervals,
+            target_ms: period * 1000.0,
+            mean_ms: self.sum / f64::from(self.intervals) * 1000.0,
+            deviation_ms: (self.sum_sq_deviation / f64::from(self.intervals)).sqrt() * 1000.0,
+        })
+    }
+}
+
+/// セッションのリズムの安定度
+#[derive(Debug, Clone, Copy)]
+pub struct RhythmSummary {
+    pub intervals: u32,
+    /// 目標の打鍵間隔
+    pub target_ms: f64,
+    /// 実際の打鍵間隔の平均
+    pub mean_ms: f64,
+    /// 目標の打鍵間隔に対する標準偏差
+    pub deviation_ms: f64,
+}
+
+impl RhythmSummary {
+    /// メニューに表示する1行
+    pub fn line(&self) -> String {
+        format!(
+            "Rhythm: target {:.0} ms · mean {:.0} ms · ±{:.0} ms ({} intervals)",
+            self.target_ms, self.mean_ms, self.deviation_ms, self.intervals
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 240 打鍵/分（周期 250 ms）
+    fn metronome() -> (Metronome, Instant) {
+        let start = Instant::now();
+        (Metronome::new(240, start).unwrap(), start)
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn a_zero_rate_is_off() {
+        assert!(Metronome::new(0, Instant::now()).is_none());
+    }
+
+    #[test]
+    fn the_beat_follows_the_elapsed_time() {
+        let (mut metronome, start) = metronome();
+        assert!(metronome.pulse_on(start + ms(20)));
+        assert!(!metronome.pulse_on(start + ms(100)));
+        assert!((metronome.phase(start + ms(375)) - 0.5).abs() < 1e-9);
+        assert_eq!(metronome.until_next_beat(start + ms(200)), ms(50));
+
+        assert!(!metronome.take_beat(start + ms(100)));
+        assert!(metronome.take_beat(start + ms(260)));
+        assert!(!metronome.take_beat(start + ms(300)));
+        // 拍をいくつか飛ばしても、知らせるのは1回だけ
+        assert!(metronome.take_beat(start + ms(1100)));
+        assert!(!metronome.take_beat(start + ms(1150)));
+    }
+
+    #[test]
+    fn keystrokes_are_ahead_on_or_behind_the_beat() {
+        let (mut metronome, start) = metronome();
+        assert_eq!(metronome.last_phase(), None);
+        metronome.record_keystroke(start + ms(255));
+        assert_eq!(metronome.last_phase(), Some(BeatPhase::OnBeat));
+        metronome.record_keystroke(start + ms(490));
+        assert_eq!(metronome.last_phase(), Some(BeatPhase::OnBeat));
+        metronome.record_keystroke(start + ms(600));
+        assert_eq!(metronome.last_phase(), Some(BeatPhase::Behind));
+        metronome.record_keystroke(start + ms(690));
+        assert_eq!(metronome.last_phase(), Some(BeatPhase::Ahead));
+    }
+
+    #[test]
+    fn the_rhythm_skips_long_gaps_and_broken_runs() {
+        let (mut metronome, start) = metronome();
+        assert!(metronome.summary().is_none());
+        for at in [0, 200, 500] {
+            metronome.record_keystroke(start + ms(at));
+        }
+        // 3 周期を超える間は数えない
+        metronome.record_keystroke(start + ms(1500));
+        metronome.break_run();
+        metronome.record_keystroke(start + ms(1600));
+
+        let summary = metronome.summary().unwrap();
+        assert_eq!(summary.intervals, 2);
+        assert!((summary.target_ms - 250.0).abs() < 1e-6);
+        assert!((summary.mean_ms - 250.0).abs() < 1e-6);
+        assert!((summary.deviation_ms - 50.0).abs() < 1e-6);
+        assert_eq!(summary.line(), "Rhythm: target 250 ms · mean 250 ms · ±50 ms (2 intervals)");
+    }
+}
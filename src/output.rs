@@ -0,0 +1,137 @@
+// ============================================
+// src/output.rs
+// コマンドの結果を人間向け / スクリプト向けの形式で出力する
+// ============================================
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use std::borrow::Cow;
+use std::io::{IsTerminal, Result, stdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::machine_format::json_pretty;
+
+/// `--output` で選べる出力形式
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// 人間向けの表示（色付き）
+    Plain,
+    /// スクリプト向けの JSON
+    Json,
+}
+
+/// コマンドの結果。JSON ではそのまま直列化し、plain では `print_plain` で表示する
+pub trait Report: Serialize {
+    fn print_plain(&self);
+}
+
+/// 指定された形式で結果を出力する
+pub fn emit<R: Report>(report: &R, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Plain => report.print_plain(),
+        OutputFormat::Json => {
+            println!("{}", json_pretty(report)?);
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// MARK:色の使い方
+// --------------------------------------------------
+
+/// `--color` で選べる色の使い方
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorMode {
+    /// 端末に出すときだけ色を付ける（NO_COLOR が設定されていれば付けない）
+    Auto,
+    Always,
+    Never,
+}
+
+/// TUI 以外の出力に色を付けるか
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 色の使い方を決める（起動時に一度だけ呼ぶ）
+/// dialoguer のメニューの色もこれに合わせる
+pub fn init_color(mode: ColorMode) {
+    let enabled = match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+            !no_color && stdout().is_terminal()
+        }
+    };
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// 色を使わないときは ANSI のエスケープシーケンス (`ESC [ ... 終端文字`) を取り除く
+pub fn styled(text: &str) -> Cow<'_, str> {
+    styled_as(text, color_enabled())
+}
+
+/// `styled` の色の設定を引数で指定する版
+pub fn styled_as(text: &str, color: bool) -> Cow<'_, str> {
+    if color || !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            plain.push(ch);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            // パラメータを読み飛ばし、終端文字 (0x40〜0x7E) で止まる
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(plain)
+}
+
+/// `println!` と同じ書式で、色の設定に合わせて出力する
+macro_rules! outln {
+    () => {
+        println!()
+    };
+    ($($arg:tt)*) => {
+        println!("{}", $crate::output::styled(&format!($($arg)*)))
+    };
+}
+
+/// `print!` と同じ書式で、色の設定に合わせて出力する
+macro_rules! out {
+    ($($arg:tt)*) => {
+        print!("{}", $crate::output::styled(&format!($($arg)*)))
+    };
+}
+
+pub(crate) use {out, outln};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_output_drops_every_escape_sequence() {
+        let text = "\x1b[1;36mLv 3\x1b[0m  \x1b[90m(ねこ)\x1b[0m";
+        assert_eq!(styled_as(text, false), "Lv 3  (ねこ)");
+        assert_eq!(styled_as(text, true), text);
+        // CSI でないエスケープは次の1文字ごと落とす
+        assert_eq!(styled_as("a\x1b7b", false), "ab");
+        assert!(matches!(styled_as("no color", false), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,224 @@
+// ============================================
+// src/pace_profile.rs
+// お題のどのあたりで遅くなるか（打ち始め・中盤・終盤の速さ）
+// 1問の打鍵の時刻を区間に分け、区間ごとの速さをそのお題全体の速さと比べて記録する
+// 全体の速さで割っておくので、速さの違うお題の記録もそのまま平均できる
+// ============================================
+
+use serde::Serialize;
+
+use std::time::{Duration, Instant};
+
+use crate::save_data::TypeRecord;
+
+/// 1問を分ける区間の数（3つに分ける）
+pub const PACE_SEGMENTS: usize = 3;
+/// これより打鍵の少ないお題は区間に分けない（区間ごとの打鍵間隔が少なすぎてぶれる）
+const MIN_PROFILE_KEYS: usize = 9;
+/// 打鍵間隔はこの長さで打ち切る（手を止めた・放置した間が区間の速さを決めてしまわないように）
+const MAX_INTERVAL: Duration = Duration::from_millis(1500);
+/// 全体との差がこれより小さければ「どこも同じ速さ」とみなす
+const EVEN_TOLERANCE: f64 = 0.03;
+/// 棒の最大の長さ（文字数）
+const BAR_WIDTH: usize = 20;
+/// 区間の名前
+const SEGMENT_LABELS: [&str; PACE_SEGMENTS] = ["first third", "middle third", "final third"];
+
+/// 1問の区間ごとの速さを、そのお題全体の速さに対する千分率で返す（1000 で全体と同じ）
+/// 打鍵間隔は後ろのキーの位置の区間に数える。打鍵が少ないお題は空を返す
+pub fn segment_pace(times: &[Instant]) -> Vec<u16> {
+    if times.len() < MIN_PROFILE_KEYS {
+        return Vec::new();
+    }
+    let intervals: Vec<Duration> = times
+        .windows(2)
+        .map(|pair| pair[1].saturating_duration_since(pair[0]).min(MAX_INTERVAL))
+        .collect();
+    let mut segments = [(0u32, Duration::ZERO); PACE_SEGMENTS];
+    for (idx, &interval) in intervals.iter().enumerate() {
+        let segment = &mut segments[idx * PACE_SEGMENTS / intervals.len()];
+        segment.0 += 1;
+        segment.1 += interval;
+    }
+    let total: Duration = intervals.iter().sum();
+    if total.is_zero() || segments.iter().any(|(_, time)| time.is_zero()) {
+        return Vec::new();
+    }
+    let overall = intervals.len() as f64 / total.as_secs_f64();
+    segments
+        .iter()
+        .map(|&(keys, time)| {
+            let ratio = f64::from(keys) / time.as_secs_f64() / overall;
+            (ratio * 1000.0).round().min(f64::from(u16::MAX)) as u16
+        })
+        .collect()
+}
+
+/// 記録をまとめた区間ごとの速さ
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PaceProfile {
+    /// 区間ごとの速さの平均（お題全体の速さを 1 とした比）
+    pub segments: [f64; PACE_SEGMENTS],
+    /// まとめた記録の数
+    pub records: usize,
+}
+
+impl PaceProfile {
+    /// 区間ごとの速さを記録している記録をまとめる（ウォームアップは除く）
+    pub fn from_records<'a>(records: impl IntoIterator<Item = &'a TypeRecord>) -> Self {
+        let mut profile = Self::default();
+        for record in records.into_iter().filter(|r| !r.warmup && r.pace.len() == PACE_SEGMENTS) {
+            for (sum, &pace) in profile.segments.iter_mut().zip(&record.pace) {
+                *sum += f64::from(pace) / 1000.0;
+            }
+            profile.records += 1;
+        }
+        if profile.records > 0 {
+            let n = profile.records as f64;
+            profile.segments.iter_mut().for_each(|sum| *sum /= n);
+        }
+        profile
+    }
+
+    /// 一番遅い区間についての一言（"You are 12% slower in the final third"）
+    pub fn summary(&self) -> Option<String> {
+        if self.records == 0 {
+            return None;
+        }
+        let (slowest, pace) = self
+            .segments
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(idx, &pace)| (SEGMENT_LABELS[idx], pace))?;
+        Some(if 1.0 - pace < EVEN_TOLERANCE {
+            "Your pace is even from start to finish".to_string()
+        } else {
+            format!("You are {:.0}% slower in the {}", (1.0 - pace) * 100.0, slowest)
+        })
+    }
+
+    /// 区間ごとの棒（"first third   ████████████████████ 104%"）
+    pub fn bar_lines(&self) -> Vec<String> {
+        let max = self.segments.iter().copied().fold(0.0, f64::max);
+        if max <= 0.0 {
+            return Vec::new();
+        }
+        SEGMENT_LABELS
+            .iter()
+            .zip(self.segments)
+            .map(|(label, pace)| {
+                let width = (pace / max * BAR_WIDTH as f64).round() as usize;
+                format!("{:<12}  {:<bar$} {:.0}%", label, "█".repeat(width), pace * 100.0, bar = BAR_WIDTH)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 最初の打鍵から、ミリ秒で並べた打鍵間隔のとおりに打った時刻
+    fn keys_at(intervals_ms: &[u64]) -> Vec<Instant> {
+        let start = Instant::now();
+        let mut at = start;
+        let mut times = vec![start];
+        for &ms in intervals_ms {
+            at += Duration::from_millis(ms);
+            times.push(at);
+        }
+        times
+    }
+
+    fn record(pace: &[u16], warmup: bool) -> TypeRecord {
+        let mut record = TypeRecord::sample("ありがとう", 10, 3.0, 0);
+        record.pace = pace.to_vec();
+        record.warmup = warmup;
+        record
+    }
+
+    #[test]
+    fn an_even_pace_is_the_same_in_every_third() {
+        assert_eq!(segment_pace(&keys_at(&[100; 8])), [1000, 1000, 1000]);
+        assert_eq!(segment_pace(&keys_at(&[250; 30])), [1000, 1000, 1000]);
+    }
+
+    #[test]
+    fn fading_at_the_end_slows_the_final_third() {
+        let pace = segment_pace(&keys_at(&[100, 100, 100, 100, 100, 100, 200, 200, 200]));
+        // 全体は 9打 / 1.2秒 = 7.5打/秒。打ち始めと中盤は 10打/秒、終盤は 5打/秒
+        assert_eq!(pace, [1333, 1333, 667]);
+    }
+
+    #[test]
+    fn short_questions_and_instant_keystrokes_are_skipped() {
+        assert!(segment_pace(&[]).is_empty());
+        assert!(segment_pace(&keys_at(&[100; 7])).is_empty(), "8 keystrokes are too few");
+        assert_eq!(segment_pace(&keys_at(&[100; 8])).len(), PACE_SEGMENTS, "9 keystrokes are enough");
+        // 時刻が進まない区間があれば速さを決められない
+        assert!(segment_pace(&keys_at(&[0; 12])).is_empty());
+        assert!(segment_pace(&keys_at(&[100, 100, 100, 0, 0, 0, 100, 100, 100])).is_empty());
+    }
+
+    #[test]
+    fn a_long_pause_is_capped_before_it_counts_against_its_third() {
+        let capped = segment_pace(&keys_at(&[100, 100, 100, 100, 100, 100, 100, 10_000]));
+        let at_cap = segment_pace(&keys_at(&[100, 100, 100, 100, 100, 100, 100, 1_500]));
+        assert_eq!(capped, at_cap);
+        // 全体は 8打 / 2.2秒。打ち始めは 10打/秒、終盤は 2打 / 1.6秒
+        assert_eq!(capped, [2750, 2750, 344]);
+    }
+
+    #[test]
+    fn the_profile_averages_recorded_paces_without_warmups() {
+        let records = [
+            record(&[1000, 1000, 900], false),
+            record(&[1100, 1000, 860], false),
+            record(&[500, 500, 500], true),
+            record(&[], false),
+        ];
+        let profile = PaceProfile::from_records(&records);
+        assert_eq!(profile.records, 2);
+        let expected = [1.05, 1.0, 0.88];
+        for (actual, expected) in profile.segments.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-9, "{:?}", profile.segments);
+        }
+        assert_eq!(profile.summary().as_deref(), Some("You are 12% slower in the final third"));
+    }
+
+    #[test]
+    fn a_nearly_even_profile_is_summarized_as_even() {
+        let cases = [
+            (&[1000, 1000, 1000], "Your pace is even from start to finish"),
+            (&[1010, 1000, 975], "Your pace is even from start to finish"),
+            (&[1030, 960, 1010], "You are 4% slower in the middle third"),
+            (&[920, 1040, 1040], "You are 8% slower in the first third"),
+        ];
+        for (pace, expected) in cases {
+            let profile = PaceProfile::from_records(&[record(pace, false)]);
+            assert_eq!(profile.summary().as_deref(), Some(expected), "{pace:?}");
+        }
+    }
+
+    #[test]
+    fn an_empty_profile_has_no_summary_or_bars() {
+        let profile = PaceProfile::from_records(&[record(&[], false), record(&[1000, 1000, 1000], true)]);
+        assert_eq!(profile.records, 0);
+        assert_eq!(profile.summary(), None);
+        assert!(profile.bar_lines().is_empty());
+    }
+
+    #[test]
+    fn bars_are_scaled_to_the_fastest_third() {
+        let profile = PaceProfile::from_records(&[record(&[1200, 900, 600], false)]);
+        assert_eq!(
+            profile.bar_lines(),
+            [
+                format!("first third   {} 120%", "█".repeat(20)),
+                format!("middle third  {}{} 90%", "█".repeat(15), " ".repeat(5)),
+                format!("final third   {}{} 60%", "█".repeat(10), " ".repeat(10)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+// ============================================
+// src/packs.rs
+// URL から取り込むお題のパック（データフォルダの packs/ に置き、通常の出題に加える）
+// ============================================
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::questions::{BUILTIN_PACK_ID, Pack, USER_PACK_ID, set_installed_packs};
+use crate::save_data::{get_data_dir, write_atomic};
+use crate::user_questions::{UserQuestion, check_gloss, check_question, leak_questions};
+
+/// パックの取得元を記録するファイル（packs/ の中）
+const SOURCES_FILE: &str = "sources.json";
+/// 1つのパックに入れられるお題の数の上限
+const MAX_PACK_QUESTIONS: usize = 10_000;
+/// ダウンロードを待つ時間
+#[cfg(feature = "http")]
+const DOWNLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// パックのファイル（packs/<パック ID>.json）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackFile {
+    /// 表示用の名前
+    pub name: String,
+    pub questions: Vec<UserQuestion>,
+}
+
+/// パックの取得元
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackSource {
+    pub url: String,
+    /// 取り込んだ内容の SHA-256（16進数。更新で内容が変わったかを比べる）
+    pub sha256: String,
+    /// 最後に内容が変わった日時
+    pub updated: DateTime<Utc>,
+}
+
+/// パック ID → 取得元（packs/sources.json）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackSources {
+    pub packs: BTreeMap<String, PackSource>,
+}
+
+impl PackSources {
+    fn load(dir: &Path) -> io::Result<Self> {
+        match fs::read(dir.join(SOURCES_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&dir.join(SOURCES_FILE), json.as_bytes())
+    }
+}
+
+/// 入っているパック
+#[derive(Debug, Clone)]
+pub struct InstalledPack {
+    pub id: String,
+    pub file: PackFile,
+    /// URL から取り込んだパックの取得元（手で置いたパックは None）
+    pub source: Option<PackSource>,
+}
+
+/// パックを置くフォルダ
+pub fn packs_dir() -> PathBuf {
+    get_data_dir().join("packs")
+}
+
+// --------------------------------------------------
+// MARK:パックの確認
+// --------------------------------------------------
+
+/// パックのファイルを読む（名前とお題の数だけを確かめる）
+fn parse_pack(bytes: &[u8]) -> Result<PackFile, String> {
+    let pack: PackFile = serde_json::from_slice(bytes).map_err(|e| format!("not a pack file: {}", e))?;
+    if pack.name.trim().is_empty() {
+        return Err("the pack has no name".to_string());
+    }
+    if pack.questions.is_empty() {
+        return Err("the pack has no questions".to_string());
+    }
+    if pack.questions.len() > MAX_PACK_QUESTIONS {
+        return Err(format!("the pack has more than {} questions", MAX_PACK_QUESTIONS));
+    }
+    Ok(pack)
+}
+
+/// 取り込むパックの内容を確かめる（どのお題も打てて、訳の書き方が正しく、重複がないこと）
+pub fn validate_pack(bytes: &[u8], roman_map: &HashMap<&'static str, Vec<&'static str>>) -> Result<PackFile, String> {
+    let pack = parse_pack(bytes)?;
+    let mut seen = HashSet::new();
+    for (idx, question) in pack.questions.iter().enumerate() {
+        let describe = |reason: String| format!("question {} ({}): {}", idx + 1, question.japanese, reason);
+        check_question(&question.japanese, &question.hiragana, roman_map).map_err(describe)?;
+        check_gloss(&question.gloss).map_err(describe)?;
+        if !seen.insert((&question.japanese, &question.hiragana)) {
+            return Err(describe("duplicate".to_string()));
+        }
+    }
+    Ok(pack)
+}
+
+/// URL からパック ID を決める（ファイル名から .json を除き、英小文字・数字・`-`・`_` 以外は `-` にする）
+pub fn pack_id_from_url(url: &str) -> Result<String, String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| format!("not an http(s) URL: {}", url))?;
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let file = path.split_once('/').map_or("", |(_, path)| path.rsplit('/').next().unwrap_or_default());
+    let stem = file.strip_suffix(".json").unwrap_or(file);
+    let id: String = stem
+        .chars()
+        .map(|c| c.to_ascii_lowercase())
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let id = id.trim_matches('-');
+    if id.is_empty() {
+        return Err(format!("the URL does not name a pack file: {}", url));
+    }
+    if [BUILTIN_PACK_ID, USER_PACK_ID, "sources"].contains(&id) {
+        return Err(format!("\"{}\" cannot be used as a pack name", id));
+    }
+    Ok(id.to_string())
+}
+
+// --------------------------------------------------
+// MARK:入っているパック
+// --------------------------------------------------
+
+/// 入っているパックを ID 順に読み込む（読めないパックは飛ばし、その理由を返す）
+pub fn installed_packs(dir: &Path) -> (Vec<InstalledPack>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return (Vec::new(), warnings),
+        Err(e) => return (Vec::new(), vec![format!("Packs could not be read: {}", e)]),
+    };
+    let mut sources = PackSources::load(dir).unwrap_or_else(|e| {
+        warnings.push(format!("Pack sources could not be read: {}", e));
+        PackSources::default()
+    });
+
+    let mut ids: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+        .filter(|id| id != "sources")
+        .collect();
+    ids.sort();
+
+    let mut packs = Vec::new();
+    for id in ids {
+        let file = fs::read(dir.join(format!("{}.json", id)))
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| parse_pack(&bytes));
+        match file {
+            Ok(file) => packs.push(InstalledPack { source: sources.packs.remove(&id), id, file }),
+            Err(reason) => warnings.push(format!("Pack \"{}\" was not loaded: {}", id, reason)),
+        }
+    }
+    (packs, warnings)
+}
+
+/// 入っているパックを通常の出題に加える（読めないパックは飛ばし、知らせる文を返す）
+pub fn serve_installed_packs() -> Vec<String> {
+    let (packs, warnings) = installed_packs(&packs_dir());
+    let served: Vec<Pack> = packs
+        .iter()
+        .map(|pack| (&*Box::leak(pack.id.clone().into_boxed_str()), leak_questions(&pack.file.questions)))
+        .collect();
+    set_installed_packs(served);
+    warnings
+}
+
+// --------------------------------------------------
+// MARK:URL からの取り込み
+// --------------------------------------------------
+
+/// 取り込みの失敗
+#[derive(Debug)]
+pub enum FetchError {
+    /// パックの URL として使えない
+    BadUrl(String),
+    /// ダウンロードできなかった（つながらない・HTTP のエラーなど）
+    Network(String),
+    /// 内容が SHA-256 と合わない（何も入れない）
+    Checksum { expected: String, actual: String },
+    /// パックの内容や SHA-256 の書き方が正しくない（何も入れない）
+    Invalid(String),
+    /// パックを保存できなかった
+    Io(io::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::BadUrl(reason) => write!(f, "bad URL: {}", reason),
+            FetchError::Network(reason) => write!(f, "network error: {}", reason),
+            FetchError::Checksum { expected, actual } => {
+                write!(f, "checksum mismatch: expected sha256 {}, got {}", expected, actual)
+            }
+            FetchError::Invalid(reason) => write!(f, "invalid pack: {}", reason),
+            FetchError::Io(e) => write!(f, "could not install the pack: {}", e),
+        }
+    }
+}
+
+/// 取り込んだパック
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fetched {
+    pub id: String,
+    pub name: String,
+    pub questions: usize,
+    /// 入っていたパックから内容が変わったか（新しく入れたときも true）
+    pub changed: bool,
+}
+
+/// URL の中身をダウンロードする関数（見つからなければ Ok(None)、それ以外の失敗は理由を返す）
+pub trait Download {
+    fn download(&self, url: &str) -> Result<Option<Vec<u8>>, String>;
+}
+
+impl<F: Fn(&str) -> Result<Option<Vec<u8>>, String>> Download for F {
+    fn download(&self, url: &str) -> Result<Option<Vec<u8>>, String> {
+        self(url)
+    }
+}
+
+/// HTTP でダウンロードする
+pub struct HttpDownload;
+
+impl Download for HttpDownload {
+    #[cfg(feature = "http")]
+    fn download(&self, url: &str) -> Result<Option<Vec<u8>>, String> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(DOWNLOAD_TIMEOUT)
+            .user_agent(concat!("typewiz/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let response = client.get(url).send().map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(|e| e.to_string())?;
+        response.bytes().map(|bytes| Some(bytes.to_vec())).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn download(&self, _url: &str) -> Result<Option<Vec<u8>>, String> {
+        Err("this build has no HTTP support (build with the `http` feature)".to_string())
+    }
+}
+
+/// 内容の SHA-256（16進数の小文字）
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 16進数 64 桁の SHA-256 を小文字にそろえる
+fn normalize_sha256(hex: &str) -> Result<String, String> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("not a sha256 checksum: {}", hex));
+    }
+    Ok(hex.to_ascii_lowercase())
+}
+
+/// `.sha256` ファイルから SHA-256 を読む（`sha256sum` の「<16進数>  <ファイル名>」の形にも対応）
+fn parse_sidecar(bytes: &[u8]) -> Result<String, String> {
+    let text = String::from_utf8_lossy(bytes);
+    let hex = text.split_whitespace().next().ok_or("the .sha256 file is empty")?;
+    normalize_sha256(hex).map_err(|reason| format!(".sha256 file: {}", reason))
+}
+
+/// URL からパックをダウンロードし、確かめてから `dir` に入れて取得元を記録する
+/// SHA-256 は `sha256` で渡されたものを、なければ `<URL>.sha256` を使う（どちらもなければ確かめない）
+/// 確かめられなかったときは何も書き換えない
+pub fn fetch(
+    dir: &Path,
+    url: &str,
+    sha256: Option<&str>,
+    roman_map: &HashMap<&'static str, Vec<&'static str>>,
+    downloader: &impl Download,
+) -> Result<Fetched, FetchError> {
+    let id = pack_id_from_url(url).map_err(FetchError::BadUrl)?;
+    let mut sources = PackSources::load(dir).map_err(FetchError::Io)?;
+    if let Some(source) = sources.packs.get(&id)
+        && source.url != url
+    {
+        return Err(FetchError::Invalid(format!("a pack named \"{}\" was already fetched from {}", id, source.url)));
+    }
+
+    let expected = match sha256 {
+        Some(hex) => Some(normalize_sha256(hex).map_err(FetchError::Invalid)?),
+        None => match downloader.download(&format!("{}.sha256", url)).map_err(FetchError::Network)? {
+            Some(bytes) => Some(parse_sidecar(&bytes).map_err(FetchError::Invalid)?),
+            None => None,
+        },
+    };
+    let bytes = downloader
+        .download(url)
+        .map_err(FetchError::Network)?
+        .ok_or_else(|| FetchError::Network(format!("{} was not found (HTTP 404)", url)))?;
+    let actual = sha256_hex(&bytes);
+    if let Some(expected) = expected
+        && expected != actual
+    {
+        return Err(FetchError::Checksum { expected, actual });
+    }
+    let pack = validate_pack(&bytes, roman_map).map_err(FetchError::Invalid)?;
+
+    let changed = sources.packs.get(&id).is_none_or(|source| source.sha256 != actual);
+    fs::create_dir_all(dir).map_err(FetchError::Io)?;
+    write_atomic(&dir.join(format!("{}.json", id)), &bytes).map_err(FetchError::Io)?;
+    let updated = match sources.packs.get(&id) {
+        Some(source) if !changed => source.updated,
+        _ => Utc::now(),
+    };
+    sources.packs.insert(id.clone(), PackSource { url: url.to_string(), sha256: actual, updated });
+    sources.save(dir).map_err(FetchError::Io)?;
+
+    Ok(Fetched { id, name: pack.name, questions: pack.questions.len(), changed })
+}
+
+/// URL から取り込んだパックをすべて取り込み直す（パック ID と結果。ID 順）
+/// 取り込み直すときは `<URL>.sha256` だけで確かめる（取り込んだときに渡した SHA-256 は古い内容のもの）
+pub fn update_all(
+    dir: &Path,
+    roman_map: &HashMap<&'static str, Vec<&'static str>>,
+    downloader: &impl Download,
+) -> io::Result<Vec<(String, Result<Fetched, FetchError>)>> {
+    let sources = PackSources::load(dir)?;
+    Ok(sources
+        .packs
+        .into_iter()
+        .map(|(id, source)| (id, fetch(dir, &source.url, None, roman_map, downloader)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::create_roman_mapping;
+    use std::cell::RefCell;
+
+    const URL: &str = "https://club.example/packs/N4 Verbs.json";
+
+    /// テストごとの一時フォルダ
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("typewiz-packs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn pack_json(questions: &[(&str, &str)]) -> Vec<u8> {
+        let questions: Vec<String> = questions
+            .iter()
+            .map(|(japanese, hiragana)| format!(r#"{{"japanese":"{}","hiragana":"{}","tags":["N4"]}}"#, japanese, hiragana))
+            .collect();
+        format!(r#"{{"name":"N4 verbs","questions":[{}]}}"#, questions.join(",")).into_bytes()
+    }
+
+    /// URL → 中身の表からダウンロードする（表にない URL は 404）。ダウンロードした URL を覚える
+    struct FakeServer {
+        files: RefCell<HashMap<String, Vec<u8>>>,
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl FakeServer {
+        fn new(files: &[(&str, Vec<u8>)]) -> Self {
+            let files = files.iter().map(|(url, body)| (url.to_string(), body.clone())).collect();
+            Self { files: RefCell::new(files), requests: RefCell::new(Vec::new()) }
+        }
+
+        fn put(&self, url: &str, body: Vec<u8>) {
+            self.files.borrow_mut().insert(url.to_string(), body);
+        }
+    }
+
+    impl Download for FakeServer {
+        fn download(&self, url: &str) -> Result<Option<Vec<u8>>, String> {
+            self.requests.borrow_mut().push(url.to_string());
+            Ok(self.files.borrow().get(url).cloned())
+        }
+    }
+
+    #[test]
+    fn pack_ids_come_from_the_file_name() {
+        assert_eq!(pack_id_from_url(URL).unwrap(), "n4-verbs");
+        assert_eq!(pack_id_from_url("http://example.com/a/food_words.json?v=2").unwrap(), "food_words");
+        assert!(pack_id_from_url("ftp://example.com/pack.json").is_err());
+        assert!(pack_id_from_url("https://example.com/").is_err());
+        assert!(pack_id_from_url("https://example.com/user.json").is_err());
+    }
+
+    #[test]
+    fn a_fetched_pack_is_installed_with_its_source() {
+        let dir = temp_dir("install");
+        let body = pack_json(&[("食べる", "たべる"), ("飲む", "のむ")]);
+        let server = FakeServer::new(&[(URL, body.clone())]);
+
+        let fetched = fetch(&dir, URL, None, &create_roman_mapping(), &server).unwrap();
+        assert_eq!(
+            fetched,
+            Fetched { id: "n4-verbs".to_string(), name: "N4 verbs".to_string(), questions: 2, changed: true }
+        );
+        assert_eq!(*server.requests.borrow(), [format!("{}.sha256", URL), URL.to_string()]);
+
+        let (packs, warnings) = installed_packs(&dir);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(packs.len(), 1);
+        assert_eq!(packs[0].file.questions[1].hiragana, "のむ");
+        let source = packs[0].source.as_ref().unwrap();
+        assert_eq!(source.url, URL);
+        assert_eq!(source.sha256, sha256_hex(&body));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_given_or_sidecar_checksum_must_match() {
+        let dir = temp_dir("checksum");
+        let body = pack_json(&[("食べる", "たべる")]);
+        let good = sha256_hex(&body);
+        let bad = "0".repeat(64);
+
+        let server = FakeServer::new(&[(URL, body.clone())]);
+        let result = fetch(&dir, URL, Some(&bad), &create_roman_mapping(), &server);
+        assert!(matches!(result, Err(FetchError::Checksum { .. })), "{:?}", result);
+        assert!(!dir.exists(), "nothing may be installed on a checksum mismatch");
+
+        let sidecar = format!("{}  N4 Verbs.json\n", bad);
+        let server = FakeServer::new(&[(URL, body.clone()), (&format!("{}.sha256", URL), sidecar.into_bytes())]);
+        let result = fetch(&dir, URL, None, &create_roman_mapping(), &server);
+        assert!(matches!(result, Err(FetchError::Checksum { .. })), "{:?}", result);
+        assert!(!dir.exists());
+
+        let server = FakeServer::new(&[(URL, body)]);
+        assert!(fetch(&dir, URL, Some(&good.to_uppercase()), &create_roman_mapping(), &server).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn network_and_validation_errors_are_told_apart() {
+        let dir = temp_dir("errors");
+        let map = create_roman_mapping();
+
+        let offline = |_: &str| -> Result<Option<Vec<u8>>, String> { Err("connection refused".to_string()) };
+        assert!(matches!(fetch(&dir, URL, None, &map, &offline), Err(FetchError::Network(_))));
+        let missing = FakeServer::new(&[]);
+        assert!(matches!(fetch(&dir, URL, None, &map, &missing), Err(FetchError::Network(_))));
+
+        for body in [b"not json".to_vec(), pack_json(&[]), pack_json(&[("猫", "ねこ"), ("猫", "ねこ")]), pack_json(&[("猫", "ねこ🙂")])] {
+            let server = FakeServer::new(&[(URL, body)]);
+            let result = fetch(&dir, URL, None, &map, &server);
+            assert!(matches!(result, Err(FetchError::Invalid(_))), "{:?}", result);
+        }
+        assert!(matches!(fetch(&dir, "file:///pack.json", None, &map, &missing), Err(FetchError::BadUrl(_))));
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn update_refetches_url_packs_and_reports_changes() {
+        let dir = temp_dir("update");
+        let map = create_roman_mapping();
+        let other = "https://club.example/packs/food.json";
+        let server = FakeServer::new(&[(URL, pack_json(&[("食べる", "たべる")])), (other, pack_json(&[("寿司", "すし")]))]);
+        fetch(&dir, URL, None, &map, &server).unwrap();
+        fetch(&dir, other, None, &map, &server).unwrap();
+        let food_updated = installed_packs(&dir).0[0].source.clone().unwrap().updated;
+
+        server.put(URL, pack_json(&[("食べる", "たべる"), ("飲む", "のむ")]));
+        let results = update_all(&dir, &map, &server).unwrap();
+        let changed: Vec<(&str, bool)> =
+            results.iter().map(|(id, result)| (id.as_str(), result.as_ref().unwrap().changed)).collect();
+        assert_eq!(changed, [("food", false), ("n4-verbs", true)]);
+
+        let (packs, _) = installed_packs(&dir);
+        assert_eq!(packs[1].file.questions.len(), 2);
+        assert_eq!(packs[0].source.as_ref().unwrap().updated, food_updated);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_failed_update_keeps_the_installed_pack() {
+        let dir = temp_dir("failed-update");
+        let map = create_roman_mapping();
+        let body = pack_json(&[("食べる", "たべる")]);
+        let server = FakeServer::new(&[(URL, body.clone())]);
+        fetch(&dir, URL, None, &map, &server).unwrap();
+
+        server.put(URL, b"<html>maintenance</html>".to_vec());
+        let results = update_all(&dir, &map, &server).unwrap();
+        assert!(matches!(results[0].1, Err(FetchError::Invalid(_))));
+        assert_eq!(fs::read(dir.join("n4-verbs.json")).unwrap(), body);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,236 @@
+// ============================================
+// src/persist.rs
+// 未保存の変更の追跡と、異常終了時の復旧ファイル・セッションのチェックポイント
+// ============================================
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::ops::Deref;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::questions::QuestionId;
+use crate::save_data::{PlayerData, get_data_dir};
+use crate::settings::Settings;
+use crate::stats::SessionStats;
+
+/// ファイルに保存できるデータ
+pub trait Persist {
+    fn persist(&self);
+}
+
+impl Persist for Settings {
+    fn persist(&self) {
+        self.save();
+    }
+}
+
+// --------------------------------------------------
+// MARK:変更の追跡
+// --------------------------------------------------
+
+/// 変更があったかを記録する包み型
+/// 読み取りは Deref で、書き換えは必ず `edit()` を通す
+pub struct Tracked<T> {
+    value: T,
+    /// 最初に保存されていない変更をした時刻
+    dirty_since: Option<Instant>,
+    /// `edit()` のたびに増える番号（復旧用のコピーを作り直すかの判断に使う）
+    generation: u64,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(value: T) -> Self {
+        Self { value, dirty_since: None, generation: 0 }
+    }
+
+    /// 書き換え用の参照（未保存の変更ありとして記録する）
+    pub fn edit(&mut self) -> &mut T {
+        self.dirty_since.get_or_insert_with(Instant::now);
+        self.generation += 1;
+        &mut self.value
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_since.is_some()
+    }
+
+    pub fn dirty_since(&self) -> Option<Instant> {
+        self.dirty_since
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// 呼び出し側で保存し終えたことを記録する（`Persist` を持たない、保存先を別に持つデータ用）
+    pub fn mark_saved(&mut self) {
+        self.dirty_since = None;
+    }
+}
+
+impl<T: Persist> Tracked<T> {
+    /// 保存して未保存の変更をなくす
+    pub fn save(&mut self) {
+        self.value.persist();
+        self.mark_saved();
+    }
+
+    /// 未保存の変更があるときだけ保存する
+    pub fn save_if_dirty(&mut self) {
+        if self.is_dirty() {
+            self.save();
+        }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+// --------------------------------------------------
+// MARK:復旧ファイル
+// --------------------------------------------------
+
+/// 異常終了したときに書き出す未保存のデータ（変更のなかったものは None）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recovery {
+    pub player_data: Option<PlayerData>,
+    pub settings: Option<Settings>,
+}
+
+/// パニック時に書き出す最新の未保存データ
+static SNAPSHOT: Mutex<Option<Recovery>> = Mutex::new(None);
+
+// MARK:復旧ファイルのパスを取得する関数
+pub fn get_recovery_file_path() -> PathBuf {
+    get_data_dir().join("recovery.json")
+}
+
+/// パニック時に書き出すデータを差し替える（None なら何も書き出さない）
+pub fn set_snapshot(recovery: Option<Recovery>) {
+    if let Ok(mut snapshot) = SNAPSHOT.lock() {
+        *snapshot = recovery;
+    }
+}
+
+/// パニック時に未保存のデータを復旧ファイルへ書き出すフックを登録する
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // パニック中にロックを待つと止まる恐れがあるので、取れなければ諦める
+        if let Ok(snapshot) = SNAPSHOT.try_lock()
+            && let Some(recovery) = snapshot.as_ref()
+            && let Ok(json) = serde_json::to_string(recovery)
+        {
+            let _ = fs::write(get_recovery_file_path(), json);
+        }
+        default_hook(info);
+    }));
+}
+
+/// 前回の復旧ファイルを読み込む（なければ None）
+pub fn load_recovery() -> Option<Recovery> {
+    let bytes = fs::read(get_recovery_file_path()).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// 復旧ファイルを削除する
+pub fn remove_recovery() {
+    let _ = fs::remove_file(get_recovery_file_path());
+}
+
+// --------------------------------------------------
+// MARK:セッションのチェックポイント
+// --------------------------------------------------
+
+/// 長いセッションの途中経過（強制終了されても、そのお題の最初から続けられるようにする）
+/// 打ち終えたお題は履歴にすぐ保存されるので、ここにはセッション単位の集計と途中のお題だけを書く
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub saved_at: DateTime<Utc>,
+    /// ここまでに打ち終えたお題の成績
+    pub session: SessionStats,
+    /// 打っている途中のお題（文章モードでは None）
+    pub question_id: Option<QuestionId>,
+    pub sentence_mode: bool,
+    /// 途中のお題の打ち終えた単位の数と全体の数
+    pub typed_units: usize,
+    pub total_units: usize,
+}
+
+// MARK:チェックポイントのパスを取得する関数
+pub fn get_checkpoint_file_path() -> PathBuf {
+    get_data_dir().join("checkpoint.json")
+}
+
+enum CheckpointMessage {
+    Write(Box<Checkpoint>),
+    /// 正常に終了したので削除する
+    Clear,
+}
+
+/// チェックポイントを別スレッドで書き出す（入力の処理を待たせない）
+#[derive(Default)]
+pub struct CheckpointWriter {
+    worker: Option<(Sender<CheckpointMessage>, JoinHandle<()>)>,
+}
+
+impl CheckpointWriter {
+    /// 書き出しを頼む（最初に呼ばれたときにスレッドを立てる）
+    pub fn write(&mut self, checkpoint: Checkpoint) {
+        let (sender, _) = self.worker.get_or_insert_with(spawn_checkpoint_worker);
+        let _ = sender.send(CheckpointMessage::Write(Box::new(checkpoint)));
+    }
+
+    /// チェックポイントを削除し、書き出しが終わるまで待つ（終了時に呼ぶ）
+    pub fn finish(&mut self) {
+        match self.worker.take() {
+            Some((sender, handle)) => {
+                let _ = sender.send(CheckpointMessage::Clear);
+                drop(sender);
+                let _ = handle.join();
+            }
+            None => remove_checkpoint(),
+        }
+    }
+}
+
+/// 届いた順にチェックポイントを書き出す / 削除するスレッドを立てる
+fn spawn_checkpoint_worker() -> (Sender<CheckpointMessage>, JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel::<CheckpointMessage>();
+    let handle = thread::spawn(move || {
+        for message in receiver {
+            match message {
+                CheckpointMessage::Write(checkpoint) => {
+                    if let Ok(json) = serde_json::to_string(&checkpoint) {
+                        let _ = fs::write(get_checkpoint_file_path(), json);
+                    }
+                }
+                CheckpointMessage::Clear => remove_checkpoint(),
+            }
+        }
+    });
+    (sender, handle)
+}
+
+/// 前回の正常に終わらなかったセッションのチェックポイントを読み込む（なければ None）
+pub fn load_checkpoint() -> Option<Checkpoint> {
+    let bytes = fs::read(get_checkpoint_file_path()).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// チェックポイントを削除する
+pub fn remove_checkpoint() {
+    let _ = fs::remove_file(get_checkpoint_file_path());
+}
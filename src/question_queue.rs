@@ -0,0 +1,565 @@
+// ============================================
+// src/question_queue.rs
+// 出題順の管理と、セッション中の難易度調整
+// ============================================
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand::seq::SliceRandom;
+
+use crate::questions::{Pack, Question, QuestionId, TIER_COUNT};
+
+/// 難易度を判断するために見る直近の問題数
+const WINDOW: usize = 3;
+/// この正確率以上が続き、平均 CPS も上回っていたら難易度を上げる
+const STEP_UP_ACCURACY: f64 = 98.0;
+/// 直近2問の正確率がこれを下回ったら難易度を下げる
+const STEP_DOWN_ACCURACY: f64 = 90.0;
+/// 次のお題を選ぶとき、シャッフル順で先頭から何問を見比べて出題回数の少ないものを選ぶか
+const BOOST_LOOKAHEAD: usize = 3;
+/// 設定で選べる出題の保証（お題の数の何倍の出題のうちに全問を出すか。0 で保証しない）
+pub const ROTATION_FACTORS: [u32; 4] = [0, 2, 3, 5];
+
+// --------------------------------------------------
+// MARK:出題キュー
+// --------------------------------------------------
+
+/// シャッフルしたお題を順番に出題する
+/// いくつかのパックのお題をまとめて出題できる（番号はパックを並べた順に通しで振る）
+pub struct QuestionQueue {
+    questions: Vec<&'static Question>,
+    /// お題（`questions` 内の番号）ごとの ID
+    ids: Vec<QuestionId>,
+    /// 出題順（`questions` 内の番号）
+    pool: Vec<usize>,
+    current: usize,
+    /// 出題しないお題（ブラックリスト）
+    excluded: HashSet<QuestionId>,
+    /// これから出題するウォームアップのお題（`questions` 内の番号）
+    prelude: VecDeque<usize>,
+    /// 出題中のウォームアップのお題
+    warmup: Option<usize>,
+    /// 出題できるお題の数の何倍の出題のうちに、どのお題も必ず1回は出すか（0 で保証しない）
+    rotation_factor: u32,
+    /// これまでに出題した回数（最初のお題を 0 回目とする）
+    draws: i64,
+    /// お題（`questions` 内の番号）ごとの、最後に出題した回
+    last_served: HashMap<usize, i64>,
+    /// お題ごとの累計の出題回数（セーブデータから引き継ぐ）
+    serve_counts: HashMap<usize, u32>,
+}
+
+impl QuestionQueue {
+    /// いくつかのパックのお題をまとめてシャッフルして出題するキューを作る（お題は1つ以上あること）
+    pub fn from_packs(packs: &[Pack]) -> Self {
+        let mut queue = Self::with_packs(packs, Vec::new());
+        queue.pool = (0..queue.questions.len()).collect();
+        queue.pool.shuffle(&mut rand::rng());
+        queue.reset_served();
+        queue
+    }
+
+    /// `keep` に合うお題だけをシャッフルして出題するキューを作る（合うお題がなければ None）
+    pub fn subset(packs: &[Pack], keep: impl Fn(QuestionId) -> bool) -> Option<Self> {
+        let mut queue = Self::with_packs(packs, Vec::new());
+        queue.pool = (0..queue.questions.len()).filter(|&idx| keep(queue.ids[idx])).collect();
+        if queue.pool.is_empty() {
+            return None;
+        }
+        queue.pool.shuffle(&mut rand::rng());
+        queue.reset_served();
+        Some(queue)
+    }
+
+    /// 出題順（`questions` 内の番号）を指定して作る（乱数を使わない）
+    pub fn with_order(pack_id: &'static str, questions: &'static [Question], pool: Vec<usize>) -> Self {
+        let mut queue = Self::with_packs(&[(pack_id, questions)], pool);
+        queue.reset_served();
+        queue
+    }
+
+    fn with_packs(packs: &[Pack], pool: Vec<usize>) -> Self {
+        let (questions, ids) = packs
+            .iter()
+            .flat_map(|&(pack_id, questions)| {
+                questions.iter().enumerate().map(move |(idx, q)| (q, QuestionId::new(pack_id, idx)))
+            })
+            .unzip();
+        Self {
+            questions,
+            ids,
+            pool,
+            current: 0,
+            excluded: HashSet::new(),
+            prelude: VecDeque::new(),
+            warmup: None,
+            rotation_factor: 0,
+            draws: 0,
+            last_served: HashMap::new(),
+            serve_counts: HashMap::new(),
+        }
+    }
+
+    /// 最初のお題を 0 回目の出題として数え直す
+    fn reset_served(&mut self) {
+        self.last_served = self.pool.first().map(|&idx| (idx, 0)).into_iter().collect();
+    }
+
+    /// どのお題も、出題できるお題の数の `factor` 倍の出題のうちに必ず1回は出すようにする（0 で保証しない）
+    /// `counts` は累計の出題回数で、少ないお題を少しだけ優先して選ぶのに使う
+    pub fn set_rotation(&mut self, factor: u32, counts: &HashMap<QuestionId, u32>) {
+        self.rotation_factor = factor;
+        self.serve_counts = (0..self.questions.len())
+            .filter_map(|idx| counts.get(&self.ids[idx]).map(|&count| (idx, count)))
+            .collect();
+    }
+
+    /// セッションの最初に出題するお題を設定する（日本語またはひらがなで指定）
+    /// 見つからないお題と出題しないお題は飛ばし、その理由を返す
+    pub fn set_prelude(&mut self, entries: &[String]) -> Vec<String> {
+        let mut skipped = Vec::new();
+        self.prelude.clear();
+        for entry in entries {
+            let found = self
+                .questions
+                .iter()
+                .position(|q| q.japanese == entry.as_str() || q.hiragana == entry.as_str());
+            match found {
+                Some(idx) if self.excluded.contains(&self.ids[idx]) => {
+                    skipped.push(format!("\"{}\" is blacklisted", entry));
+                }
+                Some(idx) => self.prelude.push_back(idx),
+                None => skipped.push(format!("\"{}\" is not in the question list", entry)),
+            }
+        }
+        self.warmup = self.prelude.pop_front();
+        skipped
+    }
+
+    /// ウォームアップの残りを取り消す
+    pub fn clear_prelude(&mut self) {
+        self.prelude.clear();
+        self.warmup = None;
+    }
+
+    /// 現在のお題がウォームアップか
+    pub fn is_warmup(&self) -> bool {
+        self.warmup.is_some()
+    }
+
+    /// 現在のお題（`questions` 内の番号）
+    fn current_index(&self) -> usize {
+        self.warmup.unwrap_or(self.pool[self.current])
+    }
+
+    /// 出題しないお題を設定する
+    pub fn set_excluded(&mut self, ids: impl IntoIterator<Item = QuestionId>) {
+        self.excluded = ids.into_iter().collect();
+    }
+
+    /// 出題しないお題か
+    pub fn is_excluded(&self, id: QuestionId) -> bool {
+        self.excluded.contains(&id)
+    }
+
+    /// `pool` の位置 `idx` のお題が出題対象か
+    fn is_allowed(&self, idx: usize) -> bool {
+        !self.excluded.contains(&self.ids[self.pool[idx]])
+    }
+
+    /// 現在のお題
+    pub fn current(&self) -> &'static Question {
+        self.questions[self.current_index()]
+    }
+
+    /// 現在のお題の ID
+    pub fn current_id(&self) -> QuestionId {
+        self.ids[self.current_index()]
+    }
+
+    /// 指定したお題に移動する。見つからなければ false
+    pub fn jump_to(&mut self, id: QuestionId) -> bool {
+        match self.pool.iter().position(|&idx| self.ids[idx] == id) {
+            Some(idx) => {
+                self.clear_prelude();
+                self.current = idx;
+                self.last_served.insert(self.pool[idx], self.draws);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 次のお題へ進む
+    /// `tier` が指定されていれば、シャッフル順でその難易度の次のお題を選ぶ（なければ順番通り）
+    /// 出題しないお題は飛ばす。すべてが対象外のときだけ無視して進み、true を返す
+    /// ウォームアップ中は残りのウォームアップを順に出し、終わったら割り込まれていたお題に戻る
+    /// 出題の保証が有効なら、期限に間に合わなくなるお題があるときは難易度よりそちらを優先する
+    pub fn advance(&mut self, tier: Option<i32>) -> bool {
+        if self.warmup.is_some() {
+            self.warmup = self.prelude.pop_front();
+            return false;
+        }
+
+        let next = self
+            .overdue()
+            .or_else(|| tier.and_then(|tier| self.pick(|q| q.tier() == tier)))
+            .or_else(|| self.pick(|_| true))
+            .or_else(|| self.is_allowed(self.current).then_some(self.current));
+        let ignored_exclusions = next.is_none();
+        self.current = next.unwrap_or((self.current + 1) % self.pool.len());
+        self.draws += 1;
+        self.last_served.insert(self.pool[self.current], self.draws);
+        *self.serve_counts.entry(self.pool[self.current]).or_default() += 1;
+        ignored_exclusions
+    }
+
+    /// シャッフル順で現在の次から `matches` に合う出題対象を探し、先頭の数問のうち出題回数の少ないものを選ぶ
+    /// 一度飛ばしたお題もすぐ後に回ってくるので、順番はあまり崩れない。現在のお題は続けて出さないので選ばない
+    fn pick(&self, matches: impl Fn(&Question) -> bool) -> Option<usize> {
+        let len = self.pool.len();
+        (1..len)
+            .map(|step| (self.current + step) % len)
+            .filter(|&idx| self.is_allowed(idx) && matches(self.questions[self.pool[idx]]))
+            .take(BOOST_LOOKAHEAD)
+            .min_by_key(|&idx| self.serve_counts.get(&self.pool[idx]).copied().unwrap_or(0))
+    }
+
+    /// 今出さないと、どれかのお題が期限（最後に出てから K 回以内）に間に合わなくなるなら、期限のいちばん近いお題
+    /// 期限が d 以下のお題の数が、d までに残っている出題の回数に達していたら余裕がない
+    fn overdue(&self) -> Option<usize> {
+        let eligible: Vec<usize> = (0..self.pool.len()).filter(|&idx| self.is_allowed(idx)).collect();
+        if self.rotation_factor == 0 || eligible.len() < 2 {
+            return None;
+        }
+        let window = i64::from(self.rotation_factor) * eligible.len() as i64;
+        let draw = self.draws + 1;
+        // 直前のお題は続けて出さない（期限がいちばん遠いので、外しても他のお題は間に合う）
+        let mut deadlines: Vec<(i64, usize)> = eligible
+            .into_iter()
+            .filter(|&idx| idx != self.current)
+            .map(|idx| (self.last_served.get(&self.pool[idx]).copied().unwrap_or(-1) + window, idx))
+            .collect();
+        deadlines.sort_unstable();
+        let tight = deadlines
+            .iter()
+            .enumerate()
+            .any(|(count, &(deadline, _))| count as i64 + 1 > deadline - draw);
+        tight.then(|| deadlines[0].1)
+    }
+}
+
+// --------------------------------------------------
+// MARK:難易度コントローラー
+// --------------------------------------------------
+
+/// 難易度の変化
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TierShift {
+    Up,
+    Down,
+}
+
+/// レベルから決まる基本の難易度
+pub fn base_tier_for_level(level: u32) -> i32 {
+    match level {
+        0..=4 => 0,
+        5..=14 => 1,
+        15..=29 => 2,
+        _ => 3,
+    }
+}
+
+/// 直近の成績から、基本の難易度に対する補正値を決める
+/// 変化した直後は成績の記録をリセットし、新しい成績が揃うまで再び変化しない（ヒステリシス）
+#[derive(Default)]
+pub struct DifficultyController {
+    offset: i32,
+    /// 直近の (正確率, CPS)
+    recent: VecDeque<(f64, f64)>,
+}
+
+impl DifficultyController {
+    /// 補正後の難易度
+    pub fn target_tier(&self, base_tier: i32) -> i32 {
+        (base_tier + self.offset).clamp(0, TIER_COUNT - 1)
+    }
+
+    /// 1問分の成績を記録し、難易度が変化したらその向きを返す
+    pub fn record(&mut self, accuracy: f64, cps: f64, average_cps: f64, base_tier: i32) -> Option<TierShift> {
+        self.recent.push_back((accuracy, cps));
+        if self.recent.len() > WINDOW {
+            self.recent.pop_front();
+        }
+
+        let tier = self.target_tier(base_tier);
+
+        let strong = self.recent.len() == WINDOW
+            && self
+                .recent
+                .iter()
+                .all(|&(acc, c)| acc >= STEP_UP_ACCURACY && c > average_cps);
+        if strong && tier < TIER_COUNT - 1 {
+            self.offset = tier + 1 - base_tier;
+            self.recent.clear();
+            return Some(TierShift::Up);
+        }
+
+        let struggling = self.recent.len() >= 2
+            && self
+                .recent
+                .iter()
+                .rev()
+                .take(2)
+                .all(|&(acc, _)| acc < STEP_DOWN_ACCURACY);
+        if struggling && tier > 0 {
+            self.offset = tier - 1 - base_tier;
+            self.recent.clear();
+            return Some(TierShift::Down);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static BUILTIN: [Question; 2] =
+        [Question { japanese: "猫", hiragana: "ねこ" }, Question { japanese: "犬", hiragana: "いぬ" }];
+    static USER: [Question; 1] = [Question { japanese: "鳥", hiragana: "とり" }];
+    const PACKS: [Pack; 2] = [("builtin", &BUILTIN), ("user", &USER)];
+
+    /// キューが一巡するあいだに出すお題の ID
+    fn served_ids(queue: &mut QuestionQueue) -> HashSet<QuestionId> {
+        let mut ids = HashSet::from([queue.current_id()]);
+        for _ in 1..queue.pool.len() {
+            queue.advance(None);
+            ids.insert(queue.current_id());
+        }
+        ids
+    }
+
+    #[test]
+    fn questions_from_every_pack_are_served() {
+        let mut queue = QuestionQueue::from_packs(&PACKS);
+        let expected = HashSet::from([QuestionId::new("builtin", 0), QuestionId::new("builtin", 1), QuestionId::new("user", 0)]);
+        assert_eq!(served_ids(&mut queue), expected);
+    }
+
+    #[test]
+    fn user_questions_keep_their_own_ids() {
+        let mut queue = QuestionQueue::from_packs(&PACKS);
+        assert!(queue.jump_to(QuestionId::new("user", 0)));
+        assert_eq!(queue.current().japanese, "鳥");
+        assert!(!queue.jump_to(QuestionId::new("user", 1)));
+    }
+
+    #[test]
+    fn subset_keeps_matching_questions_across_packs() {
+        let keep = [QuestionId::new("builtin", 1), QuestionId::new("user", 0)];
+        let mut queue = QuestionQueue::subset(&PACKS, |id| keep.contains(&id)).unwrap();
+        assert_eq!(served_ids(&mut queue), HashSet::from(keep));
+    }
+
+    #[test]
+    fn subset_without_matches_is_none() {
+        assert!(QuestionQueue::subset(&PACKS, |_| false).is_none());
+    }
+
+    #[test]
+    fn excluded_user_questions_are_skipped() {
+        let mut queue = QuestionQueue::from_packs(&PACKS);
+        queue.set_excluded([QuestionId::new("user", 0)]);
+        for _ in 0..6 {
+            queue.advance(None);
+            assert_ne!(queue.current_id(), QuestionId::new("user", 0));
+        }
+    }
+
+    static QUESTIONS: [Question; 3] = [
+        Question { japanese: "猫", hiragana: "ねこ" },
+        Question { japanese: "犬", hiragana: "いぬ" },
+        Question { japanese: "鳥", hiragana: "とり" },
+    ];
+    const PACK: &str = "test";
+
+    #[test]
+    fn excluded_questions_are_skipped_at_every_tier() {
+        let mut queue = QuestionQueue::from_packs(&[(PACK, &QUESTIONS)]);
+        queue.set_excluded([QuestionId::new(PACK, 0)]);
+        let tier = QUESTIONS[0].tier();
+        for _ in 0..6 {
+            assert!(!queue.advance(Some(tier)));
+            assert_ne!(queue.current_id(), QuestionId::new(PACK, 0));
+        }
+    }
+
+    #[test]
+    fn excluding_every_question_ignores_the_exclusions() {
+        let mut queue = QuestionQueue::from_packs(&[(PACK, &QUESTIONS)]);
+        queue.set_excluded((0..QUESTIONS.len()).map(|idx| QuestionId::new(PACK, idx)));
+        let mut ids = HashSet::new();
+        for _ in 0..3 {
+            // 出題できるお題がないと知らせつつ、順番どおりに出し続ける
+            assert!(queue.advance(None));
+            ids.insert(queue.current_id());
+        }
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn blacklisted_warmup_entries_are_skipped() {
+        let mut queue = QuestionQueue::from_packs(&[(PACK, &QUESTIONS)]);
+        queue.set_excluded([QuestionId::new(PACK, 0)]);
+        let skipped = queue.set_prelude(&["猫".to_string(), "とり".to_string(), "象".to_string()]);
+        assert_eq!(skipped, ["\"猫\" is blacklisted", "\"象\" is not in the question list"]);
+        assert!(queue.is_warmup());
+        assert_eq!(queue.current().japanese, "鳥");
+    }
+
+    const STRONG: (f64, f64) = (99.0, 3.0);
+    const STEADY: (f64, f64) = (95.0, 2.5);
+    const SLOPPY: (f64, f64) = (80.0, 1.5);
+    const AVERAGE_CPS: f64 = 2.0;
+
+    /// 成績を1問ずつ記録したあとの難易度の推移
+    fn trajectory(controller: &mut DifficultyController, results: &[(f64, f64)], base_tier: i32) -> Vec<i32> {
+        results
+            .iter()
+            .map(|&(accuracy, cps)| {
+                controller.record(accuracy, cps, AVERAGE_CPS, base_tier);
+                controller.target_tier(base_tier)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn three_strong_results_step_up_once() {
+        let mut controller = DifficultyController::default();
+        let shifts: Vec<_> = [STRONG; 3].iter().map(|&(a, c)| controller.record(a, c, AVERAGE_CPS, 0)).collect();
+        assert_eq!(shifts, [None, None, Some(TierShift::Up)]);
+        assert_eq!(controller.target_tier(0), 1);
+    }
+
+    #[test]
+    fn a_shift_needs_a_fresh_window_before_the_next_one() {
+        let mut controller = DifficultyController::default();
+        assert_eq!(trajectory(&mut controller, &[STRONG; 6], 0), [0, 0, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn a_steady_result_in_the_window_holds_the_tier() {
+        let mut controller = DifficultyController::default();
+        assert_eq!(trajectory(&mut controller, &[STRONG, STRONG, STEADY, STRONG, STRONG], 1), [1; 5]);
+    }
+
+    #[test]
+    fn speed_must_beat_the_average_to_step_up() {
+        let mut controller = DifficultyController::default();
+        let accurate_but_slow = (100.0, AVERAGE_CPS);
+        assert_eq!(trajectory(&mut controller, &[accurate_but_slow; 3], 1), [1; 3]);
+    }
+
+    #[test]
+    fn two_sloppy_results_step_down() {
+        let mut controller = DifficultyController::default();
+        assert_eq!(trajectory(&mut controller, &[SLOPPY, STEADY, SLOPPY, SLOPPY, SLOPPY, SLOPPY], 2), [2, 2, 2, 1, 1, 0]);
+    }
+
+    #[test]
+    fn the_tier_stays_within_range() {
+        let mut controller = DifficultyController::default();
+        assert_eq!(trajectory(&mut controller, &[SLOPPY; 4], 0), [0; 4]);
+        let top = TIER_COUNT - 1;
+        assert_eq!(trajectory(&mut controller, &[STRONG; 3], top), [top; 3]);
+    }
+
+    #[test]
+    fn the_offset_follows_a_level_up() {
+        let mut controller = DifficultyController::default();
+        trajectory(&mut controller, &[STRONG; 3], 0);
+        // 基本の難易度が上がっても、補正の1段はそのまま残る
+        assert_eq!(controller.target_tier(base_tier_for_level(5)), 2);
+    }
+
+    static MIXED: [Question; 10] = [
+        Question { japanese: "猫", hiragana: "ねこ" },
+        Question { japanese: "犬", hiragana: "いぬ" },
+        Question { japanese: "鳥", hiragana: "とり" },
+        Question { japanese: "焼き鳥", hiragana: "やきとり" },
+        Question { japanese: "さくらんぼ", hiragana: "さくらんぼ" },
+        Question { japanese: "図書館", hiragana: "としょかん" },
+        Question { japanese: "夏休み", hiragana: "なつやすみ" },
+        Question { japanese: "自転車置き場", hiragana: "じてんしゃおきば" },
+        Question { japanese: "冷蔵庫", hiragana: "れいぞうこ" },
+        Question { japanese: "ありがとうございました", hiragana: "ありがとうございました" },
+    ];
+
+    /// 難易度をずっと `tier` に寄せて `draws` 回出題し、最初のお題を含めた出題順の番号を返す
+    fn simulate(factor: u32, tier: Option<i32>, excluded: &[usize], draws: usize) -> Vec<usize> {
+        let mut queue = QuestionQueue::from_packs(&[("mixed", &MIXED)]);
+        // 易しいお題ほど、これまでに多く出題されていたことにする
+        let counts = (0..MIXED.len()).map(|idx| (QuestionId::new("mixed", idx), 10 * MIXED[idx].tier().abs_diff(3))).collect();
+        queue.set_rotation(factor, &counts);
+        queue.set_excluded(excluded.iter().map(|&idx| QuestionId::new("mixed", idx)));
+        let index = |id| (0..MIXED.len()).position(|idx| QuestionId::new("mixed", idx) == id).unwrap();
+        let mut order = Vec::with_capacity(draws + 1);
+        order.push(index(queue.current_id()));
+        for _ in 0..draws {
+            assert!(!queue.advance(tier));
+            order.push(index(queue.current_id()));
+        }
+        order
+    }
+
+    /// どのお題も `window` 回の出題のうちに1回は出ていて、同じお題が続いていないことを確かめる
+    fn assert_rotation(order: &[usize], eligible: &[usize], window: usize) {
+        for pair in order.windows(2) {
+            assert_ne!(pair[0], pair[1], "served twice in a row");
+        }
+        for &idx in eligible {
+            // 出題前は -1 回目に出たものとみなす
+            let mut last = -1;
+            for (draw, _) in order.iter().enumerate().filter(|&(_, &served)| served == idx) {
+                assert!(draw as i64 - last <= window as i64, "#{idx} waited {} draws", draw as i64 - last);
+                last = draw as i64;
+            }
+            assert!(order.len() as i64 - 1 - last < window as i64, "#{idx} is overdue at the end");
+        }
+    }
+
+    #[test]
+    fn every_question_is_served_within_its_window_over_thousands_of_draws() {
+        let all: Vec<usize> = (0..MIXED.len()).collect();
+        for &factor in &ROTATION_FACTORS[1..] {
+            for tier in [None, Some(0), Some(3)] {
+                let order = simulate(factor, tier, &[], 5_000);
+                assert_rotation(&order, &all, factor as usize * MIXED.len());
+            }
+        }
+    }
+
+    #[test]
+    fn the_window_counts_only_questions_that_can_be_served() {
+        let excluded = [1, 4, 9];
+        let eligible: Vec<usize> = (0..MIXED.len()).filter(|idx| !excluded.contains(idx)).collect();
+        for &factor in &ROTATION_FACTORS[1..] {
+            let order = simulate(factor, Some(0), &excluded, 5_000);
+            assert!(order[1..].iter().all(|idx| !excluded.contains(idx)));
+            assert_rotation(&order[1..], &eligible, factor as usize * eligible.len());
+        }
+    }
+
+    #[test]
+    fn the_difficulty_still_leads_between_deadlines() {
+        // 保証があっても、易しいお題を求めれば大半は易しいお題になる
+        let order = simulate(ROTATION_FACTORS[3], Some(0), &[], 5_000);
+        let easy = order.iter().filter(|&&idx| MIXED[idx].tier() == 0).count();
+        assert!(easy * 2 > order.len(), "{easy} of {}", order.len());
+        // 保証がなければ、難しいお題は出ないまま
+        let order = simulate(0, Some(0), &[], 5_000);
+        assert!(order[1..].iter().all(|&idx| MIXED[idx].tier() == 0));
+    }
+}
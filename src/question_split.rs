@@ -0,0 +1,265 @@
+// ============================================
+// src/question_split.rs
+// 長すぎるお題を、句読点や空白の位置で番号付きの部分に分ける
+// 段落を丸ごと貼り付けたお題で、入力の単位や表示の行、記録が大きくなりすぎないようにする
+// ============================================
+
+use std::collections::HashMap;
+
+use crate::roman_mapping::{canonical_keystrokes, split_units};
+use crate::user_questions::UserQuestion;
+
+/// 1問の打鍵数の上限の初期値
+pub const DEFAULT_MAX_KEYSTROKES: usize = 300;
+/// この文字の直後で分ける（句読点と空白）
+const BREAK_CHARS: [char; 12] = ['、', '。', '，', '．', ',', '.', '！', '？', '!', '?', ' ', '　'];
+
+/// 長すぎるお題の扱い（設定から作る）
+#[derive(Debug, Clone, Copy)]
+pub struct LengthLimit {
+    /// 1問の打鍵数（各単位を最初の候補で打ったとき）の上限
+    pub max_keystrokes: usize,
+    /// 上限を超えたら分ける（false なら取り込まない）
+    pub split: bool,
+}
+
+/// 読みを分けた1つ分
+struct KanaPart {
+    text: String,
+    /// 句読点・空白の直後（か読みの最後）で終わっているか
+    at_break: bool,
+    /// 読みの先頭からこの部分の最後までの句読点・空白の数
+    breaks_so_far: usize,
+}
+
+/// 上限に収まるお題はそのまま返し、長すぎるお題は「その1/3」のように番号を付けた部分に分ける
+/// 部分にはもとのお題のタグと訳をそのまま付ける。分けない設定なら長すぎる理由をエラーで返す
+pub fn fit_question(
+    question: UserQuestion,
+    limit: LengthLimit,
+    roman_map: &HashMap<&'static str, Vec<&'static str>>,
+) -> Result<Vec<UserQuestion>, String> {
+    let keystrokes = canonical_keystrokes(roman_map, &question.hiragana);
+    if keystrokes <= limit.max_keystrokes {
+        return Ok(vec![question]);
+    }
+    if !limit.split {
+        return Err(format!(
+            "too long: {} keystrokes (limit {}, splitting is off)",
+            keystrokes, limit.max_keystrokes
+        ));
+    }
+
+    let parts = split_kana(&question.hiragana, limit.max_keystrokes, roman_map);
+    // 日本語が読みと同じならそのまま、違えば同じ句読点の位置で分け、分けられなければ読みを表示に使う
+    let japanese_parts = if question.japanese == question.hiragana {
+        None
+    } else {
+        split_japanese(&question.japanese, &parts)
+    };
+    let total = parts.len();
+    Ok(parts
+        .iter()
+        .enumerate()
+        .map(|(idx, part)| {
+            let japanese = japanese_parts.as_ref().map_or(part.text.as_str(), |japanese| japanese[idx].as_str());
+            UserQuestion {
+                japanese: format!("{} その{}/{}", japanese, idx + 1, total),
+                hiragana: part.text.clone(),
+                tags: question.tags.clone(),
+                gloss: question.gloss.clone(),
+            }
+        })
+        .collect())
+}
+
+fn is_break(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| BREAK_CHARS.contains(&c)) && chars.next().is_none()
+}
+
+/// 読みを、打鍵数が上限に収まる部分に分ける（辞書の単位の途中では分けない）
+/// できるだけ句読点・空白の直後で分け、上限までに句読点も空白もなければ単位の切れ目で分ける
+/// そのときも、後ろのかなと合わせて打つ「っ」で部分が終わらないようにする
+fn split_kana(text: &str, max_keystrokes: usize, roman_map: &HashMap<&'static str, Vec<&'static str>>) -> Vec<KanaPart> {
+    let chars: Vec<char> = text.chars().collect();
+    // (単位の文字, 打鍵数, 句読点・空白か)
+    let units: Vec<(String, usize, bool)> = split_units(roman_map, text)
+        .into_iter()
+        .map(|(range, key)| {
+            let unit: String = chars[range].iter().collect();
+            let keystrokes = key.and_then(|key| roman_map.get(key)).map_or(0, |patterns| patterns[0].len());
+            let at_break = is_break(&unit);
+            (unit, keystrokes, at_break)
+        })
+        .collect();
+
+    let mut parts: Vec<KanaPart> = Vec::new();
+    let mut breaks_so_far = 0;
+    let mut start = 0;
+    while start < units.len() {
+        // 上限に収まるところまで進める（1単位だけで上限を超えるときもその単位は入れる）
+        let mut end = start;
+        let mut keystrokes = 0;
+        let mut last_break = None;
+        while end < units.len() && (end == start || keystrokes + units[end].1 <= max_keystrokes) {
+            keystrokes += units[end].1;
+            end += 1;
+            if units[end - 1].2 {
+                last_break = Some(end);
+            }
+        }
+        if end < units.len() {
+            end = match last_break {
+                Some(after_break) => after_break,
+                None if end - start > 1 && units[end - 1].0 == "っ" => end - 1,
+                None => end,
+            };
+        }
+
+        breaks_so_far += units[start..end].iter().filter(|unit| unit.2).count();
+        let text: String = units[start..end].iter().map(|unit| unit.0.as_str()).collect();
+        let text = text.trim();
+        // 空白だけの部分は前の部分にまとめる（打つものがない）
+        if text.is_empty() {
+            if let Some(previous) = parts.last_mut() {
+                previous.breaks_so_far = breaks_so_far;
+            }
+        } else {
+            parts.push(KanaPart {
+                text: text.to_string(),
+                at_break: end == units.len() || units[end - 1].2,
+                breaks_so_far,
+            });
+        }
+        start = end;
+    }
+    parts
+}
+
+/// 日本語を、読みの部分と同じ数の句読点・空白の直後で分ける
+/// どこかの部分が句読点・空白で終わっていないときや、句読点・空白の数が読みと違うときは None
+fn split_japanese(japanese: &str, parts: &[KanaPart]) -> Option<Vec<String>> {
+    let total_breaks = parts.last().map_or(0, |part| part.breaks_so_far);
+    let japanese_breaks = japanese.chars().filter(|c| BREAK_CHARS.contains(c)).count();
+    if parts.iter().any(|part| !part.at_break) || japanese_breaks != total_breaks {
+        return None;
+    }
+    let mut pieces = Vec::with_capacity(parts.len());
+    let mut current = String::new();
+    let mut breaks = 0;
+    let mut targets = parts.iter().map(|part| part.breaks_so_far);
+    let mut target = targets.next();
+    for c in japanese.chars() {
+        current.push(c);
+        if BREAK_CHARS.contains(&c) {
+            breaks += 1;
+            if Some(breaks) == target && pieces.len() + 1 < parts.len() {
+                pieces.push(current.trim().to_string());
+                current.clear();
+                target = targets.next();
+            }
+        }
+    }
+    pieces.push(current.trim().to_string());
+    // 日本語の方だけ空になった部分があれば、読みを表示に使う
+    (pieces.len() == parts.len() && pieces.iter().all(|piece| !piece.is_empty())).then_some(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::create_roman_mapping;
+    use crate::user_questions::Gloss;
+
+    fn question(japanese: &str, hiragana: &str) -> UserQuestion {
+        UserQuestion {
+            japanese: japanese.to_string(),
+            hiragana: hiragana.to_string(),
+            tags: vec!["animals".to_string()],
+            gloss: Gloss::from([("en".to_string(), "animals".to_string())]),
+        }
+    }
+
+    fn fit(japanese: &str, hiragana: &str, max_keystrokes: usize) -> Vec<UserQuestion> {
+        let limit = LengthLimit { max_keystrokes, split: true };
+        fit_question(question(japanese, hiragana), limit, &create_roman_mapping()).unwrap()
+    }
+
+    /// どの部分も上限に収まり（1単位で上限を超える場合を除く）、つなげるともとの読みになることを確かめる
+    fn assert_parts(hiragana: &str, parts: &[UserQuestion], max_keystrokes: usize) {
+        let map = create_roman_mapping();
+        for part in parts {
+            let keystrokes = canonical_keystrokes(&map, &part.hiragana);
+            assert!(keystrokes <= max_keystrokes || split_units(&map, &part.hiragana).len() == 1, "{}", part.hiragana);
+            assert_eq!(part.tags, ["animals"]);
+            assert_eq!(part.gloss.get("en").map(String::as_str), Some("animals"));
+        }
+        let joined: String = parts.iter().map(|part| part.hiragana.as_str()).collect();
+        assert_eq!(joined, hiragana.replace([' ', '　'], ""));
+    }
+
+    #[test]
+    fn a_question_within_the_limit_is_kept_as_it_is() {
+        assert_eq!(fit("猫", "ねこ", 4), [question("猫", "ねこ")]);
+    }
+
+    #[test]
+    fn an_oversized_question_is_rejected_when_splitting_is_off() {
+        let limit = LengthLimit { max_keystrokes: 3, split: false };
+        let error = fit_question(question("猫", "ねこ"), limit, &create_roman_mapping()).unwrap_err();
+        assert_eq!(error, "too long: 4 keystrokes (limit 3, splitting is off)");
+    }
+
+    #[test]
+    fn parts_end_at_punctuation_in_both_texts() {
+        let parts = fit("猫、犬、鳥", "ねこ、いぬ、とり", 6);
+        let texts: Vec<(&str, &str)> = parts.iter().map(|q| (q.japanese.as_str(), q.hiragana.as_str())).collect();
+        assert_eq!(texts, [("猫、 その1/3", "ねこ、"), ("犬、 その2/3", "いぬ、"), ("鳥 その3/3", "とり")]);
+        assert_parts("ねこ、いぬ、とり", &parts, 6);
+    }
+
+    #[test]
+    fn the_reading_is_shown_when_the_japanese_punctuation_differs() {
+        let parts = fit("猫犬鳥", "ねこ、いぬ、とり", 6);
+        let japanese: Vec<&str> = parts.iter().map(|q| q.japanese.as_str()).collect();
+        assert_eq!(japanese, ["ねこ、 その1/3", "いぬ、 その2/3", "とり その3/3"]);
+    }
+
+    #[test]
+    fn spaces_split_and_never_leave_an_empty_part() {
+        let hiragana = "ねこ　　　　いぬ とり";
+        let parts = fit(hiragana, hiragana, 5);
+        assert!(parts.iter().all(|part| !part.hiragana.trim().is_empty()));
+        assert_parts(hiragana, &parts, 5);
+    }
+
+    #[test]
+    fn pathological_inputs_without_any_punctuation() {
+        // (読み, 上限, 部分の数)
+        let cases = [
+            // 1打鍵のかなだけが 2,000 文字
+            ("あ".repeat(2_000), DEFAULT_MAX_KEYSTROKES, 7),
+            // 3打鍵の拗音の途中では分けない
+            ("しゃ".repeat(100), 10, 34),
+            // 1単位で上限を超えるときは、その単位だけの部分にする
+            ("しゃ".repeat(5), 2, 5),
+            // 「っ」だけが続いても止まらずに分けきる
+            ("っ".repeat(50), 7, 49),
+            // 「っ」で部分が終わらないよう、次のかなと一緒に後ろの部分へ回す
+            ("かっ".repeat(30), 5, 30),
+        ];
+        for (hiragana, max_keystrokes, count) in cases {
+            let parts = fit(&hiragana, &hiragana, max_keystrokes);
+            assert_eq!(parts.len(), count, "{hiragana} / {max_keystrokes}");
+            assert_parts(&hiragana, &parts, max_keystrokes);
+            assert!(parts[0].japanese.ends_with(&format!(" その1/{count}")));
+            for part in &parts {
+                assert!(!part.hiragana.starts_with(['ゃ', 'ゅ', 'ょ']), "{}", part.hiragana);
+            }
+            if hiragana.contains('か') {
+                assert!(parts[..count - 1].iter().all(|part| !part.hiragana.ends_with('っ')));
+            }
+        }
+    }
+}
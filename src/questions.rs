@@ -9,15 +9,280 @@
  * (romaji -> hiragana に変更)
  */
 
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::roman_mapping::{canonical_keystrokes, split_units};
+
 // 構造体のフィールド名を変更
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct Question {
     pub japanese: &'static str, // 表示用 (漢字混じり)
     pub hiragana: &'static str, // タイピング用 (ひらがな)
 }
 
-/// 問題リスト (ひらがなの文字数昇順)
-pub const QUESTIONS_LIST: &'static [Question] = &[
+/// 難易度の段階数 (0 が最も易しい)
+pub const TIER_COUNT: i32 = 4;
+
+impl Question {
+    /// ひらがなの文字数から決まる難易度 (0 〜 TIER_COUNT - 1)
+    pub fn tier(&self) -> i32 {
+        match self.hiragana.chars().count() {
+            0..=3 => 0,
+            4..=5 => 1,
+            6..=8 => 2,
+            _ => 3,
+        }
+    }
+}
+
+/// 読み込んだお題の難易度と打鍵数（お題を読み込んだときに1度だけ求める）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuestionMeta {
+    /// 難易度（文章モードの文章には段階がないので None）
+    pub tier: Option<i32>,
+    /// 標準的なローマ字で打ったときの打鍵数
+    pub keys: u32,
+}
+
+impl QuestionMeta {
+    /// "★★☆☆ · 14 keys"（難易度がなければ "14 keys"）
+    pub fn badge(&self) -> String {
+        match self.tier {
+            Some(tier) => {
+                let filled = (tier + 1).clamp(0, TIER_COUNT) as usize;
+                let stars = format!("{}{}", "★".repeat(filled), "☆".repeat(TIER_COUNT as usize - filled));
+                format!("{} · {} keys", stars, self.keys)
+            }
+            None => format!("{} keys", self.keys),
+        }
+    }
+}
+
+/// 組み込みのお題のパック ID
+pub const BUILTIN_PACK_ID: &str = "builtin";
+/// ユーザーが追加したお題（user_questions.json）のパック ID
+pub const USER_PACK_ID: &str = "user";
+
+/// お題の ID（パック ID とパック内の番号から作るので、読みを直しても変わらない）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode)]
+#[serde(transparent)]
+pub struct QuestionId(pub u64);
+
+impl QuestionId {
+    /// パック ID と番号から ID を作る（バージョンが変わっても同じ値になるよう FNV-1a で計算する）
+    pub fn new(pack_id: &str, index: usize) -> Self {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+        let mut hash = OFFSET_BASIS;
+        let bytes = pack_id.bytes().chain([0]).chain((index as u64).to_le_bytes());
+        for byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+        Self(hash)
+    }
+
+    /// 組み込みのお題の ID
+    pub fn builtin(index: usize) -> Self {
+        Self::new(BUILTIN_PACK_ID, index)
+    }
+}
+
+impl fmt::Display for QuestionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for QuestionId {
+    type Err = String;
+
+    /// 16進数の表記（`Display` と同じ形式）から読み取る
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s.trim(), 16)
+            .map(Self)
+            .map_err(|_| format!("invalid question id: {}", s))
+    }
+}
+
+// --------------------------------------------------
+// MARK:出題するパック
+// --------------------------------------------------
+
+/// 出題するパック（パック ID とお題）
+pub type Pack = (&'static str, &'static [Question]);
+
+/// 出題するユーザーのお題（起動時と、お題を追加したときに差し替える）
+static USER_PACK: RwLock<&'static [Question]> = RwLock::new(&[]);
+/// 出題する、ダウンロードしたパック（起動時に差し替える）
+static INSTALLED_PACKS: RwLock<Vec<Pack>> = RwLock::new(Vec::new());
+
+/// 出題するユーザーのお題を差し替える
+pub fn set_user_pack(questions: &'static [Question]) {
+    *USER_PACK.write().unwrap_or_else(|e| e.into_inner()) = questions;
+}
+
+/// 出題するユーザーのお題
+pub fn user_pack() -> &'static [Question] {
+    *USER_PACK.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 出題する、ダウンロードしたパックを差し替える
+pub fn set_installed_packs(packs: Vec<Pack>) {
+    *INSTALLED_PACKS.write().unwrap_or_else(|e| e.into_inner()) = packs;
+}
+
+/// 通常の出題に使うパック（組み込み → ユーザー → ダウンロードしたパックの順）
+pub fn served_packs() -> Vec<Pack> {
+    let installed = INSTALLED_PACKS.read().unwrap_or_else(|e| e.into_inner());
+    [(BUILTIN_PACK_ID, QUESTIONS_LIST), (USER_PACK_ID, user_pack())]
+        .into_iter()
+        .chain(installed.iter().copied())
+        .collect()
+}
+
+/// 通常の出題に使うお題を ID と一緒に並べる
+pub fn served_questions() -> impl Iterator<Item = (QuestionId, &'static Question)> {
+    served_packs()
+        .into_iter()
+        .flat_map(|(pack_id, questions)| questions.iter().enumerate().map(move |(idx, q)| (QuestionId::new(pack_id, idx), q)))
+}
+
+/// 出題するお題を ID から探す（`served_packs` の順に探す）
+pub fn find_question(id: QuestionId) -> Option<&'static Question> {
+    served_questions().find(|&(question_id, _)| question_id == id).map(|(_, q)| q)
+}
+
+// --------------------------------------------------
+// MARK:出題範囲の診断
+// --------------------------------------------------
+
+/// 1問としては長すぎると考える打鍵数
+pub const SANITY_MAX_KEYSTROKES: usize = 60;
+
+/// 診断するお題1つ
+pub struct PoolEntry<'a> {
+    /// どのパックのお題か（"builtin" / "user" / 入っているパックの ID）
+    pub pack: &'a str,
+    pub japanese: &'a str,
+    pub hiragana: &'a str,
+    /// 出題されない理由（出題されうるなら None）
+    pub unreachable: Option<&'static str>,
+}
+
+/// 出題されないお題
+#[derive(Debug, Serialize)]
+pub struct UnreachableQuestion {
+    pub pack: String,
+    pub japanese: String,
+    pub reason: String,
+}
+
+/// 打鍵数が多すぎるお題
+#[derive(Debug, Serialize)]
+pub struct LongQuestion {
+    pub japanese: String,
+    pub keystrokes: usize,
+}
+
+/// 同じ読みのお題（パックをまたいだものも含む）
+#[derive(Debug, Serialize)]
+pub struct DuplicateReading {
+    pub hiragana: String,
+    /// "パック: 日本語" の一覧
+    pub questions: Vec<String>,
+}
+
+/// 出題範囲の診断結果
+#[derive(Debug, Serialize)]
+pub struct PoolHealth {
+    pub questions: usize,
+    /// どのお題でも使われないローマ字辞書の見出し
+    pub unused_mappings: Vec<&'static str>,
+    pub unreachable: Vec<UnreachableQuestion>,
+    pub too_long: Vec<LongQuestion>,
+    pub duplicates: Vec<DuplicateReading>,
+}
+
+impl PoolHealth {
+    pub fn analyze(entries: &[PoolEntry], map: &HashMap<&'static str, Vec<&'static str>>) -> Self {
+        Self {
+            questions: entries.len(),
+            unused_mappings: unused_mappings(map, entries.iter().map(|e| e.hiragana)),
+            unreachable: entries
+                .iter()
+                .filter_map(|e| {
+                    e.unreachable.map(|reason| UnreachableQuestion {
+                        pack: e.pack.to_string(),
+                        japanese: e.japanese.to_string(),
+                        reason: reason.to_string(),
+                    })
+                })
+                .collect(),
+            too_long: entries
+                .iter()
+                .map(|e| (e, canonical_keystrokes(map, e.hiragana)))
+                .filter(|&(_, keystrokes)| keystrokes > SANITY_MAX_KEYSTROKES)
+                .map(|(e, keystrokes)| LongQuestion { japanese: e.japanese.to_string(), keystrokes })
+                .collect(),
+            duplicates: duplicate_readings(entries),
+        }
+    }
+
+    /// 1行の要約
+    pub fn summary(&self) -> String {
+        format!(
+            "{} questions · {} unreachable · {} too long · {} duplicate readings · {} unused mappings",
+            self.questions,
+            self.unreachable.len(),
+            self.too_long.len(),
+            self.duplicates.len(),
+            self.unused_mappings.len()
+        )
+    }
+}
+
+/// どのお題を区切っても出てこない辞書の見出し（並べ替え済み）
+pub fn unused_mappings<'a>(
+    map: &HashMap<&'static str, Vec<&'static str>>,
+    hiraganas: impl Iterator<Item = &'a str>,
+) -> Vec<&'static str> {
+    let mut used = BTreeSet::new();
+    for hiragana in hiraganas {
+        used.extend(split_units(map, hiragana).into_iter().filter_map(|(_, key)| key));
+    }
+    let mut unused: Vec<&'static str> = map.keys().copied().filter(|key| !used.contains(key)).collect();
+    unused.sort_unstable();
+    unused
+}
+
+/// 読みが同じお題をまとめる（2つ以上あるものだけ、読みの順）
+pub fn duplicate_readings(entries: &[PoolEntry]) -> Vec<DuplicateReading> {
+    let mut by_reading: HashMap<&str, Vec<String>> = HashMap::new();
+    for entry in entries {
+        by_reading
+            .entry(entry.hiragana)
+            .or_default()
+            .push(format!("{}: {}", entry.pack, entry.japanese));
+    }
+    let mut duplicates: Vec<DuplicateReading> = by_reading
+        .into_iter()
+        .filter(|(_, questions)| questions.len() > 1)
+        .map(|(hiragana, questions)| DuplicateReading { hiragana: hiragana.to_string(), questions })
+        .collect();
+    duplicates.sort_by(|a, b| a.hiragana.cmp(&b.hiragana));
+    duplicates
+}
+
+/// 問題リスト
+/// ※リスト内の番号がお題の ID になるため、お題を増やすときは必ず末尾に追加すること
+pub const QUESTIONS_LIST: &[Question] = &[
     // --- 都道府県・地名 (Geography) ---
     Question { japanese: "北海道", hiragana: "ほっかいどう" },
     Question { japanese: "青森県", hiragana: "あおもりけん" },
@@ -738,5 +1003,112 @@ pub const QUESTIONS_LIST: &'static [Question] = &[
     Question { japanese: "プラットフォーマー", hiragana: "ぷらっとふぉーまー" },
     Question { japanese: "ガーファ", hiragana: "がーふぁ" }, // GAFA
     Question { japanese: "デファクトスタンダード", hiragana: "でふぁくとすたんだーど" },
+    // 読みをカタカナのまま書いたお題（ひらがなと同じローマ字で入力する）
+    Question { japanese: "カフェラテ", hiragana: "カフェラテ" },
+    Question { japanese: "ノートパソコン", hiragana: "ノートパソコン" },
+    Question { japanese: "チョコレートケーキ", hiragana: "チョコレートケーキ" },
+    Question { japanese: "ヴァイオリン", hiragana: "ヴァイオリン" },
+    // 小さいかなの組み合わせ（"fa" のようにまとめても、"fu" + "xa" のように分けても打てる）
+    Question { japanese: "ファイル", hiragana: "ふぁいる" },
+    Question { japanese: "パーティー", hiragana: "ぱーてぃー" },
+    Question { japanese: "ウィンドウ", hiragana: "ういんどう" },
+    Question { japanese: "デュエット", hiragana: "でゅえっと" },
+    Question { japanese: "タトゥー", hiragana: "たとぅー" },
+    Question { japanese: "フュージョン", hiragana: "ふゅーじょん" },
+    Question { japanese: "イェール大学", hiragana: "いぇーるだいがく" },
+    Question { japanese: "ボランティア", hiragana: "ぼらんてぃあ" },
 ];
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::{create_roman_mapping, unsupported_chars};
+
+    #[test]
+    fn builtin_katakana_questions_are_typeable() {
+        let map = create_roman_mapping();
+        let katakana: Vec<_> = QUESTIONS_LIST
+            .iter()
+            .filter(|q| q.hiragana.chars().any(|c| ('ァ'..='ヶ').contains(&c)))
+            .collect();
+        assert!(katakana.len() >= 2);
+        for question in katakana {
+            assert!(unsupported_chars(&map, question.hiragana).is_empty(), "{}", question.hiragana);
+        }
+    }
+
+
+    /// 診断用の小さな辞書
+    fn tiny_map() -> HashMap<&'static str, Vec<&'static str>> {
+        HashMap::from([("あ", vec!["a"]), ("い", vec!["i"]), ("き", vec!["ki"]), ("きゃ", vec!["kya"]), ("ー", vec!["-"])])
+    }
+
+    fn entry<'a>(pack: &'a str, japanese: &'a str, hiragana: &'a str) -> PoolEntry<'a> {
+        PoolEntry { pack, japanese, hiragana, unreachable: None }
+    }
+
+    #[test]
+    fn mappings_no_question_splits_into_are_unused() {
+        // きゃ は長い見出しが優先されるので き は使われない
+        assert_eq!(unused_mappings(&tiny_map(), ["きゃあ", "あい"].into_iter()), ["き", "ー"]);
+        assert_eq!(unused_mappings(&tiny_map(), ["きー", "きゃい", "あ"].into_iter()), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn only_questions_over_the_keystroke_limit_are_too_long() {
+        let at_limit = "あ".repeat(SANITY_MAX_KEYSTROKES);
+        let over = "あ".repeat(SANITY_MAX_KEYSTROKES - 2) + "きゃ";
+        let entries = [entry("builtin", "limit", &at_limit), entry("builtin", "over", &over)];
+        let health = PoolHealth::analyze(&entries, &tiny_map());
+        let too_long: Vec<(&str, usize)> = health.too_long.iter().map(|q| (q.japanese.as_str(), q.keystrokes)).collect();
+        assert_eq!(too_long, [("over", SANITY_MAX_KEYSTROKES + 1)]);
+    }
+
+    #[test]
+    fn the_report_lists_unreachable_questions_and_duplicates_across_packs() {
+        let mut blacklisted = entry("user", "亜", "あ");
+        blacklisted.unreachable = Some("blacklisted");
+        let entries = [
+            entry("builtin", "胃", "い"),
+            blacklisted,
+            entry("builtin", "阿", "あ"),
+            entry("jlpt", "伊", "い"),
+            entry("jlpt", "木", "き"),
+        ];
+        let health = PoolHealth::analyze(&entries, &tiny_map());
+
+        let unreachable: Vec<_> = health.unreachable.iter().map(|q| (q.pack.as_str(), q.japanese.as_str(), q.reason.as_str())).collect();
+        assert_eq!(unreachable, [("user", "亜", "blacklisted")]);
+        let duplicates: Vec<_> = health.duplicates.iter().map(|d| (d.hiragana.as_str(), d.questions.clone())).collect();
+        assert_eq!(
+            duplicates,
+            [
+                ("あ", vec!["user: 亜".to_string(), "builtin: 阿".to_string()]),
+                ("い", vec!["builtin: 胃".to_string(), "jlpt: 伊".to_string()]),
+            ]
+        );
+        assert_eq!(health.unused_mappings, ["きゃ", "ー"]);
+        assert_eq!(
+            health.summary(),
+            "5 questions · 1 unreachable · 0 too long · 2 duplicate readings · 2 unused mappings"
+        );
+    }
+
+    #[test]
+    fn user_questions_are_found_after_the_builtin_ones() {
+        static USER: [Question; 1] = [Question { japanese: "鳥", hiragana: "とり" }];
+        set_user_pack(&USER);
+        assert_eq!(find_question(QuestionId::builtin(0)).map(|q| q.japanese), Some(QUESTIONS_LIST[0].japanese));
+        assert_eq!(find_question(QuestionId::new(USER_PACK_ID, 0)).map(|q| q.japanese), Some("鳥"));
+        assert!(find_question(QuestionId::new(USER_PACK_ID, 1)).is_none());
+        assert_eq!(served_questions().count(), QUESTIONS_LIST.len() + 1);
+    }
+
+    #[test]
+    fn the_badge_fills_a_star_per_tier() {
+        let meta = |tier| QuestionMeta { tier, keys: 14 };
+        assert_eq!(meta(Some(0)).badge(), "★☆☆☆ · 14 keys");
+        assert_eq!(meta(Some(TIER_COUNT - 1)).badge(), "★★★★ · 14 keys");
+        assert_eq!(meta(None).badge(), "14 keys");
+    }
+}
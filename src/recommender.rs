@@ -0,0 +1,353 @@
+// ============================================
+// src/recommender.rs
+// セッションの終わりに「次に練習すること」を1つだけ勧める
+// 遅いかな・ミスの多いキー・平均より遅い長さの帯を候補にして、平均からいちばん離れているものを選ぶ
+// ============================================
+
+use rand::seq::SliceRandom;
+
+use std::collections::HashMap;
+
+use crate::questions::Question;
+use crate::roman_mapping::{canonical_keystrokes, split_units};
+use crate::save_data::TypeRecord;
+use crate::stats::{LENGTH_BANDS, band_label, length_band};
+
+/// 集計に使う直近の記録の数
+const RECENT_RECORDS: usize = 200;
+/// かなを候補にするのに必要な出現回数
+const MIN_KANA_SAMPLES: u32 = 8;
+/// キーを候補にするのに必要な打鍵数
+const MIN_KEY_SAMPLES: u32 = 20;
+/// 長さの帯を候補にするのに必要な記録数
+const MIN_BAND_SAMPLES: u32 = 10;
+/// 前回と同じ勧めは点数をこの倍率に下げる（毎回同じことを言わないように）
+const REPEAT_PENALTY: f64 = 0.5;
+/// 点数がこれより低ければ何も勧めない（平均から 10% も離れていなければ言うほどのことはない）
+const MIN_SCORE: f64 = 0.1;
+
+type RomanMap = HashMap<&'static str, Vec<&'static str>>;
+
+/// セッション中の、打つべきだったキーごとの (正しく打った数, ミスした数)
+#[derive(Debug, Clone, Default)]
+pub struct KeyTally {
+    keys: HashMap<char, (u32, u32)>,
+}
+
+impl KeyTally {
+    pub fn record(&mut self, expected: char, hit: bool) {
+        let entry = self.keys.entry(expected.to_ascii_lowercase()).or_default();
+        if hit {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+/// 練習する対象
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    /// 打つのが遅いかな（辞書の見出し）
+    Kana(&'static str),
+    /// ミスの多いキー
+    Key(char),
+    /// 平均より遅い長さの帯（`LENGTH_BANDS` の番号）
+    LengthBand(usize),
+}
+
+impl Focus {
+    /// 前回の勧めと比べるための名前（設定に保存する）
+    pub fn id(&self) -> String {
+        match self {
+            Focus::Kana(kana) => format!("kana:{}", kana),
+            Focus::Key(key) => format!("key:{}", key),
+            Focus::LengthBand(band) => format!("band:{}", band),
+        }
+    }
+
+    /// この対象を練習できるお題か（各単位を最初の候補のローマ字で打つとして判定する）
+    fn matches(&self, map: &RomanMap, hiragana: &str) -> bool {
+        match self {
+            Focus::Kana(kana) => split_units(map, hiragana).iter().any(|(_, key)| *key == Some(*kana)),
+            Focus::Key(key) => split_units(map, hiragana)
+                .iter()
+                .filter_map(|(_, unit)| unit.and_then(|unit| map.get(unit)))
+                .any(|patterns| patterns[0].contains(*key)),
+            Focus::LengthBand(band) => length_band(canonical_keystrokes(map, hiragana) as u32) == *band,
+        }
+    }
+
+    /// 練習用の出題順（`questions` 内の番号をシャッフルしたもの）
+    pub fn drill_pool(&self, map: &RomanMap, questions: &[Question]) -> Vec<usize> {
+        let mut pool: Vec<usize> = questions
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| self.matches(map, q.hiragana))
+            .map(|(idx, _)| idx)
+            .collect();
+        pool.shuffle(&mut rand::rng());
+        pool
+    }
+}
+
+/// 勧める練習
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recommendation {
+    pub focus: Focus,
+    /// 平均からどれだけ悪いか（0.25 なら 25%）
+    pub score: f64,
+}
+
+impl Recommendation {
+    /// 勧めの1行
+    pub fn message(&self) -> String {
+        let percent = self.score * 100.0;
+        match self.focus {
+            Focus::Kana(kana) => {
+                format!("Next: drill 「{}」 — questions with it take you {:.0}% longer per kana than average", kana, percent)
+            }
+            Focus::Key(key) => {
+                format!("Next: drill the '{}' key — you miss it {:.0}% more often than your other keys", key, percent)
+            }
+            Focus::LengthBand(band) => format!(
+                "Next: drill {} keystroke questions — you type them {:.0}% slower than your average",
+                band_label(band),
+                percent
+            ),
+        }
+    }
+}
+
+/// 勧めを選ぶための集計
+#[derive(Debug, Clone, Default)]
+pub struct StatProfile {
+    /// かな → (そのかなを含む記録の1単位あたりの秒数の合計, 出現回数)
+    pub kana: HashMap<&'static str, (f64, u32)>,
+    /// キー → (正しく打った数, ミスした数)
+    pub keys: HashMap<char, (u32, u32)>,
+    /// 長さの帯ごとの (CPS の合計, 記録数)
+    pub bands: [(f64, u32); LENGTH_BANDS.len()],
+}
+
+impl StatProfile {
+    /// 直近の記録と今回のセッションのキーの集計から作る（文章モード・ウォームアップ・取り込んだ記録は除く）
+    pub fn from_history(history: &[TypeRecord], map: &RomanMap, tally: &KeyTally) -> Self {
+        let mut profile = Self { keys: tally.keys.clone(), ..Self::default() };
+        let recent = history
+            .iter()
+            .rev()
+            .filter(|r| r.counts_for_bests() && !r.is_sentence() && r.total_chars > 0 && r.duration_sec > 0.0)
+            .take(RECENT_RECORDS);
+        for record in recent {
+            let units: Vec<&'static str> =
+                split_units(map, &record.question_hiragana).into_iter().filter_map(|(_, key)| key).collect();
+            if !units.is_empty() {
+                let per_unit = record.duration_sec / units.len() as f64;
+                for unit in units {
+                    let entry = profile.kana.entry(unit).or_default();
+                    entry.0 += per_unit;
+                    entry.1 += 1;
+                }
+            }
+            let band = &mut profile.bands[length_band(record.total_chars)];
+            band.0 += record.cps;
+            band.1 += 1;
+        }
+        profile
+    }
+
+    /// 候補と点数（点数は平均からどれだけ悪いかの割合）
+    pub fn candidates(&self) -> Vec<Recommendation> {
+        let mut candidates = Vec::new();
+
+        // かな: 1単位あたりの秒数が全体の平均よりどれだけ長いか
+        let (kana_secs, kana_count) = self.kana.values().fold((0.0, 0), |(s, c), &(secs, n)| (s + secs, c + n));
+        if kana_count > 0 && kana_secs > 0.0 {
+            let average = kana_secs / f64::from(kana_count);
+            candidates.extend(self.kana.iter().filter(|(_, (_, n))| *n >= MIN_KANA_SAMPLES).map(
+                |(&kana, &(secs, n))| Recommendation {
+                    focus: Focus::Kana(kana),
+                    score: secs / f64::from(n) / average - 1.0,
+                },
+            ));
+        }
+
+        // キー: ミスの割合が全体の割合の何倍か
+        let (hits, misses) = self.keys.values().fold((0, 0), |(h, m), &(hit, miss)| (h + hit, m + miss));
+        if misses > 0 {
+            let average = f64::from(misses) / f64::from(hits + misses);
+            candidates.extend(
+                self.keys
+                    .iter()
+                    .filter(|(_, (hit, miss))| hit + miss >= MIN_KEY_SAMPLES)
+                    .map(|(&key, &(hit, miss))| Recommendation {
+                        focus: Focus::Key(key),
+                        score: f64::from(miss) / f64::from(hit + miss) / average - 1.0,
+                    }),
+            );
+        }
+
+        // 長さの帯: 帯の平均 CPS が全体の平均よりどれだけ低いか
+        let (cps_sum, records) = self.bands.iter().fold((0.0, 0), |(s, c), &(cps, n)| (s + cps, c + n));
+        if records > 0 && cps_sum > 0.0 {
+            let average = cps_sum / f64::from(records);
+            candidates.extend(
+                self.bands
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, n))| *n >= MIN_BAND_SAMPLES)
+                    .map(|(band, &(cps, n))| Recommendation {
+                        focus: Focus::LengthBand(band),
+                        score: 1.0 - cps / f64::from(n) / average,
+                    }),
+            );
+        }
+        candidates
+    }
+}
+
+/// いちばん点数の高い勧め（`last` は前回勧めた対象の `Focus::id`。同じものは順位を下げる）
+/// 同点のときは id の順で決めるので、同じ集計からはいつも同じ勧めになる
+pub fn recommend(profile: &StatProfile, last: Option<&str>) -> Option<Recommendation> {
+    let weight = |candidate: &Recommendation| {
+        if last == Some(candidate.focus.id().as_str()) { candidate.score * REPEAT_PENALTY } else { candidate.score }
+    };
+    profile
+        .candidates()
+        .into_iter()
+        .filter(|candidate| weight(candidate) >= MIN_SCORE)
+        .max_by(|a, b| weight(a).total_cmp(&weight(b)).then_with(|| b.focus.id().cmp(&a.focus.id())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::create_roman_mapping;
+
+    /// (キー, 正しく打った数, ミスした数) から作った集計
+    fn keys(counts: &[(char, u32, u32)]) -> StatProfile {
+        StatProfile {
+            keys: counts.iter().map(|&(key, hit, miss)| (key, (hit, miss))).collect(),
+            ..StatProfile::default()
+        }
+    }
+
+    fn best(profile: &StatProfile, last: Option<&str>) -> Option<Focus> {
+        recommend(profile, last).map(|r| r.focus)
+    }
+
+    #[test]
+    fn an_empty_profile_recommends_nothing() {
+        assert_eq!(recommend(&StatProfile::default(), None), None);
+    }
+
+    #[test]
+    fn the_slowest_kana_with_enough_samples_is_recommended() {
+        let profile = StatProfile {
+            // ぬ はいちばん遅いが、回数が足りないので候補にしない
+            kana: HashMap::from([("ね", (6.0, 10)), ("こ", (2.0, 10)), ("ぬ", (3.0, MIN_KANA_SAMPLES - 1))]),
+            ..StatProfile::default()
+        };
+        let candidates = profile.candidates();
+        assert!(candidates.iter().all(|c| c.focus != Focus::Kana("ぬ")));
+        let recommendation = recommend(&profile, None).unwrap();
+        assert_eq!(recommendation.focus, Focus::Kana("ね"));
+        // 1単位 0.6 秒、全体の平均は 11 / 27 秒
+        assert!((recommendation.score - (0.6 / (11.0 / 27.0) - 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_key_missed_most_often_is_recommended() {
+        let profile = keys(&[('a', 70, 30), ('k', 100, 0), ('z', 0, MIN_KEY_SAMPLES - 1)]);
+        let recommendation = recommend(&profile, None).unwrap();
+        assert_eq!(recommendation.focus, Focus::Key('a'));
+        // 全体のミスは 49 / 219
+        assert!((recommendation.score - (0.3 / (49.0 / 219.0) - 1.0)).abs() < 1e-9);
+        assert_eq!(best(&keys(&[('a', 100, 0), ('k', 100, 0)]), None), None);
+    }
+
+    #[test]
+    fn the_band_furthest_below_average_is_recommended() {
+        let mut profile = StatProfile::default();
+        profile.bands[0] = (40.0, 10);
+        profile.bands[2] = (20.0, 10);
+        profile.bands[3] = (5.0, MIN_BAND_SAMPLES - 1);
+        let recommendation = recommend(&profile, None).unwrap();
+        assert_eq!(recommendation.focus, Focus::LengthBand(2));
+        assert!((recommendation.score - (1.0 - 2.0 / (65.0 / 29.0))).abs() < 1e-9);
+        assert_eq!(
+            recommendation.message(),
+            "Next: drill 11–20 keystroke questions — you type them 11% slower than your average"
+        );
+    }
+
+    #[test]
+    fn small_deviations_are_not_worth_recommending() {
+        // ミスの割合が平均より 5% 多いだけ
+        let profile = keys(&[('a', 79, 21), ('k', 80, 20), ('s', 81, 19)]);
+        assert!(!profile.candidates().is_empty());
+        assert_eq!(recommend(&profile, None), None);
+    }
+
+    #[test]
+    fn the_same_advice_gives_way_to_the_runner_up() {
+        let mut profile = keys(&[('a', 60, 40), ('k', 70, 30), ('s', 100, 0), ('d', 100, 0)]);
+        assert_eq!(best(&profile, None), Some(Focus::Key('a')));
+        assert_eq!(best(&profile, Some("key:a")), Some(Focus::Key('k')));
+        assert_eq!(best(&profile, Some("key:k")), Some(Focus::Key('a')));
+        // 前回と同じでも、ほかに勧めることがなければ続けて勧める
+        profile.keys.remove(&'k');
+        assert_eq!(best(&profile, Some("key:a")), Some(Focus::Key('a')));
+    }
+
+    #[test]
+    fn ties_are_broken_by_the_id() {
+        let profile = keys(&[('s', 70, 30), ('a', 70, 30), ('k', 100, 0)]);
+        for _ in 0..5 {
+            assert_eq!(best(&profile.clone(), None), Some(Focus::Key('a')));
+        }
+    }
+
+    #[test]
+    fn the_profile_only_counts_recent_regular_records() {
+        let map = create_roman_mapping();
+        let mut history = vec![TypeRecord::sample("ねこ", 4, 1.0, 0), TypeRecord::sample("いぬ", 3, 2.0, 0)];
+        let mut warmup = TypeRecord::sample("ねこ", 4, 9.0, 0);
+        warmup.warmup = true;
+        history.push(warmup);
+        let mut tally = KeyTally::default();
+        tally.record('K', true);
+        tally.record('k', false);
+
+        let profile = StatProfile::from_history(&history, &map, &tally);
+        assert_eq!(profile.kana["ね"], (0.5, 1));
+        assert_eq!(profile.kana["ぬ"], (1.0, 1));
+        assert_eq!(profile.keys, HashMap::from([('k', (1, 1))]));
+        assert_eq!(profile.bands[0], (4.0 + 1.5, 2));
+    }
+
+    #[test]
+    fn drills_serve_only_questions_with_the_focus() {
+        let map = create_roman_mapping();
+        let questions = [
+            Question { japanese: "猫", hiragana: "ねこ" },
+            Question { japanese: "犬", hiragana: "いぬ" },
+            Question { japanese: "夏休み", hiragana: "なつやすみ" },
+        ];
+        let pool = |focus: Focus| {
+            let mut pool = focus.drill_pool(&map, &questions);
+            pool.sort();
+            pool
+        };
+        assert_eq!(pool(Focus::Kana("ね")), [0]);
+        assert_eq!(pool(Focus::Key('k')), [0]);
+        assert_eq!(pool(Focus::Key('n')), [0, 1, 2]);
+        assert_eq!(pool(Focus::LengthBand(1)), [2]);
+        assert_eq!(pool(Focus::Kana("ぱ")), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,140 @@
+// ============================================
+// src/remap.rs
+// キー配列リマップ（QWERTY の OS 上で Colemak / Dvorak を練習する）
+// ============================================
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// QWERTY の文字並び（リマップ表の基準。各配列の文字列と同じ物理位置で対応させる）
+const QWERTY_KEYS: &str = "qwertyuiop[]asdfghjkl;'zxcvbnm,./-=QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>?_+";
+/// 同じ物理位置にある Colemak の文字
+const COLEMAK_KEYS: &str = "qwfpgjluy;[]arstdhneio'zxcvbkm,./-=QWFPGJLUY:{}ARSTDHNEIO\"ZXCVBKM<>?_+";
+/// 同じ物理位置にある Dvorak の文字
+const DVORAK_KEYS: &str = "',.pyfgcrl/=aoeuidhtns-;qjkxbmwvz[]\"<>PYFGCRL?+AOEUIDHTNS_:QJKXBMWVZ{}";
+
+/// リマップの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyRemap {
+    None,
+    Colemak,
+    Dvorak,
+    Custom,
+}
+
+impl KeyRemap {
+    /// 表示・記録用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyRemap::None => "none",
+            KeyRemap::Colemak => "colemak",
+            KeyRemap::Dvorak => "dvorak",
+            KeyRemap::Custom => "custom",
+        }
+    }
+
+    /// 設定画面で次の選択肢に切り替える
+    pub fn next(&self) -> Self {
+        match self {
+            KeyRemap::None => KeyRemap::Colemak,
+            KeyRemap::Colemak => KeyRemap::Dvorak,
+            KeyRemap::Dvorak => KeyRemap::Custom,
+            KeyRemap::Custom => KeyRemap::None,
+        }
+    }
+}
+
+/// 入力文字を変換するリマップ表
+pub struct Remapper {
+    kind: KeyRemap,
+    table: HashMap<char, char>,
+    /// タイピング中に F2 で一時的に無効化できる
+    pub enabled: bool,
+}
+
+impl Remapper {
+    /// 設定からリマップ表を作る（custom の読み込みに失敗した場合はエラーメッセージを返す）
+    pub fn new(kind: KeyRemap, custom_file: Option<&Path>) -> (Self, Option<String>) {
+        let mut warning = None;
+        let table = match kind {
+            KeyRemap::None => HashMap::new(),
+            KeyRemap::Colemak => build_table(COLEMAK_KEYS),
+            KeyRemap::Dvorak => build_table(DVORAK_KEYS),
+            KeyRemap::Custom => match custom_file {
+                Some(path) => match load_custom_table(path) {
+                    Ok(table) => table,
+                    Err(e) => {
+                        warning = Some(format!("リマップファイルを読み込めません ({}): {}", path.display(), e));
+                        HashMap::new()
+                    }
+                },
+                None => {
+                    warning = Some("custom リマップにはファイルの指定が必要です".to_string());
+                    HashMap::new()
+                }
+            },
+        };
+
+        let remapper = Self {
+            kind,
+            table,
+            enabled: kind != KeyRemap::None,
+        };
+        (remapper, warning)
+    }
+
+    /// 入力された文字をリマップする（無効時やテーブルにない文字はそのまま）
+    pub fn apply(&self, c: char) -> char {
+        if !self.enabled {
+            return c;
+        }
+        self.table.get(&c).copied().unwrap_or(c)
+    }
+
+    /// 現在有効なリマップ名（無効化中は "none"）
+    pub fn active_label(&self) -> &'static str {
+        if self.enabled {
+            self.kind.label()
+        } else {
+            KeyRemap::None.label()
+        }
+    }
+
+    /// F2 による有効/無効の切り替え（リマップ未設定なら何もしない）
+    pub fn toggle(&mut self) {
+        if self.kind != KeyRemap::None {
+            self.enabled = !self.enabled;
+        }
+    }
+}
+
+/// QWERTY と同じ物理位置の文字同士を対応させたテーブルを作る
+fn build_table(layout: &str) -> HashMap<char, char> {
+    QWERTY_KEYS
+        .chars()
+        .zip(layout.chars())
+        .filter(|(from, to)| from != to)
+        .collect()
+}
+
+/// カスタムリマップファイルを読み込む
+/// 1行に「入力文字 変換後文字」の2文字（空白区切り可）。空行と '#' で始まる行は無視する
+fn load_custom_table(path: &Path) -> std::io::Result<HashMap<char, char>> {
+    let text = fs::read_to_string(path)?;
+    let mut table = HashMap::new();
+
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+        if let [from, to] = chars[..] {
+            table.insert(from, to);
+        }
+    }
+    Ok(table)
+}
@@ -0,0 +1,95 @@
+// ============================================
+// src/render_model.rs
+// タイピング画面・授業モード・お題を作る画面の1コマ分の描画内容
+// 描くたびに AppState から作り、描画の関数はこれだけを受け取る（AppState には触れない）
+// 描画用の値をゲームの状態と分けておくことで、描いている途中に状態を変えることもできなくなる
+// ============================================
+
+use ratatui::style::Color;
+use ratatui::text::Line;
+
+use crate::author::AuthorField;
+use crate::settings::ErrorFlash;
+use crate::typing_layout::{TypingContent, TypingPhase};
+
+/// お題の3行（打ち終えた直後の塗りの間は、打ち終えたお題）
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuestionView<'a> {
+    pub japanese: &'a str,
+    /// かなごとに色を分けたひらがな
+    pub hiragana: Line<'a>,
+    /// ローマ字と、その下に出す案内の行
+    pub romaji: Vec<Line<'a>>,
+}
+
+/// 枠・ステータスバー・結果の欄（集中モードでは描かない）
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypingChrome<'a> {
+    /// 枠の左上のタイトル
+    pub title: String,
+    /// 枠の上辺に出す文字（位置は Line の寄せで決まる。並べた順に描く）
+    pub top: Vec<Line<'a>>,
+    /// 枠の下辺に出す文字
+    pub bottom: Vec<Line<'a>>,
+    /// 経験値のゲージの割合（0.0〜1.0）
+    pub gauge_ratio: f64,
+    pub gauge_label: String,
+    /// 結果の欄の行（中身のない行は詰めてある）
+    pub results: Vec<Line<'a>>,
+}
+
+/// 画面の上に重ねるもの（並べた順に描く）
+#[derive(Debug, Clone, PartialEq)]
+pub enum Overlay<'a> {
+    /// 中央のポップアップ（クールダウン・休憩・タイムアタックの成績）
+    Popup { title: &'static str, color: Color, width: u16, lines: Vec<Line<'a>> },
+    /// キー割り当ての一覧
+    Help,
+    /// メモの入力欄
+    NoteEditor { title: String, input: Line<'static> },
+}
+
+/// タイピング画面の1コマ
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypingModel<'a> {
+    pub phase: TypingPhase,
+    pub flash: ErrorFlash,
+    pub question: QuestionView<'a>,
+    /// 日本語の下に出す訳
+    pub gloss: Option<&'a str>,
+    /// 行の割り振りに使う大きさ
+    pub content: TypingContent,
+    /// 集中モードでは None
+    pub chrome: Option<TypingChrome<'a>>,
+    pub overlays: Vec<Overlay<'a>>,
+}
+
+/// 授業モードの画面の1コマ
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosterModel<'a> {
+    /// 枠の左上のタイトル（何人目か）
+    pub title: String,
+    /// 今の生徒の名前
+    pub name: String,
+    /// 名前の下に出す案内や結果（お題を打っている間は空）
+    pub message: Vec<Line<'a>>,
+    /// 打っている間のお題
+    pub question: Option<QuestionView<'a>>,
+    /// 行の割り振りに使う、日本語とひらがなの表示幅
+    pub japanese_width: usize,
+    pub hiragana_width: usize,
+}
+
+/// お題を作る画面の1コマ
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorModel {
+    /// 入力中の欄（枠の色を変える）
+    pub focus: AuthorField,
+    pub japanese: Line<'static>,
+    /// 入力できない文字に色をつけたひらがな
+    pub hiragana: Line<'static>,
+    pub romaji: Line<'static>,
+    pub tags: Line<'static>,
+    /// 欄の下の1行（保存の結果か、入力できない文字の警告）
+    pub message: Option<Line<'static>>,
+}
@@ -0,0 +1,127 @@
+// ============================================
+// src/result_display.rs
+// タイピング画面の結果の行（直前のお題の成績）をいつまで表示するか
+// ============================================
+
+use std::time::{Duration, Instant};
+
+use crate::baseline::BaselineRank;
+use crate::error_cost::ErrorCost;
+use crate::questions::QuestionMeta;
+use crate::settings::ResultPersistence;
+use crate::stats::Percentiles;
+
+/// 直前のお題の成績
+#[derive(Debug, Clone, Copy)]
+pub struct LastResult {
+    pub cps: f64,
+    pub duration_sec: f64,
+    pub misses: u32,
+    pub score: f64,
+    /// 同じ長さのお題の過去の記録の中での順位（ウォームアップは None）
+    pub percentiles: Option<Percentiles>,
+    /// 同じ打鍵数のお題の、一般的なタイピストの目安と比べた段階
+    pub baseline: BaselineRank,
+    /// 速さの制限（ガバナー）を使ったときの (無視したキーの数, 正確率 %)
+    pub governor: Option<(u32, f64)>,
+    /// ミスから立ち直るのにかかった時間
+    pub error_cost: ErrorCost,
+    /// 打ったお題の難易度と打鍵数
+    pub meta: QuestionMeta,
+    /// 一般的なタイピストがそのお題にかける時間（秒）
+    pub expected_secs: Option<f64>,
+    /// 制限時間を過ぎて打ち切ったか
+    pub timed_out: bool,
+    /// 正確率が自己ベストの対象の下限に届かなかったときの (正確率 %, 下限 %)
+    pub below_floor: Option<(f64, f64)>,
+}
+
+/// 結果の行の表示状態
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResultDisplay {
+    result: Option<LastResult>,
+    /// この時刻を過ぎたら表示しない
+    expires_at: Option<Instant>,
+}
+
+impl ResultDisplay {
+    /// お題を打ち終えたときに結果を表示する
+    pub fn show(&mut self, result: LastResult, policy: ResultPersistence, timeout_secs: u64, now: Instant) {
+        self.result = Some(result);
+        self.expires_at = (policy == ResultPersistence::Timed).then(|| now + Duration::from_secs(timeout_secs));
+    }
+
+    /// 次のお題を打ち始めたとき
+    pub fn on_first_key(&mut self, policy: ResultPersistence) {
+        if policy == ResultPersistence::UntilFirstKey {
+            self.result = None;
+        }
+    }
+
+    /// 今表示する結果（消えていれば None）
+    pub fn visible(&self, now: Instant) -> Option<&LastResult> {
+        self.result
+            .as_ref()
+            .filter(|_| self.expires_at.is_none_or(|expires_at| now < expires_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> LastResult {
+        LastResult {
+            cps: 2.0,
+            duration_sec: 2.0,
+            misses: 1,
+            score: 100.0,
+            percentiles: None,
+            baseline: BaselineRank::classify(4, 2.0),
+            governor: None,
+            error_cost: ErrorCost::default(),
+            meta: QuestionMeta::default(),
+            expected_secs: None,
+            timed_out: false,
+            below_floor: None,
+        }
+    }
+
+    /// 結果を出してから、次のお題の最初のキーを押し、`later` 後に表示が残っているか
+    fn visible_after(policy: ResultPersistence, later: Duration) -> bool {
+        let now = Instant::now();
+        let mut display = ResultDisplay::default();
+        display.show(result(), policy, 3, now);
+        assert!(display.visible(now).is_some());
+        display.on_first_key(policy);
+        display.visible(now + later).is_some()
+    }
+
+    #[test]
+    fn always_keeps_the_result_through_the_next_question() {
+        assert!(visible_after(ResultPersistence::Always, Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn until_first_key_clears_on_the_first_key() {
+        assert!(!visible_after(ResultPersistence::UntilFirstKey, Duration::ZERO));
+        let mut display = ResultDisplay::default();
+        display.show(result(), ResultPersistence::UntilFirstKey, 3, Instant::now());
+        assert!(display.visible(Instant::now() + Duration::from_secs(3600)).is_some());
+    }
+
+    #[test]
+    fn timed_clears_once_the_timeout_passes() {
+        assert!(visible_after(ResultPersistence::Timed, Duration::from_millis(2999)));
+        assert!(!visible_after(ResultPersistence::Timed, Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn a_new_result_replaces_the_old_expiry() {
+        let now = Instant::now();
+        let mut display = ResultDisplay::default();
+        display.show(result(), ResultPersistence::Timed, 3, now);
+        display.show(result(), ResultPersistence::Always, 3, now);
+        assert!(display.visible(now + Duration::from_secs(10)).is_some());
+    }
+}
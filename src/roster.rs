@@ -0,0 +1,321 @@
+// ============================================
+// src/roster.rs
+// クラスの成績表（CSV）への書き出しと、1台の PC で生徒が順番に打つ授業モード
+// 授業モードの記録は各生徒の名前でまとめ、プレイヤーのセーブデータには入れない
+// ============================================
+
+use chrono::{Datelike, NaiveDate};
+
+use std::fs::{self, OpenOptions};
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::compact_code::SessionSummary;
+use crate::machine_format::{self, csv_accuracy, csv_cps, csv_field, csv_score};
+use crate::save_data::{get_data_dir, write_atomic};
+
+/// 成績表の見出し行
+const HEADER: &str = "name,date,chars,cps,accuracy,score";
+
+// MARK:成績表のパスを取得する関数
+pub fn get_file_path() -> PathBuf {
+    get_data_dir().join("roster.csv")
+}
+
+/// 成績表の末尾に1行追加する（ファイルがなければ見出し行から作る）
+pub fn append(summary: &SessionSummary) -> Result<PathBuf> {
+    let path = get_file_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let is_new = !path.exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    if is_new {
+        writeln!(file, "{}", HEADER)?;
+    }
+    writeln!(file, "{}", csv_row(summary))?;
+    Ok(path)
+}
+
+/// 1人分の行（数値の桁数は `machine_format` の決まりに従う）
+pub fn csv_row(summary: &SessionSummary) -> String {
+    format!(
+        "{},{},{},{},{},{}",
+        csv_field(&summary.name),
+        machine_format::date(summary.date),
+        summary.total_chars,
+        csv_cps(summary.cps),
+        csv_accuracy(summary.accuracy),
+        summary.score
+    )
+}
+
+// --------------------------------------------------
+// MARK:授業モード
+// --------------------------------------------------
+
+/// 結果の画面を表示しておく時間（過ぎたら次の生徒に進む）
+pub const RESULT_SCREEN_DURATION: Duration = Duration::from_secs(4);
+/// 結果の画面をキーで閉じられるまでの時間（打ち終えた勢いで押したキーで閉じないように）
+const RESULT_KEY_DELAY: Duration = Duration::from_secs(1);
+/// クラスのまとめの見出し行
+const CLASS_HEADER: &str = "name,cps,accuracy,score,status";
+
+/// 名簿ファイル（1行1人。空行と `#` で始まる行は無視する）を読む
+pub fn parse_names(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// その日のお題の番号（日付で決まるので、同じ日なら全員が同じお題を打つ）
+pub fn daily_question_index(date: NaiveDate, count: usize) -> usize {
+    date.num_days_from_ce().unsigned_abs() as usize % count.max(1)
+}
+
+/// 1人分の成績
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StudentResult {
+    pub cps: f64,
+    /// 正確率 (%)
+    pub accuracy: f64,
+    pub score: f64,
+}
+
+/// 1人分の結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StudentOutcome {
+    /// まだ順番が来ていない（途中で授業を終えたときもこのまま）
+    Pending,
+    /// 休み（順番を飛ばした）
+    Absent,
+    Done(StudentResult),
+}
+
+/// 授業モードの画面の段階
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RosterPhase {
+    /// 次の生徒の名前を表示して、始めるのを待っている
+    Ready,
+    /// 生徒がお題を打っている
+    Typing,
+    /// 生徒の結果を表示している
+    Result { shown_at: Instant },
+    /// 全員が終わった（または授業を終えた）
+    Finished,
+}
+
+/// 授業モードの進行状況
+#[derive(Debug, Clone)]
+pub struct ClassSession {
+    pub names: Vec<String>,
+    pub outcomes: Vec<StudentOutcome>,
+    pub current: usize,
+    pub phase: RosterPhase,
+}
+
+impl ClassSession {
+    pub fn new(names: Vec<String>) -> Self {
+        let phase = if names.is_empty() { RosterPhase::Finished } else { RosterPhase::Ready };
+        Self { outcomes: vec![StudentOutcome::Pending; names.len()], names, current: 0, phase }
+    }
+
+    /// 今の順番の生徒の名前
+    pub fn current_name(&self) -> Option<&str> {
+        self.names.get(self.current).map(String::as_str)
+    }
+
+    /// 今の生徒が打ち始める
+    pub fn start(&mut self) {
+        if self.phase == RosterPhase::Ready {
+            self.phase = RosterPhase::Typing;
+        }
+    }
+
+    /// 打っている途中でやめて、同じ生徒の準備の画面に戻る
+    pub fn interrupt(&mut self) {
+        if self.phase == RosterPhase::Typing {
+            self.phase = RosterPhase::Ready;
+        }
+    }
+
+    /// 今の生徒を休みとして飛ばす
+    pub fn skip(&mut self) {
+        if self.phase == RosterPhase::Ready {
+            self.outcomes[self.current] = StudentOutcome::Absent;
+            self.advance();
+        }
+    }
+
+    /// 今の生徒が打ち終えた
+    pub fn complete(&mut self, result: StudentResult, now: Instant) {
+        if self.phase == RosterPhase::Typing {
+            self.outcomes[self.current] = StudentOutcome::Done(result);
+            self.phase = RosterPhase::Result { shown_at: now };
+        }
+    }
+
+    /// キーを押して結果の画面を閉じ、次の生徒へ進む（表示してすぐのキーは無視する）
+    pub fn dismiss_result(&mut self, now: Instant) {
+        if let RosterPhase::Result { shown_at } = self.phase && now.saturating_duration_since(shown_at) >= RESULT_KEY_DELAY {
+            self.advance();
+        }
+    }
+
+    /// 結果の画面を表示し終えていれば次の生徒へ進む
+    pub fn tick(&mut self, now: Instant) {
+        if let RosterPhase::Result { shown_at } = self.phase && now.saturating_duration_since(shown_at) >= RESULT_SCREEN_DURATION {
+            self.advance();
+        }
+    }
+
+    /// 授業を終える（残りの生徒はまだ順番が来ていないまま）
+    pub fn finish(&mut self) {
+        self.phase = RosterPhase::Finished;
+    }
+
+    /// 打ち終えた人数
+    pub fn done_count(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| matches!(outcome, StudentOutcome::Done(_))).count()
+    }
+
+    fn advance(&mut self) {
+        self.current += 1;
+        self.phase = if self.current < self.names.len() { RosterPhase::Ready } else { RosterPhase::Finished };
+    }
+}
+
+/// クラスのまとめ（CSV の全文）
+pub fn class_summary_csv(session: &ClassSession) -> String {
+    let mut csv = format!("{}\n", CLASS_HEADER);
+    for (name, outcome) in session.names.iter().zip(&session.outcomes) {
+        let row = match outcome {
+            StudentOutcome::Done(r) => {
+                format!(
+                    "{},{},{},{},done",
+                    csv_field(name),
+                    csv_cps(r.cps),
+                    csv_accuracy(r.accuracy),
+                    csv_score(r.score)
+                )
+            }
+            StudentOutcome::Absent => format!("{},,,,absent", csv_field(name)),
+            StudentOutcome::Pending => format!("{},,,,not reached", csv_field(name)),
+        };
+        csv.push_str(&row);
+        csv.push('\n');
+    }
+    csv
+}
+
+/// クラスのまとめのパス（名簿ファイルと同じフォルダに「名簿名-日付.csv」）
+pub fn class_summary_path(names_file: &Path, date: NaiveDate) -> PathBuf {
+    let stem = names_file.file_stem().map_or("class".into(), |stem| stem.to_string_lossy());
+    names_file.with_file_name(format!("{}-{}.csv", stem, date.format("%Y-%m-%d")))
+}
+
+/// クラスのまとめを書き出す
+pub fn write_class_summary(session: &ClassSession, path: &Path) -> Result<()> {
+    write_atomic(path, class_summary_csv(session).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(cps: f64) -> StudentResult {
+        StudentResult { cps, accuracy: 97.54, score: 1234.6 }
+    }
+
+    #[test]
+    fn names_skip_blank_lines_and_comments() {
+        let names = parse_names("# 3年B組\nAoi\n\n  Ren  \n#absent: Yui\nSato, Hana\n");
+        assert_eq!(names, ["Aoi", "Ren", "Sato, Hana"]);
+    }
+
+    #[test]
+    fn everyone_gets_the_same_question_on_the_same_day() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        assert_eq!(daily_question_index(day, 10), daily_question_index(day, 10));
+        assert_ne!(daily_question_index(day, 10), daily_question_index(day.succ_opt().unwrap(), 10));
+        assert!(daily_question_index(day, 7) < 7);
+        assert_eq!(daily_question_index(day, 0), 0);
+    }
+
+    #[test]
+    fn the_class_cycles_through_every_student() {
+        let t0 = Instant::now();
+        let mut class = ClassSession::new(parse_names("Aoi\nRen\nYui"));
+        assert_eq!((class.current_name(), class.phase), (Some("Aoi"), RosterPhase::Ready));
+
+        // 打っている途中でやめたら、同じ生徒からやり直す
+        class.start();
+        class.interrupt();
+        assert_eq!((class.current_name(), class.phase), (Some("Aoi"), RosterPhase::Ready));
+
+        class.start();
+        class.complete(result(4.0), t0);
+        // 結果はすぐには閉じられず、表示し終えたら自動で次へ進む
+        class.dismiss_result(t0 + RESULT_KEY_DELAY / 2);
+        assert_eq!(class.current_name(), Some("Aoi"));
+        class.tick(t0 + RESULT_SCREEN_DURATION - Duration::from_millis(1));
+        assert_eq!(class.current_name(), Some("Aoi"));
+        class.tick(t0 + RESULT_SCREEN_DURATION);
+        assert_eq!((class.current_name(), class.phase), (Some("Ren"), RosterPhase::Ready));
+
+        // 休みの生徒は飛ばす（打っている間は飛ばせない）
+        class.start();
+        class.skip();
+        assert_eq!(class.current_name(), Some("Ren"));
+        class.interrupt();
+        class.skip();
+        assert_eq!(class.current_name(), Some("Yui"));
+
+        class.start();
+        class.complete(result(3.0), t0);
+        class.dismiss_result(t0 + RESULT_KEY_DELAY);
+        assert_eq!((class.current_name(), class.phase), (None, RosterPhase::Finished));
+        assert_eq!(class.done_count(), 2);
+        assert_eq!(class.outcomes[1], StudentOutcome::Absent);
+    }
+
+    #[test]
+    fn results_only_count_while_a_student_is_typing() {
+        let mut class = ClassSession::new(vec!["Aoi".to_string()]);
+        class.complete(result(4.0), Instant::now());
+        assert_eq!((class.outcomes[0], class.phase), (StudentOutcome::Pending, RosterPhase::Ready));
+        assert_eq!(ClassSession::new(Vec::new()).phase, RosterPhase::Finished);
+    }
+
+    #[test]
+    fn the_class_summary_lists_every_student_with_a_status() {
+        let mut class = ClassSession::new(parse_names("Aoi\nSato, Hana\nRen\nYui"));
+        class.start();
+        class.complete(result(4.256), Instant::now());
+        class.tick(Instant::now() + RESULT_SCREEN_DURATION);
+        class.start();
+        class.complete(result(3.0), Instant::now());
+        class.tick(Instant::now() + RESULT_SCREEN_DURATION);
+        class.skip();
+        // 授業を途中で終えると、残りは順番が来なかったことになる
+        class.finish();
+        assert_eq!(
+            class_summary_csv(&class),
+            "name,cps,accuracy,score,status\n\
+             Aoi,4.26,97.5,1235,done\n\
+             \"Sato, Hana\",3.00,97.5,1235,done\n\
+             Ren,,,,absent\n\
+             Yui,,,,not reached\n"
+        );
+    }
+
+    #[test]
+    fn the_summary_sits_next_to_the_names_file() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 5).unwrap();
+        assert_eq!(class_summary_path(Path::new("/school/3b.txt"), day), Path::new("/school/3b-2026-03-05.csv"));
+        assert_eq!(class_summary_path(Path::new("class"), day), Path::new("class-2026-03-05.csv"));
+    }
+}
@@ -4,17 +4,148 @@
 // ============================================
 
 use bincode::config::standard;
+use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode};
 use chrono::{DateTime, TimeZone, Utc};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::achievements::EarnedAchievement;
+use crate::break_timer::BreakEvent;
+use crate::confusion::ConfusionMatrix;
+use crate::debug_log::dlog;
+use crate::falling_words::{FallingScore, HIGH_SCORE_SLOTS, MiniGameTotals};
+use crate::machine_format::json_pretty;
+use crate::questions::{QUESTIONS_LIST, QuestionId, find_question};
+use crate::scoring::{Keystrokes, classic_accuracy, meets_accuracy_floor};
+use crate::settings::{SessionIntent, TimingPolicy};
+use crate::stats::{AggregateCache, HistoryCache, PercentileTable, QuestionAggregates, build_question_aggregates};
+use crate::xp_ledger::{XpLedger, XpSource};
 
 const SAVE_FILE_JSON: &str = "save_data.json"; // デバッグ用
 
+/// デバッグ用 JSON も書き出すか（設定で有効にしたときだけ）
+static JSON_MIRROR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// デバッグ用 JSON の書き出しを切り替える
+pub fn set_json_mirror(enabled: bool) {
+    JSON_MIRROR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 一時ファイルに書いてから置き換える（書き込み途中で落ちても元のファイルは壊れない）
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    {
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        file.write_all(bytes)?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// カレントディレクトリに旧バージョンが書き出したデバッグ用 JSON があればそのパスを返す
+pub fn find_stray_debug_json() -> Option<PathBuf> {
+    let stray = std::env::current_dir().ok()?.join(SAVE_FILE_JSON);
+    let in_data_dir = fs::canonicalize(PlayerData::get_debug_json_path()).ok();
+    (stray.exists() && fs::canonicalize(&stray).ok() != in_data_dir).then_some(stray)
+}
+
+/// バイナリセーブ先頭のマジックナンバー（ヘッダなしの旧形式と区別する）
+const SAVE_MAGIC: &[u8; 4] = b"TWIZ";
+/// バイナリセーブの形式バージョン
+/// - 2: ヘッダ付き。記録ごとにお題の文字列を保存
+/// - 3: お題の文字列を表にまとめ、記録は表の番号で参照する
+/// - 4: ヘッダ部と各記録をチェックサム付きのフレームに分ける
+const SAVE_FORMAT_VERSION: u32 = 4;
+
+/// お題テキストを表にまとめる形式になったバージョン
+const FORMAT_INTERNED_QUESTIONS: u32 = 3;
+/// チェックサム付きフレーム形式になったバージョン
+const FORMAT_CHECKSUMMED_FRAMES: u32 = 4;
+
+/// フレームの先頭バイト（壊れた箇所の後で次のフレームを探す目印）
+const FRAME_MARKER: u8 = 0xA5;
+/// ヘッダ部（レベル・累計値・お題の表）のフレーム種別
+const FRAME_KIND_HEADER: u8 = b'H';
+/// 記録1件分のフレーム種別
+const FRAME_KIND_RECORD: u8 = b'R';
+/// フレームの前置部分の長さ（目印 + 種別 + 長さ u32 + CRC32 u32）
+const FRAME_PREFIX_LEN: usize = 10;
+
+/// セーブファイル読み込み時の整合性チェック結果
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    /// 読み込んだデータの形式（"v4" / "legacy" / "json" / "none"）
+    pub source: String,
+    /// 壊れていて読み飛ばした箇所（ファイル先頭からのバイト位置）
+    pub dropped_offsets: Vec<usize>,
+    /// ヘッダ部が読めず、記録だけを取り出してレベル・累計値を作り直したか
+    pub header_rebuilt: bool,
+    /// セーブファイルがあるのに読めなかったとき、上書きされる前に残したコピー
+    pub unreadable_backup: Option<PathBuf>,
+}
+
+impl IntegrityReport {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            dropped_offsets: Vec::new(),
+            header_rebuilt: false,
+            unreadable_backup: None,
+        }
+    }
+
+    /// 記録以外のデータ（またはファイル全体）を失ったか
+    pub fn lost_data(&self) -> bool {
+        self.header_rebuilt || self.unreadable_backup.is_some()
+    }
+}
+
+/// メモの最大文字数
+pub const NOTE_MAX_CHARS: usize = 80;
+
+/// お題に付けたメモ
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct QuestionNote {
+    pub question_id: QuestionId,
+    pub text: String,
+}
+
+/// メモの文字列を検証し、前後の空白を除いたものを返す
+pub fn validate_note(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.chars().count() > NOTE_MAX_CHARS {
+        return Err(format!("Notes can be at most {} characters.", NOTE_MAX_CHARS));
+    }
+    if text.chars().any(char::is_control) {
+        return Err("Notes must fit on a single line.".to_string());
+    }
+    Ok(text.to_string())
+}
+
+/// お題の終わり方
+/// ※記録に保存するので、値を増やすときは必ず末尾に追加すること
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuestionOutcome {
+    /// 最後まで打った（この欄がない古い記録もこれとして読む）
+    #[default]
+    Completed,
+    /// 制限時間を過ぎた。打ったところまでの文字数・打鍵・ミスを記録する
+    /// 正確率と累計には含め、打たなかった残りはミスに数えない。スコアと経験値は 0 で、自己ベストや順位の対象にしない
+    TimedOut,
+}
+
 /// 1回ごとのお題の記録
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeRecord {
@@ -27,11 +158,85 @@ pub struct TypeRecord {
     pub cps: f64,
     pub score: f64,
     pub xp_gained: u32,
+    /// 入力時に有効だったキー配列リマップ ("none" / "colemak" など)
+    #[serde(default = "default_key_remap")]
+    pub key_remap: String,
+    /// 入力中に放置が検出された回数
+    #[serde(default)]
+    pub afk_pauses: u32,
+    /// 文章モードでつなげたお題ごとの内訳（通常のお題では空）
+    #[serde(default)]
+    pub components: Vec<ComponentStat>,
+    /// お題の ID（文章モードの記録では None）
+    #[serde(default)]
+    pub question_id: Option<QuestionId>,
+    /// セッション最初のウォームアップのお題か（自己ベストの対象外）
+    #[serde(default)]
+    pub warmup: bool,
+    /// 休憩をはさんだ直後のお題か（休憩の効果を比べるため）
+    #[serde(default)]
+    pub after_cooldown: bool,
+    /// タイムアタックの何回目か（一番良い回以外は経験値 0 で記録する）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_attack: Option<u32>,
+    /// 実際に押したキーの数（正確率の計算用。記録していない古い記録は 0）
+    #[serde(default)]
+    pub keystrokes: u32,
+    /// 他のタイピングソフトの記録から取り込んだものか（自己ベストの対象外。経験値は 0）
+    #[serde(default)]
+    pub imported: bool,
+    /// 時間の計り方（記録していない古い記録は最初のキーから）
+    #[serde(default)]
+    pub timing: TimingPolicy,
+    /// 速さの制限（ガバナー）の間隔（ミリ秒。0 なら制限なし）。制限した記録は自己ベストの対象外
+    #[serde(default)]
+    pub governor_ms: u32,
+    /// ミスから立ち直るのにかかった時間の見積もり（ミリ秒。記録していない古い記録は 0）
+    #[serde(default)]
+    pub error_loss_ms: u32,
+    /// ミスした「タイピング単位」の (お題の中での番号, ミス回数)。文章モードと古い記録では空
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unit_misses: Vec<(u32, u32)>,
+    /// セッションを始めるときに選んだ目的（聞かない設定のときや古い記録は unspecified）
+    #[serde(default)]
+    pub intent: SessionIntent,
+    /// コード片の練習の記録（書いてあるとおりの文字で打つ。自己ベストの対象外で、成績は別に集計する）
+    #[serde(default)]
+    pub code: bool,
+    /// お題の打ち始め・中盤・終盤の速さ（お題全体の速さに対する千分率）。打鍵の少ないお題と古い記録では空
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pace: Vec<u16>,
+    /// お題の終わり方（制限時間を過ぎたか）
+    #[serde(default)]
+    pub outcome: QuestionOutcome,
+    /// お題の意味の訳（訳の付いたお題のみ。表示言語の訳、なければ英語などほかの言語の訳）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gloss: Option<String>,
+}
+
+/// 文章モードでつなげたお題1つ分の成績
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct ComponentStat {
+    pub question_hiragana: String,
+    pub total_chars: u32,
+    pub duration_sec: f64,
+    pub misses: u32,
 }
 
-/// bincode用の内部表現（DateTimeをi64に変換）
+fn default_key_remap() -> String {
+    "none".to_string()
+}
+
+/// 保存された日時（秒）を戻す。範囲外の値は壊れた記録として扱う
+fn record_timestamp(secs: i64) -> Result<DateTime<Utc>, DecodeError> {
+    Utc.timestamp_opt(secs, 0)
+        .single()
+        .ok_or(DecodeError::Other("record timestamp is out of range"))
+}
+
+/// 旧形式（ヘッダなし）のバイナリ表現。読み込み時の移行専用
 #[derive(Encode, Decode)]
-struct TypeRecordBin {
+struct LegacyTypeRecordBin {
     timestamp_secs: i64,
     question_japanese: String,
     question_hiragana: String,
@@ -43,26 +248,12 @@ struct TypeRecordBin {
     xp_gained: u32,
 }
 
-impl From<&TypeRecord> for TypeRecordBin {
-    fn from(record: &TypeRecord) -> Self {
-        Self {
-            timestamp_secs: record.timestamp.timestamp(),
-            question_japanese: record.question_japanese.clone(),
-            question_hiragana: record.question_hiragana.clone(),
-            total_chars: record.total_chars,
-            duration_sec: record.duration_sec,
-            misses: record.misses,
-            cps: record.cps,
-            score: record.score,
-            xp_gained: record.xp_gained,
-        }
-    }
-}
+impl TryFrom<LegacyTypeRecordBin> for TypeRecord {
+    type Error = DecodeError;
 
-impl From<TypeRecordBin> for TypeRecord {
-    fn from(bin: TypeRecordBin) -> Self {
-        Self {
-            timestamp: Utc.timestamp_opt(bin.timestamp_secs, 0).unwrap(),
+    fn try_from(bin: LegacyTypeRecordBin) -> Result<Self, DecodeError> {
+        Ok(Self {
+            timestamp: record_timestamp(bin.timestamp_secs)?,
             question_japanese: bin.question_japanese,
             question_hiragana: bin.question_hiragana,
             total_chars: bin.total_chars,
@@ -71,7 +262,137 @@ impl From<TypeRecordBin> for TypeRecord {
             cps: bin.cps,
             score: bin.score,
             xp_gained: bin.xp_gained,
-        }
+            key_remap: default_key_remap(),
+            afk_pauses: 0,
+            components: Vec::new(),
+            question_id: None,
+            warmup: false,
+            after_cooldown: false,
+            time_attack: None,
+            keystrokes: 0,
+            imported: false,
+            timing: TimingPolicy::FirstKey,
+            governor_ms: 0,
+            error_loss_ms: 0,
+            unit_misses: Vec::new(),
+            intent: SessionIntent::Unspecified,
+            code: false,
+            pace: Vec::new(),
+            outcome: QuestionOutcome::Completed,
+            gloss: None,
+        })
+    }
+}
+
+impl TypeRecord {
+    /// 文章モードの記録か
+    pub fn is_sentence(&self) -> bool {
+        !self.components.is_empty()
+    }
+
+    /// 自己ベストや順位の対象になる記録か（ウォームアップ・取り込んだ記録・速さを制限した記録・コード片の記録・時間切れの記録は除く）
+    pub fn counts_for_bests(&self) -> bool {
+        !self.warmup && !self.imported && self.governor_ms == 0 && !self.code && !self.timed_out()
+    }
+
+    /// 正確率が自己ベストの対象の下限に届いているか
+    /// 下限は記録には書かず、ベストを求めるときに当てはめる（下限を変えると過去の記録にもそのまま効く）
+    pub fn meets_accuracy_floor(&self, floor: f64) -> bool {
+        meets_accuracy_floor(self.accuracy(), floor)
+    }
+
+    /// 制限時間を過ぎて打ち切った記録か
+    pub fn timed_out(&self) -> bool {
+        self.outcome == QuestionOutcome::TimedOut
+    }
+
+    /// 正確率 (%)
+    pub fn accuracy(&self) -> f64 {
+        self.keystrokes().accuracy().unwrap_or_else(|| classic_accuracy(self.total_chars, self.misses))
+    }
+
+    /// 押したキーの数（ミス以外は正しいキー）
+    pub fn keystrokes(&self) -> Keystrokes {
+        Keystrokes { correct: self.keystrokes.saturating_sub(self.misses), total: self.keystrokes }
+    }
+
+    /// レコードをバイナリに変換する（お題の文字列は表の番号 `question_idx` で保存する）
+    /// ※フィールドを増やすときは必ず末尾に追加すること（古いデータはデフォルト値で読まれる）
+    fn encode_bin(&self, question_idx: u32) -> Result<Vec<u8>, EncodeError> {
+        let mut writer = FieldWriter::new();
+        writer.write(&self.timestamp.timestamp())?;
+        writer.write(&question_idx)?;
+        writer.write(&self.total_chars)?;
+        writer.write(&self.duration_sec)?;
+        writer.write(&self.misses)?;
+        writer.write(&self.cps)?;
+        writer.write(&self.score)?;
+        writer.write(&self.xp_gained)?;
+        writer.write(&self.key_remap)?;
+        writer.write(&self.afk_pauses)?;
+        writer.write(&self.components)?;
+        writer.write(&self.question_id)?;
+        writer.write(&self.warmup)?;
+        writer.write(&self.after_cooldown)?;
+        writer.write(&self.time_attack)?;
+        writer.write(&self.keystrokes)?;
+        writer.write(&self.imported)?;
+        writer.write(&self.timing)?;
+        writer.write(&self.governor_ms)?;
+        writer.write(&self.error_loss_ms)?;
+        writer.write(&self.unit_misses)?;
+        writer.write(&self.intent)?;
+        writer.write(&self.code)?;
+        writer.write(&self.pace)?;
+        writer.write(&self.outcome)?;
+        writer.write(&self.gloss)?;
+        Ok(writer.into_bytes())
+    }
+
+    /// バイナリからレコードを復元する
+    /// `questions` が Some ならお題は表の番号で、None なら（v2 形式）文字列で保存されている
+    fn decode_bin(bytes: &[u8], questions: Option<&[(String, String)]>) -> Result<Self, DecodeError> {
+        let mut reader = FieldReader::new(bytes);
+        let timestamp_secs: i64 = reader.read()?;
+        let (question_japanese, question_hiragana) = match questions {
+            Some(table) => {
+                let idx: u32 = reader.read()?;
+                table
+                    .get(idx as usize)
+                    .cloned()
+                    .ok_or(DecodeError::Other("question index out of range"))?
+            }
+            None => (reader.read()?, reader.read()?),
+        };
+        Ok(Self {
+            timestamp: record_timestamp(timestamp_secs)?,
+            question_japanese,
+            question_hiragana,
+            total_chars: reader.read()?,
+            duration_sec: reader.read()?,
+            misses: reader.read()?,
+            cps: reader.read()?,
+            score: reader.read()?,
+            xp_gained: reader.read()?,
+            key_remap: reader.read_or(default_key_remap())?,
+            afk_pauses: reader.read()?,
+            components: reader.read()?,
+            question_id: reader.read()?,
+            warmup: reader.read()?,
+            after_cooldown: reader.read()?,
+            time_attack: reader.read()?,
+            keystrokes: reader.read()?,
+            imported: reader.read()?,
+            timing: reader.read()?,
+            governor_ms: reader.read()?,
+            error_loss_ms: reader.read()?,
+            unit_misses: reader.read()?,
+            intent: reader.read()?,
+            code: reader.read()?,
+            pace: reader.read()?,
+            outcome: reader.read()?,
+            gloss: reader.read()?,
+        })
     }
 }
 
@@ -80,43 +401,143 @@ impl From<TypeRecordBin> for TypeRecord {
 pub struct PlayerData {
     pub level: u32,
     pub current_xp: u32,
-    pub total_typed_chars: u32,
-    pub total_misses: u32,
+    /// 累計タイプ数（u32 から拡張。varint なので旧バイナリもそのまま読める）
+    pub total_typed_chars: u64,
+    /// 累計ミス数（同上）
+    pub total_misses: u64,
+    /// 累計練習時間（秒）。お題の入力中の時間だけを数え、一時停止やメニューの時間は含まない
+    #[serde(default)]
+    pub total_practice_secs: u64,
+    /// 出題しないお題（ブラックリスト）
+    #[serde(default)]
+    pub blacklist: Vec<QuestionId>,
+    /// お題に付けたメモ
+    #[serde(default)]
+    pub notes: Vec<QuestionNote>,
+    /// 入手元ごとの経験値の台帳
+    #[serde(default)]
+    pub xp_ledger: XpLedger,
+    /// 達成した実績
+    #[serde(default)]
+    pub achievements: Vec<EarnedAchievement>,
+    /// お題ごとの出題回数（出題の偏りの確認と、出題の少ないお題の優先に使う）
+    #[serde(default)]
+    pub serve_counts: HashMap<QuestionId, u32>,
+    /// お気に入りに入れたお題（お題が消えても残しておく）
+    #[serde(default)]
+    pub bookmarks: Vec<QuestionId>,
+    /// 落ち物ゲームのハイスコア表（消した単語の多い順）
+    #[serde(default)]
+    pub falling_scores: Vec<FallingScore>,
+    /// ミニゲームで打った分の累計（累計タイプ数・ミス数・練習時間にも含まれている）
+    #[serde(default)]
+    pub mini_game: MiniGameTotals,
+    /// キーの取り違えの表（打つべきキー → 押したキー）
+    #[serde(default)]
+    pub confusions: ConfusionMatrix,
+    /// 休憩を取った記録
+    #[serde(default)]
+    pub breaks: Vec<BreakEvent>,
     /// 過去のタイピング記録
     pub history: Vec<TypeRecord>,
+    /// お題ごとの集計表のキャッシュ（保存しない）
+    #[serde(skip)]
+    aggregate_cache: AggregateCache,
+    /// 打鍵数の帯ごとの成績の分布のキャッシュ（保存しない）
+    #[serde(skip)]
+    percentile_cache: HistoryCache<PercentileTable>,
 }
 
-/// bincode用の内部表現
+/// 旧形式（ヘッダなし）のバイナリ表現。読み込み時の移行専用
 #[derive(Encode, Decode)]
-struct PlayerDataBin {
+struct LegacyPlayerDataBin {
     level: u32,
     current_xp: u32,
     total_typed_chars: u32,
     total_misses: u32,
-    history: Vec<TypeRecordBin>,
+    history: Vec<LegacyTypeRecordBin>,
 }
 
-impl From<&PlayerData> for PlayerDataBin {
-    fn from(data: &PlayerData) -> Self {
+impl From<LegacyPlayerDataBin> for PlayerData {
+    fn from(bin: LegacyPlayerDataBin) -> Self {
+        // 日時の壊れた記録は読み飛ばす
+        let history: Vec<TypeRecord> = bin.history.into_iter().filter_map(|record| TypeRecord::try_from(record).ok()).collect();
         Self {
-            level: data.level,
-            current_xp: data.current_xp,
-            total_typed_chars: data.total_typed_chars,
-            total_misses: data.total_misses,
-            history: data.history.iter().map(TypeRecordBin::from).collect(),
+            level: bin.level,
+            current_xp: bin.current_xp,
+            total_typed_chars: u64::from(bin.total_typed_chars),
+            total_misses: u64::from(bin.total_misses),
+            total_practice_secs: practice_secs_from_history(&history),
+            blacklist: Vec::new(),
+            notes: Vec::new(),
+            xp_ledger: XpLedger::from_history(&history),
+            achievements: Vec::new(),
+            serve_counts: HashMap::new(),
+            bookmarks: Vec::new(),
+            falling_scores: Vec::new(),
+            mini_game: MiniGameTotals::default(),
+            confusions: ConfusionMatrix::default(),
+            breaks: Vec::new(),
+            history,
+            aggregate_cache: AggregateCache::default(),
+            percentile_cache: HistoryCache::default(),
         }
     }
 }
 
-impl From<PlayerDataBin> for PlayerData {
-    fn from(bin: PlayerDataBin) -> Self {
-        Self {
-            level: bin.level,
-            current_xp: bin.current_xp,
-            total_typed_chars: bin.total_typed_chars,
-            total_misses: bin.total_misses,
-            history: bin.history.into_iter().map(TypeRecord::from).collect(),
+// --------------------------------------------------
+// MARK:バイナリのフィールド単位の読み書き
+// --------------------------------------------------
+
+/// フィールドを順番にバイナリへ書き出す
+struct FieldWriter {
+    buf: Vec<u8>,
+}
+
+impl FieldWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    fn write<T: Encode>(&mut self, value: &T) -> Result<(), EncodeError> {
+        let bytes = bincode::encode_to_vec(value, standard())?;
+        self.buf.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// フィールドを順番にバイナリから読み出す
+/// データを読み切った後のフィールドはデフォルト値になるので、
+/// 末尾に追加されたフィールドも古いセーブからそのまま読める
+struct FieldReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// 次のフィールドを読む。データを読み切っていれば None（古いセーブで値を補うとき用）
+    fn read_opt<T: Decode<()>>(&mut self) -> Result<Option<T>, DecodeError> {
+        if self.buf.is_empty() {
+            return Ok(None);
         }
+        let (value, len) = bincode::decode_from_slice::<T, _>(self.buf, standard())?;
+        self.buf = &self.buf[len..];
+        Ok(Some(value))
+    }
+
+    fn read_or<T: Decode<()>>(&mut self, default: T) -> Result<T, DecodeError> {
+        Ok(self.read_opt()?.unwrap_or(default))
+    }
+
+    fn read<T: Decode<()> + Default>(&mut self) -> Result<T, DecodeError> {
+        self.read_or(T::default())
     }
 }
 
@@ -128,31 +549,66 @@ impl Default for PlayerData {
             current_xp: 0,
             total_typed_chars: 0,
             total_misses: 0,
+            total_practice_secs: 0,
+            blacklist: Vec::new(),
+            notes: Vec::new(),
+            xp_ledger: XpLedger::default(),
+            achievements: Vec::new(),
+            serve_counts: HashMap::new(),
+            bookmarks: Vec::new(),
+            falling_scores: Vec::new(),
+            mini_game: MiniGameTotals::default(),
+            confusions: ConfusionMatrix::default(),
+            breaks: Vec::new(),
             history: Vec::new(),
+            aggregate_cache: AggregateCache::default(),
+            percentile_cache: HistoryCache::default(),
         }
     }
 }
 
-impl PlayerData {
-    // MARK:セーブファイルのパスを取得する関数
-    fn get_save_file_path() -> PathBuf {
-        // "jp" (国), "MySchool" (組織名), "TypingGame" (アプリ名)
-        // 組織名は適当でOKですが、ユニークな名前空間を作るために使われます
-        if let Some(proj_dirs) = ProjectDirs::from("jp", "Fukumoto0141", "TYPE_WIZ") {
-            // OSごとのデータ保存用ディレクトリパスを取得
-            let data_dir = proj_dirs.data_dir();
-
-            // ディレクトリがまだなければ作成する（これ重要！）
-            if !data_dir.exists() {
-                fs::create_dir_all(data_dir).expect("データディレクトリの作成に失敗しました");
+// MARK:データ保存用ディレクトリを取得する関数
+/// 一度確かめたデータディレクトリ（ネットワークドライブでは確かめるたびに待たされるので、2回目からは見に行かない）
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn get_data_dir() -> PathBuf {
+    DATA_DIR
+        .get_or_init(|| {
+            // テストでは本物のデータフォルダに触れない（実行ごとの一時フォルダを使う）
+            if cfg!(test) {
+                let dir = std::env::temp_dir().join(format!("typewiz-test-data-{}", std::process::id()));
+                let _ = fs::create_dir_all(&dir);
+                return dir;
             }
+            // "jp" (国), "MySchool" (組織名), "TypingGame" (アプリ名)
+            // 組織名は適当でOKですが、ユニークな名前空間を作るために使われます
+            if let Some(proj_dirs) = ProjectDirs::from("jp", "Fukumoto0141", "TYPE_WIZ") {
+                // OSごとのデータ保存用ディレクトリパスを取得
+                let data_dir = proj_dirs.data_dir();
 
-            // パスとファイル名を結合して返す
-            return data_dir.join("save_data.bin");
-        }
+                // ディレクトリがまだなければ作成する（これ重要！）
+                if !data_dir.exists() {
+                    fs::create_dir_all(data_dir).expect("データディレクトリの作成に失敗しました");
+                }
+
+                return data_dir.to_path_buf();
+            }
+
+            // 万が一取得できなかったらカレントディレクトリに（フォールバック）
+            PathBuf::from(".")
+        })
+        .clone()
+}
+
+impl PlayerData {
+    // MARK:セーブファイルのパスを取得する関数
+    pub fn get_save_file_path() -> PathBuf {
+        get_data_dir().join("save_data.bin")
+    }
 
-        // 万が一取得できなかったらカレントディレクトリに（フォールバック）
-        PathBuf::from("save_data.bin")
+    // MARK:デバッグ用 JSON のパスを取得する関数
+    pub fn get_debug_json_path() -> PathBuf {
+        get_data_dir().join(SAVE_FILE_JSON)
     }
 
     /// 次のレベルまでに必要な経験値を計算する
@@ -162,70 +618,1332 @@ impl PlayerData {
 
     /// 経験値を加算し、レベルアップ判定を行う
     // `xp_to_add` (獲得XP) と `chars_typed` (タイプ文字数) を別々に受け取る
-    pub fn add_xp(&mut self, xp_to_add: u32, chars_typed: u32) -> bool {
-        self.current_xp += xp_to_add;
-        self.total_typed_chars += chars_typed; // 累計タイプ数も加算
+    pub fn add_xp(&mut self, source: XpSource, xp_to_add: u32, chars_typed: u32) -> bool {
+        // 累計タイプ数も加算（桁あふれはせず上限で止める）
+        self.total_typed_chars = self.total_typed_chars.saturating_add(u64::from(chars_typed));
+        self.xp_ledger.add(source, u64::from(xp_to_add));
+        self.gain_xp(u64::from(xp_to_add))
+    }
+
+    /// 今の経験値で上がれるだけレベルを上げる（上がったら true）
+    /// 再計算や取り込みのあとは、必要経験値を超えた経験値が残っていることがあるので読み込み後などに呼ぶ
+    pub fn normalize(&mut self) -> bool {
+        self.gain_xp(0)
+    }
+
+    /// まだ反映していないレベルアップがあるか（経験値が次のレベルの必要経験値に届いている）
+    pub fn level_pending(&self) -> bool {
+        self.level < u32::MAX && self.current_xp >= self.required_xp_for_next_level()
+    }
+
+    /// 経験値を加算し、溜まった分だけレベルを上げる
+    fn gain_xp(&mut self, xp_to_add: u64) -> bool {
+        let mut xp = u64::from(self.current_xp).saturating_add(xp_to_add);
 
         let mut leveled_up = false;
         // 必要経験値を超えている間、レベルを上げ続ける
-        while self.current_xp >= self.required_xp_for_next_level() {
-            self.current_xp -= self.required_xp_for_next_level();
+        while self.level < u32::MAX && xp >= u64::from(self.required_xp_for_next_level()) {
+            xp -= u64::from(self.required_xp_for_next_level());
             self.level += 1;
             leveled_up = true;
         }
+        self.current_xp = u32::try_from(xp).unwrap_or(u32::MAX);
         leveled_up
     }
 
+    /// ミニゲーム1回分の成績を累計に加える（履歴には残さないので、再計算用に別の累計にも加える）
+    pub fn add_mini_game_run(&mut self, xp: u32, typed: u32, misses: u32, duration_sec: f64) -> bool {
+        let secs = duration_sec.max(0.0).round() as u64;
+        self.mini_game.typed_chars = self.mini_game.typed_chars.saturating_add(u64::from(typed));
+        self.mini_game.misses = self.mini_game.misses.saturating_add(u64::from(misses));
+        self.mini_game.practice_secs = self.mini_game.practice_secs.saturating_add(secs);
+        self.total_misses = self.total_misses.saturating_add(u64::from(misses));
+        self.total_practice_secs = self.total_practice_secs.saturating_add(secs);
+        self.add_xp(XpSource::MiniGame, xp, typed)
+    }
+
+    /// お題1問分の入力時間を累計練習時間に加える
+    pub fn add_practice_time(&mut self, duration_sec: f64) {
+        self.total_practice_secs = self
+            .total_practice_secs
+            .saturating_add(duration_sec.max(0.0).round() as u64);
+    }
+
+    /// お題をブラックリストに入れる / 外す。入れたら true
+    pub fn toggle_blacklist(&mut self, id: QuestionId) -> bool {
+        if let Some(pos) = self.blacklist.iter().position(|&b| b == id) {
+            self.blacklist.remove(pos);
+            false
+        } else {
+            self.blacklist.push(id);
+            true
+        }
+    }
+
+    /// お題をお気に入りに入れる / 外す。入れたら true
+    pub fn toggle_bookmark(&mut self, id: QuestionId) -> bool {
+        if let Some(pos) = self.bookmarks.iter().position(|&b| b == id) {
+            self.bookmarks.remove(pos);
+            false
+        } else {
+            self.bookmarks.push(id);
+            true
+        }
+    }
+
+    pub fn is_bookmarked(&self, id: QuestionId) -> bool {
+        self.bookmarks.contains(&id)
+    }
+
+    /// 落ち物ゲームの結果をハイスコア表に入れる。表に入ったら順位（0 始まり）
+    /// 同じ数なら先に出した記録を上にする（1語も消していない回は入れない）
+    pub fn record_falling_score(&mut self, score: FallingScore) -> Option<usize> {
+        if score.words == 0 {
+            return None;
+        }
+        let rank = self
+            .falling_scores
+            .iter()
+            .position(|entry| score.words > entry.words)
+            .unwrap_or(self.falling_scores.len());
+        if rank >= HIGH_SCORE_SLOTS {
+            return None;
+        }
+        self.falling_scores.insert(rank, score);
+        self.falling_scores.truncate(HIGH_SCORE_SLOTS);
+        Some(rank)
+    }
+
+    /// お題のメモ
+    pub fn note(&self, id: QuestionId) -> Option<&str> {
+        self.notes
+            .iter()
+            .find(|note| note.question_id == id)
+            .map(|note| note.text.as_str())
+    }
+
+    /// お題のメモを設定する（空文字列なら削除する）
+    pub fn set_note(&mut self, id: QuestionId, text: String) {
+        self.notes.retain(|note| note.question_id != id);
+        if !text.is_empty() {
+            self.notes.push(QuestionNote { question_id: id, text });
+        }
+    }
+
+    /// 実績を達成済みか
+    pub fn has_achievement(&self, id: &str) -> bool {
+        self.achievements.iter().any(|earned| earned.id == id)
+    }
+
+    /// 実績を達成済みとして記録する（達成済みなら何もしない）
+    pub fn award_achievement(&mut self, id: &str, at: DateTime<Utc>) {
+        if !self.has_achievement(id) {
+            self.achievements.push(EarnedAchievement { id: id.to_string(), earned_at: at.timestamp() });
+        }
+    }
+
+    /// お題を1回出題したことを記録する
+    pub fn record_serve(&mut self, id: QuestionId) {
+        let count = self.serve_counts.entry(id).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// 取り込んだ記録を時刻順に履歴へ加え、累計値にも足す（経験値は与えない）
+    /// 同じ時刻の記録がすでにあるもの（同じファイルを2回取り込んだときなど）は加えない。加えた件数を返す
+    pub fn merge_imported(&mut self, records: Vec<TypeRecord>) -> usize {
+        let mut seen: HashSet<DateTime<Utc>> = self.history.iter().map(|r| r.timestamp).collect();
+        let before = self.history.len();
+        for record in records {
+            if !seen.insert(record.timestamp) {
+                continue;
+            }
+            self.total_typed_chars = self.total_typed_chars.saturating_add(u64::from(record.total_chars));
+            self.total_misses = self.total_misses.saturating_add(u64::from(record.misses));
+            self.total_practice_secs =
+                self.total_practice_secs.saturating_add(record.duration_sec.max(0.0).round() as u64);
+            self.history.push(record);
+        }
+        // 取り込んだ記録は既存の記録より古いことが多いので並べ直す（安定ソートなので既存の記録の順序は変わらない）
+        self.history.sort_by_key(|r| r.timestamp);
+        self.normalize();
+        self.history.len() - before
+    }
+
+    /// 同じ記録が重なっているもの（同じバックアップを2回復元したときなど）を1件だけ残して取り除く
+    /// 時刻（秒まで）・読み・打鍵数・ミス数がすべて同じ記録を同じものとみなし、最初の1件を残す
+    /// 取り除いた記録を返す（レベルや累計値は変えないので、呼び出し側で `recomputed` すること）
+    pub fn remove_duplicate_records(&mut self) -> Vec<TypeRecord> {
+        let mut seen: HashSet<(i64, String, u32, u32)> = HashSet::new();
+        let (kept, removed): (Vec<TypeRecord>, Vec<TypeRecord>) =
+            std::mem::take(&mut self.history).into_iter().partition(|record| {
+                seen.insert((
+                    record.timestamp.timestamp(),
+                    record.question_hiragana.clone(),
+                    record.total_chars,
+                    record.misses,
+                ))
+            });
+        self.history = kept;
+        removed
+    }
+
+    /// ID のない過去の記録に、文字列が一致する組み込みのお題の ID を割り当てる
+    /// 日本語と読みの両方が一致するものを優先し、なければ読みだけで探す
+    fn assign_question_ids(&mut self) {
+        let needs_id =
+            |record: &TypeRecord| record.question_id.is_none() && !record.is_sentence() && !record.imported;
+        if !self.history.iter().any(needs_id) {
+            return;
+        }
+
+        let mut by_text = HashMap::new();
+        let mut by_hiragana = HashMap::new();
+        for (idx, question) in QUESTIONS_LIST.iter().enumerate() {
+            let id = QuestionId::builtin(idx);
+            by_text.entry((question.japanese, question.hiragana)).or_insert(id);
+            by_hiragana.entry(question.hiragana).or_insert(id);
+        }
+
+        for record in self.history.iter_mut().filter(|record| needs_id(record)) {
+            let text = (record.question_japanese.as_str(), record.question_hiragana.as_str());
+            record.question_id = by_text
+                .get(&text)
+                .or_else(|| by_hiragana.get(record.question_hiragana.as_str()))
+                .copied();
+        }
+    }
+
+    /// お題ごとの集計表（履歴が増えるか、正確率の下限 (%) を変えるまではキャッシュを返す）
+    pub fn question_aggregates(&self, accuracy_floor: f64) -> Arc<QuestionAggregates> {
+        self.aggregate_cache.get_or_build_keyed(&self.history, accuracy_floor.to_bits(), |history| {
+            build_question_aggregates(history, accuracy_floor)
+        })
+    }
+
+    /// 打鍵数の帯ごとの成績の分布（履歴が増えるまではキャッシュを返す）
+    pub fn percentiles(&self) -> Arc<PercentileTable> {
+        self.percentile_cache.get_or_build(&self.history, PercentileTable::from_history)
+    }
+
+    /// 直近 `count` 件の平均 CPS（記録がなければ None）
+    pub fn average_cps(&self, count: usize) -> Option<f64> {
+        let recent: Vec<f64> = self.history.iter().rev().take(count).map(|r| r.cps).collect();
+        if recent.is_empty() {
+            None
+        } else {
+            Some(recent.iter().sum::<f64>() / recent.len() as f64)
+        }
+    }
+
+    /// 履歴を正として、レベル・経験値・累計値を再計算したデータを返す
+    pub fn recomputed(&self) -> PlayerData {
+        // お題以外（連続ボーナスなど）の経験値は台帳から加える
+        let total_xp = self
+            .history
+            .iter()
+            .fold(0u64, |acc, r| acc.saturating_add(u64::from(r.xp_gained)))
+            .saturating_add(self.xp_ledger.bonus_total());
+
+        let mut data = PlayerData {
+            // ミニゲームの分は履歴にないので別に持っている累計から加える
+            total_typed_chars: self
+                .history
+                .iter()
+                .fold(self.mini_game.typed_chars, |acc, r| acc.saturating_add(u64::from(r.total_chars))),
+            total_misses: self
+                .history
+                .iter()
+                .fold(self.mini_game.misses, |acc, r| acc.saturating_add(u64::from(r.misses))),
+            total_practice_secs: practice_secs_from_history(&self.history).saturating_add(self.mini_game.practice_secs),
+            blacklist: self.blacklist.clone(),
+            notes: self.notes.clone(),
+            xp_ledger: self.xp_ledger.rebuilt_from_history(&self.history),
+            achievements: self.achievements.clone(),
+            serve_counts: self.serve_counts.clone(),
+            bookmarks: self.bookmarks.clone(),
+            falling_scores: self.falling_scores.clone(),
+            mini_game: self.mini_game,
+            confusions: self.confusions.clone(),
+            breaks: self.breaks.clone(),
+            history: self.history.clone(),
+            ..PlayerData::default()
+        };
+        // 合計の経験値を一度に加えるので、レベルもここで上がり切る
+        data.gain_xp(total_xp);
+        data
+    }
+
+    /// セーブデータ本体をバイナリに変換する（マジックナンバーとバージョンは含まない）
+    /// ヘッダ部のフレームに続いて、記録が1件ずつフレームとして並ぶ
+    /// ※ヘッダ部のフィールドを増やすときは必ず末尾に追加すること
+    fn encode_bin(&self) -> Result<Vec<u8>, EncodeError> {
+        // 同じお題の文字列は表に1回だけ登録し、記録からは番号で参照する
+        let mut questions: Vec<(&str, &str)> = Vec::new();
+        let mut question_index: HashMap<(&str, &str), u32> = HashMap::new();
+        let mut history = Vec::with_capacity(self.history.len());
+        for record in &self.history {
+            let key = (record.question_japanese.as_str(), record.question_hiragana.as_str());
+            let idx = *question_index.entry(key).or_insert_with(|| {
+                questions.push(key);
+                (questions.len() - 1) as u32
+            });
+            history.push(record.encode_bin(idx)?);
+        }
+
+        let mut writer = FieldWriter::new();
+        writer.write(&self.level)?;
+        writer.write(&self.current_xp)?;
+        writer.write(&self.total_typed_chars)?;
+        writer.write(&self.total_misses)?;
+        writer.write(&questions)?;
+        writer.write(&self.total_practice_secs)?;
+        writer.write(&self.blacklist)?;
+        writer.write(&self.notes)?;
+        writer.write(&self.xp_ledger)?;
+        writer.write(&self.achievements)?;
+        writer.write(&self.serve_counts)?;
+        writer.write(&self.bookmarks)?;
+        writer.write(&self.falling_scores)?;
+        writer.write(&self.mini_game)?;
+        writer.write(&self.confusions)?;
+        writer.write(&self.breaks)?;
+
+        let mut out = Vec::new();
+        write_frame(&mut out, FRAME_KIND_HEADER, &writer.into_bytes());
+        for record in &history {
+            write_frame(&mut out, FRAME_KIND_RECORD, record);
+        }
+        Ok(out)
+    }
+
+    /// チェックサム付きフレーム形式 (v4〜) のセーブデータ本体を復元する
+    /// 壊れた記録は読み飛ばし、その位置（本体先頭からのバイト位置）を返す
+    fn decode_framed(bytes: &[u8]) -> Result<(Self, Vec<usize>), DecodeError> {
+        let (frames, mut dropped) = scan_frames(bytes);
+
+        let header = frames
+            .iter()
+            .find(|frame| frame.kind == FRAME_KIND_HEADER)
+            .ok_or(DecodeError::Other("save header is corrupted"))?;
+        let mut reader = FieldReader::new(header.payload);
+        let level = reader.read_or(1)?;
+        let current_xp = reader.read()?;
+        let total_typed_chars = reader.read()?;
+        let total_misses = reader.read()?;
+        let questions: Vec<(String, String)> = reader.read()?;
+        let total_practice_secs: Option<u64> = reader.read_opt()?;
+        let blacklist = reader.read()?;
+        let notes = reader.read()?;
+        let xp_ledger: Option<XpLedger> = reader.read_opt()?;
+        let achievements = reader.read()?;
+        let serve_counts = reader.read()?;
+        let bookmarks = reader.read()?;
+        let falling_scores = reader.read()?;
+        let mini_game = reader.read()?;
+        let confusions = reader.read()?;
+        let breaks = reader.read()?;
+
+        let mut history = Vec::new();
+        for frame in frames.iter().filter(|frame| frame.kind == FRAME_KIND_RECORD) {
+            match TypeRecord::decode_bin(frame.payload, Some(questions.as_slice())) {
+                Ok(record) => history.push(record),
+                Err(_) => dropped.push(frame.offset),
+            }
+        }
+        dropped.sort_unstable();
+
+        let data = Self {
+            level,
+            current_xp,
+            total_typed_chars,
+            total_misses,
+            // 累計練習時間がない古いセーブは履歴から補う
+            total_practice_secs: total_practice_secs
+                .unwrap_or_else(|| practice_secs_from_history(&history)),
+            blacklist,
+            notes,
+            // 台帳がない古いセーブは履歴のお題の経験値から作る
+            xp_ledger: xp_ledger.unwrap_or_else(|| XpLedger::from_history(&history)),
+            achievements,
+            serve_counts,
+            bookmarks,
+            falling_scores,
+            mini_game,
+            confusions,
+            breaks,
+            history,
+            aggregate_cache: AggregateCache::default(),
+            percentile_cache: HistoryCache::default(),
+        };
+        Ok((data, dropped))
+    }
+
+    /// ヘッダ部の読めないチェックサム付きフレーム形式から、記録だけを取り出す
+    /// お題の表もないので、お題の文字列は記録のお題 ID から組み込みのお題を引いて補う（引けなければ空のまま）
+    /// レベル・累計値は履歴から計算し直す。メモやブラックリストなど、ヘッダ部にしかないものは失われる
+    fn recover_records(bytes: &[u8]) -> (Self, Vec<usize>) {
+        let (frames, mut dropped) = scan_frames(bytes);
+        // チェックサムは合っていても中身を読めなかったヘッダ部も、壊れた箇所として数える
+        dropped.extend(frames.iter().filter(|frame| frame.kind == FRAME_KIND_HEADER).map(|frame| frame.offset));
+
+        // お題の表は記録の件数より大きくならないので、それを超える番号は壊れているとみなす
+        let records: Vec<&Frame> = frames.iter().filter(|frame| frame.kind == FRAME_KIND_RECORD).collect();
+        let table_len = records
+            .iter()
+            .filter_map(|frame| question_index(frame.payload))
+            .filter(|&idx| (idx as usize) < records.len())
+            .max()
+            .map_or(0, |idx| idx as usize + 1);
+        let placeholder = vec![(String::new(), String::new()); table_len];
+
+        let mut history = Vec::new();
+        for frame in records {
+            match TypeRecord::decode_bin(frame.payload, Some(placeholder.as_slice())) {
+                Ok(mut record) => {
+                    if let Some(question) = record.question_id.and_then(find_question) {
+                        record.question_japanese = question.japanese.to_string();
+                        record.question_hiragana = question.hiragana.to_string();
+                    }
+                    history.push(record);
+                }
+                Err(_) => dropped.push(frame.offset),
+            }
+        }
+        dropped.sort_unstable();
+
+        let data = PlayerData { history, ..PlayerData::default() }.recomputed();
+        (data, dropped)
+    }
+
+    /// フレーム化される前 (v2, v3) のセーブデータ本体を復元する
+    fn decode_bin(bytes: &[u8], version: u32) -> Result<Self, DecodeError> {
+        let mut reader = FieldReader::new(bytes);
+        let level = reader.read_or(1)?;
+        let current_xp = reader.read()?;
+        let total_typed_chars = reader.read()?;
+        let total_misses = reader.read()?;
+        let questions: Option<Vec<(String, String)>> = if version >= FORMAT_INTERNED_QUESTIONS {
+            Some(reader.read()?)
+        } else {
+            None
+        };
+        let history: Vec<Vec<u8>> = reader.read()?;
+        // 読めない記録（日時が壊れているなど）は読み飛ばし、残りの記録は捨てない
+        let history: Vec<TypeRecord> = history
+            .iter()
+            .filter_map(|bytes| match TypeRecord::decode_bin(bytes, questions.as_deref()) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    dlog!("save", "skipped an unreadable v{} record: {}", version, e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            level,
+            current_xp,
+            total_typed_chars,
+            total_misses,
+            total_practice_secs: practice_secs_from_history(&history),
+            blacklist: Vec::new(),
+            notes: Vec::new(),
+            xp_ledger: XpLedger::from_history(&history),
+            achievements: Vec::new(),
+            serve_counts: HashMap::new(),
+            bookmarks: Vec::new(),
+            falling_scores: Vec::new(),
+            mini_game: MiniGameTotals::default(),
+            confusions: ConfusionMatrix::default(),
+            breaks: Vec::new(),
+            history,
+            aggregate_cache: AggregateCache::default(),
+            percentile_cache: HistoryCache::default(),
+        })
+    }
+
+    /// ファイルの中身（ヘッダ付き / 旧形式）を判別して復元する
+    fn decode_file(buffer: &[u8]) -> Option<(Self, IntegrityReport)> {
+        // ヘッダ付きの現行形式
+        if buffer.len() >= 8 && &buffer[..4] == SAVE_MAGIC {
+            let version = u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+            if version > SAVE_FORMAT_VERSION {
+                // 新しいバージョンで保存されたデータは読めない
+                return None;
+            }
+            let mut report = IntegrityReport::new(&format!("v{}", version));
+            let body = &buffer[8..];
+            if version >= FORMAT_CHECKSUMMED_FRAMES {
+                let (data, dropped) = match Self::decode_framed(body) {
+                    Ok(decoded) => decoded,
+                    // ヘッダ部が読めなくても記録は捨てない（空のデータで上書きされないように）
+                    Err(e) => {
+                        dlog!("save", "header unreadable ({}); recovering records only", e);
+                        report.header_rebuilt = true;
+                        Self::recover_records(body)
+                    }
+                };
+                report.dropped_offsets = dropped.into_iter().map(|offset| offset + 8).collect();
+                return Some((data, report));
+            }
+            return Self::decode_bin(body, version).ok().map(|data| (data, report));
+        }
+
+        // ヘッダなしの旧形式 (v0.1.3 以前)
+        bincode::decode_from_slice::<LegacyPlayerDataBin, _>(buffer, standard())
+            .ok()
+            .map(|(bin_data, _)| (PlayerData::from(bin_data), IntegrityReport::new("legacy")))
+    }
+
     /// MARK:データをファイルに保存する (バイナリ + JSON)
+    /// ※ `storage::FileStorage` から使う（他の場所からは保存先のトレイト越しに保存する）
     pub fn save(&self) {
+        if let Err(e) = self.try_save() {
+            dlog!("save", "failed to write {}: {}", Self::get_save_file_path().display(), e);
+        }
+    }
+
+    /// 保存して、バイナリを書けなかったときはエラーを返す（書き直すかは呼び出し側が決める）
+    pub fn try_save(&self) -> std::io::Result<()> {
         let path = Self::get_save_file_path(); // ← パスを取得
 
         // --- 1. バイナリ形式で保存 (本番用) ---
-        if let Ok(file) = File::create(&path) {
-            let mut writer = BufWriter::new(file);
-            let config = standard();
-            let bin_data = PlayerDataBin::from(self);
-            if let Ok(encoded) = bincode::encode_to_vec(&bin_data, config) {
-                let _ = writer.write_all(&encoded);
-            }
-        }
+        let encoded = self.encode_bin().map_err(std::io::Error::other)?;
+        let mut bytes = Vec::with_capacity(encoded.len() + 8);
+        bytes.extend_from_slice(SAVE_MAGIC);
+        bytes.extend_from_slice(&SAVE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&encoded);
+        write_atomic(&path, &bytes)?;
+        dlog!("save", "wrote {} bytes records={} to {}", bytes.len(), self.history.len(), path.display());
 
-        // --- 2. JSON形式で保存 (デバッグ用) ---
-        if let Ok(json) = serde_json::to_string_pretty(self) {
-            let _ = fs::write(SAVE_FILE_JSON, json);
+        // --- 2. JSON形式で保存 (デバッグ用。設定で有効なときだけ) ---
+        if JSON_MIRROR_ENABLED.load(Ordering::Relaxed) && let Ok(json) = json_pretty(self) {
+            let _ = write_atomic(&Self::get_debug_json_path(), json.as_bytes());
         }
+        Ok(())
     }
 
-    /// MARK:ファイルからデータを読み込む (バイナリ優先、JSONフォールバック)
-    pub fn load() -> Self {
+    /// MARK:ファイルからデータを読み込み、整合性チェックの結果も返す (バイナリ優先、JSONフォールバック)
+    /// ※ `storage::FileStorage` から使う
+    pub fn load_with_report() -> (Self, IntegrityReport) {
         let path = Self::get_save_file_path(); // ← パスを取得
 
         // 1. バイナリファイルから読み込みを試行
+        let mut unreadable_backup = None;
         if Path::new(&path).exists() {
-            if let Ok(mut file) = File::open(&path) {
-                let mut buffer = Vec::new();
-                if file.read_to_end(&mut buffer).is_ok() {
-                    let config = standard();
-                    if let Ok((bin_data, _)) =
-                        bincode::decode_from_slice::<PlayerDataBin, _>(&buffer, config)
-                    {
-                        return PlayerData::from(bin_data);
-                    }
-                }
+            let mut buffer = Vec::new();
+            let read = File::open(&path).and_then(|mut file| file.read_to_end(&mut buffer));
+            if read.is_ok()
+                && let Some((mut data, report)) = Self::decode_file(&buffer)
+            {
+                data.assign_question_ids();
+                data.normalize();
+                return (data, report);
             }
+            // 読めないファイルは、次の保存で上書きされる前にコピーを残す
+            unreadable_backup = backup_unreadable(&path);
         }
 
         // 2. バイナリ失敗時、JSONファイルから読み込みを試行 (古いセーブデータからの移行用)
-        if Path::new(SAVE_FILE_JSON).exists() {
-            if let Ok(file) = File::open(SAVE_FILE_JSON) {
+        //    データディレクトリ → 旧バージョンの保存先 (カレントディレクトリ) の順
+        for json_path in [Self::get_debug_json_path(), PathBuf::from(SAVE_FILE_JSON)] {
+            if let Ok(file) = File::open(&json_path) {
                 let reader = BufReader::new(file);
-                if let Ok(data) = serde_json::from_reader(reader) {
-                    return data;
+                if let Ok(mut data) = serde_json::from_reader::<_, Self>(reader) {
+                    if data.total_practice_secs == 0 {
+                        data.total_practice_secs = practice_secs_from_history(&data.history);
+                    }
+                    if data.xp_ledger.entries.is_empty() {
+                        data.xp_ledger = XpLedger::from_history(&data.history);
+                    }
+                    data.assign_question_ids();
+                    data.normalize();
+                    let mut report = IntegrityReport::new("json");
+                    report.unreadable_backup = unreadable_backup;
+                    return (data, report);
                 }
             }
         }
 
         // どちらも失敗した場合はデフォルト
-        Self::default()
+        let mut report = IntegrityReport::new("none");
+        report.unreadable_backup = unreadable_backup;
+        (Self::default(), report)
+    }
+}
+
+/// 読めなかったセーブファイルを、中身のチェックサムを付けた名前でコピーする（同じ中身のコピーがあればそれを返す）
+fn backup_unreadable(path: &Path) -> Option<PathBuf> {
+    let bytes = fs::read(path).ok()?;
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".unreadable-{:08x}", crc32(&bytes)));
+    let backup = PathBuf::from(backup);
+    if !backup.exists() {
+        if let Err(e) = fs::write(&backup, &bytes) {
+            dlog!("save", "failed to back up unreadable {}: {}", path.display(), e);
+            return None;
+        }
+        dlog!("save", "backed up unreadable {} to {}", path.display(), backup.display());
+    }
+    Some(backup)
+}
+
+/// 履歴の入力時間を合計した累計練習時間（秒）
+fn practice_secs_from_history(history: &[TypeRecord]) -> u64 {
+    history.iter().map(|r| r.duration_sec.max(0.0)).sum::<f64>().round() as u64
+}
+
+// --------------------------------------------------
+// MARK:チェックサム付きフレーム
+// --------------------------------------------------
+
+/// 読み出したフレーム
+struct Frame<'a> {
+    kind: u8,
+    /// 本体先頭からのバイト位置
+    offset: usize,
+    payload: &'a [u8],
+}
+
+/// CRC-32 (IEEE 802.3) を計算する
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// 記録のフレームから、お題の表の番号だけを読む（日時の次のフィールド）
+fn question_index(payload: &[u8]) -> Option<u32> {
+    let mut reader = FieldReader::new(payload);
+    let _timestamp: i64 = reader.read().ok()?;
+    reader.read().ok()
+}
+
+/// フレームを1つ書き出す
+fn write_frame(out: &mut Vec<u8>, kind: u8, payload: &[u8]) {
+    out.push(FRAME_MARKER);
+    out.push(kind);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&crc32(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// `pos` からフレームを1つ読む。目印・長さ・チェックサムのどれかが合わなければ None
+fn read_frame(buf: &[u8], pos: usize) -> Option<(Frame<'_>, usize)> {
+    let prefix = buf.get(pos..pos + FRAME_PREFIX_LEN)?;
+    if prefix[0] != FRAME_MARKER {
+        return None;
+    }
+    let kind = prefix[1];
+    let len = u32::from_le_bytes([prefix[2], prefix[3], prefix[4], prefix[5]]) as usize;
+    let checksum = u32::from_le_bytes([prefix[6], prefix[7], prefix[8], prefix[9]]);
+
+    let start = pos + FRAME_PREFIX_LEN;
+    let payload = buf.get(start..start.checked_add(len)?)?;
+    if crc32(payload) != checksum {
+        return None;
+    }
+    Some((Frame { kind, offset: pos, payload }, start + len))
+}
+
+/// 中身だけが壊れたフレームの並びを、先頭の長さをたどって読み飛ばす
+/// 次の正しいフレーム（またはデータの終わり）にちょうどたどり着けたら、途中のフレームの位置とその先の位置を返す
+fn damaged_run(buf: &[u8], mut pos: usize) -> Option<(Vec<usize>, usize)> {
+    let mut run = Vec::new();
+    while pos < buf.len() && read_frame(buf, pos).is_none() {
+        let prefix = buf.get(pos..pos + FRAME_PREFIX_LEN)?;
+        if prefix[0] != FRAME_MARKER || ![FRAME_KIND_HEADER, FRAME_KIND_RECORD].contains(&prefix[1]) {
+            return None;
+        }
+        let len = u32::from_le_bytes([prefix[2], prefix[3], prefix[4], prefix[5]]) as usize;
+        run.push(pos);
+        pos = pos.checked_add(FRAME_PREFIX_LEN + len).filter(|&end| end <= buf.len())?;
+    }
+    Some((run, pos))
+}
+
+/// 全フレームを読み出す
+/// 中身の壊れたフレームは1つずつその位置を記録して読み飛ばす
+/// 先頭まで壊れていてたどれない箇所はその位置を記録し、次に正しく読めるフレームまで1バイトずつ読み飛ばす
+fn scan_frames(buf: &[u8]) -> (Vec<Frame<'_>>, Vec<usize>) {
+    let mut frames = Vec::new();
+    let mut corrupted = Vec::new();
+    let mut pos = 0;
+
+    while pos < buf.len() {
+        if let Some((frame, next)) = read_frame(buf, pos) {
+            frames.push(frame);
+            pos = next;
+            continue;
+        }
+        if let Some((run, next)) = damaged_run(buf, pos) {
+            corrupted.extend(run);
+            pos = next;
+            continue;
+        }
+
+        corrupted.push(pos);
+        pos += 1;
+        while pos < buf.len() && read_frame(buf, pos).is_none() {
+            pos += 1;
+        }
+    }
+    (frames, corrupted)
+}
+
+#[cfg(test)]
+impl TypeRecord {
+    /// テスト用の記録（いま打ち終えたお題。CPS は打鍵数と時間から求める）
+    pub fn sample(hiragana: &str, total_chars: u32, duration_sec: f64, misses: u32) -> Self {
+        let cps = total_chars as f64 / duration_sec;
+        TypeRecord {
+            timestamp: Utc::now(),
+            question_japanese: hiragana.to_string(),
+            question_hiragana: hiragana.to_string(),
+            total_chars,
+            duration_sec,
+            misses,
+            cps,
+            score: cps * 100.0,
+            xp_gained: total_chars,
+            key_remap: "none".to_string(),
+            afk_pauses: 0,
+            components: Vec::new(),
+            question_id: None,
+            warmup: false,
+            after_cooldown: false,
+            time_attack: None,
+            keystrokes: 0,
+            imported: false,
+            timing: TimingPolicy::default(),
+            governor_ms: 0,
+            error_loss_ms: 0,
+            unit_misses: Vec::new(),
+            intent: SessionIntent::Unspecified,
+            code: false,
+            pace: Vec::new(),
+            outcome: QuestionOutcome::Completed,
+            gloss: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::questions::BUILTIN_PACK_ID;
+    use chrono::TimeDelta;
+
+    /// 組み込みのお題を順に打った記録を n 件作る（日時は秒単位。保存すると秒より細かい部分は落ちるので）
+    fn history(n: usize, distinct: usize) -> Vec<TypeRecord> {
+        (0..n)
+            .map(|i| {
+                let idx = i % distinct;
+                let question = &QUESTIONS_LIST[idx];
+                let mut record = TypeRecord::sample(question.hiragana, 10 + (i % 7) as u32, 2.0 + (i % 5) as f64, (i % 3) as u32);
+                record.timestamp = Utc.timestamp_opt(1_700_000_000 + i as i64 * 60, 0).unwrap();
+                record.question_japanese = question.japanese.to_string();
+                record.question_id = Some(QuestionId::new(BUILTIN_PACK_ID, idx));
+                record
+            })
+            .collect()
+    }
+
+    fn data_with(history: Vec<TypeRecord>) -> PlayerData {
+        PlayerData { history, ..PlayerData::default() }.recomputed()
+    }
+
+    /// 保存するときと同じ、ヘッダ付きのファイルの中身
+    fn file_bytes(data: &PlayerData) -> Vec<u8> {
+        let mut bytes = SAVE_MAGIC.to_vec();
+        bytes.extend_from_slice(&SAVE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&data.encode_bin().unwrap());
+        bytes
+    }
+
+    /// お題の文字列を記録ごとに持つ v2 形式のファイルの中身
+    fn flat_v2_bytes(data: &PlayerData) -> Vec<u8> {
+        flat_v2_bytes_with(data, |record| record.timestamp.timestamp())
+    }
+
+    /// v2 形式のファイルの中身（記録の日時の秒は `timestamp_of` で決める。壊れた日時を書くのに使う）
+    fn flat_v2_bytes_with(data: &PlayerData, timestamp_of: impl Fn(&TypeRecord) -> i64) -> Vec<u8> {
+        let records: Vec<Vec<u8>> = data
+            .history
+            .iter()
+            .map(|record| {
+                // 表の番号の代わりに文字列を書き、残りのフィールドは現行の書き出しをそのまま使う
+                let timestamp = bincode::encode_to_vec(record.timestamp.timestamp(), standard()).unwrap();
+                let interned = record.encode_bin(0).unwrap();
+                let rest = &interned[timestamp.len() + 1..];
+                let mut writer = FieldWriter::new();
+                writer.write(&timestamp_of(record)).unwrap();
+                writer.write(&record.question_japanese).unwrap();
+                writer.write(&record.question_hiragana).unwrap();
+                let mut bytes = writer.into_bytes();
+                bytes.extend_from_slice(rest);
+                bytes
+            })
+            .collect();
+        let mut writer = FieldWriter::new();
+        writer.write(&data.level).unwrap();
+        writer.write(&data.current_xp).unwrap();
+        writer.write(&data.total_typed_chars).unwrap();
+        writer.write(&data.total_misses).unwrap();
+        writer.write(&records).unwrap();
+        let mut bytes = SAVE_MAGIC.to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&writer.into_bytes());
+        bytes
+    }
+
+    fn json(history: &[TypeRecord]) -> serde_json::Value {
+        serde_json::to_value(history).unwrap()
+    }
+
+    /// 記録のフレームの (ファイル先頭からの位置, フレームの長さ)
+    fn record_frames(bytes: &[u8]) -> Vec<(usize, usize)> {
+        let (frames, corrupted) = scan_frames(&bytes[8..]);
+        assert!(corrupted.is_empty());
+        frames
+            .iter()
+            .filter(|frame| frame.kind == FRAME_KIND_RECORD)
+            .map(|frame| (frame.offset + 8, FRAME_PREFIX_LEN + frame.payload.len()))
+            .collect()
+    }
+
+    fn flipped(bytes: &[u8], at: usize) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        bytes[at] ^= 0x5A;
+        bytes
+    }
+
+    #[test]
+    fn a_large_interned_history_round_trips() {
+        let data = data_with(history(50_000, 22));
+        let bytes = file_bytes(&data);
+        let (loaded, report) = PlayerData::decode_file(&bytes).unwrap();
+
+        assert_eq!(report.source, format!("v{}", SAVE_FORMAT_VERSION));
+        assert!(report.dropped_offsets.is_empty());
+        assert_eq!(json(&loaded.history), json(&data.history));
+        assert_eq!((loaded.level, loaded.current_xp), (data.level, data.current_xp));
+    }
+
+    #[test]
+    fn question_text_is_stored_once_per_question() {
+        let short = data_with(history(50_000, 22));
+        let mut long = short.clone();
+        for record in &mut long.history {
+            record.question_japanese = record.question_japanese.repeat(10);
+            record.question_hiragana = record.question_hiragana.repeat(10);
+        }
+        // お題の文字列を長くしても、増えるのは表の分だけ（記録ごとには増えない）
+        let interned_growth = file_bytes(&long).len() - file_bytes(&short).len();
+        let flat_growth = flat_v2_bytes(&long).len() - flat_v2_bytes(&short).len();
+        assert!(interned_growth * 1000 < flat_growth, "interned +{} bytes vs flat +{} bytes", interned_growth, flat_growth);
+        assert!(file_bytes(&long).len() * 3 < flat_v2_bytes(&long).len());
+    }
+
+    #[test]
+    fn a_flat_v2_save_is_migrated() {
+        let data = data_with(history(100, 22));
+        let (loaded, report) = PlayerData::decode_file(&flat_v2_bytes(&data)).unwrap();
+        assert_eq!(report.source, "v2");
+        assert_eq!(json(&loaded.history), json(&data.history));
+
+        // 次に保存すると現行の形式になり、同じ内容で読める
+        let (resaved, _) = PlayerData::decode_file(&file_bytes(&loaded)).unwrap();
+        assert_eq!(json(&resaved.history), json(&data.history));
+    }
+
+    #[test]
+    fn xp_and_typed_totals_saturate_instead_of_overflowing() {
+        let mut data = PlayerData {
+            level: u32::MAX,
+            current_xp: u32::MAX - 5,
+            total_typed_chars: u64::MAX - 1,
+            ..PlayerData::default()
+        };
+        // 最高レベルでは経験値が上限で止まり、レベルも上がらない
+        assert!(!data.add_xp(XpSource::QuestionCompletion, u32::MAX, u32::MAX));
+        assert_eq!((data.level, data.current_xp, data.total_typed_chars), (u32::MAX, u32::MAX, u64::MAX));
+        assert!(!data.level_pending());
+
+        // 途中のレベルでは、上限近い経験値もレベルに変わって必要経験値の手前に収まる
+        let mut data = PlayerData::default();
+        assert!(data.add_xp(XpSource::QuestionCompletion, u32::MAX, 0));
+        assert!(data.add_xp(XpSource::QuestionCompletion, u32::MAX, 0));
+        assert!(data.current_xp < data.required_xp_for_next_level());
+        assert_eq!(data.xp_ledger.total(XpSource::QuestionCompletion), 2 * u64::from(u32::MAX));
+    }
+
+    #[test]
+    fn recompute_repairs_a_save_whose_ledger_history_and_level_disagree() {
+        let history = history(6, 3);
+        let question_xp: u64 = history.iter().map(|r| u64::from(r.xp_gained)).sum();
+        // 台帳には最初の2件しかなく、レベルと累計値は履歴と合わない
+        let mut ledger = XpLedger::from_history(&history[..2]);
+        ledger.add(XpSource::MissionReward, 25);
+        let inconsistent = PlayerData {
+            level: 40,
+            current_xp: 9_999,
+            total_typed_chars: 3,
+            total_misses: 999,
+            xp_ledger: ledger,
+            history: history.clone(),
+            ..PlayerData::default()
+        };
+
+        let recomputed = inconsistent.recomputed();
+        let mut expected = PlayerData::default();
+        expected.gain_xp(question_xp + 25);
+        assert_eq!((recomputed.level, recomputed.current_xp), (expected.level, expected.current_xp));
+        assert!(!recomputed.level_pending());
+        assert_eq!(recomputed.total_typed_chars, history.iter().map(|r| u64::from(r.total_chars)).sum::<u64>());
+        assert_eq!(recomputed.total_misses, history.iter().map(|r| u64::from(r.misses)).sum::<u64>());
+        // お題の経験値は履歴から作り直し、履歴にないミッションの報酬は残す
+        assert_eq!(recomputed.xp_ledger.total(XpSource::QuestionCompletion), question_xp);
+        assert_eq!(recomputed.xp_ledger.total(XpSource::MissionReward), 25);
+
+        let again = recomputed.recomputed();
+        assert_eq!((again.level, again.current_xp), (recomputed.level, recomputed.current_xp));
+    }
+
+    #[test]
+    fn old_saves_skip_records_with_an_out_of_range_timestamp() {
+        let data = data_with(history(4, 4));
+        let corrupt = data.history[1].timestamp;
+        let bytes = flat_v2_bytes_with(&data, |record| {
+            if record.timestamp == corrupt { i64::MAX } else { record.timestamp.timestamp() }
+        });
+        let (loaded, _) = PlayerData::decode_file(&bytes).unwrap();
+        let kept: Vec<_> = data.history.iter().filter(|r| r.timestamp != corrupt).cloned().collect();
+        assert_eq!(json(&loaded.history), json(&kept));
+
+        let legacy_record = |timestamp_secs| LegacyTypeRecordBin {
+            timestamp_secs,
+            question_japanese: "猫".to_string(),
+            question_hiragana: "ねこ".to_string(),
+            total_chars: 4,
+            duration_sec: 2.0,
+            misses: 0,
+            cps: 2.0,
+            score: 100.0,
+            xp_gained: 5,
+        };
+        let legacy = LegacyPlayerDataBin {
+            level: 1,
+            current_xp: 10,
+            total_typed_chars: 8,
+            total_misses: 0,
+            history: vec![legacy_record(1_700_000_000), legacy_record(i64::MIN)],
+        };
+        let bytes = bincode::encode_to_vec(&legacy, standard()).unwrap();
+        let (loaded, report) = PlayerData::decode_file(&bytes).unwrap();
+        assert_eq!(report.source, "legacy");
+        assert_eq!(loaded.history.len(), 1);
+        assert_eq!(loaded.history[0].timestamp.timestamp(), 1_700_000_000);
+    }
+
+    /// normalize のあとの状態が一貫しているか（最高レベル未満なら、経験値が次の必要経験値に届いていない）
+    fn assert_normalized(data: &PlayerData) {
+        assert!(data.level == u32::MAX || data.current_xp < data.required_xp_for_next_level(), "{} / {}", data.level, data.current_xp);
+        assert!(!data.level_pending());
+    }
+
+    #[test]
+    fn normalize_carries_over_xp_at_or_above_the_threshold() {
+        // ちょうど必要経験値
+        let mut data = PlayerData { level: 1, current_xp: 10, ..PlayerData::default() };
+        assert!(data.level_pending());
+        assert!(data.normalize());
+        assert_eq!((data.level, data.current_xp), (2, 0));
+        assert_normalized(&data);
+
+        // 何レベル分も溜まっている（1つずつ上げたときと同じ結果になる）
+        let mut data = PlayerData { level: 5, current_xp: 10_000, ..PlayerData::default() };
+        let mut stepped = PlayerData { level: 5, ..PlayerData::default() };
+        for _ in 0..10_000 {
+            stepped.gain_xp(1);
+        }
+        assert!(data.normalize());
+        assert_eq!((data.level, data.current_xp), (stepped.level, stepped.current_xp));
+        assert_normalized(&data);
+
+        // 経験値が上限でも桁あふれしない
+        let mut data = PlayerData { level: 1, current_xp: u32::MAX, ..PlayerData::default() };
+        assert!(data.normalize());
+        assert_normalized(&data);
+    }
+
+    #[test]
+    fn normalize_clamps_at_the_level_cap() {
+        let mut data = PlayerData { level: u32::MAX, current_xp: u32::MAX, ..PlayerData::default() };
+        assert!(!data.level_pending());
+        assert!(!data.normalize());
+        assert_eq!((data.level, data.current_xp), (u32::MAX, u32::MAX));
+
+        // 上限の1つ手前からは上限まで上がって止まる
+        let mut data = PlayerData { level: u32::MAX - 1, current_xp: u32::MAX, ..PlayerData::default() };
+        assert!(data.normalize());
+        assert_eq!(data.level, u32::MAX);
+        assert_normalized(&data);
+    }
+
+    #[test]
+    fn normalize_is_idempotent() {
+        for (level, current_xp) in [(1, 0), (1, 9), (1, 10), (7, 500), (40, 123_456), (u32::MAX - 1, u32::MAX), (u32::MAX, 0)] {
+            let mut data = PlayerData { level, current_xp, ..PlayerData::default() };
+            data.normalize();
+            let once = (data.level, data.current_xp);
+            assert!(!data.normalize(), "{:?}", (level, current_xp));
+            assert_eq!((data.level, data.current_xp), once);
+        }
+    }
+
+    #[test]
+    fn merging_imported_records_resolves_pending_level_ups() {
+        let mut data = PlayerData { level: 3, current_xp: 5_000, ..PlayerData::default() };
+        assert_eq!(data.merge_imported(history(2, 2)), 2);
+        assert_normalized(&data);
+    }
+
+    #[test]
+    fn a_flipped_byte_drops_only_the_record_it_hit() {
+        let data = data_with(history(20, 5));
+        let bytes = file_bytes(&data);
+        let frames = record_frames(&bytes);
+
+        // 記録のフレームの先頭（目印・長さ・チェックサム）と中身の、いろいろな位置を壊す
+        for (hit, &(offset, len)) in frames.iter().enumerate() {
+            for at in [offset, offset + 2, offset + 6, offset + FRAME_PREFIX_LEN, offset + len / 2, offset + len - 1] {
+                let (loaded, report) = PlayerData::decode_file(&flipped(&bytes, at)).unwrap();
+                assert_eq!(report.dropped_offsets, [offset], "flipped byte {}", at);
+                assert!(!report.lost_data());
+                let mut expected = data.history.clone();
+                expected.remove(hit);
+                assert_eq!(json(&loaded.history), json(&expected), "flipped byte {}", at);
+            }
+        }
+    }
+
+    #[test]
+    fn scattered_damage_keeps_every_intact_record() {
+        let data = data_with(history(30, 5));
+        let bytes = file_bytes(&data);
+        let frames = record_frames(&bytes);
+        let mut damaged = bytes.clone();
+        let hits = [3, 4, 17, 29];
+        for &hit in &hits {
+            let (offset, len) = frames[hit];
+            damaged[offset + len / 2] ^= 0xFF;
+        }
+
+        let (loaded, report) = PlayerData::decode_file(&damaged).unwrap();
+        assert_eq!(report.dropped_offsets, hits.map(|hit| frames[hit].0));
+        let expected: Vec<TypeRecord> = data
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !hits.contains(i))
+            .map(|(_, record)| record.clone())
+            .collect();
+        assert_eq!(json(&loaded.history), json(&expected));
+    }
+
+    #[test]
+    fn a_truncated_file_keeps_the_records_before_the_cut() {
+        let data = data_with(history(10, 5));
+        let bytes = file_bytes(&data);
+        let (offset, len) = record_frames(&bytes)[9];
+        let (loaded, report) = PlayerData::decode_file(&bytes[..offset + len / 2]).unwrap();
+        assert_eq!(report.dropped_offsets, [offset]);
+        assert_eq!(json(&loaded.history), json(&data.history[..9]));
+    }
+
+    #[test]
+    fn a_corrupted_header_is_rebuilt_from_the_records() {
+        let mut data = data_with(history(12, 4));
+        data.blacklist.push(QuestionId::new(BUILTIN_PACK_ID, 0));
+        let bytes = file_bytes(&data);
+        let (loaded, report) = PlayerData::decode_file(&flipped(&bytes, 8 + FRAME_PREFIX_LEN + 1)).unwrap();
+
+        assert!(report.header_rebuilt);
+        // doctor はヘッダ部を失ったことを失敗として報告する
+        assert!(report.lost_data());
+        assert_eq!(report.dropped_offsets, [8]);
+        // お題の文字列は組み込みのお題から、レベルと累計は履歴から作り直す
+        assert_eq!(json(&loaded.history), json(&data.history));
+        assert_eq!((loaded.level, loaded.current_xp), (data.level, data.current_xp));
+        assert_eq!(loaded.total_typed_chars, data.total_typed_chars);
+        // ヘッダ部にしかないものは失われる
+        assert!(loaded.blacklist.is_empty());
+    }
+
+    #[test]
+    fn a_damaged_record_after_a_damaged_header_is_still_dropped() {
+        let data = data_with(history(6, 3));
+        let bytes = file_bytes(&data);
+        let (offset, len) = record_frames(&bytes)[2];
+        let damaged = flipped(&flipped(&bytes, 8 + FRAME_PREFIX_LEN), offset + len - 1);
+
+        let (loaded, report) = PlayerData::decode_file(&damaged).unwrap();
+        assert!(report.header_rebuilt);
+        assert_eq!(report.dropped_offsets, [8, offset]);
+        assert_eq!(loaded.history.len(), 5);
+    }
+
+    #[test]
+    fn the_blacklist_round_trips() {
+        let mut data = data_with(history(4, 2));
+        assert!(data.toggle_blacklist(QuestionId::new(BUILTIN_PACK_ID, 1)));
+        assert!(data.toggle_blacklist(QuestionId::new("user", 0)));
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.blacklist, data.blacklist);
+
+        // もう一度切り替えると外れる
+        assert!(!data.toggle_blacklist(QuestionId::new(BUILTIN_PACK_ID, 1)));
+        assert_eq!(data.blacklist, [QuestionId::new("user", 0)]);
+    }
+
+    #[test]
+    fn a_save_from_before_the_blacklist_loads_with_an_empty_one() {
+        let mut data = data_with(history(4, 2));
+        data.blacklist.push(QuestionId::new(BUILTIN_PACK_ID, 0));
+        // v2 形式にはブラックリストの欄がない
+        let (loaded, _) = PlayerData::decode_file(&flat_v2_bytes(&data)).unwrap();
+        assert!(loaded.blacklist.is_empty());
+
+        let mut value = serde_json::to_value(&data).unwrap();
+        value.as_object_mut().unwrap().remove("blacklist");
+        let loaded: PlayerData = serde_json::from_value(value).unwrap();
+        assert!(loaded.blacklist.is_empty());
+    }
+
+    #[test]
+    fn dedupe_keeps_the_first_of_each_exact_duplicate() {
+        let original = history(6, 3);
+        let mut doubled = original.clone();
+        // 同じバックアップを2回復元した分。秒未満と、比べない欄（時間・スコア）は違っていても同じ記録とみなす
+        for record in &original[..4] {
+            let mut copy = record.clone();
+            copy.timestamp += TimeDelta::milliseconds(300);
+            copy.duration_sec += 0.5;
+            copy.score += 1.0;
+            doubled.push(copy);
+        }
+        let mut data = data_with(doubled);
+        let removed = data.remove_duplicate_records();
+        assert_eq!(removed.len(), 4);
+        assert!(removed.iter().all(|r| r.timestamp.timestamp_subsec_millis() == 300));
+        let timestamps = |history: &[TypeRecord]| history.iter().map(|r| r.timestamp).collect::<Vec<_>>();
+        assert_eq!(timestamps(&data.history), timestamps(&original));
+
+        // 累計値は計算し直すと重なる前と同じになる
+        let deduped = data.recomputed();
+        let clean = data_with(original);
+        assert_eq!(
+            (deduped.level, deduped.current_xp, deduped.total_typed_chars, deduped.total_misses),
+            (clean.level, clean.current_xp, clean.total_typed_chars, clean.total_misses)
+        );
+        assert!(data.remove_duplicate_records().is_empty());
+    }
+
+    #[test]
+    fn near_duplicates_that_differ_in_one_field_are_kept() {
+        let record = history(1, 1).remove(0);
+        let near: [fn(&mut TypeRecord); 4] = [
+            |r| r.timestamp += TimeDelta::seconds(1),
+            |r| r.question_hiragana.push('ね'),
+            |r| r.total_chars += 1,
+            |r| r.misses += 1,
+        ];
+        for (i, change) in near.iter().enumerate() {
+            let mut other = record.clone();
+            change(&mut other);
+            let mut data = data_with(vec![record.clone(), other]);
+            assert!(data.remove_duplicate_records().is_empty(), "field {i}");
+            assert_eq!(data.history.len(), 2);
+        }
+    }
+
+    /// `field` の欄を書くようになる前の形（その欄から後ろがない）に記録を削って読み直す
+    /// 記録の末尾に欄を足したら、ここにも足した順に足す
+    fn decode_before(record: &TypeRecord, field: &str) -> TypeRecord {
+        let len = |write: &dyn Fn(&mut FieldWriter) -> Result<(), EncodeError>| {
+            let mut tail = FieldWriter::new();
+            write(&mut tail).unwrap();
+            tail.into_bytes().len()
+        };
+        let trailing = [
+            ("error_loss_ms", len(&|tail| tail.write(&record.error_loss_ms))),
+            ("unit_misses", len(&|tail| tail.write(&record.unit_misses))),
+            ("intent", len(&|tail| tail.write(&record.intent))),
+            ("code", len(&|tail| tail.write(&record.code))),
+            ("pace", len(&|tail| tail.write(&record.pace))),
+            ("outcome", len(&|tail| tail.write(&record.outcome))),
+            ("gloss", len(&|tail| tail.write(&record.gloss))),
+        ];
+        let start = trailing.iter().position(|&(name, _)| name == field).unwrap();
+        let cut: usize = trailing[start..].iter().map(|&(_, len)| len).sum();
+        let full = record.encode_bin(0).unwrap();
+        let questions = [(record.question_japanese.clone(), record.question_hiragana.clone())];
+        TypeRecord::decode_bin(&full[..full.len() - cut], Some(&questions)).unwrap()
+    }
+
+    #[test]
+    fn time_lost_to_errors_round_trips_and_reads_as_zero_from_older_records() {
+        let mut record = history(1, 1).remove(0);
+        record.error_loss_ms = 2_400;
+        let data = data_with(vec![record.clone()]);
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.history[0].error_loss_ms, 2_400);
+
+        // 時間を書くようになる前の記録は、その欄から後ろがない
+        let decoded = decode_before(&record, "error_loss_ms");
+        assert_eq!((decoded.error_loss_ms, decoded.misses, decoded.governor_ms), (0, record.misses, record.governor_ms));
+    }
+
+    #[test]
+    fn the_pace_profile_round_trips_and_reads_as_empty_from_older_records() {
+        let mut record = history(1, 1).remove(0);
+        record.pace = vec![1040, 1000, 880];
+        let data = data_with(vec![record.clone()]);
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.history[0].pace, [1040, 1000, 880]);
+
+        // 速さを書くようになる前の記録は、その欄から後ろがない
+        let decoded = decode_before(&record, "pace");
+        assert!(decoded.pace.is_empty());
+        assert_eq!((decoded.code, decoded.misses), (record.code, record.misses));
+
+        // 空のときは JSON に書かない
+        record.pace.clear();
+        assert!(!serde_json::to_string(&record).unwrap().contains("pace"));
+    }
+
+    #[test]
+    fn the_gloss_round_trips_and_reads_as_none_from_older_records() {
+        let mut record = history(1, 1).remove(0);
+        record.gloss = Some("cat".to_string());
+        let data = data_with(vec![record.clone()]);
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.history[0].gloss.as_deref(), Some("cat"));
+
+        // 訳を書くようになる前の記録は、その欄がない
+        let decoded = decode_before(&record, "gloss");
+        assert_eq!(decoded.gloss, None);
+        assert_eq!(decoded.outcome, record.outcome);
+    }
+
+    #[test]
+    fn timeouts_round_trip_and_older_records_read_as_completed() {
+        let mut record = history(1, 1).remove(0);
+        record.outcome = QuestionOutcome::TimedOut;
+        let data = data_with(vec![record.clone()]);
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert!(loaded.history[0].timed_out());
+        assert!(serde_json::to_string(&record).unwrap().contains(r#""outcome":"timed-out""#));
+
+        // 終わり方を書くようになる前の記録は、その欄から後ろがない
+        let decoded = decode_before(&record, "outcome");
+        assert_eq!(decoded.outcome, QuestionOutcome::Completed);
+
+        // JSON も同じ（欄がなければ最後まで打った記録）
+        let mut json: serde_json::Value = serde_json::to_value(&record).unwrap();
+        json.as_object_mut().unwrap().remove("outcome");
+        let decoded: TypeRecord = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.outcome, QuestionOutcome::Completed);
+    }
+
+    #[test]
+    fn mini_game_runs_survive_a_recompute() {
+        let mut data = data_with(history(3, 3));
+        data.add_mini_game_run(12, 40, 3, 61.4);
+        data.add_mini_game_run(0, 10, 1, 20.6);
+        let recomputed = data.recomputed();
+        assert_eq!(
+            (recomputed.total_typed_chars, recomputed.total_misses, recomputed.total_practice_secs),
+            (data.total_typed_chars, data.total_misses, data.total_practice_secs)
+        );
+        assert_eq!((recomputed.level, recomputed.current_xp), (data.level, data.current_xp));
+        assert_eq!(data.mini_game, MiniGameTotals { typed_chars: 50, misses: 4, practice_secs: 82 });
+
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.mini_game, data.mini_game);
+        assert_eq!(loaded.recomputed().total_typed_chars, data.total_typed_chars);
+    }
+
+    #[test]
+    fn bookmarks_round_trip_including_orphaned_ones() {
+        let mut data = data_with(history(4, 2));
+        let orphan = QuestionId::new("removed-pack", 3);
+        assert!(data.toggle_bookmark(QuestionId::new(BUILTIN_PACK_ID, 1)));
+        assert!(data.toggle_bookmark(orphan));
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        // 消えたお題のお気に入りも残しておく
+        assert_eq!(loaded.bookmarks, [QuestionId::new(BUILTIN_PACK_ID, 1), orphan]);
+        assert!(loaded.is_bookmarked(orphan));
+
+        assert!(!data.toggle_bookmark(QuestionId::new(BUILTIN_PACK_ID, 1)));
+        assert!(!data.is_bookmarked(QuestionId::new(BUILTIN_PACK_ID, 1)));
+
+        let (loaded, _) = PlayerData::decode_file(&flat_v2_bytes(&data)).unwrap();
+        assert!(loaded.bookmarks.is_empty());
+        let mut value = serde_json::to_value(&data).unwrap();
+        value.as_object_mut().unwrap().remove("bookmarks");
+        let loaded: PlayerData = serde_json::from_value(value).unwrap();
+        assert!(loaded.bookmarks.is_empty());
+    }
+
+    #[test]
+    fn serve_counts_round_trip_and_start_empty_for_old_saves() {
+        let mut data = data_with(history(4, 2));
+        for _ in 0..3 {
+            data.record_serve(QuestionId::new(BUILTIN_PACK_ID, 2));
+        }
+        data.record_serve(QuestionId::new("user", 0));
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.serve_counts, data.serve_counts);
+        assert_eq!(loaded.serve_counts[&QuestionId::new(BUILTIN_PACK_ID, 2)], 3);
+
+        let (loaded, _) = PlayerData::decode_file(&flat_v2_bytes(&data)).unwrap();
+        assert!(loaded.serve_counts.is_empty());
+    }
+
+    #[test]
+    fn practice_time_adds_whole_seconds_and_ignores_negative_durations() {
+        let mut data = PlayerData::default();
+        data.add_practice_time(2.4);
+        data.add_practice_time(2.6);
+        data.add_practice_time(-5.0);
+        assert_eq!(data.total_practice_secs, 5);
+        data.total_practice_secs = u64::MAX - 1;
+        data.add_practice_time(10.0);
+        assert_eq!(data.total_practice_secs, u64::MAX);
+    }
+
+    #[test]
+    fn an_old_save_backfills_practice_time_from_history() {
+        let data = data_with(history(40, 5));
+        let expected = practice_secs_from_history(&data.history);
+        assert!(expected > 0);
+        let (loaded, _) = PlayerData::decode_file(&flat_v2_bytes(&data)).unwrap();
+        assert_eq!(loaded.total_practice_secs, expected);
+    }
+
+    #[test]
+    fn a_stored_practice_time_is_kept_as_saved() {
+        let mut data = data_with(history(5, 5));
+        data.total_practice_secs = 12_345;
+        let (loaded, _) = PlayerData::decode_file(&file_bytes(&data)).unwrap();
+        assert_eq!(loaded.total_practice_secs, 12_345);
     }
 }
@@ -0,0 +1,150 @@
+// ============================================
+// src/schedule.rs
+// 曜日ごとの学習計画（例: 月曜は短いお題、水曜は長いお題）
+// 設定の `schedule` に曜日 → 絞り込みを書いておくと、その曜日のセッションでは対象のお題だけを出題する
+// ============================================
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use crate::questions::{Question, TIER_COUNT};
+
+/// 1日分の計画（今は難易度の範囲で絞り込む）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayPlan {
+    pub tiers: RangeInclusive<i32>,
+}
+
+impl DayPlan {
+    /// 絞り込みの指定を読む（"tier:2" または "tier:0-1"）
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let Some((kind, value)) = spec.trim().split_once(':') else {
+            return Err(format!("\"{}\" is not kind:value (e.g. tier:0-1)", spec));
+        };
+        if kind.trim() != "tier" {
+            return Err(format!("unknown filter \"{}\" (only tier:N or tier:N-M is supported)", kind.trim()));
+        }
+        let parse_tier = |text: &str| {
+            text.trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|tier| (0..TIER_COUNT).contains(tier))
+                .ok_or_else(|| format!("tier \"{}\" must be 0–{}", text.trim(), TIER_COUNT - 1))
+        };
+        let (low, high) = match value.split_once('-') {
+            Some((low, high)) => (parse_tier(low)?, parse_tier(high)?),
+            None => {
+                let tier = parse_tier(value)?;
+                (tier, tier)
+            }
+        };
+        if low > high {
+            return Err(format!("tier range \"{}\" is reversed", value.trim()));
+        }
+        Ok(Self { tiers: low..=high })
+    }
+
+    pub fn includes(&self, question: &Question) -> bool {
+        self.tiers.contains(&question.tier())
+    }
+
+    /// メニューに出す名前（"tiers 0–1" / "tier 2"）
+    pub fn label(&self) -> String {
+        let (low, high) = (self.tiers.start(), self.tiers.end());
+        if low == high { format!("tier {}", low) } else { format!("tiers {}–{}", low, high) }
+    }
+}
+
+/// 曜日の名前（"mon"・"Monday" など。大文字小文字は問わない）
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    name.trim().parse().ok()
+}
+
+/// 曜日ごとの計画
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    days: BTreeMap<u32, DayPlan>,
+}
+
+impl Schedule {
+    /// 設定の曜日 → 絞り込みの表から作る。読めない項目は飛ばし、その理由を返す
+    pub fn from_settings(entries: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut schedule = Self::default();
+        let mut warnings = Vec::new();
+        for (day, spec) in entries {
+            let Some(weekday) = parse_weekday(day) else {
+                warnings.push(format!("Schedule: \"{}\" is not a weekday; ignored.", day));
+                continue;
+            };
+            match DayPlan::parse(spec) {
+                Ok(plan) => {
+                    schedule.days.insert(weekday.num_days_from_monday(), plan);
+                }
+                Err(reason) => warnings.push(format!("Schedule for {}: {}; ignored.", weekday, reason)),
+            }
+        }
+        (schedule, warnings)
+    }
+
+    /// その日（ローカルの日付）の計画。計画のない曜日は None（すべてのお題を出題する）
+    pub fn plan_for(&self, date: NaiveDate) -> Option<&DayPlan> {
+        self.days.get(&date.weekday().num_days_from_monday())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(entries: &[(&str, &str)]) -> (Schedule, Vec<String>) {
+        let entries = entries.iter().map(|&(day, spec)| (day.to_string(), spec.to_string())).collect();
+        Schedule::from_settings(&entries)
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parses_single_tiers_and_ranges() {
+        assert_eq!(DayPlan::parse("tier:2"), Ok(DayPlan { tiers: 2..=2 }));
+        assert_eq!(DayPlan::parse(" tier : 0 - 1 "), Ok(DayPlan { tiers: 0..=1 }));
+        assert_eq!(DayPlan::parse("tier:2").unwrap().label(), "tier 2");
+        assert_eq!(DayPlan::parse("tier:0-1").unwrap().label(), "tiers 0–1");
+        assert!(DayPlan::parse("tier").is_err());
+        assert!(DayPlan::parse("length:3").is_err());
+        assert!(DayPlan::parse(&format!("tier:{}", TIER_COUNT)).is_err());
+        assert!(DayPlan::parse("tier:1-0").is_err());
+    }
+
+    #[test]
+    fn plans_follow_the_weekday_across_the_week_boundary() {
+        let (schedule, warnings) = schedule(&[("sun", "tier:2"), ("Monday", "tier:0-1")]);
+        assert!(warnings.is_empty());
+        // 2026-10-18 は日曜、翌日から新しい週（月曜始まり）
+        assert_eq!(schedule.plan_for(date(2026, 10, 18)), Some(&DayPlan { tiers: 2..=2 }));
+        assert_eq!(schedule.plan_for(date(2026, 10, 19)), Some(&DayPlan { tiers: 0..=1 }));
+        assert_eq!(schedule.plan_for(date(2026, 10, 25)), Some(&DayPlan { tiers: 2..=2 }));
+        assert_eq!(schedule.plan_for(date(2026, 10, 26)), Some(&DayPlan { tiers: 0..=1 }));
+    }
+
+    #[test]
+    fn days_without_a_plan_serve_every_question() {
+        let (schedule, _) = schedule(&[("wed", "tier:3")]);
+        // 2026-10-20 は火曜、10-21 は水曜
+        assert_eq!(schedule.plan_for(date(2026, 10, 20)), None);
+        assert_eq!(schedule.plan_for(date(2026, 10, 21)), Some(&DayPlan { tiers: 3..=3 }));
+        assert_eq!(Schedule::default().plan_for(date(2026, 10, 21)), None);
+    }
+
+    #[test]
+    fn unreadable_entries_are_skipped_with_a_warning() {
+        let (schedule, warnings) = schedule(&[("funday", "tier:1"), ("fri", "tier:9"), ("sat", "tier:1")]);
+        assert_eq!(warnings.len(), 2);
+        // 2026-10-16 は金曜、10-17 は土曜
+        assert_eq!(schedule.plan_for(date(2026, 10, 16)), None);
+        assert_eq!(schedule.plan_for(date(2026, 10, 17)), Some(&DayPlan { tiers: 1..=1 }));
+    }
+}
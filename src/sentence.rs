@@ -0,0 +1,164 @@
+// ============================================
+// src/sentence.rs
+// 短いお題をつなげて長い文章を作る（文章モード）
+// ============================================
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::questions::Question;
+use crate::save_data::ComponentStat;
+
+/// 1つの文章につなげるお題の数
+const MIN_PARTS: usize = 4;
+const MAX_PARTS: usize = 8;
+/// 1つの文章の最大打鍵数（各お題の標準のローマ字表記で数える。句読点も含む）
+const MAX_KEYSTROKES: usize = 150;
+
+/// お題をつなげた文章と、お題ごとの成績の記録
+pub struct Sentence {
+    pub japanese: String,
+    pub hiragana: String,
+    /// つなげたお題（出題順）
+    pub parts: Vec<&'static Question>,
+    /// 各お題（後ろの句読点を含む）の最後の CharState の次の位置
+    ends: Vec<usize>,
+    /// お題ごとのミス回数
+    misses: Vec<u32>,
+    /// 各お題を打ち終えた時点の経過秒数
+    finished_at: Vec<Option<f64>>,
+}
+
+impl Sentence {
+    /// ランダムなお題を句読点でつなげて文章を作る
+    /// `keystrokes` はひらがなの標準の打鍵数を、`allowed` はお題（`questions` 内の番号）を使ってよいかを返す
+    /// 使えるお題が足りないときは `allowed` を無視する
+    pub fn compose(
+        questions: &'static [Question],
+        keystrokes: impl Fn(&str) -> usize,
+        allowed: impl Fn(usize) -> bool,
+    ) -> Self {
+        let mut rng = rand::rng();
+        let target = rng.random_range(MIN_PARTS..=MAX_PARTS);
+
+        // 最少のお題数でも必ず収まるよう、短いお題だけを候補にする
+        let per_part = MAX_KEYSTROKES / MIN_PARTS;
+        let short: Vec<(usize, &'static Question, usize)> = questions
+            .iter()
+            .enumerate()
+            .map(|(idx, q)| (idx, q, keystrokes(q.hiragana) + 1))
+            .filter(|&(_, _, cost)| cost <= per_part)
+            .collect();
+        let mut candidates: Vec<(&'static Question, usize)> = short
+            .iter()
+            .filter(|&&(idx, _, _)| allowed(idx))
+            .map(|&(_, q, cost)| (q, cost))
+            .collect();
+        if candidates.len() < MIN_PARTS {
+            candidates = short.iter().map(|&(_, q, cost)| (q, cost)).collect();
+        }
+        candidates.shuffle(&mut rng);
+
+        let mut parts = Vec::new();
+        let mut total = 0;
+        for (question, cost) in candidates {
+            if parts.len() == target {
+                break;
+            }
+            if total + cost <= MAX_KEYSTROKES {
+                parts.push(question);
+                total += cost;
+            }
+        }
+
+        let mut japanese = String::new();
+        let mut hiragana = String::new();
+        for (idx, part) in parts.iter().enumerate() {
+            let mark = punctuation(idx, parts.len());
+            japanese.push_str(part.japanese);
+            japanese.push_str(mark);
+            hiragana.push_str(part.hiragana);
+            hiragana.push_str(mark);
+        }
+
+        Self {
+            japanese,
+            hiragana,
+            parts,
+            ends: Vec::new(),
+            misses: Vec::new(),
+            finished_at: Vec::new(),
+        }
+    }
+
+    /// `idx` 番目のお題の後ろにつける句読点
+    pub fn punctuation(&self, idx: usize) -> &'static str {
+        punctuation(idx, self.parts.len())
+    }
+
+    /// 入力の区切り位置を設定し、成績の記録をリセットする
+    pub fn reset_progress(&mut self, ends: Vec<usize>) {
+        self.misses = vec![0; ends.len()];
+        self.finished_at = vec![None; ends.len()];
+        self.ends = ends;
+    }
+
+    /// `char_index` の CharState がどのお題に属するか
+    fn part_at(&self, char_index: usize) -> usize {
+        self.ends
+            .iter()
+            .position(|&end| char_index < end)
+            .unwrap_or(self.ends.len().saturating_sub(1))
+    }
+
+    /// ミスを入力中のお題に記録する
+    pub fn record_miss(&mut self, char_index: usize) {
+        let part = self.part_at(char_index);
+        if let Some(misses) = self.misses.get_mut(part) {
+            *misses += 1;
+        }
+    }
+
+    /// 入力位置までに打ち終えたお題の完了時刻を記録する
+    pub fn record_progress(&mut self, char_index: usize, elapsed_sec: f64) {
+        for (end, finished) in self.ends.iter().zip(self.finished_at.iter_mut()) {
+            if *end <= char_index && finished.is_none() {
+                *finished = Some(elapsed_sec);
+            }
+        }
+    }
+
+    /// 入力位置が戻ったとき、まだ打ち終えていないお題の完了時刻を消す
+    pub fn rewind(&mut self, char_index: usize) {
+        for (end, finished) in self.ends.iter().zip(self.finished_at.iter_mut()) {
+            if *end > char_index {
+                *finished = None;
+            }
+        }
+    }
+
+    /// お題ごとの成績（`unit_lens` は各 CharState で実際に打ったローマ字の長さ）
+    pub fn component_stats(&self, unit_lens: &[usize]) -> Vec<ComponentStat> {
+        let mut stats = Vec::with_capacity(self.parts.len());
+        let mut start = 0;
+        let mut started_at = 0.0;
+        for (idx, part) in self.parts.iter().enumerate() {
+            let end = self.ends.get(idx).copied().unwrap_or(start).min(unit_lens.len());
+            let finished_at = self.finished_at.get(idx).copied().flatten().unwrap_or(started_at);
+            stats.push(ComponentStat {
+                question_hiragana: part.hiragana.to_string(),
+                total_chars: unit_lens[start..end].iter().sum::<usize>() as u32,
+                duration_sec: finished_at - started_at,
+                misses: self.misses.get(idx).copied().unwrap_or(0),
+            });
+            start = end;
+            started_at = finished_at;
+        }
+        stats
+    }
+}
+
+/// 最後のお題だけ「。」、それ以外は「、」で区切る
+fn punctuation(idx: usize, len: usize) -> &'static str {
+    if idx + 1 == len { "。" } else { "、" }
+}
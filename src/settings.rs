@@ -0,0 +1,484 @@
+// ============================================
+// src/settings.rs
+// ユーザー設定の構造と読み書きロジック
+// ============================================
+
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use crate::locale::UiLanguage;
+use crate::question_split::{DEFAULT_MAX_KEYSTROKES, LengthLimit};
+use crate::questions::QuestionId;
+use crate::remap::KeyRemap;
+use crate::save_data::{get_data_dir, write_atomic};
+
+/// 設定画面でウォームアップを有効にしたときのお題（短い2文字の単語）
+pub const DEFAULT_WARMUP: [&str; 3] = ["みず", "かさ", "くつ"];
+
+/// 休憩を勧めるまでの、続けて打った時間（分）の初期値
+const DEFAULT_BREAK_REMINDER_MINS: u64 = 20;
+
+/// ユーザー設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// キー配列のリマップ (none / colemak / dvorak / custom)
+    pub key_remap: KeyRemap,
+    /// `custom` 選択時に読み込むリマップファイル（1行に「入力文字 変換後文字」）
+    pub custom_remap_file: Option<PathBuf>,
+    /// 何秒キー入力がなければ放置とみなすか（0 で無効）
+    pub afk_threshold_secs: u64,
+    /// 放置を検出したときの動作
+    pub afk_action: AfkAction,
+    /// セッション中の成績に応じて難易度を自動調整する
+    pub adaptive_difficulty: bool,
+    /// どのお題も、出題できるお題の数のこの倍数の出題のうちに必ず1回は出す（0 で保証しない）
+    pub rotation_factor: u32,
+    /// 同じキーがこのミリ秒以内に2回届き、2回目がミスになる場合は無視する（0 で無効）
+    pub chatter_filter_ms: u64,
+    /// 次の次のキーが先に届いてミスになり、このミリ秒以内に本来のキーが届いたら、順序を入れ替えて両方を正しい入力にする（0 で無効）
+    pub rollover_forgiveness_ms: u64,
+    /// 前に受け付けたキーからこのミリ秒が経つまでのキーを無視して、ゆっくり正確に打つ練習をする（0 で無効）
+    pub governor_interval_ms: u64,
+    /// ミスタイプ時に枠を赤く光らせる強さ
+    pub error_flash: ErrorFlash,
+    /// お題を出すときにメモを表示する
+    pub show_notes: bool,
+    /// 表示言語でない訳も、お題の下に小さく表示する（表示言語の訳はいつも表示する）
+    pub show_gloss: bool,
+    /// 直近の正確率が下がったときの休憩の促し方
+    pub cooldown: Cooldown,
+    /// 直近5問の平均正確率 (%) がこれを下回ったら休憩を促す
+    pub cooldown_accuracy_floor: f64,
+    /// 続けてこの分数打ったら、お題の間に休憩を勧める（0 で無効）
+    pub break_reminder_mins: u64,
+    /// 1問の制限時間を、一般的なタイピストがかける時間の何倍にするか（0 で無効。ウォームアップには付けない）
+    pub time_limit_factor: f64,
+    /// セッションの最初に順番に出題するお題（日本語またはひらがなで指定。空で無効）
+    pub warmup: Vec<String>,
+    /// お題の時間をいつから計るか（自己ベストは同じ計り方の記録とだけ比べる）
+    pub timing_policy: TimingPolicy,
+    /// 直前のお題の成績をいつまで表示するか
+    pub result_persistence: ResultPersistence,
+    /// `timed` のとき、成績を表示しておく秒数
+    pub result_timeout_secs: u64,
+    /// 集中モード（タイピング画面で枠や成績を隠し、お題だけを表示する）
+    pub focus_mode: bool,
+    /// メトロノームの目標テンポ（打鍵/分。0 で無効）
+    pub metronome_kpm: u32,
+    /// メトロノームの拍を画面に表示する
+    pub metronome_pulse: bool,
+    /// メトロノームの拍でベルを鳴らす
+    pub metronome_bell: bool,
+    /// 演出などが動いている間の、タイピング画面の1秒あたりの描き直しの回数
+    pub animation_fps: u32,
+    /// 何も動いていないときのタイピング画面の描き直しの間隔（ミリ秒。大きいほど省電力）
+    pub tick_rate_ms: u64,
+    /// 他のタイピングソフトから取り込んだ記録も自己ベストの対象にする
+    pub imported_in_bests: bool,
+    /// 自己ベスト・ランキング・目標の対象にする正確率の下限 (%)。下回った記録も保存はする（0 なら下限なし）
+    pub accuracy_floor_for_bests: f64,
+    /// デバッグ用にセーブデータの JSON コピーも書き出す
+    pub json_mirror: bool,
+    /// カレントディレクトリに残った古い JSON の削除確認を済ませたか
+    pub stray_json_prompted: bool,
+    /// 最後に週報を書き出した週（例: "2024-W23"）
+    pub last_weekly_report: Option<String>,
+    /// 前回のセッションの終わりに勧めた練習（例: "kana:ふぁ"。続けて同じことを勧めないために使う）
+    pub last_recommendation: Option<String>,
+    /// 前回始めたセッションの設定（メニューの Quick Start と、フラグなしの `start` で使う）
+    pub last_session: SessionConfig,
+    /// 日付や数の書き方の言語
+    pub ui_language: UiLanguage,
+    /// 落ち物ゲームでも経験値を得る（消した単語のかなの数だけ）
+    pub falling_words_xp: bool,
+    /// 作ったお題・取り込んだお題の1問の打鍵数の上限
+    pub max_question_keystrokes: usize,
+    /// 上限を超えるお題を句読点や空白の位置で分ける（false なら取り込まない）
+    pub split_long_questions: bool,
+    /// セッションを始める前に、どんなセッションか（ウォームアップ・テスト・学習）を聞く
+    pub ask_session_intent: bool,
+    /// 前回選んだセッションの目的（次に聞くときの初期選択）
+    pub last_intent: SessionIntent,
+    /// 曜日ごとの出題範囲（例: "mon" → "tier:0-1"）。書いていない曜日はすべてのお題を出題する
+    pub schedule: BTreeMap<String, String>,
+    /// タグごとの経験値の倍率（例: "N4" → 1.5。1.0〜3.0）。そのタグの付いたお題だけに掛かる
+    pub tag_xp_multipliers: BTreeMap<String, f64>,
+    /// 複数のタグの倍率を掛け合わせたときの上限（1.0〜3.0）
+    pub tag_xp_multiplier_cap: f64,
+    /// 直前のアップデートで置き換えたバージョン（巻き戻し先。巻き戻したら None）
+    pub previous_version: Option<String>,
+}
+
+/// 放置検出時の動作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AfkAction {
+    /// タイマーを一時停止する
+    Pause,
+    /// 記録に印を付けるだけ（経過時間はそのまま）
+    MarkOnly,
+}
+
+impl AfkAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AfkAction::Pause => "pause",
+            AfkAction::MarkOnly => "mark-only",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            AfkAction::Pause => AfkAction::MarkOnly,
+            AfkAction::MarkOnly => AfkAction::Pause,
+        }
+    }
+}
+
+/// ミスタイプ時の光らせ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorFlash {
+    /// 光らせない
+    Off,
+    /// 枠だけを赤くする
+    Subtle,
+    /// 枠に加えてローマ字の行も赤くする
+    Strong,
+}
+
+impl ErrorFlash {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorFlash::Off => "off",
+            ErrorFlash::Subtle => "subtle",
+            ErrorFlash::Strong => "strong",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ErrorFlash::Off => ErrorFlash::Subtle,
+            ErrorFlash::Subtle => ErrorFlash::Strong,
+            ErrorFlash::Strong => ErrorFlash::Off,
+        }
+    }
+}
+
+/// 直前のお題の成績の表示を消すタイミング
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResultPersistence {
+    /// 次のお題を打ち終えるまで表示する
+    Always,
+    /// 次のお題を打ち始めたら消す
+    UntilFirstKey,
+    /// 一定時間で消す
+    Timed,
+}
+
+impl ResultPersistence {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResultPersistence::Always => "always",
+            ResultPersistence::UntilFirstKey => "until-first-key",
+            ResultPersistence::Timed => "timed",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ResultPersistence::Always => ResultPersistence::UntilFirstKey,
+            ResultPersistence::UntilFirstKey => ResultPersistence::Timed,
+            ResultPersistence::Timed => ResultPersistence::Always,
+        }
+    }
+}
+
+/// お題の時間をいつから計るか
+/// ※記録にも保存するので、値を増やすときは必ず末尾に追加すること
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimingPolicy {
+    /// 最初のキーを押したときから（1文字目までの反応時間は含まない）
+    #[default]
+    FirstKey,
+    /// お題を表示したときから
+    OnDisplay,
+    /// お題を表示したときからだが、最初のキーまでの時間は最大 2 秒まで
+    Hybrid,
+}
+
+impl TimingPolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimingPolicy::FirstKey => "first-key",
+            TimingPolicy::OnDisplay => "on-display",
+            TimingPolicy::Hybrid => "hybrid",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            TimingPolicy::FirstKey => TimingPolicy::OnDisplay,
+            TimingPolicy::OnDisplay => TimingPolicy::Hybrid,
+            TimingPolicy::Hybrid => TimingPolicy::FirstKey,
+        }
+    }
+}
+
+/// セッションの目的（セッションを始める前に選び、そのセッションの記録に付ける）
+/// ※記録にも保存するので、値を増やすときは必ず末尾に追加すること
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionIntent {
+    /// 選ばなかった（聞かない設定のときや、この機能より前の記録）
+    #[default]
+    Unspecified,
+    /// 指を温めるための軽い練習
+    WarmUp,
+    /// 本気で記録を測る
+    Test,
+    /// 新しいお題や苦手なキーを覚える
+    Learning,
+}
+
+impl SessionIntent {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionIntent::Unspecified => "unspecified",
+            SessionIntent::WarmUp => "warm-up",
+            SessionIntent::Test => "test",
+            SessionIntent::Learning => "learning",
+        }
+    }
+}
+
+/// 正確率が下がったときの休憩の促し方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cooldown {
+    /// 何もしない
+    Off,
+    /// お題の間に休憩を勧める（キーを押せばすぐ続けられる）
+    Suggest,
+    /// お題の間に一定時間の休憩をはさむ
+    Enforce,
+}
+
+impl Cooldown {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Cooldown::Off => "off",
+            Cooldown::Suggest => "suggest",
+            Cooldown::Enforce => "enforce",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Cooldown::Off => Cooldown::Suggest,
+            Cooldown::Suggest => Cooldown::Enforce,
+            Cooldown::Enforce => Cooldown::Off,
+        }
+    }
+}
+
+/// セッションの種類
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionMode {
+    /// お題を1問ずつ出題する
+    #[default]
+    Questions,
+    /// 短いお題をつないだ文章を打つ
+    Sentence,
+    /// 1つのお題を決まった回数だけ打って最高記録を狙う
+    TimeAttack,
+    /// お気に入りに入れたお題だけを出題する
+    Favorites,
+}
+
+impl SessionMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionMode::Questions => "questions",
+            SessionMode::Sentence => "sentence",
+            SessionMode::TimeAttack => "time attack",
+            SessionMode::Favorites => "favorites",
+        }
+    }
+}
+
+/// セッションの始め方（種類と、お題を選んで始めたときのお題）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub mode: SessionMode,
+    /// 最初に出すお題（タイムアタックでは必須。文章モードでは使わない）
+    pub question: Option<QuestionId>,
+}
+
+impl SessionConfig {
+    /// `start` のフラグで上書きする（指定しなかった項目は前回のまま）
+    /// 種類だけを変えたときは、タイムアタック以外ならお題の指定を外す
+    /// お題だけを指定したときは、前回がタイムアタックでなければお題から始める通常の出題にする
+    pub fn merged(self, mode: Option<SessionMode>, question: Option<QuestionId>) -> Self {
+        match (mode, question) {
+            (None, None) => self,
+            (Some(mode), None) => Self { mode, question: self.question.filter(|_| mode == SessionMode::TimeAttack) },
+            (mode, Some(question)) => Self {
+                mode: mode.unwrap_or(match self.mode {
+                    SessionMode::TimeAttack => SessionMode::TimeAttack,
+                    _ => SessionMode::Questions,
+                }),
+                question: Some(question),
+            },
+        }
+    }
+
+    /// メニューに出す説明（例: "time attack on 0123456789abcdef"）
+    pub fn label(&self) -> String {
+        match (self.mode, self.question) {
+            (SessionMode::Sentence, _) | (_, None) => self.mode.label().to_string(),
+            (mode, Some(id)) => format!("{} from {}", mode.label(), id),
+        }
+    }
+}
+
+impl Default for Settings {
+    /// 設定の初期値
+    fn default() -> Self {
+        Self {
+            key_remap: KeyRemap::None,
+            custom_remap_file: None,
+            afk_threshold_secs: 10,
+            afk_action: AfkAction::Pause,
+            adaptive_difficulty: true,
+            rotation_factor: 2,
+            chatter_filter_ms: 30,
+            rollover_forgiveness_ms: 0,
+            governor_interval_ms: 0,
+            error_flash: ErrorFlash::Off,
+            timing_policy: TimingPolicy::FirstKey,
+            result_persistence: ResultPersistence::Always,
+            result_timeout_secs: 3,
+            focus_mode: false,
+            show_notes: true,
+            show_gloss: false,
+            warmup: Vec::new(),
+            cooldown: Cooldown::Suggest,
+            cooldown_accuracy_floor: 85.0,
+            break_reminder_mins: DEFAULT_BREAK_REMINDER_MINS,
+            time_limit_factor: 0.0,
+            metronome_kpm: 0,
+            metronome_pulse: true,
+            metronome_bell: false,
+            animation_fps: 30,
+            tick_rate_ms: 250,
+            imported_in_bests: false,
+            accuracy_floor_for_bests: 95.0,
+            json_mirror: false,
+            stray_json_prompted: false,
+            last_weekly_report: None,
+            last_recommendation: None,
+            last_session: SessionConfig::default(),
+            ui_language: UiLanguage::default(),
+            falling_words_xp: false,
+            max_question_keystrokes: DEFAULT_MAX_KEYSTROKES,
+            split_long_questions: true,
+            ask_session_intent: false,
+            last_intent: SessionIntent::Unspecified,
+            schedule: BTreeMap::new(),
+            tag_xp_multipliers: BTreeMap::new(),
+            tag_xp_multiplier_cap: 3.0,
+            previous_version: None,
+        }
+    }
+}
+
+impl Settings {
+    // MARK:設定ファイルのパスを取得する関数
+    pub fn get_settings_file_path() -> PathBuf {
+        get_data_dir().join("settings.json")
+    }
+
+    /// MARK:設定をファイルに保存する
+    pub fn save(&self) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = write_atomic(&Self::get_settings_file_path(), json.as_bytes());
+        }
+    }
+
+    /// MARK:設定をファイルから読み込む（失敗時はデフォルト）
+    pub fn load() -> Self {
+        if let Ok(file) = File::open(Self::get_settings_file_path()) {
+            let reader = BufReader::new(file);
+            if let Ok(settings) = serde_json::from_reader(reader) {
+                return settings;
+            }
+        }
+        Self::default()
+    }
+
+    /// 作ったお題・取り込んだお題の長さの扱い
+    pub fn length_limit(&self) -> LengthLimit {
+        LengthLimit { max_keystrokes: self.max_question_keystrokes, split: self.split_long_questions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_flags_override_only_what_they_name() {
+        let id = QuestionId(0x0123_4567_89ab_cdef);
+        let other = QuestionId(42);
+        let attack = SessionConfig { mode: SessionMode::TimeAttack, question: Some(id) };
+        let sentence = SessionConfig { mode: SessionMode::Sentence, question: None };
+
+        assert_eq!(attack.merged(None, None), attack);
+        // 種類だけを変えたら、タイムアタック以外ではお題の指定を外す
+        assert_eq!(attack.merged(Some(SessionMode::Questions), None), SessionConfig { mode: SessionMode::Questions, question: None });
+        assert_eq!(attack.merged(Some(SessionMode::TimeAttack), None), attack);
+        // お題だけを指定したら、前回のタイムアタックは続け、それ以外は通常の出題にする
+        assert_eq!(attack.merged(None, Some(other)), SessionConfig { mode: SessionMode::TimeAttack, question: Some(other) });
+        assert_eq!(sentence.merged(None, Some(other)), SessionConfig { mode: SessionMode::Questions, question: Some(other) });
+        assert_eq!(
+            sentence.merged(Some(SessionMode::TimeAttack), Some(other)),
+            SessionConfig { mode: SessionMode::TimeAttack, question: Some(other) }
+        );
+    }
+
+    #[test]
+    fn labels_name_the_question_except_for_sentences() {
+        let id = QuestionId(0x0123_4567_89ab_cdef);
+        assert_eq!(SessionConfig::default().label(), "questions");
+        assert_eq!(SessionConfig { mode: SessionMode::TimeAttack, question: Some(id) }.label(), "time attack from 0123456789abcdef");
+        assert_eq!(SessionConfig { mode: SessionMode::Sentence, question: Some(id) }.label(), "sentence");
+    }
+
+    #[test]
+    fn the_last_session_round_trips_and_reads_as_default_from_older_settings() {
+        let settings = Settings {
+            last_session: SessionConfig { mode: SessionMode::TimeAttack, question: Some(QuestionId(7)) },
+            ..Settings::default()
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains("\"time-attack\""));
+        let read: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.last_session, settings.last_session);
+
+        let older: Settings = serde_json::from_str("{\"afk_threshold_secs\": 5}").unwrap();
+        assert_eq!(older.last_session, SessionConfig::default());
+        assert_eq!(older.afk_threshold_secs, 5);
+    }
+}
@@ -0,0 +1,89 @@
+// ============================================
+// src/sleep_detect.rs
+// PC のスリープからの復帰を見つける
+// Instant がスリープ中に進むかは OS によって違うので、壁時計（SystemTime）の進みと比べて判断する
+// ============================================
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// ループの1周の間にこれ以上の時間の飛びがあれば、スリープしていたとみなす
+pub const SLEEP_JUMP_THRESHOLD: Duration = Duration::from_secs(20);
+
+/// 入力待ちのループの1周ごとに時刻を渡し、前の周との間の時間の飛びを見つける
+/// 時刻は呼び出し側から渡す（決まった時刻を渡せば、スリープを起こさなくても確かめられる）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SleepDetector {
+    last: Option<(Instant, SystemTime)>,
+}
+
+impl SleepDetector {
+    /// 前の周からの間にスリープしていたら、その間に壁時計で経った時間を返す
+    /// - 壁時計だけが大きく進んだ: Instant がスリープ中に止まる OS（Linux / macOS）
+    /// - 両方とも大きく進んだ: Instant もスリープ中に進む OS（Windows）。ループは短い間隔で回るので、1周が長すぎれば止まっていたとわかる
+    ///
+    /// 壁時計が戻ったとき（時刻合わせ）は飛びとみなさない
+    pub fn check(&mut self, now: Instant, wall: SystemTime) -> Option<Duration> {
+        let (last_now, last_wall) = self.last.replace((now, wall))?;
+        let monotonic = now.saturating_duration_since(last_now);
+        let wall_elapsed = wall.duration_since(last_wall).unwrap_or(Duration::ZERO);
+        let diverged = wall_elapsed.saturating_sub(monotonic) >= SLEEP_JUMP_THRESHOLD;
+        let stalled = monotonic >= SLEEP_JUMP_THRESHOLD;
+        (diverged || stalled).then_some(wall_elapsed.max(monotonic))
+    }
+
+    /// ループに入り直すとき（メニューにいた間の時間を飛びと数えないように）
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 前の周から (Instant の進み, 壁時計の進み) だけ経ったところで確かめる
+    fn check_after(monotonic: Duration, wall: Duration) -> Option<Duration> {
+        let mut detector = SleepDetector::default();
+        let (now, then) = (Instant::now(), SystemTime::now());
+        assert_eq!(detector.check(now, then), None);
+        detector.check(now + monotonic, then + wall)
+    }
+
+    #[test]
+    fn the_first_check_only_remembers_the_time() {
+        let mut detector = SleepDetector::default();
+        assert_eq!(detector.check(Instant::now(), SystemTime::now() + Duration::from_secs(3600)), None);
+    }
+
+    #[test]
+    fn a_wall_clock_jump_past_the_threshold_is_a_sleep() {
+        let gap = Duration::from_secs(600);
+        assert_eq!(check_after(Duration::from_millis(50), gap), Some(gap));
+        assert_eq!(check_after(Duration::ZERO, SLEEP_JUMP_THRESHOLD), Some(SLEEP_JUMP_THRESHOLD));
+        assert_eq!(check_after(Duration::ZERO, SLEEP_JUMP_THRESHOLD - Duration::from_millis(1)), None);
+    }
+
+    #[test]
+    fn a_stalled_loop_is_a_sleep_even_when_both_clocks_agree() {
+        let gap = Duration::from_secs(90);
+        assert_eq!(check_after(gap, gap), Some(gap));
+        assert_eq!(check_after(Duration::from_secs(1), Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn a_wall_clock_set_back_is_not_a_sleep() {
+        let mut detector = SleepDetector::default();
+        let (now, wall) = (Instant::now(), SystemTime::now());
+        detector.check(now, wall);
+        assert_eq!(detector.check(now + Duration::from_millis(50), wall - Duration::from_secs(3600)), None);
+    }
+
+    #[test]
+    fn reset_forgets_the_previous_loop() {
+        let mut detector = SleepDetector::default();
+        let (now, wall) = (Instant::now(), SystemTime::now());
+        detector.check(now, wall);
+        detector.reset();
+        assert_eq!(detector.check(now, wall + Duration::from_secs(3600)), None);
+    }
+}
@@ -0,0 +1,1152 @@
+// ============================================
+// src/stats.rs
+// 履歴から集計する統計と、その表示用の整形
+// ============================================
+
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::questions::QuestionId;
+use crate::save_data::TypeRecord;
+use crate::settings::SessionIntent;
+use crate::streaming_stats::{DayBuckets, Reservoir, RunningTotals};
+
+// --------------------------------------------------
+// MARK:お題ごとの集計
+// --------------------------------------------------
+
+/// 1つのお題についての集計
+#[derive(Debug, Clone, Default)]
+pub struct QuestionAggregate {
+    /// 挑戦回数
+    pub attempts: u32,
+    /// 最高 CPS
+    pub best_cps: f64,
+    /// 最高スコア
+    pub best_score: f64,
+    /// 最後に挑戦した日時
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+/// お題 ID ごとの集計表
+pub type QuestionAggregates = HashMap<QuestionId, QuestionAggregate>;
+
+/// 履歴からお題ごとの集計表を作る（ID のない記録は数えない）
+/// 最高 CPS・最高スコアは、正確率が `accuracy_floor` (%) に届いた記録だけから求める
+pub fn build_question_aggregates(history: &[TypeRecord], accuracy_floor: f64) -> QuestionAggregates {
+    let mut index = QuestionAggregates::new();
+    for record in history {
+        let Some(id) = record.question_id else {
+            continue;
+        };
+        let entry = index.entry(id).or_default();
+        entry.attempts += 1;
+        // 時間切れ・正確率が下限に届かない記録は挑戦回数にだけ数える
+        if !record.timed_out() && record.meets_accuracy_floor(accuracy_floor) {
+            entry.best_cps = entry.best_cps.max(record.cps);
+            entry.best_score = entry.best_score.max(record.score);
+        }
+        if entry.last_played.is_none_or(|last| record.timestamp > last) {
+            entry.last_played = Some(record.timestamp);
+        }
+    }
+    index
+}
+
+/// 履歴から作る表のキャッシュ（履歴の件数か、作るときの条件が変わったら作り直す）
+/// PlayerData に持たせるため、複製時は空のキャッシュになる
+pub struct HistoryCache<T> {
+    inner: Mutex<Option<(usize, u64, Arc<T>)>>,
+}
+
+/// お題ごとの集計表のキャッシュ
+pub type AggregateCache = HistoryCache<QuestionAggregates>;
+
+impl<T> HistoryCache<T> {
+    /// キャッシュ済みの表を返す。履歴が増えていれば `build` で作り直す
+    pub fn get_or_build(&self, history: &[TypeRecord], build: impl FnOnce(&[TypeRecord]) -> T) -> Arc<T> {
+        self.get_or_build_keyed(history, 0, build)
+    }
+
+    /// `get_or_build` と同じだが、`key`（作るときの条件）が前と違っても作り直す
+    pub fn get_or_build_keyed(&self, history: &[TypeRecord], key: u64, build: impl FnOnce(&[TypeRecord]) -> T) -> Arc<T> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((len, cached_key, table)) = inner.as_ref() && *len == history.len() && *cached_key == key {
+            return Arc::clone(table);
+        }
+        let table = Arc::new(build(history));
+        *inner = Some((history.len(), key, Arc::clone(&table)));
+        table
+    }
+}
+
+impl<T> Default for HistoryCache<T> {
+    fn default() -> Self {
+        Self { inner: Mutex::new(None) }
+    }
+}
+
+impl<T> Clone for HistoryCache<T> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<T> std::fmt::Debug for HistoryCache<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HistoryCache")
+    }
+}
+
+// --------------------------------------------------
+// MARK:自分の履歴の中での順位
+// --------------------------------------------------
+
+/// 打鍵数の帯 (下限, 上限)。最後の帯は上限なし
+pub const LENGTH_BANDS: [(u32, u32); 4] = [(1, 5), (6, 10), (11, 20), (21, u32::MAX)];
+/// 帯の記録がこれより少ないときは順位を出さない
+pub const PERCENTILE_MIN_SAMPLES: usize = 20;
+
+/// 打鍵数が入る帯の番号（0 打鍵は最初の帯に入れる）
+pub fn length_band(keystrokes: u32) -> usize {
+    LENGTH_BANDS
+        .iter()
+        .position(|&(_, high)| keystrokes <= high)
+        .unwrap_or(LENGTH_BANDS.len() - 1)
+}
+
+/// 帯の表示名（"6–10", "21+"）
+pub fn band_label(band: usize) -> String {
+    match LENGTH_BANDS.get(band) {
+        Some(&(low, u32::MAX)) => format!("{}+", low),
+        Some(&(low, high)) => format!("{}–{}", low, high),
+        None => String::new(),
+    }
+}
+
+/// 並べ替え済みの値の中で `value` を下回る割合 (%)
+/// 同じ値は半分だけ下回るとみなす（中間順位）。記録が少なければ None
+pub fn percentile_of(sorted: &[f64], value: f64) -> Option<f64> {
+    if sorted.len() < PERCENTILE_MIN_SAMPLES {
+        return None;
+    }
+    let below = sorted.partition_point(|&x| x < value);
+    let equal = sorted.partition_point(|&x| x <= value) - below;
+    Some((below as f64 + equal as f64 / 2.0) / sorted.len() as f64 * 100.0)
+}
+
+/// 打鍵数の帯ごとの、CPS と正確率の並べ替え済みの分布
+#[derive(Debug, Clone, Default)]
+pub struct PercentileTable {
+    cps: [Vec<f64>; LENGTH_BANDS.len()],
+    accuracy: [Vec<f64>; LENGTH_BANDS.len()],
+}
+
+impl PercentileTable {
+    /// 履歴から作る（ウォームアップ・取り込んだ記録・打鍵のない記録は数えない）
+    /// 帯の記録が多いときは無作為に選んだ一部で分布を近似する
+    pub fn from_history(history: &[TypeRecord]) -> Self {
+        // 帯ごとに決まった数だけ残すので、履歴がいくら長くても使うメモリは変わらない
+        let mut cps: [Reservoir; LENGTH_BANDS.len()] = Default::default();
+        let mut accuracy: [Reservoir; LENGTH_BANDS.len()] = Default::default();
+        for record in history.iter().filter(|r| r.counts_for_bests() && r.total_chars > 0) {
+            let band = length_band(record.total_chars);
+            cps[band].push(record.cps);
+            accuracy[band].push(record.accuracy());
+        }
+        Self { cps: cps.map(Reservoir::into_sorted), accuracy: accuracy.map(Reservoir::into_sorted) }
+    }
+
+    /// 1問分の成績が、同じ帯の過去の記録の中でどの位置にあるか
+    pub fn rank(&self, keystrokes: u32, cps: f64, accuracy: f64) -> Percentiles {
+        let band = length_band(keystrokes);
+        let cps = percentile_of(&self.cps[band], cps);
+        let accuracy = percentile_of(&self.accuracy[band], accuracy);
+        Percentiles { band, values: cps.zip(accuracy) }
+    }
+}
+
+/// 1問分の成績の順位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub band: usize,
+    /// (CPS, 正確率) の順位 (%)。記録が少ない帯では None
+    pub values: Option<(f64, f64)>,
+}
+
+impl Percentiles {
+    /// "faster than 82% · more accurate than 40% of your 6–10 key attempts"
+    pub fn summary(&self) -> String {
+        let band = band_label(self.band);
+        match self.values {
+            Some((cps, accuracy)) => format!(
+                "faster than {:.0}% · more accurate than {:.0}% of your {} key attempts",
+                cps, accuracy, band
+            ),
+            None => format!("{} key questions: not enough data for percentiles", band),
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:自己ベスト
+// --------------------------------------------------
+
+/// セッション開始時に目標として表示する自己ベスト
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PersonalBests {
+    /// 最高 CPS
+    pub best_cps: f64,
+    /// ノーミスで連続して打ち終えたお題の最多数
+    pub best_streak: u32,
+    /// 1問での最高スコア
+    pub best_score: f64,
+}
+
+impl PersonalBests {
+    /// 記録（古い順）から自己ベストを求める（ウォームアップ・時間切れ・正確率が `accuracy_floor` (%) に届かない記録は除く）
+    pub fn from_records<'a>(records: impl Iterator<Item = &'a TypeRecord>, accuracy_floor: f64) -> Self {
+        let mut bests = Self::default();
+        let mut streak = 0;
+        for record in records.filter(|record| !record.warmup) {
+            // 時間切れ・正確率の足りない記録は連続記録を途切れさせるだけで、ベストには数えない
+            if record.timed_out() || !record.meets_accuracy_floor(accuracy_floor) {
+                streak = 0;
+                continue;
+            }
+            streak = if record.misses == 0 { streak + 1 } else { 0 };
+            bests.best_cps = bests.best_cps.max(record.cps);
+            bests.best_streak = bests.best_streak.max(streak);
+            bests.best_score = bests.best_score.max(record.score);
+        }
+        bests
+    }
+
+    /// 1問分の成績で自己ベストを更新し、更新した項目名を返す
+    /// まだ記録がない項目（初回）は更新しても項目名を返さない
+    pub fn update(&mut self, cps: f64, streak: u32, score: f64) -> Vec<&'static str> {
+        let mut beaten = Vec::new();
+        if cps > self.best_cps {
+            if self.best_cps > 0.0 {
+                beaten.push("CPS");
+            }
+            self.best_cps = cps;
+        }
+        if streak > self.best_streak {
+            if self.best_streak > 0 {
+                beaten.push("Streak");
+            }
+            self.best_streak = streak;
+        }
+        if score > self.best_score {
+            if self.best_score > 0.0 {
+                beaten.push("Score");
+            }
+            self.best_score = score;
+        }
+        beaten
+    }
+}
+
+// --------------------------------------------------
+// MARK:セッションの集計
+// --------------------------------------------------
+
+/// いくつかのお題の成績の合計
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SetTotals {
+    pub questions: u32,
+    pub chars: u32,
+    pub misses: u32,
+    pub duration_sec: f64,
+    /// ミスから立ち直るのにかかった時間の合計
+    #[serde(default)]
+    pub error_loss_sec: f64,
+}
+
+impl SetTotals {
+    pub fn add(&mut self, chars: u32, misses: u32, duration_sec: f64, error_loss_sec: f64) {
+        self.questions += 1;
+        self.chars += chars;
+        self.misses += misses;
+        self.duration_sec += duration_sec;
+        self.error_loss_sec += error_loss_sec;
+    }
+
+    /// 「3 q · 5.12 CPS · 98.0% · 2.4s lost to errors」の形式（お題がなければ None）
+    pub fn summary(&self) -> Option<String> {
+        if self.questions == 0 {
+            return None;
+        }
+        let cps = if self.duration_sec > 0.0 { f64::from(self.chars) / self.duration_sec } else { 0.0 };
+        let attempts = self.chars + self.misses;
+        let accuracy = if attempts > 0 { f64::from(self.chars) / f64::from(attempts) * 100.0 } else { 100.0 };
+        let lost = if self.error_loss_sec > 0.0 {
+            format!(" · {:.1}s lost to errors", self.error_loss_sec)
+        } else {
+            String::new()
+        };
+        Some(format!("{} q · {:.2} CPS · {:.1}%{}", self.questions, cps, accuracy, lost))
+    }
+}
+
+/// 休憩をはさんだ直後のお題と、それ以外（ウォームアップを除く）のお題の成績
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CooldownComparison {
+    pub after_cooldown: SetTotals,
+    pub other: SetTotals,
+}
+
+impl CooldownComparison {
+    pub fn from_history<'a>(history: impl IntoIterator<Item = &'a TypeRecord>) -> Self {
+        let mut comparison = Self::default();
+        for record in history.into_iter().filter(|record| !record.warmup) {
+            let totals = if record.after_cooldown { &mut comparison.after_cooldown } else { &mut comparison.other };
+            totals.add(record.total_chars, record.misses, record.duration_sec, f64::from(record.error_loss_ms) / 1000.0);
+        }
+        comparison
+    }
+}
+
+/// セッションの目的ごとの成績（ウォームアップのお題を除く）
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct IntentTotals {
+    pub intent: SessionIntent,
+    pub totals: SetTotals,
+}
+
+/// 目的ごとに記録を集計する（記録のない目的は含めない）
+pub fn totals_by_intent<'a>(history: impl IntoIterator<Item = &'a TypeRecord>) -> Vec<IntentTotals> {
+    let mut rows: Vec<IntentTotals> = Vec::new();
+    for record in history.into_iter().filter(|record| !record.warmup) {
+        let idx = match rows.iter().position(|row| row.intent == record.intent) {
+            Some(idx) => idx,
+            None => {
+                rows.push(IntentTotals { intent: record.intent, totals: SetTotals::default() });
+                rows.len() - 1
+            }
+        };
+        rows[idx].totals.add(record.total_chars, record.misses, record.duration_sec, f64::from(record.error_loss_ms) / 1000.0);
+    }
+    rows.sort_by_key(|row| row.intent as u8);
+    rows
+}
+
+/// 1回のセッションの成績（ウォームアップと本番を分けて数える）
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    pub warmup: SetTotals,
+    pub main: SetTotals,
+    /// セッションを始めるときに選んだ目的（古い途中経過では unspecified）
+    #[serde(default)]
+    pub intent: SessionIntent,
+}
+
+impl SessionStats {
+    /// メニューに表示する1行（何も打っていなければ None）
+    pub fn summary(&self) -> Option<String> {
+        let label = match self.intent {
+            SessionIntent::Unspecified => "Session".to_string(),
+            intent => format!("Session [{}]", intent.label()),
+        };
+        match (self.warmup.summary(), self.main.summary()) {
+            (None, None) => None,
+            (None, Some(main)) => Some(format!("{}: {}", label, main)),
+            (Some(warmup), None) => Some(format!("{}: warm-up {}", label, warmup)),
+            (Some(warmup), Some(main)) => Some(format!("{}: warm-up {} | main {}", label, warmup, main)),
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:日ごとの推移
+// --------------------------------------------------
+
+/// 1日分の集計（遊んだ日のみ）
+#[derive(Debug, Clone, Copy)]
+pub struct DailyStat {
+    /// `today` から何日前か（0 が今日）
+    pub days_ago: u32,
+    /// その日の平均 CPS
+    pub average_cps: f64,
+    /// その日の正確率 (%)
+    pub accuracy: f64,
+}
+
+/// 直近 `days` 日間（今日を含む）の日ごとの集計を、古い日から順に返す
+/// 日付はローカル時刻で区切る。記録のない日は含めない（グラフでは途切れさせる）
+pub fn build_daily_stats<'a>(
+    history: impl IntoIterator<Item = &'a TypeRecord>,
+    today: NaiveDate,
+    days: u32,
+) -> Vec<DailyStat> {
+    let mut buckets = DayBuckets::new(today, days);
+    for record in history {
+        buckets.push(record);
+    }
+    buckets
+        .finish()
+        .map(|(days_ago, average_cps, accuracy)| DailyStat { days_ago, average_cps, accuracy })
+        .collect()
+}
+
+/// 点の数が `max_points` を超えるとき、x を一定幅の区間に分けて区間ごとに平均する
+/// 戻り値は (間引いた点, 区間の幅)。間引いた点の x は区間の中央になる
+pub fn downsample(points: &[(f64, f64)], span: f64, max_points: usize) -> (Vec<(f64, f64)>, f64) {
+    let step = (span / max_points.max(1) as f64).ceil().max(1.0);
+    if step <= 1.0 {
+        return (points.to_vec(), 1.0);
+    }
+
+    // 区間の番号 → (y の合計, 点の数)
+    let mut buckets: Vec<(i64, f64, u32)> = Vec::new();
+    for &(x, y) in points {
+        let bucket = (x / step).floor() as i64;
+        match buckets.last_mut() {
+            Some((b, sum, n)) if *b == bucket => {
+                *sum += y;
+                *n += 1;
+            }
+            _ => buckets.push((bucket, y, 1)),
+        }
+    }
+    let result = buckets
+        .into_iter()
+        .map(|(bucket, sum, n)| ((bucket as f64 + 0.5) * step, sum / f64::from(n)))
+        .collect();
+    (result, step)
+}
+
+/// x の間隔が `max_gap` を超えるところで点の列を分ける（途切れた線として描くため）
+pub fn split_runs(points: &[(f64, f64)], max_gap: f64) -> Vec<Vec<(f64, f64)>> {
+    let mut runs: Vec<Vec<(f64, f64)>> = Vec::new();
+    for &point in points {
+        match runs.last_mut() {
+            Some(run) if run.last().is_some_and(|last| point.0 - last.0 <= max_gap + 1e-6) => {
+                run.push(point)
+            }
+            _ => runs.push(vec![point]),
+        }
+    }
+    runs
+}
+
+// --------------------------------------------------
+// MARK:今日の成績
+// --------------------------------------------------
+
+/// 今日の平均と比べる期間（今日を含まない直前の日数）
+pub const TODAY_COMPARE_DAYS: i64 = 30;
+
+/// 今日（ローカル時刻の日付）の成績。取り込んだ記録は数えない
+#[derive(Debug, Clone, Copy)]
+pub struct TodayStats {
+    pub sessions: u32,
+    pub questions: u32,
+    pub chars: u64,
+    pub misses: u64,
+    pub duration_sec: f64,
+    /// 今日得た経験値（連続ボーナスなどお題以外の分も含む）
+    pub xp: u64,
+    /// 今日の平均 CPS（ウォームアップとコード片の練習を除く。対象がなければ None）
+    pub average_cps: Option<f64>,
+    /// 今日より前の `TODAY_COMPARE_DAYS` 日間の平均 CPS（同じく対象がなければ None）
+    pub previous_cps: Option<f64>,
+}
+
+impl TodayStats {
+    /// 開くたびに履歴から計算し直す（日付が変わっても正しい日の分になるように）
+    /// 今日の記録がなければ None
+    pub fn build(history: &[TypeRecord], xp: u64, today: NaiveDate) -> Option<Self> {
+        let first_day = today - TimeDelta::days(TODAY_COMPARE_DAYS);
+        let mut totals = RunningTotals::default();
+        let mut duration_sec = 0.0;
+        let mut today_cps = SetTotals::default();
+        let mut previous_cps = SetTotals::default();
+        for record in history.iter().filter(|record| !record.imported) {
+            let date = record.timestamp.with_timezone(&Local).date_naive();
+            let compares = !record.warmup && !record.code;
+            if date == today {
+                totals.push(record);
+                duration_sec += record.duration_sec;
+                if compares {
+                    today_cps.add(record.total_chars, record.misses, record.duration_sec, 0.0);
+                }
+            } else if compares && date >= first_day && date < today {
+                previous_cps.add(record.total_chars, record.misses, record.duration_sec, 0.0);
+            }
+        }
+        if totals.is_empty() {
+            return None;
+        }
+        let average = |totals: &SetTotals| {
+            (totals.duration_sec > 0.0).then(|| f64::from(totals.chars) / totals.duration_sec)
+        };
+        Some(Self {
+            sessions: totals.sessions,
+            questions: totals.questions,
+            chars: totals.chars,
+            misses: totals.misses,
+            duration_sec,
+            xp,
+            average_cps: average(&today_cps),
+            previous_cps: average(&previous_cps),
+        })
+    }
+}
+
+// --------------------------------------------------
+// MARK:期間の比較
+// --------------------------------------------------
+
+/// ある期間の成績
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WindowStats {
+    pub questions: u32,
+    pub chars: u64,
+    pub sessions: u32,
+    pub average_cps: f64,
+    /// 正確率 (%)
+    pub accuracy: f64,
+    pub best_score: f64,
+}
+
+impl WindowStats {
+    /// 集計から作る（記録がなければ None）
+    fn from_totals(totals: &RunningTotals) -> Option<Self> {
+        (!totals.is_empty()).then(|| Self {
+            questions: totals.questions,
+            chars: totals.chars,
+            sessions: totals.sessions,
+            average_cps: totals.mean_cps,
+            accuracy: totals.accuracy(),
+            best_score: totals.best_score,
+        })
+    }
+}
+
+/// 直近の期間と、その前の同じ長さの期間の成績（ウォームアップとコードの練習は除く）
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WindowComparison {
+    pub days: u32,
+    pub current: Option<WindowStats>,
+    pub previous: Option<WindowStats>,
+}
+
+impl WindowComparison {
+    /// 直近の期間は (now - days, now]、前の期間は (now - 2 * days, now - days]
+    /// 履歴は古い順に並んでいるものとして1回だけなめる
+    pub fn from_history<'a>(history: impl IntoIterator<Item = &'a TypeRecord>, now: DateTime<Utc>, days: u32) -> Self {
+        let span = TimeDelta::days(i64::from(days));
+        let boundary = now - span;
+        let start = boundary - span;
+        let mut current = RunningTotals::default();
+        let mut previous = RunningTotals::default();
+        let compares = |r: &TypeRecord| !r.warmup && !r.code && r.timestamp > start && r.timestamp <= now;
+        for record in history.into_iter().filter(|r| compares(r)) {
+            if record.timestamp <= boundary {
+                previous.push(record);
+            } else {
+                current.push(record);
+            }
+        }
+        Self {
+            days,
+            current: WindowStats::from_totals(&current),
+            previous: WindowStats::from_totals(&previous),
+        }
+    }
+
+    /// 比べる指標ごとの (名前, 表示する小数の桁数, 前の期間, 直近の期間)。片方に記録がなければ None
+    pub fn rows(&self) -> Vec<(&'static str, usize, Option<f64>, Option<f64>)> {
+        let pick = |f: fn(&WindowStats) -> f64| (self.previous.as_ref().map(f), self.current.as_ref().map(f));
+        [
+            ("Chars", 0, pick(|w| w.chars as f64)),
+            ("Sessions", 0, pick(|w| f64::from(w.sessions))),
+            ("Avg CPS", 2, pick(|w| w.average_cps)),
+            ("Accuracy %", 1, pick(|w| w.accuracy)),
+            ("Best score", 0, pick(|w| w.best_score)),
+        ]
+        .into_iter()
+        .map(|(label, decimals, (previous, current))| (label, decimals, previous, current))
+        .collect()
+    }
+}
+
+/// 変化の割合 (%)。前の値が 0 のときは割合を出さない
+pub fn percent_change(previous: f64, current: f64) -> Option<f64> {
+    (previous != 0.0).then(|| (current - previous) / previous * 100.0)
+}
+
+/// 変化を "↑ 12.5%" のような形にする
+pub fn format_delta(previous: f64, current: f64) -> String {
+    let arrow = if current > previous {
+        "↑"
+    } else if current < previous {
+        "↓"
+    } else {
+        "="
+    };
+    match percent_change(previous, current) {
+        Some(pct) => format!("{} {:+.1}%", arrow, pct),
+        None => arrow.to_string(),
+    }
+}
+
+/// "7d" や "2w" の期間を日数にする（単位がなければ日）
+pub fn parse_window(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let (number, unit_days) = match text.strip_suffix(['d', 'D']) {
+        Some(number) => (number, 1),
+        None => match text.strip_suffix(['w', 'W']) {
+            Some(number) => (number, 7),
+            None => (text, 1),
+        },
+    };
+    match number.parse::<u32>() {
+        Ok(n) if n > 0 => n.checked_mul(unit_days).ok_or_else(|| format!("window too long: {}", text)),
+        _ => Err(format!("expected a window like 7d or 2w, got '{}'", text)),
+    }
+}
+
+// --------------------------------------------------
+// MARK:所要時間の見積もり
+// --------------------------------------------------
+
+/// 履歴がないときに見積もりに使う CPS
+pub const DEFAULT_ESTIMATE_CPS: f64 = 3.0;
+
+/// 始める前に見せる、出題範囲の量と所要時間の見積もり
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEstimate {
+    /// 出題範囲のお題の数
+    pub questions: usize,
+    /// 出題範囲から外しているお題の数
+    pub blacklisted: usize,
+    /// 標準的なローマ字で打ったときの打鍵数の合計
+    pub keystrokes: usize,
+    /// 見積もりに使った CPS
+    pub cps: f64,
+    /// CPS が直近の履歴からのものか（false なら既定値）
+    pub cps_from_history: bool,
+    /// 出題範囲をすべて打つのにかかる秒数
+    pub total_secs: f64,
+    /// 難易度の自動調整で出題されうる難易度の範囲
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiers: Option<(i32, i32)>,
+    /// その範囲での1問あたりの秒数 (最短の難易度, 最長の難易度)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_question_secs: Option<(f64, f64)>,
+}
+
+impl SessionEstimate {
+    /// 出題範囲の (難易度, 打鍵数) から見積もる
+    /// `tiers` を渡すと、その範囲の難易度ごとの1問あたりの秒数の幅も求める
+    pub fn new(
+        pool: &[(i32, usize)],
+        blacklisted: usize,
+        recent_cps: Option<f64>,
+        tiers: Option<(i32, i32)>,
+    ) -> Self {
+        let (cps, cps_from_history) = match recent_cps {
+            Some(cps) if cps > 0.0 => (cps, true),
+            _ => (DEFAULT_ESTIMATE_CPS, false),
+        };
+        let keystrokes: usize = pool.iter().map(|&(_, keys)| keys).sum();
+
+        let per_question_secs = tiers.and_then(|(low, high)| {
+            let averages: Vec<f64> = (low..=high)
+                .filter_map(|tier| {
+                    let keys: Vec<usize> =
+                        pool.iter().filter(|&&(t, _)| t == tier).map(|&(_, keys)| keys).collect();
+                    (!keys.is_empty()).then(|| keys.iter().sum::<usize>() as f64 / keys.len() as f64 / cps)
+                })
+                .collect();
+            let min = averages.iter().copied().reduce(f64::min)?;
+            let max = averages.iter().copied().reduce(f64::max)?;
+            Some((min, max))
+        });
+
+        Self {
+            questions: pool.len(),
+            blacklisted,
+            keystrokes,
+            cps,
+            cps_from_history,
+            total_secs: keystrokes as f64 / cps,
+            tiers,
+            per_question_secs,
+        }
+    }
+
+    /// メニューに出す1行の要約
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{} questions · {} keys · {} at {:.2} CPS",
+            self.questions,
+            self.keystrokes,
+            format_estimate(self.total_secs),
+            self.cps
+        );
+        if let Some((min, max)) = self.per_question_secs {
+            line.push_str(&format!(" · {} per question", format_secs_range(min, max)));
+        }
+        line
+    }
+}
+
+/// 見積もりの秒数を "~4 min" のような大まかな表現にする
+pub fn format_estimate(secs: f64) -> String {
+    let secs = secs.max(0.0).round() as u64;
+    match secs {
+        0..60 => format!("~{} s", secs),
+        60..3_600 => format!("~{} min", (secs + 30) / 60),
+        _ => format!("~{}h {}m", secs / 3_600, secs % 3_600 / 60),
+    }
+}
+
+/// 1問あたりの秒数の幅を "~2–5 s" の形にする（同じなら1つだけ）
+pub fn format_secs_range(min: f64, max: f64) -> String {
+    let (min, max) = (min.round() as u64, max.round() as u64);
+    if min == max { format!("~{} s", min) } else { format!("~{}–{} s", min, max) }
+}
+
+// --------------------------------------------------
+// MARK:出題の偏り
+// --------------------------------------------------
+
+/// 1つのお題の出題回数
+#[derive(Debug, Clone, Serialize)]
+pub struct RotationRow {
+    pub japanese: String,
+    pub hiragana: String,
+    pub serves: u32,
+}
+
+/// 出題範囲のお題がどれだけ均等に出題されているか（`stats --rotation`）
+#[derive(Debug, Clone, Serialize)]
+pub struct RotationReport {
+    pub total_serves: u64,
+    /// 一度も出題されていないお題の数
+    pub never_served: usize,
+    /// 出題回数のジニ係数（0 なら均等、1 に近いほど一部のお題に偏っている）
+    pub skew: f64,
+    /// 出題回数の多い順
+    pub rows: Vec<RotationRow>,
+}
+
+impl RotationReport {
+    pub fn new(mut rows: Vec<RotationRow>) -> Self {
+        rows.sort_by(|a, b| b.serves.cmp(&a.serves).then_with(|| a.hiragana.cmp(&b.hiragana)));
+        let serves: Vec<u32> = rows.iter().map(|row| row.serves).collect();
+        Self {
+            total_serves: serves.iter().map(|&n| u64::from(n)).sum(),
+            never_served: serves.iter().filter(|&&n| n == 0).count(),
+            skew: gini(&serves),
+            rows,
+        }
+    }
+}
+
+/// ジニ係数（値がすべて同じなら 0。合計が 0 のときも 0）
+pub fn gini(values: &[u32]) -> f64 {
+    let mut sorted: Vec<u64> = values.iter().map(|&n| u64::from(n)).collect();
+    sorted.sort_unstable();
+    let total: u64 = sorted.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted.iter().enumerate().map(|(i, &x)| (i as f64 + 1.0) * x as f64).sum();
+    2.0 * weighted / (n * total as f64) - (n + 1.0) / n
+}
+
+// --------------------------------------------------
+// MARK:表示用の整形
+// --------------------------------------------------
+
+/// 練習時間（秒）を "14h 32m" の形にする
+pub fn format_practice_time(secs: u64) -> String {
+    format!("{}h {}m", secs / 3_600, secs % 3_600 / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_data::{PlayerData, QuestionOutcome};
+    use chrono::{Local, TimeDelta, TimeZone};
+
+    fn record(idx: usize, cps_secs: f64, misses: u32, minutes_ago: i64) -> TypeRecord {
+        let mut record = TypeRecord::sample("ねこ", 10, cps_secs, misses);
+        record.question_id = Some(QuestionId::new("test", idx));
+        record.timestamp = Utc::now() - TimeDelta::minutes(minutes_ago);
+        record
+    }
+
+    #[test]
+    fn aggregates_count_every_attempt_and_keep_the_latest_play() {
+        let history = [record(0, 5.0, 0, 30), record(0, 2.0, 0, 90), record(1, 4.0, 0, 10)];
+        let index = build_question_aggregates(&history, 0.0);
+        let first = &index[&QuestionId::new("test", 0)];
+        assert_eq!(first.attempts, 2);
+        assert_eq!(first.best_cps, history[1].cps);
+        assert_eq!(first.best_score, history[1].score.max(history[0].score));
+        assert_eq!(first.last_played, Some(history[0].timestamp));
+        assert_eq!(index[&QuestionId::new("test", 1)].attempts, 1);
+    }
+
+    #[test]
+    fn bests_ignore_records_below_the_floor_and_timeouts() {
+        let mut timed_out = record(0, 1.0, 0, 5);
+        timed_out.outcome = QuestionOutcome::TimedOut;
+        // 10 文字で 2 ミスは正確率 83.3%
+        let sloppy = record(0, 1.5, 2, 4);
+        let clean = record(0, 4.0, 0, 3);
+        let index = build_question_aggregates(&[timed_out, sloppy, clean.clone()], 90.0);
+        let aggregate = &index[&QuestionId::new("test", 0)];
+        assert_eq!(aggregate.attempts, 3);
+        assert_eq!((aggregate.best_cps, aggregate.best_score), (clean.cps, clean.score));
+    }
+
+    #[test]
+    fn records_without_an_id_are_not_aggregated() {
+        let mut record = record(0, 2.0, 0, 1);
+        record.question_id = None;
+        assert!(build_question_aggregates(&[record], 0.0).is_empty());
+    }
+
+    #[test]
+    fn the_aggregate_cache_is_rebuilt_when_history_grows_or_the_floor_changes() {
+        let mut data = PlayerData::default();
+        data.history.push(record(0, 2.0, 0, 1));
+        let first = data.question_aggregates(90.0);
+        assert!(Arc::ptr_eq(&first, &data.question_aggregates(90.0)));
+
+        let other_floor = data.question_aggregates(80.0);
+        assert!(!Arc::ptr_eq(&first, &other_floor));
+
+        data.history.push(record(0, 3.0, 0, 0));
+        let grown = data.question_aggregates(80.0);
+        assert!(!Arc::ptr_eq(&other_floor, &grown));
+        assert_eq!(grown[&QuestionId::new("test", 0)].attempts, 2);
+    }
+
+    #[test]
+    fn practice_time_is_shown_in_hours_and_minutes() {
+        assert_eq!(format_practice_time(0), "0h 0m");
+        assert_eq!(format_practice_time(59), "0h 0m");
+        assert_eq!(format_practice_time(3_599), "0h 59m");
+        assert_eq!(format_practice_time(14 * 3_600 + 32 * 60 + 10), "14h 32m");
+        assert_eq!(format_practice_time(250 * 3_600), "250h 0m");
+    }
+
+    /// `today` の `days_ago` 日前の、ローカル時刻の正午の記録
+    fn record_on(today: NaiveDate, days_ago: i64, total_chars: u32, duration_sec: f64, misses: u32) -> TypeRecord {
+        record_at(today, days_ago, 12, total_chars, duration_sec, misses)
+    }
+
+    /// `today` の `days_ago` 日前の、ローカル時刻の `hour` 時の記録
+    fn record_at(today: NaiveDate, days_ago: i64, hour: u32, total_chars: u32, duration_sec: f64, misses: u32) -> TypeRecord {
+        use chrono::TimeZone;
+        let mut record = TypeRecord::sample("ねこ", total_chars, duration_sec, misses);
+        let at = (today - TimeDelta::days(days_ago)).and_hms_opt(hour, 0, 0).unwrap();
+        record.timestamp = Local.from_local_datetime(&at).unwrap().with_timezone(&Utc);
+        record
+    }
+
+    #[test]
+    fn daily_stats_skip_days_without_play_and_records_outside_the_window() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        let history = [
+            record_on(today, 10, 10, 1.0, 0),
+            record_on(today, 3, 10, 5.0, 0),
+            record_on(today, 0, 10, 2.0, 0),
+            record_on(today, 0, 10, 4.0, 10),
+            record_on(today, -1, 10, 1.0, 0),
+        ];
+        let days = build_daily_stats(&history, today, 7);
+
+        // 古い日から順に、遊んだ日だけ
+        assert_eq!(days.iter().map(|d| d.days_ago).collect::<Vec<_>>(), [3, 0]);
+        assert_eq!((days[0].average_cps, days[0].accuracy), (2.0, 100.0));
+        // 平均 CPS は記録ごとの CPS の平均、正確率は打鍵数とミス数の合計から
+        assert_eq!(days[1].average_cps, (5.0 + 2.5) / 2.0);
+        assert_eq!(days[1].accuracy, 20.0 / 30.0 * 100.0);
+    }
+
+    #[test]
+    fn a_history_shorter_than_the_window_gives_only_its_days() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        assert!(build_daily_stats(&[], today, 90).is_empty());
+        let days = build_daily_stats(&[record_on(today, 1, 10, 2.0, 0)], today, 365);
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].days_ago, 1);
+    }
+
+    #[test]
+    fn points_within_the_width_are_not_downsampled() {
+        let points = [(0.0, 1.0), (5.0, 2.0), (29.0, 3.0)];
+        assert_eq!(downsample(&points, 30.0, 30), (points.to_vec(), 1.0));
+    }
+
+    #[test]
+    fn downsampling_averages_each_bucket_at_its_center() {
+        let points = [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0), (4.0, 4.0), (8.0, 6.0), (9.0, 2.0)];
+        let (sampled, step) = downsample(&points, 90.0, 30);
+        assert_eq!(step, 3.0);
+        assert_eq!(sampled, [(1.5, 3.0), (4.5, 4.0), (7.5, 6.0), (10.5, 2.0)]);
+    }
+
+    #[test]
+    fn runs_break_at_gaps_wider_than_the_step() {
+        let points = [(0.0, 1.0), (1.0, 1.0), (4.0, 1.0), (5.0, 1.0), (9.0, 1.0)];
+        let runs = split_runs(&points, 1.0);
+        assert_eq!(runs, [vec![(0.0, 1.0), (1.0, 1.0)], vec![(4.0, 1.0), (5.0, 1.0)], vec![(9.0, 1.0)]]);
+        assert_eq!(split_runs(&points, 4.0).len(), 1);
+        assert!(split_runs(&[], 1.0).is_empty());
+    }
+
+    #[test]
+    fn length_bands_include_both_ends() {
+        let bands: Vec<usize> = [0, 1, 5, 6, 10, 11, 20, 21, u32::MAX].into_iter().map(length_band).collect();
+        assert_eq!(bands, [0, 0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!((band_label(1), band_label(3), band_label(4)), ("6–10".to_string(), "21+".to_string(), String::new()));
+    }
+
+    #[test]
+    fn percentiles_use_the_midrank_of_ties() {
+        let sorted: Vec<f64> = (1..=20).map(f64::from).collect();
+        assert_eq!(percentile_of(&sorted[..19], 10.0), None);
+        // 9 個が下回り、1 個が同じ値なので (9 + 0.5) / 20
+        assert_eq!(percentile_of(&sorted, 10.0), Some(47.5));
+        assert_eq!(percentile_of(&sorted, 10.5), Some(50.0));
+        assert_eq!(percentile_of(&sorted, 0.0), Some(0.0));
+        assert_eq!(percentile_of(&sorted, 21.0), Some(100.0));
+        assert_eq!(percentile_of(&[3.0; 20], 3.0), Some(50.0));
+    }
+
+    #[test]
+    fn percentiles_compare_only_within_the_length_band() {
+        // 5 打鍵は遅く、6 打鍵は速い記録ばかり
+        let mut history: Vec<TypeRecord> = (0..20).map(|i| TypeRecord::sample("ねこ", 5, 2.0 + f64::from(i) * 0.1, 0)).collect();
+        history.extend((0..20).map(|i| TypeRecord::sample("いぬ", 6, 0.5 + f64::from(i) * 0.01, 0)));
+        let table = PercentileTable::from_history(&history);
+
+        let short = table.rank(5, 5.0 / 2.0, 100.0);
+        assert_eq!(short.band, 0);
+        let (cps, accuracy) = short.values.unwrap();
+        assert!(cps > 50.0, "{cps}");
+        assert_eq!(accuracy, 50.0);
+
+        let long = table.rank(6, 5.0 / 2.0, 100.0);
+        assert_eq!(long.band, 1);
+        assert_eq!(long.values.unwrap().0, 0.0);
+        assert_eq!(long.summary(), "faster than 0% · more accurate than 50% of your 6–10 key attempts");
+    }
+
+    #[test]
+    fn a_sparse_band_has_not_enough_data() {
+        let mut history: Vec<TypeRecord> = (0..19).map(|_| TypeRecord::sample("ねこ", 12, 3.0, 0)).collect();
+        // 記録として数えないものは足しても 20 にならない
+        let mut warmup = TypeRecord::sample("ねこ", 12, 3.0, 0);
+        warmup.warmup = true;
+        history.push(warmup);
+        let rank = PercentileTable::from_history(&history).rank(12, 4.0, 100.0);
+        assert_eq!(rank, Percentiles { band: 2, values: None });
+        assert_eq!(rank.summary(), "11–20 key questions: not enough data for percentiles");
+
+        history.push(TypeRecord::sample("ねこ", 12, 3.0, 0));
+        assert!(PercentileTable::from_history(&history).rank(12, 4.0, 100.0).values.is_some());
+    }
+
+    #[test]
+    fn the_percentile_cache_is_rebuilt_when_history_grows() {
+        let mut data = PlayerData::default();
+        data.history.extend((0..20).map(|_| TypeRecord::sample("ねこ", 4, 2.0, 0)));
+        let first = data.percentiles();
+        assert!(Arc::ptr_eq(&first, &data.percentiles()));
+
+        data.history.push(TypeRecord::sample("ねこ", 4, 1.0, 0));
+        let grown = data.percentiles();
+        assert!(!Arc::ptr_eq(&first, &grown));
+        // 新しい記録 (4 CPS) が最速になる
+        assert_eq!(grown.rank(4, 4.0, 100.0).values.unwrap().0, 100.0 - 50.0 / 21.0);
+    }
+
+    #[test]
+    fn windows_split_at_the_boundary_and_skip_warmups_and_code() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 15, 12, 0, 0).unwrap();
+        let at = |days: i64, secs: i64| {
+            let mut record = TypeRecord::sample("ねこ", 10, 2.0, 0);
+            record.timestamp = now - TimeDelta::days(days) + TimeDelta::seconds(secs);
+            record
+        };
+        let mut warmup = at(1, 0);
+        warmup.warmup = true;
+        let mut code = at(1, 0);
+        code.code = true;
+        // 境界ちょうどの記録は前の期間に入り、前の期間の始まりちょうどと今より後の記録はどちらにも入らない
+        let history = [at(14, 0), at(14, 1), at(7, 0), at(7, 1), at(0, 0), at(0, 1), warmup, code];
+        let comparison = WindowComparison::from_history(&history, now, 7);
+
+        let current = comparison.current.unwrap();
+        let previous = comparison.previous.unwrap();
+        assert_eq!((current.questions, previous.questions), (2, 2));
+        assert_eq!((current.chars, current.sessions), (20, 2));
+        assert_eq!(previous.average_cps, 5.0);
+    }
+
+    #[test]
+    fn an_empty_window_has_no_stats_instead_of_zeros() {
+        let now = Utc::now();
+        let mut record = TypeRecord::sample("ねこ", 10, 2.0, 0);
+        record.timestamp = now - TimeDelta::hours(1);
+        let comparison = WindowComparison::from_history(&[record], now, 7);
+        assert!(comparison.previous.is_none());
+        assert!(comparison.current.is_some());
+        assert!(comparison.rows().iter().all(|&(_, _, previous, current)| previous.is_none() && current.is_some()));
+
+        let nothing = WindowComparison::from_history(&[], now, 7);
+        assert!(nothing.current.is_none() && nothing.previous.is_none());
+    }
+
+    #[test]
+    fn deltas_show_the_direction_and_the_percentage() {
+        assert_eq!(format_delta(2.0, 2.5), "↑ +25.0%");
+        assert_eq!(format_delta(4.0, 3.0), "↓ -25.0%");
+        assert_eq!(format_delta(3.0, 3.0), "= +0.0%");
+        // 前が 0 なら割合は出さない
+        assert_eq!(percent_change(0.0, 5.0), None);
+        assert_eq!(format_delta(0.0, 5.0), "↑");
+    }
+
+    #[test]
+    fn windows_parse_days_and_weeks() {
+        assert_eq!(parse_window("7d"), Ok(7));
+        assert_eq!(parse_window(" 2W "), Ok(14));
+        assert_eq!(parse_window("30"), Ok(30));
+        assert!(parse_window("0d").is_err());
+        assert!(parse_window("week").is_err());
+        assert!(parse_window("4294967295w").is_err());
+    }
+
+    fn rotation_row(hiragana: &str, serves: u32) -> RotationRow {
+        RotationRow { japanese: hiragana.to_string(), hiragana: hiragana.to_string(), serves }
+    }
+
+    #[test]
+    fn the_skew_is_zero_when_even_and_grows_with_imbalance() {
+        assert_eq!(gini(&[]), 0.0);
+        assert_eq!(gini(&[0, 0]), 0.0);
+        assert!(gini(&[7, 7, 7]).abs() < 1e-12);
+        // 1問だけに偏ると (n - 1) / n
+        assert!((gini(&[0, 0, 12]) - 2.0 / 3.0).abs() < 1e-12);
+        assert!((gini(&[1, 2, 3, 4]) - 0.25).abs() < 1e-12);
+        assert!(gini(&[5, 5, 6]) < gini(&[1, 5, 10]));
+        assert_eq!(gini(&[3, 1, 2]), gini(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn the_rotation_report_lists_the_most_served_first() {
+        let report = RotationReport::new(vec![
+            rotation_row("いぬ", 3),
+            rotation_row("とり", 0),
+            rotation_row("ねこ", 9),
+            rotation_row("さる", 3),
+        ]);
+        let order: Vec<&str> = report.rows.iter().map(|row| row.hiragana.as_str()).collect();
+        assert_eq!(order, ["ねこ", "いぬ", "さる", "とり"]);
+        assert_eq!((report.total_serves, report.never_served), (15, 1));
+        assert!((report.skew - gini(&[0, 3, 3, 9])).abs() < 1e-12);
+    }
+
+    #[test]
+    fn totals_by_intent_skip_warmups_and_absent_intents() {
+        let tagged = |intent: SessionIntent, chars: u32, warmup: bool| {
+            let mut record = TypeRecord::sample("ねこ", chars, 2.0, 1);
+            record.intent = intent;
+            record.warmup = warmup;
+            record
+        };
+        let history = [
+            tagged(SessionIntent::Learning, 10, false),
+            tagged(SessionIntent::Test, 20, false),
+            tagged(SessionIntent::Test, 30, false),
+            tagged(SessionIntent::WarmUp, 40, true),
+        ];
+        let rows = totals_by_intent(&history);
+        let summary: Vec<(SessionIntent, u32, u32)> = rows.iter().map(|row| (row.intent, row.totals.questions, row.totals.chars)).collect();
+        assert_eq!(summary, vec![(SessionIntent::Test, 2, 50), (SessionIntent::Learning, 1, 10)]);
+        assert!(totals_by_intent(&history[3..]).is_empty());
+    }
+
+    #[test]
+    fn today_counts_local_records_and_compares_with_the_month_before() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        let mut warmup = record_at(today, 0, 9, 100, 1.0, 1);
+        warmup.warmup = true;
+        let mut imported = record_at(today, 0, 10, 100, 1.0, 1);
+        imported.imported = true;
+        let history = vec![
+            record_at(today, TODAY_COMPARE_DAYS + 1, 9, 100, 1.0, 1),
+            record_at(today, TODAY_COMPARE_DAYS, 9, 10, 5.0, 1),
+            record_at(today, 1, 23, 10, 5.0, 1),
+            warmup,
+            imported,
+            record_at(today, 0, 9, 12, 2.0, 1),
+            record_at(today, 0, 20, 18, 3.0, 1),
+        ];
+
+        let stats = TodayStats::build(&history, 75, today).unwrap();
+        assert_eq!((stats.sessions, stats.questions, stats.chars, stats.misses), (2, 3, 130, 3));
+        assert!((stats.duration_sec - 6.0).abs() < 1e-9);
+        assert_eq!(stats.xp, 75);
+        // ウォームアップを除いた今日の平均と、今日を含まない 30 日間の平均
+        assert!((stats.average_cps.unwrap() - 6.0).abs() < 1e-9);
+        assert!((stats.previous_cps.unwrap() - 2.0).abs() < 1e-9);
+
+        assert!(TodayStats::build(&history, 0, today + TimeDelta::days(1)).is_none());
+    }
+
+    /// 押したキーのうち `correct` 個が正しかった記録（正確率は correct / total）
+    fn keyed(idx: usize, cps_secs: f64, correct: u32, total: u32) -> TypeRecord {
+        let mut record = record(idx, cps_secs, total - correct, 1);
+        record.keystrokes = total;
+        record
+    }
+
+    #[test]
+    fn records_exactly_at_the_floor_count_for_bests() {
+        // 19/20 は 95%、速い方の 18/20 は 90%
+        let at_floor = keyed(0, 3.0, 19, 20);
+        let below = keyed(0, 1.0, 18, 20);
+        let history = [at_floor.clone(), below.clone()];
+
+        let aggregate = &build_question_aggregates(&history, 95.0)[&QuestionId::new("test", 0)];
+        assert_eq!((aggregate.best_cps, aggregate.best_score), (at_floor.cps, at_floor.score));
+        let bests = PersonalBests::from_records(history.iter(), 95.0);
+        assert_eq!((bests.best_cps, bests.best_score), (at_floor.cps, at_floor.score));
+
+        // 下限を変えると、記録はそのままで過去の記録の扱いも変わる
+        let aggregate = &build_question_aggregates(&history, 90.0)[&QuestionId::new("test", 0)];
+        assert_eq!(aggregate.best_cps, below.cps);
+        assert_eq!(PersonalBests::from_records(history.iter(), 90.0).best_cps, below.cps);
+        let aggregate = &build_question_aggregates(&history, 96.0)[&QuestionId::new("test", 0)];
+        assert_eq!((aggregate.attempts, aggregate.best_cps), (2, 0.0));
+        assert_eq!(PersonalBests::from_records(history.iter(), 96.0).best_cps, 0.0);
+    }
+
+    #[test]
+    fn a_record_below_the_floor_breaks_the_no_miss_streak() {
+        let clean = || keyed(0, 2.0, 20, 20);
+        let history = [clean(), clean(), keyed(0, 2.0, 18, 20), clean()];
+        assert_eq!(PersonalBests::from_records(history.iter(), 95.0).best_streak, 2);
+        // 下限 0 ならミスのある記録として途切れる
+        assert_eq!(PersonalBests::from_records(history.iter(), 0.0).best_streak, 2);
+        let history = [clean(), clean(), keyed(0, 2.0, 19, 20), clean()];
+        assert_eq!(PersonalBests::from_records(history.iter(), 95.0).best_streak, 2);
+    }
+}
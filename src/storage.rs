@@ -0,0 +1,297 @@
+// ============================================
+// src/storage.rs
+// セーブデータの保存先（ファイル / メモリ）
+// AppState はこのトレイト越しに読み書きし、保存先のパスを直接は知らない
+// データディレクトリがネットワークドライブにあると読み書きに数秒かかることがあるので、
+// タイピング画面からの読み書きは別スレッドで行う（`ProfileLoader` / `QueuedFileStorage`）
+// ============================================
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::debug_log::dlog;
+use crate::save_data::{IntegrityReport, PlayerData};
+
+/// 保存に失敗したとき、書き直すまでの間隔
+const SAVE_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+/// 終了したあと（新しいデータが来なくなったあと）に書き直す回数の上限
+const SHUTDOWN_SAVE_ATTEMPTS: u32 = 3;
+/// 読み込みを待つ間に `tick` を呼ぶ間隔
+const LOAD_WAIT_TICK: Duration = Duration::from_millis(100);
+
+/// セーブデータの保存先
+pub trait Storage {
+    /// 読み込み、整合性チェックの結果も返す（何もなければ初期値）
+    fn load_with_report(&self) -> (PlayerData, IntegrityReport);
+
+    fn load(&self) -> PlayerData {
+        self.load_with_report().0
+    }
+
+    /// データ全体を保存する
+    fn save(&mut self, data: &PlayerData);
+
+    /// お題の記録を1件追加した直後に呼ぶ（新しい記録は `data.history` の最後）
+    /// 追記できない保存先では全体を保存する
+    fn append_history(&mut self, data: &PlayerData) {
+        self.save(data);
+    }
+
+    /// 書けずに待っている保存があるか
+    fn save_pending(&self) -> bool {
+        false
+    }
+}
+
+/// データディレクトリのセーブファイル（バイナリ + 設定で有効なら JSON のコピー）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileStorage;
+
+impl Storage for FileStorage {
+    fn load_with_report(&self) -> (PlayerData, IntegrityReport) {
+        PlayerData::load_with_report()
+    }
+
+    fn save(&mut self, data: &PlayerData) {
+        data.save();
+    }
+}
+
+/// ファイルに触れない保存先。保存したデータはメモリにだけ残る
+/// （`start --dry-run` のように、セーブファイルを書き換えてはいけない場面で使う）
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    data: Option<PlayerData>,
+}
+
+impl MemoryStorage {
+    /// `data` を保存済みの状態から始める
+    pub fn new(data: PlayerData) -> Self {
+        Self { data: Some(data) }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_with_report(&self) -> (PlayerData, IntegrityReport) {
+        let report = IntegrityReport::new("memory");
+        (self.data.clone().unwrap_or_default(), report)
+    }
+
+    fn save(&mut self, data: &PlayerData) {
+        self.data = Some(data.clone());
+    }
+}
+
+/// 別スレッドでセーブファイルを読み込む
+pub struct ProfileLoader {
+    profile: Receiver<(PlayerData, IntegrityReport)>,
+}
+
+impl ProfileLoader {
+    /// 読み込みを始める
+    pub fn start() -> Self {
+        Self::spawn(|| FileStorage.load_with_report())
+    }
+
+    /// `load` を別スレッドで呼ぶ
+    fn spawn(load: impl FnOnce() -> (PlayerData, IntegrityReport) + Send + 'static) -> Self {
+        let (sender, profile) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(load());
+        });
+        Self { profile }
+    }
+
+    /// 読み終わっていれば結果を返す（待たない）
+    pub fn poll(&self) -> Option<(PlayerData, IntegrityReport)> {
+        self.profile.try_recv().ok()
+    }
+
+    /// `deadline` まで読み終わるのを待つ（待つ間は数回 / 秒 `tick` を呼ぶ）
+    /// 間に合わなかったとき、読み込みのスレッドが止まったときは None
+    pub fn wait_until(&self, deadline: Instant, mut tick: impl FnMut()) -> Option<(PlayerData, IntegrityReport)> {
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.profile.recv_timeout(remaining.min(LOAD_WAIT_TICK)) {
+                Ok(profile) => return Some(profile),
+                Err(RecvTimeoutError::Timeout) => tick(),
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        None
+    }
+}
+
+/// データディレクトリのセーブファイル。書き込みは別スレッドで行い、呼び出し側は待たない
+/// 書けなかったときは間をおいて書き直す。待っている間に新しいデータが来たら、新しい方だけを書く
+pub struct QueuedFileStorage {
+    /// 落とすと書き込みのスレッドが残りを書いて止まる
+    saves: Option<Sender<Box<PlayerData>>>,
+    worker: Option<JoinHandle<()>>,
+    /// 書けずに書き直しを待っているか
+    pending: Arc<AtomicBool>,
+}
+
+impl QueuedFileStorage {
+    pub fn start() -> Self {
+        let (saves, receiver) = mpsc::channel();
+        let pending = Arc::new(AtomicBool::new(false));
+        let worker_pending = Arc::clone(&pending);
+        let worker = thread::spawn(move || write_queued(receiver, &worker_pending, SAVE_RETRY_INTERVAL, |data| data.try_save()));
+        Self { saves: Some(saves), worker: Some(worker), pending }
+    }
+}
+
+impl Storage for QueuedFileStorage {
+    fn load_with_report(&self) -> (PlayerData, IntegrityReport) {
+        PlayerData::load_with_report()
+    }
+
+    fn save(&mut self, data: &PlayerData) {
+        if let Some(saves) = &self.saves {
+            let _ = saves.send(Box::new(data.clone()));
+        }
+    }
+
+    fn save_pending(&self) -> bool {
+        self.pending.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for QueuedFileStorage {
+    fn drop(&mut self) {
+        // 送る側を閉じると、書き込みのスレッドは残っているデータを書いてから止まる
+        self.saves = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// 書き込みのスレッド。届いたデータのうち一番新しいものを書き、書けなければ `retry` おいて書き直す
+fn write_queued(
+    saves: Receiver<Box<PlayerData>>,
+    pending: &AtomicBool,
+    retry: Duration,
+    mut save: impl FnMut(&PlayerData) -> io::Result<()>,
+) {
+    while let Ok(mut data) = saves.recv() {
+        let mut attempts_after_close = 0;
+        loop {
+            while let Ok(newer) = saves.try_recv() {
+                data = newer;
+            }
+            let Err(e) = save(&data) else {
+                break;
+            };
+            pending.store(true, Ordering::Relaxed);
+            dlog!("save", "queued save failed, retrying in {:?}: {}", retry, e);
+            match saves.recv_timeout(retry) {
+                Ok(newer) => data = newer,
+                Err(RecvTimeoutError::Timeout) => {}
+                // 終了したあとは何度か書き直して、それでも書けなければあきらめる（終了が止まったままにならないように）
+                Err(RecvTimeoutError::Disconnected) => {
+                    attempts_after_close += 1;
+                    if attempts_after_close >= SHUTDOWN_SAVE_ATTEMPTS {
+                        dlog!("save", "gave up saving after {} attempts at shutdown", attempts_after_close);
+                        return;
+                    }
+                    thread::sleep(retry);
+                }
+            }
+        }
+        pending.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn profile(level: u32) -> (PlayerData, IntegrityReport) {
+        let mut data = PlayerData::default();
+        data.level = level;
+        (data, IntegrityReport::new("memory"))
+    }
+
+    #[test]
+    fn a_slow_load_misses_the_deadline_and_arrives_later() {
+        let (release, gate) = mpsc::channel::<()>();
+        let loader = ProfileLoader::spawn(move || {
+            let _ = gate.recv();
+            profile(7)
+        });
+        let mut ticks = 0;
+        assert!(loader.wait_until(Instant::now() + Duration::from_millis(250), || ticks += 1).is_none());
+        assert!(ticks >= 2, "{ticks}");
+        assert!(loader.poll().is_none());
+
+        release.send(()).unwrap();
+        let (data, _) = loader.wait_until(Instant::now() + Duration::from_secs(5), || {}).unwrap();
+        assert_eq!(data.level, 7);
+    }
+
+    #[test]
+    fn a_failed_load_thread_ends_the_wait_at_once() {
+        let loader = ProfileLoader::spawn(|| panic!("the share went away"));
+        let started = Instant::now();
+        assert!(loader.wait_until(started + Duration::from_secs(5), || {}).is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// 書き込みのスレッドを `failures` 回失敗する保存先で動かし、書けたデータの level と、書き直しを待っているかを返す
+    /// `close` なら送る側を先に閉じて終了時の動きにし、そうでなければ書き終わるのを待ってから閉じる
+    fn run_writer(levels: &[u32], failures: usize, close: bool) -> (Vec<u32>, bool) {
+        let (saves, receiver) = mpsc::channel();
+        for &level in levels {
+            saves.send(Box::new(profile(level).0)).unwrap();
+        }
+        let mut saves = Some(saves);
+        if close {
+            saves = None;
+        }
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let pending = Arc::new(AtomicBool::new(false));
+        let (worker_written, worker_pending) = (Arc::clone(&written), Arc::clone(&pending));
+        let mut left = failures;
+        let worker = thread::spawn(move || {
+            write_queued(receiver, &worker_pending, Duration::from_millis(5), |data| {
+                if left > 0 {
+                    left -= 1;
+                    return Err(io::Error::other("busy"));
+                }
+                worker_written.lock().unwrap().push(data.level);
+                Ok(())
+            })
+        });
+        if saves.is_some() {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while written.lock().unwrap().is_empty() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        drop(saves);
+        worker.join().unwrap();
+        let written = written.lock().unwrap().clone();
+        (written, pending.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn only_the_newest_waiting_copy_is_written() {
+        assert_eq!(run_writer(&[1, 2, 3], 0, true), (vec![3], false));
+    }
+
+    #[test]
+    fn a_failed_save_is_retried_until_it_succeeds() {
+        assert_eq!(run_writer(&[4], 2, false), (vec![4], false));
+    }
+
+    #[test]
+    fn shutdown_gives_up_after_a_few_attempts() {
+        assert_eq!(run_writer(&[5], usize::MAX, true), (Vec::new(), true));
+    }
+}
@@ -0,0 +1,271 @@
+// ============================================
+// src/streaming_stats.rs
+// 履歴を1回なめるだけで求める集計（記録の数によらずメモリは一定）
+// 記録を1件ずつ `push` して、最後に結果を取り出す
+// ============================================
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::save_data::TypeRecord;
+
+/// 記録の間がこれより空いたら別のセッションとして数える（秒）
+pub const SESSION_GAP_SECS: i64 = 30 * 60;
+/// 分布を近似するために残す値の数
+pub const RESERVOIR_CAPACITY: usize = 2048;
+/// 抽出の乱数の種（同じ履歴からはいつも同じ結果になるように固定する）
+const RESERVOIR_SEED: u64 = 0x0074_7970_6577_697a;
+
+/// 件数・合計・平均などの集計
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunningTotals {
+    pub questions: u32,
+    pub chars: u64,
+    pub misses: u64,
+    /// CPS の平均（1件ずつ更新する）
+    pub mean_cps: f64,
+    pub best_score: f64,
+    pub sessions: u32,
+    /// 直前の記録の時刻（セッションの区切りの判定用。記録は古い順に渡す）
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl RunningTotals {
+    pub fn push(&mut self, record: &TypeRecord) {
+        self.questions += 1;
+        self.chars += u64::from(record.total_chars);
+        self.misses += u64::from(record.misses);
+        self.mean_cps += (record.cps - self.mean_cps) / f64::from(self.questions);
+        self.best_score = self.best_score.max(record.score);
+        let new_session = self
+            .last_timestamp
+            .is_none_or(|last| (record.timestamp - last).num_seconds() > SESSION_GAP_SECS);
+        if new_session {
+            self.sessions += 1;
+        }
+        self.last_timestamp = Some(record.timestamp);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.questions == 0
+    }
+
+    /// 正確率 (%)。打鍵がなければ 100
+    pub fn accuracy(&self) -> f64 {
+        if self.chars + self.misses > 0 {
+            self.chars as f64 / (self.chars + self.misses) as f64 * 100.0
+        } else {
+            100.0
+        }
+    }
+}
+
+/// 決まった数だけ値を無作為に残して分布を近似する（値が容量以下なら全部残るので正確）
+#[derive(Debug, Clone)]
+pub struct Reservoir {
+    samples: Vec<f64>,
+    seen: u64,
+    rng: StdRng,
+}
+
+impl Default for Reservoir {
+    fn default() -> Self {
+        Self { samples: Vec::new(), seen: 0, rng: StdRng::seed_from_u64(RESERVOIR_SEED) }
+    }
+}
+
+impl Reservoir {
+    pub fn push(&mut self, value: f64) {
+        self.seen += 1;
+        if self.samples.len() < RESERVOIR_CAPACITY {
+            self.samples.push(value);
+        } else {
+            let slot = self.rng.random_range(0..self.seen);
+            if let Some(sample) = usize::try_from(slot).ok().and_then(|slot| self.samples.get_mut(slot)) {
+                *sample = value;
+            }
+        }
+    }
+
+    /// 残した値を並べ替えて返す
+    pub fn into_sorted(self) -> Vec<f64> {
+        let mut samples = self.samples;
+        samples.sort_by(f64::total_cmp);
+        samples
+    }
+}
+
+/// 直近 `days` 日間（今日を含む）の日ごとの集計。日数分の枠だけを持つ
+#[derive(Debug, Clone)]
+pub struct DayBuckets {
+    today: NaiveDate,
+    /// 何日前か → (CPS の合計, 記録数, 打鍵数, ミス数)
+    buckets: Vec<(f64, u32, u64, u64)>,
+}
+
+impl DayBuckets {
+    pub fn new(today: NaiveDate, days: u32) -> Self {
+        Self { today, buckets: vec![(0.0, 0, 0, 0); days as usize] }
+    }
+
+    /// 日付はローカル時刻で区切る。期間外の記録は数えない
+    pub fn push(&mut self, record: &TypeRecord) {
+        let date = record.timestamp.with_timezone(&Local).date_naive();
+        let Ok(days_ago) = usize::try_from((self.today - date).num_days()) else {
+            return;
+        };
+        if let Some(bucket) = self.buckets.get_mut(days_ago) {
+            bucket.0 += record.cps;
+            bucket.1 += 1;
+            bucket.2 += u64::from(record.total_chars);
+            bucket.3 += u64::from(record.misses);
+        }
+    }
+
+    /// 記録のあった日の (何日前か, 平均 CPS, 正確率 %) を古い日から順に
+    pub fn finish(self) -> impl Iterator<Item = (u32, f64, f64)> {
+        self.buckets
+            .into_iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, (_, count, _, _))| *count > 0)
+            .map(|(days_ago, (cps_sum, count, chars, misses))| {
+                let accuracy =
+                    if chars + misses > 0 { chars as f64 / (chars + misses) as f64 * 100.0 } else { 100.0 };
+                (days_ago as u32, cps_sum / f64::from(count), accuracy)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::collections::BTreeMap;
+
+    use crate::stats::percentile_of;
+
+    /// 乱数で作った、古い順に並んだ履歴（間隔は 0 秒〜3 時間）
+    fn random_history(rng: &mut StdRng, len: usize) -> Vec<TypeRecord> {
+        let mut at = Utc::now() - TimeDelta::days(40);
+        (0..len)
+            .map(|_| {
+                at += TimeDelta::seconds(rng.random_range(0..3 * 60 * 60));
+                let mut record = TypeRecord::sample(
+                    "ねこ",
+                    rng.random_range(1..60),
+                    rng.random_range(1.0..30.0),
+                    rng.random_range(0..10),
+                );
+                record.timestamp = at;
+                record
+            })
+            .collect()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    #[test]
+    fn running_totals_match_the_in_memory_sums() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let len = rng.random_range(0..500);
+            let history = random_history(&mut rng, len);
+            let mut totals = RunningTotals::default();
+            history.iter().for_each(|record| totals.push(record));
+
+            let n = history.len();
+            let chars: u64 = history.iter().map(|r| u64::from(r.total_chars)).sum();
+            let misses: u64 = history.iter().map(|r| u64::from(r.misses)).sum();
+            let sessions = 1 + history
+                .windows(2)
+                .filter(|pair| (pair[1].timestamp - pair[0].timestamp).num_seconds() > SESSION_GAP_SECS)
+                .count();
+            assert_eq!(totals.questions as usize, n, "seed {seed}");
+            assert_eq!((totals.chars, totals.misses), (chars, misses), "seed {seed}");
+            assert_eq!(totals.is_empty(), n == 0, "seed {seed}");
+            if n == 0 {
+                assert_eq!((totals.sessions, totals.accuracy()), (0, 100.0));
+                continue;
+            }
+            let mean = history.iter().map(|r| r.cps).sum::<f64>() / n as f64;
+            let best = history.iter().map(|r| r.score).fold(0.0, f64::max);
+            assert!(close(totals.mean_cps, mean), "seed {seed}: {} vs {mean}", totals.mean_cps);
+            assert_eq!(totals.best_score, best, "seed {seed}");
+            assert_eq!(totals.sessions as usize, sessions, "seed {seed}");
+            assert!(close(totals.accuracy(), chars as f64 / (chars + misses) as f64 * 100.0), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn a_reservoir_under_capacity_keeps_every_value() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let values: Vec<f64> = (0..RESERVOIR_CAPACITY).map(|_| rng.random_range(0.0..10.0)).collect();
+        let mut reservoir = Reservoir::default();
+        values.iter().for_each(|&v| reservoir.push(v));
+        let mut sorted = values;
+        sorted.sort_by(f64::total_cmp);
+        assert_eq!(reservoir.into_sorted(), sorted);
+    }
+
+    #[test]
+    fn reservoir_percentiles_stay_close_to_the_exact_ones() {
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            // 偏った分布（遅い記録が多い）も混ぜる
+            let values: Vec<f64> =
+                (0..20_000).map(|_| rng.random_range(0.0f64..1.0).powi(1 + (seed % 3) as i32) * 10.0).collect();
+            let mut reservoir = Reservoir::default();
+            values.iter().for_each(|&v| reservoir.push(v));
+            let sampled = reservoir.into_sorted();
+            assert_eq!(sampled.len(), RESERVOIR_CAPACITY);
+
+            let mut exact = values;
+            exact.sort_by(f64::total_cmp);
+            for probe in [0.5, 1.0, 2.5, 5.0, 7.5, 9.5] {
+                let (Some(approx), Some(truth)) = (percentile_of(&sampled, probe), percentile_of(&exact, probe)) else {
+                    panic!("seed {seed}: not enough samples");
+                };
+                assert!((approx - truth).abs() < 4.0, "seed {seed}, {probe}: {approx:.1} vs {truth:.1}");
+            }
+        }
+    }
+
+    #[test]
+    fn day_buckets_match_grouping_by_local_date() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let history = random_history(&mut rng, 400);
+            let today =
+                history.last().map_or(Local::now().date_naive(), |r| r.timestamp.with_timezone(&Local).date_naive());
+            let days = rng.random_range(1..30);
+
+            let mut buckets = DayBuckets::new(today, days);
+            history.iter().for_each(|record| buckets.push(record));
+            let streamed: Vec<_> = buckets.finish().collect();
+
+            // 何日前か → 記録の一覧
+            let mut by_day: BTreeMap<std::cmp::Reverse<i64>, Vec<&TypeRecord>> = BTreeMap::new();
+            for record in &history {
+                let days_ago = (today - record.timestamp.with_timezone(&Local).date_naive()).num_days();
+                if (0..i64::from(days)).contains(&days_ago) {
+                    by_day.entry(std::cmp::Reverse(days_ago)).or_default().push(record);
+                }
+            }
+            assert_eq!(streamed.len(), by_day.len(), "seed {seed}");
+            for ((days_ago, cps, accuracy), (std::cmp::Reverse(expected_day), records)) in
+                streamed.into_iter().zip(by_day)
+            {
+                let chars: u32 = records.iter().map(|r| r.total_chars).sum();
+                let misses: u32 = records.iter().map(|r| r.misses).sum();
+                let mean = records.iter().map(|r| r.cps).sum::<f64>() / records.len() as f64;
+                assert_eq!(i64::from(days_ago), expected_day, "seed {seed}");
+                assert!(close(cps, mean), "seed {seed}: {cps} vs {mean}");
+                assert!(close(accuracy, f64::from(chars) / f64::from(chars + misses) * 100.0), "seed {seed}");
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// ============================================
+// src/study_sheet.rs
+// 練習したお題ごとの成績をまとめた Markdown の学習シート（`export --study-sheet`）
+// 成績の悪いお題から並べ、次に何を練習するかの一覧として読めるようにする
+// ============================================
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::questions::QuestionId;
+use crate::roman_mapping::split_units;
+use crate::save_data::PlayerData;
+
+/// 正確率がこれ以上変わったら上向き / 下向きとみなす（パーセントポイント）
+const TREND_THRESHOLD: f64 = 1.0;
+/// 1つのお題で表示する、ミスの多いかなの数
+const MISSED_KANA_SHOWN: usize = 3;
+
+/// 正確率の傾向（前半の挑戦と後半の挑戦の比較）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Up,
+    Flat,
+    Down,
+    /// 挑戦が1回だけで比べられない
+    Single,
+}
+
+impl Trend {
+    fn arrow(&self) -> &'static str {
+        match self {
+            Trend::Up => "↑",
+            Trend::Flat => "→",
+            Trend::Down => "↓",
+            Trend::Single => "–",
+        }
+    }
+
+    /// 古い順の正確率から傾向を決める
+    fn from_accuracies(accuracies: &[f64]) -> Self {
+        if accuracies.len() < 2 {
+            return Trend::Single;
+        }
+        let (earlier, later) = accuracies.split_at(accuracies.len() / 2);
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let change = mean(later) - mean(earlier);
+        if change >= TREND_THRESHOLD {
+            Trend::Up
+        } else if change <= -TREND_THRESHOLD {
+            Trend::Down
+        } else {
+            Trend::Flat
+        }
+    }
+}
+
+/// 1つのお題の行
+#[derive(Debug, Clone)]
+pub struct StudyRow {
+    pub japanese: String,
+    pub hiragana: String,
+    /// お題の意味の訳（訳の付いたお題のみ）
+    pub gloss: Option<String>,
+    pub attempts: u32,
+    pub best_cps: f64,
+    pub median_cps: f64,
+    /// 全挑戦の正確率 (%)
+    pub accuracy: f64,
+    pub trend: Trend,
+    /// ミスの多いかなと回数（多い順）
+    pub missed_kana: Vec<(String, u32)>,
+    pub note: Option<String>,
+}
+
+/// 学習シート
+#[derive(Debug, Clone)]
+pub struct StudySheet {
+    pub rows: Vec<StudyRow>,
+}
+
+impl StudySheet {
+    /// 履歴のお題ごとの集計とメモから作る（文章モードの記録と ID のない記録は含めない）
+    /// 中央値の CPS が遅い順に並べる。最高 CPS は正確率が `accuracy_floor` (%) に届いた記録から求める
+    pub fn from_player_data(
+        player_data: &PlayerData,
+        map: &HashMap<&'static str, Vec<&'static str>>,
+        accuracy_floor: f64,
+    ) -> Self {
+        // お題ごとの記録（古い順）
+        let mut by_question: HashMap<QuestionId, Vec<_>> = HashMap::new();
+        for record in player_data.history.iter().filter(|record| !record.is_sentence()) {
+            if let Some(id) = record.question_id {
+                by_question.entry(id).or_default().push(record);
+            }
+        }
+
+        let aggregates = player_data.question_aggregates(accuracy_floor);
+        let mut rows: Vec<StudyRow> = by_question
+            .into_iter()
+            .filter_map(|(id, records)| {
+                let aggregate = aggregates.get(&id)?;
+                let latest = records.last()?;
+
+                let mut cps: Vec<f64> = records.iter().map(|record| record.cps).collect();
+                cps.sort_by(f64::total_cmp);
+                let mid = cps.len() / 2;
+                let median_cps = if cps.len().is_multiple_of(2) { (cps[mid - 1] + cps[mid]) / 2.0 } else { cps[mid] };
+
+                let accuracy_of = |chars: u32, misses: u32| match chars + misses {
+                    0 => 100.0,
+                    attempts => f64::from(chars) / f64::from(attempts) * 100.0,
+                };
+                let accuracies: Vec<f64> =
+                    records.iter().map(|record| accuracy_of(record.total_chars, record.misses)).collect();
+                let (chars, misses) =
+                    records.iter().fold((0, 0), |(c, m), record| (c + record.total_chars, m + record.misses));
+
+                // 単位の番号は最新の記録のかなで引く（同じ ID のお題は同じかな）
+                let chars_of_text: Vec<char> = latest.question_hiragana.chars().collect();
+                let units: Vec<String> = split_units(map, &latest.question_hiragana)
+                    .into_iter()
+                    .map(|(range, _)| chars_of_text[range].iter().collect())
+                    .collect();
+                let mut missed: BTreeMap<&str, u32> = BTreeMap::new();
+                for record in &records {
+                    for &(unit, count) in &record.unit_misses {
+                        if let Some(kana) = units.get(unit as usize) {
+                            *missed.entry(kana.as_str()).or_default() += count;
+                        }
+                    }
+                }
+                let mut missed_kana: Vec<(String, u32)> =
+                    missed.into_iter().map(|(kana, count)| (kana.to_string(), count)).collect();
+                missed_kana.sort_by_key(|&(_, count)| Reverse(count));
+                missed_kana.truncate(MISSED_KANA_SHOWN);
+
+                Some(StudyRow {
+                    japanese: latest.question_japanese.clone(),
+                    hiragana: latest.question_hiragana.clone(),
+                    gloss: records.iter().rev().find_map(|record| record.gloss.clone()),
+                    attempts: aggregate.attempts,
+                    best_cps: aggregate.best_cps,
+                    median_cps,
+                    accuracy: accuracy_of(chars, misses),
+                    trend: Trend::from_accuracies(&accuracies),
+                    missed_kana,
+                    note: player_data.note(id).map(str::to_string),
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            a.median_cps
+                .total_cmp(&b.median_cps)
+                .then(a.accuracy.total_cmp(&b.accuracy))
+                .then_with(|| a.hiragana.cmp(&b.hiragana))
+        });
+        Self { rows }
+    }
+
+    /// Markdown に書き出す
+    pub fn to_markdown(&self) -> String {
+        let mut lines = vec!["# Study sheet".to_string(), String::new()];
+        if self.rows.is_empty() {
+            lines.push("No questions practiced yet.".to_string());
+        } else {
+            lines.push("Questions are listed slowest first (by median CPS).".to_string());
+        }
+        for (rank, row) in self.rows.iter().enumerate() {
+            lines.push(String::new());
+            lines.push(format!("## {}. {} ({})", rank + 1, escape_markdown(&row.japanese), escape_markdown(&row.hiragana)));
+            lines.push(String::new());
+            if let Some(gloss) = &row.gloss {
+                lines.push(format!("- Meaning: {}", escape_markdown(gloss)));
+            }
+            let tries = if row.attempts == 1 { "try" } else { "tries" };
+            lines.push(format!("- Attempts: {} {}", row.attempts, tries));
+            lines.push(format!("- CPS: best {:.2} / median {:.2}", row.best_cps, row.median_cps));
+            lines.push(format!("- Accuracy: {:.1}% {}", row.accuracy, row.trend.arrow()));
+            if !row.missed_kana.is_empty() {
+                let kana: Vec<String> = row
+                    .missed_kana
+                    .iter()
+                    .map(|(kana, count)| format!("{} ×{}", escape_markdown(kana), count))
+                    .collect();
+                lines.push(format!("- Most missed: {}", kana.join(", ")));
+            }
+            if let Some(note) = &row.note {
+                lines.push(format!("- Note: {}", escape_markdown(note)));
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Markdown で意味を持つ文字の前に `\` を付ける
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_{}[]<>()#+-.!|~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roman_mapping::create_roman_mapping;
+    use crate::save_data::{ComponentStat, TypeRecord};
+
+    fn attempt(idx: usize, hiragana: &str, secs: f64, misses: u32) -> TypeRecord {
+        let mut record = TypeRecord::sample(hiragana, 6, secs, misses);
+        record.question_id = Some(QuestionId::new("test", idx));
+        record
+    }
+
+    fn sheet(history: Vec<TypeRecord>) -> StudySheet {
+        let mut data = PlayerData::default();
+        data.history = history;
+        StudySheet::from_player_data(&data, &create_roman_mapping(), 0.0)
+    }
+
+    #[test]
+    fn the_trend_compares_the_later_half_with_the_earlier_half() {
+        let cases = [
+            (vec![], Trend::Single),
+            (vec![90.0], Trend::Single),
+            (vec![90.0, 91.0], Trend::Up),
+            (vec![90.0, 90.9], Trend::Flat),
+            (vec![95.0, 94.0], Trend::Down),
+            // 奇数なら後半が1つ多い
+            (vec![90.0, 95.0, 96.0], Trend::Up),
+            (vec![99.0, 97.0, 92.0, 94.0], Trend::Down),
+        ];
+        for (accuracies, trend) in cases {
+            assert_eq!(Trend::from_accuracies(&accuracies), trend, "{accuracies:?}");
+        }
+    }
+
+    #[test]
+    fn markdown_characters_are_escaped() {
+        assert_eq!(escape_markdown("C++ #1 (入門) *速く*"), "C\\+\\+ \\#1 \\(入門\\) \\*速く\\*");
+        assert_eq!(escape_markdown("a|b_c [x]!"), "a\\|b\\_c \\[x\\]\\!");
+        assert_eq!(escape_markdown("ねこ。"), "ねこ。");
+    }
+
+    #[test]
+    fn rows_are_sorted_slowest_first_and_skip_untracked_records() {
+        let mut passage = attempt(2, "ねこといぬ", 9.0, 0);
+        passage.components = vec![ComponentStat::default()];
+        let mut anonymous = attempt(3, "とり", 9.0, 0);
+        anonymous.question_id = None;
+        let history = vec![
+            attempt(0, "ねこ", 1.0, 0),
+            attempt(1, "いぬ", 3.0, 0),
+            attempt(0, "ねこ", 2.0, 0),
+            attempt(0, "ねこ", 4.0, 1),
+            attempt(0, "ねこ", 3.0, 0),
+            passage,
+            anonymous,
+        ];
+        let sheet = sheet(history);
+        let order: Vec<&str> = sheet.rows.iter().map(|row| row.hiragana.as_str()).collect();
+        assert_eq!(order, ["いぬ", "ねこ"]);
+
+        // 1回だけのお題は、その回が最高でも中央値でもある
+        let single = &sheet.rows[0];
+        assert_eq!((single.attempts, single.trend, single.best_cps, single.median_cps), (1, Trend::Single, 2.0, 2.0));
+        // 偶数回なら真ん中2つの平均（6 / 2, 6 / 3）
+        let neko = &sheet.rows[1];
+        assert_eq!((neko.attempts, neko.best_cps, neko.median_cps), (4, 6.0, 2.5));
+        assert!((neko.accuracy - 24.0 / 25.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_most_missed_kana_are_summed_per_unit() {
+        let with_misses = |units: &[(u32, u32)]| {
+            let mut record = attempt(0, "さくらんぼ", 3.0, units.iter().map(|&(_, n)| n).sum());
+            record.unit_misses = units.to_vec();
+            record
+        };
+        // 単位は さ・く・ら・んぼ。範囲外の番号は数えない
+        let sheet = sheet(vec![with_misses(&[(0, 1), (2, 2)]), with_misses(&[(0, 2), (3, 1), (4, 1), (9, 5)])]);
+        let expected = [("さ".to_string(), 3), ("ら".to_string(), 2), ("んぼ".to_string(), 1)];
+        assert_eq!(sheet.rows[0].missed_kana, expected);
+    }
+
+    #[test]
+    fn an_empty_history_says_so() {
+        assert_eq!(sheet(Vec::new()).to_markdown(), "# Study sheet\n\nNo questions practiced yet.\n");
+    }
+}
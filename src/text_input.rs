@@ -0,0 +1,119 @@
+// ============================================
+// src/text_input.rs
+// TUI 用の1行テキスト入力（メモの編集やお題の作成で使う）
+// ============================================
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+
+/// キー入力を処理した結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputOutcome {
+    /// 入力を続ける
+    Editing,
+    /// Enter で確定した
+    Submitted,
+    /// Esc で取り消した
+    Cancelled,
+}
+
+/// カーソル付きの1行入力欄（最大文字数を超える入力は受け付けない）
+pub struct TextInput {
+    chars: Vec<char>,
+    /// カーソルの位置（文字単位）
+    cursor: usize,
+    max_chars: usize,
+}
+
+impl TextInput {
+    pub fn new(initial: &str, max_chars: usize) -> Self {
+        let chars: Vec<char> = initial.chars().take(max_chars).collect();
+        let cursor = chars.len();
+        Self { chars, cursor, max_chars }
+    }
+
+    /// 入力中の文字列
+    pub fn value(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// 入力欄を空にする
+    pub fn clear(&mut self) {
+        self.chars.clear();
+        self.cursor = 0;
+    }
+
+    /// 貼り付けられた文字列をカーソル位置に入れる（改行などの制御文字は除き、最大文字数で切る）
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if self.chars.len() >= self.max_chars {
+                break;
+            }
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    /// キー入力を処理する
+    pub fn handle_key(&mut self, key: &KeyEvent) -> InputOutcome {
+        match key.code {
+            KeyCode::Enter => return InputOutcome::Submitted,
+            KeyCode::Esc => return InputOutcome::Cancelled,
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.chars.len(),
+            KeyCode::Char(c)
+                if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                    && !c.is_control()
+                    && self.chars.len() < self.max_chars =>
+            {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+        InputOutcome::Editing
+    }
+
+    /// カーソル位置を反転表示した1行
+    pub fn line(&self) -> Line<'static> {
+        let before: String = self.chars[..self.cursor].iter().collect();
+        let at: String = self.chars.get(self.cursor).map_or(" ".to_string(), |c| c.to_string());
+        let after: String = self.chars.get(self.cursor + 1..).map_or(String::new(), |rest| rest.iter().collect());
+        Line::from(vec![
+            Span::raw(before),
+            Span::styled(at, Style::default().fg(Color::Black).bg(Color::White)),
+            Span::raw(after),
+        ])
+    }
+
+    /// 文字ごとにスタイルを付けた1行（`cursor` が false ならカーソルを表示しない）
+    pub fn styled_line(&self, cursor: bool, style_of: impl Fn(char) -> Style) -> Line<'static> {
+        let mut spans: Vec<Span<'static>> = self
+            .chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| {
+                let style = if cursor && i == self.cursor {
+                    Style::default().fg(Color::Black).bg(Color::White)
+                } else {
+                    style_of(c)
+                };
+                Span::styled(c.to_string(), style)
+            })
+            .collect();
+        if cursor && self.cursor == self.chars.len() {
+            spans.push(Span::styled(" ", Style::default().fg(Color::Black).bg(Color::White)));
+        }
+        Line::from(spans)
+    }
+}
@@ -0,0 +1,63 @@
+// ============================================
+// src/time_attack.rs
+// 同じお題を続けて N 回打つタイムアタックの状態
+// ============================================
+
+/// 1回のタイムアタックで打つ回数
+pub const DEFAULT_ATTEMPTS: u32 = 5;
+
+/// 1回分の成績
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub cps: f64,
+    pub score: f64,
+    pub misses: u32,
+    pub duration_sec: f64,
+    /// 通常なら得られた経験値（実際に与えるのは一番良い回の分だけ）
+    pub xp: u32,
+    /// 履歴の中での位置（最後に一番良い回の経験値を書き戻す）
+    pub history_idx: usize,
+}
+
+/// タイムアタックの進行状況
+#[derive(Debug, Clone)]
+pub struct TimeAttack {
+    pub total: u32,
+    pub attempts: Vec<Attempt>,
+    /// 始める前のこのお題の最高スコア
+    pub previous_best: Option<f64>,
+}
+
+impl TimeAttack {
+    pub fn new(total: u32, previous_best: Option<f64>) -> Self {
+        Self { total, attempts: Vec::new(), previous_best }
+    }
+
+    /// 次に打つのが何回目か (1 から)
+    pub fn current_attempt(&self) -> u32 {
+        self.attempts.len() as u32 + 1
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.attempts.len() as u32 >= self.total
+    }
+
+    /// スコアが一番良い回の番号 (0 から)
+    pub fn best_index(&self) -> Option<usize> {
+        self.attempts
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+            .map(|(idx, _)| idx)
+    }
+
+    pub fn best(&self) -> Option<&Attempt> {
+        self.best_index().map(|idx| &self.attempts[idx])
+    }
+
+    /// 一番良い回が、始める前のこのお題の最高スコアを超えたか
+    pub fn is_new_record(&self) -> bool {
+        self.best()
+            .is_some_and(|best| self.previous_best.is_none_or(|previous| best.score > previous))
+    }
+}
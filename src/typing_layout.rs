@@ -0,0 +1,166 @@
+// ============================================
+// src/typing_layout.rs
+// タイピング画面の行の割り振り（打っている間と結果を見せている間で変える）
+// ============================================
+
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+
+/// 打っている間、ローマ字の行を折り返して使ってよい行数
+const ROMAJI_MAX_ROWS: u16 = 2;
+
+/// タイピング画面の今の段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypingPhase {
+    /// お題を打っている（まだ打ち始めていないときも、結果がなければこちら）
+    Typing,
+    /// 直前のお題の結果を見せていて、次のお題はまだ打ち始めていない
+    Result,
+}
+
+/// 行の割り振りに使う、表示する内容の大きさ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypingContent {
+    /// 日本語の表示幅
+    pub japanese_width: usize,
+    /// ひらがなの表示幅
+    pub hiragana_width: usize,
+    /// ローマ字の表示幅
+    pub romaji_width: usize,
+    /// ローマ字の下に足す案内の行数（入力できない文字の案内など）
+    pub romaji_extra_rows: u16,
+    /// 日本語の下に出す訳の行数（訳がなければ 0 で、行ごと詰める）
+    pub gloss_rows: u16,
+    /// 結果の欄の中身のある行数
+    pub result_rows: u16,
+}
+
+/// タイピング画面の各欄の位置
+#[derive(Debug, Clone, Copy)]
+pub struct TypingLayout {
+    pub status: Rect,
+    pub results: Rect,
+    pub japanese: Rect,
+    /// 訳（訳がなければ高さ 0）
+    pub gloss: Rect,
+    pub hiragana: Rect,
+    /// ローマ字（画面の残りもここに含める）
+    pub romaji: Rect,
+}
+
+/// 段階と内容の大きさから各欄の行数を決める
+/// 打っている間はお題の行を優先して結果の欄を詰め、結果を見せている間は結果の欄を優先してお題を1行ずつにする
+pub fn typing_layout(area: Rect, phase: TypingPhase, content: &TypingContent) -> TypingLayout {
+    let available = area.height.saturating_sub(1);
+    let (mut japanese, mut hiragana, romaji) = match phase {
+        TypingPhase::Typing => (
+            wrapped_height(content.japanese_width, area.width),
+            wrapped_height(content.hiragana_width, area.width),
+            wrapped_height(content.romaji_width, area.width).min(ROMAJI_MAX_ROWS) + content.romaji_extra_rows,
+        ),
+        TypingPhase::Result => (1, 1, 1 + content.romaji_extra_rows),
+    };
+
+    let text = |japanese: u16, hiragana: u16| japanese + content.gloss_rows + 1 + hiragana + romaji;
+    let results = content.result_rows.min(available.saturating_sub(text(japanese, hiragana)));
+    // 画面が低くて収まらなければ、日本語とひらがなの折り返しを削る
+    while text(japanese, hiragana) + results > available && (japanese > 1 || hiragana > 1) {
+        if japanese >= hiragana {
+            japanese -= 1;
+        } else {
+            hiragana -= 1;
+        }
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(results),
+            Constraint::Length(japanese),
+            Constraint::Length(content.gloss_rows),
+            Constraint::Length(1),
+            Constraint::Length(hiragana),
+            Constraint::Min(romaji),
+        ])
+        .split(area);
+
+    TypingLayout {
+        status: chunks[0],
+        results: chunks[1],
+        japanese: chunks[2],
+        gloss: chunks[3],
+        hiragana: chunks[5],
+        romaji: chunks[6],
+    }
+}
+
+/// 幅 `text_width` のテキストを `area_width` で折り返したときの行数
+pub fn wrapped_height(text_width: usize, area_width: u16) -> u16 {
+    let area_width = usize::from(area_width.max(1));
+    text_width.div_ceil(area_width).max(1) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (結果, 日本語, 訳, ひらがな, ローマ字) の高さ
+    fn heights(layout: &TypingLayout) -> [u16; 5] {
+        [layout.results, layout.japanese, layout.gloss, layout.hiragana, layout.romaji].map(|rect| rect.height)
+    }
+
+    fn long_question(result_rows: u16) -> TypingContent {
+        TypingContent {
+            japanese_width: 100,
+            hiragana_width: 130,
+            romaji_width: 250,
+            result_rows,
+            ..TypingContent::default()
+        }
+    }
+
+    #[test]
+    fn typing_collapses_empty_results_and_wraps_the_question() {
+        let area = Rect::new(0, 0, 80, 24);
+        let layout = typing_layout(area, TypingPhase::Typing, &long_question(0));
+        // ローマ字は2行まで折り返し、残りの行もローマ字の欄に入る
+        assert_eq!(heights(&layout), [0, 2, 0, 2, 18]);
+        assert_eq!(layout.status.height, 1);
+
+        let short = TypingContent { japanese_width: 4, hiragana_width: 4, romaji_width: 4, ..TypingContent::default() };
+        assert_eq!(heights(&typing_layout(area, TypingPhase::Typing, &short)), [0, 1, 0, 1, 20]);
+    }
+
+    #[test]
+    fn results_take_the_rows_the_question_gives_up() {
+        let area = Rect::new(0, 0, 80, 24);
+        let content = TypingContent { gloss_rows: 1, romaji_extra_rows: 1, ..long_question(8) };
+        assert_eq!(heights(&typing_layout(area, TypingPhase::Typing, &content)), [8, 2, 1, 2, 9]);
+        assert_eq!(heights(&typing_layout(area, TypingPhase::Result, &content)), [8, 1, 1, 1, 11]);
+    }
+
+    #[test]
+    fn on_a_short_screen_the_phase_decides_who_gets_the_rows() {
+        let area = Rect::new(0, 0, 40, 12);
+        // 日本語 3 行・ひらがな 4 行のお題。打っている間はお題を優先し、結果は残りの 1 行だけ
+        assert_eq!(heights(&typing_layout(area, TypingPhase::Typing, &long_question(4))), [1, 3, 0, 4, 2]);
+        assert_eq!(heights(&typing_layout(area, TypingPhase::Result, &long_question(4))), [4, 1, 0, 1, 4]);
+
+        // お題だけでも入りきらなければ、日本語とひらがなの折り返しを削る
+        let layout = typing_layout(Rect::new(0, 0, 40, 8), TypingPhase::Typing, &long_question(4));
+        assert_eq!(heights(&layout), [0, 2, 0, 2, 2]);
+        assert!(layout.romaji.bottom() <= 8);
+
+        // 結果が入りきらなければ、入るだけにする
+        let tiny = Rect::new(0, 0, 40, 6);
+        assert_eq!(heights(&typing_layout(tiny, TypingPhase::Result, &long_question(8))), [1, 1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn wrapping_counts_whole_rows() {
+        assert_eq!(wrapped_height(0, 80), 1);
+        assert_eq!(wrapped_height(80, 80), 1);
+        assert_eq!(wrapped_height(81, 80), 2);
+        assert_eq!(wrapped_height(5, 0), 5);
+    }
+}
@@ -1,14 +1,371 @@
+// ============================================
+// src/update.rs
+// 自動アップデートと、1つ前のバージョンへの巻き戻し
+// 確認 → 退避 → ダウンロードと差し替え → 動作確認 の順に進める
+// 起動時の確認は別スレッドで行い、結果は受け口に置くだけにする（お知らせを出すのも差し替えるのもメインのスレッド）
+// 差し替えはいつも手動（Settings › Update Now）で、自動ではダウンロードしない
+// ============================================
+
 use self_update::cargo_crate_version;
+use self_update::update::ReleaseUpdate;
+
+use crate::debug_log::dlog;
+
+use std::env::consts::EXE_SUFFIX;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// アップデートの結果
+pub enum UpdateOutcome {
+    /// すでに最新
+    UpToDate,
+    /// 更新した（前のバージョンは `old_binary_path()` に残してある）
+    Updated { from: String, to: String },
+}
+
+/// アップデート・巻き戻しのどの段階で失敗したか
+#[derive(Debug)]
+pub enum UpdateError {
+    /// 最新のリリースを確認できなかった
+    Check(self_update::errors::Error),
+    /// 今の実行ファイルを退避できなかった
+    Stage(io::Error),
+    /// ダウンロードまたは差し替えに失敗した
+    Swap(String),
+    /// 差し替える実行ファイルが起動しなかった（今のものを残した）
+    Verify(String),
+    /// 巻き戻せる前のバージョンがない
+    NoPreviousBinary,
+}
 
-pub fn update() -> Result<(), Box<dyn std::error::Error>> {
-    let status = self_update::backends::github::Update::configure()
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Check(e) => write!(f, "could not check for updates: {}", e),
+            Self::Stage(e) => write!(f, "could not keep a copy of the current binary: {}", e),
+            Self::Swap(reason) => write!(f, "could not replace the binary: {}", reason),
+            Self::Verify(reason) => write!(f, "the candidate binary did not start ({}); kept the current one", reason),
+            Self::NoPreviousBinary => write!(f, "no previous version is available to roll back to"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// 1つ前のバージョンの実行ファイルを置く場所（今の実行ファイルと同じフォルダ）
+pub fn old_binary_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.with_file_name(format!("typewiz.old{}", EXE_SUFFIX)))
+}
+
+fn updater() -> Result<Box<dyn ReleaseUpdate>, UpdateError> {
+    self_update::backends::github::Update::configure()
         .repo_owner("Fukumoto0141")
         .repo_name("type-wiz-dev")
         .bin_name("typewiz")
         .show_download_progress(true)
         .current_version(cargo_crate_version!())
-        .build()?
-        .update()?;
-    println!("Update status: `{}`!", status.version());
+        .build()
+        .map_err(UpdateError::Check)
+}
+
+/// 最新版のバージョン（今のバージョンより新しくなければ None）。ダウンロードはしない
+pub fn latest_version() -> Result<Option<String>, UpdateError> {
+    newer_release(updater()?.as_ref())
+}
+
+fn newer_release(updater: &dyn ReleaseUpdate) -> Result<Option<String>, UpdateError> {
+    let current = cargo_crate_version!();
+    let latest = updater.get_latest_release().map_err(UpdateError::Check)?;
+    dlog!("update", "current={} latest={}", current, latest.version);
+    let newer = self_update::version::bump_is_greater(current, &latest.version).map_err(UpdateError::Check)?;
+    Ok(newer.then_some(latest.version))
+}
+
+/// 最新版があれば、今の実行ファイルを退避してから差し替える
+pub fn update() -> Result<UpdateOutcome, UpdateError> {
+    let current = cargo_crate_version!();
+    let updater = updater()?;
+
+    // 確認
+    if newer_release(updater.as_ref())?.is_none() {
+        return Ok(UpdateOutcome::UpToDate);
+    }
+
+    // 退避（Windows でも実行中のファイルの読み取りはできるので、コピーで残す）
+    let exe = std::env::current_exe().map_err(UpdateError::Stage)?;
+    let old = old_binary_path().ok_or_else(|| UpdateError::Stage(io::Error::other("no executable path")))?;
+    fs::copy(&exe, &old).map_err(UpdateError::Stage)?;
+    dlog!("update", "staged {} -> {}", exe.display(), old.display());
+
+    // ダウンロードと差し替え（実行中のファイルの置き換えは self_update に任せる）
+    let status = updater.update().map_err(|e| UpdateError::Swap(e.to_string()))?;
+
+    // 動作確認。起動しなければ退避したものに戻す
+    if let Err(reason) = verify(&exe) {
+        dlog!("update", "verify failed ({}); restoring {}", reason, old.display());
+        swap_in(&old)?;
+        return Err(UpdateError::Verify(reason));
+    }
+    dlog!("update", "updated to {}", status.version());
+    Ok(UpdateOutcome::Updated { from: current.to_string(), to: status.version().to_string() })
+}
+
+/// 退避しておいた前のバージョンに戻す（起動できることを確かめてから差し替える）
+pub fn rollback() -> Result<(), UpdateError> {
+    let old = old_binary_path().filter(|path| path.exists()).ok_or(UpdateError::NoPreviousBinary)?;
+    verify(&old).map_err(UpdateError::Verify)?;
+    dlog!("update", "rolling back to {}", old.display());
+    swap_in(&old)?;
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
+
+/// 今の実行ファイルを `binary` の中身で置き換える（Windows で実行中でも置き換えられる方法を使う）
+fn swap_in(binary: &Path) -> Result<(), UpdateError> {
+    self_update::self_replace::self_replace(binary).map_err(|e| UpdateError::Swap(e.to_string()))
+}
+
+/// `--version` を実行して、起動できるか確かめる
+fn verify(binary: &Path) -> Result<(), String> {
+    let output = Command::new(binary).arg("--version").output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    if !String::from_utf8_lossy(&output.stdout).contains("typewiz") {
+        return Err("unexpected --version output".to_string());
+    }
     Ok(())
 }
+
+// --------------------------------------------------
+// MARK:裏での確認
+// --------------------------------------------------
+
+/// 確認のスレッドから届く結果（新しいバージョン、またはエラーの文）
+type CheckResult = Result<Option<String>, String>;
+
+/// 手動の更新（Update Now）でどうなったか
+pub enum ManualUpdate {
+    /// このプロセスの中でもう更新し終えている（ダウンロードはしなかった）
+    AlreadyInstalled(String),
+    /// 更新を試した結果
+    Ran(Result<UpdateOutcome, UpdateError>),
+}
+
+/// 起動時に裏で行う更新の確認
+/// 確認のスレッドは結果を受け口に置くだけで、画面には書かず、ダウンロードもしない
+/// メインのスレッドが安全なとき（メニューを描く前・セッションの終わり）に `poll` で取り出し、お知らせを出す
+#[derive(Default)]
+pub struct UpdateCheck {
+    /// 確認の結果の受け口（受け取ったら、またはもう要らなくなったら None）
+    mailbox: Option<Receiver<CheckResult>>,
+    /// 見つけた新しいバージョン
+    available: Option<String>,
+    /// このプロセスの中で手動で更新したバージョン（これより後に届いた確認の結果は捨てる）
+    installed: Option<String>,
+}
+
+impl UpdateCheck {
+    /// `check` を別スレッドで実行する
+    pub fn spawn(check: impl FnOnce() -> Result<Option<String>, UpdateError> + Send + 'static) -> Self {
+        let (sender, mailbox) = mpsc::sync_channel(1);
+        thread::spawn(move || {
+            // 受け取る側がもういなければ（手動で更新し終えたなど）結果は捨てる
+            let _ = sender.send(check().map_err(|e| e.to_string()));
+        });
+        Self { mailbox: Some(mailbox), ..Self::default() }
+    }
+
+    /// 届いた結果を取り出す。新しいバージョンを見つけたときだけ、お知らせの文を返す（1度だけ）
+    pub fn poll(&mut self) -> Option<String> {
+        let result = match self.mailbox.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("the update check stopped".to_string()),
+        };
+        self.mailbox = None;
+        match result {
+            Ok(Some(version)) => {
+                let notice = format!("Update available: v{} (Settings › Update Now)", version);
+                self.available = Some(version);
+                Some(notice)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                dlog!("update", "background check failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 見つけた新しいバージョン
+    pub fn available(&self) -> Option<&str> {
+        self.available.as_deref()
+    }
+
+    /// このプロセスの中で手動で更新したバージョン
+    pub fn installed(&self) -> Option<&str> {
+        self.installed.as_deref()
+    }
+
+    /// 手動で更新する。このプロセスの中で更新し終えていれば `install` は呼ばない
+    pub fn update_now(&mut self, install: impl FnOnce() -> Result<UpdateOutcome, UpdateError>) -> ManualUpdate {
+        if let Some(version) = &self.installed {
+            return ManualUpdate::AlreadyInstalled(version.clone());
+        }
+        let result = install();
+        if let Ok(UpdateOutcome::Updated { to, .. }) = &result {
+            self.mark_installed(to.clone());
+        }
+        ManualUpdate::Ran(result)
+    }
+
+    /// 手動で更新した。まだ届いていない確認の結果は捨て、もう一度ダウンロードしないようにする
+    fn mark_installed(&mut self, version: String) {
+        self.mailbox = None;
+        self.available = None;
+        self.installed = Some(version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::mpsc::Sender;
+    use std::time::{Duration, Instant};
+
+    /// 結果を返すタイミングをテストから決められる確認（`release` に送った値を結果として返す）
+    fn gated_check() -> (UpdateCheck, Sender<Result<Option<String>, UpdateError>>) {
+        let (release, gate) = mpsc::channel();
+        let check = UpdateCheck::spawn(move || gate.recv().unwrap_or(Err(UpdateError::NoPreviousBinary)));
+        (check, release)
+    }
+
+    /// 確認のスレッドが結果を置くまで `poll` を繰り返し、出たお知らせを返す
+    fn poll_until_delivered(check: &mut UpdateCheck) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut notices = Vec::new();
+        while check.mailbox.is_some() && Instant::now() < deadline {
+            notices.extend(check.poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(check.mailbox.is_none(), "the check never delivered a result");
+        notices
+    }
+
+    /// ダウンロードの代わり（呼ばれた回数を数え、`to` に更新したことにする）
+    fn mock_install<'a>(downloads: &'a Cell<u32>, to: &'a str) -> impl FnOnce() -> Result<UpdateOutcome, UpdateError> + 'a {
+        move || {
+            downloads.set(downloads.get() + 1);
+            Ok(UpdateOutcome::Updated { from: "0.1.3".to_string(), to: to.to_string() })
+        }
+    }
+
+    fn was_updated(outcome: &ManualUpdate) -> bool {
+        matches!(outcome, ManualUpdate::Ran(Ok(UpdateOutcome::Updated { .. })))
+    }
+
+    #[test]
+    fn a_result_arriving_mid_session_waits_for_the_next_safe_point() {
+        let (mut check, release) = gated_check();
+        // メニュー: まだ届いていない
+        assert_eq!(check.poll(), None);
+        // セッション中に届く（この間メインのスレッドは受け口を見ない）
+        release.send(Ok(Some("9.9.9".to_string()))).unwrap();
+        // セッションの終わり: お知らせが1度だけ出る
+        let notices = poll_until_delivered(&mut check);
+        assert_eq!(notices, ["Update available: v9.9.9 (Settings › Update Now)"]);
+        assert_eq!(check.available(), Some("9.9.9"));
+        // 次のメニュー: もう出ない
+        assert_eq!(check.poll(), None);
+    }
+
+    #[test]
+    fn a_check_resolving_after_a_manual_update_does_not_download_again() {
+        let downloads = Cell::new(0);
+        let (mut check, release) = gated_check();
+        assert!(was_updated(&check.update_now(mock_install(&downloads, "9.9.9"))));
+        // 手動で更新し終えてから確認が届く（受け口はもうないので捨てられる）
+        let _ = release.send(Ok(Some("9.9.9".to_string())));
+        assert_eq!(check.poll(), None);
+        assert_eq!(check.available(), None);
+        // もう一度 Update Now を選んでもダウンロードしない
+        let again = check.update_now(mock_install(&downloads, "9.9.10"));
+        assert!(matches!(again, ManualUpdate::AlreadyInstalled(ref v) if v == "9.9.9"));
+        assert_eq!(downloads.get(), 1);
+        assert_eq!(check.installed(), Some("9.9.9"));
+    }
+
+    #[test]
+    fn a_check_resolving_during_the_download_is_discarded() {
+        let downloads = Cell::new(0);
+        let (mut check, release) = gated_check();
+        let outcome = check.update_now(|| {
+            // ダウンロード中に確認のスレッドが結果を置く
+            release.send(Ok(Some("9.9.9".to_string()))).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            mock_install(&downloads, "9.9.9")()
+        });
+        assert!(was_updated(&outcome));
+        assert_eq!(check.poll(), None);
+        assert!(matches!(check.update_now(mock_install(&downloads, "9.9.9")), ManualUpdate::AlreadyInstalled(_)));
+        assert_eq!(downloads.get(), 1);
+    }
+
+    #[test]
+    fn updating_after_the_notice_clears_it_and_downloads_once() {
+        let downloads = Cell::new(0);
+        let mut check = UpdateCheck::spawn(|| Ok(Some("9.9.9".to_string())));
+        assert_eq!(poll_until_delivered(&mut check).len(), 1);
+        assert!(was_updated(&check.update_now(mock_install(&downloads, "9.9.9"))));
+        assert_eq!(check.available(), None);
+        assert!(matches!(check.update_now(mock_install(&downloads, "9.9.9")), ManualUpdate::AlreadyInstalled(_)));
+        assert_eq!(downloads.get(), 1);
+    }
+
+    #[test]
+    fn a_failed_or_up_to_date_manual_update_can_be_tried_again() {
+        let attempts = Cell::new(0);
+        let (mut check, release) = gated_check();
+        let failed = check.update_now(|| {
+            attempts.set(attempts.get() + 1);
+            Err(UpdateError::Swap("offline".to_string()))
+        });
+        assert!(matches!(failed, ManualUpdate::Ran(Err(_))));
+        let up_to_date = check.update_now(|| {
+            attempts.set(attempts.get() + 1);
+            Ok(UpdateOutcome::UpToDate)
+        });
+        assert!(matches!(up_to_date, ManualUpdate::Ran(Ok(UpdateOutcome::UpToDate))));
+        // 更新していないので、あとから届いた確認のお知らせは出る
+        release.send(Ok(Some("9.9.9".to_string()))).unwrap();
+        assert_eq!(poll_until_delivered(&mut check).len(), 1);
+        assert!(was_updated(&check.update_now(mock_install(&attempts, "9.9.9"))));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn up_to_date_and_failed_checks_show_no_notice() {
+        let mut up_to_date = UpdateCheck::spawn(|| Ok(None));
+        assert!(poll_until_delivered(&mut up_to_date).is_empty());
+        assert_eq!(up_to_date.available(), None);
+
+        let mut failed = UpdateCheck::spawn(|| Err(UpdateError::Swap("offline".to_string())));
+        assert!(poll_until_delivered(&mut failed).is_empty());
+        assert_eq!(failed.available(), None);
+    }
+
+    #[test]
+    fn a_check_that_dies_is_treated_as_no_update() {
+        let mut check = UpdateCheck::spawn(|| panic!("checker crashed"));
+        assert!(poll_until_delivered(&mut check).is_empty());
+        assert_eq!(check.available(), None);
+    }
+}
@@ -0,0 +1,352 @@
+// ============================================
+// src/user_questions.rs
+// ユーザーが追加したお題の保存と、単語リストからの取り込み
+// ============================================
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::question_split::{LengthLimit, fit_question};
+use crate::questions::{QUESTIONS_LIST, Question, QuestionId, USER_PACK_ID, set_user_pack};
+use crate::roman_mapping::unsupported_chars;
+use crate::save_data::{get_data_dir, write_atomic};
+
+/// ユーザーが追加したお題
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserQuestion {
+    /// 表示用 (漢字混じり)
+    pub japanese: String,
+    /// タイピング用 (ひらがな)
+    pub hiragana: String,
+    /// 分類用のタグ（`QUESTION_TAGS` から選ぶ）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 意味の訳（言語コード → 訳。例: {"en": "library", "zh": "图书馆"}）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gloss: Gloss,
+}
+
+/// お題に付けられるタグ
+pub const QUESTION_TAGS: [&str; 6] = ["noun", "verb", "place", "food", "animal", "phrase"];
+
+/// お題の意味の訳（言語コード → 訳）
+pub type Gloss = BTreeMap<String, String>;
+
+/// 訳の長さの上限（お題の下の1行に収まるように）
+const MAX_GLOSS_CHARS: usize = 40;
+
+/// 訳の言語コードと訳を確かめる
+/// 言語コードは "en" や "zh-TW" の形（小文字2〜3文字、続けて "-" と英数字2〜8文字）、訳は1行で空でないこと
+pub fn check_gloss(gloss: &Gloss) -> std::result::Result<(), String> {
+    for (code, text) in gloss {
+        let (language, region) = code.split_once('-').unwrap_or((code, ""));
+        let language_ok = (2..=3).contains(&language.len()) && language.bytes().all(|b| b.is_ascii_lowercase());
+        let region_ok = !code.contains('-')
+            || ((2..=8).contains(&region.len()) && region.bytes().all(|b| b.is_ascii_alphanumeric()));
+        if !language_ok || !region_ok {
+            return Err(format!("invalid gloss language code: {:?}", code));
+        }
+        if text.trim().is_empty() {
+            return Err(format!("empty gloss for {}", code));
+        }
+        if text.chars().any(char::is_control) {
+            return Err(format!("gloss for {} must be a single line", code));
+        }
+        if text.chars().count() > MAX_GLOSS_CHARS {
+            return Err(format!("gloss for {} is longer than {} characters", code, MAX_GLOSS_CHARS));
+        }
+    }
+    Ok(())
+}
+
+/// 表示する訳を選ぶ。`language` の訳があればそれ、`any` なら英語の訳、それもなければ最初の言語の訳
+pub fn pick_gloss<'a>(gloss: &'a Gloss, language: &str, any: bool) -> Option<&'a str> {
+    if let Some(text) = gloss.get(language) {
+        return Some(text);
+    }
+    if !any {
+        return None;
+    }
+    gloss.get("en").or_else(|| gloss.values().next()).map(String::as_str)
+}
+
+/// 訳の付いたお題を日本語と読みで引くための表
+#[derive(Debug, Clone, Default)]
+pub struct GlossBook(HashMap<(String, String), Gloss>);
+
+impl GlossBook {
+    pub fn from_questions(questions: &[UserQuestion]) -> Self {
+        Self(
+            questions
+                .iter()
+                .filter(|q| !q.gloss.is_empty())
+                .map(|q| ((q.japanese.clone(), q.hiragana.clone()), q.gloss.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, japanese: &str, hiragana: &str) -> Option<&Gloss> {
+        // 訳の付いたお題がなければ、引くための文字列も作らない
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0.get(&(japanese.to_string(), hiragana.to_string()))
+    }
+}
+
+/// お題として入力できるかを確かめる（空でないこと・ローマ字辞書で入力できること）
+pub fn check_question(
+    japanese: &str,
+    hiragana: &str,
+    roman_map: &HashMap<&'static str, Vec<&'static str>>,
+) -> std::result::Result<(), String> {
+    if japanese.is_empty() || hiragana.is_empty() {
+        return Err("empty japanese or kana".to_string());
+    }
+    let unsupported = unsupported_chars(roman_map, hiragana);
+    if !unsupported.is_empty() {
+        let chars: String = unsupported.into_iter().collect();
+        return Err(format!("cannot be typed: {}", chars));
+    }
+    Ok(())
+}
+
+/// 組み込みのお題かユーザーのお題に、同じ日本語と読みのものがあるか
+pub fn is_duplicate(existing: &[UserQuestion], japanese: &str, hiragana: &str) -> bool {
+    QUESTIONS_LIST
+        .iter()
+        .any(|q| q.japanese == japanese && q.hiragana == hiragana)
+        || existing.iter().any(|q| q.japanese == japanese && q.hiragana == hiragana)
+}
+
+/// ユーザーが追加したお題の一覧（データフォルダの user_questions.json）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserQuestions {
+    pub questions: Vec<UserQuestion>,
+}
+
+impl UserQuestions {
+    // MARK:保存先のパスを取得する関数
+    pub fn get_file_path() -> PathBuf {
+        get_data_dir().join("user_questions.json")
+    }
+
+    /// MARK:ファイルから読み込む（ファイルがなければ空）
+    /// 壊れたファイルを空の一覧で上書きしないよう、読み込みエラーはそのまま返す（訳の書き方の誤りも同じ）
+    pub fn load() -> Result<Self> {
+        let loaded: Self = match fs::read(Self::get_file_path()) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        for (idx, question) in loaded.questions.iter().enumerate() {
+            check_gloss(&question.gloss).map_err(|reason| {
+                Error::new(ErrorKind::InvalidData, format!("question {} ({}): {}", idx + 1, question.japanese, reason))
+            })?;
+        }
+        Ok(loaded)
+    }
+
+    /// MARK:ファイルに保存する
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&Self::get_file_path(), json.as_bytes())
+    }
+
+    /// 出題するお題の ID ごとのタグ（タグのないお題は含めない）
+    pub fn tags_by_id(&self) -> HashMap<QuestionId, Vec<String>> {
+        self.questions
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| !q.tags.is_empty())
+            .map(|(idx, q)| (QuestionId::new(USER_PACK_ID, idx), q.tags.clone()))
+            .collect()
+    }
+
+    /// 出題用のお題に変換する（`leak_questions` を参照）
+    pub fn leak_served(&self) -> &'static [Question] {
+        leak_questions(&self.questions)
+    }
+}
+
+/// お題を出題用に変換する
+/// 出題キューは組み込みのお題と同じく `&'static` のお題を持つので、文字列はプロセスの終わりまで残す
+/// （変換するのは起動時と、お題を追加したときだけ）
+pub fn leak_questions(questions: &[UserQuestion]) -> &'static [Question] {
+    let questions: Vec<Question> = questions
+        .iter()
+        .map(|q| Question {
+            japanese: Box::leak(q.japanese.clone().into_boxed_str()),
+            hiragana: Box::leak(q.hiragana.clone().into_boxed_str()),
+        })
+        .collect();
+    Box::leak(questions.into_boxed_slice())
+}
+
+/// user_questions.json を読み込み、ユーザーのお題を通常の出題に加える
+/// 読めないときは組み込みのお題だけを出題し、知らせる文を返す（ファイルは書き換えない）
+pub fn serve_user_questions() -> (UserQuestions, Option<String>) {
+    match UserQuestions::load() {
+        Ok(user_questions) => {
+            set_user_pack(user_questions.leak_served());
+            (user_questions, None)
+        }
+        Err(e) => {
+            set_user_pack(&[]);
+            (UserQuestions::default(), Some(format!("User questions were not loaded: {}", e)))
+        }
+    }
+}
+
+// --------------------------------------------------
+// MARK:単語リストの取り込み
+// --------------------------------------------------
+
+/// 単語リストを取り込んだ結果
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// 取り込むお題
+    pub imported: Vec<UserQuestion>,
+    /// 取り込まなかった行 (行番号, 理由)
+    pub skipped: Vec<(usize, String)>,
+    /// 長すぎて分けた行 (行番号, 分けた数)
+    pub split: Vec<(usize, usize)>,
+}
+
+/// 1行1語の単語リストをお題に変換する
+/// - 空行と '#' で始まる行は読み飛ばす（件数にも数えない）
+/// - 「漢字<TAB>かな」の行は漢字を表示用に使う
+/// - タブのない行は `japanese_same` のときだけ、かなをそのまま表示用にも使う
+/// - ローマ字辞書で入力できない文字を含む行と、既存のお題と重複する行は取り込まない
+/// - 打鍵数が上限を超える行は番号付きの部分に分ける（分けない設定なら取り込まない）
+pub fn parse_word_list(
+    text: &str,
+    japanese_same: bool,
+    roman_map: &HashMap<&'static str, Vec<&'static str>>,
+    existing: &[UserQuestion],
+    limit: LengthLimit,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    let mut seen: HashSet<(String, String)> = existing
+        .iter()
+        .map(|q| (q.japanese.clone(), q.hiragana.clone()))
+        .collect();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (japanese, hiragana) = match line.split_once('\t') {
+            Some((japanese, hiragana)) => (japanese.trim(), hiragana.trim()),
+            None if japanese_same => (line, line),
+            None => {
+                summary
+                    .skipped
+                    .push((line_no, "no japanese text (use --japanese-same for kana-only lines)".to_string()));
+                continue;
+            }
+        };
+        if let Err(reason) = check_question(japanese, hiragana, roman_map) {
+            summary.skipped.push((line_no, reason));
+            continue;
+        }
+
+        if !seen.insert((japanese.to_string(), hiragana.to_string())) {
+            summary.skipped.push((line_no, "duplicate".to_string()));
+            continue;
+        }
+
+        let question = UserQuestion {
+            japanese: japanese.to_string(),
+            hiragana: hiragana.to_string(),
+            tags: Vec::new(),
+            gloss: Gloss::new(),
+        };
+        match fit_question(question, limit, roman_map) {
+            Ok(parts) => {
+                if parts.len() > 1 {
+                    summary.split.push((line_no, parts.len()));
+                }
+                summary.imported.extend(parts);
+            }
+            Err(reason) => summary.skipped.push((line_no, reason)),
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+impl UserQuestion {
+    /// テスト用のお題（タグも訳もない）
+    pub fn sample(japanese: &str, hiragana: &str) -> Self {
+        UserQuestion { japanese: japanese.to_string(), hiragana: hiragana.to_string(), tags: Vec::new(), gloss: Gloss::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn served_questions_keep_the_text_in_file_order() {
+        let user_questions =
+            UserQuestions { questions: vec![UserQuestion::sample("鳥", "とり"), UserQuestion::sample("花", "はな")] };
+        let served = user_questions.leak_served();
+        let texts: Vec<(&str, &str)> = served.iter().map(|q| (q.japanese, q.hiragana)).collect();
+        assert_eq!(texts, [("鳥", "とり"), ("花", "はな")]);
+    }
+
+    fn gloss(entries: &[(&str, &str)]) -> Gloss {
+        entries.iter().map(|&(code, text)| (code.to_string(), text.to_string())).collect()
+    }
+
+    #[test]
+    fn glosses_need_a_language_code_and_a_single_short_line() {
+        assert_eq!(check_gloss(&gloss(&[("en", "library"), ("zh", "图书馆"), ("zh-TW", "圖書館")])), Ok(()));
+        assert_eq!(check_gloss(&Gloss::new()), Ok(()));
+
+        for code in ["EN", "e", "engl", "en-", "en-x", "en_US", ""] {
+            assert!(check_gloss(&gloss(&[(code, "library")])).is_err(), "{code:?}");
+        }
+        assert_eq!(check_gloss(&gloss(&[("en", "  ")])), Err("empty gloss for en".to_string()));
+        assert_eq!(check_gloss(&gloss(&[("en", "a\nb")])), Err("gloss for en must be a single line".to_string()));
+        // 上限ちょうどは通る
+        assert_eq!(check_gloss(&gloss(&[("ja", &"あ".repeat(MAX_GLOSS_CHARS))])), Ok(()));
+        assert!(check_gloss(&gloss(&[("ja", &"あ".repeat(MAX_GLOSS_CHARS + 1))])).is_err());
+    }
+
+    #[test]
+    fn the_display_language_wins_and_others_show_only_when_asked() {
+        let both = gloss(&[("en", "library"), ("zh", "图书馆")]);
+        assert_eq!(pick_gloss(&both, "zh", false), Some("图书馆"));
+        assert_eq!(pick_gloss(&both, "ja", false), None);
+        // ほかの言語は英語を先に選ぶ
+        assert_eq!(pick_gloss(&both, "ja", true), Some("library"));
+        assert_eq!(pick_gloss(&gloss(&[("ko", "도서관"), ("zh", "图书馆")]), "ja", true), Some("도서관"));
+        assert_eq!(pick_gloss(&Gloss::new(), "en", true), None);
+    }
+
+    #[test]
+    fn the_gloss_book_looks_up_by_japanese_and_reading() {
+        let question = |japanese: &str, hiragana: &str, entries: &[(&str, &str)]| UserQuestion {
+            gloss: gloss(entries),
+            ..UserQuestion::sample(japanese, hiragana)
+        };
+        let book = GlossBook::from_questions(&[
+            question("図書館", "としょかん", &[("en", "library")]),
+            question("花", "はな", &[]),
+        ]);
+        assert_eq!(book.get("図書館", "としょかん"), Some(&gloss(&[("en", "library")])));
+        assert_eq!(book.get("図書館", "としょしつ"), None);
+        assert_eq!(book.get("花", "はな"), None);
+        assert_eq!(GlossBook::default().get("図書館", "としょかん"), None);
+    }
+}
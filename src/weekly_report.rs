@@ -0,0 +1,349 @@
+// ============================================
+// src/weekly_report.rs
+// 前の週の成績をまとめた Markdown の週報（週の最初の起動時に書き出す）
+// ============================================
+
+use chrono::{Datelike, Local, NaiveDate, TimeDelta, TimeZone, Utc};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::achievements::day_streak;
+use crate::break_timer::{BreakEvent, average_stretch_mins};
+use crate::locale::format_date;
+use crate::questions::QuestionId;
+use crate::save_data::{TypeRecord, get_data_dir, write_atomic};
+use crate::stats::{WindowComparison, format_delta};
+
+/// グラフの棒の最大の長さ（文字数）
+const CHART_WIDTH: usize = 30;
+/// 正確率の低いお題を何問まで挙げるか
+const TOUGHEST_COUNT: usize = 3;
+/// 連続日数を数える上限（週報では1年分まで数える）
+const STREAK_CAP: u32 = 366;
+
+/// 週の名前（例: "2024-W23"。ISO 週番号）
+pub fn week_label(date: NaiveDate) -> String {
+    let week = date.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// その日を含む週の月曜日
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - TimeDelta::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// 週報を置くフォルダ
+pub fn reports_dir() -> PathBuf {
+    get_data_dir().join("reports")
+}
+
+/// 週報のパス
+pub fn report_path(label: &str) -> PathBuf {
+    reports_dir().join(format!("{}.md", label))
+}
+
+/// 一番新しい週報のパス（週の名前の順で最後のもの）
+pub fn latest_report() -> Option<PathBuf> {
+    fs::read_dir(reports_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .max()
+}
+
+/// 前の週の週報をまだ書いていなければ書き出す
+/// 書き出したら (週の名前, パス) を返す。前の週に記録がなければ何もしない
+pub fn generate_if_due(
+    history: &[TypeRecord],
+    breaks: &[BreakEvent],
+    last_generated: Option<&str>,
+    today: NaiveDate,
+) -> io::Result<Option<(String, PathBuf)>> {
+    let start = week_start(today) - TimeDelta::days(7);
+    let label = week_label(start);
+    if last_generated == Some(label.as_str()) {
+        return Ok(None);
+    }
+    let report = WeeklyReport::build(history, breaks, start);
+    if report.comparison.current.is_none() {
+        return Ok(None);
+    }
+    fs::create_dir_all(reports_dir())?;
+    let path = report_path(&label);
+    write_atomic(&path, report.to_markdown().as_bytes())?;
+    Ok(Some((label, path)))
+}
+
+/// 1週間分のまとめ
+pub struct WeeklyReport {
+    pub label: String,
+    pub start: NaiveDate,
+    /// その週と前の週の比較
+    pub comparison: WindowComparison,
+    /// 月曜日から日曜日までの日ごとの打鍵数
+    pub daily_chars: [u64; 7],
+    /// スコアが一番高かったお題 (日本語, スコア, CPS)
+    pub best: Option<(String, f64, f64)>,
+    /// 前の週より平均 CPS が一番伸びたお題 (日本語, 前の週, その週)
+    pub improvement: Option<(String, f64, f64)>,
+    /// 正確率が低かったお題 (日本語, 正確率)
+    pub toughest: Vec<(String, f64)>,
+    /// 週の最後の日の時点で練習が続いていた日数
+    pub streak: u32,
+    /// 休憩までに続けて打った時間の平均（分）と休憩の回数
+    pub average_stretch: Option<(f64, usize)>,
+}
+
+impl WeeklyReport {
+    /// `start`（月曜日）から始まる週のまとめを作る
+    pub fn build(history: &[TypeRecord], breaks: &[BreakEvent], start: NaiveDate) -> Self {
+        let end = start + TimeDelta::days(7);
+        let local_midnight = |date: NaiveDate| {
+            let naive = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+            Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map_or_else(|| Utc.from_utc_datetime(&naive), |dt| dt.with_timezone(&Utc))
+        };
+        let comparison = WindowComparison::from_history(history, local_midnight(end), 7);
+
+        let date_of = |r: &TypeRecord| r.timestamp.with_timezone(&Local).date_naive();
+        let in_week = |r: &&TypeRecord| !r.warmup && (start..end).contains(&date_of(r));
+        let in_prior = |r: &&TypeRecord| !r.warmup && (start - TimeDelta::days(7)..start).contains(&date_of(r));
+        let week: Vec<&TypeRecord> = history.iter().filter(in_week).collect();
+
+        let mut daily_chars = [0u64; 7];
+        for record in &week {
+            let day = (date_of(record) - start).num_days().clamp(0, 6) as usize;
+            daily_chars[day] += u64::from(record.total_chars);
+        }
+
+        let best = week
+            .iter()
+            .filter(|r| !r.imported)
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .map(|r| (r.question_japanese.clone(), r.score, r.cps));
+
+        // お題ごとの平均 CPS を前の週と比べる
+        let week_cps = average_cps_by_question(week.iter().copied());
+        let prior_cps = average_cps_by_question(history.iter().filter(in_prior));
+        let improvement = week_cps
+            .iter()
+            .filter_map(|(id, (name, current))| {
+                prior_cps.get(id).map(|(_, previous)| (name.clone(), *previous, *current))
+            })
+            .filter(|(_, previous, current)| current > previous)
+            .max_by(|a, b| (a.2 - a.1).total_cmp(&(b.2 - b.1)));
+
+        let mut toughest: Vec<(String, f64)> = week
+            .iter()
+            .filter(|r| r.misses > 0)
+            .map(|r| (r.question_japanese.clone(), r.accuracy()))
+            .collect();
+        toughest.sort_by(|a, b| a.1.total_cmp(&b.1));
+        // 同じお題は一番悪かった回だけ残す
+        let mut seen = HashSet::new();
+        toughest.retain(|(question, _)| seen.insert(question.clone()));
+        toughest.truncate(TOUGHEST_COUNT);
+
+        let average_stretch = average_stretch_mins(
+            breaks
+                .iter()
+                .filter(|event| (start..end).contains(&event.taken_at().with_timezone(&Local).date_naive())),
+        );
+
+        // 週の後の記録は数えない（連続日数は最後の日から遡る）
+        let last_day = end - TimeDelta::days(1);
+
+        Self {
+            label: week_label(start),
+            start,
+            comparison,
+            daily_chars,
+            best,
+            improvement,
+            toughest,
+            streak: day_streak(history, last_day, STREAK_CAP),
+            average_stretch,
+        }
+    }
+
+    /// Markdown にする
+    pub fn to_markdown(&self) -> String {
+        let end = self.start + TimeDelta::days(6);
+        let mut lines = vec![
+            format!("# Weekly report {}", self.label),
+            String::new(),
+            format!("{} – {}", format_date(self.start), format_date(end)),
+            String::new(),
+            "## Totals".to_string(),
+            String::new(),
+            "| | previous week | this week | change |".to_string(),
+            "|---|---:|---:|---|".to_string(),
+        ];
+        for (label, decimals, previous, current) in self.comparison.rows() {
+            let cell = |value: Option<f64>| value.map_or("–".to_string(), |v| format!("{:.*}", decimals, v));
+            let change = match (previous, current) {
+                (Some(previous), Some(current)) => format_delta(previous, current),
+                _ => String::new(),
+            };
+            lines.push(format!("| {} | {} | {} | {} |", label, cell(previous), cell(current), change));
+        }
+
+        lines.extend([String::new(), "## Characters per day".to_string(), String::new(), "```".to_string()]);
+        let max = self.daily_chars.iter().copied().max().unwrap_or(0).max(1);
+        for (idx, &chars) in self.daily_chars.iter().enumerate() {
+            let day = self.start + TimeDelta::days(idx as i64);
+            let bar = "#".repeat((chars as usize * CHART_WIDTH).div_ceil(max as usize));
+            lines.push(format!("{} {:<width$} {}", day.format("%a"), bar, chars, width = CHART_WIDTH));
+        }
+        lines.extend(["```".to_string(), String::new(), "## Highlights".to_string(), String::new()]);
+
+        lines.push(match &self.best {
+            Some((question, score, cps)) => format!("- Best question: {} (score {:.0}, {:.2} CPS)", question, score, cps),
+            None => "- Best question: –".to_string(),
+        });
+        lines.push(match &self.improvement {
+            Some((question, previous, current)) => format!(
+                "- Biggest improvement: {} ({:.2} → {:.2} CPS, {})",
+                question,
+                previous,
+                current,
+                format_delta(*previous, *current)
+            ),
+            None => "- Biggest improvement: – (no question was faster than the week before)".to_string(),
+        });
+        lines.push(if self.toughest.is_empty() {
+            "- Toughest questions: – (no misses)".to_string()
+        } else {
+            let list: Vec<String> = self.toughest.iter().map(|(q, acc)| format!("{} ({:.1}%)", q, acc)).collect();
+            format!("- Toughest questions: {}", list.join(", "))
+        });
+        lines.push(format!("- Streak: {} day(s) in a row at the end of the week", self.streak));
+        lines.push(match self.average_stretch {
+            Some((mins, count)) => format!("- Average typing stretch before a break: {:.0} min ({} break(s))", mins, count),
+            None => "- Average typing stretch before a break: – (no breaks recorded)".to_string(),
+        });
+
+        let mut md = lines.join("\n");
+        md.push('\n');
+        md
+    }
+}
+
+/// お題ごとの平均 CPS（文章モードの記録は除く）
+fn average_cps_by_question<'a>(
+    records: impl Iterator<Item = &'a TypeRecord>,
+) -> HashMap<QuestionId, (String, f64)> {
+    let mut sums: HashMap<QuestionId, (String, f64, u32)> = HashMap::new();
+    for record in records {
+        let Some(id) = record.question_id else {
+            continue;
+        };
+        let entry = sums.entry(id).or_insert_with(|| (record.question_japanese.clone(), 0.0, 0));
+        entry.1 += record.cps;
+        entry.2 += 1;
+    }
+    sums.into_iter()
+        .map(|(id, (name, sum, n))| (id, (name, sum / f64::from(n))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    /// `date` の昼に打った記録
+    fn record_on(date: NaiveDate, idx: usize, duration_sec: f64, misses: u32) -> TypeRecord {
+        let japanese = ["猫", "犬"][idx];
+        let mut record = TypeRecord::sample(["ねこ", "いぬ"][idx], 4, duration_sec, misses);
+        record.question_japanese = japanese.to_string();
+        record.question_id = Some(QuestionId::builtin(idx));
+        let noon = date.and_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        record.timestamp = Local.from_local_datetime(&noon).unwrap().with_timezone(&Utc);
+        record
+    }
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn weeks_start_on_monday_and_use_iso_numbers() {
+        assert_eq!(week_start(day(6, 3)), day(6, 3));
+        assert_eq!(week_start(day(6, 9)), day(6, 3));
+        assert_eq!(week_label(day(6, 3)), "2024-W23");
+        // 年をまたぐ週は ISO 週番号の年になる
+        assert_eq!(week_label(day(12, 30)), "2025-W01");
+        assert_eq!(week_label(NaiveDate::from_ymd_opt(2021, 1, 3).unwrap()), "2020-W53");
+    }
+
+    /// 2024-W23 と前の週の記録
+    fn history() -> Vec<TypeRecord> {
+        let mut warmup = record_on(day(6, 4), 1, 1.0, 0);
+        warmup.warmup = true;
+        vec![
+            record_on(day(5, 29), 0, 2.0, 0),
+            record_on(day(6, 3), 0, 1.0, 0),
+            warmup,
+            record_on(day(6, 5), 1, 2.0, 1),
+            record_on(day(6, 9), 0, 2.0, 0),
+            // 次の週の記録は数えない
+            record_on(day(6, 10), 1, 0.5, 3),
+        ]
+    }
+
+    #[test]
+    fn the_report_summarizes_the_week_against_the_one_before() {
+        let report = WeeklyReport::build(&history(), &[], day(6, 3));
+        assert_eq!(report.label, "2024-W23");
+        assert_eq!(report.daily_chars, [4, 0, 4, 0, 0, 0, 4]);
+        assert_eq!(report.comparison.current.unwrap().questions, 3);
+        assert_eq!(report.comparison.previous.unwrap().questions, 1);
+        assert_eq!(report.best.as_ref().map(|(q, _, cps)| (q.as_str(), *cps)), Some(("猫", 4.0)));
+        assert_eq!(report.improvement, Some(("猫".to_string(), 2.0, 3.0)));
+        assert_eq!(report.toughest.iter().map(|(q, _)| q.as_str()).collect::<Vec<_>>(), ["犬"]);
+        assert_eq!(report.streak, 1);
+        assert_eq!(report.average_stretch, None);
+    }
+
+    #[test]
+    fn the_markdown_has_the_table_chart_and_highlights() {
+        let report = WeeklyReport::build(&history(), &[], day(6, 3));
+        let accuracy = report.toughest[0].1;
+        let md = report.to_markdown();
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(lines[0], "# Weekly report 2024-W23");
+        assert_eq!(lines[2], "Jun 3, 2024 – Jun 9, 2024");
+        assert!(lines.contains(&"| Chars | 4 | 12 | ↑ +200.0% |"), "{md}");
+        assert!(lines.contains(&"| Sessions | 1 | 3 | ↑ +200.0% |"), "{md}");
+        let bar = "#".repeat(CHART_WIDTH);
+        assert!(lines.contains(&format!("Mon {} 4", bar).as_str()));
+        assert!(lines.contains(&format!("Tue {:<width$} 0", "", width = CHART_WIDTH).as_str()));
+        assert!(lines.contains(&"- Biggest improvement: 猫 (2.00 → 3.00 CPS, ↑ +50.0%)"));
+        assert!(lines.contains(&format!("- Toughest questions: 犬 ({:.1}%)", accuracy).as_str()));
+        assert!(lines.contains(&"- Streak: 1 day(s) in a row at the end of the week"));
+        assert!(md.ends_with("(no breaks recorded)\n"));
+    }
+
+    #[test]
+    fn a_week_without_records_has_dashes_instead_of_numbers() {
+        let md = WeeklyReport::build(&history()[..1], &[], day(6, 3)).to_markdown();
+        assert!(md.contains("| Chars | 4 | – |  |"), "{md}");
+        assert!(md.contains("- Best question: –"));
+        assert!(md.contains("- Toughest questions: – (no misses)"));
+    }
+
+    #[test]
+    fn nothing_is_written_for_an_already_generated_or_empty_week() {
+        let history = history();
+        // 2024-06-10 (月) に起動すると 2024-W23 の週報を書く
+        assert_eq!(generate_if_due(&history, &[], Some("2024-W23"), day(6, 10)).unwrap(), None);
+        // 前の週に記録がなければ書かない
+        assert_eq!(generate_if_due(&history, &[], None, day(7, 1)).unwrap(), None);
+    }
+}
@@ -0,0 +1,215 @@
+// ============================================
+// src/xp_ledger.rs
+// 経験値を入手元ごとに日別で記録する台帳
+// ============================================
+
+use bincode::{Decode, Encode};
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use crate::save_data::TypeRecord;
+
+/// 日別のまま残す日数（これより古い分は月ごとにまとめる）
+const DAILY_DAYS: i64 = 90;
+
+/// 経験値の入手元
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode)]
+#[serde(rename_all = "kebab-case")]
+pub enum XpSource {
+    /// お題を打ち終えたとき
+    QuestionCompletion,
+    /// ミッションの報酬
+    MissionReward,
+    /// ノーミスの連続ボーナス
+    StreakBonus,
+    /// デイリーボーナス
+    DailyBonus,
+    /// ミニゲーム（設定で有効にしたときだけ）
+    MiniGame,
+    /// タグの経験値の倍率で増えた分（履歴のお題の経験値には含まれている）
+    TagBoost,
+}
+
+impl XpSource {
+    pub const ALL: [XpSource; 6] = [
+        XpSource::QuestionCompletion,
+        XpSource::MissionReward,
+        XpSource::StreakBonus,
+        XpSource::DailyBonus,
+        XpSource::MiniGame,
+        XpSource::TagBoost,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            XpSource::QuestionCompletion => "questions",
+            XpSource::MissionReward => "missions",
+            XpSource::StreakBonus => "streak",
+            XpSource::DailyBonus => "daily",
+            XpSource::MiniGame => "mini-game",
+            XpSource::TagBoost => "tag boost",
+        }
+    }
+
+    /// 履歴のお題の経験値に含まれる入手元（履歴から作り直すと、お題の分にまとまる）
+    pub fn in_history(&self) -> bool {
+        matches!(self, XpSource::QuestionCompletion | XpSource::TagBoost)
+    }
+}
+
+/// ある日（月ごとにまとめたものはその月の1日）に入手元から得た経験値
+#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct XpLedgerEntry {
+    /// 西暦1年1月1日からの日数（ローカル時刻）
+    pub day: i32,
+    pub source: XpSource,
+    pub amount: u64,
+}
+
+impl XpLedgerEntry {
+    pub fn date(&self) -> NaiveDate {
+        NaiveDate::from_num_days_from_ce_opt(self.day).unwrap_or_default()
+    }
+}
+
+/// 経験値の台帳（同じ日・同じ入手元はまとめて1件にする）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct XpLedger {
+    pub entries: Vec<XpLedgerEntry>,
+}
+
+impl XpLedger {
+    /// 履歴のお題の経験値から台帳を作る（台帳のない古いセーブ用）
+    pub fn from_history(history: &[TypeRecord]) -> Self {
+        let mut ledger = Self::default();
+        for record in history {
+            let date = record.timestamp.with_timezone(&Local).date_naive();
+            ledger.add_on(date, XpSource::QuestionCompletion, u64::from(record.xp_gained));
+        }
+        ledger.rollup(Local::now().date_naive());
+        ledger
+    }
+
+    /// 今日の分として記録する
+    pub fn add(&mut self, source: XpSource, amount: u64) {
+        let today = Local::now().date_naive();
+        self.add_on(today, source, amount);
+        self.rollup(today);
+    }
+
+    fn add_on(&mut self, date: NaiveDate, source: XpSource, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        let day = date.num_days_from_ce();
+        match self.entries.iter_mut().find(|e| e.day == day && e.source == source) {
+            Some(entry) => entry.amount = entry.amount.saturating_add(amount),
+            None => self.entries.push(XpLedgerEntry { day, source, amount }),
+        }
+    }
+
+    /// 古い日別の記録を月ごとにまとめる
+    fn rollup(&mut self, today: NaiveDate) {
+        let cutoff = today.num_days_from_ce() - DAILY_DAYS as i32;
+        if !self.entries.iter().any(|e| e.day < cutoff && e.date().day() != 1) {
+            return;
+        }
+        let entries = std::mem::take(&mut self.entries);
+        for entry in entries {
+            let date = entry.date();
+            let date = if entry.day < cutoff { date.with_day(1).unwrap_or(date) } else { date };
+            self.add_on(date, entry.source, entry.amount);
+        }
+    }
+
+    /// 入手元ごとの合計
+    pub fn total(&self, source: XpSource) -> u64 {
+        self.entries
+            .iter()
+            .filter(|e| e.source == source)
+            .fold(0u64, |acc, e| acc.saturating_add(e.amount))
+    }
+
+    /// その日に得た経験値の合計（日別で残っている日だけ正しい）
+    pub fn total_on(&self, date: NaiveDate) -> u64 {
+        let day = date.num_days_from_ce();
+        self.entries
+            .iter()
+            .filter(|e| e.day == day)
+            .fold(0u64, |acc, e| acc.saturating_add(e.amount))
+    }
+
+    /// お題以外から得た経験値の合計（履歴から計算し直せない分）
+    pub fn bonus_total(&self) -> u64 {
+        XpSource::ALL
+            .iter()
+            .filter(|source| !source.in_history())
+            .fold(0u64, |acc, &source| acc.saturating_add(self.total(source)))
+    }
+
+    /// 月ごとの入手元別の合計（古い順。並びは `XpSource::ALL` と同じ）
+    pub fn monthly_totals(&self) -> Vec<(NaiveDate, [u64; XpSource::ALL.len()])> {
+        let mut months: BTreeMap<NaiveDate, [u64; XpSource::ALL.len()]> = BTreeMap::new();
+        for entry in &self.entries {
+            let date = entry.date();
+            let month = date.with_day(1).unwrap_or(date);
+            let idx = XpSource::ALL.iter().position(|&s| s == entry.source).unwrap_or(0);
+            let totals = months.entry(month).or_default();
+            totals[idx] = totals[idx].saturating_add(entry.amount);
+        }
+        months.into_iter().collect()
+    }
+
+    /// お題の分だけ履歴から作り直し、それ以外の入手元はそのまま残す
+    pub fn rebuilt_from_history(&self, history: &[TypeRecord]) -> Self {
+        let mut ledger = Self::from_history(history);
+        for entry in self.entries.iter().filter(|e| !e.source.in_history()) {
+            ledger.add_on(entry.date(), entry.source, entry.amount);
+        }
+        ledger
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn total_on_sums_every_source_of_that_day() {
+        let today = Local::now().date_naive();
+        let mut ledger = XpLedger::default();
+        ledger.add(XpSource::QuestionCompletion, 30);
+        ledger.add(XpSource::DailyBonus, 10);
+        ledger.add_on(today - chrono::TimeDelta::days(1), XpSource::QuestionCompletion, 99);
+        assert_eq!(ledger.total_on(today), 40);
+        assert_eq!(ledger.total_on(today - chrono::TimeDelta::days(2)), 0);
+    }
+
+    #[test]
+    fn tag_boosts_are_folded_into_questions_when_rebuilt_from_history() {
+        // 倍率で 30 → 45 になったお題（履歴には倍率を掛けた経験値を残す）
+        let record = TypeRecord { xp_gained: 45, ..TypeRecord::sample("ねこ", 30, 10.0, 0) };
+        let mut ledger = XpLedger::default();
+        ledger.add(XpSource::QuestionCompletion, 30);
+        ledger.add(XpSource::TagBoost, 15);
+        ledger.add(XpSource::DailyBonus, 10);
+        assert_eq!(ledger.bonus_total(), 10);
+
+        let rebuilt = ledger.rebuilt_from_history(&[record]);
+        assert_eq!(rebuilt.total(XpSource::QuestionCompletion), 45);
+        assert_eq!(rebuilt.total(XpSource::TagBoost), 0);
+        assert_eq!(rebuilt.total(XpSource::DailyBonus), 10);
+    }
+
+    #[test]
+    fn monthly_totals_have_a_column_per_source() {
+        let mut ledger = XpLedger::default();
+        ledger.add(XpSource::TagBoost, 15);
+        let months = ledger.monthly_totals();
+        assert_eq!(months.len(), 1);
+        let idx = XpSource::ALL.iter().position(|&source| source == XpSource::TagBoost).unwrap();
+        assert_eq!(months[0].1[idx], 15);
+    }
+}